@@ -0,0 +1,259 @@
+// Copyright 2021 Databricks, Inc.
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+
+// http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A cache of list results, so running list commands back to back doesn't need to hit the api
+//! server every time. Entries expire after the `cache_ttl_secs` set in the click config.
+
+use serde_json::Value;
+
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// What a cached list is stored under. namespace is None for lists across all namespaces, and for
+/// lists of cluster scoped kinds
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct CacheKey {
+    pub context: String,
+    pub namespace: Option<String>,
+    pub kind: String,
+}
+
+struct CacheEntry {
+    // the request uri, since things like selectors also change what's in the list
+    uri: String,
+    fetched: Instant,
+    list: Value,
+}
+
+#[derive(Default)]
+pub struct ListCache {
+    entries: HashMap<CacheKey, CacheEntry>,
+}
+
+impl ListCache {
+    pub fn new() -> ListCache {
+        ListCache::default()
+    }
+
+    /// Get the list cached for key and uri, if it was fetched less than ttl ago. Returns the list
+    /// and how long ago it was fetched
+    pub fn get(&self, key: &CacheKey, uri: &str, ttl: Duration) -> Option<(&Value, Duration)> {
+        self.entries.get(key).and_then(|entry| {
+            let age = entry.fetched.elapsed();
+            if entry.uri == uri && age < ttl {
+                Some((&entry.list, age))
+            } else {
+                None
+            }
+        })
+    }
+
+    pub fn insert(&mut self, key: CacheKey, uri: String, list: Value) {
+        self.entries.insert(
+            key,
+            CacheEntry {
+                uri,
+                fetched: Instant::now(),
+                list,
+            },
+        );
+    }
+
+    /// All the lists of kind cached in context that were fetched less than ttl ago, whatever
+    /// namespace or query they were fetched with
+    pub fn lists_of<'a>(
+        &'a self,
+        context: &'a str,
+        kind: &'a str,
+        ttl: Duration,
+    ) -> impl Iterator<Item = &'a Value> + 'a {
+        self.entries
+            .iter()
+            .filter(move |(key, entry)| {
+                key.context == context && key.kind == kind && entry.fetched.elapsed() < ttl
+            })
+            .map(|(_, entry)| &entry.list)
+    }
+
+    /// Drop any cached lists of kind in context that could include objects from namespace. This
+    /// means the list for namespace itself, and the list across all namespaces.
+    pub fn invalidate(&mut self, context: &str, namespace: Option<&str>, kind: &str) {
+        self.entries.retain(|key, _| {
+            !(key.context == context
+                && key.kind == kind
+                && (key.namespace.is_none() || key.namespace.as_deref() == namespace))
+        });
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
+
+/// What a cached describe is stored under: the object, and the options that change the output
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct DescribeKey {
+    pub context: String,
+    pub namespace: Option<String>,
+    pub kind: String,
+    pub name: String,
+    pub options: String,
+}
+
+/// The output of describe for recently described objects, so describing them again doesn't have
+/// to fetch the object, its events and anything else its description includes
+#[derive(Default)]
+pub struct DescribeCache {
+    entries: HashMap<DescribeKey, (Instant, Vec<u8>)>,
+}
+
+impl DescribeCache {
+    pub fn new() -> DescribeCache {
+        DescribeCache::default()
+    }
+
+    /// Get the output cached for key, if it was cached less than ttl ago. Returns the output and
+    /// how long ago it was cached
+    pub fn get(&self, key: &DescribeKey, ttl: Duration) -> Option<(&[u8], Duration)> {
+        self.entries.get(key).and_then(|(cached, output)| {
+            let age = cached.elapsed();
+            if age < ttl {
+                Some((output.as_slice(), age))
+            } else {
+                None
+            }
+        })
+    }
+
+    pub fn insert(&mut self, key: DescribeKey, output: Vec<u8>) {
+        self.entries.insert(key, (Instant::now(), output));
+    }
+
+    /// Drop the output cached for an object, however it was described
+    pub fn invalidate(&mut self, context: &str, namespace: Option<&str>, kind: &str, name: &str) {
+        self.entries.retain(|key, _| {
+            !(key.context == context
+                && key.namespace.as_deref() == namespace
+                && key.kind == kind
+                && key.name == name)
+        });
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(namespace: Option<&str>, kind: &str) -> CacheKey {
+        CacheKey {
+            context: "ctx".to_string(),
+            namespace: namespace.map(|ns| ns.to_string()),
+            kind: kind.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_get_within_ttl() {
+        let mut cache = ListCache::new();
+        let ttl = Duration::from_secs(60);
+        cache.insert(
+            key(Some("ns"), "Pod"),
+            "/pods".to_string(),
+            Value::Bool(true),
+        );
+        assert!(cache.get(&key(Some("ns"), "Pod"), "/pods", ttl).is_some());
+        // different uri, kind, or namespace all miss
+        assert!(cache.get(&key(Some("ns"), "Pod"), "/pods?x", ttl).is_none());
+        assert!(cache.get(&key(Some("ns"), "Node"), "/pods", ttl).is_none());
+        assert!(cache.get(&key(None, "Pod"), "/pods", ttl).is_none());
+        // expired
+        assert!(cache
+            .get(&key(Some("ns"), "Pod"), "/pods", Duration::from_secs(0))
+            .is_none());
+    }
+
+    #[test]
+    fn test_invalidate() {
+        let mut cache = ListCache::new();
+        let ttl = Duration::from_secs(60);
+        cache.insert(key(Some("ns"), "Pod"), "/a".to_string(), Value::Null);
+        cache.insert(key(Some("other"), "Pod"), "/b".to_string(), Value::Null);
+        cache.insert(key(None, "Pod"), "/c".to_string(), Value::Null);
+        cache.insert(key(Some("ns"), "Service"), "/d".to_string(), Value::Null);
+        cache.invalidate("ctx", Some("ns"), "Pod");
+        assert!(cache.get(&key(Some("ns"), "Pod"), "/a", ttl).is_none());
+        assert!(cache.get(&key(Some("other"), "Pod"), "/b", ttl).is_some());
+        assert!(cache.get(&key(None, "Pod"), "/c", ttl).is_none());
+        assert!(cache.get(&key(Some("ns"), "Service"), "/d", ttl).is_some());
+    }
+
+    #[test]
+    fn test_lists_of() {
+        let mut cache = ListCache::new();
+        let ttl = Duration::from_secs(60);
+        cache.insert(
+            key(Some("a"), "Namespace"),
+            "/ns".to_string(),
+            Value::Bool(true),
+        );
+        cache.insert(
+            key(None, "Namespace"),
+            "/ns?x".to_string(),
+            Value::Bool(false),
+        );
+        cache.insert(key(Some("a"), "Pod"), "/pods".to_string(), Value::Null);
+        assert_eq!(cache.lists_of("ctx", "Namespace", ttl).count(), 2);
+        assert_eq!(cache.lists_of("other", "Namespace", ttl).count(), 0);
+        assert_eq!(
+            cache
+                .lists_of("ctx", "Namespace", Duration::from_secs(0))
+                .count(),
+            0
+        );
+    }
+
+    fn describe_key(name: &str, options: &str) -> DescribeKey {
+        DescribeKey {
+            context: "ctx".to_string(),
+            namespace: Some("ns".to_string()),
+            kind: "Pod".to_string(),
+            name: name.to_string(),
+            options: options.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_describe_cache() {
+        let mut cache = DescribeCache::new();
+        let ttl = Duration::from_secs(60);
+        cache.insert(describe_key("a", ""), b"a".to_vec());
+        cache.insert(describe_key("a", "-j"), b"{}".to_vec());
+        cache.insert(describe_key("b", ""), b"b".to_vec());
+        assert_eq!(
+            cache.get(&describe_key("a", "-j"), ttl).map(|(out, _)| out),
+            Some(&b"{}"[..])
+        );
+        assert!(cache
+            .get(&describe_key("a", ""), Duration::from_secs(0))
+            .is_none());
+        cache.invalidate("ctx", Some("ns"), "Pod", "a");
+        assert!(cache.get(&describe_key("a", ""), ttl).is_none());
+        assert!(cache.get(&describe_key("a", "-j"), ttl).is_none());
+        assert!(cache.get(&describe_key("b", ""), ttl).is_some());
+    }
+}
@@ -61,6 +61,14 @@ As with Bash: The first word of the expansion is tested for aliases, but a word
 an alias being expanded is not expanded a second time.  So one can alias logs to \"logs -e\", for
 instance, without causing infinite expansion.
 
+The expansion can refer to the words typed after the alias with $1 through $9, and to all of them
+with $*. Positions with no matching word expand to nothing. Words after the last one referenced are
+added to the end of the expansion, unless $* is used. If the expansion refers to no words,
+everything after the alias is added to the end as is.
+
+Environment variables can be used in the expansion as $VAR or ${VAR}. They're expanded each time
+the alias is used, and ones that aren't set are left as they are. Use $$ for a literal $.
+
 Examples:
   # Display current aliases
   alias
@@ -72,7 +80,10 @@ Examples:
   alias pn \"pods -r nginx\"
 
   # alias el to run logs and grep for ERROR
-  alias el \"logs | grep ERROR\""
+  alias el \"logs | grep ERROR\"
+
+  # alias pg so 'pg nginx' lists pods and greps for nginx
+  alias pg \"pods | grep $1\""
         ),
     vec!["alias", "aliases"],
     noop_complete!(),
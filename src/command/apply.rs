@@ -0,0 +1,271 @@
+// Copyright 2021 Databricks, Inc.
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+
+// http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use ansi_term::Colour::Yellow;
+use clap::{App, Arg};
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::APIResource;
+use k8s_openapi::http::{self, Request, StatusCode};
+use rustyline::completion::Pair as RustlinePair;
+use serde_json::Value;
+
+use crate::{
+    command::command_def::{exec_match, start_clap, Cmd},
+    command::{change_query_pairs, dry_run_arg, field_manager_arg, path_with_query, DryRun},
+    completer,
+    crd::{get_api_group_resources, GetAPIGroupResourcesResponse},
+    env::Env,
+    error::ClickError,
+    output::{stdin_is_tty, ClickWriter},
+    values::{val_str, val_str_opt, yaml_objects},
+};
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::io::{Read, Write};
+
+/// The field manager our server-side applies are recorded under
+const FIELD_MANAGER: &str = "click";
+
+fn response_message(body: &[u8]) -> String {
+    serde_json::from_slice::<Value>(body)
+        .map(|val| val_str("/message", &val, "<No message>").into_owned())
+        .unwrap_or_else(|_| "<No message>".to_string())
+}
+
+pub fn read_input(path: &str) -> Result<String, ClickError> {
+    if path == "-" {
+        if stdin_is_tty() {
+            return Err(ClickError::CommandError(
+                "Can only read from stdin when it's piped in (e.g. click --exec 'apply -f -' < \
+                 objs.yaml)"
+                    .to_string(),
+            ));
+        }
+        let mut content = String::new();
+        std::io::stdin().read_to_string(&mut content)?;
+        Ok(content)
+    } else {
+        Ok(std::fs::read_to_string(path)
+            .map_err(|e| ClickError::CommandError(format!("Could not read {}: {}", path, e)))?)
+    }
+}
+
+/// Finds which resource serves a kind, asking the server about each group version once
+struct Discovery {
+    resources: HashMap<String, Vec<APIResource>>,
+}
+
+impl Discovery {
+    fn new() -> Discovery {
+        Discovery {
+            resources: HashMap::new(),
+        }
+    }
+
+    fn find(
+        &mut self,
+        env: &Env,
+        api_version: &str,
+        kind: &str,
+    ) -> Result<&APIResource, ClickError> {
+        if !self.resources.contains_key(api_version) {
+            let (request, _) = get_api_group_resources(api_version)?;
+            let resources =
+                match env.run_on_context::<_, GetAPIGroupResourcesResponse>(|c| c.read(request))? {
+                    GetAPIGroupResourcesResponse::Ok(list) => list.resources,
+                    GetAPIGroupResourcesResponse::Other(_) => vec![],
+                };
+            self.resources.insert(api_version.to_string(), resources);
+        }
+        self.resources[api_version]
+            .iter()
+            // subresources (like deployments/scale) have the kind of the parent's subresource
+            .find(|r| r.kind == kind && !r.name.contains('/'))
+            .ok_or_else(|| {
+                ClickError::CommandError(format!(
+                    "The server doesn't serve {} from {}",
+                    kind, api_version
+                ))
+            })
+    }
+}
+
+/// Remove what changes on every write (or dry run) from an object returned by the server, so two
+/// versions can be compared to see if an apply changed anything
+fn without_write_metadata(mut obj: Value) -> Value {
+    if let Some(meta) = obj.get_mut("metadata").and_then(|m| m.as_object_mut()) {
+        meta.remove("managedFields");
+        meta.remove("resourceVersion");
+    }
+    obj
+}
+
+/// Server-side apply obj, returning what happened to it (created, configured or unchanged). A
+/// client dry run only checks if obj exists, so it can't tell if it would be unchanged
+fn apply_obj(
+    env: &Env,
+    discovery: &mut Discovery,
+    obj: &Value,
+    dry_run: DryRun,
+    field_manager: &str,
+) -> Result<String, ClickError> {
+    let api_version = val_str_opt("/apiVersion", obj)
+        .ok_or_else(|| ClickError::CommandError("Object has no apiVersion".to_string()))?;
+    let kind = val_str_opt("/kind", obj)
+        .ok_or_else(|| ClickError::CommandError("Object has no kind".to_string()))?;
+    let name = val_str_opt("/metadata/name", obj).ok_or_else(|| {
+        ClickError::CommandError(format!("{} has no metadata.name", kind.to_lowercase()))
+    })?;
+    let desc = format!("{}/{}", kind.to_lowercase(), name);
+
+    let resource = discovery.find(env, &api_version, &kind)?;
+    let prefix = if api_version == "v1" {
+        "/api/v1".to_string()
+    } else {
+        format!("/apis/{}", api_version)
+    };
+    let path = if resource.namespaced {
+        let namespace = val_str_opt("/metadata/namespace", obj)
+            .or_else(|| env.namespace.clone())
+            .ok_or_else(|| {
+                ClickError::CommandError(format!(
+                    "{} has no namespace, and no namespace is set",
+                    desc
+                ))
+            })?;
+        format!(
+            "{}/namespaces/{}/{}/{}",
+            prefix, namespace, resource.name, name
+        )
+    } else {
+        format!("{}/{}/{}", prefix, resource.name, name)
+    };
+
+    // see what's there now, so we can tell if the apply changed anything
+    let request = Request::get(&path).body(vec![]).unwrap(); // safe, valid path
+    let response = env.run_on_context(|c| c.execute(request))?;
+    let existing: Option<Value> = match response.status() {
+        StatusCode::NOT_FOUND => None,
+        status if status.is_success() => Some(serde_json::from_slice(response.body())?),
+        status => {
+            return Err(ClickError::CommandError(format!(
+                "Could not get {} ({}): {}",
+                desc,
+                status,
+                response_message(response.body())
+            )))
+        }
+    };
+
+    if dry_run == DryRun::Client {
+        let result = if existing.is_some() {
+            "configured"
+        } else {
+            "created"
+        };
+        return Ok(format!("{} {}{}", desc, result, dry_run.suffix()));
+    }
+
+    let mut query = change_query_pairs(dry_run, Some(field_manager));
+    query.push(("force", "true"));
+    let request = Request::patch(path_with_query(&path, &query))
+        .header(http::header::CONTENT_TYPE, "application/apply-patch+yaml")
+        // json is valid yaml
+        .body(serde_json::to_vec(obj)?)
+        .unwrap(); // safe, path built from valid names
+    let response = env.run_on_context(|c| c.execute(request))?;
+    if !response.status().is_success() {
+        return Err(ClickError::CommandError(format!(
+            "Could not apply {} ({}): {}",
+            desc,
+            response.status(),
+            response_message(response.body())
+        )));
+    }
+    let applied: Value = serde_json::from_slice(response.body())?;
+    let result = match existing {
+        None => "created",
+        // a dry run doesn't bump the resourceVersion, so compare the objects themselves
+        Some(existing) => {
+            if without_write_metadata(existing) == without_write_metadata(applied) {
+                "unchanged"
+            } else {
+                "configured"
+            }
+        }
+    };
+    Ok(format!("{} {}{}", desc, result, dry_run.suffix()))
+}
+
+command!(
+    Apply,
+    "apply",
+    "Create or update the objects in a yaml file with a server-side apply. The file can have \
+     many objects, separated by ---",
+    |clap: App<'static, 'static>| clap
+        .arg(
+            Arg::with_name("filename")
+                .short("f")
+                .long("filename")
+                .help(
+                    "The yaml file with the objects to apply, or - to read them from stdin \
+                     (when running with --exec and input piped in)"
+                )
+                .takes_value(true)
+                .required(true)
+        )
+        .arg(dry_run_arg())
+        .arg(field_manager_arg().default_value(FIELD_MANAGER))
+        .after_help(
+            "Objects without a namespace are applied in the current namespace. Fields are \
+             owned by the field manager ('click' unless --field-manager is given), and \
+             conflicts with other managers are overridden (like kubectl apply --server-side \
+             --force-conflicts)"
+        ),
+    vec!["apply"],
+    noop_complete!(),
+    no_named_complete!(),
+    |matches, env, writer| {
+        let content = read_input(matches.value_of("filename").unwrap())?; // safe, required
+        let objects = yaml_objects(&content)?;
+        if objects.is_empty() {
+            return Err(ClickError::CommandError(
+                "No objects found to apply".to_string(),
+            ));
+        }
+        let dry_run = DryRun::from_matches(&matches);
+        let field_manager = matches.value_of("field_manager").unwrap(); // safe, has default
+        let mut discovery = Discovery::new();
+        let mut failed = 0;
+        for obj in objects.iter() {
+            match apply_obj(env, &mut discovery, obj, dry_run, field_manager) {
+                Ok(result) => clickwriteln!(writer, "{}", result),
+                Err(e) => {
+                    failed += 1;
+                    clickwriteln!(writer, "{}", e);
+                }
+            }
+        }
+        env.clear_list_cache();
+        if failed > 0 {
+            Err(ClickError::CommandError(format!(
+                "{} of {} objects could not be applied",
+                failed,
+                objects.len()
+            )))
+        } else {
+            Ok(())
+        }
+    }
+);
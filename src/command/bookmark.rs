@@ -0,0 +1,147 @@
+// Copyright 2021 Databricks, Inc.
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+
+// http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use ansi_term::Colour::Yellow;
+use clap::{App, Arg};
+use rustyline::completion::Pair as RustlinePair;
+
+use crate::{
+    command::command_def::{exec_match, start_clap, Cmd},
+    completer,
+    env::Env,
+    error::ClickError,
+    output::ClickWriter,
+};
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::io::Write;
+
+pub const BOOKMARK_ACTIONS: &[&str] = &["add", "go", "list", "remove"];
+
+fn go_to_bookmark(name: &str, env: &mut Env, writer: &mut ClickWriter) -> Result<(), ClickError> {
+    let bookmark = env
+        .click_config
+        .bookmarks
+        .get(name)
+        .cloned()
+        .ok_or_else(|| ClickError::CommandError(format!("No bookmark called {}", name)))?;
+    if !env.config.contexts.contains_key(&bookmark.context) {
+        return Err(ClickError::CommandError(format!(
+            "Bookmark {} is for context {}, which isn't in your kubeconfig anymore",
+            name, bookmark.context
+        )));
+    }
+    env.set_context(Some(&bookmark.context));
+    if env.context.is_none() {
+        // set_context has said why
+        return Ok(());
+    }
+    if bookmark.namespace.is_some() {
+        env.set_namespace(bookmark.namespace.as_deref());
+    }
+    clickwriteln!(
+        writer,
+        "Switched to context {}{}",
+        bookmark.context,
+        bookmark
+            .namespace
+            .as_ref()
+            .map(|ns| format!(", namespace {}", ns))
+            .unwrap_or_default()
+    );
+    Ok(())
+}
+
+command!(
+    Bookmark,
+    "bookmark",
+    "Save the current context and namespace under a name, and switch back to them later. Default \
+     is to list bookmarks.",
+    |clap: App<'static, 'static>| clap
+        .arg(
+            Arg::with_name("action")
+                .help("Action to take")
+                .required(false)
+                .possible_values(BOOKMARK_ACTIONS)
+                .index(1)
+        )
+        .arg(
+            Arg::with_name("name")
+                .help("The bookmark to add, go to, or remove")
+                .required_ifs(&[("action", "add"), ("action", "go"), ("action", "remove")])
+                .index(2)
+        )
+        .after_help(
+            "Examples:
+  # bookmark the current context and namespace as prod-web
+  bookmark add prod-web
+
+  # switch to the context and namespace bookmarked as prod-web
+  bm go prod-web
+
+  # list bookmarks
+  bm
+
+  # remove the prod-web bookmark
+  bm remove prod-web"
+        ),
+    vec!["bookmark", "bookmarks", "bm"],
+    vec![
+        &completer::bookmarkaction_values_completer,
+        &completer::bookmark_completer
+    ],
+    no_named_complete!(),
+    |matches, env, writer| {
+        let name = matches.value_of("name");
+        match (matches.value_of("action").unwrap_or("list"), name) {
+            ("add", Some(name)) => {
+                if env.add_bookmark(name) {
+                    clickwriteln!(
+                        writer,
+                        "Bookmarked current context and namespace as {}",
+                        name
+                    );
+                } else {
+                    return Err(ClickError::CommandError(
+                        "No active context, use 'context' to set one".to_string(),
+                    ));
+                }
+            }
+            ("go", Some(name)) => go_to_bookmark(name, env, writer)?,
+            ("remove", Some(name)) => {
+                if env.remove_bookmark(name) {
+                    clickwriteln!(writer, "Removed bookmark {}", name);
+                } else {
+                    clickwriteln!(writer, "No bookmark called {}", name);
+                }
+            }
+            _ => {
+                if env.click_config.bookmarks.is_empty() {
+                    clickwriteln!(writer, "No bookmarks, add one with 'bookmark add <name>'");
+                }
+                for (name, bookmark) in env.click_config.bookmarks.iter() {
+                    clickwriteln!(
+                        writer,
+                        "{}: context {}, namespace {}",
+                        name,
+                        bookmark.context,
+                        bookmark.namespace.as_deref().unwrap_or("<none>")
+                    );
+                }
+            }
+        }
+        Ok(())
+    }
+);
@@ -14,7 +14,8 @@
 
 use ansi_term::Colour::Yellow;
 use chrono::offset::Utc;
-use clap::{App, Arg};
+use clap::{App, Arg, SubCommand};
+use k8s_openapi::{apimachinery::pkg::version::Info, GetCodeVersionResponse};
 use prettytable::Table;
 use rustyline::completion::Pair as RustlinePair;
 
@@ -22,7 +23,9 @@ use crate::{
     command::command_def::{exec_match, identity, start_clap, Cmd},
     completer, config,
     env::Env,
-    output::ClickWriter,
+    error::ClickError,
+    k8s,
+    output::{clear_screen, ClickWriter},
     table::CellSpec,
 };
 
@@ -44,6 +47,20 @@ command!(
     }
 );
 
+command!(
+    ClearScreen,
+    "cls",
+    "Clear the terminal screen (clear clears the selected object)",
+    identity,
+    vec!["cls", "clear-screen"],
+    noop_complete!(),
+    no_named_complete!(),
+    |_, _, _| {
+        clear_screen();
+        Ok(())
+    }
+);
+
 fn print_contexts(env: &Env, writer: &mut ClickWriter) {
     let mut contexts: Vec<&String> = env.config.contexts.keys().collect();
     contexts.sort();
@@ -63,33 +80,134 @@ fn print_contexts(env: &Env, writer: &mut ClickWriter) {
     crate::table::print_table(row!["Context", "Api Server Address"], ctxs, writer);
 }
 
+fn warn_if_not_in_kubeconfig(env: &Env, context: &str, writer: &mut ClickWriter) {
+    if !env.config.contexts.contains_key(context) {
+        clickwriteln!(
+            writer,
+            "{}",
+            Yellow.paint(format!(
+                "Warning: {} is not a context in your kubernetes config",
+                context
+            ))
+        );
+    }
+}
+
 command!(
     Context,
     "context",
     "Set the current context (will clear any selected pod). \
      With no argument, lists available contexts.",
-    |clap: App<'static, 'static>| clap.arg(
-        Arg::with_name("context")
-            .help("The name of the context")
-            .required(false)
-            .index(1)
-    ),
+    |clap: App<'static, 'static>| clap
+        .arg(
+            Arg::with_name("context")
+                .help(
+                    "The name of the context. This can be any part of the name, or its \
+                     characters in order, as long as only one context matches"
+                )
+                .required(false)
+                .index(1)
+        )
+        .subcommand(
+            SubCommand::with_name("rename")
+                .about(
+                    "Move the state click keeps for a context (like the last namespace used) \
+                     to a new name. This does not change your kubernetes config"
+                )
+                .arg(
+                    Arg::with_name("old")
+                        .help("The context to move state from")
+                        .required(true)
+                        .index(1)
+                )
+                .arg(
+                    Arg::with_name("new")
+                        .help("The context to move state to")
+                        .required(true)
+                        .index(2)
+                )
+                .arg(
+                    Arg::with_name("force")
+                        .short("f")
+                        .long("force")
+                        .help("Replace the state saved for the new context, if there is any")
+                        .takes_value(false)
+                )
+        )
+        .subcommand(
+            SubCommand::with_name("forget")
+                .about(
+                    "Remove the state click keeps for a context. This does not change your \
+                     kubernetes config"
+                )
+                .arg(
+                    Arg::with_name("context")
+                        .help("The context to forget")
+                        .required(true)
+                        .index(1)
+                )
+        ),
     vec!["ctx", "context"],
     vec![&completer::context_complete],
     no_named_complete!(),
     |matches, env, writer| {
-        if matches.is_present("context") {
-            let context = matches.value_of("context");
-            if let (&Some(ref cur), Some(c)) = (&env.context, context) {
-                if cur.name == c {
-                    // no-op if we're already in the specified context1
-                    return Ok(());
+        match matches.subcommand() {
+            ("rename", Some(sub)) => {
+                let old = sub.value_of("old").unwrap(); // safe, required
+                let new = sub.value_of("new").unwrap(); // safe, required
+                warn_if_not_in_kubeconfig(env, new, writer);
+                if env.rename_context_state(old, new, sub.is_present("force"))? {
+                    env.save_click_config();
+                    clickwriteln!(writer, "Moved saved state for {} to {}", old, new);
+                } else {
+                    clickwriteln!(writer, "No saved state for {}", old);
+                }
+            }
+            ("forget", Some(sub)) => {
+                let context = sub.value_of("context").unwrap(); // safe, required
+                if env.forget_context_state(context) {
+                    env.save_click_config();
+                    clickwriteln!(writer, "Forgot saved state for {}", context);
+                } else {
+                    warn_if_not_in_kubeconfig(env, context, writer);
+                    clickwriteln!(writer, "No saved state for {}", context);
+                }
+            }
+            _ => {
+                if let Some(query) = matches.value_of("context") {
+                    let context = match env.find_context(query).as_slice() {
+                        [] => {
+                            clickwriteln!(writer, "No context matches {}", query);
+                            return Ok(());
+                        }
+                        [context] => context.to_string(),
+                        candidates => {
+                            clickwriteln!(
+                                writer,
+                                "{} matches multiple contexts, not switching:",
+                                query
+                            );
+                            for candidate in candidates.iter() {
+                                clickwriteln!(writer, "  {}", candidate);
+                            }
+                            return Ok(());
+                        }
+                    };
+                    if let Some(ref cur) = env.context {
+                        if cur.name == context {
+                            // no-op if we're already in the specified context
+                            return Ok(());
+                        }
+                    }
+                    if context != query {
+                        clickwriteln!(writer, "Switching to context {}", context);
+                    }
+                    env.set_context(Some(&context));
+                    env.clear_current();
+                } else {
+                    print_contexts(env, writer);
                 }
             }
-            env.set_context(context);
-            env.clear_current();
-        } else {
-            print_contexts(env, writer);
         }
         Ok(())
     }
@@ -109,6 +227,156 @@ command!(
     }
 );
 
+/// Print the merged kubernetes config, and which file each part of it came from
+fn print_config_view(env: &Env, writer: &mut ClickWriter) {
+    let config = &env.config;
+    let origins = &config.origins;
+    let from = |origin: Option<&String>| origin.cloned().unwrap_or_default();
+    clickwriteln!(
+        writer,
+        "Config files: {} (from {})",
+        config.source_file,
+        config.source
+    );
+    match config.current_context.as_ref() {
+        Some(ctx) => clickwriteln!(
+            writer,
+            "Current context: {} (from {})",
+            ctx,
+            from(origins.current_context.as_ref())
+        ),
+        None => clickwriteln!(writer, "Current context: <not set>"),
+    }
+
+    clickwriteln!(writer, "\nClusters:");
+    let mut clusters: Vec<(&String, &config::ClusterConf)> = config.clusters.iter().collect();
+    clusters.sort_by_key(|(name, _)| *name);
+    let rows = clusters
+        .into_iter()
+        .map(|(name, cluster)| {
+            vec![
+                CellSpec::with_style(name.as_str().into(), "FR"),
+                cluster.server.as_str().into(),
+                from(origins.clusters.get(name)).into(),
+            ]
+        })
+        .collect();
+    crate::table::print_table(row!["Name", "Server", "From"], rows, writer);
+
+    clickwriteln!(writer, "\nContexts:");
+    let rows = config
+        .contexts
+        .iter()
+        .map(|(name, context)| {
+            vec![
+                CellSpec::with_style(name.as_str().into(), "FR"),
+                context.cluster.as_str().into(),
+                context.user.as_str().into(),
+                context.namespace.as_deref().unwrap_or("").into(),
+                from(origins.contexts.get(name)).into(),
+            ]
+        })
+        .collect();
+    crate::table::print_table(
+        row!["Name", "Cluster", "User", "Namespace", "From"],
+        rows,
+        writer,
+    );
+
+    clickwriteln!(writer, "\nUsers:");
+    let mut users: Vec<(&String, &config::UserConf)> = config.users.iter().collect();
+    users.sort_by_key(|(name, _)| *name);
+    let rows = users
+        .into_iter()
+        .map(|(name, user)| {
+            vec![
+                CellSpec::with_style(name.as_str().into(), "FR"),
+                user.auth_kinds().join(", ").into(),
+                from(origins.users.get(name)).into(),
+            ]
+        })
+        .collect();
+    crate::table::print_table(row!["Name", "Auth", "From"], rows, writer);
+}
+
+/// Set or remove the default namespace of the current context in the kubeconfig
+fn set_context_namespace(
+    env: &mut Env,
+    namespace: Option<&str>,
+    writer: &mut ClickWriter,
+) -> Result<(), ClickError> {
+    let context = match env.context.as_ref() {
+        Some(context) => context.name.clone(),
+        None => {
+            return Err(ClickError::CommandError(
+                "No active context, use 'context' to set one".to_string(),
+            ))
+        }
+    };
+    env.config.set_context_namespace(&context, namespace)?;
+    match namespace {
+        Some(ns) => {
+            clickwriteln!(writer, "Set default namespace of {} to {}", context, ns);
+            env.set_namespace(Some(ns));
+        }
+        None => {
+            // forget the namespace click remembers too, so switching back to the context doesn't
+            // pick it up
+            env.click_config.context_namespaces.remove(&context);
+            clickwriteln!(writer, "Removed default namespace of {}", context);
+        }
+    }
+    Ok(())
+}
+
+command!(
+    KubeConfig,
+    "config",
+    "Inspect your kubernetes config, or set the default namespace of the current context",
+    |clap: App<'static, 'static>| {
+        clap
+        .subcommand(SubCommand::with_name("view").about(
+            "Show the merged kubernetes config, and which file each cluster, context and user \
+             came from. When several files (from --kubeconfig or $KUBECONFIG) define the same \
+             name, the first one wins, and the current context comes from the first file that sets one"
+        ))
+        .subcommand(
+            SubCommand::with_name("set-namespace")
+                .about(
+                    "Set the namespace of the current context in your kubeconfig (like 'kubectl \
+                     config set-context --current --namespace'), and switch to it. It's used \
+                     when switching to the context if click hasn't remembered another namespace \
+                     for it"
+                )
+                .arg(
+                    Arg::with_name("namespace")
+                        .help("The namespace to set")
+                        .required(true)
+                        .index(1)
+                )
+        )
+        .subcommand(
+            SubCommand::with_name("unset-namespace")
+                .about("Remove the namespace of the current context from your kubeconfig")
+        )
+    },
+    vec!["config"],
+    noop_complete!(),
+    no_named_complete!(),
+    |matches, env, writer| {
+        match matches.subcommand() {
+            ("set-namespace", Some(sub)) => {
+                set_context_namespace(env, sub.value_of("namespace"), writer)
+            }
+            ("unset-namespace", _) => set_context_namespace(env, None, writer),
+            _ => {
+                print_config_view(env, writer);
+                Ok(())
+            }
+        }
+    }
+);
+
 command!(
     EnvCmd,
     "env",
@@ -162,13 +430,98 @@ command!(
     }
 );
 
+command!(
+    Record,
+    "record",
+    "Record the commands you enter to a file, so they can be replayed later",
+    |clap: App<'static, 'static>| {
+        clap.arg(
+            Arg::with_name("file")
+                .help(
+                    "The file to record to (commands are appended if it exists), or 'stop' to \
+                     stop recording. With no argument, shows the current recording",
+                )
+                .required(false)
+                .index(1),
+        )
+        .arg(
+            Arg::with_name("skip_read_only")
+                .short("r")
+                .long("skip-read-only")
+                .help(
+                    "Don't record commands that only display information, like describe, logs \
+                     or events",
+                )
+                .takes_value(false),
+        )
+        .after_help(
+            "Commands are written one per line, after aliases have been expanded. As with \
+history, commands that start with a space are not recorded.
+
+Example:
+  # Start recording, leaving out read-only commands
+  record -r /tmp/session.click
+
+  # Stop recording
+  record stop",
+        )
+    },
+    vec!["record"],
+    noop_complete!(),
+    no_named_complete!(),
+    |matches, env, writer| {
+        match matches.value_of("file") {
+            Some("stop") => match env.stop_recording() {
+                Some(recording) => {
+                    clickwriteln!(writer, "Stopped recording to {}", recording.path)
+                }
+                None => clickwriteln!(writer, "Not recording"),
+            },
+            Some(path) => {
+                if let Some(recording) = env.stop_recording() {
+                    clickwriteln!(writer, "Stopped recording to {}", recording.path);
+                }
+                env.start_recording(path, matches.is_present("skip_read_only"))?;
+                clickwriteln!(writer, "Recording commands to {}", path);
+            }
+            None => match env.recording() {
+                Some(recording) => clickwriteln!(
+                    writer,
+                    "Recording commands to {}{}",
+                    recording.path,
+                    if recording.skip_read_only {
+                        " (skipping read-only commands)"
+                    } else {
+                        ""
+                    }
+                ),
+                None => clickwriteln!(writer, "Not recording"),
+            },
+        }
+        Ok(())
+    }
+);
+
 pub const SET_OPTS: &[&str] = &[
     "completion_type",
     "edit_mode",
     "editor",
+    "history",
+    "pager",
+    "paging",
+    "clipboard",
+    "color_table_headers",
+    "clear_on_switch",
+    "namespace_check",
+    "proxy",
     "terminal",
     "range_separator",
     "describe_include_events",
+    "cache_ttl_secs",
+    "max_items",
+    "prompt_template",
+    "impersonate",
+    "verbose",
 ];
 
 command!(
@@ -198,7 +551,54 @@ Example:
   set -- range_separator \"---- {name} [{namespace}] ----\"
 
   # set edit_mode
-  set edit_mode emacs",
+  set edit_mode emacs
+
+  # set the editor. environment variables ($VAR or ${VAR}) in editor and terminal are expanded
+  # when they're used, use $$ for a literal $
+  set editor \"$VISUAL -w\"
+
+  # send output that doesn't fit on the screen through a pager (less -R, or $PAGER if set)
+  set paging on
+  set pager \"less -RS\"
+
+  # copy to the clipboard with xsel rather than the first tool found (set it to \"\" to go back)
+  set clipboard \"xsel --clipboard --input\"
+
+  # show table headers in the current context's color. a context's color is set by adding
+  # color: red (or black, green, yellow, blue, purple, cyan or white) to it in the kubeconfig,
+  # which also colors the context in the prompt
+  set color_table_headers on
+
+  # clear the screen when changing context or namespace, so old output isn't mistaken for the
+  # new cluster's
+  set clear_on_switch on
+
+  # refuse to switch to a namespace that doesn't exist. warn (the default) still switches, but
+  # prints a warning, and off doesn't check
+  set namespace_check strict
+
+  # connect to the current context's server directly, rather than through the proxy in
+  # HTTPS_PROXY (or the cluster's proxy-url). set it back to on to use the proxy again
+  set proxy off
+
+  # keep a separate command history (and ctrl-r search) for each context. the default is global
+  set history per_context
+
+  # cache list results for 30 seconds (use --refresh on a list command to bypass the cache)
+  set cache_ttl_secs 30
+
+  # set the prompt. placeholders are {context}, {namespace}, {object} and {objkind}. colours
+  # are set with {red}, {green}, {yellow}, {blue}, {purple}, {cyan}, {white}, {black}, {bold}
+  # and {reset}. set it to \"\" to go back to the default prompt
+  set prompt_template \"{blue}{context}{reset}/{namespace} {object} > \"
+
+  # send requests as user alice in groups devs and system:masters (until the context changes).
+  # set it to off to stop. to impersonate for a single command, pass it --as and --as-group
+  set impersonate alice,devs,system:masters
+
+  # log each request's method, url, status and timing to stderr. 2 logs the request and response
+  # headers too (with credentials redacted), 0 turns logging off
+  set verbose 1",
         )
     },
     vec!["set"],
@@ -231,12 +631,90 @@ Example:
                     failed = true;
                 }
             },
+            "history" => match value {
+                "global" => env.set_history_mode(config::HistoryMode::Global),
+                "per_context" => env.set_history_mode(config::HistoryMode::PerContext),
+                _ => {
+                    clickwriteln!(
+                        writer,
+                        "Invalid history mode.  Possible values are: [global, per_context]"
+                    );
+                    failed = true;
+                }
+            },
             "editor" => {
                 env.set_editor(Some(value));
             }
+            "pager" => {
+                env.set_pager(Some(value));
+            }
+            "paging" => match value {
+                "on" | "true" => env.click_config.paging = true,
+                "off" | "false" => env.click_config.paging = false,
+                _ => {
+                    clickwriteln!(
+                        writer,
+                        "Invalid paging value.  Possible values are: [on, off]"
+                    );
+                    failed = true;
+                }
+            },
+            "color_table_headers" => match value {
+                "on" | "true" => env.click_config.color_table_headers = true,
+                "off" | "false" => env.click_config.color_table_headers = false,
+                _ => {
+                    clickwriteln!(
+                        writer,
+                        "Invalid color_table_headers value.  Possible values are: [on, off]"
+                    );
+                    failed = true;
+                }
+            },
+            "clear_on_switch" => match value {
+                "on" | "true" => env.click_config.clear_on_switch = true,
+                "off" | "false" => env.click_config.clear_on_switch = false,
+                _ => {
+                    clickwriteln!(
+                        writer,
+                        "Invalid clear_on_switch value.  Possible values are: [on, off]"
+                    );
+                    failed = true;
+                }
+            },
+            "namespace_check" => match value {
+                "off" => env.click_config.namespace_check = config::NamespaceCheck::Off,
+                "warn" => env.click_config.namespace_check = config::NamespaceCheck::Warn,
+                "strict" => env.click_config.namespace_check = config::NamespaceCheck::Strict,
+                _ => {
+                    clickwriteln!(
+                        writer,
+                        "Invalid namespace_check value.  Possible values are: [off, warn, strict]"
+                    );
+                    failed = true;
+                }
+            },
             "terminal" => {
                 env.set_terminal(Some(value));
             }
+            "clipboard" => {
+                let clipboard = if value.is_empty() { None } else { Some(value) };
+                env.set_clipboard(clipboard);
+            }
+            "proxy" => match value {
+                "on" | "true" | "off" | "false" => {
+                    if !env.set_proxy_enabled(value == "on" || value == "true")? {
+                        clickwriteln!(writer, "No active context, use 'context' to set one");
+                        failed = true;
+                    }
+                }
+                _ => {
+                    clickwriteln!(
+                        writer,
+                        "Invalid proxy value.  Possible values are: [on, off]"
+                    );
+                    failed = true;
+                }
+            },
             "range_separator" => {
                 env.click_config.range_separator = value.to_string();
             }
@@ -250,6 +728,61 @@ Example:
                     failed = true;
                 }
             },
+            "cache_ttl_secs" => match value.parse::<u32>() {
+                Ok(secs) => {
+                    env.click_config.cache_ttl_secs = secs;
+                    if secs == 0 {
+                        env.clear_list_cache();
+                    }
+                }
+                Err(_) => {
+                    clickwriteln!(
+                        writer,
+                        "cache_ttl_secs must be a non-negative number of seconds (0 disables caching)"
+                    );
+                    failed = true;
+                }
+            },
+            "prompt_template" => {
+                let template = if value.is_empty() { None } else { Some(value) };
+                for placeholder in env.set_prompt_template(template) {
+                    clickwriteln!(
+                        writer,
+                        "Warning: unknown placeholder {} in prompt_template, it will be shown as is",
+                        placeholder
+                    );
+                }
+            }
+            "impersonate" => {
+                let impersonation = match value {
+                    "" | "off" | "none" => None,
+                    spec => k8s::Impersonation::parse(spec),
+                };
+                if !env.set_impersonation(impersonation) {
+                    clickwriteln!(writer, "No active context, use 'context' to set one");
+                    failed = true;
+                }
+            }
+            "verbose" => match value.parse::<u8>() {
+                Ok(level) if level <= crate::trace::MAX_VERBOSITY => env.set_verbosity(level, true),
+                _ => {
+                    clickwriteln!(
+                        writer,
+                        "verbose must be 0 (off), 1 (log requests) or 2 (log requests and headers)"
+                    );
+                    failed = true;
+                }
+            },
+            "max_items" => match value.parse::<u32>() {
+                Ok(max) => env.click_config.max_items = max,
+                Err(_) => {
+                    clickwriteln!(
+                        writer,
+                        "max_items must be a non-negative number (0 shows everything)"
+                    );
+                    failed = true;
+                }
+            },
             _ => {
                 // this shouldn't happen
                 write!(stderr(), "Invalid option\n").unwrap_or(());
@@ -276,3 +809,73 @@ command!(
         Ok(())
     }
 );
+
+fn server_version(env: &Env) -> Result<Info, ClickError> {
+    let (request, _) = k8s_openapi::get_code_version()?;
+    match env.run_on_context::<_, GetCodeVersionResponse>(|c| c.read(request))? {
+        GetCodeVersionResponse::Ok(info) => Ok(info),
+        GetCodeVersionResponse::Other(_) => Err(ClickError::CommandError(
+            "Could not get the server version".to_string(),
+        )),
+    }
+}
+
+/// The group versions the server prefers, with the core group (which isn't in the group list)
+/// first
+fn preferred_group_versions(env: &mut Env) -> Result<Vec<String>, ClickError> {
+    let mut versions: Vec<String> = crate::crd::get_api_groups(env)?
+        .into_iter()
+        .filter_map(|group| match group.preferred_version {
+            Some(pv) => Some(pv.group_version),
+            None => group.versions.into_iter().next().map(|v| v.group_version),
+        })
+        .collect();
+    versions.sort();
+    versions.insert(0, "v1".to_string());
+    Ok(versions)
+}
+
+command!(
+    Version,
+    "version",
+    "Print the version of click and of the current context's api server",
+    |clap: App<'static, 'static>| clap.arg(
+        Arg::with_name("api_versions")
+            .short("a")
+            .long("api-versions")
+            .help("Also list the api group versions the server supports")
+            .takes_value(false)
+    ),
+    vec!["version"],
+    noop_complete!(),
+    no_named_complete!(),
+    |matches, env, writer| {
+        clickwriteln!(writer, "Client Version: {}", crate_version!());
+        if env.context.is_none() {
+            return Ok(());
+        }
+        let info = server_version(env)?;
+        clickwriteln!(
+            writer,
+            "Server Version: {} ({}, {}, built {})",
+            info.git_version,
+            info.platform,
+            info.go_version,
+            info.build_date
+        );
+        let versions = preferred_group_versions(env)?;
+        if matches.is_present("api_versions") {
+            clickwriteln!(writer, "Api Versions:");
+            for version in versions.iter() {
+                clickwriteln!(writer, "  {}", version);
+            }
+        } else {
+            clickwriteln!(
+                writer,
+                "Api Groups: {} (use -a to list them)",
+                versions.len()
+            );
+        }
+        Ok(())
+    }
+);
@@ -20,6 +20,7 @@ use rustyline::completion::Pair as RustlinePair;
 use crate::env::Env;
 use crate::error::ClickError;
 use crate::output::ClickWriter;
+use crate::parser::{closest_match, JsonPathSeg, OVERRIDE_FLAGS};
 
 use std::cell::RefCell;
 use std::cmp::Ordering;
@@ -80,12 +81,21 @@ pub trait Cmd {
     ) -> Result<(), ClickError>;
     fn is(&self, l: &str) -> bool;
     fn get_name(&self) -> &'static str;
-    fn try_complete(&self, index: usize, prefix: &str, env: &Env) -> Vec<RustlinePair>;
+    /// Complete the positional argument at index. args are the positional arguments typed before
+    /// it, for commands where what to offer depends on them
+    fn try_complete(
+        &self,
+        index: usize,
+        prefix: &str,
+        args: &[&str],
+        env: &Env,
+    ) -> Vec<RustlinePair>;
     fn try_completed_named(
         &self,
         index: usize,
         opt: &str,
         prefix: &str,
+        args: &[&str],
         env: &Env,
     ) -> Vec<RustlinePair>;
     fn complete_option(&self, prefix: &str) -> Vec<RustlinePair>;
@@ -113,6 +123,39 @@ pub fn start_clap(
     }
 }
 
+/// The long names (without the leading --) of all the flags and options of app
+pub fn long_options(app: &App<'static, 'static>) -> Vec<&'static str> {
+    let flags = app.p.flags.iter().filter_map(|flag| flag.s.long);
+    let opts = app.p.opts.iter().filter_map(|opt| opt.s.long);
+    flags.chain(opts).collect()
+}
+
+/// An error for an option app doesn't have, suggesting the closest one it (or click, for the
+/// context and namespace overrides) does have, if there's one close enough to be a likely typo
+fn unknown_option_error(app: &App<'static, 'static>, option: &str) -> ClickError {
+    let known: Vec<String> = long_options(app)
+        .iter()
+        .map(|long| format!("--{}", long))
+        .chain(std::iter::once("--help".to_string()))
+        .chain(OVERRIDE_FLAGS.iter().map(|flag| flag.to_string()))
+        .collect();
+    let name = option.split('=').next().unwrap_or(option); // safe, split always has one
+    let suggestion = if name.starts_with("--") {
+        closest_match(name, known.iter().map(|k| k.as_str()))
+    } else {
+        None
+    };
+    let mut msg = format!("Unknown option {} for {}.", name, app.p.meta.name);
+    if let Some(suggestion) = suggestion {
+        msg.push_str(&format!(" Did you mean {}?", suggestion));
+    }
+    msg.push_str(&format!(
+        " See '{} --help' for the options.",
+        app.p.meta.name
+    ));
+    ClickError::CommandError(msg)
+}
+
 /// Run specified closure with given matches. Returns () on success, or an Err if an error occurs
 pub fn exec_match<F>(
     clap: &RefCell<App<'static, 'static>>,
@@ -124,7 +167,9 @@ pub fn exec_match<F>(
 where
     F: FnOnce(ArgMatches, &mut Env, &mut ClickWriter) -> Result<(), ClickError>,
 {
-    let matches = clap.borrow_mut().get_matches_from_safe_borrow(args);
+    // match with a copy, since clap keeps some parsing state (like having seen the start of
+    // trailing args) in the app, which would leak into the next run of the command
+    let matches = clap.borrow().clone().get_matches_from_safe(args);
     match matches {
         Ok(matches) => func(matches, env, writer),
         Err(e) => {
@@ -133,6 +178,13 @@ where
             {
                 clickwriteln!(writer, "{}", e.message);
                 Ok(())
+            } else if e.kind == clap::ErrorKind::UnknownArgument {
+                match e.info.as_ref().and_then(|info| info.first()) {
+                    Some(arg) if arg.starts_with('-') => {
+                        Err(unknown_option_error(&clap.borrow(), arg))
+                    }
+                    _ => Err(ClickError::Clap(e)),
+                }
             } else {
                 Err(ClickError::Clap(e))
             }
@@ -164,6 +216,8 @@ macro_rules! no_named_complete {
 /// * named_cmplters: a map of argument -> completer for completing named arguments
 /// * cmd_expr: a closure taking matches, env, and writer that runs to execute the command
 /// * trailing_var_arg: set the "TrailingVarArg" setting for clap (see clap docs, default false)
+/// * args_cmplter: a completer for positional args that also gets the positional args typed
+///   before the one being completed. Used instead of cmplt_expr if set (default None)
 ///
 /// # Example
 /// ```
@@ -198,11 +252,28 @@ macro_rules! command {
 
     ($cmd_name:ident, $name:expr, $about:expr, $extra_args:expr, $aliases:expr, $cmplters: expr,
      $named_cmplters: expr, $cmd_expr:expr, $trailing_var_arg: expr) => {
+        command!(
+            $cmd_name,
+            $name,
+            $about,
+            $extra_args,
+            $aliases,
+            $cmplters,
+            $named_cmplters,
+            $cmd_expr,
+            $trailing_var_arg,
+            None
+        );
+    };
+
+    ($cmd_name:ident, $name:expr, $about:expr, $extra_args:expr, $aliases:expr, $cmplters: expr,
+     $named_cmplters: expr, $cmd_expr:expr, $trailing_var_arg: expr, $args_cmplter: expr) => {
         pub struct $cmd_name {
             aliases: Vec<&'static str>,
             clap: RefCell<App<'static, 'static>>,
             completers: Vec<&'static dyn Fn(&str, &Env) -> Vec<RustlinePair>>,
             named_completers: HashMap<String, fn(&str, &Env) -> Vec<RustlinePair>>,
+            args_completer: Option<fn(usize, &str, &[&str], &Env) -> Vec<RustlinePair>>,
         }
 
         impl $cmd_name {
@@ -218,6 +289,7 @@ macro_rules! command {
                     clap: RefCell::new(extra),
                     completers: $cmplters,
                     named_completers: $named_cmplters,
+                    args_completer: $args_cmplter,
                 }
             }
         }
@@ -252,7 +324,16 @@ macro_rules! command {
                 $about
             }
 
-            fn try_complete(&self, index: usize, prefix: &str, env: &Env) -> Vec<RustlinePair> {
+            fn try_complete(
+                &self,
+                index: usize,
+                prefix: &str,
+                args: &[&str],
+                env: &Env,
+            ) -> Vec<RustlinePair> {
+                if let Some(args_completer) = self.args_completer {
+                    return args_completer(index, prefix, args, env);
+                }
                 match self.completers.get(index) {
                     Some(completer) => completer(prefix, env),
                     None => vec![],
@@ -264,6 +345,7 @@ macro_rules! command {
                 index: usize,
                 opt: &str,
                 prefix: &str,
+                args: &[&str],
                 env: &Env,
             ) -> Vec<RustlinePair> {
                 let parser = &self.clap.borrow().p;
@@ -284,45 +366,13 @@ macro_rules! command {
                         Some(completer) => completer(prefix, env),
                         None => vec![],
                     },
-                    None => self.try_complete(index, prefix, env),
+                    None => self.try_complete(index, prefix, args, env),
                 }
             }
 
-            /**
-             *  Completes all possible long options for this command, with the given prefix.
-             *  This is rather gross as we have to do everything inside this method.
-             *  clap::arg is private, so we can't define methods that take the traits
-             *  that all args implement, and have to handle each individually
-             */
+            /// Completes all possible long options for this command, with the given prefix
             fn complete_option(&self, prefix: &str) -> Vec<RustlinePair> {
-                let repoff = prefix.len();
-                let parser = &self.clap.borrow().p;
-
-                let flags = parser
-                    .flags
-                    .iter()
-                    .filter(|flag_builder| completer::long_matches(&flag_builder.s.long, prefix))
-                    .map(|flag_builder| RustlinePair {
-                        display: format!("--{}", flag_builder.s.long.unwrap()),
-                        replacement: format!(
-                            "{} ",
-                            flag_builder.s.long.unwrap()[repoff..].to_string()
-                        ),
-                    });
-
-                let opts = parser
-                    .opts
-                    .iter()
-                    .filter(|opt_builder| completer::long_matches(&opt_builder.s.long, prefix))
-                    .map(|opt_builder| RustlinePair {
-                        display: format!("--{}", opt_builder.s.long.unwrap()),
-                        replacement: format!(
-                            "{} ",
-                            opt_builder.s.long.unwrap()[repoff..].to_string()
-                        ),
-                    });
-
-                flags.chain(opts).collect()
+                completer::complete_long_option(&self.clap.borrow(), prefix)
             }
         }
     };
@@ -357,7 +407,20 @@ macro_rules! list_command {
             $cmd_name,
             $name,
             $about,
-            $extra_args,
+            |clap: App<'static, 'static>| $extra_args(clap)
+                .arg(crate::command::command_def::all_namespaces_arg())
+                .arg(crate::command::command_def::chunk_size_arg())
+                .arg(crate::command::command_def::buffer_arg())
+                .arg(crate::command::command_def::output_arg())
+                .arg(crate::command::command_def::refresh_arg())
+                .arg(crate::command::command_def::request_timeout_arg())
+                .arg(crate::command::command_def::no_headers_arg())
+                .arg(crate::command::command_def::selector_arg())
+                .arg(crate::command::command_def::field_selector_arg())
+                .arg(crate::command::command_def::max_items_arg())
+                .arg(crate::command::command_def::limit_arg())
+                .arg(crate::command::command_def::sort_by_arg())
+                .arg(crate::command::command_def::sort_reverse_arg()),
             $aliases,
             $cmplters,
             //$named_cmplters,
@@ -366,6 +429,10 @@ macro_rules! list_command {
                     "sort".to_string(),
                     list_sort_completers::$cmd_name as fn(&str, &Env) -> Vec<RustlinePair>
                 ),
+                (
+                    "sort-by".to_string(),
+                    list_sort_completers::$cmd_name as fn(&str, &Env) -> Vec<RustlinePair>
+                ),
                 (
                     "show".to_string(),
                     list_show_completers::$cmd_name as fn(&str, &Env) -> Vec<RustlinePair>
@@ -389,7 +456,7 @@ macro_rules! list_command {
 /// flags: A vector of the flags that were passed by the user
 /// extra_cols: Extra cols to consider. This is a vector of (column_name, flag). If flag is in
 /// flags, then column_name is added to cols. The order in this vector is the order columns will be
-/// displayed in the output
+/// displayed in the output. Columns already in cols aren't added again
 pub fn add_extra_cols<'a>(
     cols: &mut Vec<&'a str>,
     labels: bool,
@@ -399,6 +466,9 @@ pub fn add_extra_cols<'a>(
     let show_all = flags.iter().any(|e| e.eq_ignore_ascii_case("all"));
 
     for (flag, col) in extra_cols.iter() {
+        if cols.contains(col) {
+            continue;
+        }
         if col.eq(&"Labels") {
             if labels || flags.iter().any(|e| e.eq_ignore_ascii_case("labels")) {
                 cols.push(col)
@@ -413,7 +483,8 @@ pub fn add_extra_cols<'a>(
 // just before display
 pub enum SortFunc<T> {
     Pre(PreExtractSort<T>),
-    Post(&'static str), // sort based on column index given
+    Post(&'static str),         // sort based on column index given
+    JsonPath(Vec<JsonPathSeg>), // sort on the first value the path selects from each object
 }
 
 /// A function that can sort based on a column, pre extraction
@@ -474,3 +545,190 @@ pub fn show_arg<'a>(extra_cols: &[&'a str], labels: bool) -> Arg<'a, 'a> {
         arg.help(SHOW_HELP)
     }
 }
+
+/// get a clap arg for fetching a list in chunks of the specified size
+pub fn chunk_size_arg<'a>() -> Arg<'a, 'a> {
+    Arg::with_name("chunk_size")
+        .long("chunk-size")
+        .help(
+            "Fetch the list from the server in pages of this many items, printing each page as \
+             it arrives. Column widths are set by the first page, so later pages might not line \
+             up perfectly (see --buffer)",
+        )
+        .takes_value(true)
+        .validator(crate::command::valid_u32)
+}
+
+/// get a clap arg to list objects from all namespaces, rather than the current one
+pub fn all_namespaces_arg<'a>() -> Arg<'a, 'a> {
+    Arg::with_name("all_namespaces")
+        .short("A")
+        .long("all-namespaces")
+        .help(
+            "List objects from all namespaces, not just the current one. A Namespace column is \
+             added, and selecting an object from the list uses its namespace",
+        )
+        .takes_value(false)
+}
+
+/// get a clap arg for sorting by columns or by arbitrary fields
+pub fn sort_by_arg<'a>() -> Arg<'a, 'a> {
+    Arg::with_name("sort_by")
+        .long("sort-by")
+        .value_name("COLUMN|JSONPATH")
+        .help(
+            "Sort by the specified column (any column --sort accepts), or by a field given as a \
+             jsonpath, like {.status.startTime}. Give more than one key, by repeating --sort-by \
+             or as a comma separated list, to break ties in the first key with the next one. \
+             Numbers (like restarts and age) sort numerically, everything else lexically, and \
+             objects without the field go last. Rows that tie on every key keep the order the api \
+             server returned",
+        )
+        .takes_value(true)
+        .multiple(true)
+        .number_of_values(1)
+}
+
+/// get a clap arg for sorting in descending order
+pub fn sort_reverse_arg<'a>() -> Arg<'a, 'a> {
+    Arg::with_name("sort_reverse")
+        .long("sort-reverse")
+        .help(
+            "Sort in descending order. Unlike --reverse, rows that tie on every sort key still \
+             keep the order the api server returned",
+        )
+        .takes_value(false)
+}
+
+/// get a clap arg to limit how many rows of a list are printed
+pub fn max_items_arg<'a>() -> Arg<'a, 'a> {
+    Arg::with_name("max_items")
+        .long("max-items")
+        .value_name("N")
+        .help(
+            "Only show the first N items (after sorting and filtering). 0 shows everything. The \
+             default can be set with 'set max_items N'",
+        )
+        .takes_value(true)
+        .validator(crate::command::valid_u32)
+}
+
+/// get a clap arg to stop fetching a list from the server after a number of items
+pub fn limit_arg<'a>() -> Arg<'a, 'a> {
+    Arg::with_name("limit")
+        .long("limit")
+        .value_name("N")
+        .help(
+            "Only fetch the first N items from the server. Unlike --max-items the rest are never \
+             fetched, which is much faster for huge lists, but sorting only sees the N items \
+             fetched. With --chunk-size, pages are fetched until N items have arrived",
+        )
+        .takes_value(true)
+        .validator(|s: String| match s.parse::<u32>() {
+            Ok(0) => Err("Limit must be at least 1".to_string()),
+            Ok(_) => Ok(()),
+            Err(e) => Err(e.to_string()),
+        })
+}
+
+/// get a clap arg to buffer all pages of a chunked list before printing
+pub fn buffer_arg<'a>() -> Arg<'a, 'a> {
+    Arg::with_name("buffer")
+        .long("buffer")
+        .help(
+            "When used with --chunk-size, wait for all pages to arrive and print them as a \
+             single, perfectly aligned table",
+        )
+        .takes_value(false)
+}
+
+/// get a clap arg for specifying an output format (see OutputFormat)
+pub fn output_arg<'a>() -> Arg<'a, 'a> {
+    Arg::with_name("output")
+        .short("o")
+        .long("output")
+        .help(
+            "Output format. One of json, yaml, wide, jsonpath=<expr> or go-template=<template>. \
+             json and yaml print the objects (as a List for list commands) without colors, for \
+             use in scripts. wide adds all the extra columns --show knows about, except labels. \
+             jsonpath prints the result of evaluating the kubectl style jsonpath expression \
+             against each object, one line per object (e.g. -o jsonpath='{.spec.nodeName}'). \
+             go-template renders a Go template against the object (the List for list commands), \
+             e.g. -o go-template='{{range .items}}{{.metadata.name}}{{\"\\n\"}}{{end}}'. \
+             Supported: fields, $, if/else, range, pipelines, and printf, len, index, eq, ne, \
+             not, and, or",
+        )
+        .takes_value(true)
+        .validator(|s: String| {
+            s.parse::<crate::command::OutputFormat>()
+                .map(|_| ())
+                .map_err(|e| e.to_string())
+        })
+}
+
+/// get a clap arg to filter lists by label
+pub fn selector_arg<'a>() -> Arg<'a, 'a> {
+    Arg::with_name("selector")
+        .short("l")
+        .long("selector")
+        .value_name("SELECTOR")
+        .help(
+            "Only list objects whose labels match the selector. Supports key=val, key!=val, \
+             key, !key, key in (a,b) and key notin (a,b), separated by commas",
+        )
+        .validator(|s: String| crate::parser::parse_label_selector(&s).map(|_| ()))
+        .takes_value(true)
+}
+
+/// get a clap arg to filter lists by fields of the objects
+pub fn field_selector_arg<'a>() -> Arg<'a, 'a> {
+    Arg::with_name("field_selector")
+        .long("field-selector")
+        .value_name("SELECTOR")
+        .help(
+            "Only list objects whose fields match the selector, like status.phase=Running. \
+             Supports field=val and field!=val, separated by commas. All kinds support \
+             metadata.name and metadata.namespace, some support more (e.g. status.phase and \
+             spec.nodeName for pods)",
+        )
+        .validator(|s: String| crate::parser::parse_field_selector(&s).map(|_| ()))
+        .takes_value(true)
+}
+
+/// get a clap arg to print lists without the header row
+pub fn no_headers_arg<'a>() -> Arg<'a, 'a> {
+    Arg::with_name("no_headers")
+        .long("no-headers")
+        .help(
+            "Print just the rows of the list, without the header, separators or #### column, \
+             for scripts. Objects can still be selected by their number in the list",
+        )
+        .takes_value(false)
+}
+
+/// get a clap arg to limit how long fetching a list can take
+pub fn request_timeout_arg<'a>() -> Arg<'a, 'a> {
+    Arg::with_name("request_timeout")
+        .long("request-timeout")
+        .value_name("SECONDS")
+        .help(
+            "Give up on fetching the list after this many seconds. The server is asked to stop \
+             (via timeoutSeconds) and click stops waiting shortly after, overriding \
+             read_timeout_secs. With --chunk-size the limit applies to each page. By default \
+             the server sets no limit",
+        )
+        .takes_value(true)
+        .validator(|s: String| match s.parse::<u32>() {
+            Ok(0) => Err("Request timeout must be at least 1 second".to_string()),
+            Ok(_) => Ok(()),
+            Err(e) => Err(e.to_string()),
+        })
+}
+
+/// get a clap arg to bypass the list cache
+pub fn refresh_arg<'a>() -> Arg<'a, 'a> {
+    Arg::with_name("refresh")
+        .long("refresh")
+        .help("Always fetch the list from the server, even if there's a cached copy (see 'set cache_ttl_secs')")
+        .takes_value(false)
+}
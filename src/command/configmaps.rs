@@ -18,7 +18,7 @@ use k8s_openapi::api::core::v1 as api;
 
 use crate::{
     command::command_def::{exec_match, show_arg, sort_arg, start_clap, Cmd},
-    command::{run_list_command, Extractor},
+    command::{list_namespace, run_list_command, Extractor},
     completer,
     env::Env,
     kobj::{KObj, ObjType},
@@ -93,7 +93,7 @@ list_command!(
     noop_complete!(),
     IntoIter::new([]),
     |matches, env, writer| {
-        let (request, _response_body) = match &env.namespace {
+        let (request, _response_body) = match list_namespace(&matches, env) {
             Some(ns) => api::ConfigMap::list_namespaced_config_map(ns, Default::default())?,
             None => api::ConfigMap::list_config_map_for_all_namespaces(Default::default())?,
         };
@@ -0,0 +1,227 @@
+// Copyright 2021 Databricks, Inc.
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+
+// http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use ansi_term::Colour::Yellow;
+use clap::{App, Arg};
+use k8s_openapi::http::{Request, StatusCode};
+use prettytable::Row;
+use rustyline::completion::Pair as RustlinePair;
+use serde_json::Value;
+
+use crate::{
+    command::command_def::{all_namespaces_arg, exec_match, selector_arg, start_clap, Cmd},
+    command::get::kind_list_path,
+    command::{list_namespace, path_with_query},
+    completer,
+    env::Env,
+    error::ClickError,
+    output::ClickWriter,
+    parser::parse_label_selector,
+    table::{print_table, title_cell, CellSpec},
+    values::val_str,
+};
+
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap};
+use std::io::Write;
+
+/// The kinds counted if none are given
+const DEFAULT_KINDS: &[&str] = &[
+    "pods",
+    "deployments",
+    "replicasets",
+    "statefulsets",
+    "jobs",
+    "cronjobs",
+    "services",
+    "ingresses",
+    "configmaps",
+    "secrets",
+    "persistentvolumeclaims",
+];
+
+/// Ways the objects of a kind can be split up, as (flag, column title, pods only)
+const GROUPINGS: &[(&str, &str, bool)] = &[
+    ("by_namespace", "Namespace", false),
+    ("by_status", "Status", true),
+    ("by_node", "Node", true),
+];
+
+/// What an object is counted under for a grouping
+fn group_value(column: &str, item: &Value) -> String {
+    match column {
+        "Namespace" => val_str("/metadata/namespace", item, "<none>").into_owned(),
+        "Status" => val_str("/status/phase", item, "Unknown").into_owned(),
+        "Node" => val_str("/spec/nodeName", item, "<unscheduled>").into_owned(),
+        _ => String::new(),
+    }
+}
+
+/// List the objects at path and return them
+fn list_items(env: &Env, path: &str) -> Result<Vec<Value>, ClickError> {
+    let request = Request::get(path).body(vec![]).unwrap(); // safe, valid path
+    let response = env.run_on_context(|c| c.execute(request))?;
+    if response.status() != StatusCode::OK {
+        return Err(ClickError::CommandError(response.status().to_string()));
+    }
+    let mut list: Value = serde_json::from_slice(response.body())?;
+    match list.get_mut("items").map(Value::take) {
+        Some(Value::Array(items)) => Ok(items),
+        _ => Ok(vec![]),
+    }
+}
+
+command!(
+    Count,
+    "count",
+    "Count the objects of each kind in the current namespace (or all namespaces with -A)",
+    |clap: App<'static, 'static>| clap
+        .arg(
+            Arg::with_name("kinds")
+                .help(
+                    "The kinds to count. Defaults to pods, deployments, replicasets, \
+                     statefulsets, jobs, cronjobs, services, ingresses, configmaps, secrets and \
+                     persistentvolumeclaims"
+                )
+                .multiple(true)
+                .index(1)
+        )
+        .arg(all_namespaces_arg())
+        .arg(selector_arg())
+        .arg(
+            Arg::with_name("by_namespace")
+                .long("by-namespace")
+                .help("Count the objects in each namespace separately")
+                .takes_value(false)
+        )
+        .arg(
+            Arg::with_name("by_status")
+                .long("by-status")
+                .help("Count pods in each phase (Running, Pending, ...) separately")
+                .takes_value(false)
+        )
+        .arg(
+            Arg::with_name("by_node")
+                .long("by-node")
+                .help("Count the pods on each node separately")
+                .takes_value(false)
+        )
+        .after_help(
+            "Examples:
+  # count the objects in the current namespace
+  count
+
+  # count deployments and services in all namespaces, for each namespace
+  count deploy svc -A --by-namespace
+
+  # count the pods in each phase on each node
+  count pods --by-node --by-status"
+        ),
+    vec!["count"],
+    vec![&completer::kind_values_completer],
+    no_named_complete!(),
+    |matches, env, writer| {
+        let kinds: Vec<&str> = matches
+            .values_of("kinds")
+            .map(|kinds| kinds.collect())
+            .unwrap_or_else(|| DEFAULT_KINDS.to_vec());
+        let groupings: Vec<(&str, bool)> = GROUPINGS
+            .iter()
+            .filter(|(flag, _, _)| matches.is_present(flag))
+            .map(|(_, column, pods_only)| (*column, *pods_only))
+            .collect();
+        let namespace = list_namespace(&matches, env).map(|ns| ns.to_string());
+        let selector = matches.value_of("selector").map(|sel| {
+            // already validated, so parse can't fail
+            let reqs: Vec<String> = parse_label_selector(sel)
+                .unwrap()
+                .iter()
+                .map(|r| r.to_string())
+                .collect();
+            reqs.join(",")
+        });
+
+        // check all the kinds before fetching anything
+        let mut paths = vec![];
+        for kind in kinds.iter() {
+            let (name, namespaced, path) = kind_list_path(kind, namespace.as_deref(), env)?;
+            if let Some((column, _)) = groupings.iter().find(|(_, pods_only)| *pods_only) {
+                if name != "pods" {
+                    return Err(ClickError::CommandError(format!(
+                        "Can only count pods by {}, not {}",
+                        column.to_lowercase(),
+                        name
+                    )));
+                }
+            }
+            if !namespaced && groupings.iter().any(|(column, _)| *column == "Namespace") {
+                return Err(ClickError::CommandError(format!(
+                    "{} aren't in a namespace, so can't be counted by namespace",
+                    name
+                )));
+            }
+            let path = match selector.as_deref() {
+                Some(sel) => path_with_query(&path, &[("labelSelector", sel)]),
+                None => path,
+            };
+            paths.push((name, path));
+        }
+
+        let mut rows: Vec<Vec<CellSpec>> = vec![];
+        let mut failures = vec![];
+        for (name, path) in paths.into_iter() {
+            let items = match list_items(env, &path) {
+                Ok(items) => items,
+                Err(e) => {
+                    // one kind we can't list (say secrets we aren't allowed to see) shouldn't
+                    // stop us counting the others
+                    rows.push(vec![name.into(), "-".into()]);
+                    failures.push(format!("Couldn't count {}: {}", name, e));
+                    continue;
+                }
+            };
+            let mut counts: BTreeMap<Vec<String>, usize> = BTreeMap::new();
+            for item in items.iter() {
+                let key = groupings
+                    .iter()
+                    .map(|(column, _)| group_value(column, item))
+                    .collect();
+                *counts.entry(key).or_insert(0) += 1;
+            }
+            if counts.is_empty() {
+                counts.insert(vec!["".to_string(); groupings.len()], 0);
+            }
+            for (key, count) in counts.into_iter() {
+                let mut row: Vec<CellSpec> = vec![name.into()];
+                row.extend(key.into_iter().map(CellSpec::from));
+                row.push(count.into());
+                rows.push(row);
+            }
+        }
+
+        let title_colour = env.table_header_colour();
+        let mut titles = vec![title_cell("Kind", title_colour)];
+        titles.extend(
+            groupings
+                .iter()
+                .map(|(column, _)| title_cell(column, title_colour)),
+        );
+        titles.push(title_cell("Count", title_colour));
+        print_table(Row::new(titles), rows, writer);
+        for failure in failures.iter() {
+            clickwriteln!(writer, "{}", failure);
+        }
+        Ok(())
+    }
+);
@@ -0,0 +1,527 @@
+// Copyright 2021 Databricks, Inc.
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+
+// http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use ansi_term::Colour::Yellow;
+use clap::{App, Arg};
+use os_pipe::pipe;
+use rustyline::completion::Pair as RustlinePair;
+
+use crate::{
+    command::command_def::{exec_match, start_clap, Cmd},
+    completer,
+    env::Env,
+    error::ClickError,
+    kobj::{KObj, ObjType},
+    output::ClickWriter,
+};
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::io::{self, Write};
+use std::path::Path;
+use std::process::{Command, Output, Stdio};
+
+/// Split a path in the container into its parent directory and final component
+fn split_pod_path(path: &str) -> (&str, &str) {
+    let path = if path.len() > 1 {
+        path.trim_end_matches('/')
+    } else {
+        path
+    };
+    match path.rsplit_once('/') {
+        Some(("", name)) => ("/", name),
+        Some((parent, name)) => (parent, name),
+        None => (".", path),
+    }
+}
+
+/// A copy between the local machine and the container
+#[derive(Debug, PartialEq)]
+enum Direction<'a> {
+    ToPod { local: &'a str, remote: &'a str },
+    FromPod { remote: &'a str, local: &'a str },
+}
+
+/// Work out which way a copy goes from its source and destination. The one in the container is
+/// prefixed with :, and exactly one of them must be
+fn parse_direction<'a>(src: &'a str, dest: &'a str) -> Option<Direction<'a>> {
+    match (src.strip_prefix(':'), dest.strip_prefix(':')) {
+        (None, Some(remote)) => Some(Direction::ToPod { local: src, remote }),
+        (Some(remote), None) => Some(Direction::FromPod {
+            remote,
+            local: dest,
+        }),
+        _ => None,
+    }
+}
+
+/// The directory in the container to extract into, and the name the copy gets there. A remote
+/// path ending in / is a directory to copy into, keeping the local name
+fn remote_target<'a>(remote: &'a str, local_name: &'a str) -> (&'a str, &'a str) {
+    if remote.ends_with('/') {
+        (remote, local_name)
+    } else {
+        split_pod_path(remote)
+    }
+}
+
+/// Where a copy out of the container goes locally
+#[derive(Debug, PartialEq)]
+enum LocalTarget<'a> {
+    /// an existing directory, the copy goes in it and keeps its name from the container
+    Into(&'a Path),
+    /// the path doesn't exist (or isn't a directory), the copy is made in parent and renamed to it
+    Rename { parent: &'a Path },
+}
+
+/// The directory path is in, which is . for a bare name
+fn parent_dir(path: &Path) -> &Path {
+    match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    }
+}
+
+fn local_target(local: &Path) -> LocalTarget<'_> {
+    if local.is_dir() {
+        LocalTarget::Into(local)
+    } else {
+        LocalTarget::Rename {
+            parent: parent_dir(local),
+        }
+    }
+}
+
+fn pick_container<'a>(
+    pod: &'a KObj,
+    cont_opt: Option<&'a str>,
+    writer: &mut ClickWriter,
+) -> Option<&'a str> {
+    match (cont_opt, &pod.typ) {
+        (Some(cont), _) => Some(cont),
+        (None, ObjType::Pod { containers }) => {
+            if containers.len() > 1 {
+                clickwriteln!(
+                    writer,
+                    "Pod has multiple containers, using {} (use -c to pick another)",
+                    containers[0]
+                );
+            }
+            containers.first().map(|c| c.as_str())
+        }
+        _ => None,
+    }
+}
+
+/// build a kubectl command that will exec args in the container
+fn kubectl_exec(
+    env: &Env,
+    context: &str,
+    pod: &KObj,
+    container: Option<&str>,
+    args: &[&str],
+) -> Command {
+    let mut command = Command::new("kubectl");
+    command
+        .arg("--namespace")
+        .arg(pod.namespace.as_deref().unwrap_or("default"))
+        .arg("--context")
+        .arg(context)
+        .args(env.kubectl_impersonation_args())
+        .arg("exec")
+        .arg("-i")
+        .arg(pod.name());
+    if let Some(cont) = container {
+        command.arg("-c").arg(cont);
+    }
+    command.arg("--").args(args);
+    command
+}
+
+fn spawn_err(what: &str, e: io::Error) -> ClickError {
+    if let io::ErrorKind::NotFound = e.kind() {
+        ClickError::CommandError(format!(
+            "Could not find {} binary. Is it in your PATH?",
+            what
+        ))
+    } else {
+        ClickError::Io(e)
+    }
+}
+
+/// Turn the output of the kubectl side of the copy into an error if it failed
+fn check_kubectl(output: &Output) -> Result<(), ClickError> {
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(kubectl_error(
+            output.status.code(),
+            &String::from_utf8_lossy(&output.stderr),
+        ))
+    }
+}
+
+/// The error for a failed kubectl exec, calling out a missing tar in the container specifically
+fn kubectl_error(code: Option<i32>, stderr: &str) -> ClickError {
+    if stderr.contains("executable file not found")
+        || stderr.contains("tar: not found")
+        || stderr.contains("tar: command not found")
+        || code == Some(127)
+    {
+        ClickError::CommandError(
+            "tar is not available in the container, so files can't be copied".to_string(),
+        )
+    } else {
+        ClickError::CommandError(format!("Copy in container failed: {}", stderr.trim()))
+    }
+}
+
+fn check_tar(output: &Output) -> Result<(), ClickError> {
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(ClickError::CommandError(format!(
+            "Local tar failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )))
+    }
+}
+
+/// Run producer with its stdout connected to consumer's stdin, and wait for both to finish
+fn run_pipeline(
+    mut producer: Command,
+    producer_name: &str,
+    mut consumer: Command,
+    consumer_name: &str,
+) -> Result<(Output, Output), ClickError> {
+    let (reader, pwriter) = pipe()?;
+    producer.stdout(pwriter).stderr(Stdio::piped());
+    consumer
+        .stdin(reader)
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped());
+    let producer_child = producer.spawn().map_err(|e| spawn_err(producer_name, e))?;
+    let consumer_child = consumer.spawn().map_err(|e| spawn_err(consumer_name, e))?;
+    // the commands hold copies of the pipe ends, drop them so the consumer sees eof when the
+    // producer exits
+    drop(producer);
+    drop(consumer);
+    let producer_out = producer_child.wait_with_output()?;
+    let consumer_out = consumer_child.wait_with_output()?;
+    Ok((producer_out, consumer_out))
+}
+
+/// Make local available at staged, for tar to pick up under staged's name
+#[cfg(unix)]
+fn stage_as(local: &Path, staged: &Path) -> Result<(), ClickError> {
+    // tar follows the link
+    std::os::unix::fs::symlink(local.canonicalize()?, staged)?;
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn stage_as(local: &Path, staged: &Path) -> Result<(), ClickError> {
+    if local.is_dir() {
+        return Err(ClickError::CommandError(
+            "Directories can only be renamed while copying on unix, copy to a path ending in / \
+             to keep the name"
+                .to_string(),
+        ));
+    }
+    std::fs::copy(local, staged)?;
+    Ok(())
+}
+
+fn copy_to_pod(
+    env: &Env,
+    context: &str,
+    pod: &KObj,
+    container: Option<&str>,
+    local: &str,
+    remote: &str,
+) -> Result<(), ClickError> {
+    let local_path = Path::new(local);
+    if !local_path.exists() {
+        return Err(ClickError::CommandError(format!(
+            "{} does not exist",
+            local
+        )));
+    }
+    let local_name = match local_path.file_name().and_then(|n| n.to_str()) {
+        Some(name) => name,
+        None => {
+            return Err(ClickError::CommandError(format!(
+                "Can't copy {}, it has no file name",
+                local
+            )))
+        }
+    };
+    let (remote_dir, remote_name) = remote_target(remote, local_name);
+
+    let mut tar = Command::new("tar");
+    // keep the temp dir around until tar is done with it
+    let _tmpdir = if remote_name == local_name {
+        tar.arg("chf")
+            .arg("-")
+            .arg("-C")
+            .arg(parent_dir(local_path))
+            .arg(local_name);
+        None
+    } else {
+        // put the local path in a temp dir under the name it should have in the container, so
+        // we don't need any tar-specific rename options
+        let tmpdir = match env.tempdir {
+            Ok(ref td) => tempdir::TempDir::new_in(td.path(), "cp")?,
+            Err(ref e) => {
+                return Err(ClickError::CommandError(format!(
+                    "Failed to create tempdir: {}",
+                    e
+                )));
+            }
+        };
+        stage_as(local_path, &tmpdir.path().join(remote_name))?;
+        tar.arg("chf")
+            .arg("-")
+            .arg("-C")
+            .arg(tmpdir.path())
+            .arg(remote_name);
+        Some(tmpdir)
+    };
+    let kubectl = kubectl_exec(
+        env,
+        context,
+        pod,
+        container,
+        &["tar", "xf", "-", "-C", remote_dir],
+    );
+    let (tar_out, kubectl_out) = run_pipeline(tar, "tar", kubectl, "kubectl")?;
+    check_kubectl(&kubectl_out)?;
+    check_tar(&tar_out)
+}
+
+fn copy_from_pod(
+    env: &Env,
+    context: &str,
+    pod: &KObj,
+    container: Option<&str>,
+    remote: &str,
+    local: &str,
+) -> Result<(), ClickError> {
+    let (remote_dir, remote_name) = split_pod_path(remote);
+    let local_path = Path::new(local);
+    let kubectl = kubectl_exec(
+        env,
+        context,
+        pod,
+        container,
+        &["tar", "cf", "-", "-C", remote_dir, remote_name],
+    );
+
+    match local_target(local_path) {
+        LocalTarget::Into(dir) => {
+            let mut tar = Command::new("tar");
+            tar.arg("xf").arg("-").arg("-C").arg(dir);
+            let (kubectl_out, tar_out) = run_pipeline(kubectl, "kubectl", tar, "tar")?;
+            check_kubectl(&kubectl_out)?;
+            check_tar(&tar_out)
+        }
+        LocalTarget::Rename { parent } => {
+            // extract next to the destination, so moving it into place is a rename on the same
+            // filesystem
+            let tmpdir = tempdir::TempDir::new_in(parent, ".click-cp")?;
+            let mut tar = Command::new("tar");
+            tar.arg("xf").arg("-").arg("-C").arg(tmpdir.path());
+            let (kubectl_out, tar_out) = run_pipeline(kubectl, "kubectl", tar, "tar")?;
+            check_kubectl(&kubectl_out)?;
+            check_tar(&tar_out)?;
+            std::fs::rename(tmpdir.path().join(remote_name), local_path)?;
+            Ok(())
+        }
+    }
+}
+
+command!(
+    Cp,
+    "cp",
+    "Copy files and directories to and from the active pod",
+    |clap: App<'static, 'static>| clap
+        .arg(
+            Arg::with_name("src")
+                .help("The source. Prefix with : to refer to a path in the container")
+                .required(true)
+                .index(1)
+        )
+        .arg(
+            Arg::with_name("dest")
+                .help("The destination. Prefix with : to refer to a path in the container")
+                .required(true)
+                .index(2)
+        )
+        .arg(
+            Arg::with_name("container")
+                .short("c")
+                .long("container")
+                .help("Copy to/from the specified container (default is the first container)")
+                .takes_value(true)
+        )
+        .after_help(
+            "Files are copied by running tar in the container, so tar must be installed there.
+
+Examples:
+  # Copy a local file into /tmp in the container
+  cp ./debug.sh :/tmp/
+
+  # Copy a directory out of the container
+  cp :/var/log/app ./app-logs"
+        ),
+    vec!["cp"],
+    noop_complete!(),
+    std::iter::once((
+        "container".to_string(),
+        completer::container_completer as fn(&str, &Env) -> Vec<RustlinePair>
+    ))
+    .collect(),
+    |matches, env, writer| {
+        let src = matches.value_of("src").unwrap(); // safe, required
+        let dest = matches.value_of("dest").unwrap(); // safe, required
+        let context = match env.context.as_ref() {
+            Some(c) => c.name.clone(),
+            None => {
+                return Err(ClickError::CommandError(
+                    "Need an active context in order to copy files.".to_string(),
+                ))
+            }
+        };
+        let pod = match env.current_pod() {
+            Some(pod) => pod,
+            None => {
+                return Err(ClickError::CommandError(
+                    "No active pod, select one to copy files".to_string(),
+                ))
+            }
+        };
+        let container = pick_container(pod, matches.value_of("container"), writer);
+        match parse_direction(src, dest) {
+            Some(Direction::ToPod { local, remote }) => {
+                copy_to_pod(env, &context, pod, container, local, remote)?;
+                clickwriteln!(writer, "Copied {} to {}:{}", local, pod.name(), remote);
+                Ok(())
+            }
+            Some(Direction::FromPod { remote, local }) => {
+                copy_from_pod(env, &context, pod, container, remote, local)?;
+                clickwriteln!(writer, "Copied {}:{} to {}", pod.name(), remote, local);
+                Ok(())
+            }
+            None => {
+                clickwriteln!(
+                    writer,
+                    "{}",
+                    Yellow.paint("Exactly one of the source or destination must start with ':'")
+                );
+                Ok(())
+            }
+        }
+    }
+);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempdir::TempDir;
+
+    #[test]
+    fn direction() {
+        assert_eq!(
+            parse_direction("./debug.sh", ":/tmp/"),
+            Some(Direction::ToPod {
+                local: "./debug.sh",
+                remote: "/tmp/"
+            })
+        );
+        assert_eq!(
+            parse_direction(":/var/log/app", "./app-logs"),
+            Some(Direction::FromPod {
+                remote: "/var/log/app",
+                local: "./app-logs"
+            })
+        );
+        assert_eq!(parse_direction("a", "b"), None);
+        assert_eq!(parse_direction(":a", ":b"), None);
+    }
+
+    #[test]
+    fn pod_paths() {
+        assert_eq!(split_pod_path("/var/log/app"), ("/var/log", "app"));
+        assert_eq!(split_pod_path("/var/log/app/"), ("/var/log", "app"));
+        assert_eq!(split_pod_path("/app"), ("/", "app"));
+        assert_eq!(split_pod_path("app"), (".", "app"));
+    }
+
+    #[test]
+    fn copy_to_pod_target() {
+        // a trailing / copies into the directory, keeping the local name
+        assert_eq!(remote_target("/tmp/", "debug.sh"), ("/tmp/", "debug.sh"));
+        // otherwise the last component is the new name
+        assert_eq!(remote_target("/tmp/run.sh", "debug.sh"), ("/tmp", "run.sh"));
+        assert_eq!(remote_target("run.sh", "debug.sh"), (".", "run.sh"));
+    }
+
+    #[test]
+    fn copy_from_pod_target() {
+        let dir = TempDir::new("cp").unwrap();
+        assert_eq!(local_target(dir.path()), LocalTarget::Into(dir.path()));
+        let new = dir.path().join("app-logs");
+        assert_eq!(
+            local_target(&new),
+            LocalTarget::Rename { parent: dir.path() }
+        );
+        // an existing file is replaced, not copied into
+        let file = dir.path().join("file");
+        std::fs::write(&file, "").unwrap();
+        assert_eq!(
+            local_target(&file),
+            LocalTarget::Rename { parent: dir.path() }
+        );
+        assert_eq!(
+            local_target(Path::new("no-such-dir-for-click")),
+            LocalTarget::Rename {
+                parent: Path::new(".")
+            }
+        );
+    }
+
+    #[test]
+    fn missing_tar() {
+        let missing = [
+            (
+                Some(1),
+                "OCI runtime exec failed: exec: \"tar\": executable file not found in $PATH",
+            ),
+            (Some(127), "sh: tar: not found"),
+            (Some(127), "bash: tar: command not found"),
+            (Some(127), ""),
+        ];
+        for (code, stderr) in missing.iter() {
+            let err = kubectl_error(*code, stderr).to_string();
+            assert!(err.contains("tar is not available"), "{}", err);
+        }
+        let err = kubectl_error(
+            Some(2),
+            "tar: /nope: Cannot stat: No such file or directory\n",
+        );
+        assert!(err.to_string().ends_with(
+            "Copy in container failed: tar: /nope: Cannot stat: No such file or directory"
+        ));
+    }
+}
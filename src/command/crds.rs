@@ -18,7 +18,8 @@ use clap::{App, Arg};
 use rustyline::completion::Pair as RustlinePair;
 
 use crate::{
-    command::command_def::{exec_match, start_clap, Cmd},
+    command::command_def::{all_namespaces_arg, exec_match, start_clap, Cmd},
+    command::list_namespace,
     completer,
     crd::GetAPIGroupResourcesResponse,
     env::Env,
@@ -88,12 +89,14 @@ command!(
     Crd,
     "crd",
     "Get a list of resources with the specified name that have been defined by a CRD.",
-    |clap: App<'static, 'static>| clap.arg(
-        Arg::with_name("name")
-            .help("The name of the resource defined by a CRD to get")
-            .required(true)
-            .index(1)
-    ),
+    |clap: App<'static, 'static>| clap
+        .arg(
+            Arg::with_name("name")
+                .help("The name of the resource defined by a CRD to get")
+                .required(true)
+                .index(1)
+        )
+        .arg(all_namespaces_arg()),
     vec!["crd"],
     noop_complete!(),
     no_named_complete!(),
@@ -102,11 +105,12 @@ command!(
         let api_desc = find_desc_for(env, name)?;
         match api_desc {
             Some(desc) => {
-                let (request, _) = get_k8s_table(&desc.url(env.namespace.as_deref()))?;
+                let namespace = list_namespace(&matches, env);
+                let (request, _) = get_k8s_table(&desc.url(namespace))?;
                 match env.run_on_context::<_, GetTableResponse>(|c| c.read(request))? {
                     GetTableResponse::Ok(resp) => {
                         let kobjs = resp.print_to(
-                            env.namespace.is_none(),
+                            namespace.is_none(),
                             &desc.name,
                             &desc.group_version,
                             writer,
@@ -0,0 +1,198 @@
+// Copyright 2021 Databricks, Inc.
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+
+// http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use ansi_term::Colour::Yellow;
+use clap::{App, Arg, ArgMatches};
+use k8s_openapi::api::core::v1 as api;
+
+use crate::{
+    command::command_def::{exec_match, show_arg, sort_arg, start_clap, Cmd},
+    command::{get_list_request_for_url, list_namespace, run_list_command, Extractor},
+    completer,
+    cronjob::{cronjob_api_version, BatchV1, BatchV1Beta1, CronJob, CronJobApi},
+    env::Env,
+    error::ClickError,
+    kobj::{KObj, ObjType},
+    output::ClickWriter,
+    table::{time_since, CellSpec},
+};
+
+use std::array::IntoIter;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::io::Write;
+
+// the extractors are generic over the api version, so we can't build them once in a lazy_static
+fn cronjob_extractors<V: CronJobApi>() -> HashMap<String, Extractor<CronJob<V>>> {
+    let mut m: HashMap<String, Extractor<CronJob<V>>> = HashMap::new();
+    m.insert("Schedule".to_owned(), cronjob_schedule);
+    m.insert("Suspend".to_owned(), cronjob_suspend);
+    m.insert("Active".to_owned(), cronjob_active);
+    m.insert("Last Schedule".to_owned(), cronjob_last_schedule);
+    m.insert("Containers".to_owned(), cronjob_containers);
+    m.insert("Images".to_owned(), cronjob_images);
+    m
+}
+
+const COL_MAP: &[(&str, &str)] = &[
+    ("name", "Name"),
+    ("schedule", "Schedule"),
+    ("suspend", "Suspend"),
+    ("active", "Active"),
+    ("lastschedule", "Last Schedule"),
+    ("age", "Age"),
+];
+
+const COL_FLAGS: &[&str] = &{ extract_first!(COL_MAP) };
+
+const EXTRA_COL_MAP: &[(&str, &str)] = &[
+    ("namespace", "Namespace"),
+    ("containers", "Containers"),
+    ("images", "Images"),
+    ("labels", "Labels"),
+];
+
+const EXTRA_COL_FLAGS: &[&str] = &{ extract_first!(EXTRA_COL_MAP) };
+
+fn cronjob_to_kobj<V>(cronjob: &CronJob<V>) -> KObj {
+    let meta = &cronjob.metadata;
+    KObj {
+        name: meta.name.clone().unwrap_or_else(|| "<Unknown>".into()),
+        namespace: meta.namespace.clone(),
+        typ: ObjType::CronJob,
+    }
+}
+
+fn cronjob_schedule<V>(cronjob: &CronJob<V>) -> Option<CellSpec<'_>> {
+    cronjob
+        .spec
+        .as_ref()
+        .map(|spec| spec.schedule.as_str().into())
+}
+
+fn cronjob_suspend<V>(cronjob: &CronJob<V>) -> Option<CellSpec<'_>> {
+    let suspend = cronjob
+        .spec
+        .as_ref()
+        .and_then(|spec| spec.suspend)
+        .unwrap_or(false);
+    Some(suspend.to_string().into())
+}
+
+fn cronjob_active<V>(cronjob: &CronJob<V>) -> Option<CellSpec<'_>> {
+    let active = cronjob
+        .status
+        .as_ref()
+        .map(|status| status.active.len())
+        .unwrap_or(0);
+    Some(CellSpec::new_int(active as i64))
+}
+
+fn cronjob_last_schedule<V>(cronjob: &CronJob<V>) -> Option<CellSpec<'_>> {
+    match cronjob
+        .status
+        .as_ref()
+        .and_then(|status| status.last_schedule_time.as_ref())
+    {
+        Some(time) => Some(time_since(time.0).into()),
+        None => Some("<none>".into()),
+    }
+}
+
+fn template_containers<V>(cronjob: &CronJob<V>) -> &[api::Container] {
+    cronjob
+        .spec
+        .as_ref()
+        .and_then(|spec| spec.job_template.spec.as_ref())
+        .and_then(|job_spec| job_spec.template.spec.as_ref())
+        .map(|pod_spec| pod_spec.containers.as_slice())
+        .unwrap_or(&[])
+}
+
+fn cronjob_containers<V>(cronjob: &CronJob<V>) -> Option<CellSpec<'_>> {
+    let names: Vec<&str> = template_containers(cronjob)
+        .iter()
+        .map(|cont| cont.name.as_str())
+        .collect();
+    Some(names.join(", ").into())
+}
+
+fn cronjob_images<V>(cronjob: &CronJob<V>) -> Option<CellSpec<'_>> {
+    let images: Vec<&str> = template_containers(cronjob)
+        .iter()
+        .map(|cont| cont.image.as_deref().unwrap_or("<unknown>"))
+        .collect();
+    Some(images.join(", ").into())
+}
+
+fn list_cronjobs<V: CronJobApi + std::fmt::Debug>(
+    matches: ArgMatches,
+    env: &mut Env,
+    writer: &mut ClickWriter,
+) -> Result<(), ClickError> {
+    let url = match list_namespace(&matches, env) {
+        Some(ns) => format!("/apis/{}/namespaces/{}/cronjobs", V::API_VERSION, ns),
+        None => format!("/apis/{}/cronjobs", V::API_VERSION),
+    };
+    let (request, _response_body) =
+        get_list_request_for_url::<CronJob<V>>(url, Default::default())?;
+    let cols: Vec<&str> = COL_MAP.iter().map(|(_, col)| *col).collect();
+    let extractors = cronjob_extractors::<V>();
+    run_list_command(
+        matches,
+        env,
+        writer,
+        cols,
+        request,
+        COL_MAP,
+        Some(EXTRA_COL_MAP),
+        Some(&extractors),
+        cronjob_to_kobj,
+    )
+}
+
+list_command!(
+    CronJobs,
+    "cronjobs",
+    "Get cronjobs (in current namespace if set)",
+    super::COL_FLAGS,
+    super::EXTRA_COL_FLAGS,
+    |clap: App<'static, 'static>| {
+        clap.arg(
+            Arg::with_name("regex")
+                .short("r")
+                .long("regex")
+                .help("Filter cronjobs by the specified regex")
+                .takes_value(true),
+        )
+        .arg(show_arg(EXTRA_COL_FLAGS, true))
+        .arg(sort_arg(COL_FLAGS, Some(EXTRA_COL_FLAGS)))
+        .arg(
+            Arg::with_name("reverse")
+                .short("R")
+                .long("reverse")
+                .help("Reverse the order of the returned list")
+                .takes_value(false),
+        )
+    },
+    vec!["cronjobs", "cronjob", "cj"],
+    noop_complete!(),
+    no_named_complete!(),
+    |matches, env, writer| {
+        match cronjob_api_version(env)? {
+            BatchV1::API_VERSION => list_cronjobs::<BatchV1>(matches, env, writer),
+            _ => list_cronjobs::<BatchV1Beta1>(matches, env, writer),
+        }
+    }
+);
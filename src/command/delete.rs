@@ -23,7 +23,7 @@ use serde::de::DeserializeOwned;
 
 use crate::{
     command::command_def::{exec_match, start_clap, Cmd},
-    command::{uppercase_first, valid_u32},
+    command::{dry_run_arg, uppercase_first, valid_u32, DryRun},
     completer,
     env::Env,
     error::ClickError,
@@ -41,15 +41,16 @@ fn send_delete<D: DeserializeOwned + Debug>(
     env: &Env,
     writer: &mut ClickWriter,
     request: Request<Vec<u8>>,
+    dry_run: DryRun,
 ) -> Result<(), ClickError> {
     let r = env.run_on_context::<_, DeleteResponse<D>>(|c| c.read(request))?;
     match r {
         DeleteResponse::OkStatus(_) | DeleteResponse::OkValue(_) => {
-            clickwriteln!(writer, "Deleted");
+            clickwriteln!(writer, "Deleted{}", dry_run.suffix());
             Ok(())
         }
         DeleteResponse::Accepted(_) => {
-            clickwriteln!(writer, "Delete request accepted");
+            clickwriteln!(writer, "Delete request accepted{}", dry_run.suffix());
             Ok(())
         }
         DeleteResponse::Other(res) => match res {
@@ -78,7 +79,12 @@ fn delete_obj(
     writer: &mut ClickWriter,
     obj: &KObj,
     options: DeleteOptional,
+    dry_run: DryRun,
 ) -> Result<(), ClickError> {
+    if dry_run == DryRun::Client {
+        clickwriteln!(writer, "Deleted{}", dry_run.suffix());
+        return Ok(());
+    }
     match obj.namespace.as_ref() {
         Some(ns) => match obj.typ {
             ObjType::ConfigMap => {
@@ -88,7 +94,7 @@ fn delete_obj(
                     options,
                 )?
                 .0;
-                send_delete::<api::ConfigMap>(env, writer, req)
+                send_delete::<api::ConfigMap>(env, writer, req, dry_run)
             }
             ObjType::Deployment => {
                 let req = api_apps::Deployment::delete_namespaced_deployment(
@@ -97,13 +103,23 @@ fn delete_obj(
                     options,
                 )?
                 .0;
-                send_delete::<api_apps::Deployment>(env, writer, req)
+                send_delete::<api_apps::Deployment>(env, writer, req, dry_run)
             }
             ObjType::Job => {
                 let req =
                     api_batch::Job::delete_namespaced_job(obj.name.as_str(), ns.as_str(), options)?
                         .0;
-                send_delete::<api_batch::Job>(env, writer, req)
+                send_delete::<api_batch::Job>(env, writer, req, dry_run)
+            }
+            ObjType::CronJob => {
+                let group_version = crate::cronjob::cronjob_api_version(env)?;
+                let req = crate::cronjob::delete_namespaced_cronjob(
+                    group_version,
+                    obj.name.as_str(),
+                    ns.as_str(),
+                    options,
+                )?;
+                send_delete::<serde_json::Value>(env, writer, req, dry_run)
             }
             ObjType::Namespace => {
                 clickwriteln!(
@@ -112,7 +128,7 @@ fn delete_obj(
                      Deleting anyway"
                 );
                 let req = api::Namespace::delete_namespace(obj.name.as_str(), options)?.0;
-                send_delete::<api::Namespace>(env, writer, req)
+                send_delete::<api::Namespace>(env, writer, req, dry_run)
             }
             ObjType::Node => {
                 clickwriteln!(
@@ -121,7 +137,7 @@ fn delete_obj(
                          Deleting anyway"
                 );
                 let req = api::Node::delete_node(obj.name.as_str(), options)?.0;
-                send_delete::<api::Node>(env, writer, req)
+                send_delete::<api::Node>(env, writer, req, dry_run)
             }
             ObjType::PersistentVolume => {
                 clickwriteln!(
@@ -131,12 +147,31 @@ fn delete_obj(
                 );
                 let req =
                     api::PersistentVolume::delete_persistent_volume(obj.name.as_str(), options)?.0;
-                send_delete::<api::PersistentVolume>(env, writer, req)
+                send_delete::<api::PersistentVolume>(env, writer, req, dry_run)
+            }
+            ObjType::PersistentVolumeClaim => {
+                let req = api::PersistentVolumeClaim::delete_namespaced_persistent_volume_claim(
+                    obj.name.as_str(),
+                    ns.as_str(),
+                    options,
+                )?
+                .0;
+                send_delete::<api::PersistentVolumeClaim>(env, writer, req, dry_run)
             }
             ObjType::Pod { .. } => {
                 let req =
                     api::Pod::delete_namespaced_pod(obj.name.as_str(), ns.as_str(), options)?.0;
-                send_delete::<api::Pod>(env, writer, req)
+                send_delete::<api::Pod>(env, writer, req, dry_run)
+            }
+            ObjType::Ingress => {
+                let group_version = crate::ingress::ingress_api_version(env)?;
+                let req = crate::ingress::delete_namespaced_ingress(
+                    group_version,
+                    obj.name.as_str(),
+                    ns.as_str(),
+                    options,
+                )?;
+                send_delete::<serde_json::Value>(env, writer, req, dry_run)
             }
             ObjType::Crd { .. } => Err(ClickError::CommandError(
                 "Can't delete CRDs yet".to_string(),
@@ -148,7 +183,7 @@ fn delete_obj(
                     options,
                 )?
                 .0;
-                send_delete::<api_apps::ReplicaSet>(env, writer, req)
+                send_delete::<api_apps::ReplicaSet>(env, writer, req, dry_run)
             }
             ObjType::StatefulSet => {
                 let req = api_apps::StatefulSet::delete_namespaced_stateful_set(
@@ -157,13 +192,22 @@ fn delete_obj(
                     options,
                 )?
                 .0;
-                send_delete::<api_apps::StatefulSet>(env, writer, req)
+                send_delete::<api_apps::StatefulSet>(env, writer, req, dry_run)
+            }
+            ObjType::DaemonSet => {
+                let req = api_apps::DaemonSet::delete_namespaced_daemon_set(
+                    obj.name.as_str(),
+                    ns.as_str(),
+                    options,
+                )?
+                .0;
+                send_delete::<api_apps::DaemonSet>(env, writer, req, dry_run)
             }
             ObjType::Secret => {
                 let req =
                     api::Secret::delete_namespaced_secret(obj.name.as_str(), ns.as_str(), options)?
                         .0;
-                send_delete::<api::Secret>(env, writer, req)
+                send_delete::<api::Secret>(env, writer, req, dry_run)
             }
             ObjType::Service => {
                 let req = api::Service::delete_namespaced_service(
@@ -172,7 +216,7 @@ fn delete_obj(
                     options,
                 )?
                 .0;
-                send_delete::<api::Service>(env, writer, req)
+                send_delete::<api::Service>(env, writer, req, dry_run)
             }
             ObjType::StorageClass => {
                 clickwriteln!(
@@ -182,7 +226,7 @@ fn delete_obj(
                 );
                 let req =
                     api_storage::StorageClass::delete_storage_class(obj.name.as_str(), options)?.0;
-                send_delete::<api_storage::StorageClass>(env, writer, req)
+                send_delete::<api_storage::StorageClass>(env, writer, req, dry_run)
             }
             #[cfg(feature = "argorollouts")]
             ObjType::Rollout => Err(ClickError::CommandError(
@@ -192,21 +236,21 @@ fn delete_obj(
         None => match obj.typ {
             ObjType::Node => {
                 let req = api::Node::delete_node(obj.name.as_str(), options)?.0;
-                send_delete::<api::Node>(env, writer, req)
+                send_delete::<api::Node>(env, writer, req, dry_run)
             }
             ObjType::Namespace => {
                 let req = api::Namespace::delete_namespace(obj.name.as_str(), options)?.0;
-                send_delete::<api::Namespace>(env, writer, req)
+                send_delete::<api::Namespace>(env, writer, req, dry_run)
             }
             ObjType::PersistentVolume => {
                 let req =
                     api::PersistentVolume::delete_persistent_volume(obj.name.as_str(), options)?.0;
-                send_delete::<api::PersistentVolume>(env, writer, req)
+                send_delete::<api::PersistentVolume>(env, writer, req, dry_run)
             }
             ObjType::StorageClass => {
                 let req =
                     api_storage::StorageClass::delete_storage_class(obj.name.as_str(), options)?.0;
-                send_delete::<api_storage::StorageClass>(env, writer, req)
+                send_delete::<api_storage::StorageClass>(env, writer, req, dry_run)
             }
             _ => {
                 let msg = format!("Object {} has no namespace. Cannot delete", obj.name());
@@ -216,37 +260,49 @@ fn delete_obj(
     }
 }
 
+/// Ask before deleting obj. Returns true if it was deleted
 fn confirm_delete(
     env: &Env,
     obj: &KObj,
     options: DeleteOptional,
     writer: &mut ClickWriter,
-) -> Result<(), ClickError> {
-    let name = obj.name();
-    clickwrite!(writer, "Delete {} {} [y/N]? ", obj.type_str(), name);
-    io::stdout().flush().expect("Could not flush stdout");
+) -> Result<bool, ClickError> {
+    let kind = obj.type_str().to_lowercase();
+    match obj.namespace.as_ref() {
+        Some(ns) => clickwrite!(
+            writer,
+            "Really delete {}/{} in {}? [y/N] ",
+            kind,
+            obj.name(),
+            ns
+        ),
+        None => clickwrite!(writer, "Really delete {}/{}? [y/N] ", kind, obj.name()),
+    }
+    writer.flush().unwrap_or(());
     let mut conf = String::new();
     if io::stdin().read_line(&mut conf).is_ok() {
         if conf.trim() == "y" || conf.trim() == "yes" {
-            delete_obj(env, writer, obj, options)?;
+            delete_obj(env, writer, obj, options, DryRun::Off)?;
+            return Ok(true);
         } else {
             clickwriteln!(writer, "Not deleting");
         }
     } else {
         writeln!(stderr(), "Could not read response, not deleting.").unwrap_or(());
     }
-    Ok(())
+    Ok(false)
 }
 
 command!(
     Delete,
     "delete",
-    "Delete the active object (will ask for confirmation)",
+    "Delete the active object (will ask for confirmation, unless it's a dry run)",
     |clap: App<'static, 'static>| {
         clap.arg(
             Arg::with_name("grace")
                 .short("g")
                 .long("gracePeriod")
+                .visible_alias("grace-period")
                 .help("The duration in seconds before the object should be deleted.")
                 .validator(valid_u32)
                 .takes_value(true),
@@ -282,6 +338,7 @@ command!(
                 .conflicts_with("grace")
                 .conflicts_with("now"),
         )
+        .arg(dry_run_arg())
     },
     vec!["delete"],
     noop_complete!(),
@@ -303,16 +360,32 @@ command!(
             uppercase_first(lower.as_str())
         });
 
+        let dry_run = DryRun::from_matches(&matches);
+        let dry_run_param: Option<Vec<String>> = dry_run.param().map(|p| vec![p.to_string()]);
         let delete_options: DeleteOptional = DeleteOptional {
+            dry_run: dry_run_param.as_deref(),
             propagation_policy: propagation_policy.as_deref(),
             grace_period_seconds: grace,
             ..Default::default()
         };
 
-        env.apply_to_selection(
+        let mut deleted = vec![];
+        let res = env.apply_to_selection(
             writer,
             Some(&env.click_config.range_separator),
-            |obj, writer| confirm_delete(env, obj, delete_options, writer),
-        )
+            |obj, writer| {
+                if dry_run != DryRun::Off {
+                    // nothing will really be deleted, so there's no need to ask
+                    clickwrite!(writer, "{}/{}: ", obj.type_str().to_lowercase(), obj.name());
+                    delete_obj(env, writer, obj, delete_options, dry_run)?;
+                } else if confirm_delete(env, obj, delete_options, writer)? {
+                    deleted.push(obj.clone());
+                }
+                Ok(())
+            },
+        );
+        env.invalidate_cache_for_selection();
+        env.remove_from_selection(&deleted);
+        res
     }
 );
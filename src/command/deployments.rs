@@ -12,24 +12,41 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use ansi_term::Colour::Yellow;
-use clap::{App, Arg};
+use ansi_term::Colour::{Green, Yellow};
+use chrono::{Local, SecondsFormat};
+use clap::{App, AppSettings, Arg, SubCommand};
 use k8s_openapi::api::apps::v1 as apps_api;
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::Patch;
+use k8s_openapi::{http::Request, List, ListOptional, PatchOptional};
+use serde::de::DeserializeOwned;
+use serde_json::{json, Value};
 
 use crate::{
     command::command_def::{exec_match, show_arg, sort_arg, start_clap, Cmd},
-    command::{run_list_command, Extractor},
+    command::{list_namespace, poll_until_interrupted, run_list_command, valid_u32, Extractor},
     completer,
     env::Env,
+    error::ClickError,
     kobj::{KObj, ObjType},
     output::ClickWriter,
     table::CellSpec,
+    values::val_str,
 };
 
 use std::array::IntoIter;
 use std::cell::RefCell;
 use std::collections::HashMap;
 use std::io::Write;
+use std::time::Duration;
+
+/// The annotation deployments and their replicasets record the rollout revision in
+pub const REVISION_ANNOTATION: &str = "deployment.kubernetes.io/revision";
+
+/// The pod template annotation kubectl sets to trigger a rolling restart
+const RESTARTED_AT_ANNOTATION: &str = "kubectl.kubernetes.io/restartedAt";
+
+/// How often rollout status checks on the deployment
+const ROLLOUT_POLL_SECS: u64 = 2;
 
 lazy_static! {
     static ref DEPLOYMENT_EXTRACTORS: HashMap<String, Extractor<apps_api::Deployment>> = {
@@ -168,7 +185,7 @@ list_command!(
     noop_complete!(),
     IntoIter::new([]),
     |matches, env, writer| {
-        let (request, _response_body) = match &env.namespace {
+        let (request, _response_body) = match list_namespace(&matches, env) {
             Some(ns) => apps_api::Deployment::list_namespaced_deployment(ns, Default::default())?,
             None => apps_api::Deployment::list_deployment_for_all_namespaces(Default::default())?,
         };
@@ -187,3 +204,671 @@ list_command!(
         )
     }
 );
+
+fn read_deployment(env: &Env, name: &str, ns: &str) -> Result<apps_api::Deployment, ClickError> {
+    let (request, _) =
+        apps_api::Deployment::read_namespaced_deployment(name, ns, Default::default())?;
+    match env.run_on_context(|c| c.read(request))? {
+        apps_api::ReadNamespacedDeploymentResponse::Ok(deployment) => Ok(deployment),
+        _ => Err(ClickError::CommandError(format!(
+            "Could not read deployment {}",
+            name
+        ))),
+    }
+}
+
+fn revision(meta: &k8s_openapi::apimachinery::pkg::apis::meta::v1::ObjectMeta) -> Option<i64> {
+    meta.annotations
+        .get(REVISION_ANNOTATION)
+        .and_then(|rev| rev.parse().ok())
+}
+
+/// Work out how far along the rollout of deployment is, the way kubectl does. Returns a message
+/// describing the state, and whether the rollout is done
+fn rollout_state(deployment: &apps_api::Deployment) -> Result<(String, bool), ClickError> {
+    let name = deployment.metadata.name.as_deref().unwrap_or("<unknown>");
+    let status = match deployment.status.as_ref() {
+        Some(status) => status,
+        None => return Ok(("Waiting for deployment status".to_string(), false)),
+    };
+    if deployment.metadata.generation > status.observed_generation {
+        return Ok((
+            "Waiting for deployment spec update to be observed...".to_string(),
+            false,
+        ));
+    }
+    let progress_deadline_exceeded = status.conditions.iter().any(|cond| {
+        cond.type_ == "Progressing" && cond.reason.as_deref() == Some("ProgressDeadlineExceeded")
+    });
+    if progress_deadline_exceeded {
+        return Err(ClickError::CommandError(format!(
+            "deployment {} exceeded its progress deadline",
+            name
+        )));
+    }
+    let desired = deployment
+        .spec
+        .as_ref()
+        .and_then(|spec| spec.replicas)
+        .unwrap_or(1);
+    let updated = status.updated_replicas.unwrap_or(0);
+    let replicas = status.replicas.unwrap_or(0);
+    let available = status.available_replicas.unwrap_or(0);
+    let state = if updated < desired {
+        (
+            format!(
+                "Waiting for deployment {} rollout to finish: {} out of {} new replicas have \
+                 been updated...",
+                name, updated, desired
+            ),
+            false,
+        )
+    } else if replicas > updated {
+        (
+            format!(
+                "Waiting for deployment {} rollout to finish: {} old replicas are pending \
+                 termination...",
+                name,
+                replicas - updated
+            ),
+            false,
+        )
+    } else if available < updated {
+        (
+            format!(
+                "Waiting for deployment {} rollout to finish: {} of {} updated replicas are \
+                 available...",
+                name, available, updated
+            ),
+            false,
+        )
+    } else {
+        (format!("deployment {} successfully rolled out", name), true)
+    };
+    Ok(state)
+}
+
+/// Work out how far along the rollout of a statefulset is, the way kubectl does
+fn statefulset_rollout_state(
+    statefulset: &apps_api::StatefulSet,
+) -> Result<(String, bool), ClickError> {
+    let name = statefulset.metadata.name.as_deref().unwrap_or("<unknown>");
+    let spec = statefulset
+        .spec
+        .as_ref()
+        .ok_or_else(|| ClickError::CommandError(format!("statefulset {} has no spec", name)))?;
+    let strategy = spec.update_strategy.as_ref();
+    if strategy.and_then(|s| s.type_.as_deref()) == Some("OnDelete") {
+        return Err(ClickError::CommandError(format!(
+            "statefulset {} uses the OnDelete update strategy, its pods are only replaced when \
+             they're deleted",
+            name
+        )));
+    }
+    let status = match statefulset.status.as_ref() {
+        Some(status) => status,
+        None => return Ok(("Waiting for statefulset status".to_string(), false)),
+    };
+    if status.observed_generation.is_none()
+        || statefulset.metadata.generation > status.observed_generation
+    {
+        return Ok((
+            "Waiting for statefulset spec update to be observed...".to_string(),
+            false,
+        ));
+    }
+    let desired = spec.replicas.unwrap_or(1);
+    // pods below the partition keep the old template
+    let partition = strategy
+        .and_then(|s| s.rolling_update.as_ref())
+        .and_then(|r| r.partition)
+        .unwrap_or(0);
+    let to_update = (desired - partition).max(0);
+    let updated = status.updated_replicas.unwrap_or(0);
+    let ready = status.ready_replicas.unwrap_or(0);
+    let state = if updated < to_update {
+        (
+            format!(
+                "Waiting for statefulset {} rollout to finish: {} out of {} new pods have been \
+                 updated...",
+                name, updated, to_update
+            ),
+            false,
+        )
+    } else if ready < desired {
+        (
+            format!(
+                "Waiting for statefulset {} rollout to finish: {} of {} pods are ready...",
+                name, ready, desired
+            ),
+            false,
+        )
+    } else {
+        (
+            format!("statefulset {} successfully rolled out", name),
+            true,
+        )
+    };
+    Ok(state)
+}
+
+/// Work out how far along the rollout of a daemonset is, the way kubectl does
+fn daemonset_rollout_state(daemonset: &apps_api::DaemonSet) -> Result<(String, bool), ClickError> {
+    let name = daemonset.metadata.name.as_deref().unwrap_or("<unknown>");
+    let strategy = daemonset
+        .spec
+        .as_ref()
+        .and_then(|spec| spec.update_strategy.as_ref())
+        .and_then(|s| s.type_.as_deref());
+    if strategy == Some("OnDelete") {
+        return Err(ClickError::CommandError(format!(
+            "daemonset {} uses the OnDelete update strategy, its pods are only replaced when \
+             they're deleted",
+            name
+        )));
+    }
+    let status = match daemonset.status.as_ref() {
+        Some(status) => status,
+        None => return Ok(("Waiting for daemonset status".to_string(), false)),
+    };
+    if status.observed_generation.is_none()
+        || daemonset.metadata.generation > status.observed_generation
+    {
+        return Ok((
+            "Waiting for daemonset spec update to be observed...".to_string(),
+            false,
+        ));
+    }
+    let desired = status.desired_number_scheduled;
+    let updated = status.updated_number_scheduled.unwrap_or(0);
+    let available = status.number_available.unwrap_or(0);
+    let state = if updated < desired {
+        (
+            format!(
+                "Waiting for daemonset {} rollout to finish: {} out of {} new pods have been \
+                 updated...",
+                name, updated, desired
+            ),
+            false,
+        )
+    } else if available < desired {
+        (
+            format!(
+                "Waiting for daemonset {} rollout to finish: {} of {} updated pods are \
+                 available...",
+                name, available, desired
+            ),
+            false,
+        )
+    } else {
+        (format!("daemonset {} successfully rolled out", name), true)
+    };
+    Ok(state)
+}
+
+/// Poll the deployment until its rollout is done, or ctrl-c is pressed
+fn rollout_status(
+    env: &Env,
+    name: &str,
+    ns: &str,
+    writer: &mut ClickWriter,
+) -> Result<(), ClickError> {
+    watch_rollout(env, writer, || {
+        rollout_state(&read_deployment(env, name, ns)?)
+    })
+}
+
+/// Print the state of a rollout each time it changes, until it's done or ctrl-c is pressed. state
+/// reads the object and works out the state, like rollout_state
+fn watch_rollout<F>(env: &Env, writer: &mut ClickWriter, mut state: F) -> Result<(), ClickError>
+where
+    F: FnMut() -> Result<(String, bool), ClickError>,
+{
+    let mut last_msg = String::new();
+    let interval = Duration::from_secs(ROLLOUT_POLL_SECS);
+    poll_until_interrupted(&env.ctrlcbool, interval, || {
+        let (msg, done) = state()?;
+        if msg != last_msg {
+            if done {
+                clickwriteln!(writer, "{}", Green.paint(msg.as_str()));
+            } else {
+                clickwriteln!(writer, "{}", msg);
+            }
+            writer.flush().unwrap_or(());
+        }
+        last_msg = msg;
+        Ok(!done)
+    })
+}
+
+/// Read obj as a T, for kinds there's no read_ function for here
+fn read_obj<T: DeserializeOwned>(env: &Env, obj: &KObj) -> Result<T, ClickError> {
+    let request = Request::get(obj.api_path(env)?).body(vec![]).unwrap(); // safe, valid path
+    let response = env.run_on_context(|c| c.execute(request))?;
+    Ok(serde_json::from_slice(response.body())?)
+}
+
+fn send_patch(env: &Env, request: Request<Vec<u8>>, action: &str) -> Result<(), ClickError> {
+    let response = env.run_on_context(|c| c.execute(request))?;
+    if response.status().is_success() {
+        Ok(())
+    } else {
+        let message = serde_json::from_slice::<Value>(response.body())
+            .map(|val| val_str("/message", &val, "<No message>").into_owned())
+            .unwrap_or_else(|_| "<No message>".to_string());
+        Err(ClickError::CommandError(format!(
+            "{} failed ({}). Message: {}",
+            action,
+            response.status(),
+            message
+        )))
+    }
+}
+
+/// Roll the deployment back to the pod template of one of its replicasets. With no revision this
+/// is the revision before the current one
+fn rollout_undo(
+    env: &Env,
+    name: &str,
+    ns: &str,
+    to_revision: Option<i64>,
+    writer: &mut ClickWriter,
+) -> Result<(), ClickError> {
+    let deployment = read_deployment(env, name, ns)?;
+    let spec = deployment
+        .spec
+        .as_ref()
+        .ok_or_else(|| ClickError::CommandError(format!("deployment {} has no spec", name)))?;
+    if spec.paused == Some(true) {
+        return Err(ClickError::CommandError(format!(
+            "deployment {} is paused, resume it before rolling back",
+            name
+        )));
+    }
+
+    let selector = spec
+        .selector
+        .match_labels
+        .iter()
+        .map(|(k, v)| format!("{}={}", k, v))
+        .collect::<Vec<String>>()
+        .join(",");
+    let opts = ListOptional {
+        label_selector: Some(&selector),
+        ..Default::default()
+    };
+    let (request, _) = apps_api::ReplicaSet::list_namespaced_replica_set(ns, opts)?;
+    let replicasets: List<apps_api::ReplicaSet> =
+        env.run_on_context(|c| c.execute_list(request))?;
+    // only look at replicasets this deployment owns, others could match the selector
+    let mut history: Vec<(i64, &apps_api::ReplicaSet)> = replicasets
+        .items
+        .iter()
+        .filter(|rs| {
+            rs.metadata
+                .owner_references
+                .iter()
+                .any(|owner| Some(&owner.uid) == deployment.metadata.uid.as_ref())
+        })
+        .filter_map(|rs| revision(&rs.metadata).map(|rev| (rev, rs)))
+        .collect();
+    history.sort_by_key(|(rev, _)| *rev);
+
+    let current = revision(&deployment.metadata).unwrap_or(0);
+    let target = match to_revision {
+        Some(rev) => history.iter().find(|(r, _)| *r == rev).ok_or_else(|| {
+            ClickError::CommandError(format!(
+                "Unable to find revision {} in the history of deployment {}",
+                rev, name
+            ))
+        })?,
+        None => history
+            .iter()
+            .rev()
+            .find(|(r, _)| *r < current)
+            .ok_or_else(|| {
+                ClickError::CommandError(format!(
+                    "No previous revision of deployment {} to roll back to",
+                    name
+                ))
+            })?,
+    };
+    let (target_rev, target_rs) = target;
+    if *target_rev == current {
+        clickwriteln!(
+            writer,
+            "deployment {} is already at revision {}, skipping rollback",
+            name,
+            current
+        );
+        return Ok(());
+    }
+
+    let mut template = match target_rs
+        .spec
+        .as_ref()
+        .and_then(|spec| spec.template.as_ref())
+    {
+        Some(template) => serde_json::to_value(template)?,
+        None => {
+            return Err(ClickError::CommandError(format!(
+                "Revision {} of deployment {} has no pod template",
+                target_rev, name
+            )))
+        }
+    };
+    // the replicaset's template has the hash label added, the deployment's doesn't
+    if let Some(labels) = template
+        .pointer_mut("/metadata/labels")
+        .and_then(|labels| labels.as_object_mut())
+    {
+        labels.remove("pod-template-hash");
+    }
+    let patch = Patch::Json(vec![json!({
+        "op": "replace",
+        "path": "/spec/template",
+        "value": template,
+    })]);
+    let (request, _) = apps_api::Deployment::patch_namespaced_deployment(
+        name,
+        ns,
+        &patch,
+        PatchOptional::default(),
+    )?;
+    send_patch(env, request, "Rollback")?;
+    clickwriteln!(
+        writer,
+        "Rolled back deployment {} to revision {}",
+        name,
+        target_rev
+    );
+    Ok(())
+}
+
+command!(
+    Rollout,
+    "rollout",
+    "Check on or roll back the rollout of the active deployment",
+    |clap: App<'static, 'static>| clap
+        .setting(AppSettings::SubcommandRequiredElseHelp)
+        .subcommand(
+            SubCommand::with_name("status")
+                .about("Wait for the rollout of the deployment to finish, printing progress")
+        )
+        .subcommand(
+            SubCommand::with_name("undo")
+                .about("Roll the deployment back to the previous (or a specified) revision")
+                .arg(
+                    Arg::with_name("to_revision")
+                        .long("to-revision")
+                        .value_name("REVISION")
+                        .help("The revision to roll back to")
+                        .validator(valid_u32)
+                        .takes_value(true)
+                )
+        ),
+    vec!["rollout"],
+    noop_complete!(),
+    no_named_complete!(),
+    |matches, env, writer| {
+        let res = env.apply_to_selection(
+            writer,
+            Some(&env.click_config.range_separator),
+            |obj, writer| {
+                if !obj.is(ObjType::Deployment) {
+                    return Err(ClickError::CommandError(format!(
+                        "{} is a {}, rollout only works on deployments",
+                        obj.name(),
+                        obj.type_str()
+                    )));
+                }
+                let ns = obj.namespace.as_deref().ok_or_else(|| {
+                    ClickError::CommandError(format!("{} has no namespace", obj.name()))
+                })?;
+                match matches.subcommand() {
+                    ("undo", Some(sub)) => {
+                        let to_revision = sub
+                            .value_of("to_revision")
+                            .map(|rev| rev.parse::<i64>().unwrap()); // safe, validated
+                        rollout_undo(env, obj.name(), ns, to_revision, writer)
+                    }
+                    _ => rollout_status(env, obj.name(), ns, writer),
+                }
+            },
+        );
+        if matches.subcommand_name() == Some("undo") {
+            env.invalidate_cache_for_selection();
+        }
+        res
+    }
+);
+
+/// Trigger a rolling restart of a deployment, statefulset or daemonset the way kubectl does, by
+/// setting an annotation on the pod template so that it changes
+fn restart_obj(
+    env: &Env,
+    obj: &KObj,
+    watch: bool,
+    writer: &mut ClickWriter,
+) -> Result<(), ClickError> {
+    let name = obj.name();
+    let ns = obj
+        .namespace
+        .as_deref()
+        .ok_or_else(|| ClickError::CommandError(format!("{} has no namespace", name)))?;
+    let restarted_at = Local::now().to_rfc3339_opts(SecondsFormat::Secs, false);
+    let patch = Patch::StrategicMerge(json!({
+        "spec": { "template": { "metadata": { "annotations": {
+            RESTARTED_AT_ANNOTATION: restarted_at
+        }}}}
+    }));
+    let opts = PatchOptional::default();
+    let request = match obj.typ {
+        ObjType::Deployment => {
+            let deployment = read_deployment(env, name, ns)?;
+            if deployment.spec.and_then(|spec| spec.paused) == Some(true) {
+                return Err(ClickError::CommandError(format!(
+                    "deployment {} is paused, resume it before restarting",
+                    name
+                )));
+            }
+            apps_api::Deployment::patch_namespaced_deployment(name, ns, &patch, opts)?.0
+        }
+        ObjType::StatefulSet => {
+            apps_api::StatefulSet::patch_namespaced_stateful_set(name, ns, &patch, opts)?.0
+        }
+        ObjType::DaemonSet => {
+            apps_api::DaemonSet::patch_namespaced_daemon_set(name, ns, &patch, opts)?.0
+        }
+        _ => {
+            return Err(ClickError::CommandError(format!(
+                "Can't restart {} {}, only deployments, statefulsets and daemonsets can be \
+                 restarted",
+                obj.type_str(),
+                name
+            )))
+        }
+    };
+    send_patch(env, request, "Restart")?;
+    clickwriteln!(
+        writer,
+        "Restarted {} {} ({}: {})",
+        obj.type_str(),
+        name,
+        RESTARTED_AT_ANNOTATION,
+        restarted_at
+    );
+    if watch {
+        match obj.typ {
+            ObjType::Deployment => rollout_status(env, name, ns, writer)?,
+            ObjType::StatefulSet => watch_rollout(env, writer, || {
+                statefulset_rollout_state(&read_obj(env, obj)?)
+            })?,
+            ObjType::DaemonSet => watch_rollout(env, writer, || {
+                daemonset_rollout_state(&read_obj(env, obj)?)
+            })?,
+            // nothing else can be restarted, see above
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+command!(
+    Restart,
+    "restart",
+    "Trigger a rolling restart of the active deployment, statefulset or daemonset",
+    |clap: App<'static, 'static>| clap
+        .arg(
+            Arg::with_name("watch")
+                .short("w")
+                .long("watch")
+                .help("After restarting, wait for the rollout to finish (like 'rollout status')")
+                .takes_value(false)
+        )
+        .after_help(
+            "All pods are replaced, following the update strategy of the object. This works by \
+             setting the kubectl.kubernetes.io/restartedAt annotation on the pod template, like \
+             'kubectl rollout restart' does."
+        ),
+    vec!["restart"],
+    noop_complete!(),
+    no_named_complete!(),
+    |matches, env, writer| {
+        let watch = matches.is_present("watch");
+        let res = env.apply_to_selection(
+            writer,
+            Some(&env.click_config.range_separator),
+            |obj, writer| restart_obj(env, obj, watch, writer),
+        );
+        env.invalidate_cache_for_selection();
+        res
+    }
+);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn statefulset(spec: Value, status: Value) -> apps_api::StatefulSet {
+        serde_json::from_value(json!({
+            "apiVersion": "apps/v1",
+            "kind": "StatefulSet",
+            "metadata": { "name": "db", "generation": 2 },
+            "spec": spec,
+            "status": status,
+        }))
+        .unwrap()
+    }
+
+    fn daemonset(spec: Value, status: Value) -> apps_api::DaemonSet {
+        serde_json::from_value(json!({
+            "apiVersion": "apps/v1",
+            "kind": "DaemonSet",
+            "metadata": { "name": "agent", "generation": 2 },
+            "spec": spec,
+            "status": status,
+        }))
+        .unwrap()
+    }
+
+    fn sts_spec(partition: i32) -> Value {
+        json!({
+            "replicas": 3,
+            "selector": {},
+            "serviceName": "db",
+            "template": {},
+            "updateStrategy": {
+                "type": "RollingUpdate",
+                "rollingUpdate": { "partition": partition },
+            },
+        })
+    }
+
+    #[test]
+    fn statefulset_rollout() {
+        let state = |spec, status| statefulset_rollout_state(&statefulset(spec, status)).unwrap();
+        let (msg, done) = state(
+            sts_spec(0),
+            json!({ "observedGeneration": 1, "replicas": 3, "readyReplicas": 3 }),
+        );
+        assert!(!done);
+        assert!(msg.contains("spec update to be observed"));
+        let (msg, done) = state(
+            sts_spec(0),
+            json!({
+                "observedGeneration": 2, "replicas": 3, "updatedReplicas": 1, "readyReplicas": 3
+            }),
+        );
+        assert!(!done);
+        assert!(msg.contains("1 out of 3 new pods"));
+        let (msg, done) = state(
+            sts_spec(0),
+            json!({
+                "observedGeneration": 2, "replicas": 3, "updatedReplicas": 3, "readyReplicas": 2
+            }),
+        );
+        assert!(!done);
+        assert!(msg.contains("2 of 3 pods are ready"));
+        let (_, done) = state(
+            sts_spec(0),
+            json!({
+                "observedGeneration": 2, "replicas": 3, "updatedReplicas": 3, "readyReplicas": 3
+            }),
+        );
+        assert!(done);
+        // pods below the partition aren't updated
+        let (_, done) = state(
+            sts_spec(2),
+            json!({
+                "observedGeneration": 2, "replicas": 3, "updatedReplicas": 1, "readyReplicas": 3
+            }),
+        );
+        assert!(done);
+
+        let on_delete = json!({
+            "selector": {},
+            "serviceName": "db",
+            "template": {},
+            "updateStrategy": { "type": "OnDelete" },
+        });
+        assert!(
+            statefulset_rollout_state(&statefulset(on_delete, json!({ "replicas": 3 }))).is_err()
+        );
+    }
+
+    #[test]
+    fn daemonset_rollout() {
+        let spec = json!({ "selector": {}, "template": {} });
+        let state = |status| daemonset_rollout_state(&daemonset(spec.clone(), status)).unwrap();
+        let status = |observed: i64, updated: i32, available: i32| {
+            json!({
+                "observedGeneration": observed,
+                "desiredNumberScheduled": 4,
+                "currentNumberScheduled": 4,
+                "numberMisscheduled": 0,
+                "numberReady": available,
+                "updatedNumberScheduled": updated,
+                "numberAvailable": available,
+            })
+        };
+        let (msg, done) = state(status(1, 4, 4));
+        assert!(!done);
+        assert!(msg.contains("spec update to be observed"));
+        let (msg, done) = state(status(2, 1, 4));
+        assert!(!done);
+        assert!(msg.contains("1 out of 4 new pods"));
+        let (msg, done) = state(status(2, 4, 3));
+        assert!(!done);
+        assert!(msg.contains("3 of 4 updated pods are available"));
+        let (_, done) = state(status(2, 4, 4));
+        assert!(done);
+
+        let on_delete = json!({
+            "selector": {},
+            "template": {},
+            "updateStrategy": { "type": "OnDelete" },
+        });
+        assert!(daemonset_rollout_state(&daemonset(on_delete, status(2, 4, 4))).is_err());
+    }
+}
@@ -12,12 +12,12 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use ansi_term::Colour::Yellow;
+use ansi_term::{Colour::Yellow, Style};
 use clap::{App, Arg};
 use rustyline::completion::Pair as RustlinePair;
 
 use crate::{
-    command::command_def::{exec_match, start_clap, Cmd},
+    command::command_def::{exec_match, output_arg, start_clap, Cmd},
     completer,
     env::Env,
     output::ClickWriter,
@@ -59,6 +59,34 @@ command!(
                 .takes_value(true)
                 .possible_values(&["true", "false"]),
         )
+        .arg(output_arg())
+        .arg(
+            Arg::with_name("show")
+                .long("show")
+                .help(
+                    "Show the values of configmaps, cut off at the end of their first line or \
+                     60 characters. Secret values stay hidden, use 'decode --show' to see them",
+                )
+                .takes_value(false),
+        )
+        .arg(
+            Arg::with_name("refresh")
+                .long("refresh")
+                .help(
+                    "Always fetch from the server, even if the object was described recently \
+                     enough to have its description cached (see 'set cache_ttl_secs')",
+                )
+                .takes_value(false),
+        )
+        .arg(
+            Arg::with_name("show_managed_fields")
+                .long("show-managed-fields")
+                .help(
+                    "Include metadata.managedFields, which are hidden by default. With -j/-y/-o \
+                     they are included in the output, otherwise they are summarized by manager",
+                )
+                .takes_value(false),
+        )
     },
     vec!["describe"],
     noop_complete!(),
@@ -68,17 +96,55 @@ command!(
         if let Some(b) = matches.value_of("include_events") {
             include_events = b.parse().unwrap(); // safe, validated to be true/false
         }
+        let full_output = matches.is_present("json")
+            || matches.is_present("yaml")
+            || matches.is_present("output");
+        // everything that changes what describe prints, so a cached description is only reused
+        // for the same options
+        let options = format!(
+            "{} {} {:?} {} {} {} {} {}",
+            matches.is_present("json"),
+            matches.is_present("yaml"),
+            matches.value_of("output"),
+            include_events,
+            matches.is_present("show"),
+            matches.is_present("show_managed_fields"),
+            writer.is_stdout(),
+            writer.use_color(),
+        );
         env.apply_to_selection(
             writer,
             Some(&env.click_config.range_separator),
             |obj, writer| {
-                obj.describe(&matches, env, writer)?;
-                if include_events {
-                    clickwriteln!(writer, "Events:");
-                    print_events_for_obj(obj, env, writer)
-                } else {
-                    Ok(())
+                if !matches.is_present("refresh") {
+                    if let Some((output, age)) = env.get_cached_describe(obj, &options) {
+                        writer.write_all(&output)?;
+                        if !full_output {
+                            clickwriteln!(
+                                writer,
+                                "{}",
+                                Style::new()
+                                    .dimmed()
+                                    .paint(format!("(cached {}s ago)", age.as_secs()))
+                            );
+                        }
+                        return Ok(());
+                    }
+                }
+                writer.start_capture();
+                let res = obj.describe(&matches, env, writer).and_then(|_| {
+                    if include_events {
+                        clickwriteln!(writer, "Events:");
+                        print_events_for_obj(obj, env, writer)
+                    } else {
+                        Ok(())
+                    }
+                });
+                let output = writer.take_capture();
+                if let (Ok(()), Some(output)) = (res.as_ref(), output) {
+                    env.cache_describe(obj, &options, output);
                 }
+                res
             },
         )
     }
@@ -0,0 +1,177 @@
+// Copyright 2021 Databricks, Inc.
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+
+// http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use ansi_term::Colour::{Cyan, Green, Red, Yellow};
+use clap::{App, Arg};
+use k8s_openapi::http::Request;
+use rustyline::completion::Pair as RustlinePair;
+use serde_json::Value;
+
+use crate::{
+    command::command_def::{exec_match, start_clap, Cmd},
+    completer,
+    diff::{diff_lines, hunks, DiffLine},
+    env::Env,
+    error::ClickError,
+    kobj::KObj,
+    output::ClickWriter,
+    values::val_str,
+};
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::io::Write;
+
+/// Fields that change on their own, and so would only clutter the diff
+const VOLATILE_METADATA: &[&str] = &[
+    "creationTimestamp",
+    "generation",
+    "managedFields",
+    "resourceVersion",
+    "selfLink",
+    "uid",
+];
+
+fn response_message(body: &[u8]) -> String {
+    serde_json::from_slice::<Value>(body)
+        .map(|val| val_str("/message", &val, "<No message>").into_owned())
+        .unwrap_or_else(|_| "<No message>".to_string())
+}
+
+fn object_at(env: &Env, index: &str) -> Result<KObj, ClickError> {
+    let num = index.parse::<usize>().map_err(|_| {
+        ClickError::CommandError(format!("Invalid index '{}', expected a number", index))
+    })?;
+    env.item_at(num).cloned().ok_or_else(|| {
+        ClickError::CommandError(format!(
+            "No object at index {}, list some objects first (e.g. 'pods')",
+            num
+        ))
+    })
+}
+
+/// Get obj as yaml, without status and volatile metadata
+fn normalized_yaml(env: &Env, obj: &KObj) -> Result<String, ClickError> {
+    let request = Request::get(obj.api_path(env)?).body(vec![]).unwrap(); // safe, valid path
+    let response = env.run_on_context(|c| c.execute(request))?;
+    if !response.status().is_success() {
+        return Err(ClickError::CommandError(format!(
+            "Could not get {} {} ({}): {}",
+            obj.type_str(),
+            obj.name(),
+            response.status(),
+            response_message(response.body())
+        )));
+    }
+    let mut value: Value = serde_json::from_slice(response.body())?;
+    if let Some(value_obj) = value.as_object_mut() {
+        value_obj.remove("status");
+    }
+    if let Some(meta) = value
+        .get_mut("metadata")
+        .and_then(|meta| meta.as_object_mut())
+    {
+        for field in VOLATILE_METADATA.iter() {
+            meta.remove(*field);
+        }
+    }
+    Ok(serde_yaml::to_string(&value)?)
+}
+
+fn obj_label(obj: &KObj) -> String {
+    match obj.namespace.as_ref() {
+        Some(ns) => format!("{}/{}/{}", obj.type_str().to_lowercase(), ns, obj.name()),
+        None => format!("{}/{}", obj.type_str().to_lowercase(), obj.name()),
+    }
+}
+
+command!(
+    Diff,
+    "diff",
+    "Show the differences between two objects from the last list, as a unified diff of their \
+     yaml. Status and metadata that changes on its own (like resourceVersion and uid) are left \
+     out, so the diff is of the specs",
+    |clap: App<'static, 'static>| clap
+        .arg(
+            Arg::with_name("first")
+                .help("The index of the first object, as shown in the last list")
+                .required(true)
+                .index(1)
+        )
+        .arg(
+            Arg::with_name("second")
+                .help("The index of the second object, as shown in the last list")
+                .required(true)
+                .index(2)
+        )
+        .arg(
+            Arg::with_name("context")
+                .short("U")
+                .long("context-lines")
+                .help("How many unchanged lines to show around each change")
+                .takes_value(true)
+                .default_value("3")
+                .validator(|s| s.parse::<usize>().map(|_| ()).map_err(|e| e.to_string()))
+        ),
+    vec!["diff"],
+    noop_complete!(),
+    no_named_complete!(),
+    |matches, env, writer| {
+        let first = object_at(env, matches.value_of("first").unwrap())?; // safe, required
+        let second = object_at(env, matches.value_of("second").unwrap())?; // safe, required
+        let context = matches
+            .value_of("context")
+            .unwrap() // safe, has default
+            .parse::<usize>()
+            .unwrap(); // safe, validated
+        let old = normalized_yaml(env, &first)?;
+        let new = normalized_yaml(env, &second)?;
+        let found = hunks(diff_lines(&old, &new), context);
+        if found.is_empty() {
+            clickwriteln!(writer, "No differences");
+            return Ok(());
+        }
+
+        let color = writer.use_color();
+        let paint = |colour: ansi_term::Colour, line: String| {
+            if color {
+                colour.paint(line).to_string()
+            } else {
+                line
+            }
+        };
+        clickwriteln!(
+            writer,
+            "{}",
+            paint(Red, format!("--- {}", obj_label(&first)))
+        );
+        clickwriteln!(
+            writer,
+            "{}",
+            paint(Green, format!("+++ {}", obj_label(&second)))
+        );
+        for hunk in found.iter() {
+            clickwriteln!(writer, "{}", paint(Cyan, hunk.header()));
+            for line in hunk.lines.iter() {
+                let line = match line {
+                    DiffLine::Same(l) => format!(" {}", l),
+                    DiffLine::Removed(l) => paint(Red, format!("-{}", l)),
+                    DiffLine::Added(l) => paint(Green, format!("+{}", l)),
+                };
+                clickwriteln!(writer, "{}", line);
+            }
+        }
+        Ok(())
+    }
+);
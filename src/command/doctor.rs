@@ -0,0 +1,426 @@
+// Copyright 2021 Databricks, Inc.
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+
+// http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use ansi_term::Colour::{Green, Red, Yellow};
+use chrono::{DateTime, Duration, Utc};
+use clap::App;
+use k8s_openapi::http::{self, Request, StatusCode};
+use rustyline::completion::Pair as RustlinePair;
+use serde_json::Value;
+
+use crate::{
+    certs::cert_not_after,
+    command::command_def::{exec_match, start_clap, Cmd},
+    command::find_binary,
+    completer,
+    config::{ClickConfig, Config},
+    env::Env,
+    error::ClickError,
+    k8s::Context,
+    output::ClickWriter,
+};
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::Path;
+
+/// Warn about certificates that expire within this many days
+const CERT_WARN_DAYS: i64 = 7;
+
+/// The outcome of a single check. Warnings and failures carry a hint about how to fix things
+enum Check {
+    Pass(String),
+    Warn(String, String),
+    Fail(String, String),
+}
+
+impl Check {
+    fn fail<S: Into<String>, H: Into<String>>(msg: S, hint: H) -> Check {
+        Check::Fail(msg.into(), hint.into())
+    }
+
+    fn passed(&self) -> bool {
+        !matches!(self, Check::Fail(..))
+    }
+}
+
+/// Print the result of a check, returning true if it didn't fail
+fn report(writer: &mut ClickWriter, what: &str, check: Check) -> bool {
+    let passed = check.passed();
+    match check {
+        Check::Pass(msg) => {
+            clickwriteln!(writer, "{} {}: {}", Green.paint("✓"), what, msg);
+        }
+        Check::Warn(msg, hint) => {
+            clickwriteln!(writer, "{} {}: {}", Yellow.paint("!"), what, msg);
+            clickwriteln!(writer, "    {}", Yellow.paint(hint));
+        }
+        Check::Fail(msg, hint) => {
+            clickwriteln!(writer, "{} {}: {}", Red.paint("✗"), what, Red.paint(msg));
+            clickwriteln!(writer, "    {}", Yellow.paint(hint));
+        }
+    }
+    passed
+}
+
+fn check_click_config(path: &Path) -> Check {
+    if !path.exists() {
+        return Check::Pass(format!("{} does not exist, using defaults", path.display()));
+    }
+    match ClickConfig::from_file(&path.to_string_lossy()) {
+        Ok(_) => Check::Pass(format!("{} parsed", path.display())),
+        Err(e) => Check::fail(
+            format!("{}: {}", path.display(), e),
+            "Fix the file, or move it aside and click will write a new one when it exits",
+        ),
+    }
+}
+
+fn check_kube_config(path: &Path) -> Check {
+    match Config::from_files(&[path.to_string_lossy().into_owned()]) {
+        Ok(_) => Check::Pass(format!("{} parsed", path.display())),
+        Err(e) => Check::fail(
+            format!("{}: {}", path.display(), e),
+            "Check the file is valid, or point --kubeconfig, KUBECONFIG or --config_dir at one \
+             that is",
+        ),
+    }
+}
+
+fn check_context(env: &Env) -> Check {
+    match (env.context.as_ref(), env.click_config.context.as_ref()) {
+        (Some(context), _) => Check::Pass(context.name.clone()),
+        (None, Some(name)) => match env.config.get_context(name, &env.click_config) {
+            Ok(_) => Check::Pass(name.clone()),
+            Err(e) => Check::fail(
+                format!("{} could not be loaded: {}", name, e),
+                "Check the context's cluster and user in your kubeconfig, or pick another \
+                 context with `context`",
+            ),
+        },
+        (None, None) => Check::fail(
+            "no context selected",
+            "Run `contexts` to see the available contexts, and `context <name>` to pick one",
+        ),
+    }
+}
+
+fn check_cert(pem: Result<String, ClickError>, what: &str, now: DateTime<Utc>) -> Check {
+    let not_after = match pem.and_then(|pem| cert_not_after(&pem)) {
+        Ok(not_after) => not_after,
+        Err(e) => {
+            return Check::fail(
+                format!("could not read {}: {}", what, e),
+                "Check the certificate path or data in your kubeconfig",
+            )
+        }
+    };
+    if not_after < now {
+        Check::fail(
+            format!("{} expired at {}", what, not_after),
+            "Get a new certificate from your cluster administrator or login tool",
+        )
+    } else if not_after < now + Duration::days(CERT_WARN_DAYS) {
+        Check::Warn(
+            format!("{} expires soon, at {}", what, not_after),
+            "Get a new certificate before it expires".to_string(),
+        )
+    } else {
+        Check::Pass(format!("valid until {}", not_after))
+    }
+}
+
+fn check_binary(cmd: &str) -> Check {
+    match find_binary(cmd) {
+        Some(path) => Check::Pass(format!("found at {}", path.display())),
+        None => Check::fail(
+            format!("{} not found", cmd),
+            "Install it or add it to your PATH, it's needed to get credentials for this context",
+        ),
+    }
+}
+
+fn check_reachable(context: &Context) -> Check {
+    let request = Request::get("/version").body(vec![]).unwrap(); // safe, static request
+    match context.execute(request) {
+        Ok(response) if response.status().is_success() => {
+            let version = serde_json::from_slice::<Value>(response.body())
+                .ok()
+                .and_then(|v| v["gitVersion"].as_str().map(|s| s.to_string()));
+            Check::Pass(format!(
+                "{} responded, server version {}",
+                context.endpoint(),
+                version.as_deref().unwrap_or("unknown")
+            ))
+        }
+        // some clusters don't allow anonymous access to /version, but we still got an answer
+        Ok(response) => Check::Pass(format!(
+            "{} responded ({})",
+            context.endpoint(),
+            response.status()
+        )),
+        Err(e) => Check::fail(
+            format!("could not connect to {}: {}", context.endpoint(), e),
+            "Check the server url in your kubeconfig, and that you can reach it (VPN, proxy, \
+             firewall)",
+        ),
+    }
+}
+
+fn auth_failed(status: StatusCode) -> Check {
+    Check::fail(
+        format!("credentials were rejected ({})", status),
+        "Your credentials are invalid or have expired. Log in to the cluster again, for example \
+         by running a kubectl command against it",
+    )
+}
+
+/// Ask the server who we are. This uses SelfSubjectReview where it's available, and falls back
+/// to a request that requires authentication on older clusters
+fn check_auth(context: &Context) -> Check {
+    let review = serde_json::json!({
+        "apiVersion": "authentication.k8s.io/v1",
+        "kind": "SelfSubjectReview",
+    });
+    let request = Request::post("/apis/authentication.k8s.io/v1/selfsubjectreviews")
+        .header(http::header::CONTENT_TYPE, "application/json")
+        .body(review.to_string().into_bytes())
+        .unwrap(); // safe, static request
+    match context.execute(request) {
+        Ok(response) if response.status().is_success() => {
+            let user = serde_json::from_slice::<Value>(response.body())
+                .ok()
+                .and_then(|v| {
+                    v["status"]["userInfo"]["username"]
+                        .as_str()
+                        .map(|s| s.to_string())
+                });
+            return Check::Pass(format!(
+                "authenticated as {}",
+                user.as_deref().unwrap_or("unknown user")
+            ));
+        }
+        Ok(response) if response.status() == StatusCode::UNAUTHORIZED => {
+            return auth_failed(response.status())
+        }
+        Ok(_) => {} // probably an older cluster, fall back to below
+        Err(e) => {
+            return Check::fail(
+                format!("request failed: {}", e),
+                "Check your credentials in your kubeconfig",
+            )
+        }
+    }
+
+    let request = Request::get("/api/v1/namespaces?limit=1")
+        .body(vec![])
+        .unwrap(); // safe, static request
+    match context.execute(request) {
+        Ok(response) if response.status().is_success() => Check::Pass("authenticated".to_string()),
+        Ok(response) if response.status() == StatusCode::FORBIDDEN => {
+            Check::Pass("authenticated (but not allowed to list namespaces)".to_string())
+        }
+        Ok(response) if response.status() == StatusCode::UNAUTHORIZED => {
+            auth_failed(response.status())
+        }
+        Ok(response) => Check::fail(
+            format!("unexpected response from server ({})", response.status()),
+            "Check your credentials in your kubeconfig",
+        ),
+        Err(e) => Check::fail(
+            format!("request failed: {}", e),
+            "Check your credentials in your kubeconfig",
+        ),
+    }
+}
+
+fn run_checks(env: &Env, writer: &mut ClickWriter) -> usize {
+    let mut failures = 0;
+    let mut report = |writer: &mut ClickWriter, what: &str, check: Check| {
+        if !report(writer, what, check) {
+            failures += 1;
+        }
+    };
+
+    report(
+        writer,
+        "Click config",
+        check_click_config(env.click_config_path()),
+    );
+    for path in std::env::split_paths(&env.config.source_file) {
+        report(writer, "Kube config", check_kube_config(&path));
+    }
+
+    let context_check = check_context(env);
+    let context_ok = context_check.passed();
+    report(writer, "Context", context_check);
+    if !context_ok {
+        clickwriteln!(writer, "Skipping cluster checks, no usable context");
+        return failures;
+    }
+
+    // check_context passed, so one of these is set
+    let context_name = env
+        .context
+        .as_ref()
+        .map(|c| c.name.as_str())
+        .or(env.click_config.context.as_deref())
+        .unwrap();
+    let context_conf = env.config.contexts.get(context_name);
+    if let Some(cluster) = context_conf.and_then(|c| env.config.clusters.get(&c.cluster)) {
+        if let Some(ca) = cluster.cert.as_ref() {
+            report(
+                writer,
+                "Cluster CA certificate",
+                check_cert(Ok(ca.clone()), "cluster CA certificate", Utc::now()),
+            );
+        }
+    }
+    if let Some(user) = context_conf.and_then(|c| env.config.users.get(&c.user)) {
+        if let Some(pem) = user.client_cert_pem() {
+            report(
+                writer,
+                "Client certificate",
+                check_cert(pem, "client certificate", Utc::now()),
+            );
+        }
+        for cmd in user.auth_commands() {
+            report(writer, &format!("Auth command {}", cmd), check_binary(cmd));
+        }
+    }
+
+    match env.context.as_ref() {
+        Some(context) => {
+            let reachable = check_reachable(context);
+            let reachable_ok = reachable.passed();
+            report(writer, "Cluster", reachable);
+            if reachable_ok {
+                report(writer, "Authentication", check_auth(context));
+            }
+        }
+        None => {
+            clickwriteln!(writer, "Skipping cluster checks, context is not active");
+        }
+    }
+    failures
+}
+
+command!(
+    Doctor,
+    "doctor",
+    "Check your configuration and connection to the current context for common problems",
+    |clap: App<'static, 'static>| clap.after_help(
+        "Checks that config files parse, that the current context can be loaded, that \
+         certificates haven't expired, that any commands needed for authentication are \
+         installed, and that the cluster is reachable and accepts your credentials."
+    ),
+    vec!["doctor"],
+    noop_complete!(),
+    no_named_complete!(),
+    |_matches, env, writer| {
+        let failures = run_checks(env, writer);
+        if failures == 0 {
+            clickwriteln!(writer, "\n{}", Green.paint("No problems found"));
+        } else {
+            clickwriteln!(
+                writer,
+                "\n{}",
+                Red.paint(format!(
+                    "{} problem{} found",
+                    failures,
+                    if failures == 1 { "" } else { "s" }
+                ))
+            );
+        }
+        Ok(())
+    }
+);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::certs::{cert_not_after, tests::CA_PEM};
+
+    use std::fs;
+    use tempdir::TempDir;
+
+    fn cert_check(now: DateTime<Utc>) -> Check {
+        check_cert(Ok(CA_PEM.to_string()), "test certificate", now)
+    }
+
+    #[test]
+    fn cert_expiry() {
+        let not_after = cert_not_after(CA_PEM).unwrap();
+        assert!(matches!(
+            cert_check(not_after + Duration::days(1)),
+            Check::Fail(msg, _) if msg.contains("expired")
+        ));
+        assert!(matches!(
+            cert_check(not_after - Duration::days(CERT_WARN_DAYS - 1)),
+            Check::Warn(msg, _) if msg.contains("expires soon")
+        ));
+        assert!(matches!(
+            cert_check(not_after - Duration::days(CERT_WARN_DAYS + 1)),
+            Check::Pass(msg) if msg.starts_with("valid until")
+        ));
+        assert!(matches!(
+            check_cert(Ok("not a cert".to_string()), "test certificate", Utc::now()),
+            Check::Fail(msg, _) if msg.starts_with("could not read")
+        ));
+    }
+
+    #[test]
+    fn unparseable_configs() {
+        let dir = TempDir::new("doctor").unwrap();
+        let bad = dir.path().join("bad.yaml");
+        fs::write(&bad, "contexts: [not: {closed").unwrap();
+        assert!(matches!(check_click_config(&bad), Check::Fail(..)));
+        assert!(matches!(check_kube_config(&bad), Check::Fail(..)));
+
+        let missing = dir.path().join("missing.yaml");
+        assert!(matches!(check_click_config(&missing), Check::Pass(..)));
+    }
+
+    #[test]
+    fn failures_counted() {
+        assert!(Check::Pass("ok".to_string()).passed());
+        assert!(Check::Warn("soon".to_string(), "hint".to_string()).passed());
+        assert!(!Check::fail("broken", "hint").passed());
+
+        let mut writer = ClickWriter::with_buffer(vec![], false);
+        assert!(report(&mut writer, "pass", Check::Pass("ok".to_string())));
+        assert!(report(
+            &mut writer,
+            "warn",
+            Check::Warn("soon".to_string(), "hint".to_string())
+        ));
+        assert!(!report(&mut writer, "fail", Check::fail("broken", "hint")));
+
+        // a broken click config and no context are two failures, and the cluster checks are
+        // skipped
+        let dir = TempDir::new("doctor").unwrap();
+        let kube = dir.path().join("config");
+        fs::write(
+            &kube,
+            "apiVersion: v1\nclusters: []\ncontexts: []\nusers: []\n",
+        )
+        .unwrap();
+        let click = dir.path().join("click.config");
+        fs::write(&click, "namespace: [").unwrap();
+        let config = Config::from_files(&[kube.to_str().unwrap().to_string()]).unwrap();
+        let env = Env::new(config, ClickConfig::default(), click);
+        assert_eq!(run_checks(&env, &mut writer), 2);
+    }
+}
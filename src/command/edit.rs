@@ -0,0 +1,249 @@
+// Copyright 2021 Databricks, Inc.
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+
+// http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use ansi_term::Colour::Yellow;
+use clap::{App, Arg};
+use k8s_openapi::http::{self, Request, StatusCode};
+use rustyline::completion::Pair as RustlinePair;
+use serde_json::Value;
+
+use crate::{
+    command::command_def::{exec_match, start_clap, Cmd},
+    command::{
+        change_query_pairs, dry_run_arg, editor_expression, field_manager_arg, path_with_query,
+        DryRun,
+    },
+    completer,
+    env::Env,
+    error::ClickError,
+    kobj::KObj,
+    output::ClickWriter,
+    values::{merge_patch, val_str},
+};
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::io::Write;
+
+const EDIT_HEADER: &str = "\
+# Please edit the object below. Lines beginning with a '#' will be ignored,
+# and an empty file will abort the edit. If an error occurs while saving this file will be
+# reopened with the relevant failures.
+#
+";
+
+fn response_message(body: &[u8]) -> String {
+    serde_json::from_slice::<Value>(body)
+        .map(|val| val_str("/message", &val, "<No message>").into_owned())
+        .unwrap_or_else(|_| "<No message>".to_string())
+}
+
+fn get_object(env: &Env, path: &str) -> Result<Value, ClickError> {
+    let request = Request::get(path).body(vec![]).unwrap(); // safe, path built from valid names
+    let response = env.run_on_context(|c| c.execute(request))?;
+    if response.status().is_success() {
+        serde_json::from_slice(response.body()).map_err(|e| e.into())
+    } else {
+        Err(ClickError::CommandError(format!(
+            "Could not get object ({}): {}",
+            response.status(),
+            response_message(response.body())
+        )))
+    }
+}
+
+/// Send patch as a json merge patch. Returns Ok(Err(message)) if the server rejected the object as
+/// invalid, since the user can fix that by editing again
+fn send_merge_patch(
+    env: &Env,
+    path: &str,
+    patch: &Value,
+    query: &[(&str, &str)],
+) -> Result<Result<(), String>, ClickError> {
+    let request = Request::patch(path_with_query(path, query))
+        .header(http::header::CONTENT_TYPE, "application/merge-patch+json")
+        .body(serde_json::to_vec(patch)?)
+        .unwrap(); // safe, path built from valid names
+    let response = env.run_on_context(|c| c.execute(request))?;
+    match response.status() {
+        status if status.is_success() => Ok(Ok(())),
+        StatusCode::BAD_REQUEST | StatusCode::UNPROCESSABLE_ENTITY => {
+            Ok(Err(response_message(response.body())))
+        }
+        status => Err(ClickError::CommandError(format!(
+            "Edit failed ({}): {}",
+            status,
+            response_message(response.body())
+        ))),
+    }
+}
+
+/// Remove the comment block at the top of the file (our header, and any error we added). Other
+/// lines starting with # are left alone, since they could be in the middle of a multi-line string
+fn strip_comments(content: &str) -> String {
+    content
+        .lines()
+        .skip_while(|line| line.starts_with('#'))
+        .map(|line| format!("{}\n", line))
+        .collect()
+}
+
+fn edit_obj(
+    env: &Env,
+    obj: &KObj,
+    editor_opt: Option<&str>,
+    dry_run: DryRun,
+    field_manager: Option<&str>,
+    writer: &mut ClickWriter,
+) -> Result<(), ClickError> {
+    let path = obj.api_path(env)?;
+    let query = change_query_pairs(dry_run, field_manager);
+    let mut original = get_object(env, &path)?;
+    // managed fields are just noise when editing
+    if let Some(meta) = original
+        .get_mut("metadata")
+        .and_then(|meta| meta.as_object_mut())
+    {
+        meta.remove("managedFields");
+    }
+
+    let tmpdir = match env.tempdir {
+        Ok(ref td) => td,
+        Err(ref e) => {
+            return Err(ClickError::CommandError(format!(
+                "Failed to create tempdir: {}",
+                e
+            )));
+        }
+    };
+    let kind = obj.type_str().to_lowercase();
+    let file_path = tmpdir.path().join(format!("{}-{}.yaml", kind, obj.name()));
+
+    let mut content = format!("{}{}", EDIT_HEADER, serde_yaml::to_string(&original)?);
+    loop {
+        std::fs::write(&file_path, &content)?;
+        editor_expression(env, editor_opt, &file_path)?.run()?;
+        let edited = strip_comments(&std::fs::read_to_string(&file_path)?);
+        if edited.trim().is_empty() {
+            clickwriteln!(writer, "Edit cancelled, file was empty");
+            return Ok(());
+        }
+
+        let error = match serde_yaml::from_str::<Value>(&edited) {
+            Ok(modified) => match merge_patch(&original, &modified) {
+                Some(mut patch) => {
+                    // send the version we started from, so the patch fails rather than
+                    // overwriting changes someone else made while we were editing
+                    if let (Some(version), Some(patch_obj)) = (
+                        original.pointer("/metadata/resourceVersion"),
+                        patch.as_object_mut(),
+                    ) {
+                        let meta = patch_obj
+                            .entry("metadata")
+                            .or_insert_with(|| Value::Object(Default::default()));
+                        if let Some(meta) = meta.as_object_mut() {
+                            meta.insert("resourceVersion".to_string(), version.clone());
+                        }
+                    }
+                    if dry_run == DryRun::Client {
+                        clickwriteln!(
+                            writer,
+                            "{}/{} edited{}, patch:",
+                            kind,
+                            obj.name(),
+                            dry_run.suffix()
+                        );
+                        clickwrite!(writer, "{}", serde_yaml::to_string(&patch)?);
+                        return Ok(());
+                    }
+                    match send_merge_patch(env, &path, &patch, &query)? {
+                        Ok(()) => {
+                            clickwriteln!(
+                                writer,
+                                "{}/{} edited{}",
+                                kind,
+                                obj.name(),
+                                dry_run.suffix()
+                            );
+                            return Ok(());
+                        }
+                        Err(message) => message,
+                    }
+                }
+                None => {
+                    clickwriteln!(writer, "Edit cancelled, no changes made");
+                    return Ok(());
+                }
+            },
+            Err(e) => format!("Could not parse yaml: {}", e),
+        };
+
+        let error_lines: String = error
+            .lines()
+            .map(|line| format!("# * {}\n", line))
+            .collect();
+        content = format!(
+            "{}# {}/{} was not valid:\n{}#\n{}",
+            EDIT_HEADER,
+            kind,
+            obj.name(),
+            error_lines,
+            edited
+        );
+    }
+}
+
+command!(
+    Edit,
+    "edit",
+    "Edit the active object in your editor. When the editor exits the changes are sent to the \
+     cluster. If they are rejected the editor is re-opened with the error at the top of the file",
+    |clap: App<'static, 'static>| clap
+        .arg(
+            Arg::with_name("editor")
+                .long("editor")
+                .short("e")
+                .value_name("EDITOR")
+                .help(
+                    "Use the specified command as the editor. Otherwise the click editor setting \
+                     (see set/env commands) is used, otherwise $EDITOR. The editor must wait \
+                     until the file is closed before exiting (e.g. 'code -w')"
+                )
+                .takes_value(true)
+        )
+        .arg(dry_run_arg())
+        .arg(field_manager_arg()),
+    vec!["edit"],
+    noop_complete!(),
+    no_named_complete!(),
+    |matches, env, writer| {
+        let dry_run = DryRun::from_matches(&matches);
+        let res = env.apply_to_selection(
+            writer,
+            Some(&env.click_config.range_separator),
+            |obj, writer| {
+                edit_obj(
+                    env,
+                    obj,
+                    matches.value_of("editor"),
+                    dry_run,
+                    matches.value_of("field_manager"),
+                    writer,
+                )
+            },
+        );
+        env.invalidate_cache_for_selection();
+        res
+    }
+);
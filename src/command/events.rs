@@ -13,7 +13,7 @@
 // limitations under the License.
 
 use ansi_term::Colour::Yellow;
-use clap::App;
+use clap::{App, Arg};
 use k8s_openapi::ListOptional;
 use k8s_openapi::{api::core::v1 as api, http::Request, List};
 use prettytable::{Cell, Row, Table};
@@ -21,12 +21,12 @@ use rustyline::completion::Pair as RustlinePair;
 
 use crate::{
     command::command_def::{exec_match, start_clap, Cmd},
-    command::time_since,
     completer,
     env::{Env, ObjectSelection},
     error::ClickError,
     kobj::KObj,
     output::ClickWriter,
+    table::time_since,
 };
 
 use std::cell::RefCell;
@@ -67,16 +67,34 @@ pub fn print_events_for_obj(
     print_events(request, env, writer, include_namespace, false)
 }
 
-fn print_events_no_obj(env: &Env, writer: &mut ClickWriter) -> Result<(), ClickError> {
+/// Print events in the current namespace (or all namespaces if all_namespaces is set or there's
+/// no current namespace), optionally only those for objects called for_name
+fn print_events_no_obj(
+    env: &Env,
+    all_namespaces: bool,
+    for_name: Option<&str>,
+    writer: &mut ClickWriter,
+) -> Result<(), ClickError> {
     let mut opts: ListOptional = Default::default();
     let mut include_namespace = false;
-    let (request, _body) = if let Some(ns) = env.namespace.as_ref() {
-        let fs = format!("involvedObject.namespace={}", ns);
-        opts.field_selector = Some(&fs);
-        api::Event::list_namespaced_event(ns, opts)?
-    } else {
-        include_namespace = true;
-        api::Event::list_event_for_all_namespaces(opts)?
+    let (request, _body) = match env.namespace.as_ref() {
+        Some(ns) if !all_namespaces => {
+            let fs = match for_name {
+                Some(name) => format!(
+                    "involvedObject.namespace={},involvedObject.name={}",
+                    ns, name
+                ),
+                None => format!("involvedObject.namespace={}", ns),
+            };
+            opts.field_selector = Some(&fs);
+            api::Event::list_namespaced_event(ns, opts)?
+        }
+        _ => {
+            include_namespace = true;
+            let fs = for_name.map(|name| format!("involvedObject.name={}", name));
+            opts.field_selector = fs.as_deref();
+            api::Event::list_event_for_all_namespaces(opts)?
+        }
     };
     print_events(request, env, writer, include_namespace, true)
 }
@@ -90,7 +108,8 @@ fn print_events(
 ) -> Result<(), ClickError> {
     let mut event_list: List<api::Event> = env.run_on_context(|c| c.execute_list(request))?;
     if !event_list.items.is_empty() {
-        event_list.items.sort_by(event_cmp);
+        // most recent first
+        event_list.items.sort_by(|e1, e2| event_cmp(e2, e1));
         let mut table = Table::new();
         let mut titles = if include_namespace {
             vec![
@@ -123,7 +142,12 @@ fn print_events(
                     .unwrap_or_else(|| "unknown".to_string()),
             };
             row.push(Cell::new(&timestr));
-            row.push(Cell::new(event.type_.as_deref().unwrap_or("unknown")));
+            let typ = event.type_.as_deref().unwrap_or("unknown");
+            if typ == "Warning" {
+                row.push(Cell::new(typ).style_spec("Fr"));
+            } else {
+                row.push(Cell::new(typ));
+            }
             row.push(Cell::new(event.reason.as_deref().unwrap_or("unknown")));
             if include_object {
                 row.push(Cell::new(
@@ -133,8 +157,7 @@ fn print_events(
             row.push(Cell::new(event.message.as_deref().unwrap_or("<none>")));
             table.add_row(Row::new(row));
         }
-        table.set_format(*crate::table::TBLFMT);
-        table.print(writer).unwrap_or(0);
+        crate::table::print_filled_table(&mut table, writer);
     } else {
         clickwriteln!(writer, "No events");
     }
@@ -144,14 +167,33 @@ fn print_events(
 command!(
     Events,
     "events",
-    "Get events for the active pod",
-    crate::command::command_def::identity,
+    "Get events for the active object, or for the current namespace if nothing is active. \
+     The most recent events are shown first",
+    |clap: App<'static, 'static>| clap
+        .arg(
+            Arg::with_name("for")
+                .long("for")
+                .value_name("NAME")
+                .help("Only show events for objects with the specified name")
+                .takes_value(true)
+        )
+        .arg(
+            Arg::with_name("all_namespaces")
+                .short("A")
+                .long("all-namespaces")
+                .help("Show events from all namespaces, not just the current one")
+                .takes_value(false)
+        ),
     vec!["events"],
     noop_complete!(),
     no_named_complete!(),
-    |_matches, env, writer| {
-        if let ObjectSelection::None = env.current_selection() {
-            print_events_no_obj(env, writer)
+    |matches, env, writer| {
+        let all_namespaces = matches.is_present("all_namespaces");
+        let for_name = matches.value_of("for");
+        if for_name.is_some() || all_namespaces {
+            print_events_no_obj(env, all_namespaces, for_name, writer)
+        } else if let ObjectSelection::None = env.current_selection() {
+            print_events_no_obj(env, false, None, writer)
         } else {
             env.apply_to_selection(
                 writer,
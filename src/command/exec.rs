@@ -14,15 +14,17 @@
 
 use ansi_term::Colour::Yellow;
 use clap::{App, Arg};
+use k8s_openapi::api::core::v1 as api;
 use rustyline::completion::Pair as RustlinePair;
 
 use crate::{
     command::command_def::{exec_match, start_clap, Cmd},
+    command::pods::pod_to_kobj,
     completer,
     env::Env,
     error::ClickError,
-    kobj::KObj,
-    output::ClickWriter,
+    kobj::{KObj, ObjType},
+    output::{stdin_is_tty, ClickWriter},
 };
 
 use std::array::IntoIter;
@@ -36,13 +38,85 @@ fn valid_bool(s: String) -> Result<(), String> {
     s.parse::<bool>().map(|_| ()).map_err(|e| e.to_string())
 }
 
+/// Split `<pod> [-c <container>] -- <command>` into the pod, container and command. Without a
+/// `--`, or if what's before it isn't just a pod name and container, all of args is the command to
+/// run in the active pod
+#[allow(clippy::type_complexity)]
+fn split_pod_target<'a>(
+    args: &[&'a str],
+) -> Result<(Option<&'a str>, Option<&'a str>, Vec<&'a str>), ClickError> {
+    let sep = match args.iter().position(|arg| *arg == "--") {
+        Some(sep) => sep,
+        None => return Ok((None, None, args.to_vec())),
+    };
+    let mut pod = None;
+    let mut container = None;
+    let mut before = args[..sep].iter();
+    while let Some(arg) = before.next() {
+        if *arg == "-c" || *arg == "--container" {
+            container = Some(*before.next().ok_or_else(|| {
+                ClickError::CommandError(format!("{} needs a container name", arg))
+            })?);
+        } else if let Some(name) = arg.strip_prefix("--container=") {
+            container = Some(name);
+        } else if arg.starts_with('-') || pod.is_some() {
+            return Ok((None, None, args.to_vec()));
+        } else {
+            pod = Some(*arg);
+        }
+    }
+    let cmd = args[sep + 1..].to_vec();
+    match pod {
+        Some(_) if cmd.is_empty() => Err(ClickError::CommandError(
+            "No command given after --".to_string(),
+        )),
+        Some(pod) => Ok((Some(pod), container, cmd)),
+        None => Ok((None, None, args.to_vec())),
+    }
+}
+
+/// Get the pod called name in the current namespace
+fn named_pod(env: &Env, name: &str) -> Result<KObj, ClickError> {
+    let ns = env.namespace.as_deref().ok_or_else(|| {
+        ClickError::CommandError(format!(
+            "No namespace is set, can't find pod {} (set one with 'namespace')",
+            name
+        ))
+    })?;
+    let (request, _) = api::Pod::read_namespaced_pod(name, ns, Default::default())?;
+    match env.run_on_context(|c| c.read(request))? {
+        api::ReadNamespacedPodResponse::Ok(pod) => Ok(pod_to_kobj(&pod)),
+        _ => Err(ClickError::CommandError(format!(
+            "No pod named {} in namespace {}",
+            name, ns
+        ))),
+    }
+}
+
+/// Error if pod doesn't have the container we were asked to exec in
+fn check_container(pod: &KObj, cont_opt: Option<&str>) -> Result<(), ClickError> {
+    match (&pod.typ, cont_opt) {
+        (ObjType::Pod { containers }, Some(cont))
+            if !containers.is_empty() && !containers.iter().any(|c| c == cont) =>
+        {
+            Err(ClickError::CommandError(format!(
+                "Pod {} has no container {}, it has: {}",
+                pod.name(),
+                cont,
+                containers.join(", ")
+            )))
+        }
+        _ => Ok(()),
+    }
+}
+
 #[allow(clippy::too_many_arguments)]
 fn do_exec(
     env: &Env,
     pod: &KObj,
     kluster_name: &str,
     cmd: &[&str],
-    it_arg: &str,
+    it_arg: Option<&str>,
     cont_opt: &Option<&str>,
     term_opt: &Option<&str>,
     do_terminal: bool,
@@ -51,24 +125,20 @@ fn do_exec(
     let ns = pod.namespace.as_ref().unwrap();
     if do_terminal {
         let terminal = if let Some(t) = term_opt {
-            t
+            t.to_string()
         } else if let Some(ref t) = env.click_config.terminal {
-            t
+            crate::config::expand_env_vars(t)
         } else {
-            "xterm -e"
+            "xterm -e".to_string()
         };
+        let impersonation = env.kubectl_impersonation_args();
         let mut targs: Vec<&str> = terminal.split_whitespace().collect();
-        let mut kubectl_args = vec![
-            "kubectl",
-            "--namespace",
-            ns,
-            "--context",
-            kluster_name,
-            "exec",
-            it_arg,
-            pod.name(),
-        ];
+        let mut kubectl_args = vec!["kubectl", "--namespace", ns, "--context", kluster_name];
         targs.append(&mut kubectl_args);
+        targs.extend(impersonation.iter().map(|arg| arg.as_str()));
+        targs.push("exec");
+        targs.extend(it_arg);
+        targs.push(pod.name());
         if let Some(cont) = cont_opt {
             targs.push("-c");
             targs.push(cont);
@@ -85,8 +155,9 @@ fn do_exec(
             .arg(ns)
             .arg("--context")
             .arg(kluster_name)
+            .args(env.kubectl_impersonation_args())
             .arg("exec")
-            .arg(it_arg)
+            .args(it_arg)
             .arg(pod.name());
         let command = if let Some(cont) = cont_opt {
             command.arg("-c").arg(cont).arg("--").args(cmd)
@@ -119,11 +190,15 @@ fn do_exec(
 command!(
     Exec,
     "exec",
-    "exec specified command on active pod",
+    "exec specified command on active pod, or on a pod named like: exec <pod> -- <command>",
     |clap: App<'static, 'static>| clap
         .arg(
             Arg::with_name("command")
-                .help("The command to execute")
+                .help(
+                    "The command to execute. To run it in a pod other than the active one, \
+                     give the pod's name and then the command after a --. The pod is looked up \
+                     in the current namespace, and doesn't become the active object"
+                )
                 .required(true)
                 .multiple(true) // required for trailing_var_arg
                 .index(1)
@@ -132,7 +207,10 @@ command!(
             Arg::with_name("container")
                 .short("c")
                 .long("container")
-                .help("Exec in the specified container")
+                .help(
+                    "Exec in the specified container. With a pod name this can also go \
+                     between the name and the -- (exec <pod> -c <container> -- <command>)"
+                )
                 .takes_value(true)
         )
         .arg(
@@ -152,7 +230,12 @@ command!(
             Arg::with_name("tty")
                 .short("T")
                 .long("tty")
-                .help("If stdin is a TTY. Contrary to kubectl, this defaults to TRUE")
+                .help(
+                    "Allocate a TTY for the command, so interactive shells get line editing, job \
+                     control and terminal resizes. Contrary to kubectl, this defaults to TRUE when \
+                     click is running in a terminal (and to FALSE otherwise, e.g. with --exec in \
+                     a script)"
+                )
                 .validator(valid_bool)
                 .takes_value(true)
                 .min_values(0)
@@ -174,7 +257,9 @@ command!(
     )])
     .collect(),
     |matches, env, writer| {
-        let cmd: Vec<&str> = matches.values_of("command").unwrap().collect(); // safe as required
+        let args: Vec<&str> = matches.values_of("command").unwrap().collect(); // safe as required
+        let (pod_name, pod_container, cmd) = split_pod_target(&args)?;
+        let container = pod_container.or_else(|| matches.value_of("container"));
         if let Some(context) = env.context.as_ref() {
             let tty = if matches.is_present("tty") {
                 if let Some(v) = matches.value_of("tty") {
@@ -184,7 +269,10 @@ command!(
                     true
                 }
             } else {
-                true
+                // a new terminal window has its own tty, no matter where click is running.
+                // Otherwise kubectl can only set up a pty on the remote end if our input is a
+                // terminal, it fails with "Unable to use a TTY"
+                matches.is_present("terminal") || stdin_is_tty()
             };
             let stdin = if matches.is_present("stdin") {
                 if let Some(v) = matches.value_of("stdin") {
@@ -196,24 +284,48 @@ command!(
             } else {
                 true
             };
+            // the pty (and passing on window size changes) is set up by kubectl when it gets -t
             let it_arg = match (tty, stdin) {
-                (true, true) => "-it",
-                (true, false) => "-t",
-                (false, true) => "-i",
-                (false, false) => "",
+                (true, true) => Some("-it"),
+                (true, false) => Some("-t"),
+                (false, true) => Some("-i"),
+                (false, false) => None,
             };
+            if tty && !matches.is_present("terminal") && !stdin_is_tty() {
+                clickwriteln!(
+                    writer,
+                    "{}",
+                    Yellow.paint("Warning: asked for a TTY but click isn't running in a terminal")
+                );
+            }
+            if let Some(pod_name) = pod_name {
+                let pod = named_pod(env, pod_name)?;
+                check_container(&pod, container)?;
+                return do_exec(
+                    env,
+                    &pod,
+                    &context.name,
+                    &cmd,
+                    it_arg,
+                    &container,
+                    &matches.value_of("terminal"),
+                    matches.is_present("terminal"),
+                    writer,
+                );
+            }
             env.apply_to_selection(
                 writer,
                 Some(&env.click_config.range_separator),
                 |obj, writer| {
                     if obj.is_pod() {
+                        check_container(obj, container)?;
                         do_exec(
                             env,
                             obj,
                             &context.name,
                             &cmd,
                             it_arg,
-                            &matches.value_of("container"),
+                            &container,
                             &matches.value_of("terminal"),
                             matches.is_present("terminal"),
                             writer,
@@ -0,0 +1,357 @@
+// Copyright 2021 Databricks, Inc.
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+
+// http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use ansi_term::Colour::Yellow;
+use clap::{App, AppSettings, Arg};
+use k8s_openapi::http::{Request, StatusCode};
+use rustyline::completion::Pair as RustlinePair;
+use serde_json::Value;
+
+use crate::{
+    command::command_def::{exec_match, start_clap, Cmd},
+    completer,
+    env::Env,
+    error::ClickError,
+    kobj::{KObj, ObjType},
+    output::ClickWriter,
+};
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::io::Write;
+
+/// A kind of object `get` knows how to fetch
+struct Kind {
+    /// the first name is the canonical one, the rest are aliases
+    names: &'static [&'static str],
+    namespaced: bool,
+    /// the command that lists objects of this kind
+    list_cmd: fn() -> Box<dyn Cmd>,
+    /// build the object type from the object the api server returned
+    obj_type: fn(&Value) -> ObjType,
+}
+
+fn pod_type(pod: &Value) -> ObjType {
+    let containers = match pod.pointer("/spec/containers").and_then(|c| c.as_array()) {
+        Some(containers) => containers
+            .iter()
+            .filter_map(|cont| cont["name"].as_str().map(|name| name.to_string()))
+            .collect(),
+        None => vec![],
+    };
+    ObjType::Pod { containers }
+}
+
+macro_rules! kind {
+    ($names: expr, $namespaced: expr, $cmd: path, $typ: expr) => {
+        Kind {
+            names: $names,
+            namespaced: $namespaced,
+            list_cmd: || Box::new(<$cmd>::new()),
+            obj_type: |_| $typ,
+        }
+    };
+}
+
+const KINDS: &[Kind] = &[
+    Kind {
+        names: &["pods", "pod", "po"],
+        namespaced: true,
+        list_cmd: || Box::new(crate::command::pods::Pods::new()),
+        obj_type: pod_type,
+    },
+    kind!(
+        &["deployments", "deployment", "deploy"],
+        true,
+        crate::command::deployments::Deployments,
+        ObjType::Deployment
+    ),
+    kind!(
+        &["services", "service", "svc"],
+        true,
+        crate::command::services::Services,
+        ObjType::Service
+    ),
+    kind!(
+        &["replicasets", "replicaset", "rs"],
+        true,
+        crate::command::replicasets::ReplicaSets,
+        ObjType::ReplicaSet
+    ),
+    kind!(
+        &["statefulsets", "statefulset", "sts"],
+        true,
+        crate::command::statefulsets::StatefulSets,
+        ObjType::StatefulSet
+    ),
+    kind!(
+        &["daemonsets", "daemonset", "ds"],
+        true,
+        crate::command::daemonsets::DaemonSets,
+        ObjType::DaemonSet
+    ),
+    kind!(
+        &["configmaps", "configmap", "cm"],
+        true,
+        crate::command::configmaps::ConfigMaps,
+        ObjType::ConfigMap
+    ),
+    kind!(
+        &["secrets", "secret"],
+        true,
+        crate::command::secrets::Secrets,
+        ObjType::Secret
+    ),
+    kind!(
+        &["jobs", "job"],
+        true,
+        crate::command::jobs::Jobs,
+        ObjType::Job
+    ),
+    kind!(
+        &["cronjobs", "cronjob", "cj"],
+        true,
+        crate::command::cronjobs::CronJobs,
+        ObjType::CronJob
+    ),
+    kind!(
+        &["ingresses", "ingress", "ing"],
+        true,
+        crate::command::ingresses::Ingresses,
+        ObjType::Ingress
+    ),
+    kind!(
+        &["persistentvolumeclaims", "persistentvolumeclaim", "pvc"],
+        true,
+        crate::command::volumeclaims::PersistentVolumeClaims,
+        ObjType::PersistentVolumeClaim
+    ),
+    kind!(
+        &["nodes", "node", "no"],
+        false,
+        crate::command::nodes::Nodes,
+        ObjType::Node
+    ),
+    kind!(
+        &["namespaces", "namespace", "ns"],
+        false,
+        crate::command::namespaces::Namespaces,
+        ObjType::Namespace
+    ),
+    kind!(
+        &["persistentvolumes", "persistentvolume", "pv"],
+        false,
+        crate::command::volumes::PersistentVolumes,
+        ObjType::PersistentVolume
+    ),
+    kind!(
+        &["storageclasses", "storageclass", "sc"],
+        false,
+        crate::command::storage::StorageClasses,
+        ObjType::StorageClass
+    ),
+    #[cfg(feature = "argorollouts")]
+    kind!(
+        &["rollouts", "rollout", "ro"],
+        true,
+        crate::command::rollouts::Rollouts,
+        ObjType::Rollout
+    ),
+];
+
+/// The canonical name of every kind, for completion
+pub const KIND_NAMES: &[&str] = &[
+    "configmaps",
+    "cronjobs",
+    "daemonsets",
+    "deployments",
+    "ingresses",
+    "jobs",
+    "namespaces",
+    "nodes",
+    "persistentvolumeclaims",
+    "persistentvolumes",
+    "pods",
+    "replicasets",
+    "secrets",
+    "services",
+    "statefulsets",
+    "storageclasses",
+    #[cfg(feature = "argorollouts")]
+    "rollouts",
+];
+
+fn find_kind(name: &str) -> Result<&'static Kind, ClickError> {
+    let lower = name.to_lowercase();
+    match KINDS
+        .iter()
+        .find(|kind| kind.names.contains(&lower.as_str()))
+    {
+        Some(kind) => Ok(kind),
+        None => Err(ClickError::CommandError(format!(
+            "Unknown kind '{}'. Known kinds: {}",
+            name,
+            KIND_NAMES.join(", ")
+        ))),
+    }
+}
+
+/// Is kind_name (as a user would type it, like pods or pvc) a name for the kind k8s calls kind
+pub fn is_kind(kind_name: &str, kind: &str) -> bool {
+    find_kind(kind_name)
+        .map(|k| k.names.contains(&kind.to_lowercase().as_str()))
+        .unwrap_or(false)
+}
+
+/// The canonical name of the kind called kind_name, whether it's namespaced, and the api path to
+/// list objects of that kind. Namespaced kinds are listed in namespace, or in all namespaces if
+/// it's None
+pub fn kind_list_path(
+    kind_name: &str,
+    namespace: Option<&str>,
+    env: &Env,
+) -> Result<(&'static str, bool, String), ClickError> {
+    let kind = find_kind(kind_name)?;
+    let placeholder = KObj {
+        name: String::new(),
+        namespace: namespace
+            .filter(|_| kind.namespaced)
+            .map(|ns| ns.to_string()),
+        typ: (kind.obj_type)(&Value::Null),
+    };
+    let path = placeholder.api_path(env)?;
+    Ok((
+        kind.names[0],
+        kind.namespaced,
+        path.trim_end_matches('/').to_string(),
+    ))
+}
+
+/// Fetch the named object and make it the current object
+fn get_object(
+    env: &mut Env,
+    kind: &Kind,
+    name: &str,
+    writer: &mut ClickWriter,
+) -> Result<(), ClickError> {
+    // allow namespace/name to get something outside the current namespace
+    let (namespace, name) = match (kind.namespaced, name.split_once('/')) {
+        (true, Some((ns, name))) => (Some(ns.to_string()), name),
+        (true, None) => match env.namespace.as_ref() {
+            Some(ns) => (Some(ns.clone()), name),
+            None => {
+                return Err(ClickError::CommandError(format!(
+                    "No namespace set, use 'namespace' to set one or get {} <namespace>/{}",
+                    kind.names[0], name
+                )))
+            }
+        },
+        (false, _) => (None, name),
+    };
+    let placeholder = KObj {
+        name: name.to_string(),
+        namespace,
+        typ: (kind.obj_type)(&Value::Null),
+    };
+    let request = Request::get(placeholder.api_path(env)?)
+        .body(vec![])
+        .unwrap(); // safe, valid path
+    let response = env.run_on_context(|c| c.execute(request))?;
+    match response.status() {
+        StatusCode::OK => {}
+        StatusCode::NOT_FOUND => {
+            let location = match placeholder.namespace.as_ref() {
+                Some(ns) => format!(" in namespace {}", ns),
+                None => "".to_string(),
+            };
+            return Err(ClickError::CommandError(format!(
+                "{} {} not found{}",
+                placeholder.type_str(),
+                name,
+                location
+            )));
+        }
+        status => {
+            return Err(ClickError::CommandError(format!(
+                "Could not get {} {}: {}",
+                placeholder.type_str(),
+                name,
+                status
+            )));
+        }
+    }
+    let value: Value = serde_json::from_slice(response.body())?;
+    let obj = KObj {
+        typ: (kind.obj_type)(&value),
+        ..placeholder
+    };
+    clickwriteln!(writer, "Selected {} {}", obj.type_str(), obj.name());
+    env.set_current_obj(obj);
+    Ok(())
+}
+
+command!(
+    Get,
+    "get",
+    "Get an object by kind and name and make it the active object. Without a name, list objects \
+     of that kind (like running the list command for the kind)",
+    |clap: App<'static, 'static>| clap
+        // so flags for the list command aren't rejected
+        .setting(AppSettings::AllowLeadingHyphen)
+        .arg(
+            Arg::with_name("kind")
+                .help(
+                    "The kind of object to get. Accepts the usual kubectl short names, like po, \
+                     deploy, svc, rs, sts, cm, job or cj"
+                )
+                .required(true)
+                .index(1)
+        )
+        .arg(
+            Arg::with_name("args")
+                .help(
+                    "The name of the object to get (use <namespace>/<name> for objects outside \
+                     the current namespace). If no name is given, any arguments are passed on to \
+                     the list command for the kind (e.g. 'get po -l app=web')"
+                )
+                .required(false)
+                .multiple(true) // required for trailing_var_arg
+                .allow_hyphen_values(true)
+                .index(2)
+        ),
+    vec!["get"],
+    noop_complete!(),
+    no_named_complete!(),
+    |matches, env, writer| {
+        let kind = find_kind(matches.value_of("kind").unwrap())?; // safe, required
+        let args: Vec<&str> = matches
+            .values_of("args")
+            .map(|args| args.collect())
+            .unwrap_or_default();
+        match args.split_first() {
+            Some((name, rest)) if !name.starts_with('-') => {
+                if !rest.is_empty() {
+                    return Err(ClickError::CommandError(format!(
+                        "Unexpected arguments after name: {}",
+                        rest.join(" ")
+                    )));
+                }
+                get_object(env, kind, name, writer)
+            }
+            _ => (kind.list_cmd)().exec(env, &mut args.into_iter(), writer),
+        }
+    },
+    true,
+    Some(completer::get_args_completer)
+);
@@ -0,0 +1,159 @@
+// Copyright 2021 Databricks, Inc.
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+
+// http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use ansi_term::Colour::Yellow;
+use clap::{App, Arg, ArgMatches};
+
+use crate::{
+    command::command_def::{exec_match, show_arg, sort_arg, start_clap, Cmd},
+    command::{get_list_request_for_url, list_namespace, run_list_command, Extractor},
+    completer,
+    env::Env,
+    error::ClickError,
+    ingress::{
+        ingress_api_version, ExtensionsV1Beta1, Ingress, IngressApi, NetworkingV1,
+        NetworkingV1Beta1,
+    },
+    kobj::{KObj, ObjType},
+    output::ClickWriter,
+    table::CellSpec,
+};
+
+use std::array::IntoIter;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::io::Write;
+
+// the extractors are generic over the api version, so we can't build them once in a lazy_static
+fn ingress_extractors<V: IngressApi>() -> HashMap<String, Extractor<Ingress<V>>> {
+    let mut m: HashMap<String, Extractor<Ingress<V>>> = HashMap::new();
+    m.insert("Hosts".to_owned(), ingress_hosts);
+    m.insert("Address".to_owned(), ingress_address);
+    m.insert("Ports".to_owned(), ingress_ports);
+    m.insert("Rules".to_owned(), ingress_rules);
+    m.insert("Class".to_owned(), ingress_class);
+    m
+}
+
+const COL_MAP: &[(&str, &str)] = &[
+    ("name", "Name"),
+    ("hosts", "Hosts"),
+    ("address", "Address"),
+    ("ports", "Ports"),
+    ("rules", "Rules"),
+    ("age", "Age"),
+];
+
+const COL_FLAGS: &[&str] = &{ extract_first!(COL_MAP) };
+
+const EXTRA_COL_MAP: &[(&str, &str)] = &[
+    ("namespace", "Namespace"),
+    ("class", "Class"),
+    ("labels", "Labels"),
+];
+
+const EXTRA_COL_FLAGS: &[&str] = &{ extract_first!(EXTRA_COL_MAP) };
+
+fn ingress_to_kobj<V>(ingress: &Ingress<V>) -> KObj {
+    let meta = &ingress.metadata;
+    KObj {
+        name: meta.name.clone().unwrap_or_else(|| "<Unknown>".into()),
+        namespace: meta.namespace.clone(),
+        typ: ObjType::Ingress,
+    }
+}
+
+fn ingress_hosts<V>(ingress: &Ingress<V>) -> Option<CellSpec<'_>> {
+    Some(ingress.hosts().join(", ").into())
+}
+
+fn ingress_address<V>(ingress: &Ingress<V>) -> Option<CellSpec<'_>> {
+    Some(ingress.addresses().join(", ").into())
+}
+
+fn ingress_ports<V>(ingress: &Ingress<V>) -> Option<CellSpec<'_>> {
+    Some(ingress.ports().into())
+}
+
+fn ingress_rules<V>(ingress: &Ingress<V>) -> Option<CellSpec<'_>> {
+    Some(ingress.rule_lines().join("\n").into())
+}
+
+fn ingress_class<V>(ingress: &Ingress<V>) -> Option<CellSpec<'_>> {
+    ingress.class().map(|class| class.into())
+}
+
+fn list_ingresses<V: IngressApi + std::fmt::Debug>(
+    matches: ArgMatches,
+    env: &mut Env,
+    writer: &mut ClickWriter,
+) -> Result<(), ClickError> {
+    let url = match list_namespace(&matches, env) {
+        Some(ns) => format!("/apis/{}/namespaces/{}/ingresses", V::API_VERSION, ns),
+        None => format!("/apis/{}/ingresses", V::API_VERSION),
+    };
+    let (request, _response_body) =
+        get_list_request_for_url::<Ingress<V>>(url, Default::default())?;
+    let cols: Vec<&str> = COL_MAP.iter().map(|(_, col)| *col).collect();
+    let extractors = ingress_extractors::<V>();
+    run_list_command(
+        matches,
+        env,
+        writer,
+        cols,
+        request,
+        COL_MAP,
+        Some(EXTRA_COL_MAP),
+        Some(&extractors),
+        ingress_to_kobj,
+    )
+}
+
+list_command!(
+    Ingresses,
+    "ingresses",
+    "Get ingresses (in current namespace if set). Rules are shown as host → path → service:port",
+    super::COL_FLAGS,
+    super::EXTRA_COL_FLAGS,
+    |clap: App<'static, 'static>| {
+        clap.arg(
+            Arg::with_name("regex")
+                .short("r")
+                .long("regex")
+                .help("Filter ingresses by the specified regex")
+                .takes_value(true),
+        )
+        .arg(show_arg(EXTRA_COL_FLAGS, true))
+        .arg(sort_arg(COL_FLAGS, Some(EXTRA_COL_FLAGS)))
+        .arg(
+            Arg::with_name("reverse")
+                .short("R")
+                .long("reverse")
+                .help("Reverse the order of the returned list")
+                .takes_value(false),
+        )
+    },
+    vec!["ingresses", "ingress", "ing"],
+    noop_complete!(),
+    no_named_complete!(),
+    |matches, env, writer| {
+        match ingress_api_version(env)? {
+            NetworkingV1::API_VERSION => list_ingresses::<NetworkingV1>(matches, env, writer),
+            NetworkingV1Beta1::API_VERSION => {
+                list_ingresses::<NetworkingV1Beta1>(matches, env, writer)
+            }
+            _ => list_ingresses::<ExtensionsV1Beta1>(matches, env, writer),
+        }
+    }
+);
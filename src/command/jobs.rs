@@ -18,12 +18,12 @@ use k8s_openapi::api::batch::v1 as batch_api;
 
 use crate::{
     command::command_def::{exec_match, show_arg, sort_arg, start_clap, Cmd},
-    command::{format_duration, keyval_string, run_list_command, time_since, Extractor},
+    command::{keyval_string, list_namespace, run_list_command, Extractor},
     completer,
     env::Env,
     kobj::{KObj, ObjType},
     output::ClickWriter,
-    table::CellSpec,
+    table::{format_duration, time_since, CellSpec},
 };
 
 use std::array::IntoIter;
@@ -65,7 +65,7 @@ fn job_to_kobj(job: &batch_api::Job) -> KObj {
     KObj {
         name: meta.name.clone().unwrap_or_else(|| "<Unknown>".into()),
         namespace: meta.namespace.clone(),
-        typ: ObjType::StatefulSet,
+        typ: ObjType::Job,
     }
 }
 
@@ -87,7 +87,7 @@ fn job_duration(job: &batch_api::Job) -> Option<CellSpec<'_>> {
                         .find(|cond| {
                             // we assume a succeeded job has a completion_time so here,
                             // find the "failed" condition and find when it happened
-                            cond.type_ == "Failed" || cond.status == "True"
+                            cond.type_ == "Failed" && cond.status == "True"
                         })
                         .and_then(|cond| cond.last_transition_time.as_ref())
                 })
@@ -172,7 +172,7 @@ list_command!(
     noop_complete!(),
     IntoIter::new([]),
     |matches, env, writer| {
-        let (request, _response_body) = match &env.namespace {
+        let (request, _response_body) = match list_namespace(&matches, env) {
             Some(ns) => batch_api::Job::list_namespaced_job(ns, Default::default())?,
             None => batch_api::Job::list_job_for_all_namespaces(Default::default())?,
         };
@@ -0,0 +1,327 @@
+// Copyright 2021 Databricks, Inc.
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+
+// http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use ansi_term::Colour::Yellow;
+use clap::{App, Arg, ArgMatches};
+use k8s_openapi::http::{self, Request};
+use rustyline::completion::Pair as RustlinePair;
+use serde_json::{Map, Value};
+
+use crate::{
+    command::command_def::{exec_match, start_clap, Cmd},
+    command::{change_query_pairs, dry_run_arg, field_manager_arg, path_with_query, DryRun},
+    completer,
+    env::Env,
+    error::ClickError,
+    kobj::{KObj, ObjType},
+    output::ClickWriter,
+    values::val_str,
+};
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::io::Write;
+
+fn response_message(body: &[u8]) -> String {
+    serde_json::from_slice::<Value>(body)
+        .map(|val| val_str("/message", &val, "<No message>").into_owned())
+        .unwrap_or_else(|_| "<No message>".to_string())
+}
+
+/// Parse key=val (set) or key- (remove) into (key, Some(val)) or (key, None)
+fn parse_change(arg: &str) -> Result<(&str, Option<&str>), ClickError> {
+    let (key, val) = match arg.split_once('=') {
+        Some((key, val)) => (key, Some(val)),
+        None => match arg.strip_suffix('-') {
+            Some(key) => (key, None),
+            None => {
+                return Err(ClickError::CommandError(format!(
+                    "Invalid argument '{}', expected key=value or key-",
+                    arg
+                )))
+            }
+        },
+    };
+    if key.is_empty() {
+        Err(ClickError::CommandError(format!(
+            "Invalid argument '{}', key can't be empty",
+            arg
+        )))
+    } else {
+        Ok((key, val))
+    }
+}
+
+/// Build the patch for changes to the metadata field (labels or annotations) of obj. Errors if a
+/// change would alter an existing key and overwrite isn't set
+fn build_patch(
+    obj: &Value,
+    field: &str,
+    changes: &[(&str, Option<&str>)],
+    overwrite: bool,
+) -> Result<Value, ClickError> {
+    let existing = obj.get("metadata").and_then(|m| m.get(field));
+    let mut map = Map::new();
+    for (key, val) in changes.iter() {
+        if let Some(val) = val {
+            match existing.and_then(|e| e.get(key)).and_then(|v| v.as_str()) {
+                Some(cur) if cur != *val && !overwrite => {
+                    return Err(ClickError::CommandError(format!(
+                        "'{}' already has a value ({}), and --overwrite is not set",
+                        key, cur
+                    )));
+                }
+                _ => {}
+            }
+        }
+        let val = val
+            .map(|v| Value::String(v.to_string()))
+            .unwrap_or(Value::Null);
+        map.insert(key.to_string(), val);
+    }
+    let mut metadata = Map::new();
+    metadata.insert(field.to_string(), Value::Object(map));
+    let mut patch = Map::new();
+    patch.insert("metadata".to_string(), Value::Object(metadata));
+    Ok(Value::Object(patch))
+}
+
+/// What label and annotate were asked to do
+struct MetadataChange<'a> {
+    field: &'a str,
+    changes: Vec<(&'a str, Option<&'a str>)>,
+    overwrite: bool,
+    dry_run: DryRun,
+    field_manager: Option<&'a str>,
+}
+
+fn patch_metadata(
+    env: &Env,
+    obj: &KObj,
+    change: &MetadataChange,
+    writer: &mut ClickWriter,
+) -> Result<(), ClickError> {
+    let path = obj.api_path(env)?;
+    let request = Request::get(&path).body(vec![]).unwrap(); // safe, valid path
+    let response = env.run_on_context(|c| c.execute(request))?;
+    if !response.status().is_success() {
+        return Err(ClickError::CommandError(format!(
+            "Could not get object ({}): {}",
+            response.status(),
+            response_message(response.body())
+        )));
+    }
+    let current: Value = serde_json::from_slice(response.body())?;
+    let patch = build_patch(&current, change.field, &change.changes, change.overwrite)?;
+    let verb = if change.field == "labels" {
+        "labeled"
+    } else {
+        "annotated"
+    };
+    if change.dry_run == DryRun::Client {
+        clickwriteln!(
+            writer,
+            "{} {} {}{}, patch:",
+            obj.type_str(),
+            obj.name(),
+            verb,
+            change.dry_run.suffix()
+        );
+        clickwrite!(writer, "{}", serde_yaml::to_string(&patch)?);
+        return Ok(());
+    }
+
+    // custom resources don't support strategic merge patches, but for metadata a json merge patch
+    // does the same thing
+    let content_type = match obj.typ {
+        ObjType::Crd { .. } => "application/merge-patch+json",
+        _ => "application/strategic-merge-patch+json",
+    };
+    let query = change_query_pairs(change.dry_run, change.field_manager);
+    let request = Request::patch(path_with_query(&path, &query))
+        .header(http::header::CONTENT_TYPE, content_type)
+        .body(serde_json::to_vec(&patch)?)
+        .unwrap(); // safe, path built from valid names
+    let response = env.run_on_context(|c| c.execute(request))?;
+    if response.status().is_success() {
+        clickwriteln!(
+            writer,
+            "{} {} {}{}",
+            obj.type_str(),
+            obj.name(),
+            verb,
+            change.dry_run.suffix()
+        );
+        Ok(())
+    } else {
+        Err(ClickError::CommandError(format!(
+            "Patch failed ({}): {}",
+            response.status(),
+            response_message(response.body())
+        )))
+    }
+}
+
+/// The arguments label and annotate share
+fn change_args(
+    clap: App<'static, 'static>,
+    changes_help: &'static str,
+    overwrite_help: &'static str,
+) -> App<'static, 'static> {
+    clap.arg(
+        Arg::with_name("changes")
+            .help(changes_help)
+            .required(true)
+            .multiple(true)
+            .index(1),
+    )
+    .arg(
+        Arg::with_name("overwrite")
+            .long("overwrite")
+            .help(overwrite_help)
+            .takes_value(false),
+    )
+    .arg(dry_run_arg())
+    .arg(field_manager_arg())
+}
+
+fn run_patch(
+    matches: ArgMatches,
+    env: &mut Env,
+    writer: &mut ClickWriter,
+    field: &str,
+) -> Result<(), ClickError> {
+    let change = MetadataChange {
+        field,
+        changes: matches
+            .values_of("changes")
+            .unwrap() // safe, required
+            .map(parse_change)
+            .collect::<Result<Vec<_>, _>>()?,
+        overwrite: matches.is_present("overwrite"),
+        dry_run: DryRun::from_matches(&matches),
+        field_manager: matches.value_of("field_manager"),
+    };
+    let res = env.apply_to_selection(
+        writer,
+        Some(&env.click_config.range_separator),
+        |obj, writer| patch_metadata(env, obj, &change, writer),
+    );
+    env.invalidate_cache_for_selection();
+    res
+}
+
+command!(
+    Label,
+    "label",
+    "Add, change or remove labels on the active object",
+    |clap: App<'static, 'static>| change_args(
+        clap,
+        "The labels to set as key=value, or to remove as key-",
+        "Allow changing the value of labels that are already set"
+    )
+    .after_help(
+        "Examples:
+  # Label the active object with app=web
+  label app=web
+
+  # Change the value of an existing label, and remove another
+  label --overwrite tier=frontend canary-"
+    ),
+    vec!["label"],
+    noop_complete!(),
+    no_named_complete!(),
+    |matches, env, writer| run_patch(matches, env, writer, "labels")
+);
+
+command!(
+    Annotate,
+    "annotate",
+    "Add, change or remove annotations on the active object",
+    |clap: App<'static, 'static>| change_args(
+        clap,
+        "The annotations to set as key=value, or to remove as key-",
+        "Allow changing the value of annotations that are already set"
+    )
+    .after_help(
+        "Examples:
+  # Annotate the active object
+  annotate owner=team-a
+
+  # Remove an annotation
+  annotate owner-"
+    ),
+    vec!["annotate"],
+    noop_complete!(),
+    no_named_complete!(),
+    |matches, env, writer| run_patch(matches, env, writer, "annotations")
+);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn pod() -> Value {
+        json!({
+            "metadata": {
+                "name": "web",
+                "labels": {"app": "web", "tier": "frontend"}
+            }
+        })
+    }
+
+    #[test]
+    fn parse_changes() {
+        assert_eq!(parse_change("app=web").unwrap(), ("app", Some("web")));
+        assert_eq!(parse_change("app=").unwrap(), ("app", Some("")));
+        assert_eq!(parse_change("url=a=b").unwrap(), ("url", Some("a=b")));
+        assert_eq!(parse_change("app-").unwrap(), ("app", None));
+        assert!(parse_change("app").is_err());
+        assert!(parse_change("=web").is_err());
+        assert!(parse_change("-").is_err());
+    }
+
+    #[test]
+    fn remove() {
+        let patch = build_patch(&pod(), "labels", &[("tier", None)], false).unwrap();
+        assert_eq!(patch, json!({"metadata": {"labels": {"tier": null}}}));
+    }
+
+    #[test]
+    fn overwrite() {
+        let changes = [("app", Some("api"))];
+        let err = build_patch(&pod(), "labels", &changes, false).unwrap_err();
+        assert!(err.to_string().contains("--overwrite is not set"));
+        let patch = build_patch(&pod(), "labels", &changes, true).unwrap();
+        assert_eq!(patch, json!({"metadata": {"labels": {"app": "api"}}}));
+    }
+
+    #[test]
+    fn unchanged_and_new() {
+        // setting a key to the value it has, or adding a new one, doesn't need --overwrite
+        let changes = [("app", Some("web")), ("team", Some("infra"))];
+        let patch = build_patch(&pod(), "labels", &changes, false).unwrap();
+        assert_eq!(
+            patch,
+            json!({"metadata": {"labels": {"app": "web", "team": "infra"}}})
+        );
+        // objects without any labels yet
+        let patch = build_patch(&json!({"metadata": {}}), "annotations", &changes, false).unwrap();
+        assert_eq!(
+            patch,
+            json!({"metadata": {"annotations": {"app": "web", "team": "infra"}}})
+        );
+    }
+}
@@ -12,24 +12,27 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use ansi_term::Colour::Yellow;
+use ansi_term::Colour::{Red, Yellow};
 use chrono::offset::{Local, Utc};
 use chrono::DateTime;
 use clap::{App, Arg};
 use k8s_openapi::api::core::v1 as api;
-
+use regex::Regex;
 use reqwest::blocking::Response;
 use rustyline::completion::Pair as RustlinePair;
 use strfmt::strfmt;
 
 use crate::{
     command::command_def::{exec_match, start_clap, Cmd},
-    command::{parse_duration, valid_date, valid_duration, valid_u32},
+    command::{
+        editor_expression, parse_duration, valid_date, valid_duration, valid_regex, valid_u32,
+    },
     completer,
     env::Env,
     error::ClickError,
     kobj::{KObj, ObjType},
     output::ClickWriter,
+    values::val_str,
 };
 
 use std::cell::RefCell;
@@ -40,7 +43,10 @@ use std::path::PathBuf;
 use std::sync::atomic::Ordering;
 use std::sync::mpsc::{channel, RecvTimeoutError};
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
+
+/// How long to wait before reconnecting to a followed log stream that ended
+const RECONNECT_DELAY: Duration = Duration::from_secs(2);
 
 // logs helper commands
 fn pick_container<'a>(obj: &'a KObj, writer: &mut ClickWriter) -> &'a str {
@@ -73,6 +79,112 @@ fn write_logs_to_file(
     file.flush().map_err(ClickError::from)
 }
 
+/// If line matches grep, return it with the matches highlighted (when color is true). Returns None
+/// for lines that don't match
+fn grep_line(line: &str, grep: &Regex, color: bool) -> Option<String> {
+    if !grep.is_match(line) {
+        return None;
+    }
+    if !color {
+        return Some(line.to_string());
+    }
+    let mut highlighted = String::with_capacity(line.len());
+    let mut last = 0;
+    for mat in grep.find_iter(line) {
+        highlighted.push_str(&line[last..mat.start()]);
+        highlighted.push_str(&Red.bold().paint(mat.as_str()).to_string());
+        last = mat.end();
+    }
+    highlighted.push_str(&line[last..]);
+    Some(highlighted)
+}
+
+/// Print lines from reader as they arrive, until the stream ends or the user hits ^C. If grep is
+/// specified only matching lines are printed. Returns true if the stream ended by itself, false if
+/// it was interrupted
+fn print_logs(
+    env: &Env,
+    mut reader: BufReader<Response>,
+    grep: Option<&Regex>,
+    writer: &mut ClickWriter,
+) -> bool {
+    let (sender, receiver) = channel();
+    thread::spawn(move || {
+        loop {
+            let mut line = String::new();
+            if let Ok(amt) = reader.read_line(&mut line) {
+                if amt > 0 {
+                    if sender.send(line).is_err() {
+                        // probably user hit ctrl-c, just stop
+                        break;
+                    }
+                } else {
+                    break;
+                }
+            } else {
+                break;
+            }
+        }
+    });
+    while !env.ctrlcbool.load(Ordering::SeqCst) {
+        match receiver.recv_timeout(Duration::new(1, 0)) {
+            Ok(line) => match grep {
+                Some(grep) => {
+                    if let Some(line) = grep_line(&line, grep, writer.is_stdout()) {
+                        clickwrite!(writer, "{}", line);
+                    }
+                }
+                None => {
+                    clickwrite!(writer, "{}", line); // newlines already in line
+                }
+            },
+            Err(e) => {
+                if let RecvTimeoutError::Disconnected = e {
+                    return true;
+                }
+            }
+        }
+    }
+    false
+}
+
+/// Reconnect (once) to a followed log stream that ended. Only logs from after the stream ended are
+/// requested, so lines we've already printed aren't repeated
+fn reconnect_logs<'a>(
+    obj: &'a KObj,
+    env: &Env,
+    mut opts: api::ReadNamespacedPodLogOptional<'a>,
+    grep: Option<&Regex>,
+    writer: &mut ClickWriter,
+) -> Result<(), ClickError> {
+    let ended = Instant::now();
+    clickwriteln!(
+        writer,
+        "{}",
+        Yellow.paint("Log stream ended, reconnecting (the container may have restarted)")
+    );
+    thread::sleep(RECONNECT_DELAY);
+    if env.ctrlcbool.load(Ordering::SeqCst) {
+        return Ok(());
+    }
+    opts.tail_lines = None;
+    opts.since_seconds = Some(ended.elapsed().as_secs() as i64 + 1);
+    let (request, _resp) =
+        api::Pod::read_namespaced_pod_log(obj.name(), obj.namespace.as_ref().unwrap(), opts)?;
+    match env.run_on_context(|c| c.execute_reader(request, None)) {
+        Ok(lreader) => {
+            if print_logs(env, BufReader::new(lreader), grep, writer) {
+                clickwriteln!(writer, "{}", Yellow.paint("Log stream ended"));
+            }
+            Ok(())
+        }
+        Err(e) => Err(ClickError::CommandError(format!(
+            "Could not reconnect to log stream: {}",
+            e
+        ))),
+    }
+}
+
 #[allow(clippy::too_many_arguments)]
 fn do_logs<'a>(
     obj: &'a KObj,
@@ -82,6 +194,7 @@ fn do_logs<'a>(
     output_opt: Option<&str>,
     editor: bool,
     editor_opt: Option<&str>,
+    grep: Option<&Regex>,
     timeout: Option<Duration>,
     writer: &mut ClickWriter,
 ) -> Result<(), ClickError> {
@@ -94,7 +207,7 @@ fn do_logs<'a>(
     let logs_reader_res = env.run_on_context(|c| c.execute_reader(request, timeout));
     match logs_reader_res {
         Ok(lreader) => {
-            let mut reader = BufReader::new(lreader);
+            let reader = BufReader::new(lreader);
             env.ctrlcbool.store(false, Ordering::SeqCst);
             if let Some(output) = output_opt {
                 let mut fmtvars = HashMap::new();
@@ -119,21 +232,6 @@ fn do_logs<'a>(
                 }
             } else if editor {
                 // We're opening in an editor, save to a temp
-                let editor = if let Some(v) = editor_opt {
-                    v.to_owned()
-                } else if let Some(ref e) = env.click_config.editor {
-                    e.clone()
-                } else {
-                    match std::env::var("EDITOR") {
-                        Ok(ed) => ed,
-                        Err(e) => {
-                            return Err(ClickError::CommandError(format!(
-                                "Could not get EDITOR environment variable: {}",
-                                e
-                            )));
-                        }
-                    }
-                };
                 let tmpdir = match env.tempdir {
                     Ok(ref td) => td,
                     Err(ref e) => {
@@ -152,50 +250,28 @@ fn do_logs<'a>(
                 write_logs_to_file(env, &file_path, reader)?;
 
                 clickwriteln!(writer, "Logs downloaded, starting editor");
-                let expr = if editor.contains(' ') {
-                    // split the whitespace
-                    let mut eargs: Vec<&str> = editor.split_whitespace().collect();
-                    eargs.push(file_path.to_str().unwrap());
-                    duct::cmd(eargs[0], &eargs[1..])
-                } else {
-                    cmd!(editor, file_path)
-                };
-                expr.start()?;
+                editor_expression(env, editor_opt, &file_path)?.start()?;
                 Ok(())
             } else {
-                let (sender, receiver) = channel();
-                thread::spawn(move || {
-                    loop {
-                        let mut line = String::new();
-                        if let Ok(amt) = reader.read_line(&mut line) {
-                            if amt > 0 {
-                                if sender.send(line).is_err() {
-                                    // probably user hit ctrl-c, just stop
-                                    break;
-                                }
-                            } else {
-                                break;
-                            }
-                        } else {
-                            break;
-                        }
-                    }
-                });
-                while !env.ctrlcbool.load(Ordering::SeqCst) {
-                    match receiver.recv_timeout(Duration::new(1, 0)) {
-                        Ok(line) => {
-                            clickwrite!(writer, "{}", line); // newlines already in line
-                        }
-                        Err(e) => {
-                            if let RecvTimeoutError::Disconnected = e {
-                                break;
-                            }
-                        }
-                    }
+                let ended = print_logs(env, reader, grep, writer);
+                if ended && opts.follow == Some(true) && opts.previous != Some(true) {
+                    // the stream ended without a ^C, which usually means the container restarted.
+                    // a previous container's logs end because it's gone, so there's nothing to
+                    // reconnect to
+                    reconnect_logs(obj, env, opts, grep, writer)?;
                 }
                 Ok(())
             }
         }
+        Err(ClickError::Reqwest(_, Some(body))) if opts.previous == Some(true) => {
+            // most likely the container hasn't restarted, so there's no previous instance
+            Err(ClickError::CommandError(format!(
+                "No previous logs for container {} in {}: {}",
+                cont,
+                obj.name(),
+                val_str("/message", &body, "no message returned")
+            )))
+        }
         Err(e) => Err(e),
     }
 }
@@ -212,6 +288,15 @@ command!(
                     .required(false)
                     .index(1),
             )
+            .arg(
+                Arg::with_name("container_opt")
+                    .short("c")
+                    .long("container")
+                    .value_name("CONTAINER")
+                    .help("Specify which container to get logs from (same as the positional arg)")
+                    .conflicts_with("container")
+                    .takes_value(true),
+            )
             .arg(
                 Arg::with_name("follow")
                     .short("f")
@@ -226,14 +311,20 @@ command!(
                     .short("t")
                     .long("tail")
                     .validator(valid_u32)
-                    .help("Number of lines from the end of the logs to show")
+                    .help(
+                        "Number of lines from the end of the logs to show. If --since is also \
+                         specified, this is the number of lines from the end of that range",
+                    )
                     .takes_value(true),
             )
             .arg(
                 Arg::with_name("previous")
                     .short("p")
                     .long("previous")
-                    .help("Return previous terminated container logs")
+                    .help(
+                        "Return the logs of the previous (terminated) instance of the \
+                         container, e.g. to see why it's in CrashLoopBackOff",
+                    )
                     .takes_value(false),
             )
             .arg(
@@ -267,6 +358,20 @@ command!(
                     )
                     .takes_value(false),
             )
+            .arg(
+                Arg::with_name("grep")
+                    .long("grep")
+                    .short("g")
+                    .value_name("REGEX")
+                    .conflicts_with("editor")
+                    .conflicts_with("output")
+                    .validator(valid_regex)
+                    .help(
+                        "Only print lines matching the specified regex, with matches \
+                         highlighted. Highlighting is skipped when output is piped or redirected",
+                    )
+                    .takes_value(true),
+            )
             .arg(
                 Arg::with_name("editor")
                     .long("editor")
@@ -309,7 +414,11 @@ command!(
     },
     vec!["logs"],
     vec![&completer::container_completer],
-    no_named_complete!(),
+    std::iter::once((
+        "container".to_string(),
+        completer::container_completer as fn(&str, &Env) -> Vec<RustlinePair>
+    ))
+    .collect(),
     #[allow(clippy::cognitive_complexity)]
     |matches, env, writer| {
         let mut opts: api::ReadNamespacedPodLogOptional = Default::default();
@@ -355,6 +464,8 @@ command!(
         if matches.is_present("timestamps") {
             opts.timestamps = Some(true);
         }
+        // already validated
+        let grep = matches.value_of("grep").map(|re| Regex::new(re).unwrap());
 
         env.apply_to_selection(
             writer,
@@ -365,10 +476,13 @@ command!(
                         obj,
                         env,
                         opts,
-                        matches.value_of("container"),
+                        matches
+                            .value_of("container")
+                            .or_else(|| matches.value_of("container_opt")),
                         matches.value_of("output"),
                         matches.is_present("editor"),
                         matches.value_of("editor"),
+                        grep.as_ref(),
                         timeout,
                         writer,
                     )
@@ -12,61 +12,197 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use chrono::offset::Utc;
-use chrono::{DateTime, Duration};
-use clap::ArgMatches;
+use ansi_term::Style;
+use chrono::DateTime;
+use clap::{Arg, ArgMatches};
 use humantime::parse_duration;
 use k8s_openapi::{
-    apimachinery::pkg::apis::meta::v1::ObjectMeta,
+    apimachinery::pkg::apis::meta::v1::{ListMeta, ObjectMeta},
     http::{self, Request},
     List, ListOptional, ListResponse, ListableResource, Metadata, RequestError, ResponseBody,
 };
 use prettytable::{Cell, Row};
 use regex::Regex;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
 use crate::env::Env;
 use crate::error::ClickError;
 use crate::kobj::KObj;
 use crate::output::ClickWriter;
-use crate::table::CellSpec;
+use crate::parser::{
+    parse_field_selector, parse_jsonpath, parse_label_selector, split_sort_keys, JsonPathSeg,
+};
+use crate::table::{sort_by_keys, time_since, title_cell, CellSpec, SortKeyCmp};
+use crate::template::Template;
+use crate::values::{jsonpath_cmp, jsonpath_str, jsonpath_values};
 
 use std::borrow::Cow;
-use std::collections::{BTreeMap, HashMap};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::ffi::OsStr;
 use std::fmt::Debug;
 use std::io::{stderr, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
 
 #[macro_use]
 pub mod command_def;
 
 pub mod alias; // commands for alias/unalias
+pub mod apply; // command to create or update objects from yaml
+pub mod bookmark; // command to save and switch to context and namespace pairs
 pub mod click; // commands internal to click (setting config values, etc)
 pub mod configmaps; // commands relating to configmaps
+pub mod count; // command to count objects by kind
+pub mod cp; // command to copy files to and from pods
 pub mod crds; // commands to query crd created objects
+pub mod cronjobs; // commands relating to cronjobs
+pub mod daemonsets; // commands for daemonsets
 pub mod delete; // command to delete objects
-pub mod deployments; // command to list deployments
+pub mod deployments; // commands relating to deployments
 pub mod describe; // the describe command
+pub mod diff; // command to diff two objects
+pub mod doctor; // command to diagnose config and connection problems
+pub mod edit; // command to edit objects in an editor
 pub mod events; // commands to print events
 pub mod exec; // command to exec into pods
+pub mod get; // command to get an object by kind and name
+pub mod ingresses; // commands relating to ingresses
 pub mod jobs; // commands relating to jobs
+pub mod label; // commands to change labels and annotations
 pub mod logs; // command to get pod logs
 pub mod namespaces; // commands relating to namespaces
 pub mod nodes; // commands relating to nodes
 pub mod pods; //commands relating to pods
 pub mod portforwards; // commands for forwarding ports
+pub mod raw; // command to send requests to any api path
 pub mod replicasets; // commands relating to relicasets
+pub mod scale; // command to scale deployments, replicasets and statefulsets
 pub mod secrets; // commands for secrets
 pub mod services; // commands for services
 pub mod statefulsets; // commands for statefulsets
 pub mod storage; // commands relating to storage objects (like storageclass)
+pub mod stream; // command to stream changes to objects as they happen
+pub mod top; // command to show resource usage from metrics-server
+pub mod trigger; // command to manually run a cronjob
+pub mod volumeclaims; // commands relating to persistent volume claims
 pub mod volumes; // commands relating to volumes
+pub mod yank; // command to copy the names of the active objects to the clipboard
 
 #[cfg(feature = "argorollouts")]
 pub mod rollouts;
 
+/// How much longer than --request-timeout we wait for the server to answer
+const REQUEST_TIMEOUT_GRACE_SECS: u64 = 2;
+
+/// How many times a chunked list starts again from the first page because its continue token
+/// expired, before giving up
+const MAX_PAGINATION_RESTARTS: u32 = 3;
+
 // utility types
 type RowSpec<'a> = Vec<CellSpec<'a>>;
 type Extractor<T> = fn(&T) -> Option<CellSpec<'_>>;
+/// Columns from the click config that show a field of each object, keyed by column title
+pub type FieldColumns = HashMap<String, Vec<JsonPathSeg>>;
+
+/// The ways the output of list commands and describe can be formatted, via -o/--output
+pub enum OutputFormat {
+    Json,
+    Yaml,
+    JsonPath(Vec<JsonPathSeg>),
+    GoTemplate(Template),
+    /// The normal table, plus the extra columns (other than labels) the command has
+    Wide,
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<OutputFormat, String> {
+        match s {
+            "json" => Ok(OutputFormat::Json),
+            "yaml" => Ok(OutputFormat::Yaml),
+            "wide" => Ok(OutputFormat::Wide),
+            _ => {
+                if let Some(expr) = s.strip_prefix("jsonpath=") {
+                    parse_jsonpath(expr).map(OutputFormat::JsonPath)
+                } else if let Some(template) = s.strip_prefix("go-template=") {
+                    Template::parse(template).map(OutputFormat::GoTemplate)
+                } else {
+                    Err(format!(
+                        "Unknown output format '{}', expected json, yaml, wide, jsonpath=<expr> \
+                         or go-template=<template>",
+                        s
+                    ))
+                }
+            }
+        }
+    }
+}
+
+impl OutputFormat {
+    /// Get the output format specified by -o/--output, if any. Expects the arg to have been
+    /// validated already
+    pub fn from_matches(matches: &ArgMatches) -> Option<OutputFormat> {
+        matches.value_of("output").and_then(|s| s.parse().ok())
+    }
+
+    /// Like from_matches, but ignores formats that just change which columns are printed
+    pub fn structured_from_matches(matches: &ArgMatches) -> Option<OutputFormat> {
+        OutputFormat::from_matches(matches).filter(|f| !matches!(f, OutputFormat::Wide))
+    }
+
+    /// Write value to writer in this format. json and yaml are written without color, so they can
+    /// be consumed by other tools
+    pub fn write_value(&self, value: &serde_json::Value, writer: &mut ClickWriter) {
+        match self {
+            OutputFormat::Json => {
+                if let Err(e) = serde_json::to_writer_pretty(&mut *writer, value) {
+                    clickwriteln!(writer, "Could not write json: {}", e);
+                }
+                clickwriteln!(writer, "");
+            }
+            OutputFormat::Yaml => {
+                if let Err(e) = writer.print_yaml(value) {
+                    clickwriteln!(writer, "Could not write yaml: {}", e);
+                }
+                clickwriteln!(writer, "");
+            }
+            OutputFormat::JsonPath(path) => {
+                clickwriteln!(writer, "{}", crate::values::jsonpath_str(path, value));
+            }
+            // like kubectl, no newline is added, the template has to include any it wants
+            OutputFormat::GoTemplate(template) => match template.render(value) {
+                Ok(rendered) => clickwrite!(writer, "{}", rendered),
+                Err(e) => clickwriteln!(writer, "Error rendering go-template: {}", e),
+            },
+            // wide only changes which columns tables have, see structured_from_matches
+            OutputFormat::Wide => {}
+        }
+    }
+
+    /// Write the objects from a list. json, yaml and go-template get a single List object like
+    /// kubectl does, jsonpath is evaluated against each one
+    pub fn write_list(&self, values: Vec<serde_json::Value>, writer: &mut ClickWriter) {
+        match self {
+            OutputFormat::Json | OutputFormat::Yaml | OutputFormat::GoTemplate(_) => {
+                let list = serde_json::json!({
+                    "apiVersion": "v1",
+                    "kind": "List",
+                    "items": values,
+                });
+                self.write_value(&list, writer);
+            }
+            OutputFormat::JsonPath(_) | OutputFormat::Wide => {
+                for value in values.iter() {
+                    self.write_value(value, writer);
+                }
+            }
+        }
+    }
+}
 
 fn mapped_val(key: &str, map: &[(&'static str, &'static str)]) -> Option<&'static str> {
     for (map_key, val) in map.iter() {
@@ -77,12 +213,174 @@ fn mapped_val(key: &str, map: &[(&'static str, &'static str)]) -> Option<&'stati
     None
 }
 
+/// The fields the api server supports in field selectors for kind, beyond metadata.name and
+/// metadata.namespace which every kind supports
+fn extra_selector_fields(kind: &str) -> &'static [&'static str] {
+    match kind {
+        "Pod" => &[
+            "spec.nodeName",
+            "spec.restartPolicy",
+            "spec.schedulerName",
+            "spec.serviceAccountName",
+            "status.phase",
+            "status.podIP",
+            "status.nominatedNodeName",
+        ],
+        "Node" => &["spec.unschedulable"],
+        "Namespace" => &["status.phase"],
+        "Secret" => &["type"],
+        "ReplicaSet" => &["status.replicas"],
+        "Job" => &["status.successful"],
+        "Event" => &[
+            "involvedObject.kind",
+            "involvedObject.namespace",
+            "involvedObject.name",
+            "involvedObject.uid",
+            "involvedObject.apiVersion",
+            "involvedObject.resourceVersion",
+            "involvedObject.fieldPath",
+            "reason",
+            "source",
+            "type",
+        ],
+        _ => &[],
+    }
+}
+
+/// Parse a field selector and check kind supports all the fields in it, returning the selector in
+/// canonical form
+fn field_selector_for_kind(selector: &str, kind: &str) -> Result<String, ClickError> {
+    let reqs = parse_field_selector(selector).map_err(ClickError::CommandError)?;
+    let extra = extra_selector_fields(kind);
+    for req in reqs.iter() {
+        let field = req.field.as_str();
+        if field != "metadata.name" && field != "metadata.namespace" && !extra.contains(&field) {
+            let mut supported = vec!["metadata.name", "metadata.namespace"];
+            supported.extend_from_slice(extra);
+            return Err(ClickError::CommandError(format!(
+                "Field '{}' can't be used in a field selector for {}s. Supported fields: {}",
+                field,
+                kind.to_lowercase(),
+                supported.join(", ")
+            )));
+        }
+    }
+    let reqs: Vec<String> = reqs.iter().map(|r| r.to_string()).collect();
+    Ok(reqs.join(","))
+}
+
+/// Work out the columns configured for a kind in the click config. Each entry is either one of
+/// the kind's columns, or a field of the object as a jsonpath like metadata.labels.app, optionally
+/// with a title as TITLE:jsonpath. Returns the column titles, and the paths of the field columns
+fn configured_columns(
+    entries: &[String],
+    kind: &str,
+    col_map: &[(&'static str, &'static str)],
+    extra_col_map: Option<&[(&'static str, &'static str)]>,
+) -> Result<(Vec<String>, FieldColumns), ClickError> {
+    let mut known: Vec<&str> = col_map
+        .iter()
+        .chain(extra_col_map.unwrap_or(&[]).iter())
+        .map(|(_, col)| *col)
+        .collect();
+    // build_specs can always extract these
+    for col in ["Name", "Age"].iter() {
+        if !known.contains(col) {
+            known.push(col);
+        }
+    }
+    let mut cols = vec![];
+    let mut fields = FieldColumns::new();
+    for entry in entries.iter() {
+        let entry = entry.trim();
+        let builtin = known.iter().find(|col| {
+            col.eq_ignore_ascii_case(entry) || col.replace(' ', "").eq_ignore_ascii_case(entry)
+        });
+        if let Some(col) = builtin {
+            cols.push(col.to_string());
+            continue;
+        }
+        let (title, path) = match entry.find(':') {
+            Some(idx) if !entry[..idx].contains(['.', '[', '{']) => {
+                (Some(&entry[..idx]), &entry[idx + 1..])
+            }
+            _ => (None, entry),
+        };
+        if title.is_none() && !path.contains(['.', '[', '{']) {
+            return Err(ClickError::CommandError(format!(
+                "Unknown column '{}' for {} in the click config, expected one of {}, or a field \
+                 like metadata.labels.app",
+                entry,
+                kind,
+                known.join(", ")
+            )));
+        }
+        let segs = parse_jsonpath(path).map_err(|e| {
+            ClickError::CommandError(format!(
+                "Invalid column '{}' for {} in the click config: {}",
+                entry, kind, e
+            ))
+        })?;
+        let title = match title {
+            Some(title) => title.to_string(),
+            None => match segs.last() {
+                Some(JsonPathSeg::Field(field)) => uppercase_first(field),
+                _ => path.to_string(),
+            },
+        };
+        cols.push(title.clone());
+        fields.insert(title, segs);
+    }
+    Ok((cols, fields))
+}
+
+/// How to sort by key, which is any column in col_map or extra_col_map, or a jsonpath. Sorting by
+/// an extra column shows it, so its flag is added to flags
+fn sort_func<'a, T>(
+    key: &str,
+    col_map: &[(&'static str, &'static str)],
+    extra_col_map: Option<&[(&'a str, &'static str)]>,
+    flags: &mut Vec<&'a str>,
+) -> Result<command_def::SortFunc<T>, ClickError>
+where
+    T: Metadata<Ty = ObjectMeta>,
+{
+    if key.starts_with(['{', '.', '$']) {
+        return Ok(command_def::SortFunc::JsonPath(
+            parse_jsonpath(key).map_err(ClickError::CommandError)?,
+        ));
+    }
+    let lower = key.to_lowercase();
+    if lower == "age" {
+        return Ok(command_def::SortFunc::Pre(command_def::PreExtractSort {
+            cmp: command_def::age_cmp,
+        }));
+    }
+    if let Some(col) = mapped_val(&lower, col_map) {
+        return Ok(command_def::SortFunc::Post(col));
+    }
+    if let Some((flag, col)) = extra_col_map.and_then(|ecm| ecm.iter().find(|(f, _)| *f == lower)) {
+        flags.push(flag);
+        return Ok(command_def::SortFunc::Post(col));
+    }
+    let cols: Vec<&str> = col_map
+        .iter()
+        .chain(extra_col_map.unwrap_or(&[]).iter())
+        .map(|(flag, _)| *flag)
+        .collect();
+    Err(ClickError::CommandError(format!(
+        "Can't sort by '{}', expected one of {}, or a jsonpath like {{.metadata.name}}",
+        key,
+        cols.join(", ")
+    )))
+}
+
 #[allow(clippy::too_many_arguments)] // factoring this out into structs just makes it worse
 pub fn run_list_command<T, F>(
     matches: ArgMatches,
     env: &mut Env,
     writer: &mut ClickWriter,
-    mut cols: Vec<&str>,
+    cols: Vec<&str>,
     request: Request<Vec<u8>>,
     col_map: &[(&'static str, &'static str)],
     extra_col_map: Option<&[(&'static str, &'static str)]>,
@@ -90,7 +388,7 @@ pub fn run_list_command<T, F>(
     get_kobj: F,
 ) -> Result<(), ClickError>
 where
-    T: ListableResource + Metadata<Ty = ObjectMeta> + for<'de> Deserialize<'de> + Debug,
+    T: ListableResource + Metadata<Ty = ObjectMeta> + for<'de> Deserialize<'de> + Serialize + Debug,
     F: Fn(&T) -> KObj,
 {
     let regex = match crate::table::get_regex(&matches) {
@@ -101,67 +399,588 @@ where
         }
     };
 
-    let list_res = env.run_on_context::<_, List<T>>(|c| c.execute_list(request));
-    if list_res.is_err() {
-        env.clear_last_objs();
-    }
-    let list = list_res?;
-
     let mut flags: Vec<&str> = match matches.values_of("show") {
         Some(v) => v.collect(),
         None => vec![],
     };
+    if let Some(OutputFormat::Wide) = OutputFormat::from_matches(&matches) {
+        flags.push("all");
+    }
 
-    let sort = matches
-        .value_of("sort")
-        .map(|s| match s.to_lowercase().as_str() {
-            "age" => {
-                let sf = command_def::PreExtractSort {
-                    cmp: command_def::age_cmp,
-                };
-                command_def::SortFunc::Pre(sf)
+    // --sort-by takes any column --sort does, or a jsonpath, and can be given more than once
+    let sort_keys: Vec<&str> = match matches.values_of("sort_by") {
+        Some(by) => {
+            if matches.is_present("sort") {
+                return Err(ClickError::CommandError(
+                    "Only one of --sort and --sort-by can be used".to_string(),
+                ));
             }
-            other => {
-                if let Some(col) = mapped_val(other, col_map) {
-                    command_def::SortFunc::Post(col)
-                } else if let Some(ecm) = extra_col_map {
-                    let mut func = None;
-                    for (flag, col) in ecm.iter() {
-                        if flag.eq(&other) {
-                            flags.push(flag);
-                            func = Some(command_def::SortFunc::Post(col));
-                        }
-                    }
-                    match func {
-                        Some(f) => f,
-                        None => panic!("Shouldn't be allowed to ask to sort by: {}", other),
-                    }
-                } else {
-                    panic!("Shouldn't be allowed to ask to sort by: {}", other);
-                }
-            }
-        });
+            by.flat_map(split_sort_keys).collect()
+        }
+        None => matches.value_of("sort").into_iter().collect(),
+    };
+    let sort = sort_keys
+        .iter()
+        .map(|key| sort_func(key, col_map, extra_col_map, &mut flags))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    // columns from the click config replace the command's usual ones
+    let configured = env
+        .click_config
+        .columns
+        .iter()
+        .find(|(kind, _)| crate::command::get::is_kind(kind, T::KIND))
+        .map(|(kind, entries)| configured_columns(entries, kind, col_map, extra_col_map))
+        .transpose()?;
+    let (configured_cols, fields) = configured.unwrap_or_default();
+    let mut cols: Vec<&str> = if configured_cols.is_empty() {
+        cols
+    } else {
+        configured_cols.iter().map(|col| col.as_str()).collect()
+    };
 
     if let Some(ecm) = extra_col_map {
         // if we're not in a namespace, we want to add a namespace col if it's in extra_col_map
-        if env.namespace.is_none() && mapped_val("namespace", ecm).is_some() {
+        if list_namespace(&matches, env).is_none() && mapped_val("namespace", ecm).is_some() {
             flags.push("namespace");
         }
 
         command_def::add_extra_cols(&mut cols, matches.is_present("labels"), flags, ecm);
     }
 
+    let output = OutputFormat::structured_from_matches(&matches);
+    let chunk_size = matches
+        .value_of("chunk_size")
+        .map(|s| s.parse::<u32>().unwrap()); // safe, validated
+    let fetch_limit = matches.value_of("limit").map(|s| s.parse::<u32>().unwrap()); // safe, validated
+
+    let selector = matches.value_of("selector").map(|sel| {
+        // already validated, so parse can't fail
+        let reqs: Vec<String> = parse_label_selector(sel)
+            .unwrap()
+            .iter()
+            .map(|r| r.to_string())
+            .collect();
+        reqs.join(",")
+    });
+    let field_selector = match matches.value_of("field_selector") {
+        Some(sel) => Some(field_selector_for_kind(sel, T::KIND)?),
+        None => None,
+    };
+    let mut query = vec![];
+    if let Some(sel) = selector.as_ref() {
+        query.push(("labelSelector", sel.as_str()));
+    }
+    if let Some(sel) = field_selector.as_ref() {
+        query.push(("fieldSelector", sel.as_str()));
+    }
+    let request = if query.is_empty() {
+        request
+    } else {
+        add_query_pairs(&request, &query)?
+    };
+    // notes like these would get in the way of scripts
+    let show_notes = output.is_none() && !matches.is_present("no_headers");
+    if show_notes {
+        // make it obvious the list is filtered
+        if let Some(sel) = selector.as_ref() {
+            clickwriteln!(
+                writer,
+                "{}",
+                Style::new().bold().paint(format!("Selector: {}", sel))
+            );
+        }
+        if let Some(sel) = field_selector.as_ref() {
+            clickwriteln!(
+                writer,
+                "{}",
+                Style::new()
+                    .bold()
+                    .paint(format!("Field selector: {}", sel))
+            );
+        }
+    }
+
+    let max_items = match matches.value_of("max_items") {
+        Some(n) => n.parse::<u32>().unwrap(), // safe, validated
+        None => env.click_config.max_items,
+    };
+    let max_items = if max_items == 0 {
+        None
+    } else {
+        Some(max_items as usize)
+    };
+
+    let uri = match fetch_limit {
+        // a limited list is only the first few items, so mustn't be mistaken for the whole list
+        Some(limit) => {
+            path_with_query(&request.uri().to_string(), &[("limit", &limit.to_string())])
+        }
+        None => request.uri().to_string(),
+    };
+    let cache_key = env.list_cache_key(T::KIND, &uri);
+    // added after working out the uri, so the timeout doesn't stop us using a cached list
+    let timeout_secs = matches.value_of("request_timeout");
+    let (request, timeout) = match timeout_secs {
+        Some(secs) => (
+            add_query_pairs(&request, &[("timeoutSeconds", secs)])?,
+            Some(client_timeout(secs.parse().unwrap())), // safe, validated
+        ),
+        None => (request, None),
+    };
+    let cached: Option<(List<T>, Duration)> = if matches.is_present("refresh") {
+        None
+    } else {
+        cache_key
+            .as_ref()
+            .and_then(|key| env.get_cached_list(key, &uri))
+            .and_then(|(val, age)| {
+                serde_json::from_value(val.clone())
+                    .ok()
+                    .map(|list| (list, age))
+            })
+    };
+
+    let (list, cached_age) = match cached {
+        Some((list, age)) => (list, Some(age)),
+        None => {
+            let list_res = match chunk_size {
+                Some(limit) => {
+                    if sort.is_empty()
+                        && output.is_none()
+                        && max_items.is_none()
+                        && !matches.is_present("reverse")
+                        && !matches.is_present("buffer")
+                    {
+                        // nothing requires the whole list, so print pages as they arrive
+                        return stream_list_result(
+                            env,
+                            writer,
+                            cols,
+                            request,
+                            limit,
+                            fetch_limit,
+                            timeout,
+                            matches.is_present("no_headers"),
+                            extractors,
+                            &fields,
+                            regex,
+                            get_kobj,
+                        );
+                    }
+                    fetch_all_pages(env, &request, limit, fetch_limit, timeout)
+                }
+                None => match fetch_limit {
+                    Some(limit) => fetch_all_pages(env, &request, limit, Some(limit), timeout),
+                    None => env.run_on_context::<_, List<T>>(|c| {
+                        c.execute_list_with_timeout(request, timeout)
+                    }),
+                },
+            };
+            if list_res.is_err() {
+                env.clear_last_objs();
+            }
+            let list = list_res?;
+            if let Some(key) = cache_key {
+                env.cache_list(key, uri, &list);
+            }
+            (list, None)
+        }
+    };
+
+    let more_on_server = fetch_limit.is_some() && next_page_token(&list.metadata).is_some();
     handle_list_result(
         env,
         writer,
         cols,
         list,
         extractors,
+        &fields,
         regex,
         sort,
+        matches.is_present("sort_reverse"),
         matches.is_present("reverse"),
+        max_items,
+        output,
+        matches.is_present("no_headers"),
         get_kobj,
-    )
+    )?;
+    if more_on_server && show_notes {
+        more_items_note(writer);
+    }
+    if let (Some(age), true) = (cached_age, show_notes) {
+        clickwriteln!(
+            writer,
+            "{}",
+            Style::new()
+                .dimmed()
+                .paint(format!("(cached {}s ago)", age.as_secs()))
+        );
+    }
+    Ok(())
+}
+
+/// Add the specified query parameters to path (which may already have some)
+pub fn path_with_query(path: &str, pairs: &[(&str, &str)]) -> String {
+    if pairs.is_empty() {
+        return path.to_string();
+    }
+    let mut query = url::form_urlencoded::Serializer::new(String::new());
+    for (key, value) in pairs.iter() {
+        query.append_pair(key, value);
+    }
+    let sep = if path.ends_with('?') || path.ends_with('&') {
+        ""
+    } else if path.contains('?') {
+        "&"
+    } else {
+        "?"
+    };
+    format!("{}{}{}", path, sep, query.finish())
+}
+
+/// Build a copy of request with the specified query parameters added
+fn add_query_pairs(
+    request: &Request<Vec<u8>>,
+    pairs: &[(&str, &str)],
+) -> Result<Request<Vec<u8>>, ClickError> {
+    Request::get(path_with_query(&request.uri().to_string(), pairs))
+        .body(vec![])
+        .map_err(|e| ClickError::RequestError(RequestError::Http(e)))
+}
+
+/// How long to wait for a list fetched with --request-timeout SECS. We wait a little longer than
+/// the server was asked to, so we get its response rather than just giving up
+fn client_timeout(secs: u64) -> Duration {
+    Duration::from_secs(secs + REQUEST_TIMEOUT_GRACE_SECS)
+}
+
+/// Build a copy of request that fetches a single page of at most limit items, starting from
+/// continue_token if specified
+fn paged_request(
+    request: &Request<Vec<u8>>,
+    limit: u32,
+    continue_token: Option<&str>,
+) -> Result<Request<Vec<u8>>, ClickError> {
+    let limit = limit.to_string();
+    let mut pairs = vec![("limit", limit.as_str())];
+    if let Some(token) = continue_token {
+        pairs.push(("continue", token));
+    }
+    add_query_pairs(request, &pairs)
+}
+
+/// Get the continue token out of a list's metadata, if there are more pages to fetch
+fn next_page_token(metadata: &ListMeta) -> Option<String> {
+    metadata
+        .continue_
+        .as_ref()
+        .filter(|token| !token.is_empty())
+        .cloned()
+}
+
+/// Note that there are more items on the server than were fetched because of --limit
+fn more_items_note(writer: &mut ClickWriter) {
+    clickwriteln!(
+        writer,
+        "{}",
+        Style::new()
+            .dimmed()
+            .paint("(there are more items, use a higher --limit to see them)")
+    );
+}
+
+/// True if err is because a continue token expired, which happens when the list changes a lot
+/// while we're paging through it. The only way on is to start again
+fn continue_expired(err: &ClickError) -> bool {
+    matches!(err, ClickError::Status(410, _))
+}
+
+/// How many items to ask for in the next page: page_size, but no more than are still needed to
+/// reach fetch_limit
+fn next_page_size(page_size: u32, fetch_limit: Option<u32>, fetched: usize) -> u32 {
+    match fetch_limit {
+        Some(max) => std::cmp::min(page_size, max.saturating_sub(fetched as u32)),
+        None => page_size,
+    }
+}
+
+/// Show how many items have been fetched so far, overwriting the last count. Only shown if stderr
+/// is a terminal, so scripts don't see it
+fn show_fetch_progress(fetched: usize, done: bool) {
+    if crate::output::stderr_is_tty() {
+        if done {
+            eprint!("\r\x1b[K");
+        } else {
+            eprint!("\rFetched {} items...", fetched);
+        }
+        stderr().flush().unwrap_or(());
+    }
+}
+
+/// Fetch the list specified by request page_size items at a time, passing the items in each page to
+/// on_page as it arrives, along with how many items have been fetched so far. At most fetch_limit
+/// items are fetched, if specified. If the continue token expires part way through, we start again
+/// from the first page (up to MAX_PAGINATION_RESTARTS times), leaving out items that were already
+/// passed to on_page. Returns the metadata of the last page, which keeps the continue token if
+/// there were more items
+fn for_each_page<T, F>(
+    env: &Env,
+    request: &Request<Vec<u8>>,
+    page_size: u32,
+    fetch_limit: Option<u32>,
+    timeout: Option<Duration>,
+    mut on_page: F,
+) -> Result<ListMeta, ClickError>
+where
+    T: ListableResource + Metadata<Ty = ObjectMeta> + for<'de> Deserialize<'de> + Debug,
+    F: FnMut(Vec<T>, usize),
+{
+    // everything fetched so far, which is what fetch_limit counts, and how repeats are spotted
+    // after a restart
+    let mut fetched: HashSet<(Option<String>, String)> = HashSet::new();
+    let item_key = |item: &T| {
+        let meta = item.metadata();
+        (
+            meta.namespace.clone(),
+            meta.name.clone().unwrap_or_default(),
+        )
+    };
+    let mut token: Option<String> = None;
+    let mut restarts = 0;
+    loop {
+        let size = next_page_size(page_size, fetch_limit, fetched.len());
+        let page_res = env.run_on_context::<_, List<T>>(|c| {
+            c.execute_list_with_timeout(paged_request(request, size, token.as_deref())?, timeout)
+        });
+        let mut page = match page_res {
+            Ok(page) => page,
+            Err(ref e)
+                if token.is_some() && continue_expired(e) && restarts < MAX_PAGINATION_RESTARTS =>
+            {
+                restarts += 1;
+                token = None;
+                continue;
+            }
+            Err(e) => return Err(e),
+        };
+        page.items.retain(|item| fetched.insert(item_key(item)));
+        on_page(page.items, fetched.len());
+        token = next_page_token(&page.metadata);
+        let reached_limit = fetch_limit
+            .map(|max| fetched.len() >= max as usize)
+            .unwrap_or(false);
+        if token.is_none() || reached_limit {
+            return Ok(page.metadata);
+        }
+    }
+}
+
+/// Fetch the list specified by request page_size items at a time, and return them all as a single
+/// list, showing progress as the pages arrive. See `for_each_page`
+fn fetch_all_pages<T>(
+    env: &Env,
+    request: &Request<Vec<u8>>,
+    page_size: u32,
+    fetch_limit: Option<u32>,
+    timeout: Option<Duration>,
+) -> Result<List<T>, ClickError>
+where
+    T: ListableResource + Metadata<Ty = ObjectMeta> + for<'de> Deserialize<'de> + Debug,
+{
+    let mut items = vec![];
+    let res = for_each_page(
+        env,
+        request,
+        page_size,
+        fetch_limit,
+        timeout,
+        |page, fetched| {
+            items.extend(page);
+            show_fetch_progress(fetched, false);
+        },
+    );
+    show_fetch_progress(items.len(), true);
+    res.map(|metadata| List { items, metadata })
+}
+
+/// Like handle_list_result, but fetches the list page_size items at a time and prints each page as
+/// it arrives, rather than waiting for the whole list. The env's last objects are set to everything
+/// printed once the final page is done. See `for_each_page`
+#[allow(clippy::too_many_arguments)]
+fn stream_list_result<T, F>(
+    env: &mut Env,
+    writer: &mut ClickWriter,
+    cols: Vec<&str>,
+    request: Request<Vec<u8>>,
+    page_size: u32,
+    fetch_limit: Option<u32>,
+    timeout: Option<Duration>,
+    no_headers: bool,
+    extractors: Option<&HashMap<String, Extractor<T>>>,
+    fields: &FieldColumns,
+    regex: Option<Regex>,
+    get_kobj: F,
+) -> Result<(), ClickError>
+where
+    T: ListableResource + Metadata<Ty = ObjectMeta> + for<'de> Deserialize<'de> + Serialize + Debug,
+    F: Fn(&T) -> KObj,
+{
+    let mut table = if no_headers {
+        crate::table::StreamingTable::without_titles()
+    } else {
+        let mut titles = vec!["####".to_string()];
+        titles.extend(cols.iter().map(|col| col.to_string()));
+        crate::table::StreamingTable::new(titles, env.table_header_colour())
+    };
+
+    let mut all_kobjs: Vec<KObj> = vec![];
+    let res = for_each_page(
+        env,
+        &request,
+        page_size,
+        fetch_limit,
+        timeout,
+        |items, _| {
+            let page = List {
+                items,
+                metadata: ListMeta::default(),
+            };
+            let specs = build_specs(
+                &cols,
+                &page,
+                extractors,
+                fields,
+                true,
+                regex.clone(),
+                &get_kobj,
+            );
+            let (kobjs, mut rows): (Vec<KObj>, Vec<RowSpec>) = specs.into_iter().unzip();
+            if no_headers {
+                strip_index_cells(&mut rows);
+            }
+            table.print_page(rows, writer);
+            all_kobjs.extend(kobjs);
+        },
+    );
+    match res {
+        Ok(metadata) => {
+            if next_page_token(&metadata).is_some() && !no_headers {
+                more_items_note(writer);
+            }
+            env.set_last_objs(all_kobjs);
+            Ok(())
+        }
+        Err(e) => {
+            env.clear_last_objs();
+            Err(e)
+        }
+    }
+}
+
+/// What a command that changes objects should do, as specified by --dry-run
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DryRun {
+    /// Make the change
+    Off,
+    /// Only work out and show the change, without sending it
+    Client,
+    /// Send the change with dryRun=All, so the server validates it but doesn't persist it
+    Server,
+}
+
+impl DryRun {
+    /// Get the dry run mode specified by --dry-run. Expects the arg to have been validated already
+    pub fn from_matches(matches: &ArgMatches) -> DryRun {
+        match matches.value_of("dry_run") {
+            Some("client") => DryRun::Client,
+            Some("server") => DryRun::Server,
+            _ => DryRun::Off,
+        }
+    }
+
+    /// The value of the dryRun query parameter for requests, if one should be sent
+    pub fn param(self) -> Option<&'static str> {
+        match self {
+            DryRun::Server => Some("All"),
+            _ => None,
+        }
+    }
+
+    /// A suffix for messages about changes, so it's clear when nothing was persisted
+    pub fn suffix(self) -> &'static str {
+        match self {
+            DryRun::Off => "",
+            DryRun::Client => " (dry run)",
+            DryRun::Server => " (server dry run)",
+        }
+    }
+}
+
+/// The --dry-run arg shared by commands that change objects
+pub fn dry_run_arg() -> Arg<'static, 'static> {
+    Arg::with_name("dry_run")
+        .long("dry-run")
+        .value_name("MODE")
+        .help(
+            "Don't persist any changes. With 'server' the change is sent to the server, which \
+             validates it without saving it. With 'client' the change is only shown",
+        )
+        .takes_value(true)
+        .possible_values(&["client", "server"])
+}
+
+/// The --field-manager arg shared by commands that patch objects
+pub fn field_manager_arg() -> Arg<'static, 'static> {
+    Arg::with_name("field_manager")
+        .long("field-manager")
+        .value_name("NAME")
+        .help("The name of the manager that owns the fields this change sets")
+        .takes_value(true)
+}
+
+/// The query parameters for a request that changes an object, for the given dry run mode and field
+/// manager
+pub fn change_query_pairs(dry_run: DryRun, field_manager: Option<&str>) -> Vec<(&str, &str)> {
+    let mut pairs = vec![];
+    if let Some(param) = dry_run.param() {
+        pairs.push(("dryRun", param));
+    }
+    if let Some(manager) = field_manager {
+        pairs.push(("fieldManager", manager));
+    }
+    pairs
+}
+
+/// Call poll every interval until it returns false or fails, or ctrl-c is pressed. We sleep in
+/// small steps between calls so ctrl-c is noticed quickly, and an error after ctrl-c is just the
+/// interrupted request, so isn't returned. The ctrl-c flag is cleared before starting and when done
+pub fn poll_until_interrupted<F>(
+    ctrlc: &AtomicBool,
+    interval: Duration,
+    mut poll: F,
+) -> Result<(), ClickError>
+where
+    F: FnMut() -> Result<bool, ClickError>,
+{
+    ctrlc.store(false, Ordering::SeqCst);
+    let res = loop {
+        if ctrlc.load(Ordering::SeqCst) {
+            break Ok(());
+        }
+        let started = Instant::now();
+        match poll() {
+            Ok(true) => {}
+            Ok(false) => break Ok(()),
+            Err(_) if ctrlc.load(Ordering::SeqCst) => break Ok(()),
+            Err(e) => break Err(e),
+        }
+        while started.elapsed() < interval && !ctrlc.load(Ordering::SeqCst) {
+            thread::sleep(Duration::from_millis(100));
+        }
+    };
+    ctrlc.store(false, Ordering::SeqCst);
+    res
 }
 
 /// Uppercase the first letter of the given str
@@ -187,11 +1006,152 @@ fn valid_date(s: String) -> Result<(), String> {
         .map_err(|e| e.to_string())
 }
 
+/// The namespace a list command should list objects from. None means all namespaces, either
+/// because there's no current namespace or because -A/--all-namespaces was passed
+pub fn list_namespace<'a>(matches: &ArgMatches, env: &'a Env) -> Option<&'a str> {
+    if matches.is_present("all_namespaces") {
+        None
+    } else {
+        env.namespace.as_deref()
+    }
+}
+
+/// Look for cmd the way a shell would, returning the path to the binary if it's found
+pub fn find_binary(cmd: &str) -> Option<PathBuf> {
+    if cmd.contains('/') {
+        let path = PathBuf::from(cmd);
+        return if path.is_file() { Some(path) } else { None };
+    }
+    std::env::var_os("PATH").and_then(|paths| {
+        std::env::split_paths(&paths)
+            .map(|dir| dir.join(cmd))
+            .find(|path| path.is_file())
+    })
+}
+
+/// Get an expression that opens path in an editor. The editor is editor_opt if specified, otherwise
+/// the click editor setting (with environment variables expanded), otherwise $EDITOR. The editor
+/// can include arguments, like "code -w"
+pub fn editor_expression(
+    env: &Env,
+    editor_opt: Option<&str>,
+    path: &Path,
+) -> Result<duct::Expression, ClickError> {
+    let editor = if let Some(v) = editor_opt {
+        v.to_owned()
+    } else if let Some(ref e) = env.click_config.editor {
+        crate::config::expand_env_vars(e)
+    } else {
+        std::env::var("EDITOR").map_err(|e| {
+            ClickError::CommandError(format!("Could not get EDITOR environment variable: {}", e))
+        })?
+    };
+    let mut eargs: Vec<&OsStr> = editor.split_whitespace().map(OsStr::new).collect();
+    if eargs.is_empty() {
+        return Err(ClickError::CommandError("Editor is empty".to_string()));
+    }
+    eargs.push(path.as_os_str());
+    Ok(duct::cmd(eargs[0], &eargs[1..]))
+}
+
+/// a clap validator for regexes
+pub fn valid_regex(s: String) -> Result<(), String> {
+    Regex::new(s.as_str())
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+}
+
 /// a clap validator for u32
 pub fn valid_u32(s: String) -> Result<(), String> {
     s.parse::<u32>().map(|_| ()).map_err(|e| e.to_string())
 }
 
+/// Remove the #### cell from the start of each row, for --no-headers. Without the header row the
+/// numbers would only get in the way of scripts
+fn strip_index_cells(rows: &mut [RowSpec]) {
+    for row in rows.iter_mut() {
+        if !row.is_empty() {
+            row.remove(0);
+        }
+    }
+}
+
+/// Sort the rows of a list by each of the sort keys in turn, see table::sort_by_keys. Age and
+/// jsonpath keys compare the objects the rows came from, column keys compare the cells
+fn sort_specs<T>(
+    specs: &mut Vec<(KObj, RowSpec)>,
+    items: &[T],
+    cols: &[&str],
+    sort: &[command_def::SortFunc<T>],
+    descending: bool,
+    writer: &mut ClickWriter,
+) where
+    T: Metadata<Ty = ObjectMeta> + Serialize,
+{
+    let positions: HashMap<(Option<&str>, &str), usize> = items
+        .iter()
+        .enumerate()
+        .map(|(pos, item)| {
+            let meta = item.metadata();
+            (
+                (
+                    meta.namespace.as_deref(),
+                    meta.name.as_deref().unwrap_or("<Unknown>"),
+                ),
+                pos,
+            )
+        })
+        .collect();
+    let mut rows: Vec<(usize, (KObj, RowSpec))> = specs
+        .drain(..)
+        .map(|spec| {
+            let pos = positions
+                .get(&(spec.0.namespace.as_deref(), spec.0.name()))
+                .copied()
+                .unwrap_or(0);
+            (pos, spec)
+        })
+        .collect();
+
+    let mut keys: Vec<SortKeyCmp<(usize, (KObj, RowSpec))>> = vec![];
+    for func in sort.iter() {
+        match func {
+            command_def::SortFunc::Pre(func) => {
+                let cmp = func.cmp;
+                keys.push(Box::new(move |a, b| cmp(&items[a.0], &items[b.0])));
+            }
+            command_def::SortFunc::JsonPath(path) => {
+                let values: Vec<Option<serde_json::Value>> = items
+                    .iter()
+                    .map(|item| {
+                        serde_json::to_value(item)
+                            .ok()
+                            .and_then(|val| jsonpath_values(path, &val).first().cloned().cloned())
+                    })
+                    .collect();
+                keys.push(Box::new(move |a, b| {
+                    jsonpath_cmp(values[a.0].as_ref(), values[b.0].as_ref())
+                }));
+            }
+            command_def::SortFunc::Post(colname) => {
+                match cols.iter().position(|c| c == colname) {
+                    Some(index) => {
+                        let idx = index + 1; // +1 for #### col
+                        keys.push(Box::new(move |a, b| (a.1).1[idx].cmp(&(b.1).1[idx])));
+                    }
+                    None => clickwriteln!(
+                        writer,
+                        "Asked to sort by {}, but it's not a column in the output",
+                        colname
+                    ),
+                }
+            }
+        }
+    }
+    sort_by_keys(&mut rows, &keys, descending);
+    specs.extend(rows.into_iter().map(|(_, spec)| spec));
+}
+
 // table printing / building
 /* this function abstracts the standard handling code for when a k8s call returns a list of objects.
  * it does the following thins:
@@ -208,51 +1168,91 @@ pub fn handle_list_result<'a, T, F>(
     env: &mut Env,
     writer: &mut ClickWriter,
     cols: Vec<&str>,
-    mut list: List<T>,
+    list: List<T>,
     extractors: Option<&HashMap<String, Extractor<T>>>,
+    fields: &FieldColumns,
     regex: Option<Regex>,
-    sort: Option<command_def::SortFunc<T>>,
+    sort: Vec<command_def::SortFunc<T>>,
+    sort_reverse: bool,
     reverse: bool,
+    max_items: Option<usize>,
+    output: Option<OutputFormat>,
+    no_headers: bool,
     get_kobj: F,
 ) -> Result<(), ClickError>
 where
-    T: 'a + ListableResource + Metadata<Ty = ObjectMeta>,
+    T: 'a + ListableResource + Metadata<Ty = ObjectMeta> + Serialize,
     F: Fn(&T) -> KObj,
 {
-    if let Some(command_def::SortFunc::Pre(func)) = sort.as_ref() {
-        list.items.sort_by(|a, b| (func.cmp)(a, b));
-    }
+    let mut specs = build_specs(&cols, &list, extractors, fields, true, regex, get_kobj);
 
-    let mut specs = build_specs(&cols, &list, extractors, true, regex, get_kobj);
-
-    let mut titles: Vec<Cell> = vec![Cell::new("####")];
+    let title_colour = env.table_header_colour();
+    let mut titles: Vec<Cell> = vec![title_cell("####", title_colour)];
     titles.reserve(cols.len());
     for col in cols.iter() {
-        titles.push(Cell::new(col));
+        titles.push(title_cell(col, title_colour));
     }
 
-    if let Some(command_def::SortFunc::Post(colname)) = sort {
-        let index = cols.iter().position(|&c| c == colname);
-        match index {
-            Some(index) => {
-                let idx = index + 1; // +1 for #### col
-                specs.sort_by(|a, b| a.1.get(idx).unwrap().cmp(b.1.get(idx).unwrap()));
-            }
-            None => clickwriteln!(
-                writer,
-                "Asked to sort by {}, but it's not a column in the output",
-                colname
-            ),
-        }
+    if !sort.is_empty() {
+        sort_specs(&mut specs, &list.items, &cols, &sort, sort_reverse, writer);
     }
 
-    let (kobjs, rows): (Vec<KObj>, Vec<RowSpec>) = if reverse {
+    let (mut kobjs, mut rows): (Vec<KObj>, Vec<RowSpec>) = if reverse {
         specs.into_iter().rev().unzip()
     } else {
         specs.into_iter().unzip()
     };
 
-    crate::table::print_table(Row::new(titles), rows, writer);
+    let hidden = match max_items {
+        Some(max) if kobjs.len() > max => {
+            let hidden = kobjs.len() - max;
+            kobjs.truncate(max);
+            rows.truncate(max);
+            hidden
+        }
+        _ => 0,
+    };
+
+    match output {
+        Some(format) => {
+            // print the objects in the same order as the table would have been
+            let by_name: HashMap<(Option<&str>, &str), &T> = list
+                .items
+                .iter()
+                .map(|item| {
+                    let meta = item.metadata();
+                    (
+                        (
+                            meta.namespace.as_deref(),
+                            meta.name.as_deref().unwrap_or("<Unknown>"),
+                        ),
+                        item,
+                    )
+                })
+                .collect();
+            let mut values = Vec::with_capacity(kobjs.len());
+            for kobj in kobjs.iter() {
+                if let Some(item) = by_name.get(&(kobj.namespace.as_deref(), kobj.name())) {
+                    values.push(serde_json::to_value(item)?);
+                }
+            }
+            format.write_list(values, writer);
+        }
+        None if no_headers => {
+            strip_index_cells(&mut rows);
+            crate::table::print_table_no_titles(rows, writer);
+        }
+        None => {
+            crate::table::print_table(Row::new(titles), rows, writer);
+            if hidden > 0 {
+                clickwriteln!(
+                    writer,
+                    "... and {} more (use --max-items 0 for all)",
+                    hidden
+                );
+            }
+        }
+    }
     env.set_last_objs(kobjs);
     Ok(())
 }
@@ -262,7 +1262,8 @@ where
 /* Build row specs and a kobj vec from data returned from k8s.
  *
  * cols is a list of names of columns to build. "Name" * and "Age" are handled, other names need to
- * be in 'extractors', and the extractor for the specified name will be used.
+ * be in 'extractors', and the extractor for the specified name will be used, or in 'fields', in
+ * which case the cell is the value at the field's jsonpath.
  *
  * include_index = true will put an index (numbered) column as the first item in the row
  *
@@ -278,12 +1279,13 @@ pub fn build_specs<'a, T, F>(
     cols: &[&str],
     list: &'a List<T>,
     extractors: Option<&HashMap<String, Extractor<T>>>,
+    fields: &FieldColumns,
     include_index: bool,
     regex: Option<Regex>,
     get_kobj: F,
 ) -> Vec<(KObj, RowSpec<'a>)>
 where
-    T: 'a + ListableResource + Metadata<Ty = ObjectMeta>,
+    T: 'a + ListableResource + Metadata<Ty = ObjectMeta> + Serialize,
     F: Fn(&T) -> KObj,
 {
     let mut ret = vec![];
@@ -293,7 +1295,21 @@ where
         } else {
             vec![]
         };
+        // only serialize the object if there are fields to pick out of it
+        let value = if fields.is_empty() {
+            None
+        } else {
+            serde_json::to_value(item).ok()
+        };
         for col in cols.iter() {
+            if let Some(path) = fields.get(*col) {
+                let text = value
+                    .as_ref()
+                    .map(|value| jsonpath_str(path, value))
+                    .filter(|text| !text.is_empty());
+                row.push(text.unwrap_or_else(|| "<none>".to_string()).into());
+                continue;
+            }
             match *col {
                 "Age" => row.push(extract_age(item).into()),
                 "Labels" => row.push(extract_labels(item).into()),
@@ -333,9 +1349,10 @@ pub fn extract_name<T: Metadata<Ty = ObjectMeta>>(obj: &T) -> Option<Cow<'_, str
 /// An extractor for the Age field. Extracts the age out of the object metadata
 pub fn extract_age<T: Metadata<Ty = ObjectMeta>>(obj: &T) -> Option<Cow<'_, str>> {
     let meta = obj.metadata();
-    meta.creation_timestamp
-        .as_ref()
-        .map(|ts| time_since(ts.0).into())
+    Some(match meta.creation_timestamp.as_ref() {
+        Some(ts) => time_since(ts.0).into(),
+        None => "<unknown>".into(),
+    })
 }
 
 /// An extractor for the Namespace field. Extracts the namespace out of the object metadata
@@ -361,41 +1378,6 @@ fn row_matches<'a>(row: &[CellSpec<'a>], regex: &Regex) -> bool {
     has_match
 }
 
-pub fn format_duration(duration: Duration) -> String {
-    if duration.num_days() > 365 {
-        // TODO: maybe be more smart about printing years, or at least have an option
-        let days = duration.num_days();
-        let yrs = days / 365;
-        format!("{}y {}d", yrs, (duration.num_days() - (yrs * 365)))
-    } else if duration.num_days() > 0 {
-        format!(
-            "{}d {}h",
-            duration.num_days(),
-            (duration.num_hours() - (24 * duration.num_days()))
-        )
-    } else if duration.num_hours() > 0 {
-        format!(
-            "{}h {}m",
-            duration.num_hours(),
-            (duration.num_minutes() - (60 * duration.num_hours()))
-        )
-    } else if duration.num_minutes() > 0 {
-        format!(
-            "{}m {}s",
-            duration.num_minutes(),
-            (duration.num_seconds() - (60 * duration.num_minutes()))
-        )
-    } else {
-        format!("{}s", duration.num_seconds())
-    }
-}
-
-pub fn time_since(date: DateTime<Utc>) -> String {
-    let now = Utc::now();
-    let diff = now.signed_duration_since(date);
-    format_duration(diff)
-}
-
 /// Build a multi-line string of the specified keyvals
 pub fn keyval_string(keyvals: &BTreeMap<String, String>) -> String {
     let mut buf = String::new();
@@ -449,3 +1431,411 @@ pub fn get_list_request_for_url<T: ListableResource + for<'de> serde::Deserializ
         Err(err) => Err(RequestError::Http(err)),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::command::command_def::Cmd;
+    use crate::config::{ClickConfig, Config};
+    use crate::kobj::ObjType;
+
+    use k8s_openapi::api::core::v1::Pod;
+
+    use std::io::{BufRead, BufReader, Read};
+    use std::net::TcpListener;
+    use std::sync::{Arc, Mutex};
+    use tempdir::TempDir;
+
+    type Handler = dyn Fn(&str, &str) -> (u16, String) + Send;
+
+    /// A fake api server. Requests are answered by a handler, which is passed the method and path
+    /// (with query), and the method and path of each request are kept so tests can check them
+    struct MockApi {
+        url: String,
+        requests: Arc<Mutex<Vec<(String, String)>>>,
+    }
+
+    impl MockApi {
+        fn start(handler: Box<Handler>) -> MockApi {
+            let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+            let url = format!("http://{}", listener.local_addr().unwrap());
+            let requests = Arc::new(Mutex::new(vec![]));
+            let seen = requests.clone();
+            std::thread::spawn(move || {
+                for stream in listener.incoming() {
+                    let mut stream = stream.unwrap();
+                    let mut reader = BufReader::new(stream.try_clone().unwrap());
+                    let mut request_line = String::new();
+                    reader.read_line(&mut request_line).unwrap();
+                    let mut length = 0;
+                    loop {
+                        let mut header = String::new();
+                        reader.read_line(&mut header).unwrap();
+                        if header.trim().is_empty() {
+                            break;
+                        }
+                        let header = header.to_lowercase();
+                        if let Some(value) = header.strip_prefix("content-length:") {
+                            length = value.trim().parse().unwrap();
+                        }
+                    }
+                    let mut body = vec![0; length];
+                    reader.read_exact(&mut body).unwrap();
+
+                    let mut parts = request_line.split_whitespace();
+                    let method = parts.next().unwrap_or("").to_string();
+                    let path = parts.next().unwrap_or("").to_string();
+                    let (status, body) = handler(&method, &path);
+                    seen.lock().unwrap().push((method, path));
+                    write!(
+                        stream,
+                        "HTTP/1.1 {} Mock\r\nContent-Type: application/json\r\n\
+                         Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+                        status,
+                        body.len(),
+                        body
+                    )
+                    .unwrap();
+                }
+            });
+            MockApi { url, requests }
+        }
+
+        fn requests(&self) -> Vec<(String, String)> {
+            self.requests.lock().unwrap().clone()
+        }
+    }
+
+    /// An Env whose current context talks to api, with namespace ns
+    fn mock_env(api: &MockApi, dir: &TempDir) -> Env {
+        let path = dir.path().join("config");
+        std::fs::write(
+            &path,
+            format!(
+                "apiVersion: v1
+clusters:
+- cluster:
+    server: {}
+  name: mock
+contexts:
+- context:
+    cluster: mock
+    user: mock
+  name: mock
+users:
+- name: mock
+  user:
+    token: secret
+",
+                api.url
+            ),
+        )
+        .unwrap();
+        let config = Config::from_files(&[path.to_str().unwrap().to_string()]).unwrap();
+        let mut env = Env::new(
+            config,
+            ClickConfig::default(),
+            dir.path().join("click.config"),
+        );
+        env.set_context(Some("mock"));
+        env.set_namespace(Some("ns"));
+        env
+    }
+
+    fn run(cmd: &dyn Cmd, env: &mut Env, args: &[&str]) -> Result<(), ClickError> {
+        let mut writer = ClickWriter::with_buffer(vec![], false);
+        let mut args = args.iter().copied();
+        cmd.exec(env, &mut args, &mut writer)
+    }
+
+    /// Answer every request with an object that will do for the commands that change things
+    fn object_handler(_method: &str, path: &str) -> (u16, String) {
+        let metadata =
+            serde_json::json!({"name": "web", "namespace": "ns", "labels": {"app": "web"}});
+        let body = if path.contains("/scale") {
+            serde_json::json!({
+                "apiVersion": "autoscaling/v1",
+                "kind": "Scale",
+                "metadata": metadata,
+                "spec": {"replicas": 1},
+            })
+        } else {
+            serde_json::json!({"apiVersion": "apps/v1", "kind": "Deployment", "metadata": metadata})
+        };
+        (200, body.to_string())
+    }
+
+    fn select_deployment(env: &mut Env) {
+        env.set_last_objs(vec![KObj {
+            name: "web".to_string(),
+            namespace: Some("ns".to_string()),
+            typ: ObjType::Deployment,
+        }]);
+        env.set_current(0);
+    }
+
+    #[test]
+    fn change_query() {
+        let path = "/apis/apps/v1/namespaces/ns/deployments/web";
+        let query = change_query_pairs(DryRun::Server, Some("me"));
+        assert_eq!(
+            path_with_query(path, &query),
+            format!("{}?dryRun=All&fieldManager=me", path)
+        );
+        let query = change_query_pairs(DryRun::Client, Some("me"));
+        assert_eq!(
+            path_with_query(path, &query),
+            format!("{}?fieldManager=me", path)
+        );
+        assert!(change_query_pairs(DryRun::Off, None).is_empty());
+        assert_eq!(path_with_query(path, &[]), path);
+        assert_eq!(
+            path_with_query("/api/v1/pods?limit=5", &[("dryRun", "All")]),
+            "/api/v1/pods?limit=5&dryRun=All"
+        );
+    }
+
+    #[test]
+    fn client_dry_run_only_reads() {
+        let api = MockApi::start(Box::new(object_handler));
+        let dir = TempDir::new("click-dry-run-test").unwrap();
+        let mut env = mock_env(&api, &dir);
+        select_deployment(&mut env);
+
+        let commands: Vec<(Box<dyn Cmd>, Vec<&str>)> = vec![
+            (Box::new(label::Label::new()), vec!["tier=front"]),
+            (Box::new(label::Annotate::new()), vec!["note=hi"]),
+            (Box::new(scale::Scale::new()), vec!["3"]),
+            (Box::new(delete::Delete::new()), vec![]),
+        ];
+        for (cmd, args) in commands.iter() {
+            let args: Vec<&str> = args
+                .iter()
+                .copied()
+                .chain(vec!["--dry-run", "client"])
+                .collect();
+            run(cmd.as_ref(), &mut env, &args).unwrap();
+        }
+        let requests = api.requests();
+        assert!(!requests.is_empty());
+        for (method, path) in requests.iter() {
+            assert_eq!(method, "GET", "{} {}", method, path);
+        }
+    }
+
+    #[test]
+    fn server_dry_run_query() {
+        let api = MockApi::start(Box::new(object_handler));
+        let dir = TempDir::new("click-dry-run-test").unwrap();
+        let mut env = mock_env(&api, &dir);
+        select_deployment(&mut env);
+
+        let args = &["tier=front", "--dry-run", "server", "--field-manager", "me"];
+        run(&label::Label::new(), &mut env, args).unwrap();
+        run(
+            &scale::Scale::new(),
+            &mut env,
+            &["3", "--dry-run", "server"],
+        )
+        .unwrap();
+        // k8s_openapi starts its queries with a stray &, so compare the parsed pairs
+        let changes = api
+            .requests()
+            .into_iter()
+            .filter(|(method, _)| method != "GET")
+            .map(|(method, path)| {
+                let (path, query) = path.split_once('?').unwrap_or((&path, ""));
+                let pairs = url::form_urlencoded::parse(query.as_bytes())
+                    .into_owned()
+                    .collect();
+                (method, path.to_string(), pairs)
+            })
+            .collect::<Vec<(_, _, Vec<_>)>>();
+        let pair = |k: &str, v: &str| (k.to_string(), v.to_string());
+        assert_eq!(
+            changes,
+            vec![
+                (
+                    "PATCH".to_string(),
+                    "/apis/apps/v1/namespaces/ns/deployments/web".to_string(),
+                    vec![pair("dryRun", "All"), pair("fieldManager", "me")]
+                ),
+                (
+                    "PATCH".to_string(),
+                    "/apis/apps/v1/namespaces/ns/deployments/web/scale".to_string(),
+                    vec![pair("dryRun", "All")]
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn poll_until_done() {
+        let ctrlc = AtomicBool::new(true);
+        let mut calls = 0;
+        // the flag is cleared first, so an old ctrl-c doesn't stop it
+        poll_until_interrupted(&ctrlc, Duration::from_secs(0), || {
+            calls += 1;
+            Ok(calls < 3)
+        })
+        .unwrap();
+        assert_eq!(calls, 3);
+
+        let res = poll_until_interrupted(&ctrlc, Duration::from_secs(0), || {
+            Err(ClickError::CommandError("failed".to_string()))
+        });
+        assert!(res.is_err());
+
+        // stops at ctrl-c, ignoring the error from the interrupted request, and clears the flag
+        poll_until_interrupted(&ctrlc, Duration::from_secs(60), || {
+            ctrlc.store(true, Ordering::SeqCst);
+            Err(ClickError::CommandError("interrupted".to_string()))
+        })
+        .unwrap();
+        assert!(!ctrlc.load(Ordering::SeqCst));
+    }
+
+    /// The query parameters in a request path
+    fn query_pairs(path: &str) -> Vec<(String, String)> {
+        let query = path.split_once('?').map(|(_, q)| q).unwrap_or("");
+        url::form_urlencoded::parse(query.as_bytes())
+            .into_owned()
+            .collect()
+    }
+
+    const POD_COUNT: usize = 5;
+
+    /// Serve a list of POD_COUNT pods, a page at a time. Continue tokens are just the index of the
+    /// next pod. If expire is set, the first request with a continue token fails with a 410
+    fn pod_pages(expire: bool) -> Box<Handler> {
+        let expired = std::sync::atomic::AtomicBool::new(!expire);
+        Box::new(move |_method, path| {
+            let mut limit = POD_COUNT;
+            let mut start = None;
+            for (key, value) in query_pairs(path) {
+                match key.as_str() {
+                    "limit" => limit = value.parse().unwrap(),
+                    "continue" => start = Some(value.parse::<usize>().unwrap()),
+                    _ => {}
+                }
+            }
+            if start.is_some() && !expired.swap(true, std::sync::atomic::Ordering::SeqCst) {
+                let status = serde_json::json!({
+                    "apiVersion": "v1",
+                    "kind": "Status",
+                    "status": "Failure",
+                    "reason": "Expired",
+                    "code": 410,
+                });
+                return (410, status.to_string());
+            }
+            let start = start.unwrap_or(0);
+            let end = std::cmp::min(start + limit, POD_COUNT);
+            let items: Vec<serde_json::Value> = (start..end)
+                .map(|i| {
+                    let name = format!("pod-{}", i);
+                    serde_json::json!({"metadata": {"name": name, "namespace": "ns"}})
+                })
+                .collect();
+            let token = if end < POD_COUNT {
+                end.to_string()
+            } else {
+                String::new()
+            };
+            let list = serde_json::json!({
+                "apiVersion": "v1",
+                "kind": "PodList",
+                "metadata": {"continue": token},
+                "items": items,
+            });
+            (200, list.to_string())
+        })
+    }
+
+    fn pod_names(count: usize) -> Vec<String> {
+        (0..count).map(|i| format!("pod-{}", i)).collect()
+    }
+
+    fn pods_request() -> Request<Vec<u8>> {
+        Request::get("/api/v1/namespaces/ns/pods")
+            .body(vec![])
+            .unwrap()
+    }
+
+    /// Fetch pages of pods from api with for_each_page, returning the names in each page
+    fn page_names(api: &MockApi, page_size: u32, limit: Option<u32>) -> Vec<Vec<String>> {
+        let dir = TempDir::new("click-list-test").unwrap();
+        let env = mock_env(api, &dir);
+        let mut pages = vec![];
+        for_each_page(
+            &env,
+            &pods_request(),
+            page_size,
+            limit,
+            None,
+            |page: Vec<Pod>, _| {
+                pages.push(
+                    page.iter()
+                        .map(|pod| pod.metadata.name.clone().unwrap())
+                        .collect(),
+                )
+            },
+        )
+        .unwrap();
+        pages
+    }
+
+    fn all_names(api: &MockApi, page_size: u32, limit: Option<u32>) -> Vec<String> {
+        let dir = TempDir::new("click-list-test").unwrap();
+        let env = mock_env(api, &dir);
+        let list: List<Pod> =
+            fetch_all_pages(&env, &pods_request(), page_size, limit, None).unwrap();
+        list.items
+            .iter()
+            .map(|pod| pod.metadata.name.clone().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn list_restarts_without_duplicates() {
+        let api = MockApi::start(pod_pages(true));
+        assert_eq!(
+            page_names(&api, 2, None),
+            vec![
+                pod_names(2),
+                vec![], // the first page again
+                vec!["pod-2".to_string(), "pod-3".to_string()],
+                vec!["pod-4".to_string()],
+            ]
+        );
+        // the first page, the expired page, the first page again, then the rest
+        assert_eq!(api.requests().len(), 5);
+
+        let api = MockApi::start(pod_pages(true));
+        assert_eq!(all_names(&api, 2, None), pod_names(POD_COUNT));
+    }
+
+    #[test]
+    fn list_limit() {
+        let api = MockApi::start(pod_pages(false));
+        assert_eq!(all_names(&api, 3, Some(3)), pod_names(3));
+        assert_eq!(api.requests().len(), 1);
+
+        // the last page is only as big as needed to reach the limit
+        let api = MockApi::start(pod_pages(false));
+        assert_eq!(all_names(&api, 2, Some(3)), pod_names(3));
+        let limits: Vec<String> = api
+            .requests()
+            .iter()
+            .flat_map(|(_, path)| query_pairs(path))
+            .filter(|(key, _)| key == "limit")
+            .map(|(_, value)| value)
+            .collect();
+        assert_eq!(limits, vec!["2", "1"]);
+
+        // pods fetched again after a restart aren't counted twice
+        let api = MockApi::start(pod_pages(true));
+        let pages = page_names(&api, 2, Some(4));
+        assert_eq!(pages.concat(), pod_names(4));
+    }
+}
@@ -18,10 +18,15 @@ use k8s_openapi::api::core::v1 as api;
 use rustyline::completion::Pair as RustlinePair;
 
 use crate::{
-    command::command_def::{exec_match, sort_arg, start_clap, Cmd},
+    command::command_def::{
+        buffer_arg, chunk_size_arg, exec_match, limit_arg, max_items_arg, no_headers_arg,
+        output_arg, refresh_arg, request_timeout_arg, selector_arg, sort_arg, start_clap, Cmd,
+    },
     command::{run_list_command, Extractor},
     completer,
+    config::NamespaceCheck,
     env::Env,
+    error::ClickError,
     kobj::{KObj, ObjType},
     output::ClickWriter,
     table::CellSpec,
@@ -47,17 +52,44 @@ command!(
     Namespace,
     "namespace",
     "Set the current namespace (no argument to clear namespace)",
-    |clap: App<'static, 'static>| clap.arg(
+    |clap: App<'static, 'static>| {
+        clap.arg(
         Arg::with_name("namespace")
             .help("The namespace to use")
             .required(false)
             .index(1)
-    ),
+    )
+    .after_help(
+        "Switching to a namespace that doesn't exist prints a warning. Use 'set namespace_check \
+         strict' to refuse to switch instead, or 'set namespace_check off' to not check"
+    )
+    },
     vec!["ns", "namespace"],
     vec![&completer::namespace_completer],
     no_named_complete!(),
-    |matches, env, _| {
+    |matches, env, writer| {
         let ns = matches.value_of("namespace");
+        if let Some(ns) = ns {
+            if env.click_config.namespace_check != NamespaceCheck::Off
+                && env.namespace_exists(ns) == Some(false)
+            {
+                let context = env.context.as_ref().map(|c| c.name.as_str()).unwrap_or("");
+                if env.click_config.namespace_check == NamespaceCheck::Strict {
+                    return Err(ClickError::CommandError(format!(
+                        "Namespace {} doesn't exist in context {}",
+                        ns, context
+                    )));
+                }
+                clickwriteln!(
+                    writer,
+                    "{}",
+                    Yellow.paint(format!(
+                        "Warning: namespace {} doesn't exist in context {}, lists will be empty",
+                        ns, context
+                    ))
+                );
+            }
+        }
         env.set_namespace(ns);
         Ok(())
     }
@@ -85,7 +117,7 @@ fn namespace_status(namespace: &api::Namespace) -> Option<CellSpec<'_>> {
 command!(
     Namespaces,
     "namespaces",
-    "Get namespaces in current context",
+    "Get namespaces in current context. Selecting one by number switches to that namespace",
     |clap: App<'static, 'static>| {
         clap.arg(
             Arg::with_name("regex")
@@ -102,6 +134,15 @@ command!(
                 .help("Reverse the order of the returned list")
                 .takes_value(false),
         )
+        .arg(chunk_size_arg())
+        .arg(buffer_arg())
+        .arg(output_arg())
+        .arg(refresh_arg())
+        .arg(request_timeout_arg())
+        .arg(no_headers_arg())
+        .arg(selector_arg())
+        .arg(max_items_arg())
+        .arg(limit_arg())
     },
     vec!["namespaces"],
     noop_complete!(),
@@ -16,28 +16,35 @@ use ansi_term::{
     Colour::{Green, Red, Yellow},
     Style,
 };
-use clap::{App, Arg};
+use chrono::{Duration, Local, Utc};
+use clap::{App, Arg, ArgMatches};
 use k8s_openapi::api::core::v1 as api;
-use k8s_openapi::ListOptional;
+use k8s_openapi::{List, ListOptional};
+use prettytable::{Attr, Cell, Row, Table};
 
 use crate::{
-    command::command_def::{exec_match, show_arg, sort_arg, start_clap, Cmd},
-    command::{run_list_command, Extractor},
+    command::command_def::{add_extra_cols, exec_match, show_arg, sort_arg, start_clap, Cmd},
+    command::{
+        build_specs, list_namespace, poll_until_interrupted, row_matches, run_list_command,
+        Extractor, FieldColumns,
+    },
     completer,
     env::{Env, ObjectSelection},
     error::ClickError,
     kobj::{KObj, ObjType},
-    output::ClickWriter,
-    table::CellSpec,
+    output::{clear_screen, ClickWriter},
+    parser::parse_label_selector,
+    table::{get_regex, print_table, print_table_no_titles, time_since, title_cell, CellSpec},
 };
 
 use std::array::IntoIter;
 use std::cell::RefCell;
 use std::collections::HashMap;
 use std::io::Write;
+use std::sync::atomic::Ordering;
 
 lazy_static! {
-    static ref POD_EXTRACTORS: HashMap<String, Extractor<api::Pod>> = {
+    pub static ref POD_EXTRACTORS: HashMap<String, Extractor<api::Pod>> = {
         let mut m: HashMap<String, Extractor<api::Pod>> = HashMap::new();
         m.insert("IP".to_owned(), pod_ip);
         m.insert("Node".to_owned(), pod_node);
@@ -50,7 +57,7 @@ lazy_static! {
     };
 }
 
-const COL_MAP: &[(&str, &str)] = &[
+pub const COL_MAP: &[(&str, &str)] = &[
     ("name", "Name"),
     ("ready", "Ready"),
     ("status", "Status"),
@@ -71,7 +78,7 @@ const EXTRA_COL_MAP: &[(&str, &str)] = &[
 
 const EXTRA_COL_FLAGS: &[&str] = &{ extract_first!(EXTRA_COL_MAP) };
 
-fn pod_to_kobj(pod: &api::Pod) -> KObj {
+pub fn pod_to_kobj(pod: &api::Pod) -> KObj {
     let containers = match &pod.spec {
         Some(spec) => spec
             .containers
@@ -88,32 +95,40 @@ fn pod_to_kobj(pod: &api::Pod) -> KObj {
     }
 }
 
-// Check if a pod has a waiting container
-fn has_waiting(pod: &api::Pod) -> bool {
-    match pod.status.as_ref().map(|stat| &stat.container_statuses) {
-        Some(stats) => {
-            stats.iter().any(|cs| {
-                match cs.state.as_ref() {
-                    Some(state) => {
-                        state.waiting.is_some()
-                            || (
-                                // if all 3 are None, default is waiting
-                                state.running.is_none() && state.terminated.is_none()
-                            )
-                    }
-                    None => false,
+/// Only show how long ago the last restart was if it was at least this recent
+const RECENT_RESTART_HOURS: i64 = 24;
+
+/// The reason a container isn't running, like kubectl shows in the status column (e.g.
+/// CrashLoopBackOff or Error). A waiting container with no reason is ContainerCreating
+fn container_problem(pod: &api::Pod) -> Option<&str> {
+    let stats = &pod.status.as_ref()?.container_statuses;
+    stats.iter().find_map(|cs| match cs.state.as_ref() {
+        Some(state) => {
+            if let Some(waiting) = state.waiting.as_ref() {
+                Some(waiting.reason.as_deref().unwrap_or("ContainerCreating"))
+            } else if let Some(terminated) = state.terminated.as_ref() {
+                if terminated.exit_code != 0 {
+                    Some(terminated.reason.as_deref().unwrap_or("Error"))
+                } else {
+                    None
                 }
-            })
+            } else if state.running.is_none() {
+                // if all 3 are None, default is waiting
+                Some("ContainerCreating")
+            } else {
+                None
+            }
         }
-        None => false,
-    }
+        None => None,
+    })
 }
 
 fn phase_style_str(phase: &str) -> &'static str {
     match phase {
         "Running" | "Active" => "Fg",
         "Terminated" | "Terminating" => "Fr",
-        "Pending" | "ContainerCreating" => "Fy",
+        "Pending" | "ContainerCreating" | "PodInitializing" => "Fy",
+        "CrashLoopBackOff" | "Error" | "ImagePullBackOff" | "ErrImagePull" => "Fr",
         "Succeeded" => "Fb",
         "Failed" => "Fr",
         "Unknown" => "Fr",
@@ -153,7 +168,14 @@ fn ready_counts(pod: &api::Pod) -> Option<CellSpec<'_>> {
                 ready += 1;
             }
         }
-        format!("{}/{}", ready, count).into()
+        let style = if count > 0 && ready == count {
+            "Fg"
+        } else if ready > 0 {
+            "Fy"
+        } else {
+            "Fr"
+        };
+        CellSpec::with_style(format!("{}/{}", ready, count).into(), style)
     })
 }
 
@@ -172,13 +194,37 @@ fn pod_readiness_gates(pod: &api::Pod) -> Option<CellSpec<'_>> {
     })
 }
 
+/// The most restarts of any container in the pod, and how long ago the last restart was if it was
+/// recent, like "5 (3m ago)"
 fn restart_count(pod: &api::Pod) -> Option<CellSpec<'_>> {
     pod.status.as_ref().map(|stat| {
         let count = stat
             .container_statuses
             .iter()
-            .fold(0, |acc, cs| acc + cs.restart_count);
-        count.into()
+            .map(|cs| cs.restart_count)
+            .max()
+            .unwrap_or(0);
+        let last_restart = stat
+            .container_statuses
+            .iter()
+            .filter_map(|cs| {
+                cs.last_state
+                    .as_ref()
+                    .and_then(|ls| ls.terminated.as_ref())
+                    .and_then(|t| t.finished_at.as_ref())
+            })
+            .map(|time| time.0)
+            .max();
+        match last_restart {
+            Some(time)
+                if count > 0
+                    && Utc::now().signed_duration_since(time)
+                        < Duration::hours(RECENT_RESTART_HOURS) =>
+            {
+                CellSpec::new_int_suffix(count.into(), format!("({} ago)", time_since(time)))
+            }
+            _ => count.into(),
+        }
     })
 }
 
@@ -186,8 +232,8 @@ fn pod_status(pod: &api::Pod) -> Option<CellSpec<'_>> {
     let status = if pod.metadata.deletion_timestamp.is_some() {
         // Was deleted
         "Terminating"
-    } else if has_waiting(pod) {
-        "ContainerCreating"
+    } else if let Some(problem) = container_problem(pod) {
+        problem
     } else {
         pod.status
             .as_ref()
@@ -198,6 +244,324 @@ fn pod_status(pod: &api::Pod) -> Option<CellSpec<'_>> {
     Some(CellSpec::with_style(status.into(), style))
 }
 
+/// The columns pods --containers shows, after Namespace if listing all namespaces
+const CONTAINER_COLS: &[&str] = &["Pod", "Container", "Image", "Ready", "Restarts", "State"];
+
+/// The state of a container as one word (or the reason it isn't running), like Running,
+/// CrashLoopBackOff or Completed. A container that's exited non-zero also shows its exit code
+fn container_state(state: Option<&api::ContainerState>) -> CellSpec<'_> {
+    let state = match state {
+        Some(state) => state,
+        None => return CellSpec::with_style("Waiting".into(), "Fy"),
+    };
+    if state.running.is_some() {
+        CellSpec::with_style("Running".into(), "Fg")
+    } else if let Some(terminated) = state.terminated.as_ref() {
+        if terminated.exit_code == 0 {
+            let reason = terminated.reason.as_deref().unwrap_or("Completed");
+            CellSpec::with_style(reason.into(), "Fb")
+        } else {
+            let reason = terminated.reason.as_deref().unwrap_or("Error");
+            CellSpec::with_style(
+                format!("{} (exit code {})", reason, terminated.exit_code).into(),
+                "Fr",
+            )
+        }
+    } else {
+        let reason = state
+            .waiting
+            .as_ref()
+            .and_then(|waiting| waiting.reason.as_deref())
+            .unwrap_or("ContainerCreating");
+        CellSpec::with_style(reason.into(), phase_style_str(reason))
+    }
+}
+
+/// One row for each container of pod, init containers first. Containers the kubelet hasn't
+/// reported a status for yet show as not ready and waiting
+fn container_rows(pod: &api::Pod, namespace_col: bool) -> Vec<Vec<CellSpec<'_>>> {
+    let spec = match pod.spec.as_ref() {
+        Some(spec) => spec,
+        None => return vec![],
+    };
+    let (statuses, init_statuses) = match pod.status.as_ref() {
+        Some(status) => (
+            status.container_statuses.as_slice(),
+            status.init_container_statuses.as_slice(),
+        ),
+        None => (&[][..], &[][..]),
+    };
+    let init = spec.init_containers.iter().map(|cont| (cont, true));
+    let regular = spec.containers.iter().map(|cont| (cont, false));
+    init.chain(regular)
+        .map(|(cont, is_init)| {
+            let status = if is_init { init_statuses } else { statuses }
+                .iter()
+                .find(|cs| cs.name == cont.name);
+            let mut row = vec![CellSpec::new_index()];
+            if namespace_col {
+                row.push(pod.metadata.namespace.as_deref().unwrap_or("").into());
+            }
+            row.push(pod.metadata.name.as_deref().unwrap_or("<Unknown>").into());
+            if is_init {
+                row.push(format!("{} (init)", cont.name).into());
+            } else {
+                row.push(cont.name.as_str().into());
+            }
+            row.push(cont.image.as_deref().unwrap_or("<none>").into());
+            let ready = status.is_some_and(|cs| cs.ready);
+            row.push(CellSpec::with_style(
+                ready.to_string().into(),
+                if ready { "Fg" } else { "Fr" },
+            ));
+            row.push(status.map(|cs| cs.restart_count).unwrap_or(0).into());
+            row.push(container_state(status.and_then(|cs| cs.state.as_ref())));
+            row
+        })
+        .collect()
+}
+
+/// List the pods with a row for each container rather than each pod. Selecting a row selects the
+/// pod it's in
+fn list_containers(
+    matches: &ArgMatches,
+    env: &mut Env,
+    writer: &mut ClickWriter,
+    request: k8s_openapi::http::Request<Vec<u8>>,
+) -> Result<(), ClickError> {
+    let regex = get_regex(matches).map_err(ClickError::CommandError)?;
+    let namespace_col = list_namespace(matches, env).is_none();
+    let list: List<api::Pod> = env.run_on_context(|c| c.execute_list(request))?;
+
+    let mut kobjs = vec![];
+    let mut rows = vec![];
+    for pod in list.items.iter() {
+        for row in container_rows(pod, namespace_col).into_iter() {
+            let keep = match regex.as_ref() {
+                Some(regex) => row_matches(&row, regex),
+                None => true,
+            };
+            if keep {
+                kobjs.push(pod_to_kobj(pod));
+                rows.push(row);
+            }
+        }
+    }
+
+    if matches.is_present("no_headers") {
+        for row in rows.iter_mut() {
+            row.remove(0);
+        }
+        print_table_no_titles(rows, writer);
+    } else {
+        let title_colour = env.table_header_colour();
+        let mut titles = vec![title_cell("####", title_colour)];
+        if namespace_col {
+            titles.push(title_cell("Namespace", title_colour));
+        }
+        titles.extend(
+            CONTAINER_COLS
+                .iter()
+                .map(|col| title_cell(col, title_colour)),
+        );
+        print_table(Row::new(titles), rows, writer);
+    }
+    env.set_last_objs(kobjs);
+    Ok(())
+}
+
+/// How often pods -w lists the pods again, if --interval isn't given
+const DEFAULT_WATCH_INTERVAL_SECS: &str = "2";
+
+/// How a pod has changed since the last time pods -w listed the pods
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum PodChange {
+    Unchanged,
+    /// the pod is new, or its status is different
+    Changed,
+    /// the pod has gone. it's shown dimmed for one refresh before being dropped
+    Deleted,
+}
+
+fn pod_uid(pod: &api::Pod) -> &str {
+    pod.metadata.uid.as_deref().unwrap_or("")
+}
+
+fn pod_key(namespace: Option<&str>, name: &str) -> (String, String) {
+    (namespace.unwrap_or("").to_string(), name.to_string())
+}
+
+/// Compare pods with the last list pods -w showed, matching pods by uid. Returns how each pod
+/// changed, keyed by pod_key, and the pods from the last list that have gone. Everything counts as
+/// unchanged when there's no last list, as there's nothing to compare with
+fn pod_changes(
+    last: Option<&HashMap<String, api::Pod>>,
+    pods: &[api::Pod],
+) -> (HashMap<(String, String), PodChange>, Vec<api::Pod>) {
+    let last = match last {
+        Some(last) => last,
+        None => return (HashMap::new(), vec![]),
+    };
+    let changes = pods
+        .iter()
+        .map(|pod| {
+            let change = match last.get(pod_uid(pod)) {
+                Some(prev) if prev.status == pod.status => PodChange::Unchanged,
+                _ => PodChange::Changed,
+            };
+            let meta = &pod.metadata;
+            let key = pod_key(
+                meta.namespace.as_deref(),
+                meta.name.as_deref().unwrap_or(""),
+            );
+            (key, change)
+        })
+        .collect();
+    let deleted = last
+        .values()
+        .filter(|prev| !pods.iter().any(|pod| pod_uid(pod) == pod_uid(prev)))
+        .cloned()
+        .collect();
+    (changes, deleted)
+}
+
+/// The cells of a row in the pods -w table, highlighted if the pod changed and dimmed if it was
+/// deleted. Deleted pods get no index, as they can't be selected
+fn watch_row(row: &[CellSpec], index: usize, change: PodChange) -> Row {
+    let cells = row
+        .iter()
+        .enumerate()
+        .map(|(col, spec)| {
+            let cell = if col == 0 && change == PodChange::Deleted {
+                Cell::new("-")
+            } else {
+                spec.to_cell(index)
+            };
+            match change {
+                PodChange::Unchanged => cell,
+                PodChange::Changed => cell.with_style(Attr::Reverse),
+                PodChange::Deleted => cell.with_style(Attr::Dim),
+            }
+        })
+        .collect();
+    Row::new(cells)
+}
+
+/// List the pods every interval until ctrl-c is pressed, highlighting the ones that are new or
+/// whose status changed since the last list, and showing the ones that went away dimmed for one
+/// refresh. Pods are matched between lists by uid, so a pod that was replaced by one with the same
+/// name counts as changed
+fn watch_pods(
+    matches: &ArgMatches,
+    env: &mut Env,
+    writer: &mut ClickWriter,
+    field_selector: Option<&str>,
+    interval: std::time::Duration,
+) -> Result<(), ClickError> {
+    let regex = get_regex(matches).map_err(ClickError::CommandError)?;
+    let namespace = list_namespace(matches, env).map(|ns| ns.to_string());
+    let selector = matches.value_of("selector").map(|sel| {
+        // already validated, so parse can't fail
+        let reqs: Vec<String> = parse_label_selector(sel)
+            .unwrap()
+            .iter()
+            .map(|r| r.to_string())
+            .collect();
+        reqs.join(",")
+    });
+    let mut cols: Vec<&str> = COL_MAP.iter().map(|(_, col)| *col).collect();
+    let mut flags: Vec<&str> = matches
+        .values_of("show")
+        .map(|v| v.collect())
+        .unwrap_or_default();
+    if namespace.is_none() {
+        flags.push("namespace");
+    }
+    add_extra_cols(
+        &mut cols,
+        matches.is_present("labels"),
+        flags,
+        EXTRA_COL_MAP,
+    );
+
+    let mut last: Option<HashMap<String, api::Pod>> = None;
+    let ctrlc = env.ctrlcbool.clone();
+    poll_until_interrupted(&ctrlc, interval, || {
+        let opts = ListOptional {
+            label_selector: selector.as_deref(),
+            field_selector,
+            ..Default::default()
+        };
+        let (request, _) = match namespace.as_deref() {
+            Some(ns) => api::Pod::list_namespaced_pod(ns, opts)?,
+            None => api::Pod::list_pod_for_all_namespaces(opts)?,
+        };
+        let list: List<api::Pod> = env.run_on_context(|c| c.execute_list(request))?;
+
+        let (changes, deleted) = pod_changes(last.as_ref(), &list.items);
+        let deleted = List {
+            items: deleted,
+            metadata: Default::default(),
+        };
+
+        let specs = build_specs(
+            &cols,
+            &list,
+            Some(&POD_EXTRACTORS),
+            &FieldColumns::new(),
+            true,
+            regex.clone(),
+            pod_to_kobj,
+        );
+        let deleted_specs = build_specs(
+            &cols,
+            &deleted,
+            Some(&POD_EXTRACTORS),
+            &FieldColumns::new(),
+            true,
+            regex.clone(),
+            pod_to_kobj,
+        );
+        let mut table = Table::new();
+        let title_colour = env.table_header_colour();
+        let mut titles = vec![title_cell("####", title_colour)];
+        titles.extend(cols.iter().map(|col| title_cell(col, title_colour)));
+        table.set_titles(Row::new(titles));
+        let mut kobjs = vec![];
+        for (index, (kobj, row)) in specs.into_iter().enumerate() {
+            let change = changes
+                .get(&pod_key(kobj.namespace.as_deref(), &kobj.name))
+                .copied()
+                .unwrap_or(PodChange::Unchanged);
+            table.add_row(watch_row(&row, index, change));
+            kobjs.push(kobj);
+        }
+        for (_, row) in deleted_specs.iter() {
+            table.add_row(watch_row(row, 0, PodChange::Deleted));
+        }
+
+        clear_screen();
+        clickwriteln!(
+            writer,
+            "Every {}s: pods in {} (Ctrl-C to stop)    {}\n",
+            interval.as_secs(),
+            namespace.as_deref().unwrap_or("all namespaces"),
+            Local::now().format("%a %b %e %H:%M:%S %Y")
+        );
+        crate::table::print_filled_table(&mut table, writer);
+        writer.flush().unwrap_or(());
+        env.set_last_objs(kobjs);
+
+        last = Some(
+            list.items
+                .into_iter()
+                .map(|pod| (pod_uid(&pod).to_string(), pod))
+                .collect(),
+        );
+        Ok(true)
+    })
+}
+
 list_command!(
     Pods,
     "pods",
@@ -235,15 +599,69 @@ list_command!(
                 .help("Reverse the order of the returned list")
                 .takes_value(false),
         )
+        .arg(
+            Arg::with_name("watch")
+                .short("w")
+                .long("watch")
+                .help(
+                    "Keep listing the pods until Ctrl-C is pressed. Pods that are new or whose \
+                     status changed since the last list are highlighted, and pods that went away \
+                     are shown dimmed once",
+                )
+                .conflicts_with_all(&[
+                    "output",
+                    "sort",
+                    "sort_by",
+                    "sort_reverse",
+                    "reverse",
+                    "chunk_size",
+                    "buffer",
+                    "limit",
+                    "field_selector",
+                ])
+                .takes_value(false),
+        )
+        .arg(
+            Arg::with_name("containers")
+                .long("containers")
+                .help(
+                    "Show a row for each container of each pod, with its image, whether it's \
+                     ready, its restarts and its state. Selecting a row selects its pod",
+                )
+                .conflicts_with_all(&[
+                    "watch",
+                    "output",
+                    "show",
+                    "labels",
+                    "sort",
+                    "sort_by",
+                    "sort_reverse",
+                    "reverse",
+                    "chunk_size",
+                    "buffer",
+                    "max_items",
+                    "limit",
+                ])
+                .takes_value(false),
+        )
+        .arg(
+            Arg::with_name("interval")
+                .long("interval")
+                .value_name("SECS")
+                .help("How often to list the pods with --watch")
+                .requires("watch")
+                .validator(|s: String| match s.parse::<u64>() {
+                    Ok(secs) if secs >= 1 => Ok(()),
+                    _ => Err("Interval must be a whole number of seconds, at least 1".to_string()),
+                })
+                .takes_value(true),
+        )
     },
     vec!["pods"],
     noop_complete!(),
     IntoIter::new([]),
     |matches, env, writer| {
-        let mut opts: ListOptional = ListOptional::<'_> {
-            label_selector: matches.value_of("label"),
-            ..Default::default()
-        };
+        let mut opts: ListOptional = Default::default();
         let mut field_sel = None;
         match matches.value_of("node") {
             Some(nodeval) => {
@@ -259,11 +677,33 @@ list_command!(
         }
         opts.field_selector = field_sel.as_deref();
 
-        let (request, _response_body) = match &env.namespace {
+        if matches.is_present("watch") {
+            let secs = matches
+                .value_of("interval")
+                .unwrap_or(DEFAULT_WATCH_INTERVAL_SECS)
+                .parse()
+                .unwrap(); // safe, validated
+            env.ctrlcbool.store(false, Ordering::SeqCst);
+            let res = watch_pods(
+                &matches,
+                env,
+                writer,
+                field_sel.as_deref(),
+                std::time::Duration::from_secs(secs),
+            );
+            env.ctrlcbool.store(false, Ordering::SeqCst);
+            return res;
+        }
+
+        let (request, _response_body) = match list_namespace(&matches, env) {
             Some(ns) => api::Pod::list_namespaced_pod(ns, opts)?,
             None => api::Pod::list_pod_for_all_namespaces(opts)?,
         };
 
+        if matches.is_present("containers") {
+            return list_containers(&matches, env, writer, request);
+        }
+
         let cols: Vec<&str> = COL_MAP.iter().map(|(_, col)| *col).collect();
 
         run_list_command(
@@ -452,3 +892,65 @@ fn print_state_string(state: &Option<api::ContainerState>, writer: &mut ClickWri
         None => clickwrite!(writer, "Unknown"),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use k8s_openapi::apimachinery::pkg::apis::meta::v1::ObjectMeta;
+
+    fn pod(name: &str, uid: &str, phase: &str) -> api::Pod {
+        api::Pod {
+            metadata: ObjectMeta {
+                name: Some(name.to_string()),
+                namespace: Some("ns".to_string()),
+                uid: Some(uid.to_string()),
+                ..Default::default()
+            },
+            status: Some(api::PodStatus {
+                phase: Some(phase.to_string()),
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+    }
+
+    fn by_uid(pods: &[api::Pod]) -> HashMap<String, api::Pod> {
+        pods.iter()
+            .map(|pod| (pod_uid(pod).to_string(), pod.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn watch_changes() {
+        let first = vec![
+            pod("same", "1", "Running"),
+            pod("started", "2", "Pending"),
+            pod("replaced", "3", "Running"),
+            pod("gone", "4", "Running"),
+        ];
+        let second = vec![
+            pod("same", "1", "Running"),
+            pod("started", "2", "Running"),
+            // same name, but a new pod
+            pod("replaced", "5", "Running"),
+            pod("new", "6", "Pending"),
+        ];
+
+        // nothing to compare the first list with
+        let (changes, deleted) = pod_changes(None, &first);
+        assert!(changes.is_empty());
+        assert!(deleted.is_empty());
+
+        let (changes, deleted) = pod_changes(Some(&by_uid(&first)), &second);
+        let change = |name: &str| changes.get(&pod_key(Some("ns"), name)).copied();
+        assert_eq!(change("same"), Some(PodChange::Unchanged));
+        assert_eq!(change("started"), Some(PodChange::Changed));
+        assert_eq!(change("replaced"), Some(PodChange::Changed));
+        assert_eq!(change("new"), Some(PodChange::Changed));
+        assert_eq!(changes.len(), 4);
+        // the replaced pod went too
+        let mut deleted: Vec<&str> = deleted.iter().map(pod_uid).collect();
+        deleted.sort_unstable();
+        assert_eq!(deleted, vec!["3", "4"]);
+    }
+}
@@ -32,6 +32,109 @@ use std::process::{Command, Stdio};
 use std::sync::{Arc, Mutex};
 use std::thread;
 
+/// Start kubectl forwarding ports to pod, and add it to env's port forwards
+fn start_forward(
+    env: &mut Env,
+    context: String,
+    namespace: String,
+    pod: String,
+    ports: Vec<String>,
+    impersonation: Vec<String>,
+) -> Result<(), io::Error> {
+    let mut child = Command::new("kubectl")
+        .arg("--namespace")
+        .arg(&namespace)
+        .arg("--context")
+        .arg(&context)
+        .args(impersonation)
+        .arg("port-forward")
+        .arg(&pod)
+        .args(ports.iter())
+        .stdout(Stdio::piped())
+        .spawn()?;
+    let mut stdout = child.stdout.take().unwrap();
+    let output = Arc::new(Mutex::new(String::new()));
+    let output_clone = output.clone();
+
+    thread::spawn(move || {
+        let mut buffer = [0; 128];
+        loop {
+            match stdout.read(&mut buffer[..]) {
+                Ok(read) => {
+                    if read > 0 {
+                        let readstr = String::from_utf8_lossy(&buffer[0..read]);
+                        let mut res = output_clone.lock().unwrap();
+                        res.push_str(&*readstr);
+                    } else {
+                        break;
+                    }
+                }
+                Err(e) => {
+                    write!(stderr(), "Error reading child output: {}", e).unwrap_or(());
+                    break;
+                }
+            }
+        }
+    });
+
+    env.add_port_forward(env::PortForward {
+        child,
+        context,
+        namespace,
+        pod,
+        ports,
+        output,
+    });
+    Ok(())
+}
+
+/// Deal with the port forwards saved by other sessions. Forwards whose kubectl is still running
+/// after the session that started them has gone were orphaned by click not exiting cleanly, and
+/// are reported so they can be killed. Forwards a session stopped when it exited are started again
+/// if restore is true, otherwise they're left for a later session to restore. Forwards belonging
+/// to sessions that are still running are left alone.
+pub fn handle_saved_forwards(env: &mut Env, restore: bool) {
+    let saved = match env.load_saved_port_forwards() {
+        Ok(saved) => saved,
+        Err(e) => {
+            println!("[WARN] Could not read saved port forwards: {}", e);
+            vec![]
+        }
+    };
+    let mut restored = vec![];
+    for pf in saved.into_iter() {
+        if let Some(pid) = pf.pid {
+            if pf.forward_running() && !pf.session_running() {
+                println!(
+                    "Port forward to {} ({}) from a previous session is still running as pid {}",
+                    pf.pod,
+                    pf.ports.join(", "),
+                    pid
+                );
+            }
+        } else if restore {
+            restored.push(pf);
+        }
+    }
+    // forget them first, so another session starting now doesn't restore them too
+    env.forget_saved_port_forwards(&restored);
+    for pf in restored.into_iter() {
+        let desc = format!("{}/{} in {}", pf.namespace, pf.pod, pf.context);
+        // impersonation doesn't outlive a session, so restored forwards don't use it
+        match start_forward(
+            env,
+            pf.context,
+            pf.namespace,
+            pf.pod,
+            pf.ports.clone(),
+            vec![],
+        ) {
+            Ok(()) => println!("Restored port forward to {}: {}", desc, pf.ports.join(", ")),
+            Err(e) => println!("Could not restore port forward to {}: {}", desc, e),
+        }
+    }
+}
+
 command!(
     PortForward,
     "port-forward",
@@ -103,52 +206,11 @@ Examples:
             return Err(ClickError::CommandError("No active context".to_string()));
         };
 
-        match Command::new("kubectl")
-            .arg("--namespace")
-            .arg(ns)
-            .arg("--context")
-            .arg(context)
-            .arg("port-forward")
-            .arg(&pod)
-            .args(ports.iter())
-            .stdout(Stdio::piped())
-            .spawn()
-        {
-            Ok(mut child) => {
-                let mut stdout = child.stdout.take().unwrap();
-                let output = Arc::new(Mutex::new(String::new()));
-                let output_clone = output.clone();
-
-                thread::spawn(move || {
-                    let mut buffer = [0; 128];
-                    loop {
-                        match stdout.read(&mut buffer[..]) {
-                            Ok(read) => {
-                                if read > 0 {
-                                    let readstr = String::from_utf8_lossy(&buffer[0..read]);
-                                    let mut res = output_clone.lock().unwrap();
-                                    res.push_str(&*readstr);
-                                } else {
-                                    break;
-                                }
-                            }
-                            Err(e) => {
-                                write!(stderr(), "Error reading child output: {}", e).unwrap_or(());
-                                break;
-                            }
-                        }
-                    }
-                });
-
-                let pvec: Vec<String> = ports.iter().map(|s| (*s).to_owned()).collect();
+        let pvec: Vec<String> = ports.iter().map(|s| (*s).to_owned()).collect();
+        let impersonation = env.kubectl_impersonation_args();
+        match start_forward(env, context, ns, pod, pvec.clone(), impersonation) {
+            Ok(()) => {
                 clickwriteln!(writer, "Forwarding port(s): {}", pvec.join(", "));
-
-                env.add_port_forward(env::PortForward {
-                    child,
-                    pod,
-                    ports: pvec,
-                    output,
-                });
             }
             Err(e) => match e.kind() {
                 io::ErrorKind::NotFound => {
@@ -172,6 +234,15 @@ Examples:
     }
 );
 
+/// Alive if kubectl is still running, otherwise dead with how it exited
+fn pf_status(pf: &mut env::PortForward) -> String {
+    match pf.child.try_wait() {
+        Ok(Some(stat)) => format!("Dead ({})", stat),
+        Ok(None) => "Alive".to_string(),
+        Err(e) => format!("Unknown: {}", e),
+    }
+}
+
 /// Print out port forwards found in iterator
 fn print_pfs(pfs: std::slice::IterMut<env::PortForward>, writer: &mut ClickWriter) {
     let mut table = Table::new();
@@ -184,14 +255,7 @@ fn print_pfs(pfs: std::slice::IterMut<env::PortForward>, writer: &mut ClickWrite
         ));
         row.push(Cell::new(pf.pod.as_str()));
         row.push(Cell::new(pf.ports.join(", ").as_str()));
-
-        let status = match pf.child.try_wait() {
-            Ok(Some(stat)) => format!("Exited with code {}", stat),
-            Ok(None) => "Running".to_string(),
-            Err(e) => format!("Error: {}", e),
-        };
-        row.push(Cell::new(status.as_str()));
-
+        row.push(Cell::new(pf_status(pf).as_str()));
         table.add_row(Row::new(row));
     }
     if table.is_empty() {
@@ -200,9 +264,24 @@ fn print_pfs(pfs: std::slice::IterMut<env::PortForward>, writer: &mut ClickWrite
             "No active port forwards, see `port-forward -h` for help creating one"
         );
     } else {
-        table.set_format(*crate::table::TBLFMT);
-        table.printstd();
+        crate::table::print_filled_table(&mut table, writer);
+    }
+}
+
+/// Ask the user to confirm stopping, returns true if they said yes
+fn confirm_stop(writer: &mut ClickWriter) -> bool {
+    clickwrite!(writer, "  [y/N]? ");
+    writer.flush().unwrap_or(());
+    let mut conf = String::new();
+    if io::stdin().read_line(&mut conf).is_ok() {
+        if conf.trim() == "y" || conf.trim() == "yes" {
+            return true;
+        }
+        clickwriteln!(writer, "Not stopping");
+    } else {
+        clickwriteln!(writer, "Could not read response, not stopping.");
     }
+    false
 }
 
 command!(
@@ -214,56 +293,92 @@ command!(
             Arg::with_name("action")
                 .help("Action to take")
                 .required(false)
-                .possible_values(&["list", "output", "stop"])
+                .possible_values(&["list", "logs", "output", "stop"])
                 .index(1)
         )
         .arg(
             Arg::with_name("index")
-                .help("Index (from 'port-forwards list') of port forward to take action on")
-                .validator(|s: String| s.parse::<usize>().map(|_| ()).map_err(|e| e.to_string()))
+                .help(
+                    "Index (from 'port-forwards list') of port forward to take action on. \
+                     'stop' also accepts 'all'"
+                )
+                .validator(|s: String| {
+                    if s == "all" {
+                        Ok(())
+                    } else {
+                        s.parse::<usize>().map(|_| ()).map_err(|e| e.to_string())
+                    }
+                })
                 .required(false)
                 .index(2)
         )
         .after_help(
             "Example:
-  # List all active port forwards
+  # List all active port forwards, and whether kubectl is still alive for each one
   pfs
 
+  # Show the output kubectl has printed for item number 3 (output is the same as logs)
+  pfs logs 3
+
   # Stop item number 3 in list from above command
-  pfs stop 3"
+  pfs stop 3
+
+  # Stop every port forward
+  pfs stop all"
         ),
     vec!["pfs", "port-forwards"],
     vec![&completer::portforwardaction_values_completer],
     no_named_complete!(),
     |matches, env, writer| {
-        let stop = matches.is_present("action") && matches.value_of("action").unwrap() == "stop";
-        let output =
-            matches.is_present("action") && matches.value_of("action").unwrap() == "output";
-        if let Some(index) = matches.value_of("index") {
-            let i = index.parse::<usize>().unwrap();
-            match env.get_port_forward(i) {
-                Some(pf) => {
-                    if stop {
-                        clickwrite!(writer, "Stop port-forward: ");
-                    }
-                    clickwrite!(writer, "Pod: {}, Port(s): {}", pf.pod, pf.ports.join(", "));
-
-                    if output {
-                        clickwriteln!(writer, " Output:{}", *pf.output.lock().unwrap());
-                    }
+        let action = matches.value_of("action").unwrap_or("list");
+        let stop = action == "stop";
+        let output = action == "output" || action == "logs";
+        match matches.value_of("index") {
+            Some("all") if stop => {
+                let count = env.get_port_forwards().count();
+                if count == 0 {
+                    clickwriteln!(writer, "No active port forwards");
+                    return Ok(());
                 }
-                None => {
-                    clickwriteln!(writer, "Invalid index (try without args to get a list)");
-                    return Ok(()); // TODO: Return error
+                clickwrite!(writer, "Stop all {} port forwards", count);
+                if confirm_stop(writer) {
+                    env.stop_all_forwards();
+                    clickwriteln!(writer, "Stopped");
                 }
             }
+            Some("all") => {
+                return Err(ClickError::CommandError(
+                    "'all' can only be used with stop".to_string(),
+                ));
+            }
+            Some(index) => {
+                let i = index.parse::<usize>().unwrap(); // safe, validated
+                match env.get_port_forward(i) {
+                    Some(pf) => {
+                        if stop {
+                            clickwrite!(writer, "Stop port-forward: ");
+                        }
+                        let status = pf_status(pf);
+                        clickwrite!(
+                            writer,
+                            "Pod: {}, Port(s): {}, Status: {}",
+                            pf.pod,
+                            pf.ports.join(", "),
+                            status
+                        );
+                        if output {
+                            clickwrite!(writer, "\nOutput:\n{}", *pf.output.lock().unwrap());
+                        }
+                    }
+                    None => {
+                        return Err(ClickError::CommandError(
+                            "Invalid index (try without args to get a list)".to_string(),
+                        ));
+                    }
+                }
 
-            if stop {
-                clickwrite!(writer, "  [y/N]? ");
-                io::stdout().flush().expect("Could not flush stdout");
-                let mut conf = String::new();
-                if io::stdin().read_line(&mut conf).is_ok() {
-                    if conf.trim() == "y" || conf.trim() == "yes" {
+                if stop {
+                    if confirm_stop(writer) {
                         match env.stop_port_forward(i) {
                             Ok(()) => {
                                 clickwriteln!(writer, "Stopped");
@@ -272,17 +387,18 @@ command!(
                                 write!(stderr(), "Failed to stop: {}", e).unwrap_or(());
                             }
                         }
-                    } else {
-                        clickwriteln!(writer, "Not stopping");
                     }
                 } else {
-                    clickwriteln!(writer, "Could not read response, not stopping.");
+                    clickwrite!(writer, "\n"); // just flush the above description
                 }
-            } else {
-                clickwrite!(writer, "\n"); // just flush the above description
             }
-        } else {
-            print_pfs(env.get_port_forwards(), writer);
+            None if stop || output => {
+                return Err(ClickError::CommandError(format!(
+                    "{} needs the index of a port forward",
+                    action
+                )));
+            }
+            None => print_pfs(env.get_port_forwards(), writer),
         }
         Ok(())
     }
@@ -0,0 +1,186 @@
+// Copyright 2021 Databricks, Inc.
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+
+// http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use ansi_term::Colour::Yellow;
+use clap::{App, Arg};
+use k8s_openapi::http::{self, Method, Request};
+use rustyline::completion::Pair as RustlinePair;
+use serde_json::Value;
+
+use crate::{
+    command::apply::read_input,
+    command::command_def::{exec_match, start_clap, Cmd},
+    completer,
+    env::Env,
+    error::ClickError,
+    output::ClickWriter,
+};
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::io::Write;
+
+pub const RAW_METHODS: &[&str] = &["GET", "POST", "PUT", "PATCH", "DELETE"];
+
+/// Replaced in paths with the current namespace
+const NAMESPACE_PLACEHOLDER: &str = "{namespace}";
+
+/// The path to request, with any namespace placeholder filled in
+fn expand_path(path: &str, namespace: Option<&str>) -> Result<String, ClickError> {
+    if !path.starts_with('/') {
+        return Err(ClickError::CommandError(format!(
+            "Path must start with /, like /api/v1/namespaces, got {}",
+            path
+        )));
+    }
+    if !path.contains(NAMESPACE_PLACEHOLDER) {
+        return Ok(path.to_string());
+    }
+    match namespace {
+        Some(ns) => Ok(path.replace(NAMESPACE_PLACEHOLDER, ns)),
+        None => Err(ClickError::CommandError(format!(
+            "Path contains {} but no namespace is active",
+            NAMESPACE_PLACEHOLDER
+        ))),
+    }
+}
+
+/// Pretty print body if it's json, otherwise print it as is
+fn print_body(body: &[u8], writer: &mut ClickWriter) {
+    match serde_json::from_slice::<Value>(body) {
+        Ok(val) => clickwriteln!(
+            writer,
+            "{}",
+            serde_json::to_string_pretty(&val).unwrap_or_default()
+        ),
+        Err(_) if body.is_empty() => {}
+        Err(_) => {
+            let text = String::from_utf8_lossy(body);
+            if text.ends_with('\n') {
+                clickwrite!(writer, "{}", text);
+            } else {
+                clickwriteln!(writer, "{}", text);
+            }
+        }
+    }
+}
+
+command!(
+    Raw,
+    "raw",
+    "Send a request to any api path using the current context's credentials, and print the \
+     response",
+    |clap: App<'static, 'static>| clap
+        .arg(
+            Arg::with_name("method")
+                .help("The http method to use")
+                .required(true)
+                .possible_values(RAW_METHODS)
+                .case_insensitive(true)
+                .index(1)
+        )
+        .arg(
+            Arg::with_name("path")
+                .help(
+                    "The api path to request, including any query. {namespace} is replaced with \
+                     the current namespace"
+                )
+                .required(true)
+                .index(2)
+        )
+        .arg(
+            Arg::with_name("body")
+                .help("The body to send")
+                .conflicts_with("file")
+                .index(3)
+        )
+        .arg(
+            Arg::with_name("file")
+                .short("f")
+                .long("file")
+                .value_name("FILE")
+                .help("Read the body to send from this file, or stdin if FILE is -")
+                .takes_value(true)
+        )
+        .arg(
+            Arg::with_name("content_type")
+                .long("content-type")
+                .value_name("TYPE")
+                .help(
+                    "The content type of the body. Defaults to application/merge-patch+json for \
+                     PATCH and application/json otherwise"
+                )
+                .takes_value(true)
+        )
+        .after_help(
+            "Json responses are pretty printed, anything else is printed as it is. If the server \
+             returns an error status the response is still printed, and the command fails.
+
+Examples:
+  # list deployments in all namespaces
+  raw GET /apis/apps/v1/deployments
+
+  # get the configmaps in the current namespace (or the one given with --namespace)
+  raw get /api/v1/namespaces/{namespace}/configmaps
+
+  # scale a deployment
+  raw patch /apis/apps/v1/namespaces/{namespace}/deployments/web/scale '{\"spec\":{\"replicas\":3}}'
+
+  # create an object from a json file
+  raw POST /api/v1/namespaces/{namespace}/configmaps -f cm.json"
+        ),
+    vec!["raw"],
+    vec![&completer::rawmethod_values_completer],
+    no_named_complete!(),
+    |matches, env, writer| {
+        let method: Method = matches
+            .value_of("method")
+            .unwrap() // safe, required
+            .to_uppercase()
+            .parse()
+            .unwrap(); // safe, one of RAW_METHODS
+        let path = expand_path(
+            matches.value_of("path").unwrap(), // safe, required
+            env.namespace.as_deref(),
+        )?;
+        let body = match (matches.value_of("body"), matches.value_of("file")) {
+            (Some(body), _) => body.as_bytes().to_vec(),
+            (None, Some(file)) => read_input(file)?.into_bytes(),
+            (None, None) => vec![],
+        };
+        let content_type = match matches.value_of("content_type") {
+            Some(content_type) => content_type,
+            None if method == Method::PATCH => "application/merge-patch+json",
+            None => "application/json",
+        };
+        let mut builder = Request::builder().method(method).uri(&path);
+        if !body.is_empty() {
+            builder = builder.header(http::header::CONTENT_TYPE, content_type);
+        }
+        let request = builder
+            .body(body)
+            .map_err(|e| ClickError::CommandError(format!("Invalid request: {}", e)))?;
+        let response = env.run_on_context(|c| c.execute(request))?;
+        print_body(response.body(), writer);
+        let status = response.status();
+        if status.is_success() {
+            Ok(())
+        } else {
+            Err(ClickError::Status(
+                status.as_u16(),
+                status.canonical_reason().unwrap_or("").to_string(),
+            ))
+        }
+    }
+);
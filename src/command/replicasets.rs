@@ -18,7 +18,7 @@ use k8s_openapi::api::apps::v1 as apps_api;
 
 use crate::{
     command::command_def::{exec_match, show_arg, sort_arg, start_clap, Cmd},
-    command::{keyval_string, run_list_command, Extractor},
+    command::{keyval_string, list_namespace, run_list_command, Extractor},
     completer,
     env::Env,
     kobj::{KObj, ObjType},
@@ -164,7 +164,7 @@ list_command!(
     noop_complete!(),
     IntoIter::new([]),
     |matches, env, writer| {
-        let (request, _response_body) = match &env.namespace {
+        let (request, _response_body) = match list_namespace(&matches, env) {
             Some(ns) => apps_api::ReplicaSet::list_namespaced_replica_set(ns, Default::default())?,
             None => apps_api::ReplicaSet::list_replica_set_for_all_namespaces(Default::default())?,
         };
@@ -24,7 +24,10 @@ use serde_json::{value::from_value, Error, Value};
 
 use crate::{
     command::command_def::{exec_match, show_arg, sort_arg, start_clap, Cmd},
-    command::{get_list_request_for_url, get_read_request_for_url, run_list_command, Extractor},
+    command::{
+        get_list_request_for_url, get_read_request_for_url, list_namespace, run_list_command,
+        Extractor,
+    },
     completer,
     env::Env,
     kobj::{KObj, ObjType},
@@ -122,7 +125,7 @@ list_command!(
     noop_complete!(),
     IntoIter::new([]),
     |matches, env, writer| {
-        let (request, _response_body) = match &env.namespace {
+        let (request, _response_body) = match list_namespace(&matches, env) {
             Some(ns) => RolloutValue::list_namespaced_rollout(ns, Default::default())?,
             None => RolloutValue::list_rollout_for_all_namespaces(Default::default())?,
         };
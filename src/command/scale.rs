@@ -0,0 +1,190 @@
+// Copyright 2021 Databricks, Inc.
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+
+// http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use ansi_term::Colour::Yellow;
+use clap::{App, Arg};
+use k8s_openapi::{
+    api::autoscaling::v1 as api_autoscaling, apimachinery::pkg::apis::meta::v1::Patch,
+    http::Request, PatchOptional,
+};
+use rustyline::completion::Pair as RustlinePair;
+use serde_json::{json, Value};
+
+use crate::{
+    command::command_def::{exec_match, start_clap, Cmd},
+    command::{dry_run_arg, field_manager_arg, valid_u32, DryRun},
+    completer,
+    env::Env,
+    error::ClickError,
+    kobj::{KObj, ObjType},
+    output::ClickWriter,
+    values::val_str,
+};
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::io::Write;
+
+/// Send a request to a scale subresource, and parse the Scale it returns
+fn send_scale_request(
+    env: &Env,
+    request: Request<Vec<u8>>,
+) -> Result<api_autoscaling::Scale, ClickError> {
+    let response = env.run_on_context(|c| c.execute(request))?;
+    if response.status().is_success() {
+        serde_json::from_slice(response.body()).map_err(ClickError::from)
+    } else {
+        let message = serde_json::from_slice::<Value>(response.body())
+            .map(|val| val_str("/message", &val, "<No message>").into_owned())
+            .unwrap_or_else(|_| "<No message>".to_string());
+        Err(ClickError::CommandError(format!(
+            "Scale request failed ({}). Message: {}",
+            response.status(),
+            message
+        )))
+    }
+}
+
+fn replica_count(scale: &api_autoscaling::Scale) -> i32 {
+    scale.spec.as_ref().and_then(|s| s.replicas).unwrap_or(0)
+}
+
+fn scale_obj(
+    env: &Env,
+    obj: &KObj,
+    replicas: i32,
+    dry_run: DryRun,
+    field_manager: Option<&str>,
+    writer: &mut ClickWriter,
+) -> Result<(), ClickError> {
+    let ns = match obj.namespace.as_ref() {
+        Some(ns) => ns.as_str(),
+        None => {
+            return Err(ClickError::CommandError(format!(
+                "{} has no namespace, cannot scale",
+                obj.name()
+            )))
+        }
+    };
+    let name = obj.name();
+    let patch = Patch::Merge(json!({ "spec": { "replicas": replicas } }));
+    let opts = PatchOptional {
+        dry_run: dry_run.param(),
+        field_manager,
+        ..Default::default()
+    };
+    let (read, update) = match obj.typ {
+        ObjType::Deployment => (
+            api_autoscaling::Scale::read_namespaced_deployment_scale(name, ns, Default::default())?
+                .0,
+            api_autoscaling::Scale::patch_namespaced_deployment_scale(name, ns, &patch, opts)?.0,
+        ),
+        ObjType::ReplicaSet => (
+            api_autoscaling::Scale::read_namespaced_replica_set_scale(
+                name,
+                ns,
+                Default::default(),
+            )?
+            .0,
+            api_autoscaling::Scale::patch_namespaced_replica_set_scale(name, ns, &patch, opts)?.0,
+        ),
+        ObjType::StatefulSet => (
+            api_autoscaling::Scale::read_namespaced_stateful_set_scale(
+                name,
+                ns,
+                Default::default(),
+            )?
+            .0,
+            api_autoscaling::Scale::patch_namespaced_stateful_set_scale(name, ns, &patch, opts)?.0,
+        ),
+        _ => {
+            return Err(ClickError::CommandError(format!(
+                "Can't scale {} {}, only deployments, replicasets and statefulsets can be scaled",
+                obj.type_str(),
+                name
+            )))
+        }
+    };
+    let old = replica_count(&send_scale_request(env, read)?);
+    let new = if dry_run == DryRun::Client {
+        replicas
+    } else {
+        replica_count(&send_scale_request(env, update)?)
+    };
+    clickwriteln!(
+        writer,
+        "Scaled {} {} from {} to {} replicas{}",
+        obj.type_str(),
+        name,
+        old,
+        new,
+        dry_run.suffix()
+    );
+    Ok(())
+}
+
+command!(
+    Scale,
+    "scale",
+    "Set the number of replicas for the active deployment, replicaset, or statefulset",
+    |clap: App<'static, 'static>| clap
+        .arg(
+            Arg::with_name("count")
+                .help("The number of replicas to scale to")
+                .validator(valid_u32)
+                .required_unless("replicas")
+                .conflicts_with("replicas")
+                .index(1)
+        )
+        .arg(
+            Arg::with_name("replicas")
+                .long("replicas")
+                .help("The number of replicas to scale to (alternative to passing a count)")
+                .validator(valid_u32)
+                .takes_value(true)
+        )
+        .arg(dry_run_arg())
+        .arg(field_manager_arg())
+        .after_help(
+            "Examples:
+  # Scale the active deployment to 3 replicas
+  scale 3
+
+  # Scale down to nothing
+  scale --replicas 0
+
+  # Check that scaling to 5 would be accepted, without changing anything
+  scale 5 --dry-run server"
+        ),
+    vec!["scale"],
+    noop_complete!(),
+    no_named_complete!(),
+    |matches, env, writer| {
+        let replicas = matches
+            .value_of("count")
+            .or_else(|| matches.value_of("replicas"))
+            .unwrap() // safe, one is required
+            .parse::<i32>()
+            .map_err(|e| ClickError::CommandError(format!("Invalid replica count: {}", e)))?;
+        let dry_run = DryRun::from_matches(&matches);
+        let field_manager = matches.value_of("field_manager");
+        let res = env.apply_to_selection(
+            writer,
+            Some(&env.click_config.range_separator),
+            |obj, writer| scale_obj(env, obj, replicas, dry_run, field_manager, writer),
+        );
+        env.invalidate_cache_for_selection();
+        res
+    }
+);
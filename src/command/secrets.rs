@@ -15,15 +15,19 @@
 use ansi_term::Colour::Yellow;
 use clap::{App, Arg};
 use k8s_openapi::api::core::v1 as api;
+use k8s_openapi::http::Request;
+use serde_json::Value;
 
 use crate::{
     command::command_def::{exec_match, show_arg, sort_arg, start_clap, Cmd},
-    command::{run_list_command, Extractor},
+    command::{list_namespace, run_list_command, Extractor},
     completer,
     env::Env,
+    error::ClickError,
     kobj::{KObj, ObjType},
     output::ClickWriter,
     table::CellSpec,
+    values::val_str,
 };
 
 use std::array::IntoIter;
@@ -52,6 +56,9 @@ const EXTRA_COL_MAP: &[(&str, &str)] = &[("namespace", "Namespace"), ("labels",
 
 const EXTRA_COL_FLAGS: &[&str] = &{ extract_first!(EXTRA_COL_MAP) };
 
+/// How many bytes of a binary value to show (as hex) when decoding
+const BINARY_PREVIEW_BYTES: usize = 16;
+
 fn secret_to_kobj(secret: &api::Secret) -> KObj {
     let meta = &secret.metadata;
     KObj {
@@ -103,7 +110,7 @@ list_command!(
     noop_complete!(),
     IntoIter::new([]),
     |matches, env, writer| {
-        let (request, _response_body) = match &env.namespace {
+        let (request, _response_body) = match list_namespace(&matches, env) {
             Some(ns) => api::Secret::list_namespaced_secret(ns, Default::default())?,
             None => api::Secret::list_secret_for_all_namespaces(Default::default())?,
         };
@@ -122,3 +129,132 @@ list_command!(
         )
     }
 );
+
+/// How to show a decoded value: as is if it's text, otherwise its size and first few bytes in hex
+fn decoded_str(decoded: &[u8]) -> String {
+    match std::str::from_utf8(decoded) {
+        Ok(text) => text.to_string(),
+        Err(_) => {
+            let hex: String = decoded
+                .iter()
+                .take(BINARY_PREVIEW_BYTES)
+                .map(|b| format!("{:02x}", b))
+                .collect();
+            let more = if decoded.len() > BINARY_PREVIEW_BYTES {
+                "..."
+            } else {
+                ""
+            };
+            format!("<binary, {} bytes: {}{}>", decoded.len(), hex, more)
+        }
+    }
+}
+
+fn decode_secret(
+    env: &Env,
+    obj: &KObj,
+    keys: &[&str],
+    show: bool,
+    writer: &mut ClickWriter,
+) -> Result<(), ClickError> {
+    if !obj.is(ObjType::Secret) {
+        return Err(ClickError::CommandError(format!(
+            "{} is a {}, not a secret",
+            obj.name(),
+            obj.type_str()
+        )));
+    }
+    let request = Request::get(obj.api_path(env)?).body(vec![]).unwrap(); // safe, valid path
+    let response = env.run_on_context(|c| c.execute(request))?;
+    let secret: Value = serde_json::from_slice(response.body())?;
+    if !response.status().is_success() {
+        return Err(ClickError::CommandError(format!(
+            "Could not get secret {} ({}): {}",
+            obj.name(),
+            response.status(),
+            val_str("/message", &secret, "<No message>")
+        )));
+    }
+    let data = secret.get("data").and_then(|d| d.as_object());
+    for key in keys.iter() {
+        if !data.map(|d| d.contains_key(*key)).unwrap_or(false) {
+            return Err(ClickError::CommandError(format!(
+                "Secret {} has no key {}",
+                obj.name(),
+                key
+            )));
+        }
+    }
+    let data = match data {
+        Some(data) if !data.is_empty() => data,
+        _ => {
+            clickwriteln!(writer, "Secret {} has no data", obj.name());
+            return Ok(());
+        }
+    };
+    for (key, val) in data.iter() {
+        if !keys.is_empty() && !keys.contains(&key.as_str()) {
+            continue;
+        }
+        let decoded = match val.as_str().map(base64::decode) {
+            Some(Ok(decoded)) => decoded,
+            _ => {
+                clickwriteln!(writer, "{}: <could not decode>", key);
+                continue;
+            }
+        };
+        if show {
+            clickwriteln!(writer, "{}: {}", key, decoded_str(&decoded));
+        } else {
+            clickwriteln!(writer, "{}: <{} bytes hidden>", key, decoded.len());
+        }
+    }
+    if !show {
+        clickwriteln!(writer, "(pass --show to reveal the values)");
+    }
+    Ok(())
+}
+
+command!(
+    Decode,
+    "decode",
+    "Show the keys of the active secret with their base64 decoded values. Values are hidden \
+     unless --show is passed",
+    |clap: App<'static, 'static>| clap
+        .arg(
+            Arg::with_name("show")
+                .long("show")
+                .help("Reveal the decoded values. Without this only their sizes are shown")
+                .takes_value(false)
+        )
+        .arg(
+            Arg::with_name("keys")
+                .help("Only decode these keys (default is all keys)")
+                .multiple(true)
+        )
+        .after_help(
+            "Values that aren't valid utf-8 are shown as their size and first bytes in hex.
+
+Examples:
+  # see which keys the active secret has, and how big their values are
+  decode
+
+  # reveal the password key
+  decode --show password"
+        ),
+    vec!["decode"],
+    noop_complete!(),
+    no_named_complete!(),
+    |matches, env, writer| {
+        let keys: Vec<&str> = matches
+            .values_of("keys")
+            .map(|keys| keys.collect())
+            .unwrap_or_default();
+        let show = matches.is_present("show");
+        env.apply_to_selection(
+            writer,
+            Some(&env.click_config.range_separator),
+            |obj, writer| decode_secret(env, obj, &keys, show, writer),
+        )
+    }
+);
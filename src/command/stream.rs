@@ -0,0 +1,338 @@
+// Copyright 2021 Databricks, Inc.
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+
+// http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use ansi_term::Colour::{Green, Red, Yellow};
+use chrono::Local;
+use clap::{App, Arg};
+use k8s_openapi::api::core::v1 as api;
+use k8s_openapi::http::{Request, StatusCode};
+use k8s_openapi::{List, ListOptional, WatchOptional};
+use prettytable::Row;
+use rustyline::completion::Pair as RustlinePair;
+use serde_json::Value;
+
+use crate::{
+    command::command_def::{all_namespaces_arg, exec_match, selector_arg, start_clap, Cmd},
+    command::pods::{pod_to_kobj, COL_MAP, POD_EXTRACTORS},
+    command::{build_specs, list_namespace, FieldColumns},
+    completer,
+    env::Env,
+    error::ClickError,
+    output::{clear_screen, ClickWriter},
+    parser::parse_label_selector,
+    table::title_cell,
+    values::{val_str, val_u64},
+};
+
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::io::{BufRead, BufReader, Write};
+use std::sync::atomic::Ordering;
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::Duration;
+
+/// How long the server keeps a watch open. When it closes we start a new one from where it left
+/// off, so this only bounds how long a dead connection can go unnoticed
+const WATCH_TIMEOUT_SECS: i64 = 300;
+
+/// How many of the most recent changes are shown under the table
+const RECENT_CHANGES: usize = 10;
+
+/// The pods we know about, keyed by namespace and name, and the changes we've seen to them
+struct PodState {
+    pods: BTreeMap<(String, String), api::Pod>,
+    changes: VecDeque<String>,
+    resource_version: String,
+}
+
+fn pod_key(pod: &api::Pod) -> (String, String) {
+    (
+        pod.metadata.namespace.clone().unwrap_or_default(),
+        pod.metadata.name.clone().unwrap_or_default(),
+    )
+}
+
+impl PodState {
+    /// List the pods, replacing anything we had. Returns the resource version to watch from
+    fn relist(
+        &mut self,
+        env: &Env,
+        namespace: Option<&str>,
+        selector: Option<&str>,
+    ) -> Result<(), ClickError> {
+        let opts = ListOptional {
+            label_selector: selector,
+            ..Default::default()
+        };
+        let (request, _) = match namespace {
+            Some(ns) => api::Pod::list_namespaced_pod(ns, opts)?,
+            None => api::Pod::list_pod_for_all_namespaces(opts)?,
+        };
+        let list: List<api::Pod> = env.run_on_context(|c| c.execute_list(request))?;
+        self.pods = list
+            .items
+            .into_iter()
+            .map(|pod| (pod_key(&pod), pod))
+            .collect();
+        self.resource_version = list.metadata.resource_version.unwrap_or_default();
+        Ok(())
+    }
+
+    fn record_change(&mut self, typ: &str, pod: &api::Pod) {
+        let colored_type = match typ {
+            "ADDED" => Green.paint(typ),
+            "DELETED" => Red.paint(typ),
+            _ => Yellow.paint(typ),
+        };
+        let (ns, name) = pod_key(pod);
+        let phase = pod
+            .status
+            .as_ref()
+            .and_then(|s| s.phase.as_deref())
+            .unwrap_or("Unknown");
+        self.changes.push_back(format!(
+            "{} {:8} {}/{} ({})",
+            Local::now().format("%H:%M:%S"),
+            colored_type,
+            ns,
+            name,
+            phase
+        ));
+        if self.changes.len() > RECENT_CHANGES {
+            self.changes.pop_front();
+        }
+    }
+
+    /// Apply a watch event to the pods. Returns Ok(false) if the resource version we were watching
+    /// from has expired, and we need to list again
+    fn apply_event(&mut self, event: &Value) -> Result<bool, ClickError> {
+        let typ = val_str("/type", event, "");
+        let object = event.get("object").cloned().unwrap_or(Value::Null);
+        if typ == "ERROR" {
+            if val_u64("/code", &object, 0) == u64::from(StatusCode::GONE.as_u16()) {
+                return Ok(false);
+            }
+            return Err(ClickError::CommandError(format!(
+                "Watch failed: {}",
+                val_str("/message", &object, "no message returned")
+            )));
+        }
+        let pod: api::Pod = serde_json::from_value(object)?;
+        if let Some(rv) = pod.metadata.resource_version.as_ref() {
+            self.resource_version = rv.clone();
+        }
+        match &*typ {
+            "ADDED" | "MODIFIED" => {
+                self.record_change(&typ, &pod);
+                self.pods.insert(pod_key(&pod), pod);
+            }
+            "DELETED" => {
+                self.record_change(&typ, &pod);
+                self.pods.remove(&pod_key(&pod));
+            }
+            _ => {} // BOOKMARK, or something new. either way the resource version is all we need
+        }
+        Ok(true)
+    }
+
+    /// Clear the screen and print the pods and the latest changes
+    fn draw(&self, env: &mut Env, namespace: Option<&str>, writer: &mut ClickWriter) {
+        clear_screen();
+        clickwriteln!(
+            writer,
+            "Streaming pods in {} (Ctrl-C to stop)    {}\n",
+            namespace.unwrap_or("all namespaces"),
+            Local::now().format("%a %b %e %H:%M:%S %Y")
+        );
+        let mut cols: Vec<&str> = COL_MAP.iter().map(|(_, col)| *col).collect();
+        if namespace.is_none() {
+            cols.insert(1, "Namespace");
+        }
+        let list = List {
+            items: self.pods.values().cloned().collect(),
+            metadata: Default::default(),
+        };
+        let specs = build_specs(
+            &cols,
+            &list,
+            Some(&POD_EXTRACTORS),
+            &FieldColumns::new(),
+            true,
+            None,
+            pod_to_kobj,
+        );
+        let (kobjs, rows): (Vec<_>, Vec<_>) = specs.into_iter().unzip();
+        let title_colour = env.table_header_colour();
+        let mut titles = vec![title_cell("####", title_colour)];
+        titles.extend(cols.iter().map(|col| title_cell(col, title_colour)));
+        crate::table::print_table(Row::new(titles), rows, writer);
+        if !self.changes.is_empty() {
+            clickwriteln!(writer, "\nRecent changes:");
+            for change in self.changes.iter() {
+                clickwriteln!(writer, "  {}", change);
+            }
+        }
+        writer.flush().unwrap_or(());
+        env.set_last_objs(kobjs);
+    }
+}
+
+/// Start a watch from resource_version, returning a channel that gets each event as it arrives.
+/// The channel disconnects when the watch ends
+fn start_watch(
+    env: &Env,
+    namespace: Option<&str>,
+    selector: Option<&str>,
+    resource_version: &str,
+) -> Result<Receiver<Result<Value, ClickError>>, ClickError> {
+    let opts = WatchOptional {
+        label_selector: selector,
+        resource_version: Some(resource_version),
+        timeout_seconds: Some(WATCH_TIMEOUT_SECS),
+        ..Default::default()
+    };
+    let (request, _): (Request<Vec<u8>>, _) = match namespace {
+        Some(ns) => api::Pod::watch_namespaced_pod(ns, opts)?,
+        None => api::Pod::watch_pod_for_all_namespaces(opts)?,
+    };
+    let timeout = Duration::from_secs(WATCH_TIMEOUT_SECS as u64 + 5);
+    let response = env.run_on_context(|c| c.execute_reader(request, Some(timeout)))?;
+    let (sender, receiver) = channel();
+    thread::spawn(move || {
+        for line in BufReader::new(response).lines() {
+            let event = match line {
+                Ok(line) if line.trim().is_empty() => continue,
+                Ok(line) => serde_json::from_str(&line).map_err(ClickError::from),
+                // the connection dropped or timed out, we'll start a new watch
+                Err(_) => break,
+            };
+            if sender.send(event).is_err() {
+                // probably user hit ctrl-c, just stop
+                break;
+            }
+        }
+    });
+    Ok(receiver)
+}
+
+fn is_gone(err: &ClickError) -> bool {
+    match err {
+        ClickError::Reqwest(e, _) => e.status() == Some(StatusCode::GONE),
+        _ => false,
+    }
+}
+
+/// Keep the table of pods up to date until ctrl-c is pressed. Each watch starts from the
+/// resource version of the last change we saw, so nothing is missed between watches, and if that
+/// version has expired we list everything again
+fn stream_pods(
+    env: &mut Env,
+    namespace: Option<&str>,
+    selector: Option<&str>,
+    writer: &mut ClickWriter,
+) -> Result<(), ClickError> {
+    let mut state = PodState {
+        pods: BTreeMap::new(),
+        changes: VecDeque::new(),
+        resource_version: String::new(),
+    };
+    state.relist(env, namespace, selector)?;
+    state.draw(env, namespace, writer);
+    while !env.ctrlcbool.load(Ordering::SeqCst) {
+        let receiver = match start_watch(env, namespace, selector, &state.resource_version) {
+            Ok(receiver) => receiver,
+            Err(ref e) if is_gone(e) => {
+                state.relist(env, namespace, selector)?;
+                state.draw(env, namespace, writer);
+                continue;
+            }
+            Err(_) if env.ctrlcbool.load(Ordering::SeqCst) => break,
+            Err(e) => return Err(e),
+        };
+        let mut relist = false;
+        while !relist && !env.ctrlcbool.load(Ordering::SeqCst) {
+            let event = match receiver.recv_timeout(Duration::from_secs(1)) {
+                Ok(event) => event?,
+                Err(RecvTimeoutError::Timeout) => continue,
+                Err(RecvTimeoutError::Disconnected) => break,
+            };
+            relist = !state.apply_event(&event)?;
+            // apply everything that's already arrived before redrawing, so a burst of changes
+            // only redraws once
+            while !relist {
+                match receiver.try_recv() {
+                    Ok(event) => relist = !state.apply_event(&event?)?,
+                    Err(_) => break,
+                }
+            }
+            if !relist {
+                state.draw(env, namespace, writer);
+            }
+        }
+        if relist {
+            state.relist(env, namespace, selector)?;
+            state.draw(env, namespace, writer);
+        }
+    }
+    Ok(())
+}
+
+command!(
+    Stream,
+    "stream",
+    "Show a table of objects that updates as they change on the server, until Ctrl-C is pressed",
+    |clap: App<'static, 'static>| clap
+        .arg(
+            Arg::with_name("kind")
+                .help("The kind of object to stream")
+                .required(true)
+                .possible_values(&["pods", "pod", "po"])
+                .index(1)
+        )
+        .arg(all_namespaces_arg())
+        .arg(selector_arg())
+        .after_help(
+            "Unlike watch, which lists everything again every few seconds, stream lists the \
+             objects once and then has the server send each change as it happens, which is much \
+             lighter on busy namespaces. The latest changes are shown under the table. When \
+             stopped, the objects in the table can be selected by number, like any list.
+
+Examples:
+  # stream the pods in the current namespace
+  stream pods
+
+  # stream pods with the label app=web in all namespaces
+  stream pods -A -l app=web"
+        ),
+    vec!["stream"],
+    noop_complete!(),
+    no_named_complete!(),
+    |matches, env, writer| {
+        let namespace = list_namespace(&matches, env).map(|ns| ns.to_string());
+        let selector = matches.value_of("selector").map(|sel| {
+            // already validated, so parse can't fail
+            let reqs: Vec<String> = parse_label_selector(sel)
+                .unwrap()
+                .iter()
+                .map(|r| r.to_string())
+                .collect();
+            reqs.join(",")
+        });
+        env.ctrlcbool.store(false, Ordering::SeqCst);
+        let res = stream_pods(env, namespace.as_deref(), selector.as_deref(), writer);
+        env.ctrlcbool.store(false, Ordering::SeqCst);
+        res
+    }
+);
@@ -0,0 +1,285 @@
+// Copyright 2021 Databricks, Inc.
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+
+// http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use ansi_term::Colour::Yellow;
+use clap::{App, Arg};
+use k8s_openapi::api::core::v1 as api;
+use k8s_openapi::http::{Request, StatusCode};
+use k8s_openapi::List;
+use prettytable::{Cell, Row, Table};
+use rustyline::completion::Pair as RustlinePair;
+use serde::de::DeserializeOwned;
+
+use crate::{
+    command::command_def::{exec_match, start_clap, Cmd},
+    completer,
+    env::Env,
+    error::ClickError,
+    output::ClickWriter,
+    values::{format_cpu, format_memory, parse_quantity},
+};
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::io::Write;
+
+const METRICS_PREFIX: &str = "/apis/metrics.k8s.io/v1beta1";
+
+#[derive(Deserialize)]
+struct MetricsMeta {
+    name: String,
+    namespace: Option<String>,
+}
+
+/// Resource usage as reported by metrics-server. Values are quantities, like "250m" or "64Mi"
+#[derive(Deserialize)]
+struct Usage {
+    cpu: String,
+    memory: String,
+}
+
+#[derive(Deserialize)]
+struct ContainerMetrics {
+    name: String,
+    usage: Usage,
+}
+
+#[derive(Deserialize)]
+struct PodMetrics {
+    metadata: MetricsMeta,
+    containers: Vec<ContainerMetrics>,
+}
+
+#[derive(Deserialize)]
+struct NodeMetrics {
+    metadata: MetricsMeta,
+    usage: Usage,
+}
+
+#[derive(Deserialize)]
+struct MetricsList<T> {
+    items: Vec<T>,
+}
+
+fn usage_values(usage: &Usage) -> (f64, f64) {
+    (
+        parse_quantity(&usage.cpu).unwrap_or(0.0),
+        parse_quantity(&usage.memory).unwrap_or(0.0),
+    )
+}
+
+fn format_percent(used: f64, available: Option<f64>) -> String {
+    match available {
+        Some(available) if available > 0.0 => format!("{}%", (used / available * 100.0).round()),
+        _ => "<unknown>".to_string(),
+    }
+}
+
+/// Get something from the metrics api, turning a missing api into an error that explains why
+fn get_metrics<T: DeserializeOwned>(env: &Env, path: &str) -> Result<T, ClickError> {
+    let request = Request::get(format!("{}{}", METRICS_PREFIX, path))
+        .body(vec![])
+        .unwrap(); // safe, path is built from names that are valid in urls
+    let response = env.run_on_context(|c| c.execute(request))?;
+    match response.status() {
+        status if status.is_success() => {
+            serde_json::from_slice(response.body()).map_err(|e| e.into())
+        }
+        StatusCode::NOT_FOUND | StatusCode::SERVICE_UNAVAILABLE => Err(ClickError::CommandError(
+            "The metrics API isn't available on this cluster. top needs metrics-server to be \
+             installed and running, see https://github.com/kubernetes-sigs/metrics-server"
+                .to_string(),
+        )),
+        status => Err(ClickError::CommandError(format!(
+            "Failed to get metrics: {}",
+            status
+        ))),
+    }
+}
+
+/// Sort rows of (cpu, memory, ...) by the specified resource, largest first
+fn sort_rows<T>(rows: &mut [(f64, f64, T)], sort_by: Option<&str>) {
+    match sort_by {
+        Some("cpu") => rows.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap()),
+        Some("memory") => rows.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap()),
+        _ => {} // leave in the order the server returned
+    }
+}
+
+/// The pod and container a row of top pods is for. The container is None for a row that's a whole
+/// pod
+type PodRow<'a> = (&'a MetricsMeta, Option<&'a str>);
+
+/// Show usage for each pod, or for each container of each pod if containers is true
+fn top_pods(
+    env: &Env,
+    sort_by: Option<&str>,
+    containers: bool,
+    writer: &mut ClickWriter,
+) -> Result<(), ClickError> {
+    let path = match env.namespace.as_ref() {
+        Some(ns) => format!("/namespaces/{}/pods", ns),
+        None => "/pods".to_string(),
+    };
+    let metrics: MetricsList<PodMetrics> = get_metrics(env, &path)?;
+    if metrics.items.is_empty() {
+        clickwriteln!(writer, "No pod metrics found");
+        return Ok(());
+    }
+
+    let include_namespace = env.namespace.is_none();
+    let mut rows: Vec<(f64, f64, PodRow)> = if containers {
+        metrics
+            .items
+            .iter()
+            .flat_map(|pod| {
+                pod.containers.iter().map(move |container| {
+                    let (cpu, memory) = usage_values(&container.usage);
+                    (cpu, memory, (&pod.metadata, Some(container.name.as_str())))
+                })
+            })
+            .collect()
+    } else {
+        metrics
+            .items
+            .iter()
+            .map(|pod| {
+                let (cpu, memory) = pod
+                    .containers
+                    .iter()
+                    .map(|container| usage_values(&container.usage))
+                    .fold((0.0, 0.0), |(c1, m1), (c2, m2)| (c1 + c2, m1 + m2));
+                (cpu, memory, (&pod.metadata, None))
+            })
+            .collect()
+    };
+    sort_rows(&mut rows, sort_by);
+
+    let mut table = Table::new();
+    let mut titles = vec![cell!("Name"), cell!("CPU"), cell!("Memory")];
+    if containers {
+        titles[0] = cell!("Pod");
+        titles.insert(1, cell!("Container"));
+    }
+    if include_namespace {
+        titles.insert(0, cell!("Namespace"));
+    }
+    table.set_titles(Row::new(titles));
+    for (cpu, memory, (meta, container)) in rows.iter() {
+        let mut row = vec![Cell::new(&meta.name)];
+        if let Some(container) = container {
+            row.push(Cell::new(container));
+        }
+        row.push(Cell::new(&format_cpu(*cpu)));
+        row.push(Cell::new(&format_memory(*memory)));
+        if include_namespace {
+            row.insert(0, Cell::new(meta.namespace.as_deref().unwrap_or("unknown")));
+        }
+        table.add_row(Row::new(row));
+    }
+    crate::table::print_filled_table(&mut table, writer);
+    Ok(())
+}
+
+fn top_nodes(env: &Env, sort_by: Option<&str>, writer: &mut ClickWriter) -> Result<(), ClickError> {
+    let metrics: MetricsList<NodeMetrics> = get_metrics(env, "/nodes")?;
+    if metrics.items.is_empty() {
+        clickwriteln!(writer, "No node metrics found");
+        return Ok(());
+    }
+
+    // percentages are of what's allocatable on each node
+    let (request, _body) = api::Node::list_node(Default::default())?;
+    let nodes: List<api::Node> = env.run_on_context(|c| c.execute_list(request))?;
+    let allocatable = |name: &str, resource: &str| {
+        nodes
+            .items
+            .iter()
+            .find(|node| node.metadata.name.as_deref() == Some(name))
+            .and_then(|node| node.status.as_ref())
+            .and_then(|status| status.allocatable.get(resource))
+            .and_then(|quantity| parse_quantity(&quantity.0))
+    };
+
+    let mut rows: Vec<(f64, f64, &MetricsMeta)> = metrics
+        .items
+        .iter()
+        .map(|node| {
+            let (cpu, memory) = usage_values(&node.usage);
+            (cpu, memory, &node.metadata)
+        })
+        .collect();
+    sort_rows(&mut rows, sort_by);
+
+    let mut table = Table::new();
+    table.set_titles(row!["Name", "CPU", "CPU%", "Memory", "Memory%"]);
+    for (cpu, memory, meta) in rows.iter() {
+        table.add_row(Row::new(vec![
+            Cell::new(&meta.name),
+            Cell::new(&format_cpu(*cpu)),
+            Cell::new(&format_percent(*cpu, allocatable(&meta.name, "cpu"))),
+            Cell::new(&format_memory(*memory)),
+            Cell::new(&format_percent(*memory, allocatable(&meta.name, "memory"))),
+        ]));
+    }
+    crate::table::print_filled_table(&mut table, writer);
+    Ok(())
+}
+
+command!(
+    Top,
+    "top",
+    "Show cpu and memory usage of pods or nodes. Pods are shown for the current namespace, or \
+     for all namespaces if no namespace is set. This needs metrics-server to be running in the \
+     cluster",
+    |clap: App<'static, 'static>| clap
+        .arg(
+            Arg::with_name("type")
+                .help("What to show usage for")
+                .required(true)
+                .possible_values(&["pods", "nodes"])
+                .index(1)
+        )
+        .arg(
+            Arg::with_name("sort_by")
+                .long("sort-by")
+                .value_name("RESOURCE")
+                .help("Sort by the specified resource, largest first")
+                .possible_values(&["cpu", "memory"])
+                .takes_value(true)
+        )
+        .arg(
+            Arg::with_name("containers")
+                .long("containers")
+                .help(
+                    "Show usage for each container of each pod, rather than each pod's total. \
+                     With --sort-by the containers are sorted on their own usage"
+                )
+                .takes_value(false)
+        ),
+    vec!["top"],
+    noop_complete!(),
+    no_named_complete!(),
+    |matches, env, writer| {
+        let sort_by = matches.value_of("sort_by");
+        let containers = matches.is_present("containers");
+        match matches.value_of("type") {
+            Some("nodes") if containers => Err(ClickError::CommandError(
+                "--containers can only be used with top pods".to_string(),
+            )),
+            Some("nodes") => top_nodes(env, sort_by, writer),
+            _ => top_pods(env, sort_by, containers, writer),
+        }
+    }
+);
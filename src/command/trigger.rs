@@ -0,0 +1,151 @@
+// Copyright 2021 Databricks, Inc.
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+
+// http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use ansi_term::Colour::Yellow;
+use chrono::Utc;
+use clap::{App, Arg};
+use k8s_openapi::http::{self, Request};
+use rustyline::completion::Pair as RustlinePair;
+use serde_json::Value;
+
+use crate::{
+    command::command_def::{exec_match, start_clap, Cmd},
+    completer,
+    cronjob::job_from_cronjob,
+    env::{Env, ObjectSelection},
+    error::ClickError,
+    kobj::{KObj, ObjType},
+    output::ClickWriter,
+    values::val_str,
+};
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::io::Write;
+
+// job names end up in the job-name label of the pods, which is limited to 63 characters
+const MAX_JOB_NAME_LEN: usize = 63;
+
+fn response_message(body: &[u8]) -> String {
+    serde_json::from_slice::<Value>(body)
+        .map(|val| val_str("/message", &val, "<No message>").into_owned())
+        .unwrap_or_else(|_| "<No message>".to_string())
+}
+
+/// cronjob-manual-<timestamp>, shortening the cronjob name if needed
+fn default_job_name(cronjob_name: &str) -> String {
+    let suffix = format!("-manual-{}", Utc::now().timestamp());
+    let max_prefix = MAX_JOB_NAME_LEN - suffix.len();
+    let prefix = if cronjob_name.len() > max_prefix {
+        cronjob_name[..max_prefix].trim_end_matches('-')
+    } else {
+        cronjob_name
+    };
+    format!("{}{}", prefix, suffix)
+}
+
+fn trigger_cronjob(
+    env: &Env,
+    obj: &KObj,
+    job_name: Option<&str>,
+    writer: &mut ClickWriter,
+) -> Result<(), ClickError> {
+    if obj.typ != ObjType::CronJob {
+        return Err(ClickError::CommandError(format!(
+            "{} is a {}, only cronjobs can be triggered",
+            obj.name(),
+            obj.type_str()
+        )));
+    }
+    let ns = obj.namespace.as_deref().ok_or_else(|| {
+        ClickError::CommandError(format!("{} has no namespace, cannot trigger", obj.name()))
+    })?;
+
+    let request = Request::get(obj.api_path(env)?).body(vec![]).unwrap(); // safe, valid path
+    let response = env.run_on_context(|c| c.execute(request))?;
+    if !response.status().is_success() {
+        return Err(ClickError::CommandError(format!(
+            "Could not get cronjob ({}): {}",
+            response.status(),
+            response_message(response.body())
+        )));
+    }
+    let cronjob: Value = serde_json::from_slice(response.body())?;
+
+    let job_name = job_name
+        .map(|name| name.to_string())
+        .unwrap_or_else(|| default_job_name(obj.name()));
+    let job = job_from_cronjob(&cronjob, &job_name)?;
+    let request = Request::post(format!("/apis/batch/v1/namespaces/{}/jobs", ns))
+        .header(http::header::CONTENT_TYPE, "application/json")
+        .body(serde_json::to_vec(&job)?)
+        .unwrap(); // safe, path built from valid names
+    let response = env.run_on_context(|c| c.execute(request))?;
+    if response.status().is_success() {
+        clickwriteln!(
+            writer,
+            "Created job {} from cronjob {}",
+            job_name,
+            obj.name()
+        );
+        Ok(())
+    } else {
+        Err(ClickError::CommandError(format!(
+            "Could not create job ({}): {}",
+            response.status(),
+            response_message(response.body())
+        )))
+    }
+}
+
+command!(
+    Trigger,
+    "trigger",
+    "Run the active cronjob now, by creating a job from its template (like kubectl create job \
+     --from=cronjob/<name>)",
+    |clap: App<'static, 'static>| clap.arg(
+        Arg::with_name("name")
+            .help(
+                "The name of the job to create. Defaults to <cronjob>-manual-<timestamp>. Can't \
+                 be used with a range of cronjobs selected"
+            )
+            .required(false)
+            .index(1)
+    ),
+    vec!["trigger"],
+    noop_complete!(),
+    no_named_complete!(),
+    |matches, env, writer| {
+        let job_name = matches.value_of("name");
+        if let (Some(_), ObjectSelection::Range(_)) = (job_name, env.current_selection()) {
+            return Err(ClickError::CommandError(
+                "Can't specify a job name when triggering a range of cronjobs".to_string(),
+            ));
+        }
+        let mut created_in = vec![];
+        let res = env.apply_to_selection(
+            writer,
+            Some(&env.click_config.range_separator),
+            |obj, writer| {
+                trigger_cronjob(env, obj, job_name, writer)?;
+                created_in.extend(obj.namespace.clone());
+                Ok(())
+            },
+        );
+        for ns in created_in.iter() {
+            env.invalidate_list_cache(ns, "Job");
+        }
+        res
+    }
+);
@@ -0,0 +1,173 @@
+// Copyright 2021 Databricks, Inc.
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+
+// http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use ansi_term::Colour::Yellow;
+use clap::{App, Arg};
+use k8s_openapi::api::core::v1 as api;
+
+use crate::{
+    command::command_def::{exec_match, show_arg, sort_arg, start_clap, Cmd},
+    command::{list_namespace, run_list_command, volumes::access_modes_str, Extractor},
+    completer,
+    env::Env,
+    kobj::{KObj, ObjType},
+    output::ClickWriter,
+    table::CellSpec,
+};
+
+use std::array::IntoIter;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::io::Write;
+
+lazy_static! {
+    static ref PVC_EXTRACTORS: HashMap<String, Extractor<api::PersistentVolumeClaim>> = {
+        let mut m: HashMap<String, Extractor<api::PersistentVolumeClaim>> = HashMap::new();
+        m.insert("Status".to_owned(), claim_status);
+        m.insert("Volume".to_owned(), claim_volume);
+        m.insert("Capacity".to_owned(), claim_capacity);
+        m.insert("Access Modes".to_owned(), claim_access_modes);
+        m.insert("Storage Class".to_owned(), claim_storage_class);
+        m.insert("Volume Mode".to_owned(), claim_volume_mode);
+        m
+    };
+}
+
+const COL_MAP: &[(&str, &str)] = &[
+    ("name", "Name"),
+    ("status", "Status"),
+    ("volume", "Volume"),
+    ("capacity", "Capacity"),
+    ("accessmodes", "Access Modes"),
+    ("storageclass", "Storage Class"),
+    ("age", "Age"),
+];
+
+const COL_FLAGS: &[&str] = &{ extract_first!(COL_MAP) };
+
+const EXTRA_COL_MAP: &[(&str, &str)] = &[
+    ("namespace", "Namespace"),
+    ("labels", "Labels"),
+    ("volumemode", "Volume Mode"),
+];
+
+const EXTRA_COL_FLAGS: &[&str] = &{ extract_first!(EXTRA_COL_MAP) };
+
+fn pvc_to_kobj(claim: &api::PersistentVolumeClaim) -> KObj {
+    let meta = &claim.metadata;
+    KObj {
+        name: meta.name.clone().unwrap_or_else(|| "<Unknown>".into()),
+        namespace: meta.namespace.clone(),
+        typ: ObjType::PersistentVolumeClaim,
+    }
+}
+
+fn claim_status(claim: &api::PersistentVolumeClaim) -> Option<CellSpec<'_>> {
+    claim
+        .status
+        .as_ref()
+        .and_then(|stat| stat.phase.as_ref().map(|p| p.as_str().into()))
+}
+
+fn claim_volume(claim: &api::PersistentVolumeClaim) -> Option<CellSpec<'_>> {
+    claim
+        .spec
+        .as_ref()
+        .map(|spec| spec.volume_name.as_deref().unwrap_or("").into())
+}
+
+// capacity is only known once the claim is bound, so it's in the status
+fn claim_capacity(claim: &api::PersistentVolumeClaim) -> Option<CellSpec<'_>> {
+    claim.status.as_ref().map(|stat| {
+        stat.capacity
+            .get("storage")
+            .map(|q| q.0.as_str())
+            .unwrap_or("")
+            .into()
+    })
+}
+
+fn claim_access_modes(claim: &api::PersistentVolumeClaim) -> Option<CellSpec<'_>> {
+    claim
+        .status
+        .as_ref()
+        .map(|stat| access_modes_str(&stat.access_modes).into())
+}
+
+fn claim_storage_class(claim: &api::PersistentVolumeClaim) -> Option<CellSpec<'_>> {
+    claim.spec.as_ref().and_then(|spec| {
+        spec.storage_class_name
+            .as_ref()
+            .map(|sc| sc.as_str().into())
+    })
+}
+
+fn claim_volume_mode(claim: &api::PersistentVolumeClaim) -> Option<CellSpec<'_>> {
+    claim
+        .spec
+        .as_ref()
+        .and_then(|spec| spec.volume_mode.as_ref().map(|mode| mode.as_str().into()))
+}
+
+list_command!(
+    PersistentVolumeClaims,
+    "persistentvolumeclaims",
+    "Get persistent volume claims (in current namespace if set)",
+    super::COL_FLAGS,
+    super::EXTRA_COL_FLAGS,
+    |clap: App<'static, 'static>| {
+        clap.arg(
+            Arg::with_name("regex")
+                .short("r")
+                .long("regex")
+                .help("Filter pvcs by the specified regex")
+                .takes_value(true),
+        )
+        .arg(show_arg(EXTRA_COL_FLAGS, true))
+        .arg(sort_arg(COL_FLAGS, Some(EXTRA_COL_FLAGS)))
+        .arg(
+            Arg::with_name("reverse")
+                .short("R")
+                .long("reverse")
+                .help("Reverse the order of the returned list")
+                .takes_value(false),
+        )
+    },
+    vec!["persistentvolumeclaims", "pvcs"],
+    noop_complete!(),
+    no_named_complete!(),
+    |matches, env, writer| {
+        let (request, _response_body) = match list_namespace(&matches, env) {
+            Some(ns) => api::PersistentVolumeClaim::list_namespaced_persistent_volume_claim(
+                ns,
+                Default::default(),
+            )?,
+            None => api::PersistentVolumeClaim::list_persistent_volume_claim_for_all_namespaces(
+                Default::default(),
+            )?,
+        };
+        let cols: Vec<&str> = COL_MAP.iter().map(|(_, col)| *col).collect();
+        run_list_command(
+            matches,
+            env,
+            writer,
+            cols,
+            request,
+            COL_MAP,
+            Some(EXTRA_COL_MAP),
+            Some(&PVC_EXTRACTORS),
+            pvc_to_kobj,
+        )
+    }
+);
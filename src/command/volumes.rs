@@ -83,21 +83,26 @@ fn volume_capacity(volume: &api::PersistentVolume) -> Option<CellSpec<'_>> {
     })
 }
 
+/// Short forms of access modes, like kubectl shows
+pub fn access_modes_str(modes: &[String]) -> String {
+    modes
+        .iter()
+        .map(|mode| match mode.as_str() {
+            "ReadWriteOnce" => "RWO",
+            "ReadOnlyMany" => "ROX",
+            "ReadWriteMany" => "RWX",
+            "ReadWriteOncePod" => "RWOP",
+            _ => "Unknown",
+        })
+        .collect::<Vec<&str>>()
+        .join(", ")
+}
+
 fn volume_access_modes(volume: &api::PersistentVolume) -> Option<CellSpec<'_>> {
-    volume.spec.as_ref().map(|spec| {
-        spec.access_modes
-            .iter()
-            .map(|mode| match mode.as_str() {
-                "ReadWriteOnce" => "RWO",
-                "ReadOnlyMany" => "ROX",
-                "ReadWriteMany" => "RWX",
-                "ReadWriteOncePod" => "RWOP",
-                _ => "Unknown",
-            })
-            .collect::<Vec<&str>>()
-            .join(", ")
-            .into()
-    })
+    volume
+        .spec
+        .as_ref()
+        .map(|spec| access_modes_str(&spec.access_modes).into())
 }
 
 fn volume_reclaim_policy(volume: &api::PersistentVolume) -> Option<CellSpec<'_>> {
@@ -0,0 +1,138 @@
+// Copyright 2021 Databricks, Inc.
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+
+// http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use ansi_term::Colour::Yellow;
+use clap::{App, Arg};
+use duct_sh::sh_dangerous;
+use rustyline::completion::Pair as RustlinePair;
+
+use crate::{
+    command::command_def::{exec_match, start_clap, Cmd},
+    command::find_binary,
+    completer,
+    env::{Env, ObjectSelection},
+    error::ClickError,
+    kobj::KObj,
+    output::ClickWriter,
+};
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::io::Write;
+
+/// The clipboard tools we look for, and how to run them, in the order they're tried
+const CLIPBOARD_TOOLS: &[(&str, &str)] = &[
+    ("pbcopy", "pbcopy"),
+    ("wl-copy", "wl-copy"),
+    ("xclip", "xclip -selection clipboard"),
+    ("xsel", "xsel --clipboard --input"),
+];
+
+/// The command to copy with: the clipboard setting (with environment variables expanded),
+/// otherwise the first clipboard tool that's installed. wl-copy is skipped outside of wayland,
+/// since it can't work there
+fn clipboard_command(env: &Env) -> Option<String> {
+    if let Some(ref cmd) = env.click_config.clipboard {
+        return Some(crate::config::expand_env_vars(cmd));
+    }
+    let wayland = std::env::var_os("WAYLAND_DISPLAY").is_some();
+    CLIPBOARD_TOOLS
+        .iter()
+        .filter(|(bin, _)| wayland || *bin != "wl-copy")
+        .find(|(bin, _)| find_binary(bin).is_some())
+        .map(|(_, cmd)| cmd.to_string())
+}
+
+fn yank_name(obj: &KObj, with_namespace: bool) -> String {
+    match obj.namespace.as_ref() {
+        Some(ns) if with_namespace => format!("{}/{}", ns, obj.name),
+        _ => obj.name.clone(),
+    }
+}
+
+/// Run cmd with text as its input. stderr is left alone, so the tool's own errors are shown,
+/// and isn't captured, since tools like xclip stay running in the background holding it open
+fn copy_to_clipboard(cmd: &str, text: &str) -> Result<(), ClickError> {
+    let output = sh_dangerous(cmd)
+        .stdin_bytes(text.as_bytes())
+        .stdout_null()
+        .unchecked()
+        .run()?;
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(ClickError::CommandError(format!(
+            "Clipboard command '{}' failed ({})",
+            cmd, output.status
+        )))
+    }
+}
+
+command!(
+    Yank,
+    "yank",
+    "Copy the name of the active object (or the names of the active range) to the clipboard",
+    |clap: App<'static, 'static>| clap
+        .arg(
+            Arg::with_name("with_namespace")
+                .short("w")
+                .long("with-namespace")
+                .help("Copy namespace/name rather than just the name, for namespaced objects")
+                .takes_value(false)
+        )
+        .after_help(
+            "The clipboard is written with pbcopy, wl-copy, xclip or xsel, whichever is \
+             installed. Use 'set clipboard' to pick another command, which is given the text on \
+             its stdin. A range is copied one name per line.
+
+Examples:
+  # copy the name of the active pod
+  yank
+
+  # copy it as namespace/name
+  yank -w"
+        ),
+    vec!["yank", "copy-current"],
+    noop_complete!(),
+    no_named_complete!(),
+    |matches, env, writer| {
+        let with_namespace = matches.is_present("with_namespace");
+        let names: Vec<String> = match env.current_selection() {
+            ObjectSelection::Single(obj) => vec![yank_name(obj, with_namespace)],
+            ObjectSelection::Range(objs) => objs
+                .iter()
+                .map(|obj| yank_name(obj, with_namespace))
+                .collect(),
+            ObjectSelection::None => {
+                return Err(ClickError::CommandError(
+                    "No active object, select one first".to_string(),
+                ))
+            }
+        };
+        let cmd = clipboard_command(env).ok_or_else(|| {
+            ClickError::CommandError(
+                "No clipboard tool found. Install pbcopy, wl-copy, xclip or xsel, or use \
+                 'set clipboard' to give a command that copies its input"
+                    .to_string(),
+            )
+        })?;
+        copy_to_clipboard(&cmd, &names.join("\n"))?;
+        if names.len() == 1 {
+            clickwriteln!(writer, "Copied {} to the clipboard", names[0]);
+        } else {
+            clickwriteln!(writer, "Copied {} names to the clipboard", names.len());
+        }
+        Ok(())
+    }
+);
@@ -13,24 +13,51 @@
 // limitations under the License.
 
 use crate::command::command_def::Cmd;
+use crate::command::poll_until_interrupted;
 use crate::completer::ClickHelper;
-use crate::error::ClickError;
+use crate::error::{ClickError, ErrorCategory};
 use crate::kobj::KObj;
 use crate::output::ClickWriter;
-use crate::parser::{try_parse_csl, try_parse_range, Parser};
+use crate::parser::{extract_overrides, try_parse_csl, try_parse_range, Parser};
 use crate::values::val_str;
 
+use chrono::Local;
+use clap::Shell;
 use rustyline::config as rustyconfig;
 use rustyline::error::ReadlineError;
 use rustyline::Editor;
 
-use crate::env::Env;
+use crate::env::{Env, NameMatch};
 
 use std::fs::{File, OpenOptions};
 use std::io::Write;
-use std::ops::Range;
 use std::path::{Path, PathBuf};
 use std::rc::Rc;
+use std::time::Duration;
+
+/// Commands that only display information, which aren't recorded if a recording was started with
+/// --skip-read-only. List commands are still recorded since later selections depend on them
+const READ_ONLY_COMMANDS: &[&str] = &[
+    "cls",
+    "containers",
+    "contexts",
+    "count",
+    "decode",
+    "describe",
+    "diff",
+    "doctor",
+    "env",
+    "events",
+    "logs",
+    "range",
+    "top",
+    "utc",
+    "version",
+    "yank",
+];
+
+/// How often watch runs its command, if no interval is given
+const WATCH_INTERVAL_SECS: u64 = 2;
 
 /// Things the can come after a | or > char in input
 #[derive(Debug, PartialEq)]
@@ -44,26 +71,37 @@ enum RightExpr<'a> {
     Append(&'a str),
 }
 
-fn build_parser_expr(line: &str, range: Range<usize>) -> Result<(&str, RightExpr<'_>), ClickError> {
-    let (click_cmd, rest) = line.split_at(range.start);
+/// Split line at sep_pos, the index of the first unquoted | or >, into the click command and what
+/// its output should go to. Everything after a | is run by the shell, so it can be a pipeline of
+/// its own
+fn build_parser_expr(line: &str, sep_pos: usize) -> Result<(&str, RightExpr<'_>), ClickError> {
+    let (click_cmd, rest) = line.split_at(sep_pos);
 
     let rbytes = rest.as_bytes();
     let sep = rbytes[0];
     let mut sepcnt = 0;
 
-    while rbytes[sepcnt] == sep {
+    while sepcnt < rbytes.len() && rbytes[sepcnt] == sep {
         sepcnt += 1;
     }
 
     if sep == b'|' && sepcnt > 1 {
         Err(ClickError::ParseErr(format!(
             "Parse error at {}: unexpected ||",
-            range.start
+            sep_pos
         )))
     } else if sep == b'>' && sepcnt > 2 {
         Err(ClickError::ParseErr(format!(
             "Parse error at {}: unexpected >>",
-            range.start
+            sep_pos
+        )))
+    } else if rest[sepcnt..].trim().is_empty() && (sep == b'|' || sep == b'>') {
+        let what = if sep == b'|' { "command" } else { "file" };
+        Err(ClickError::ParseErr(format!(
+            "Parse error at {}: no {} after {}",
+            sep_pos,
+            what,
+            &rest[..sepcnt]
         )))
     } else {
         let right = match sep {
@@ -78,7 +116,7 @@ fn build_parser_expr(line: &str, range: Range<usize>) -> Result<(&str, RightExpr
             _ => {
                 return Err(ClickError::ParseErr(format!(
                     "Parse error at {}: unexpected separator",
-                    range.start
+                    sep_pos
                 )))
             }
         };
@@ -87,30 +125,220 @@ fn build_parser_expr(line: &str, range: Range<usize>) -> Result<(&str, RightExpr
 }
 
 pub fn alias_expand_line(env: &Env, line: &str) -> String {
-    let expa = env.try_expand_alias(line, None);
-    let mut alias_stack = vec![expa];
-    #[allow(clippy::while_let_loop)] // needed due to borrow restrictions
+    let mut line = line.to_string();
+    let mut prev_alias: Option<String> = None;
+    // keep expanding while the first word is an alias. try_expand_alias won't expand an alias
+    // that's identical to the one we just expanded, so an alias can refer to itself
     loop {
-        let expa = match alias_stack.last().unwrap().expansion {
-            Some(prev) => {
-                // previous thing expanded an alias, so try and expand that too
-                env.try_expand_alias(prev.expanded.as_str(), Some(prev.alias.as_str()))
+        let expa = env.try_expand_alias(&line, prev_alias.as_deref());
+        match expa.expansion {
+            Some(alias) => {
+                let expanded = alias.expand(expa.rest);
+                prev_alias = Some(alias.alias.clone());
+                line = expanded;
             }
             None => break,
-        };
-        alias_stack.push(expa);
+        }
+    }
+    line
+}
+
+/// Expand bash style history references in line: `!!` is the previous command, `!n` is history
+/// entry n (counting from 1), `!-n` is the command n back, and `!prefix` is the most recent command
+/// starting with prefix. Nothing inside quotes is expanded, and neither is a ! followed by
+/// whitespace, = or (, so things like `-l tier!=db` are left alone. Returns None if there was
+/// nothing to expand
+fn expand_history_refs(line: &str, history: &[&str]) -> Result<Option<String>, ClickError> {
+    let mut ret = String::with_capacity(line.len());
+    let mut expanded = false;
+    let mut quote = None;
+    let mut rest = line;
+    while let Some(c) = rest.chars().next() {
+        let after = &rest[c.len_utf8()..];
+        match (quote, c) {
+            (None, '"') | (None, '\'') => quote = Some(c),
+            (Some(q), c) if q == c => quote = None,
+            (None, '!') => {
+                let end = after
+                    .find(|c: char| c.is_whitespace() || "|>\"'".contains(c))
+                    .unwrap_or(after.len());
+                let event = &after[..end];
+                if !event.is_empty() && !event.starts_with(['=', '(']) {
+                    let (found, used) = if event.starts_with('!') {
+                        // anything after !! is kept, like bash
+                        (history.last(), 1)
+                    } else if let Ok(num) = event.parse::<isize>() {
+                        let index = if num < 0 {
+                            history.len() as isize + num
+                        } else {
+                            num - 1
+                        };
+                        let found = if index < 0 {
+                            None
+                        } else {
+                            history.get(index as usize)
+                        };
+                        (found, end)
+                    } else {
+                        (history.iter().rev().find(|cmd| cmd.starts_with(event)), end)
+                    };
+                    match found {
+                        Some(cmd) => {
+                            ret.push_str(cmd);
+                            expanded = true;
+                            rest = &after[used..];
+                            continue;
+                        }
+                        None => {
+                            return Err(ClickError::ParseErr(format!(
+                                "!{}: event not found",
+                                &event[..used]
+                            )))
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+        ret.push(c);
+        rest = after;
+    }
+    Ok(if expanded { Some(ret) } else { None })
+}
+
+fn print_cmd_error(e: ClickError, writer: &mut ClickWriter) {
+    match e {
+        ClickError::Reqwest(_, Some(val)) => {
+            let reason = val_str("/reason", &val, "no reason given");
+            let msg = val_str("/message", &val, "no message returned");
+            clickwriteln!(
+                writer,
+                "Error executing request. Reason: {}, Message: {}",
+                reason,
+                msg
+            )
+        }
+        _ => clickwriteln!(writer, "{}", e),
+    };
+}
+
+/// Run a command over and over, clearing the screen before each run, until ctrl-c is pressed
+fn run_watch(
+    commands: &[Box<dyn Cmd>],
+    env: &mut Env,
+    parts: &mut dyn Iterator<Item = &str>,
+    writer: &mut ClickWriter,
+) {
+    let mut args: Vec<&str> = parts.collect();
+    let mut interval = WATCH_INTERVAL_SECS;
+    if matches!(args.first(), Some(&"-n") | Some(&"--interval")) {
+        match args.get(1).and_then(|secs| secs.parse::<u64>().ok()) {
+            Some(secs) if secs > 0 => interval = secs,
+            _ => {
+                clickwriteln!(writer, "Interval must be a positive number of seconds");
+                return;
+            }
+        }
+        args.drain(..2);
+    }
+    let cmd = match args.first() {
+        Some(cmdstr) => match commands.iter().find(|c| c.is(cmdstr)) {
+            Some(cmd) => cmd,
+            None => {
+                clickwriteln!(writer, "Unknown command {}", cmdstr);
+                return;
+            }
+        },
+        None => {
+            clickwriteln!(writer, "{}", WATCHHELP);
+            return;
+        }
+    };
+
+    let line = args.join(" ");
+    let ctrlc = env.ctrlcbool.clone();
+    let res = poll_until_interrupted(&ctrlc, Duration::from_secs(interval), || {
+        // clear the screen and move to the top left
+        clickwrite!(writer, "\x1b[2J\x1b[H");
+        clickwriteln!(
+            writer,
+            "Every {}s: {}    {}\n",
+            interval,
+            line,
+            Local::now().format("%a %b %e %H:%M:%S %Y")
+        );
+        // we want to see what's there now, not a cached list
+        env.clear_list_cache();
+        let mut cmd_parts = args[1..].iter().copied();
+        cmd.exec(env, &mut cmd_parts, writer)?;
+        writer.flush().unwrap_or(());
+        Ok(true)
+    });
+    if let Err(e) = res {
+        print_cmd_error(e, writer);
     }
-    // At this point, all the "real" stuff is in the chain of "rest" memebers of the
-    // alias_stack, let's gather them up
-    let rests: Vec<&str> = alias_stack.iter().rev().map(|ea| ea.rest).collect();
-    rests.concat()
+}
+
+/// Write a completion script for click's command line args to writer. The script is the one clap
+/// generates, with the value of --exec completing to the names of click's commands
+fn write_completions(
+    commands: &[Box<dyn Cmd>],
+    parts: &mut dyn Iterator<Item = &str>,
+    writer: &mut ClickWriter,
+) -> Result<(), ClickError> {
+    let shell = match parts.next() {
+        Some("bash") => Shell::Bash,
+        Some("zsh") => Shell::Zsh,
+        Some("fish") => Shell::Fish,
+        _ => {
+            return Err(ClickError::ParseErr(
+                "Usage: completions <bash|zsh|fish>".to_string(),
+            ))
+        }
+    };
+    let mut names: Vec<&str> = commands.iter().map(|cmd| cmd.get_name()).collect();
+    // not commands themselves, as they need the commands vec
+    names.extend(&["help", "watch"]);
+    names.sort_unstable();
+    let names = names.join(" ");
+
+    let mut script = vec![];
+    crate::cli_app().gen_completions_to("click", shell, &mut script);
+    let script = String::from_utf8_lossy(&script);
+    let mut after_exec = false;
+    for line in script.lines() {
+        match shell {
+            Shell::Bash if after_exec => {
+                clickwriteln!(
+                    writer,
+                    "{}",
+                    line.replace("compgen -f", &format!("compgen -W \"{}\" --", names))
+                );
+            }
+            Shell::Zsh if line.starts_with("'--exec=[") => {
+                clickwriteln!(
+                    writer,
+                    "{}:command:({})' \\",
+                    line.trim_end_matches("' \\"),
+                    names
+                );
+            }
+            Shell::Fish if line.contains(" -l exec ") => {
+                clickwriteln!(writer, "{} -x -a '{}'", line.trim_end_matches(" -r"), names);
+            }
+            _ => clickwriteln!(writer, "{}", line),
+        }
+        after_exec = line.trim() == "--exec)";
+    }
+    Ok(())
 }
 
 fn parse_line(line: &str) -> Result<(&str, RightExpr), ClickError> {
     let parser = Parser::new(line);
     for (range, sep, _) in parser {
         match sep {
-            '|' | '>' => return build_parser_expr(line, range),
+            // the range is of the word before the separator, which ends where the separator is
+            '|' | '>' => return build_parser_expr(line, range.end),
             _ => {}
         }
     }
@@ -125,10 +353,13 @@ fn get_editor(config: rustyconfig::Config, hist_path: &Path) -> Editor<ClickHelp
         vec![
             "completion",
             "edit_mode",
+            "history",
+            "overrides",
             "shell",
             "pipes",
             "redirection",
             "ranges",
+            "watch",
         ],
     )));
     rl.load_history(hist_path).unwrap_or_default();
@@ -140,6 +371,7 @@ pub struct CommandProcessor {
     rl: Editor<ClickHelper>,
     hist_path: PathBuf,
     commands: Vec<Box<dyn Cmd>>,
+    last_error: Option<ErrorCategory>,
 }
 
 impl CommandProcessor {
@@ -152,6 +384,7 @@ impl CommandProcessor {
             rl,
             hist_path,
             commands,
+            last_error: None,
         }
     }
 
@@ -168,6 +401,7 @@ impl CommandProcessor {
             rl,
             hist_path,
             commands,
+            last_error: None,
         }
     }
 
@@ -175,22 +409,42 @@ impl CommandProcessor {
         let commands: Vec<Box<dyn Cmd>> = vec![
             Box::new(crate::command::alias::Alias::new()),
             Box::new(crate::command::alias::Unalias::new()),
+            Box::new(crate::command::label::Annotate::new()),
+            Box::new(crate::command::apply::Apply::new()),
+            Box::new(crate::command::bookmark::Bookmark::new()),
             Box::new(crate::command::click::Clear::new()),
+            Box::new(crate::command::click::ClearScreen::new()),
             Box::new(crate::command::click::Context::new()),
+            Box::new(crate::command::click::KubeConfig::new()),
             Box::new(crate::command::click::Contexts::new()),
             Box::new(crate::command::click::EnvCmd::new()),
             Box::new(crate::command::click::Quit::new()),
             Box::new(crate::command::click::Range::new()),
+            Box::new(crate::command::click::Record::new()),
             Box::new(crate::command::click::SetCmd::new()),
             Box::new(crate::command::click::UtcCmd::new()),
+            Box::new(crate::command::click::Version::new()),
             Box::new(crate::command::configmaps::ConfigMaps::new()),
+            Box::new(crate::command::count::Count::new()),
+            Box::new(crate::command::cp::Cp::new()),
             Box::new(crate::command::crds::Crd::new()),
+            Box::new(crate::command::cronjobs::CronJobs::new()),
+            Box::new(crate::command::daemonsets::DaemonSets::new()),
+            Box::new(crate::command::secrets::Decode::new()),
             Box::new(crate::command::delete::Delete::new()),
             Box::new(crate::command::deployments::Deployments::new()),
+            Box::new(crate::command::deployments::Restart::new()),
+            Box::new(crate::command::deployments::Rollout::new()),
             Box::new(crate::command::describe::Describe::new()),
+            Box::new(crate::command::diff::Diff::new()),
+            Box::new(crate::command::doctor::Doctor::new()),
+            Box::new(crate::command::edit::Edit::new()),
             Box::new(crate::command::events::Events::new()),
             Box::new(crate::command::exec::Exec::new()),
+            Box::new(crate::command::get::Get::new()),
+            Box::new(crate::command::ingresses::Ingresses::new()),
             Box::new(crate::command::jobs::Jobs::new()),
+            Box::new(crate::command::label::Label::new()),
             Box::new(crate::command::logs::Logs::new()),
             Box::new(crate::command::namespaces::Namespace::new()),
             Box::new(crate::command::namespaces::Namespaces::new()),
@@ -199,20 +453,54 @@ impl CommandProcessor {
             Box::new(crate::command::pods::Pods::new()),
             Box::new(crate::command::portforwards::PortForward::new()),
             Box::new(crate::command::portforwards::PortForwards::new()),
+            Box::new(crate::command::raw::Raw::new()),
             Box::new(crate::command::replicasets::ReplicaSets::new()),
+            Box::new(crate::command::scale::Scale::new()),
             Box::new(crate::command::secrets::Secrets::new()),
             Box::new(crate::command::services::Services::new()),
             Box::new(crate::command::statefulsets::StatefulSets::new()),
             Box::new(crate::command::storage::StorageClasses::new()),
+            Box::new(crate::command::stream::Stream::new()),
+            Box::new(crate::command::top::Top::new()),
+            Box::new(crate::command::trigger::Trigger::new()),
+            Box::new(crate::command::volumeclaims::PersistentVolumeClaims::new()),
             Box::new(crate::command::volumes::PersistentVolumes::new()),
+            Box::new(crate::command::yank::Yank::new()),
             #[cfg(feature = "argorollouts")]
             Box::new(crate::command::rollouts::Rollouts::new()),
         ];
         commands
     }
 
+    /// If the history file has changed (because history is per context and the context changed, or
+    /// the history mode changed), save history to the old file and load it from the new one
+    fn switch_history_if_needed(&mut self) {
+        let hist_path = self.env.history_path();
+        if hist_path != self.hist_path {
+            if let Err(e) = self.rl.save_history(self.hist_path.as_path()) {
+                println!("Couldn't save command history: {}", e);
+            }
+            self.rl.clear_history();
+            self.rl.load_history(&hist_path).unwrap_or_default();
+            self.hist_path = hist_path;
+        }
+    }
+
+    /// Run the startup_commands from the click config, as if they'd been typed at the prompt. A
+    /// command that fails has its error printed like any other, and the rest still run
+    pub fn run_startup_commands(&mut self) {
+        for command in self.env.click_config.startup_commands.clone().iter() {
+            if self.env.quit {
+                break;
+            }
+            // the leading space keeps startup commands out of the history
+            self.process_line(&format!(" {}", command.trim()), ClickWriter::new());
+        }
+    }
+
     pub fn run_repl(&mut self) {
         while !self.env.quit {
+            self.switch_history_if_needed();
             let mut writer = ClickWriter::new();
             if self.env.need_new_editor {
                 self.rl = get_editor(self.env.get_rustyline_conf(), &self.hist_path);
@@ -229,9 +517,20 @@ impl CommandProcessor {
                 h.set_env(None)
             }
             match readline {
-                Ok(line) => {
-                    self.process_line(line.as_str(), writer);
-                }
+                Ok(line) => match self.expand_history(&line) {
+                    Ok(Some(expanded)) => {
+                        // show what's actually being run, like bash
+                        clickwriteln!(writer, "{}", expanded);
+                        self.process_line(expanded.as_str(), writer);
+                    }
+                    Ok(None) => {
+                        self.process_line(line.as_str(), writer);
+                    }
+                    Err(e) => {
+                        self.last_error = Some(e.category());
+                        print_cmd_error(e, &mut writer);
+                    }
+                },
                 Err(ReadlineError::Interrupted) => {} // don't exit on Ctrl-C
                 Err(ReadlineError::Eof) => {
                     // Ctrl-D
@@ -248,11 +547,28 @@ impl CommandProcessor {
         if let Err(e) = self.rl.save_history(self.hist_path.as_path()) {
             println!("Couldn't save command history: {}", e);
         }
-        env.stop_all_forwards();
+        env.shutdown_forwards();
+    }
+
+    /// Expand any history references (like !!) in a line typed at the prompt, see
+    /// expand_history_refs. This is only done interactively, so lines run with --exec or as
+    /// startup commands are never changed
+    fn expand_history(&self, line: &str) -> Result<Option<String>, ClickError> {
+        if !line.contains('!') {
+            return Ok(None);
+        }
+        let history: Vec<&str> = self.rl.history().iter().map(String::as_str).collect();
+        expand_history_refs(line, &history)
+    }
+
+    /// The kind of error the last processed line failed with, if it failed
+    pub fn last_error(&self) -> Option<ErrorCategory> {
+        self.last_error
     }
 
     /// Process the line.  Returns the result of finish_output on the writer
     pub fn process_line(&mut self, line: &str, mut writer: ClickWriter) -> Option<Vec<u8>> {
+        self.last_error = None;
         if line.is_empty() {
             return writer.finish_output();
         }
@@ -263,7 +579,8 @@ impl CommandProcessor {
             }
             first_non_whitespace += 1;
         }
-        let lstr = if first_non_whitespace == 0 {
+        let record = first_non_whitespace == 0;
+        let lstr = if record {
             // bash semantics: don't add to history if start with space
             self.rl.add_history_entry(line);
             line
@@ -275,10 +592,15 @@ impl CommandProcessor {
             Ok((left, right)) => {
                 // set up output
                 match right {
-                    RightExpr::None => {} // do nothing
+                    RightExpr::None => {
+                        if self.env.click_config.paging {
+                            writer.setup_pager(&self.env.pager_command());
+                        }
+                    }
                     RightExpr::Pipe(cmd) => {
                         if let Err(e) = writer.setup_pipe(cmd) {
                             println!("{}", e);
+                            self.last_error = Some(e.category());
                             return writer.finish_output();
                         }
                     }
@@ -288,6 +610,7 @@ impl CommandProcessor {
                         }
                         Err(ref e) => {
                             println!("Can't open output file: {}", e);
+                            self.last_error = Some(ErrorCategory::General);
                             return writer.finish_output();
                         }
                     },
@@ -298,6 +621,7 @@ impl CommandProcessor {
                             }
                             Err(ref e) => {
                                 println!("Can't open output file: {}", e);
+                                self.last_error = Some(ErrorCategory::General);
                                 return writer.finish_output();
                             }
                         }
@@ -310,6 +634,9 @@ impl CommandProcessor {
                     // There was something typed
                     if let Ok(num) = (cmdstr as &str).parse::<usize>() {
                         env.set_current(num);
+                        if record {
+                            env.record_line(&expanded_line);
+                        }
                     } else if let Some(range) = try_parse_range(cmdstr) {
                         // Switch to this when map_while is stable
                         // let objs: Vec<KObj> =
@@ -326,6 +653,9 @@ impl CommandProcessor {
                         } else {
                             env.set_range(objs);
                         }
+                        if record {
+                            env.record_line(&expanded_line);
+                        }
                     } else if let Some(range) = try_parse_csl(left) {
                         // parse whole thing before sep since we might type "1, 2, 3" with spaces
                         let objs: Vec<KObj> =
@@ -335,26 +665,60 @@ impl CommandProcessor {
                         } else {
                             env.set_range(objs);
                         }
+                        if record {
+                            env.record_line(&expanded_line);
+                        }
                     } else if let Some(cmd) = self.commands.iter().find(|&c| c.is(cmdstr)) {
                         // found a matching command
-                        if let Err(e) = cmd.exec(env, &mut parts, &mut writer) {
-                            match e {
-                                ClickError::Reqwest(_, Some(val)) => {
-                                    let reason = val_str("/reason", &val, "no reason given");
-                                    let msg = val_str("/message", &val, "no message returned");
-                                    clickwriteln!(
-                                        writer,
-                                        "Error executing request. Reason: {}, Message: {}",
-                                        reason,
-                                        msg
-                                    )
+                        let record = record
+                            && cmd.get_name() != "record"
+                            && !(env.recording().is_some_and(|r| r.skip_read_only)
+                                && READ_ONLY_COMMANDS.contains(&cmd.get_name()));
+                        let args: Vec<&str> = parts.collect();
+                        let res = match extract_overrides(&args) {
+                            Ok((rest, overrides)) if overrides.is_empty() => {
+                                cmd.exec(env, &mut rest.into_iter(), &mut writer)
+                            }
+                            Ok((rest, overrides)) => env.with_overrides(&overrides, |env| {
+                                cmd.exec(env, &mut rest.into_iter(), &mut writer)
+                            }),
+                            Err(msg) => Err(ClickError::CommandError(msg)),
+                        };
+                        match res {
+                            Ok(()) => {
+                                if record {
+                                    env.record_line(&expanded_line);
                                 }
-                                _ => clickwriteln!(writer, "{}", e),
-                            };
+                            }
+                            Err(e) => {
+                                self.last_error = Some(e.category());
+                                print_cmd_error(e, &mut writer);
+                            }
                         }
                     } else if cmdstr == "help" {
                         self.show_help(&mut parts, &mut writer);
+                    } else if cmdstr == "completions" {
+                        // hidden, as it's meant to be run via --exec
+                        if let Err(e) = write_completions(&self.commands, &mut parts, &mut writer) {
+                            self.last_error = Some(e.category());
+                            print_cmd_error(e, &mut writer);
+                        }
+                    } else if cmdstr == "watch" {
+                        // like help, watch needs the commands vec so it isn't a command itself
+                        run_watch(&self.commands, env, &mut parts, &mut writer);
+                    } else if env.find_by_name(cmdstr) != NameMatch::None {
+                        // select by name, record the namespace so replaying picks the same object
+                        if let Some(obj) = env.select_by_name(cmdstr, &mut writer) {
+                            if record {
+                                let line = match obj.namespace.as_ref() {
+                                    Some(ns) => format!("{}/{}", ns, obj.name),
+                                    None => obj.name,
+                                };
+                                env.record_line(&line);
+                            }
+                        }
                     } else {
+                        self.last_error = Some(ErrorCategory::Parse);
                         clickwriteln!(writer, "Unknown command");
                     }
                 }
@@ -364,6 +728,7 @@ impl CommandProcessor {
             }
             Err(err) => {
                 println!("{}", err);
+                self.last_error = Some(err.category());
                 None
             }
         }
@@ -386,9 +751,18 @@ impl CommandProcessor {
                     "edit_mode" => {
                         clickwriteln!(writer, "{}", EDITMODEHELP);
                     }
+                    "history" => {
+                        clickwriteln!(writer, "{}", HISTORYHELP);
+                    }
+                    "overrides" => {
+                        clickwriteln!(writer, "{}", OVERRIDEHELP);
+                    }
                     "ranges" => {
                         clickwriteln!(writer, "{}", RANGEHELP);
                     }
+                    "watch" => {
+                        clickwriteln!(writer, "{}", WATCHHELP);
+                    }
                     _ => {
                         if let Some(alias) = self.env.get_alias(hcmd) {
                             clickwriteln!(writer, "{} is an alias for '{}'", hcmd, alias.expanded);
@@ -403,15 +777,9 @@ impl CommandProcessor {
                 writer,
                 "Available commands (type 'help [COMMAND]' for details):"
             );
-            let spacer = "                  ";
             for c in self.commands.iter() {
-                clickwriteln!(
-                    writer,
-                    "  {}{}{}",
-                    c.get_name(),
-                    &spacer[0..(20 - c.get_name().len())],
-                    c.about()
-                );
+                // pad to line up the descriptions, long names just get a single space
+                clickwriteln!(writer, "  {:<19} {}", c.get_name(), c.about());
             }
             clickwriteln!(
                 writer,
@@ -427,6 +795,16 @@ impl CommandProcessor {
                 "  edit_mode           Available edit_mode values for \
                  the 'set' command, and what they mean"
             );
+            clickwriteln!(
+                writer,
+                "  history             Rerunning previous commands with !!, \
+                 !n and !prefix"
+            );
+            clickwriteln!(
+                writer,
+                "  overrides           Running a single command in another \
+                 context or namespace, or as another user"
+            );
             clickwriteln!(
                 writer,
                 "  ranges              Selecting and operating on multiple \
@@ -437,6 +815,11 @@ impl CommandProcessor {
                 "  shell               Redirecting and piping click \
                  output to shell commands"
             );
+            clickwriteln!(
+                writer,
+                "  watch               Re-running a command every few \
+                 seconds"
+            );
         }
     }
 }
@@ -453,6 +836,49 @@ Examples:\n\
  # Append log lines that contain \"foo bar\" to logs.txt\n\
  logs the-cont | grep \"foo bar\" >> /tmp/logs.txt";
 
+static WATCHHELP: &str = "Usage: watch [-n SECONDS] COMMAND [ARGS...]\n
+Clear the screen and run COMMAND every 2 seconds (or every SECONDS if -n is given), until \
+Ctrl-C is pressed. Any arguments are passed to COMMAND, and cached lists are never used. The \
+wait starts when a run finishes, so a slow server delays the next refresh. Give list commands \
+--request-timeout to limit how long each run can take.\n
+Examples:\n\
+ # watch the pods in the current namespace\n\
+ watch pods\n\n\
+ # watch pods with a label, with their extra columns, every 5 seconds\n\
+ watch -n 5 pods -l app=web -o wide\n\n\
+ # watch nodes, giving up on any refresh that takes more than 10 seconds\n\
+ watch nodes --request-timeout 10";
+
+static OVERRIDEHELP: &str = "Any command can be given --context <name> and/or --namespace <ns> \
+to run just that command against another context or namespace, and --as <user> (with any number \
+of --as-group <group>, and optionally --as-uid <uid>) to impersonate someone for just that \
+command. The current context, namespace, \
+selection and prompt are left as they were, and the connection to the other context is cached so \
+running more commands against it is quick. Like the context command, the context name can be \
+shortened as long as it only matches one context.\n
+Examples:\n\
+ # list pods in the kube-system namespace\n\
+ pods --namespace kube-system\n\n\
+ # list deployments in the app namespace of the prod context\n\
+ deployments --context prod --namespace=app\n\n\
+ # check what the dev group can see\n\
+ pods --as alice --as-group dev";
+
+static HISTORYHELP: &str = "Previous commands can be rerun, or reused in a new command, with bash \
+style history references. Lines with a reference are printed after expanding them, then run. \
+References inside quotes, and a ! followed by a space, = or ( aren't expanded, so selectors like \
+-l tier!=db work as usual. Expansion only happens at the prompt, not for --exec or startup \
+commands.\n
+  !!        the previous command
+  !n        command n in the history, counting from 1
+  !-n       the command n before this one
+  !prefix   the most recent command starting with prefix\n
+Examples:\n\
+ # rerun the last command, sending its output to a file\n\
+ !! > /tmp/out.txt\n\n\
+ # rerun the last logs command\n\
+ !logs";
+
 static COMPLETIONHELP: &str = "There are two completion types: list or circular.
 - list: complete the next full match (like in Vim by default) (do: set completion list)
 - circular: complete until the longest match. If there is more than one match, \
@@ -573,7 +999,13 @@ mod tests {
             "This is the about"
         }
 
-        fn try_complete(&self, _index: usize, _prefix: &str, _env: &Env) -> Vec<RustlinePair> {
+        fn try_complete(
+            &self,
+            _index: usize,
+            _prefix: &str,
+            _args: &[&str],
+            _env: &Env,
+        ) -> Vec<RustlinePair> {
             Vec::new()
         }
 
@@ -582,6 +1014,7 @@ mod tests {
             _index: usize,
             _opt: &str,
             _prefix: &str,
+            _args: &[&str],
             _env: &Env,
         ) -> Vec<RustlinePair> {
             Vec::new()
@@ -642,12 +1075,54 @@ mod tests {
 Other help topics (type 'help [TOPIC]' for details)
   completion          Available completion_type values for the 'set' command, and what they mean
   edit_mode           Available edit_mode values for the 'set' command, and what they mean
+  history             Rerunning previous commands with !!, !n and !prefix
+  overrides           Running a single command in another context or namespace, or as another user
   ranges              Selecting and operating on multiple objects at once
-  shell               Redirecting and piping click output to shell commands\n"
+  shell               Redirecting and piping click output to shell commands
+  watch               Re-running a command every few seconds\n"
                 .as_bytes()
         );
     }
 
+    #[test]
+    fn watch_bad_args() {
+        let mut p = get_processor();
+        let writer = ClickWriter::with_buffer(Vec::new(), false);
+        let res = p.process_line("watch blah", writer).unwrap();
+        assert_eq!(res, "Unknown command blah\n".as_bytes());
+
+        let writer = ClickWriter::with_buffer(Vec::new(), false);
+        let res = p.process_line("watch -n soon testcmd", writer).unwrap();
+        assert_eq!(
+            res,
+            "Interval must be a positive number of seconds\n".as_bytes()
+        );
+
+        let writer = ClickWriter::with_buffer(Vec::new(), false);
+        let res = p.process_line("watch", writer).unwrap();
+        assert!(String::from_utf8(res)
+            .unwrap()
+            .starts_with("Usage: watch [-n SECONDS]"));
+    }
+
+    #[test]
+    fn completions() {
+        let mut p = get_processor();
+        let writer = ClickWriter::with_buffer(Vec::new(), false);
+        let res = String::from_utf8(p.process_line("completions bash", writer).unwrap()).unwrap();
+        assert!(res.contains("--kubeconfig"));
+        assert!(res.contains(r#"COMPREPLY=($(compgen -W "help testcmd watch" -- "${cur}"))"#));
+        assert_eq!(p.last_error(), None);
+
+        let writer = ClickWriter::with_buffer(Vec::new(), false);
+        let res = String::from_utf8(p.process_line("completions zsh", writer).unwrap()).unwrap();
+        assert!(res.contains("]:command:(help testcmd watch)' \\"));
+
+        let writer = ClickWriter::with_buffer(Vec::new(), false);
+        p.process_line("completions ksh", writer);
+        assert_eq!(p.last_error(), Some(ErrorCategory::Parse));
+    }
+
     #[test]
     fn unknown_command() {
         let mut p = get_processor();
@@ -655,6 +1130,12 @@ Other help topics (type 'help [TOPIC]' for details)
         let writer = ClickWriter::with_buffer(buf, false);
         let res = p.process_line("blah", writer).unwrap();
         assert_eq!(res, "Unknown command\n".as_bytes());
+        assert_eq!(p.last_error(), Some(ErrorCategory::Parse));
+
+        // a successful line clears the error
+        let writer = ClickWriter::with_buffer(Vec::new(), false);
+        p.process_line("testcmd", writer).unwrap();
+        assert_eq!(p.last_error(), None);
     }
 
     #[test]
@@ -851,7 +1332,7 @@ Other help topics (type 'help [TOPIC]' for details)
             "Parse Error: Parse error at 5: unexpected >>"
         );
 
-        let p = build_parser_expr("a * b", std::ops::Range { start: 2, end: 5 });
+        let p = build_parser_expr("a * b", 2);
         assert!(p.is_err());
         assert_eq!(
             p.err().unwrap().to_string(),
@@ -859,21 +1340,66 @@ Other help topics (type 'help [TOPIC]' for details)
         );
     }
 
+    #[test]
+    fn parse_pipelines() {
+        // everything after the first unquoted | goes to the shell, later stages included
+        assert_eq!(
+            parse_line("get pods -o json | jq '.items[].metadata.name' | sort").unwrap(),
+            (
+                "get pods -o json ",
+                RightExpr::Pipe(" jq '.items[].metadata.name' | sort")
+            )
+        );
+        // no spaces around the separator
+        assert_eq!(
+            parse_line("pods|grep web").unwrap(),
+            ("pods", RightExpr::Pipe("grep web"))
+        );
+        assert_eq!(
+            parse_line("pods -l \"a\">out.txt").unwrap(),
+            ("pods -l \"a\"", RightExpr::Redir("out.txt"))
+        );
+        // quoted and escaped separators are part of the click command
+        assert_eq!(
+            parse_line("pods -r 'web|api' | wc -l").unwrap(),
+            ("pods -r 'web|api' ", RightExpr::Pipe(" wc -l"))
+        );
+        assert_eq!(
+            parse_line("pods -r \"a > b\" -r a\\|b >> log").unwrap(),
+            ("pods -r \"a > b\" -r a\\|b ", RightExpr::Append("log"))
+        );
+        assert_eq!(
+            parse_line("pods -r \"unterminated | quote").unwrap(),
+            ("pods -r \"unterminated | quote", RightExpr::None)
+        );
+
+        let p = parse_line("pods | ");
+        assert_eq!(
+            p.err().unwrap().to_string(),
+            "Parse Error: Parse error at 5: no command after |"
+        );
+        let p = parse_line("pods >>");
+        assert_eq!(
+            p.err().unwrap().to_string(),
+            "Parse Error: Parse error at 5: no file after >>"
+        );
+    }
+
     #[test]
     fn build_parser_exp() {
-        let p = build_parser_expr("a | b", std::ops::Range { start: 2, end: 5 });
+        let p = build_parser_expr("a | b", 2);
         assert!(p.is_ok());
         let r = p.unwrap();
         assert_eq!(r.0, "a ");
         assert_eq!(r.1, RightExpr::Pipe(" b"));
 
-        let p = build_parser_expr("a > b", std::ops::Range { start: 2, end: 5 });
+        let p = build_parser_expr("a > b", 2);
         assert!(p.is_ok());
         let r = p.unwrap();
         assert_eq!(r.0, "a ");
         assert_eq!(r.1, RightExpr::Redir("b"));
 
-        let p = build_parser_expr("a >> b", std::ops::Range { start: 2, end: 6 });
+        let p = build_parser_expr("a >> b", 2);
         assert!(p.is_ok());
         let r = p.unwrap();
         assert_eq!(r.0, "a ");
@@ -889,6 +1415,31 @@ Other help topics (type 'help [TOPIC]' for details)
         assert_eq!(p.rl.history().len(), 0);
     }
 
+    #[test]
+    fn record_session() {
+        let dir = tempdir::TempDir::new("click_test_dir").unwrap();
+        let file_path_buf = dir.path().join("session.click");
+
+        let mut p = get_processor();
+        Rc::get_mut(&mut p.env)
+            .unwrap()
+            .start_recording(file_path_buf.to_str().unwrap(), false)
+            .unwrap();
+        p.process_line("testcmd arg1", ClickWriter::with_buffer(vec![], false));
+        p.process_line(" testcmd secret", ClickWriter::with_buffer(vec![], false));
+        p.process_line("blah", ClickWriter::with_buffer(vec![], false));
+        p.process_line("testcmd arg2", ClickWriter::with_buffer(vec![], false));
+        assert!(Rc::get_mut(&mut p.env).unwrap().stop_recording().is_some());
+        p.process_line("testcmd arg3", ClickWriter::with_buffer(vec![], false));
+
+        let mut file = File::open(file_path_buf).unwrap();
+        let mut contents = String::new();
+        file.read_to_string(&mut contents).unwrap();
+        assert_eq!(contents, "testcmd arg1\ntestcmd arg2\n");
+
+        dir.close().unwrap();
+    }
+
     #[test]
     fn test_alias_expand_line() {
         let mut cc = ClickConfig::default();
@@ -944,4 +1495,39 @@ Other help topics (type 'help [TOPIC]' for details)
 
         assert_eq!(alias_expand_line(&env, "z x"), "xpand arg x");
     }
+
+    #[test]
+    fn history_expansion() {
+        let history = ["pods", "logs web-1 app", "describe"];
+        let expand = |line| expand_history_refs(line, &history);
+        assert_eq!(expand("!!").unwrap(), Some("describe".to_string()));
+        assert_eq!(
+            expand("!! | grep x").unwrap(),
+            Some("describe | grep x".to_string())
+        );
+        assert_eq!(expand("!1").unwrap(), Some("pods".to_string()));
+        assert_eq!(
+            expand("!-2 -f").unwrap(),
+            Some("logs web-1 app -f".to_string())
+        );
+        assert_eq!(expand("!lo").unwrap(), Some("logs web-1 app".to_string()));
+        assert_eq!(expand("!p -A").unwrap(), Some("pods -A".to_string()));
+        assert_eq!(
+            expand("!!!!").unwrap(),
+            Some("describedescribe".to_string())
+        );
+
+        // nothing to expand
+        assert_eq!(expand("pods -l tier!=db").unwrap(), None);
+        assert_eq!(expand("pods -l 'tier!=db,!canary'").unwrap(), None);
+        assert_eq!(expand("exec \"echo !!\"").unwrap(), None);
+        assert_eq!(expand("echo ! done").unwrap(), None);
+        assert_eq!(expand("pods").unwrap(), None);
+
+        assert!(expand("!4").is_err());
+        assert!(expand("!0").is_err());
+        assert!(expand("!-4").is_err());
+        assert!(expand("!nope").is_err());
+        assert!(expand_history_refs("!!", &[]).is_err());
+    }
 }
@@ -12,6 +12,7 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use clap::App;
 use k8s_openapi::{api::core::v1 as api, List};
 use rustyline::{
     completion::{Completer, Pair},
@@ -21,11 +22,15 @@ use rustyline::{
     Context, Helper, Result,
 };
 
-use crate::command::command_def::Cmd;
+use crate::cache::CacheKey;
+use crate::command::command_def::{long_options, Cmd};
+use crate::command::path_with_query;
 use crate::env::Env;
 use crate::kobj::ObjType;
+use crate::parser::OVERRIDE_FLAGS;
 
 use std::rc::Rc;
+use std::time::Duration;
 
 pub struct ClickHelper {
     commands: Vec<Box<dyn Cmd>>,
@@ -99,6 +104,12 @@ impl ClickHelper {
         let linecmd = split.next().unwrap(); // safe, only ever call this if we know there's a space
                                              // gather up any none switch type args
         if let Some(cmd) = self.get_exact_command(linecmd) {
+            // the positional args typed so far, for completers that depend on earlier args
+            let args: Vec<&str> = line
+                .split_whitespace()
+                .skip(1)
+                .filter(|s| !s.starts_with('-'))
+                .collect();
             // first thing is a full command, do complete on it
             // check what we're trying to complete
             let (pos, prefix, last_opt) = match split.next_back() {
@@ -128,6 +139,13 @@ impl ClickHelper {
                     } else if let Some(opt_str) = back.strip_prefix("--") {
                         // last thing is a long option, complete on available options
                         let mut opts = cmd.complete_option(opt_str);
+                        // the overrides every command takes
+                        opts.extend(OVERRIDE_FLAGS.iter().filter_map(|flag| {
+                            flag.strip_prefix(back).map(|rest| Pair {
+                                display: flag.to_string(),
+                                replacement: format!("{} ", rest),
+                            })
+                        }));
                         if "--help".starts_with(back) {
                             // add in help completion
                             opts.push(Pair {
@@ -156,13 +174,16 @@ impl ClickHelper {
             // here the last thing typed wasn't a '-' option, so we ask the command to
             // do completion
             if let Some(ref env) = self.env {
+                let args = &args[..pos.min(args.len())];
                 match last_opt {
+                    Some("--context") => (cmd_len, context_complete(prefix, env)),
+                    Some("--namespace") => (cmd_len, namespace_completer(prefix, env)),
                     Some(opt) => {
-                        let opts = cmd.try_completed_named(pos, opt, prefix, &*env);
+                        let opts = cmd.try_completed_named(pos, opt, prefix, args, &*env);
                         (cmd_len, opts)
                     }
                     None => {
-                        let opts = cmd.try_complete(pos, prefix, &*env);
+                        let opts = cmd.try_complete(pos, prefix, args, &*env);
                         (cmd_len, opts)
                     }
                 }
@@ -223,12 +244,17 @@ impl ClickHelper {
     }
 }
 
-/// Does the short option (an Option<char>) from clap match
-pub fn long_matches(long: &Option<&str>, prefix: &str) -> bool {
-    match long {
-        Some(lstr) => lstr.starts_with(prefix),
-        None => false,
-    }
+/// Complete the long options of app that start with prefix (which doesn't include the --)
+pub fn complete_long_option(app: &App<'static, 'static>, prefix: &str) -> Vec<Pair> {
+    long_options(app)
+        .into_iter()
+        .filter_map(|long| {
+            long.strip_prefix(prefix).map(|rest| Pair {
+                display: format!("--{}", long),
+                replacement: format!("{} ", rest),
+            })
+        })
+        .collect()
 }
 
 impl Completer for ClickHelper {
@@ -269,6 +295,19 @@ pub fn context_complete(prefix: &str, env: &Env) -> Vec<Pair> {
     v
 }
 
+pub fn bookmark_completer(prefix: &str, env: &Env) -> Vec<Pair> {
+    let mut v = Vec::new();
+    for bookmark in env.click_config.bookmarks.keys() {
+        if let Some(rest) = bookmark.strip_prefix(prefix) {
+            v.push(Pair {
+                display: bookmark.to_string(),
+                replacement: rest.to_string(),
+            })
+        }
+    }
+    v
+}
+
 pub fn namespace_completer(prefix: &str, env: &Env) -> Vec<Pair> {
     let (request, _response_body) = api::Namespace::list_namespace(Default::default()).unwrap();
     match env.run_on_context::<_, List<api::Namespace>>(|c| c.execute_list(request)) {
@@ -293,6 +332,8 @@ pub fn namespace_completer(prefix: &str, env: &Env) -> Vec<Pair> {
     }
 }
 
+/// Complete with the names of the containers in the current pod. If the current object isn't a
+/// single pod there is nothing to offer
 pub fn container_completer(prefix: &str, env: &Env) -> Vec<Pair> {
     let mut v = vec![];
     if let Some(pod) = env.current_pod() {
@@ -329,7 +370,99 @@ macro_rules! possible_values_completer {
 
 possible_values_completer!(setoptions_values_completer, crate::command::click::SET_OPTS);
 
+possible_values_completer!(
+    bookmarkaction_values_completer,
+    crate::command::bookmark::BOOKMARK_ACTIONS
+);
+
 possible_values_completer!(
     portforwardaction_values_completer,
-    ["list", "output", "stop"]
+    ["list", "logs", "output", "stop"]
 );
+
+possible_values_completer!(rawmethod_values_completer, crate::command::raw::RAW_METHODS);
+
+possible_values_completer!(kind_values_completer, crate::command::get::KIND_NAMES);
+
+/// How long fetched object names are used for completion before fetching them again
+const NAME_COMPLETION_TTL_SECS: u64 = 10;
+
+/// How long to wait for the names to complete, so TAB doesn't hang on a slow cluster
+const NAME_COMPLETION_TIMEOUT_SECS: u64 = 2;
+
+/// The most names fetched for completion
+const NAME_COMPLETION_LIMIT: u32 = 500;
+
+/// Asks the server for just the metadata of the objects in a list, like kubectl does, falling back
+/// to the whole objects on servers that can't do that
+const METADATA_ONLY_ACCEPT: &str = "application/json;as=PartialObjectMetadataList;g=meta.k8s.io;\
+                                    v=v1,application/json;as=PartialObjectMetadataList;\
+                                    g=meta.k8s.io;v=v1beta1,application/json";
+
+/// Complete the args to get: the kind, and then the names of objects of that kind. Names are
+/// fetched when tab is pressed, and kept for a few seconds so completing repeatedly doesn't list
+/// everything again each time. A prefix of the form namespace/ completes names in that namespace
+pub fn get_args_completer(index: usize, prefix: &str, args: &[&str], env: &Env) -> Vec<Pair> {
+    let kind = match (index, args.first()) {
+        (0, _) => return kind_values_completer(prefix, env),
+        (1, Some(kind)) => *kind,
+        _ => return vec![],
+    };
+    let (namespace, name_prefix) = match prefix.split_once('/') {
+        Some((ns, rest)) => (Some(ns), rest),
+        None => (env.namespace.as_deref(), prefix),
+    };
+    let names = match object_names(kind, namespace, env) {
+        Some(names) => names,
+        None => return vec![],
+    };
+    names
+        .iter()
+        .filter_map(|name| {
+            name.strip_prefix(name_prefix).map(|rest| Pair {
+                display: name.clone(),
+                replacement: rest.to_string(),
+            })
+        })
+        .collect()
+}
+
+fn object_names(kind: &str, namespace: Option<&str>, env: &Env) -> Option<Vec<String>> {
+    let (kind, namespaced, path) =
+        crate::command::get::kind_list_path(kind, namespace, env).ok()?;
+    // listing across all namespaces could be a lot to fetch while the user waits
+    if namespaced && namespace.is_none() {
+        return None;
+    }
+    let key = CacheKey {
+        context: env.context.as_ref()?.name.clone(),
+        namespace: namespace.filter(|_| namespaced).map(|ns| ns.to_string()),
+        kind: kind.to_string(),
+    };
+    let ttl = Duration::from_secs(NAME_COMPLETION_TTL_SECS);
+    if let Some(names) = env.completion_names(&key, ttl) {
+        return Some(names);
+    }
+    let path = path_with_query(&path, &[("limit", &NAME_COMPLETION_LIMIT.to_string())]);
+    let request = k8s_openapi::http::Request::get(path)
+        .header(k8s_openapi::http::header::ACCEPT, METADATA_ONLY_ACCEPT)
+        .body(vec![])
+        .ok()?;
+    let timeout = Some(Duration::from_secs(NAME_COMPLETION_TIMEOUT_SECS));
+    let response = env
+        .run_on_context(|c| c.execute_with_timeout(request, timeout))
+        .ok()?;
+    if !response.status().is_success() {
+        return None;
+    }
+    let list: serde_json::Value = serde_json::from_slice(response.body()).ok()?;
+    let names: Vec<String> = list
+        .get("items")?
+        .as_array()?
+        .iter()
+        .filter_map(|item| item.pointer("/metadata/name")?.as_str())
+        .map(|name| name.to_string())
+        .collect();
+    env.cache_completion_names(key, names.clone());
+    Some(names)
+}
@@ -13,21 +13,152 @@
 // limitations under the License.
 
 /// Click config
+use ansi_term::Colour;
 use atomicwrites::{AllowOverwrite, AtomicFile};
 use rustyline::config as rustyconfig;
 
+use std::collections::{BTreeMap, BTreeSet};
 use std::fmt;
 use std::fs::File;
 use std::io::Read;
 
 use crate::error::ClickError;
 
+/// A context and namespace to switch to together, see the bookmark command
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Bookmark {
+    pub context: String,
+    pub namespace: Option<String>,
+}
+
 #[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
 pub struct Alias {
     pub alias: String,
     pub expanded: String,
 }
 
+impl Alias {
+    /// Expand this alias, given the rest of the line after the alias word.
+    ///
+    /// If the expansion contains no $1..$9 or $* tokens, rest is appended verbatim. Otherwise rest
+    /// is split into whitespace separated words, $N is replaced with the Nth word (or nothing if
+    /// fewer words were given), and $* with all the words. Any words after the highest $N used are
+    /// appended to the end, unless $* was used, in which case they're considered consumed.
+    /// Environment variables in the expansion are expanded too, see expand_env_vars
+    pub fn expand(&self, rest: &str) -> String {
+        self.expand_with(rest, |name| std::env::var(name).ok())
+    }
+
+    fn expand_with<L>(&self, rest: &str, lookup: L) -> String
+    where
+        L: Fn(&str) -> Option<String>,
+    {
+        let args: Vec<&str> = rest.split_whitespace().collect();
+        let mut expanded = String::with_capacity(self.expanded.len() + rest.len());
+        let mut max_used = 0;
+        let mut used_all = false;
+        let mut remaining = self.expanded.as_str();
+        while let Some(pos) = remaining.find('$') {
+            expanded.push_str(&remaining[..pos]);
+            remaining = &remaining[pos + 1..];
+            match remaining.chars().next() {
+                Some('*') => {
+                    remaining = &remaining[1..];
+                    expanded.push_str(&args.join(" "));
+                    used_all = true;
+                }
+                Some(d @ '1'..='9') => {
+                    let n = d.to_digit(10).unwrap() as usize; // safe, matched a digit
+                    remaining = &remaining[1..];
+                    if let Some(arg) = args.get(n - 1) {
+                        expanded.push_str(arg);
+                    }
+                    max_used = std::cmp::max(max_used, n);
+                }
+                _ => match expand_var(remaining, &lookup) {
+                    Some((val, used)) => {
+                        expanded.push_str(&val);
+                        remaining = &remaining[used..];
+                    }
+                    None => expanded.push('$'),
+                },
+            }
+        }
+        expanded.push_str(remaining);
+        if max_used == 0 && !used_all {
+            expanded.push_str(rest);
+        } else if !used_all && args.len() > max_used {
+            expanded.push(' ');
+            expanded.push_str(&args[max_used..].join(" "));
+        }
+        expanded
+    }
+}
+
+/// How many bytes at the start of s make up a variable name (letters, digits and _, not starting
+/// with a digit)
+fn var_name_len(s: &str) -> usize {
+    s.char_indices()
+        .find(|(i, c)| !(c.is_ascii_alphanumeric() || *c == '_') || (*i == 0 && c.is_ascii_digit()))
+        .map(|(i, _)| i)
+        .unwrap_or_else(|| s.len())
+}
+
+/// Expand the variable reference at the start of rest, which is what followed a $. Returns the
+/// value and how many bytes of rest were used, or None if it isn't a reference to a variable
+/// lookup knows, in which case it should be left as is
+fn expand_var<L>(rest: &str, lookup: &L) -> Option<(String, usize)>
+where
+    L: Fn(&str) -> Option<String>,
+{
+    if rest.starts_with('$') {
+        return Some(("$".to_string(), 1));
+    }
+    let (name, used) = match rest.strip_prefix('{') {
+        Some(braced) => {
+            let end = braced.find('}')?;
+            (&braced[..end], end + 2)
+        }
+        None => {
+            let len = var_name_len(rest);
+            (&rest[..len], len)
+        }
+    };
+    if name.is_empty() || var_name_len(name) != name.len() {
+        return None;
+    }
+    lookup(name).map(|val| (val, used))
+}
+
+fn expand_vars_with<L>(s: &str, lookup: L) -> String
+where
+    L: Fn(&str) -> Option<String>,
+{
+    let mut expanded = String::with_capacity(s.len());
+    let mut remaining = s;
+    while let Some(pos) = remaining.find('$') {
+        expanded.push_str(&remaining[..pos]);
+        remaining = &remaining[pos + 1..];
+        match expand_var(remaining, &lookup) {
+            Some((val, used)) => {
+                expanded.push_str(&val);
+                remaining = &remaining[used..];
+            }
+            None => expanded.push('$'),
+        }
+    }
+    expanded.push_str(remaining);
+    expanded
+}
+
+/// Expand $VAR and ${VAR} in s with the values of environment variables. This is done when a
+/// setting is used rather than when the config is loaded, so changes to the environment are
+/// picked up. Variables that aren't set are left as is, and $$ is a literal $. Values aren't
+/// expanded again, so a value with a $ in it is used as is
+pub fn expand_env_vars(s: &str) -> String {
+    expand_vars_with(s, |name| std::env::var(name).ok())
+}
+
 #[derive(PartialEq, Debug, Deserialize, Serialize)]
 pub enum EditMode {
     Emacs,
@@ -90,6 +221,45 @@ impl From<&CompletionType> for String {
     }
 }
 
+/// Whether command history is shared by all contexts, or kept separately for each one
+#[derive(PartialEq, Debug, Default, Deserialize, Serialize)]
+pub enum HistoryMode {
+    #[default]
+    Global,
+    PerContext,
+}
+
+impl fmt::Display for HistoryMode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                HistoryMode::Global => "Global",
+                HistoryMode::PerContext => "PerContext",
+            }
+        )
+    }
+}
+
+impl From<&HistoryMode> for String {
+    fn from(hm: &HistoryMode) -> String {
+        format!("{}", hm)
+    }
+}
+
+/// What the namespace command does when asked to switch to a namespace that doesn't exist
+#[derive(PartialEq, Debug, Default, Deserialize, Serialize)]
+pub enum NamespaceCheck {
+    /// don't check
+    Off,
+    /// print a warning, but still switch
+    #[default]
+    Warn,
+    /// refuse to switch
+    Strict,
+}
+
 fn default_range_sep() -> String {
     "--- {name} ---".to_string()
 }
@@ -102,10 +272,109 @@ fn default_read_timeout() -> u32 {
     20
 }
 
+fn default_request_retries() -> u32 {
+    2
+}
+
 fn default_describe_include_events() -> bool {
     true
 }
 
+fn default_cache_ttl() -> u32 {
+    0
+}
+
+/// A piece of a parsed prompt_template
+#[derive(Clone, Debug, PartialEq)]
+pub enum PromptPart {
+    Text(String),
+    Context,
+    Namespace,
+    Object,
+    ObjKind,
+    /// Use this colour for everything after it
+    Colour(Colour),
+    /// Make everything after this bold
+    Bold,
+    /// Go back to the default styling
+    Reset,
+}
+
+/// The colours that can be used by name, in prompt_template and for a context's color
+pub const COLOUR_NAMES: &[&str] = &[
+    "black", "red", "green", "yellow", "blue", "purple", "cyan", "white",
+];
+
+/// The colour called name, if it's one of COLOUR_NAMES
+pub fn parse_colour(name: &str) -> Option<Colour> {
+    match name {
+        "black" => Some(Colour::Black),
+        "red" => Some(Colour::Red),
+        "green" => Some(Colour::Green),
+        "yellow" => Some(Colour::Yellow),
+        "blue" => Some(Colour::Blue),
+        "purple" => Some(Colour::Purple),
+        "cyan" => Some(Colour::Cyan),
+        "white" => Some(Colour::White),
+        _ => None,
+    }
+}
+
+/// Parse a prompt_template like "{context}:{namespace} {yellow}{object}{reset} > ". Placeholders
+/// we don't know about are kept as they are, and also returned so the caller can warn about them.
+/// Use {{ and }} to get a literal { or }.
+pub fn parse_prompt_template(template: &str) -> (Vec<PromptPart>, Vec<String>) {
+    let mut parts = vec![];
+    let mut unknown = vec![];
+    let mut text = String::new();
+    let mut rest = template;
+    while let Some(pos) = rest.find(['{', '}']) {
+        text.push_str(&rest[..pos]);
+        rest = &rest[pos..];
+        if rest.starts_with("{{") || rest.starts_with("}}") {
+            text.push_str(&rest[..1]);
+            rest = &rest[2..];
+            continue;
+        }
+        let end = match rest.find('}') {
+            Some(end) if rest.starts_with('{') && !rest[1..end].contains('{') => end,
+            _ => {
+                // a lone } or an unclosed {, keep as is
+                text.push_str(&rest[..1]);
+                rest = &rest[1..];
+                continue;
+            }
+        };
+        let part = match &rest[1..end] {
+            "context" => PromptPart::Context,
+            "namespace" => PromptPart::Namespace,
+            "object" => PromptPart::Object,
+            "objkind" => PromptPart::ObjKind,
+            "bold" => PromptPart::Bold,
+            "reset" => PromptPart::Reset,
+            name => match parse_colour(name) {
+                Some(colour) => PromptPart::Colour(colour),
+                None => {
+                    unknown.push(rest[..=end].to_string());
+                    text.push_str(&rest[..=end]);
+                    rest = &rest[end + 1..];
+                    continue;
+                }
+            },
+        };
+        if !text.is_empty() {
+            parts.push(PromptPart::Text(std::mem::take(&mut text)));
+        }
+        parts.push(part);
+        rest = &rest[end + 1..];
+    }
+    text.push_str(rest);
+    if !text.is_empty() {
+        parts.push(PromptPart::Text(text));
+    }
+    (parts, unknown)
+}
+
 #[derive(Debug, Deserialize, Serialize)]
 pub struct ClickConfig {
     pub namespace: Option<String>,
@@ -118,16 +387,80 @@ pub struct ClickConfig {
     pub completiontype: CompletionType,
     #[serde(default = "Vec::new")]
     pub aliases: Vec<Alias>,
+    #[serde(default = "BTreeMap::new")]
+    pub bookmarks: BTreeMap<String, Bookmark>,
+    /// commands run, in order, when click starts, before the first prompt
+    #[serde(default = "Vec::new")]
+    pub startup_commands: Vec<String>,
     #[serde(default = "default_range_sep")]
     pub range_separator: String,
 
     #[serde(default = "default_connect_timeout")]
     pub connect_timeout_secs: u32,
-    #[serde(default = "default_read_timeout")]
+    /// how long a whole request can take. request_timeout_secs is accepted too, as that's what
+    /// kubectl calls it
+    #[serde(default = "default_read_timeout", alias = "request_timeout_secs")]
     pub read_timeout_secs: u32,
+    /// how many times to retry a GET that couldn't connect to the server
+    #[serde(default = "default_request_retries")]
+    pub request_retries: u32,
 
     #[serde(default = "default_describe_include_events")]
     pub describe_include_events: bool,
+
+    /// how long list results are cached for. 0 disables caching
+    #[serde(default = "default_cache_ttl")]
+    pub cache_ttl_secs: u32,
+
+    /// how many items list commands show by default. 0 shows everything
+    #[serde(default)]
+    pub max_items: u32,
+
+    /// the namespace last used in each context, restored when switching back to that context
+    #[serde(default = "BTreeMap::new")]
+    pub context_namespaces: BTreeMap<String, String>,
+
+    /// contexts that connect directly, ignoring any proxy in the environment or the kubeconfig
+    #[serde(default = "BTreeSet::new")]
+    pub proxy_disabled_contexts: BTreeSet<String>,
+
+    /// template for the prompt, see parse_prompt_template. None uses the default prompt
+    pub prompt_template: Option<String>,
+
+    /// keep one history file for all contexts, or one per context
+    #[serde(default = "HistoryMode::default")]
+    pub history: HistoryMode,
+
+    /// send output that doesn't fit in the terminal through the pager
+    #[serde(default)]
+    pub paging: bool,
+    /// command to page output with. None uses $PAGER, or less -R if that isn't set
+    pub pager: Option<String>,
+
+    /// command yank copies to the clipboard with. None uses pbcopy, wl-copy, xclip or xsel,
+    /// whichever is installed
+    pub clipboard: Option<String>,
+
+    /// colour table headers with the current context's color, if it has one
+    #[serde(default)]
+    pub color_table_headers: bool,
+
+    /// clear the screen when switching to another context or namespace
+    #[serde(default)]
+    pub clear_on_switch: bool,
+
+    /// whether to check a namespace exists before switching to it
+    #[serde(default = "NamespaceCheck::default")]
+    pub namespace_check: NamespaceCheck,
+
+    /// the columns list commands show for a kind, in place of the usual ones. keyed by kind name
+    /// (pods, deploy, ...), see configured_columns in command/mod.rs for what the entries can be
+    #[serde(default = "BTreeMap::new")]
+    pub columns: BTreeMap<String, Vec<String>>,
+
+    /// how much of each request to log to stderr, see crate::trace. 0 logs nothing
+    #[serde(default)]
+    pub verbose: u8,
 }
 
 impl Default for ClickConfig {
@@ -140,10 +473,27 @@ impl Default for ClickConfig {
             editmode: EditMode::default(),
             completiontype: CompletionType::default(),
             aliases: vec![],
+            bookmarks: BTreeMap::new(),
+            startup_commands: vec![],
             range_separator: default_range_sep(),
             connect_timeout_secs: default_connect_timeout(),
             read_timeout_secs: default_read_timeout(),
+            request_retries: default_request_retries(),
             describe_include_events: true,
+            cache_ttl_secs: default_cache_ttl(),
+            max_items: 0,
+            context_namespaces: BTreeMap::new(),
+            proxy_disabled_contexts: BTreeSet::new(),
+            prompt_template: None,
+            history: HistoryMode::default(),
+            paging: false,
+            pager: None,
+            clipboard: None,
+            color_table_headers: false,
+            clear_on_switch: false,
+            namespace_check: NamespaceCheck::default(),
+            columns: BTreeMap::new(),
+            verbose: 0,
         }
     }
 }
@@ -201,7 +551,15 @@ editmode: Vi
 completiontype: List
 aliases:
   - alias: pn
-    expanded: pods --sort node";
+    expanded: pods --sort node
+context_namespaces:
+  ctx: ns
+namespace_check: Strict
+startup_commands:
+  - ns
+  - pods
+columns:
+  pods: [Name, Status, Node, metadata.labels.app]";
 
     #[test]
     fn test_parse_config() {
@@ -219,8 +577,25 @@ aliases:
         let a = config.aliases.get(0).unwrap();
         assert_eq!(a.alias, "pn");
         assert_eq!(a.expanded, "pods --sort node");
+        assert_eq!(config.startup_commands, vec!["ns", "pods"]);
+        assert_eq!(
+            config.columns.get("pods"),
+            Some(&vec![
+                "Name".to_string(),
+                "Status".to_string(),
+                "Node".to_string(),
+                "metadata.labels.app".to_string()
+            ])
+        );
         assert_eq!(config.connect_timeout_secs, default_connect_timeout());
         assert_eq!(config.read_timeout_secs, default_read_timeout());
+        assert_eq!(config.cache_ttl_secs, default_cache_ttl());
+        assert_eq!(config.max_items, 0);
+        assert_eq!(config.namespace_check, NamespaceCheck::Strict);
+        assert_eq!(
+            config.context_namespaces.get("ctx").map(|s| s.as_str()),
+            Some("ns")
+        );
     }
 
     #[test]
@@ -232,6 +607,112 @@ aliases:
         assert_eq!(config.read_timeout_secs, default_read_timeout());
         assert_eq!(config.connect_timeout_secs, default_connect_timeout());
         assert_eq!(config.range_separator, default_range_sep());
+        assert_eq!(config.namespace_check, NamespaceCheck::Warn);
+    }
+
+    #[test]
+    fn test_request_timeout_alias() {
+        let config =
+            ClickConfig::from_reader("request_timeout_secs: 5\nrequest_retries: 0".as_bytes())
+                .unwrap();
+        assert_eq!(config.read_timeout_secs, 5);
+        assert_eq!(config.request_retries, 0);
+        assert_eq!(config.connect_timeout_secs, default_connect_timeout());
+    }
+
+    #[test]
+    fn test_parse_prompt_template() {
+        let (parts, unknown) = parse_prompt_template("{red}{context}{reset}:{namespace} {{x}} > ");
+        assert_eq!(
+            parts,
+            vec![
+                PromptPart::Colour(Colour::Red),
+                PromptPart::Context,
+                PromptPart::Reset,
+                PromptPart::Text(":".to_string()),
+                PromptPart::Namespace,
+                PromptPart::Text(" {x} > ".to_string()),
+            ]
+        );
+        assert!(unknown.is_empty());
+
+        let (parts, unknown) = parse_prompt_template("{bold}{objkind}/{object} {foo} {a {oops");
+        assert_eq!(
+            parts,
+            vec![
+                PromptPart::Bold,
+                PromptPart::ObjKind,
+                PromptPart::Text("/".to_string()),
+                PromptPart::Object,
+                PromptPart::Text(" {foo} {a {oops".to_string()),
+            ]
+        );
+        assert_eq!(unknown, vec!["{foo}".to_string()]);
+    }
+
+    #[test]
+    fn test_alias_expand() {
+        let alias = |expanded: &str| Alias {
+            alias: "a".to_string(),
+            expanded: expanded.to_string(),
+        };
+        assert_eq!(alias("pods").expand(" --sort node"), "pods --sort node");
+        assert_eq!(alias("pods | grep $1").expand(" foo"), "pods | grep foo");
+        assert_eq!(alias("pods | grep $1").expand(""), "pods | grep ");
+        assert_eq!(
+            alias("pods | grep $1").expand(" foo -i"),
+            "pods | grep foo -i"
+        );
+        assert_eq!(alias("logs $2 -t $1").expand(" 10 cont"), "logs cont -t 10");
+        assert_eq!(alias("echo $*").expand(" a  b c"), "echo a b c");
+        assert_eq!(alias("echo $1 $*").expand(" a b"), "echo a a b");
+        assert_eq!(alias("cost $5").expand(""), "cost ");
+        assert_eq!(alias("price $x $").expand(" y"), "price $x $ y");
+
+        // environment variables are expanded, but not the words typed after the alias
+        let lookup = |name: &str| match name {
+            "NS" => Some("prod".to_string()),
+            _ => None,
+        };
+        assert_eq!(
+            alias("pods -n $NS $1").expand_with(" $NS", lookup),
+            "pods -n prod $NS"
+        );
+        assert_eq!(
+            alias("echo $$1 ${NS}1 $UNSET").expand_with(" a", lookup),
+            "echo $1 prod1 $UNSET a"
+        );
+    }
+
+    #[test]
+    fn test_expand_vars() {
+        let lookup = |name: &str| match name {
+            "HOME" => Some("/home/me".to_string()),
+            "EDITOR_ARGS" => Some("-w".to_string()),
+            "NESTED" => Some("${HOME}/$EDITOR_ARGS".to_string()),
+            "EMPTY" => Some("".to_string()),
+            _ => None,
+        };
+        assert_eq!(expand_vars_with("code $EDITOR_ARGS", lookup), "code -w");
+        assert_eq!(
+            expand_vars_with("${HOME}/bin/ed${EMPTY}it", lookup),
+            "/home/me/bin/edit"
+        );
+        assert_eq!(expand_vars_with("$HOME_DIR/x", lookup), "$HOME_DIR/x");
+        // values aren't expanded again
+        assert_eq!(
+            expand_vars_with("cd $NESTED", lookup),
+            "cd ${HOME}/$EDITOR_ARGS"
+        );
+        // missing variables, invalid names and unterminated braces are left as they are
+        assert_eq!(
+            expand_vars_with("$MISSING ${MISSING} ${NO SPACES} $1 ${HOME", lookup),
+            "$MISSING ${MISSING} ${NO SPACES} $1 ${HOME"
+        );
+        assert_eq!(
+            expand_vars_with("cost $$5 $$HOME $", lookup),
+            "cost $5 $HOME $"
+        );
     }
 
     #[test]
@@ -19,36 +19,49 @@ use std::collections::BTreeMap;
 use std::collections::HashMap;
 use std::convert::From;
 use std::env;
+use std::fmt;
 use std::fs::File;
 use std::io::{BufReader, Read};
 
 //use crate::certs::{get_cert, get_cert_from_pem, get_key_from_str, get_private_key};
+use super::kubeedit::{edit_kubeconfig, KubeconfigEdit};
 use super::kubefile::{AuthProvider, ExecProvider};
-use crate::config::ClickConfig;
+use crate::certs::{pkcs12_to_pems, ServerNameOverride, PKCS12_PASSWORD_VAR};
+use crate::config::{parse_colour, ClickConfig, COLOUR_NAMES};
 use crate::error::{ClickErrNo, ClickError};
-use crate::k8s::UserAuth as K8SUserAuth;
+use crate::k8s::{ProxySetting, UserAuth as K8SUserAuth};
 
 #[derive(Debug)]
 pub struct ClusterConf {
     pub cert: Option<String>,
     pub server: String,
     pub insecure_skip_tls_verify: bool,
+    pub tls_server_name: Option<String>,
+    pub proxy_url: Option<String>,
 }
 
 impl ClusterConf {
-    fn new(cert: Option<String>, server: String) -> ClusterConf {
+    fn new(cert: Option<String>, server: String, tls_server_name: Option<String>) -> ClusterConf {
         ClusterConf {
             cert,
             server,
             insecure_skip_tls_verify: false,
+            tls_server_name,
+            proxy_url: None,
         }
     }
 
-    fn new_insecure(cert: Option<String>, server: String) -> ClusterConf {
+    fn new_insecure(
+        cert: Option<String>,
+        server: String,
+        tls_server_name: Option<String>,
+    ) -> ClusterConf {
         ClusterConf {
             cert,
             server,
             insecure_skip_tls_verify: true,
+            tls_server_name,
+            proxy_url: None,
         }
     }
 }
@@ -62,6 +75,7 @@ pub enum UserAuth {
     Token(String),
     KeyCertPath(String, String),
     KeyCertData(String, String),
+    Pkcs12Path(String),
     UserPass(String, String),
     AuthProvider(Box<AuthProvider>),
     ExecProvider(ExecProvider),
@@ -82,8 +96,15 @@ impl From<super::kubefile::UserConf> for UserConf {
         if let (Some(username), Some(password)) = (conf.username, conf.password) {
             auth_vec.push(UserAuth::UserPass(username, password))
         }
-        if let (Some(client_cert_path), Some(key_path)) = (conf.client_cert, conf.client_key) {
-            auth_vec.push(UserAuth::KeyCertPath(client_cert_path, key_path))
+        match (conf.client_cert, conf.client_key) {
+            (Some(client_cert_path), Some(key_path)) => {
+                auth_vec.push(UserAuth::KeyCertPath(client_cert_path, key_path))
+            }
+            // a PKCS#12 bundle has the key in it too
+            (Some(client_cert_path), None) if is_pkcs12_path(&client_cert_path) => {
+                auth_vec.push(UserAuth::Pkcs12Path(client_cert_path))
+            }
+            _ => {}
         }
         if let (Some(client_cert_data), Some(key_data)) =
             (conf.client_cert_data, conf.client_key_data)
@@ -100,17 +121,122 @@ impl From<super::kubefile::UserConf> for UserConf {
     }
 }
 
+impl UserConf {
+    /// The client certificate this user authenticates with as a pem, if it has one
+    pub fn client_cert_pem(&self) -> Option<Result<String, ClickError>> {
+        self.auths.iter().find_map(|auth| match auth {
+            UserAuth::KeyCertPath(cert_path, _) => Some(
+                get_full_path(cert_path.clone())
+                    .and_then(|path| std::fs::read_to_string(path).map_err(ClickError::from)),
+            ),
+            UserAuth::Pkcs12Path(bundle_path) => Some(
+                get_full_path(bundle_path.clone())
+                    .and_then(|path| {
+                        let der = std::fs::read(&path)?;
+                        let password = std::env::var(PKCS12_PASSWORD_VAR).unwrap_or_default();
+                        pkcs12_to_pems(&der, &password, &path)
+                    })
+                    .map(|(_, cert)| String::from_utf8_lossy(&cert).into_owned()),
+            ),
+            UserAuth::KeyCertData(cert_data, _) => Some(
+                ::base64::decode(cert_data.as_str())
+                    .map_err(ClickError::from)
+                    .and_then(|cert| {
+                        String::from_utf8(cert).map_err(|e| {
+                            ClickError::ConfigFileError(format!(
+                                "Invalid utf8 data in certificate: {}",
+                                e
+                            ))
+                        })
+                    }),
+            ),
+            _ => None,
+        })
+    }
+
+    /// The external commands this user's auth-provider or exec config runs to get credentials
+    pub fn auth_commands(&self) -> Vec<&str> {
+        self.auths
+            .iter()
+            .filter_map(|auth| match auth {
+                UserAuth::AuthProvider(provider) => provider.command(),
+                UserAuth::ExecProvider(provider) => provider.config.command(),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+impl UserConf {
+    /// Short names for the kinds of auth this user has, for display
+    pub fn auth_kinds(&self) -> Vec<&'static str> {
+        self.auths
+            .iter()
+            .map(|auth| match auth {
+                UserAuth::Token(_) => "token",
+                UserAuth::KeyCertPath(_, _)
+                | UserAuth::KeyCertData(_, _)
+                | UserAuth::Pkcs12Path(_) => "client-cert",
+                UserAuth::UserPass(_, _) => "user/pass",
+                UserAuth::AuthProvider(_) => "auth-provider",
+                UserAuth::ExecProvider(_) => "exec",
+            })
+            .collect()
+    }
+}
+
+/// Which file each cluster, context and user in a merged config came from
+#[derive(Debug, Default)]
+pub struct ConfigOrigins {
+    pub clusters: BTreeMap<String, String>,
+    pub contexts: BTreeMap<String, String>,
+    pub users: BTreeMap<String, String>,
+    pub current_context: Option<String>,
+}
+
+/// Where the list of kubernetes config files to load came from
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ConfigSource {
+    /// The --kubeconfig argument
+    Arg,
+    /// The KUBECONFIG environment variable
+    Env,
+    /// The config file in the config dir (~/.kube by default)
+    Default,
+}
+
+impl fmt::Display for ConfigSource {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConfigSource::Arg => write!(f, "--kubeconfig"),
+            ConfigSource::Env => write!(f, "$KUBECONFIG"),
+            ConfigSource::Default => write!(f, "the config dir"),
+        }
+    }
+}
+
 /// A kubernetes config
 // This is actual config we expose
 #[derive(Debug)]
 pub struct Config {
     pub source_file: String,
+    /// Where the list of files in source_file came from
+    pub source: ConfigSource,
     pub clusters: HashMap<String, ClusterConf>,
     pub contexts: BTreeMap<String, super::kubefile::ContextConf>,
     pub users: HashMap<String, UserConf>,
+    /// current-context from the first file that sets it
+    pub current_context: Option<String>,
+    pub origins: ConfigOrigins,
 }
 
 // some utility functions
+/// Is path a PKCS#12 bundle, going by its extension
+fn is_pkcs12_path(path: &str) -> bool {
+    let lower = path.to_lowercase();
+    lower.ends_with(".p12") || lower.ends_with(".pfx")
+}
+
 fn get_full_path(path: String) -> Result<String, ClickError> {
     if path.is_empty() {
         return Err(ClickError::ConfigFileError(
@@ -133,21 +259,29 @@ fn get_full_path(path: String) -> Result<String, ClickError> {
 }
 
 impl Config {
+    /// Read and merge the config files in paths. Like kubectl, the first file to define a cluster,
+    /// context, or user wins, and current-context comes from the first file that sets it
     pub fn from_files(paths: &[String]) -> Result<Config, ClickError> {
         let iconfs = paths
             .iter()
             .map(|config_path| super::kubefile::Config::from_file(config_path))
             .collect::<Result<Vec<_>, _>>()?;
+        let mut origins = ConfigOrigins::default();
 
         // copy over clusters
         let mut cluster_map = HashMap::new();
-        for iconf in iconfs.iter() {
+        for (path, iconf) in paths.iter().zip(iconfs.iter()) {
             for cluster in iconf.clusters.iter() {
+                if origins.clusters.contains_key(&cluster.name) {
+                    continue;
+                }
+                origins.clusters.insert(cluster.name.clone(), path.clone());
                 // make sure we've specified one of:
                 //  - a cert file
                 //  - cert data
                 //  - insecure-skip-tls-verify
                 let has_cd = cluster.conf.cert_data.is_some();
+                let tls_server_name = cluster.conf.tls_server_name.clone();
                 match (&cluster.conf.cert, &cluster.conf.cert_data) {
                     (Some(cert_config_path), _) => {
                         if has_cd {
@@ -165,7 +299,11 @@ impl Config {
                                 br.read_to_string(&mut s).expect("Couldn't read cert");
                                 cluster_map.insert(
                                     cluster.name.clone(),
-                                    ClusterConf::new(Some(s), cluster.conf.server.clone()),
+                                    ClusterConf::new(
+                                        Some(s),
+                                        cluster.conf.server.clone(),
+                                        tls_server_name,
+                                    ),
                                 );
                             }
                             Err(e) => {
@@ -188,7 +326,11 @@ impl Config {
                             })?;
                             cluster_map.insert(
                                 cluster.name.clone(),
-                                ClusterConf::new(Some(cert_pem), cluster.conf.server.clone()),
+                                ClusterConf::new(
+                                    Some(cert_pem),
+                                    cluster.conf.server.clone(),
+                                    tls_server_name,
+                                ),
                             );
                         }
                         Err(e) => {
@@ -197,32 +339,68 @@ impl Config {
                     },
                     (None, None) => {
                         let conf = if cluster.conf.skip_tls {
-                            ClusterConf::new_insecure(None, cluster.conf.server.clone())
+                            ClusterConf::new_insecure(
+                                None,
+                                cluster.conf.server.clone(),
+                                tls_server_name,
+                            )
                         } else {
-                            ClusterConf::new(None, cluster.conf.server.clone())
+                            ClusterConf::new(None, cluster.conf.server.clone(), tls_server_name)
                         };
                         cluster_map.insert(cluster.name.clone(), conf);
                     }
                 }
+                if let Some(conf) = cluster_map.get_mut(&cluster.name) {
+                    conf.proxy_url = cluster.conf.proxy_url.clone();
+                }
             }
         }
 
         // copy over contexts
         let mut context_map = BTreeMap::new();
-        for iconf in iconfs.iter() {
+        for (path, iconf) in paths.iter().zip(iconfs.iter()) {
             for context in iconf.contexts.iter() {
-                context_map.insert(context.name.clone(), context.conf.clone());
+                if !context_map.contains_key(&context.name) {
+                    if let Some(color) = context.conf.color.as_ref() {
+                        if parse_colour(color).is_none() {
+                            println!(
+                                "[WARN] Context {} has an unknown color {}, it will be ignored. \
+                                 Valid colors are: {}",
+                                context.name,
+                                color,
+                                COLOUR_NAMES.join(", ")
+                            );
+                        }
+                    }
+                    context_map.insert(context.name.clone(), context.conf.clone());
+                    origins.contexts.insert(context.name.clone(), path.clone());
+                }
             }
         }
 
         // copy over users
         let mut user_map = HashMap::new();
-        for iconf in iconfs.iter() {
+        for (path, iconf) in paths.iter().zip(iconfs.iter()) {
             for user in iconf.users.iter() {
-                user_map.insert(user.name.clone(), user.conf.clone().into());
+                if !user_map.contains_key(&user.name) {
+                    user_map.insert(user.name.clone(), user.conf.clone().into());
+                    origins.users.insert(user.name.clone(), path.clone());
+                }
             }
         }
 
+        let mut current_context = None;
+        if let Some((path, ctx)) = paths.iter().zip(iconfs.iter()).find_map(|(path, iconf)| {
+            iconf
+                .current_context
+                .as_ref()
+                .filter(|ctx| !ctx.is_empty())
+                .map(|ctx| (path, ctx))
+        }) {
+            current_context = Some(ctx.clone());
+            origins.current_context = Some(path.clone());
+        }
+
         let sources = match env::join_paths(paths.iter())?.into_string() {
             Ok(srcs) => srcs,
             Err(_) => "[config paths contain non-utf8 characters, cannot be displayed]".to_string(),
@@ -230,12 +408,42 @@ impl Config {
 
         Ok(Config {
             source_file: sources,
+            source: ConfigSource::Default,
             clusters: cluster_map,
             contexts: context_map,
             users: user_map,
+            current_context,
+            origins,
         })
     }
 
+    /// Set the default namespace of a context (or remove it, if namespace is None), and save the
+    /// change to the file the context came from
+    pub fn set_context_namespace(
+        &mut self,
+        context: &str,
+        namespace: Option<&str>,
+    ) -> Result<(), ClickError> {
+        let path = self
+            .origins
+            .contexts
+            .get(context)
+            .ok_or(ClickError::Kube(ClickErrNo::InvalidContextName))?;
+        edit_kubeconfig(
+            path,
+            &[KubeconfigEdit {
+                list: "contexts",
+                name: context,
+                keys: &["context", "namespace"],
+                value: namespace,
+            }],
+        )?;
+        if let Some(conf) = self.contexts.get_mut(context) {
+            conf.namespace = namespace.map(|ns| ns.to_string());
+        }
+        Ok(())
+    }
+
     pub fn get_context(
         &self,
         context_name: &str,
@@ -256,6 +464,21 @@ impl Config {
 
         let endpoint = reqwest::Url::parse(&cluster.server).unwrap();
         let ca_cert = cluster.cert.as_ref().map(|c| get_reqwest_cert(c));
+        let server_name = match cluster.tls_server_name.as_ref() {
+            Some(name) => Some(ServerNameOverride::new(
+                name.clone(),
+                cluster.cert.as_deref(),
+            )?),
+            None => None,
+        };
+        let proxy = if click_conf.proxy_disabled_contexts.contains(context_name) {
+            ProxySetting::Off
+        } else {
+            match cluster.proxy_url.as_deref() {
+                Some(url) => ProxySetting::Url(parse_proxy_url(url)?),
+                None => ProxySetting::Environment,
+            }
+        };
 
         let mut k8suser = Err(ClickError::ConfigFileError(
             "[WARN]: Context {} has no client certificate and key, nor does it specify \
@@ -274,41 +497,61 @@ impl Config {
                         K8SUserAuth::with_user_pass(username.to_string(), password.to_string());
                 }
                 UserAuth::AuthProvider(provider) => {
+                    let mut provider = *provider.clone();
+                    if let Some(path) = self.origins.users.get(&context.user) {
+                        provider.set_origin(path.clone(), context.user.clone());
+                    }
                     provider.copy_up();
-                    k8suser = K8SUserAuth::with_auth_provider(*provider.clone());
+                    k8suser = K8SUserAuth::with_auth_provider(provider);
                 }
                 UserAuth::ExecProvider(provider) => {
                     k8suser = K8SUserAuth::with_exec_provider(provider.clone());
                 }
                 UserAuth::KeyCertData(cert_data, key_data) => {
-                    k8suser = K8SUserAuth::from_key_cert_data(
-                        key_data.clone(),
-                        cert_data.clone(),
-                        &endpoint,
-                    );
+                    k8suser = K8SUserAuth::from_key_cert_data(key_data.clone(), cert_data.clone());
                 }
                 UserAuth::KeyCertPath(cert_path, key_path) => {
                     let cert_full_path = get_full_path(cert_path.clone())?;
                     let key_full_path = get_full_path(key_path.clone())?;
-                    k8suser =
-                        K8SUserAuth::from_key_cert(&key_full_path, &cert_full_path, &endpoint);
+                    k8suser = K8SUserAuth::from_key_cert(&key_full_path, &cert_full_path);
+                }
+                UserAuth::Pkcs12Path(bundle_path) => {
+                    let bundle_full_path = get_full_path(bundle_path.clone())?;
+                    k8suser = K8SUserAuth::from_pkcs12(&bundle_full_path);
                 }
             };
         }
 
-        k8suser.map(|user| {
+        k8suser.and_then(|user| {
             crate::k8s::Context::new(
                 context_name,
                 endpoint,
                 ca_cert,
+                server_name,
                 Some(user),
                 click_conf.connect_timeout_secs,
                 click_conf.read_timeout_secs,
+                click_conf.request_retries,
+                cluster.insecure_skip_tls_verify,
+                proxy,
             )
         })
     }
 }
 
+/// Parse a cluster's proxy-url. Only http and https proxies are supported
+fn parse_proxy_url(url: &str) -> Result<reqwest::Url, ClickError> {
+    let parsed = reqwest::Url::parse(url)
+        .map_err(|e| ClickError::ConfigFileError(format!("Invalid proxy-url {}: {}", url, e)))?;
+    match parsed.scheme() {
+        "http" | "https" => Ok(parsed),
+        scheme => Err(ClickError::ConfigFileError(format!(
+            "Unsupported proxy-url scheme {} in {}, only http and https proxies are supported",
+            scheme, url
+        ))),
+    }
+}
+
 fn get_reqwest_cert(data: &str) -> reqwest::Certificate {
     reqwest::Certificate::from_pem(data.as_bytes()).unwrap()
 }
@@ -321,9 +564,146 @@ pub mod tests {
     pub fn get_test_config() -> Config {
         Config {
             source_file: "/tmp/test.conf".to_string(),
+            source: ConfigSource::Default,
             clusters: HashMap::new(),
             contexts: BTreeMap::new(),
             users: HashMap::new(),
+            current_context: None,
+            origins: ConfigOrigins::default(),
         }
     }
+
+    static FIRST_CONFIG: &str = r"apiVersion: v1
+clusters:
+- cluster:
+    server: https://first.test
+  name: shared
+contexts:
+- context:
+    cluster: shared
+    user: shared
+    namespace: first
+  name: shared
+users:
+- name: shared
+  user:
+    token: FIRST
+";
+
+    static SECOND_CONFIG: &str = r"apiVersion: v1
+clusters:
+- cluster:
+    server: https://second.test
+  name: shared
+- cluster:
+    server: https://only-second.test
+  name: second
+contexts:
+- context:
+    cluster: shared
+    user: shared
+    namespace: second
+  name: shared
+- context:
+    cluster: second
+    user: second
+  name: second
+current-context: second
+users:
+- name: shared
+  user:
+    username: second
+    password: second
+- name: second
+  user:
+    token: SECOND
+";
+
+    static THIRD_CONFIG: &str = r"apiVersion: v1
+current-context: shared
+";
+
+    #[test]
+    fn merge_first_wins() {
+        let dir = tempdir::TempDir::new("click-kubeconfig-test").unwrap();
+        let paths: Vec<String> = [FIRST_CONFIG, SECOND_CONFIG, THIRD_CONFIG]
+            .iter()
+            .enumerate()
+            .map(|(i, content)| {
+                let path = dir.path().join(format!("config{}", i));
+                std::fs::write(&path, content).unwrap();
+                path.to_str().unwrap().to_string()
+            })
+            .collect();
+        let config = Config::from_files(&paths).unwrap();
+
+        assert_eq!(config.clusters["shared"].server, "https://first.test");
+        assert_eq!(config.clusters["second"].server, "https://only-second.test");
+        assert_eq!(
+            config.contexts["shared"].namespace.as_deref(),
+            Some("first")
+        );
+        assert!(config.contexts.contains_key("second"));
+        assert_eq!(config.users["shared"].auth_kinds(), vec!["token"]);
+        assert_eq!(config.users["second"].auth_kinds(), vec!["token"]);
+
+        // first file doesn't set it, so it comes from the second, not the third
+        assert_eq!(config.current_context.as_deref(), Some("second"));
+        assert_eq!(config.origins.current_context.as_ref(), Some(&paths[1]));
+
+        assert_eq!(config.origins.clusters["shared"], paths[0]);
+        assert_eq!(config.origins.clusters["second"], paths[1]);
+        assert_eq!(config.origins.contexts["shared"], paths[0]);
+        assert_eq!(config.origins.users["second"], paths[1]);
+    }
+
+    #[test]
+    fn proxy_url() {
+        assert_eq!(
+            parse_proxy_url("http://proxy.test:3128").unwrap().as_str(),
+            "http://proxy.test:3128/"
+        );
+        assert!(parse_proxy_url("https://proxy.test").is_ok());
+        assert!(parse_proxy_url("socks5://proxy.test:1080").is_err());
+        assert!(parse_proxy_url("not a url").is_err());
+    }
+
+    #[test]
+    fn pkcs12_client_certificate() {
+        let conf: super::super::kubefile::UserConf =
+            serde_yaml::from_str("client-certificate: certs/me.P12").unwrap();
+        let user = UserConf::from(conf);
+        assert!(matches!(&user.auths[..], [UserAuth::Pkcs12Path(path)] if path == "certs/me.P12"));
+        // a pem certificate needs a key to go with it
+        let conf: super::super::kubefile::UserConf =
+            serde_yaml::from_str("client-certificate: certs/me.crt").unwrap();
+        assert!(UserConf::from(conf).auths.is_empty());
+    }
+
+    #[test]
+    fn set_context_namespace() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempdir::TempDir::new("click-kubeconfig-test").unwrap();
+        let path = dir.path().join("config");
+        std::fs::write(&path, SECOND_CONFIG).unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600)).unwrap();
+        let paths = vec![path.to_str().unwrap().to_string()];
+        let mut config = Config::from_files(&paths).unwrap();
+
+        config.set_context_namespace("second", Some("web")).unwrap();
+        config.set_context_namespace("shared", None).unwrap();
+        assert_eq!(config.contexts["second"].namespace.as_deref(), Some("web"));
+        assert_eq!(config.contexts["shared"].namespace, None);
+        assert!(config.set_context_namespace("nope", Some("web")).is_err());
+
+        // and it was saved
+        let reread = Config::from_files(&paths).unwrap();
+        assert_eq!(reread.contexts["second"].namespace.as_deref(), Some("web"));
+        assert_eq!(reread.contexts["shared"].namespace, None);
+        assert_eq!(reread.current_context.as_deref(), Some("second"));
+        // without widening the mode of the file
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
 }
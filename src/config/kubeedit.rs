@@ -0,0 +1,368 @@
+// Copyright 2021 Databricks, Inc.
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+
+// http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Changing values in the user's kubeconfig files. Only the lines holding the values are changed,
+//! so comments and formatting are kept, and the file is written back with the permissions it had,
+//! since kubeconfigs usually hold credentials.
+
+use atomicwrites::{AllowOverwrite, AtomicFile};
+use serde_yaml::Value;
+
+use std::fs;
+use std::io::Write;
+
+use crate::error::ClickError;
+
+/// A change to one value in a kubeconfig. The value is at keys, in the entry called name in the
+/// top level list (users, contexts, ...). It's removed if value is None
+#[derive(Debug)]
+pub struct KubeconfigEdit<'a> {
+    pub list: &'a str,
+    pub name: &'a str,
+    pub keys: &'a [&'a str],
+    pub value: Option<&'a str>,
+}
+
+/// Make edits to the kubeconfig at path. It's an error if an entry, or the mapping that holds one
+/// of the values, isn't there
+pub fn edit_kubeconfig(path: &str, edits: &[KubeconfigEdit]) -> Result<(), ClickError> {
+    let contents = fs::read_to_string(path)?;
+    let mut expected: Value = serde_yaml::from_str(&contents)?;
+    for edit in edits.iter() {
+        if !edit_value(&mut expected, edit) {
+            return Err(ClickError::ConfigFileError(format!(
+                "No {} in the {} entry {} in {}",
+                edit.keys.join("."),
+                edit.list,
+                edit.name,
+                path
+            )));
+        }
+    }
+    // the edited text has to mean the same as the edited yaml. if it doesn't, or the file is laid
+    // out in a way edit_text can't handle, fall back to writing the yaml out, losing comments
+    let edited = edit_text(&contents, edits).filter(|text| same_yaml(text, &expected));
+    let new_contents = match edited {
+        Some(text) => text,
+        None => serde_yaml::to_string(&expected)?,
+    };
+    write_kubeconfig(path, new_contents.as_bytes())
+}
+
+/// Replace the kubeconfig at path with contents, atomically, keeping its permissions
+fn write_kubeconfig(path: &str, contents: &[u8]) -> Result<(), ClickError> {
+    let permissions = fs::metadata(path)?.permissions();
+    let af = AtomicFile::new(path, AllowOverwrite);
+    af.write(|f| {
+        // the temporary file is created with the default mode, which is usually world readable
+        f.set_permissions(permissions)?;
+        f.write_all(contents)
+    })
+    .map_err(|e| ClickError::ConfigFileError(format!("Failed to write {}: {}", path, e)))
+}
+
+/// Make edit to a parsed kubeconfig. Returns false if there's nowhere to make it
+fn edit_value(kubeconfig: &mut Value, edit: &KubeconfigEdit) -> bool {
+    let (key, parents) = match edit.keys.split_last() {
+        Some(split) => split,
+        None => return false,
+    };
+    let mapping = kubeconfig
+        .get_mut(edit.list)
+        .and_then(|entries| entries.as_sequence_mut())
+        .and_then(|entries| {
+            entries
+                .iter_mut()
+                .find(|e| e.get("name").and_then(|n| n.as_str()) == Some(edit.name))
+        })
+        .and_then(|entry| parents.iter().try_fold(entry, |val, key| val.get_mut(*key)))
+        .and_then(|val| val.as_mapping_mut());
+    match mapping {
+        Some(mapping) => {
+            match edit.value {
+                Some(value) => mapping.insert((*key).into(), value.into()),
+                None => mapping.remove(&(*key).into()),
+            };
+            true
+        }
+        None => false,
+    }
+}
+
+/// Does text parse to expected. Mappings are compared ignoring order, since the parsed mapping
+/// moves values it updates to the end
+fn same_yaml(text: &str, expected: &Value) -> bool {
+    let as_json = |val: &Value| serde_json::to_value(val).ok();
+    match serde_yaml::from_str::<Value>(text) {
+        Ok(parsed) => as_json(&parsed).is_some() && as_json(&parsed) == as_json(expected),
+        Err(_) => false,
+    }
+}
+
+/// Make edits to the text of a kubeconfig. Returns None if the values aren't all simple block
+/// style scalars this can find
+fn edit_text(contents: &str, edits: &[KubeconfigEdit]) -> Option<String> {
+    let mut lines: Vec<String> = contents.lines().map(|line| line.to_string()).collect();
+    for edit in edits.iter() {
+        edit_lines(&mut lines, edit)?;
+    }
+    let mut text = lines.join("\n");
+    if contents.ends_with('\n') {
+        text.push('\n');
+    }
+    Some(text)
+}
+
+/// Lines that are neither blank nor comments
+fn is_content(line: &str) -> bool {
+    let trimmed = line.trim_start();
+    !trimmed.is_empty() && !trimmed.starts_with('#')
+}
+
+/// The indent of line, counting the dash that starts a list item as indent, since the keys in the
+/// item are at that indent
+fn key_indent(line: &str) -> usize {
+    let spaces = line.len() - line.trim_start_matches(' ').len();
+    let rest = &line[spaces..];
+    match rest.strip_prefix("- ") {
+        Some(item) => spaces + 2 + (item.len() - item.trim_start_matches(' ').len()),
+        None => spaces,
+    }
+}
+
+/// If line is a mapping key, its key and the rest of the line after the colon
+fn split_key(line: &str) -> Option<(&str, &str)> {
+    let rest = &line[key_indent(line)..];
+    let colon = rest
+        .find(": ")
+        .or_else(|| rest.strip_suffix(':').map(|r| r.len()))?;
+    let key = rest[..colon].trim_matches(|c| c == '"' || c == '\'');
+    Some((key, rest[colon + 1..].trim()))
+}
+
+/// The end of the block of lines from start that are indented more than indent
+fn block_end(lines: &[String], start: usize, end: usize, indent: usize) -> usize {
+    (start..end)
+        .find(|i| is_content(&lines[*i]) && key_indent(&lines[*i]) <= indent)
+        .unwrap_or(end)
+}
+
+/// Find key in the mapping at indent in lines[start..end]
+fn find_key(lines: &[String], start: usize, end: usize, indent: usize, key: &str) -> Option<usize> {
+    (start..end).find(|i| {
+        let line = &lines[*i];
+        is_content(line)
+            && key_indent(line) == indent
+            && split_key(line).map(|(k, _)| k) == Some(key)
+    })
+}
+
+/// The value for a key, quoted if it wouldn't read back as the same string
+fn yaml_scalar(value: &str) -> String {
+    let plain = serde_yaml::from_str::<Value>(value).ok() == Some(Value::String(value.into()))
+        && !value.contains(" #");
+    if plain {
+        value.to_string()
+    } else {
+        // a json string is a valid double quoted yaml scalar
+        serde_json::to_string(value).unwrap() // safe, it's a string
+    }
+}
+
+fn edit_lines(lines: &mut Vec<String>, edit: &KubeconfigEdit) -> Option<()> {
+    let (key, parents) = edit.keys.split_last()?;
+    let list = find_key(lines, 0, lines.len(), 0, edit.list)?;
+    let list_end = (list + 1..lines.len())
+        .find(|i| {
+            let line = &lines[*i];
+            is_content(line) && !line.starts_with(' ') && !line.starts_with('-')
+        })
+        .unwrap_or(lines.len());
+
+    // the list items, which all start with a dash at the same indent
+    let first = (list + 1..list_end).find(|i| is_content(&lines[*i]))?;
+    let dash_indent = lines[first].len() - lines[first].trim_start().len();
+    let items: Vec<usize> = (first..list_end)
+        .filter(|i| {
+            let line = &lines[*i];
+            line.len() - line.trim_start().len() == dash_indent
+                && line.trim_start().starts_with("- ")
+        })
+        .collect();
+    let (mut start, mut end, mut indent) = items.iter().enumerate().find_map(|(n, item)| {
+        let item_end = items.get(n + 1).copied().unwrap_or(list_end);
+        let indent = key_indent(&lines[*item]);
+        let name = find_key(lines, *item, item_end, indent, "name")?;
+        let value = split_key(&lines[name])?.1;
+        if value.trim_matches(|c| c == '"' || c == '\'') == edit.name {
+            Some((*item, item_end, indent))
+        } else {
+            None
+        }
+    })?;
+
+    // down through the mappings holding the value
+    for parent in parents.iter() {
+        let line = find_key(lines, start, end, indent, parent)?;
+        if !split_key(&lines[line])?.1.is_empty() {
+            return None;
+        }
+        end = block_end(lines, line + 1, end, indent);
+        start = (line + 1..end).find(|i| is_content(&lines[*i]))?;
+        indent = key_indent(&lines[start]);
+    }
+
+    match (find_key(lines, start, end, indent, key), edit.value) {
+        (Some(line), value) => {
+            let current = split_key(&lines[line])?.1;
+            let multi_line = current.is_empty()
+                || current.starts_with('|')
+                || current.starts_with('>')
+                || block_end(lines, line + 1, end, indent) != line + 1;
+            if multi_line || lines[line].trim_start().starts_with('-') {
+                return None;
+            }
+            match value {
+                Some(value) => {
+                    let indent = key_indent(&lines[line]);
+                    let key_end = indent + lines[line][indent..].find(':')?;
+                    let new_line = format!("{}: {}", &lines[line][..key_end], yaml_scalar(value));
+                    lines[line] = new_line;
+                }
+                None => {
+                    lines.remove(line);
+                }
+            }
+        }
+        (None, Some(value)) => {
+            // after the last line of the mapping, so it's in the same place as in the parsed yaml
+            let last = (start..end).rev().find(|i| is_content(&lines[*i]))?;
+            let new_line = format!("{}{}: {}", " ".repeat(indent), key, yaml_scalar(value));
+            lines.insert(last + 1, new_line);
+        }
+        (None, None) => {}
+    }
+    Some(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::os::unix::fs::PermissionsExt;
+    use tempdir::TempDir;
+
+    static KUBECONFIG: &str = r#"# written by hand
+apiVersion: v1
+contexts:
+- context:
+    cluster: prod
+    # the team's namespace
+    namespace: team-a
+    user: me
+  name: prod
+- name: dev
+  context:
+    cluster: dev
+    user: me
+users:
+- name: other
+  user:
+    token: abc
+- name: me
+  user:
+    auth-provider:
+      name: oidc
+      config:
+        client-id: click
+        id-token: old-id  # refreshed by click
+        refresh-token: old-refresh
+        extra-scopes:
+        - groups
+"#;
+
+    fn edited(edits: &[KubeconfigEdit]) -> Option<String> {
+        let text = edit_text(KUBECONFIG, edits)?;
+        let mut expected: Value = serde_yaml::from_str(KUBECONFIG).unwrap();
+        for edit in edits.iter() {
+            assert!(edit_value(&mut expected, edit));
+        }
+        assert!(same_yaml(&text, &expected), "{}", text);
+        Some(text)
+    }
+
+    fn namespace_edit<'a>(context: &'a str, namespace: Option<&'a str>) -> KubeconfigEdit<'a> {
+        KubeconfigEdit {
+            list: "contexts",
+            name: context,
+            keys: &["context", "namespace"],
+            value: namespace,
+        }
+    }
+
+    #[test]
+    fn edit_tokens_in_place() {
+        let keys = &["user", "auth-provider", "config", "id-token"];
+        let text = edited(&[KubeconfigEdit {
+            list: "users",
+            name: "me",
+            keys,
+            value: Some("new-id"),
+        }])
+        .unwrap();
+        assert!(text.starts_with("# written by hand\n"));
+        assert!(text.contains("\n        id-token: new-id\n        refresh-token: old-refresh\n"));
+        assert_eq!(text.lines().count(), KUBECONFIG.lines().count());
+    }
+
+    #[test]
+    fn edit_namespaces_in_place() {
+        let text = edited(&[namespace_edit("dev", Some("team-b"))]).unwrap();
+        assert!(text.contains("    user: me\n    namespace: team-b\nusers:"));
+        assert!(text.contains("    # the team's namespace\n"));
+
+        let text = edited(&[namespace_edit("prod", None)]).unwrap();
+        assert!(!text.contains("team-a"));
+        assert_eq!(text.lines().count(), KUBECONFIG.lines().count() - 1);
+
+        // values that wouldn't read back as strings are quoted
+        let text = edited(&[namespace_edit("prod", Some("true"))]).unwrap();
+        assert!(text.contains("    namespace: \"true\"\n"));
+    }
+
+    #[test]
+    fn edit_missing_entry() {
+        let mut kubeconfig: Value = serde_yaml::from_str(KUBECONFIG).unwrap();
+        assert!(!edit_value(
+            &mut kubeconfig,
+            &namespace_edit("nope", Some("ns"))
+        ));
+        assert!(edit_text(KUBECONFIG, &[namespace_edit("nope", Some("ns"))]).is_none());
+    }
+
+    #[test]
+    fn write_keeps_mode() {
+        let dir = TempDir::new("click-kubeedit-test").unwrap();
+        let path = dir.path().join("config");
+        let path = path.to_str().unwrap();
+        fs::write(path, KUBECONFIG).unwrap();
+        fs::set_permissions(path, fs::Permissions::from_mode(0o600)).unwrap();
+        edit_kubeconfig(path, &[namespace_edit("dev", Some("team-b"))]).unwrap();
+        let mode = fs::metadata(path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        let contents = fs::read_to_string(path).unwrap();
+        assert!(contents.starts_with("# written by hand\n"));
+        assert!(contents.contains("namespace: team-b"));
+    }
+}
@@ -19,7 +19,7 @@ use serde_json::{self, Value};
 
 use std::cell::RefCell;
 use std::fs::File;
-use std::io::Read;
+use std::io::{self, Read};
 
 use crate::error::ClickError;
 
@@ -32,9 +32,14 @@ use duct::cmd as ductcmd;
 /// Kubernetes cluster config
 #[derive(Debug, Deserialize)]
 pub struct Config {
+    #[serde(default)]
     pub clusters: Vec<Cluster>,
+    #[serde(default)]
     pub contexts: Vec<Context>,
+    #[serde(default)]
     pub users: Vec<User>,
+    #[serde(rename = "current-context")]
+    pub current_context: Option<String>,
 }
 
 impl Config {
@@ -72,6 +77,10 @@ pub struct ClusterConf {
     #[serde(rename = "insecure-skip-tls-verify", default = "default_false")]
     pub skip_tls: bool,
     pub server: String,
+    #[serde(rename = "tls-server-name")]
+    pub tls_server_name: Option<String>,
+    #[serde(rename = "proxy-url")]
+    pub proxy_url: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -118,23 +127,48 @@ pub struct ContextConf {
     pub cluster: String,
     pub namespace: Option<String>,
     pub user: String,
+    /// click only: the colour to show this context in (one of COLOUR_NAMES), so contexts like
+    /// prod stand out
+    pub color: Option<String>,
 }
 
 // Classes to hold deserialized data for auth
-#[derive(PartialEq, Debug, Deserialize, Clone)]
+#[derive(PartialEq, Debug, Default, Deserialize, Clone)]
 pub struct AuthProvider {
     name: String,
     pub token: RefCell<Option<String>>,
     pub expiry: RefCell<Option<DateTime<Local>>>,
     pub config: AuthProviderConfig,
+    /// the current oidc refresh token, which the identity provider can rotate
+    #[serde(skip)]
+    refresh_token: RefCell<Option<String>>,
+    /// the kubeconfig file and user this came from, so refreshed oidc tokens can be saved
+    #[serde(skip)]
+    origin: Option<(String, String)>,
 }
 
-#[derive(PartialEq, Debug, Deserialize, Clone)]
+#[derive(PartialEq, Debug, Default, Deserialize, Clone)]
 pub struct AuthProviderConfig {
     #[serde(rename = "access-token")]
     pub access_token: Option<String>,
     expiry: Option<String>,
 
+    // oidc
+    #[serde(rename = "id-token")]
+    pub id_token: Option<String>,
+    #[serde(rename = "refresh-token")]
+    pub refresh_token: Option<String>,
+    #[serde(rename = "client-id")]
+    pub client_id: Option<String>,
+    #[serde(rename = "client-secret")]
+    pub client_secret: Option<String>,
+    #[serde(rename = "idp-issuer-url")]
+    pub idp_issuer_url: Option<String>,
+    #[serde(rename = "idp-certificate-authority")]
+    pub idp_certificate_authority: Option<String>,
+    #[serde(rename = "idp-certificate-authority-data")]
+    pub idp_certificate_authority_data: Option<String>,
+
     #[serde(rename = "cmd-args")]
     cmd_args: Option<String>,
     #[serde(rename = "cmd-path")]
@@ -146,11 +180,27 @@ pub struct AuthProviderConfig {
 }
 
 impl AuthProvider {
+    /// Is this the oidc provider, which we refresh ourselves rather than by running a command
+    pub fn is_oidc(&self) -> bool {
+        self.name == "oidc"
+    }
+
+    /// Remember which kubeconfig file and user this came from
+    pub fn set_origin(&mut self, path: String, user: String) {
+        self.origin = Some((path, user));
+    }
+
     // Copy the token and expiry out of the config into the refcells
     pub fn copy_up(&self) {
         let mut token = self.token.borrow_mut();
-        *token = self.config.access_token.clone();
         let mut expiry = self.expiry.borrow_mut();
+        if self.is_oidc() {
+            *token = self.config.id_token.clone();
+            *expiry = token.as_deref().and_then(super::oidc::jwt_expiry);
+            *self.refresh_token.borrow_mut() = self.config.refresh_token.clone();
+            return;
+        }
+        *token = self.config.access_token.clone();
         if let Some(expiry_str) = &self.config.expiry {
             match AuthProvider::parse_expiry(expiry_str.as_str()) {
                 Ok(e) => *expiry = Some(e),
@@ -183,6 +233,8 @@ impl AuthProvider {
                 let now = Local::now();
                 e < now
             }
+            // id-tokens without an exp claim are used until the server rejects them
+            None if self.is_oidc() => false,
             None => {
                 eprintln!("No expiry, cannot validate if token is still valid, assuming expired");
                 true
@@ -252,7 +304,42 @@ impl AuthProvider {
         }
     }
 
+    /// Get a new id-token from the identity provider, and save it to the kubeconfig
+    fn refresh_oidc(&self, token: &mut Option<String>, expiry: &mut Option<DateTime<Local>>) {
+        let refresh_token = self.refresh_token.borrow().clone();
+        let refreshed = match refresh_token.as_deref() {
+            Some(refresh_token) => super::oidc::refresh(&self.config, refresh_token),
+            None => Err(ClickError::ConfigFileError(
+                "oidc auth-provider has no refresh-token, can't refresh".to_string(),
+            )),
+        };
+        match refreshed {
+            Ok(tokens) => {
+                *token = Some(tokens.id_token.clone());
+                *expiry = super::oidc::jwt_expiry(&tokens.id_token);
+                if let Some(new_refresh) = tokens.refresh_token.as_ref() {
+                    *self.refresh_token.borrow_mut() = Some(new_refresh.clone());
+                }
+                if let Some((path, user)) = self.origin.as_ref() {
+                    if let Err(e) = super::oidc::write_back(path, user, &tokens) {
+                        eprintln!("Could not save refreshed oidc token: {}", e);
+                    }
+                }
+            }
+            Err(e) => {
+                eprintln!(
+                    "Could not refresh oidc token: {}\nPlease log in again with your identity \
+                     provider's login tool (e.g. kubectl oidc-login), then retry the command",
+                    e
+                );
+            }
+        }
+    }
+
     fn update_token(&self, token: &mut Option<String>, expiry: &mut Option<DateTime<Local>>) {
+        if self.is_oidc() {
+            return self.refresh_oidc(token, expiry);
+        }
         match self.config.cmd_path {
             Some(ref conf_cmd) => {
                 let args = self
@@ -276,6 +363,27 @@ impl AuthProvider {
         }
     }
 
+    /// The command run to get a new token, if one is configured
+    pub fn command(&self) -> Option<&str> {
+        self.config.cmd_path.as_deref()
+    }
+
+    /// Forget the current token, so the next ensure_token runs the update command. Returns false
+    /// if there's no update command, in which case the token is kept since we can't get a new one
+    pub fn invalidate(&self) -> bool {
+        let can_refresh = if self.is_oidc() {
+            super::oidc::can_refresh(&self.config, self.refresh_token.borrow().as_deref())
+        } else {
+            self.config.cmd_path.is_some()
+        };
+        if can_refresh {
+            *self.token.borrow_mut() = None;
+            true
+        } else {
+            false
+        }
+    }
+
     /// Checks that we have a valid token, and if not, attempts to update it based on the config
     pub fn ensure_token(&self) -> Option<String> {
         let mut token = self.token.borrow_mut();
@@ -328,28 +436,84 @@ struct ExecResultStatus {
     pub client_key_data: Option<String>,
 }
 
+/// Env var exec plugins read to find out what we expect them to return
+const EXEC_INFO_ENV: &str = "KUBERNETES_EXEC_INFO";
+/// What we ask exec plugins for if the config doesn't say
+const DEFAULT_EXEC_API_VERSION: &str = "client.authentication.k8s.io/v1beta1";
+
 impl ExecConfig {
+    /// The command run to get credentials
+    pub fn command(&self) -> Option<&str> {
+        self.command.as_deref()
+    }
+
+    fn api_version(&self) -> &str {
+        self.api_version
+            .as_deref()
+            .unwrap_or(DEFAULT_EXEC_API_VERSION)
+    }
+
+    /// The ExecCredential passed to the plugin in KUBERNETES_EXEC_INFO
+    fn exec_info(&self) -> String {
+        serde_json::json!({
+            "apiVersion": self.api_version(),
+            "kind": "ExecCredential",
+            "spec": {
+                "interactive": false,
+            },
+        })
+        .to_string()
+    }
+
     fn exec(&self) -> Result<ExecResult, ClickError> {
-        match self.command {
-            Some(ref command) => {
-                let expr = if let Some(args) = &self.args {
-                    ductcmd(command, args)
-                } else {
-                    let args: Vec<String> = vec![];
-                    ductcmd(command, args)
-                };
-                let expr = if let Some(env) = &self.env {
-                    let env = env.iter().map(|nv| (&nv.name, &nv.value));
-                    expr.full_env(env)
+        let command = self.command.as_ref().ok_or_else(|| {
+            ClickError::ConfigFileError("No command specified in exec config".to_string())
+        })?;
+        let args: &[String] = self.args.as_deref().unwrap_or(&[]);
+        let mut expr = ductcmd(command, args).env(EXEC_INFO_ENV, self.exec_info());
+        // env from the config is added to our env, not used instead of it
+        for nv in self.env.iter().flatten() {
+            expr = expr.env(&nv.name, &nv.value);
+        }
+
+        let output = expr
+            .stdout_capture()
+            .stderr_capture()
+            .unchecked()
+            .run()
+            .map_err(|e| {
+                if e.kind() == io::ErrorKind::NotFound {
+                    ClickError::ConfigFileError(format!(
+                        "exec plugin {} not found, make sure it is installed and on your PATH",
+                        command
+                    ))
                 } else {
-                    expr
-                };
+                    ClickError::ConfigFileError(format!(
+                        "could not run exec plugin {}: {}",
+                        command, e
+                    ))
+                }
+            })?;
+        if !output.status.success() {
+            return Err(ClickError::ConfigFileError(format!(
+                "exec plugin {} failed ({}): {}",
+                command,
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
 
-                serde_json::from_reader(expr.reader()?).map_err(ClickError::from)
+        let result: ExecResult = serde_json::from_slice(&output.stdout)?;
+        match result.api_version.as_deref() {
+            Some(version) if version != self.api_version() => {
+                Err(ClickError::ConfigFileError(format!(
+                    "exec plugin {} returned an ExecCredential with apiVersion {}, expected {}",
+                    command,
+                    version,
+                    self.api_version()
+                )))
             }
-            None => Err(ClickError::ConfigFileError(
-                "No command specified in exec config".to_string(),
-            )),
+            _ => Ok(result),
         }
     }
 }
@@ -360,12 +524,6 @@ pub enum ExecAuth {
     ClientCertKey { cert_data: String, key_data: String },
 }
 
-impl ExecAuth {
-    fn default() -> ExecAuth {
-        ExecAuth::Token("".to_string())
-    }
-}
-
 #[derive(Clone, Debug)]
 pub struct ExecProvider {
     pub auth: RefCell<Option<ExecAuth>>,
@@ -393,54 +551,54 @@ impl ExecProvider {
         }
     }
 
-    fn update_auth(&self) {
-        match self.config.exec() {
-            Ok(result) => match result.status {
-                Some(status) => {
-                    if status.expiration.is_none() {
-                        eprintln!(
-                                "exec command returned no expiration. future commands will refetch token."
-                            );
-                    }
-                    if let Some(token) = status.token {
-                        *self.auth.borrow_mut() = Some(ExecAuth::Token(token));
-                    } else if let Some(cert_data) = status.client_certificate_data {
-                        let key_data = match status.client_key_data {
-                            Some(data) => data,
-                            None => {
-                                eprintln!("exec returned certificate but no key, can't auth.");
-                                return;
-                            }
-                        };
-                        *self.auth.borrow_mut() = Some(ExecAuth::ClientCertKey {
-                            cert_data,
-                            key_data,
-                        });
-                    }
-                    *self.expiry.borrow_mut() = status.expiration;
-                }
-                None => {
-                    eprintln!("No status block returned by exec, can't update auth");
+    fn update_auth(&self) -> Result<(), ClickError> {
+        let status = self.config.exec()?.status.ok_or_else(|| {
+            ClickError::ConfigFileError("No status block returned by exec plugin".to_string())
+        })?;
+        if status.expiration.is_none() {
+            eprintln!("exec command returned no expiration. future commands will refetch token.");
+        }
+        let auth = match (status.token, status.client_certificate_data) {
+            (Some(token), _) => ExecAuth::Token(token),
+            (None, Some(cert_data)) => {
+                let key_data = status.client_key_data.ok_or_else(|| {
+                    ClickError::ConfigFileError(
+                        "exec plugin returned a certificate but no key".to_string(),
+                    )
+                })?;
+                ExecAuth::ClientCertKey {
+                    cert_data,
+                    key_data,
                 }
-            },
-            Err(e) => {
-                println!("Error running specified exec command: {}", e);
             }
-        }
+            (None, None) => {
+                return Err(ClickError::ConfigFileError(
+                    "exec plugin returned neither a token nor a client certificate".to_string(),
+                ))
+            }
+        };
+        *self.auth.borrow_mut() = Some(auth);
+        *self.expiry.borrow_mut() = status.expiration;
+        Ok(())
+    }
+
+    /// Treat the current credentials as expired, so the next get_auth runs the plugin again
+    pub fn invalidate(&self) {
+        *self.expiry.borrow_mut() = None;
     }
 
-    pub fn get_auth(&self) -> (ExecAuth, bool) {
+    /// Get the current credentials, running the plugin if we don't have any or they have
+    /// expired. The returned bool is true if the plugin was run
+    pub fn get_auth(&self) -> Result<(ExecAuth, bool), ClickError> {
         let was_expired = if self.is_expired() {
-            self.update_auth();
+            self.update_auth()?;
             true
         } else {
             false
         };
-        // TODO: Fix kube.rs to be able to handle an option here
-        match &*self.auth.borrow() {
-            Some(auth) => (auth.clone(), was_expired),
-            None => (ExecAuth::default(), was_expired),
-        }
+        // update_auth either set auth or returned an error
+        let auth = self.auth.borrow().clone().unwrap();
+        Ok((auth, was_expired))
     }
 }
 
@@ -457,6 +615,8 @@ clusters:
 - cluster:
     certificate-authority: /absolute-path/ca.pem
     server: https://cluster2.foo:8443
+    tls-server-name: kubernetes.internal
+    proxy-url: http://proxy.foo:3128
   name: cluster2
 - cluster:
     insecure-skip-tls-verify: true
@@ -475,6 +635,7 @@ contexts:
 - context:
     cluster: cluster2
     user: c2user
+    color: red
   name: c2ctx
 current-context: c1ctx
 users:
@@ -524,6 +685,8 @@ users:
                     && c.conf.cert_data == cluster.conf.cert_data
                     && c.conf.skip_tls == cluster.conf.skip_tls
                     && c.conf.server == cluster.conf.server
+                    && c.conf.tls_server_name == cluster.conf.tls_server_name
+                    && c.conf.proxy_url == cluster.conf.proxy_url
                 {
                     return true;
                 }
@@ -538,6 +701,7 @@ users:
                 if c.conf.cluster == context.conf.cluster
                     && c.conf.user == context.conf.user
                     && c.conf.namespace == context.conf.namespace
+                    && c.conf.color == context.conf.color
                 {
                     return true;
                 }
@@ -582,6 +746,8 @@ users:
                     cert_data: Some("aGVsbG8K".to_string()),
                     skip_tls: false,
                     server: "http://nos.foo:80".to_string(),
+                    tls_server_name: None,
+                    proxy_url: None,
                 }
             }
         ));
@@ -594,6 +760,8 @@ users:
                     cert_data: None,
                     skip_tls: false,
                     server: "https://cluster1.test:443".to_string(),
+                    tls_server_name: None,
+                    proxy_url: None,
                 }
             }
         ));
@@ -606,6 +774,8 @@ users:
                     cert_data: None,
                     skip_tls: false,
                     server: "https://cluster2.foo:8443".to_string(),
+                    tls_server_name: Some("kubernetes.internal".to_string()),
+                    proxy_url: Some("http://proxy.foo:3128".to_string()),
                 }
             }
         ));
@@ -618,6 +788,8 @@ users:
                     cert_data: None,
                     skip_tls: true,
                     server: "https://insecure.blah".to_string(),
+                    tls_server_name: None,
+                    proxy_url: None,
                 }
             }
         ));
@@ -629,6 +801,7 @@ users:
                     cluster: "cluster1".to_string(),
                     user: "c1user".to_string(),
                     namespace: Some("ns1".to_string()),
+                    color: None,
                 }
             }
         ));
@@ -640,6 +813,7 @@ users:
                     cluster: "cluster2".to_string(),
                     user: "c2user".to_string(),
                     namespace: None,
+                    color: Some("red".to_string()),
                 }
             }
         ));
@@ -747,7 +921,9 @@ users:
                 cmd_path: None,
                 expiry_key: Some("{.credential.token_expiry}".to_string()),
                 token_key: Some("{.credential.access_token}".to_string()),
+                ..Default::default()
             },
+            ..Default::default()
         };
         {
             // scope for token/expiry borrow
@@ -801,7 +977,9 @@ users:
                 cmd_path: None,
                 expiry_key: None,
                 token_key: None,
+                ..Default::default()
             },
+            ..Default::default()
         };
         ap.copy_up();
         assert_eq!(ap.token, RefCell::new(Some("CTOKEN".to_string())));
@@ -848,7 +1026,9 @@ users:
                 cmd_path: None,
                 expiry_key: None,
                 token_key: None,
+                ..Default::default()
             },
+            ..Default::default()
         };
         assert!(ap.is_expired());
 
@@ -863,7 +1043,9 @@ users:
                 cmd_path: None,
                 expiry_key: None,
                 token_key: None,
+                ..Default::default()
             },
+            ..Default::default()
         };
         assert!(!ap.is_expired());
     }
@@ -879,7 +1061,7 @@ users:
             config: exec_config.unwrap().conf.exec.as_ref().unwrap().clone(),
         };
 
-        let (auth, was_expired) = provider.get_auth();
+        let (auth, was_expired) = provider.get_auth().unwrap();
         assert!(was_expired);
         assert_eq!(auth, ExecAuth::Token("testtoken".to_string()));
     }
@@ -895,8 +1077,34 @@ users:
             config: exec_config.unwrap().conf.exec.as_ref().unwrap().clone(),
         };
 
-        let (auth, was_expired) = provider.get_auth();
+        let (auth, was_expired) = provider.get_auth().unwrap();
         assert!(was_expired);
         assert_eq!(auth, ExecAuth::Token("testtoken".to_string()));
     }
+
+    #[test]
+    fn exec_errors() {
+        let config = |command: &str| ExecConfig {
+            command: Some(command.to_string()),
+            args: None,
+            env: None,
+            api_version: None,
+        };
+
+        let provider = ExecProvider::new(config("not-installed"));
+        match provider.get_auth() {
+            Err(ClickError::ConfigFileError(msg)) => assert!(msg.contains("not found")),
+            other => panic!("expected a not found error, got {:?}", other),
+        }
+
+        let provider = ExecProvider::new(config("failing-plugin"));
+        match provider.get_auth() {
+            Err(ClickError::ConfigFileError(msg)) => {
+                assert!(msg.contains("failed"));
+                assert!(msg.contains("could not get token"));
+            }
+            other => panic!("expected a failed error, got {:?}", other),
+        }
+        assert!(provider.auth.borrow().is_none());
+    }
 }
@@ -14,16 +14,24 @@
 
 mod click;
 mod kube;
+mod kubeedit;
 mod kubefile;
+mod oidc;
 
+pub use self::click::expand_env_vars;
 pub use self::click::Alias;
+pub use self::click::Bookmark;
 pub use self::click::ClickConfig;
 pub use self::click::CompletionType;
 pub use self::click::EditMode;
+pub use self::click::HistoryMode;
+pub use self::click::NamespaceCheck;
+pub use self::click::{parse_colour, parse_prompt_template, PromptPart, COLOUR_NAMES};
 
 #[cfg(test)]
 pub use self::kube::tests::get_test_config;
-pub use self::kube::Config;
+pub use self::kube::{ClusterConf, UserConf};
+pub use self::kube::{Config, ConfigSource};
 
 pub use self::kubefile::AuthProvider;
 pub use self::kubefile::ContextConf;
@@ -0,0 +1,228 @@
+// Copyright 2021 Databricks, Inc.
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+
+// http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Support for the `oidc` auth-provider. The id-token from the kubeconfig is used as the bearer
+//! token, and when it expires the refresh-token is exchanged for a new one at the token endpoint
+//! of the identity provider. Like kubectl, the new tokens are written back to the kubeconfig so
+//! other tools (and the next click session) can use them.
+
+use chrono::{DateTime, Local, TimeZone};
+use serde_json::Value;
+
+use std::time::Duration;
+
+use super::kubeedit::{edit_kubeconfig, KubeconfigEdit};
+use super::kubefile::AuthProviderConfig;
+use crate::error::ClickError;
+
+const OIDC_TIMEOUT_SECS: u64 = 20;
+
+/// The tokens the identity provider gave us on refresh. Some providers rotate the refresh token,
+/// others keep using the old one, in which case refresh_token is None
+#[derive(Debug, PartialEq)]
+pub struct OidcTokens {
+    pub id_token: String,
+    pub refresh_token: Option<String>,
+}
+
+/// Get the expiry (the exp claim) of a JWT. We don't verify the signature, the api server does
+/// that, we just want to know when to refresh
+pub fn jwt_expiry(token: &str) -> Option<DateTime<Local>> {
+    let payload = token.split('.').nth(1)?;
+    let decoded =
+        base64::decode_config(payload.trim_end_matches('='), base64::URL_SAFE_NO_PAD).ok()?;
+    let claims: Value = serde_json::from_slice(&decoded).ok()?;
+    let exp = claims.get("exp")?.as_i64()?;
+    Some(Local.timestamp(exp, 0))
+}
+
+/// Do we have everything needed to get a new id-token
+pub fn can_refresh(config: &AuthProviderConfig, refresh_token: Option<&str>) -> bool {
+    refresh_token.is_some() && config.client_id.is_some() && config.idp_issuer_url.is_some()
+}
+
+fn missing(what: &str) -> ClickError {
+    ClickError::ConfigFileError(format!("oidc auth-provider has no {}, can't refresh", what))
+}
+
+fn oidc_client(config: &AuthProviderConfig) -> Result<reqwest::blocking::Client, ClickError> {
+    let builder =
+        reqwest::blocking::Client::builder().timeout(Duration::from_secs(OIDC_TIMEOUT_SECS));
+    let ca_pem = match (
+        config.idp_certificate_authority_data.as_ref(),
+        config.idp_certificate_authority.as_ref(),
+    ) {
+        (Some(data), _) => Some(base64::decode(data)?),
+        (None, Some(path)) => Some(std::fs::read(path)?),
+        (None, None) => None,
+    };
+    let builder = match ca_pem {
+        Some(pem) => builder.add_root_certificate(reqwest::Certificate::from_pem(&pem)?),
+        None => builder,
+    };
+    Ok(builder.build()?)
+}
+
+/// Exchange refresh_token for a new id-token, using the token endpoint from the issuer's discovery
+/// document
+pub fn refresh(config: &AuthProviderConfig, refresh_token: &str) -> Result<OidcTokens, ClickError> {
+    let issuer = config
+        .idp_issuer_url
+        .as_ref()
+        .ok_or_else(|| missing("idp-issuer-url"))?;
+    let client_id = config
+        .client_id
+        .as_ref()
+        .ok_or_else(|| missing("client-id"))?;
+    let client = oidc_client(config)?;
+
+    let discovery_url = format!(
+        "{}/.well-known/openid-configuration",
+        issuer.trim_end_matches('/')
+    );
+    let discovery: Value = client
+        .get(&discovery_url)
+        .send()?
+        .error_for_status()?
+        .json()?;
+    let token_endpoint = discovery
+        .get("token_endpoint")
+        .and_then(|e| e.as_str())
+        .ok_or_else(|| {
+            ClickError::ConfigFileError(format!("No token_endpoint in {}", discovery_url))
+        })?;
+
+    let mut form = vec![
+        ("grant_type", "refresh_token"),
+        ("refresh_token", refresh_token),
+        ("client_id", client_id.as_str()),
+    ];
+    if let Some(secret) = config.client_secret.as_ref() {
+        form.push(("client_secret", secret.as_str()));
+    }
+    let response = client.post(token_endpoint).form(&form).send()?;
+    if !response.status().is_success() {
+        let status = response.status();
+        let body: Value = response.json().unwrap_or(Value::Null);
+        let reason = body
+            .get("error_description")
+            .or_else(|| body.get("error"))
+            .and_then(|e| e.as_str())
+            .unwrap_or("<no error description>");
+        return Err(ClickError::ConfigFileError(format!(
+            "Token refresh rejected by {} ({}): {}",
+            token_endpoint, status, reason
+        )));
+    }
+    let body: Value = response.json()?;
+    let id_token = body
+        .get("id_token")
+        .and_then(|t| t.as_str())
+        .ok_or_else(|| {
+            ClickError::ConfigFileError("Token endpoint didn't return an id_token".to_string())
+        })?;
+    Ok(OidcTokens {
+        id_token: id_token.to_string(),
+        refresh_token: body
+            .get("refresh_token")
+            .and_then(|t| t.as_str())
+            .map(|t| t.to_string()),
+    })
+}
+
+const ID_TOKEN_KEYS: &[&str] = &["user", "auth-provider", "config", "id-token"];
+const REFRESH_TOKEN_KEYS: &[&str] = &["user", "auth-provider", "config", "refresh-token"];
+
+/// Write the new tokens for user back to the kubeconfig at path. The refresh token is left alone if
+/// there isn't a new one
+pub fn write_back(path: &str, user: &str, tokens: &OidcTokens) -> Result<(), ClickError> {
+    let mut edits = vec![KubeconfigEdit {
+        list: "users",
+        name: user,
+        keys: ID_TOKEN_KEYS,
+        value: Some(&tokens.id_token),
+    }];
+    if let Some(refresh_token) = tokens.refresh_token.as_ref() {
+        edits.push(KubeconfigEdit {
+            list: "users",
+            name: user,
+            keys: REFRESH_TOKEN_KEYS,
+            value: Some(refresh_token),
+        });
+    }
+    edit_kubeconfig(path, &edits)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use serde_yaml::Value as YamlValue;
+    use tempdir::TempDir;
+
+    #[test]
+    fn expiry_from_jwt() {
+        // {"alg":"none"} . {"sub":"me","exp":1600000000} . no signature
+        let token = format!(
+            "{}.{}.",
+            base64::encode_config(r#"{"alg":"none"}"#, base64::URL_SAFE_NO_PAD),
+            base64::encode_config(r#"{"sub":"me","exp":1600000000}"#, base64::URL_SAFE_NO_PAD),
+        );
+        assert_eq!(jwt_expiry(&token), Some(Local.timestamp(1600000000, 0)));
+        assert_eq!(jwt_expiry("not-a-jwt"), None);
+        assert_eq!(jwt_expiry("a.b.c"), None);
+    }
+
+    #[test]
+    fn update_kubeconfig_tokens() {
+        let dir = TempDir::new("click-oidc-test").unwrap();
+        let path = dir.path().join("config");
+        let path = path.to_str().unwrap();
+        std::fs::write(
+            path,
+            r"# my clusters
+users:
+- name: other
+  user:
+    token: abc
+- name: me
+  user:
+    auth-provider:
+      name: oidc
+      config:
+        client-id: click
+        id-token: old-id
+        refresh-token: old-refresh
+        idp-issuer-url: https://issuer.example.com
+",
+        )
+        .unwrap();
+        let tokens = OidcTokens {
+            id_token: "new-id".to_string(),
+            refresh_token: None,
+        };
+        write_back(path, "me", &tokens).unwrap();
+        let contents = std::fs::read_to_string(path).unwrap();
+        assert!(contents.starts_with("# my clusters\n"));
+        let kubeconfig: YamlValue = serde_yaml::from_str(&contents).unwrap();
+        let config = &kubeconfig["users"][1]["user"]["auth-provider"]["config"];
+        assert_eq!(config["id-token"].as_str(), Some("new-id"));
+        // no new refresh token, so the old one is kept
+        assert_eq!(config["refresh-token"].as_str(), Some("old-refresh"));
+        assert_eq!(config["client-id"].as_str(), Some("click"));
+
+        assert!(write_back(path, "other", &tokens).is_err());
+        assert!(write_back(path, "nobody", &tokens).is_err());
+    }
+}
@@ -42,7 +42,12 @@ pub fn get_api_group_resources(
     ),
     RequestError,
 > {
-    let url = format!("/apis/{}", group_version);
+    // the core group lives under /api, everything else under /apis
+    let url = if group_version == "v1" {
+        "/api/v1".to_string()
+    } else {
+        format!("/apis/{}", group_version)
+    };
     let request = Request::get(url);
     let body = vec![];
     match request.body(body) {
@@ -51,6 +56,30 @@ pub fn get_api_group_resources(
     }
 }
 
+/// Find the first of `versions` that the server serves `resource` from. Used for types that have
+/// moved between api groups, where we have to ask the server which one it knows about
+pub fn preferred_api_version(
+    env: &Env,
+    versions: &[&'static str],
+    resource: &str,
+) -> Option<&'static str> {
+    for version in versions.iter() {
+        let (request, _) = match get_api_group_resources(version) {
+            Ok(req) => req,
+            Err(_) => continue,
+        };
+        // servers that don't have the version respond with a 404, which ends up as Other
+        if let Ok(GetAPIGroupResourcesResponse::Ok(resources)) =
+            env.run_on_context::<_, GetAPIGroupResourcesResponse>(|c| c.read(request))
+        {
+            if resources.resources.iter().any(|r| r.name == resource) {
+                return Some(version);
+            }
+        }
+    }
+    None
+}
+
 #[derive(Debug)]
 pub enum GetAPIGroupResourcesResponse {
     Ok(APIResourceList),
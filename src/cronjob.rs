@@ -0,0 +1,232 @@
+// Copyright 2021 Databricks, Inc.
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+
+// http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! CronJobs went GA in batch/v1, which the version of k8s_openapi we use doesn't know about. The
+//! shape is the same as batch/v1beta1 though, so we have our own CronJob type that reuses the
+//! v1beta1 spec and status, and use discovery to decide which version to ask the server for
+
+use k8s_openapi::api::batch::v1beta1::{CronJobSpec, CronJobStatus};
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::ObjectMeta;
+use k8s_openapi::http::{self, Request};
+use k8s_openapi::{ListableResource, Metadata, NamespaceResourceScope, Resource};
+use serde::ser::{Serialize, SerializeStruct, Serializer};
+use serde_json::{json, Value};
+
+use crate::env::Env;
+use crate::error::ClickError;
+
+use std::marker::PhantomData;
+
+/// An api group/version that can serve cronjobs
+pub trait CronJobApi {
+    const API_VERSION: &'static str;
+    const VERSION: &'static str;
+}
+
+#[derive(Debug)]
+pub struct BatchV1;
+impl CronJobApi for BatchV1 {
+    const API_VERSION: &'static str = "batch/v1";
+    const VERSION: &'static str = "v1";
+}
+
+#[derive(Debug)]
+pub struct BatchV1Beta1;
+impl CronJobApi for BatchV1Beta1 {
+    const API_VERSION: &'static str = "batch/v1beta1";
+    const VERSION: &'static str = "v1beta1";
+}
+
+/// The api versions we know how to list cronjobs from, most preferred first
+pub const CRONJOB_API_VERSIONS: &[&str] = &[BatchV1::API_VERSION, BatchV1Beta1::API_VERSION];
+
+/// The annotation kubectl puts on jobs created by hand from a cronjob
+const INSTANTIATE_ANNOTATION: &str = "cronjob.kubernetes.io/instantiate";
+
+/// A cronjob from either of the api versions
+#[derive(Debug, Deserialize)]
+#[serde(bound = "")]
+pub struct CronJob<V> {
+    pub metadata: ObjectMeta,
+    #[serde(default)]
+    pub spec: Option<CronJobSpec>,
+    #[serde(default)]
+    pub status: Option<CronJobStatus>,
+    #[serde(skip)]
+    api: PhantomData<V>,
+}
+
+impl<V: CronJobApi> Resource for CronJob<V> {
+    const API_VERSION: &'static str = V::API_VERSION;
+    const GROUP: &'static str = "batch";
+    const KIND: &'static str = "CronJob";
+    const VERSION: &'static str = V::VERSION;
+    const URL_PATH_SEGMENT: &'static str = "cronjobs";
+    type Scope = NamespaceResourceScope;
+}
+
+impl<V: CronJobApi> ListableResource for CronJob<V> {
+    const LIST_KIND: &'static str = "CronJobList";
+}
+
+impl<V: CronJobApi> Metadata for CronJob<V> {
+    type Ty = ObjectMeta;
+
+    fn metadata(&self) -> &ObjectMeta {
+        &self.metadata
+    }
+
+    fn metadata_mut(&mut self) -> &mut ObjectMeta {
+        &mut self.metadata
+    }
+}
+
+impl<V: CronJobApi> Serialize for CronJob<V> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("CronJob", 5)?;
+        state.serialize_field("apiVersion", V::API_VERSION)?;
+        state.serialize_field("kind", "CronJob")?;
+        state.serialize_field("metadata", &self.metadata)?;
+        if let Some(spec) = self.spec.as_ref() {
+            state.serialize_field("spec", spec)?;
+        }
+        if let Some(status) = self.status.as_ref() {
+            state.serialize_field("status", status)?;
+        }
+        state.end()
+    }
+}
+
+/// Find the most preferred api version the server can give us cronjobs from
+pub fn cronjob_api_version(env: &Env) -> Result<&'static str, ClickError> {
+    crate::crd::preferred_api_version(env, CRONJOB_API_VERSIONS, "cronjobs").ok_or_else(|| {
+        ClickError::CommandError(
+            "Cluster doesn't serve cronjobs from any api version click knows about".to_string(),
+        )
+    })
+}
+
+/// Build a request to delete the named cronjob via the specified api version
+pub fn delete_namespaced_cronjob(
+    group_version: &str,
+    name: &str,
+    namespace: &str,
+    optional: k8s_openapi::DeleteOptional<'_>,
+) -> Result<Request<Vec<u8>>, ClickError> {
+    let url = format!(
+        "/apis/{}/namespaces/{}/cronjobs/{}",
+        group_version, namespace, name
+    );
+    let body = serde_json::to_vec(&optional)?;
+    Request::delete(url)
+        .header(http::header::CONTENT_TYPE, "application/json")
+        .body(body)
+        .map_err(|e| ClickError::CommandError(format!("Could not build delete request: {}", e)))
+}
+
+/// Build a Job from the template of cronjob (as returned by the api server), like `kubectl create
+/// job --from=cronjob/...` does. The job is owned by the cronjob so it gets cleaned up with it
+pub fn job_from_cronjob(cronjob: &Value, job_name: &str) -> Result<Value, ClickError> {
+    let template = cronjob
+        .pointer("/spec/jobTemplate")
+        .ok_or_else(|| ClickError::CommandError("CronJob has no job template".to_string()))?;
+    let mut annotations = template
+        .pointer("/metadata/annotations")
+        .cloned()
+        .unwrap_or_else(|| json!({}));
+    if let Some(annotations) = annotations.as_object_mut() {
+        annotations.insert(INSTANTIATE_ANNOTATION.to_string(), json!("manual"));
+    }
+    let mut metadata = json!({
+        "name": job_name,
+        "annotations": annotations,
+        "ownerReferences": [{
+            "apiVersion": cronjob["apiVersion"],
+            "kind": "CronJob",
+            "name": cronjob.pointer("/metadata/name"),
+            "uid": cronjob.pointer("/metadata/uid"),
+            "controller": true,
+        }],
+    });
+    if let Some(labels) = template.pointer("/metadata/labels") {
+        metadata["labels"] = labels.clone();
+    }
+    if let Some(namespace) = cronjob.pointer("/metadata/namespace") {
+        metadata["namespace"] = namespace.clone();
+    }
+    Ok(json!({
+        "apiVersion": "batch/v1",
+        "kind": "Job",
+        "metadata": metadata,
+        "spec": template.get("spec").cloned().unwrap_or_else(|| json!({})),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deserialize_v1() {
+        let cronjob: CronJob<BatchV1> = serde_json::from_value(json!({
+            "metadata": { "name": "nightly", "namespace": "default" },
+            "spec": {
+                "schedule": "0 0 * * *",
+                "suspend": true,
+                "jobTemplate": { "spec": { "template": { "spec": { "containers": [] } } } },
+            },
+            "status": {
+                "active": [{ "name": "nightly-1234" }],
+                "lastScheduleTime": "2021-06-01T00:00:00Z",
+                "lastSuccessfulTime": "2021-06-01T00:05:00Z",
+            },
+        }))
+        .unwrap();
+        let spec = cronjob.spec.as_ref().unwrap();
+        assert_eq!(spec.schedule, "0 0 * * *");
+        assert_eq!(spec.suspend, Some(true));
+        let status = cronjob.status.as_ref().unwrap();
+        assert_eq!(status.active.len(), 1);
+        assert!(status.last_schedule_time.is_some());
+    }
+
+    #[test]
+    fn job_from_template() {
+        let cronjob = json!({
+            "apiVersion": "batch/v1",
+            "kind": "CronJob",
+            "metadata": { "name": "nightly", "namespace": "jobs", "uid": "abc-123" },
+            "spec": {
+                "schedule": "0 0 * * *",
+                "jobTemplate": {
+                    "metadata": { "labels": { "app": "nightly" } },
+                    "spec": { "backoffLimit": 2 },
+                },
+            },
+        });
+        let job = job_from_cronjob(&cronjob, "nightly-manual-1").unwrap();
+        assert_eq!(job["kind"], "Job");
+        assert_eq!(job["metadata"]["name"], "nightly-manual-1");
+        assert_eq!(job["metadata"]["namespace"], "jobs");
+        assert_eq!(job["metadata"]["labels"]["app"], "nightly");
+        assert_eq!(
+            job["metadata"]["annotations"][INSTANTIATE_ANNOTATION],
+            "manual"
+        );
+        assert_eq!(job["metadata"]["ownerReferences"][0]["uid"], "abc-123");
+        assert_eq!(job["spec"]["backoffLimit"], 2);
+
+        assert!(job_from_cronjob(&json!({ "metadata": {} }), "x").is_err());
+    }
+}
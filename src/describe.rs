@@ -15,7 +15,10 @@
 //!  Utility functions for the Describe command, used to output
 //!  information for supported kubernetes object types
 
-use crate::values::{val_str, val_str_opt, val_u64};
+use crate::command::deployments::REVISION_ANNOTATION;
+use crate::ingress::{Ingress, IngressApi, ServiceHealth};
+use crate::table::{format_duration, time_since};
+use crate::values::{format_cpu, format_memory, parse_quantity, val_str, val_str_opt, val_u64};
 
 use ansi_term::Colour;
 use chrono::offset::Local;
@@ -24,6 +27,7 @@ use chrono::DateTime;
 use serde_json::Value;
 
 use std::borrow::Cow;
+use std::collections::HashMap;
 use std::fmt::Write;
 use std::str::{self, FromStr};
 
@@ -89,7 +93,14 @@ fn keyval_str<'a>(v: &'a Value, parent: &str, secret_vals: bool) -> Cow<'a, str>
                     } else if secret_vals {
                         outstr.push_str(":\t");
                         match ::base64::decode(keyvals.get(key).unwrap().as_str().unwrap()) {
-                            Ok(dec) => outstr.push_str(format!("{} bytes", dec.len()).as_str()),
+                            Ok(dec) => {
+                                let binary = if str::from_utf8(&dec).is_ok() {
+                                    ""
+                                } else {
+                                    ", binary"
+                                };
+                                outstr.push_str(format!("{} bytes{}", dec.len(), binary).as_str())
+                            }
                             Err(_) => outstr.push_str("Could not decode secret"),
                         }
                     } else {
@@ -155,7 +166,7 @@ where
 
 /// Utility function for describe to print out value
 pub fn describe_format_pod(v: Value) -> String {
-    let fields = vec![
+    let mut fields = vec![
         (
             "Name:\t\t",
             DescItem::MetadataValStr {
@@ -193,6 +204,14 @@ pub fn describe_format_pod(v: Value) -> String {
                 default: "<No Phase>",
             },
         ),
+        (
+            "Containers:\n",
+            DescItem::CustomFunc {
+                path: None,
+                func: &pod_containers,
+                default: "",
+            },
+        ),
         (
             "Labels:\t",
             DescItem::KeyValStr {
@@ -216,9 +235,159 @@ pub fn describe_format_pod(v: Value) -> String {
             },
         ),
     ];
+    if v.pointer("/spec/initContainers").is_some() {
+        // before the regular containers, since they run first
+        fields.insert(
+            6,
+            (
+                "Init Containers:\n",
+                DescItem::CustomFunc {
+                    path: None,
+                    func: &pod_init_containers,
+                    default: "",
+                },
+            ),
+        );
+    }
     describe_object(&v, fields.into_iter())
 }
 
+/// Reasons a container can be waiting that mean something is wrong, rather than that it's just
+/// starting up
+const FAILED_WAITING_REASONS: &[&str] = &[
+    "CrashLoopBackOff",
+    "ErrImagePull",
+    "ImagePullBackOff",
+    "InvalidImageName",
+    "CreateContainerConfigError",
+    "CreateContainerError",
+    "RunContainerError",
+];
+
+/// A container state (running, waiting or terminated) and its details, like kubectl shows them.
+/// Crash reasons and non-zero exit codes are red
+fn container_state_str(title: &str, state: &Value) -> String {
+    let mut buf = String::new();
+    if let Some(running) = state.get("running") {
+        writeln!(buf, "    {}\tRunning", title).unwrap();
+        writeln!(
+            buf,
+            "      Started:\t{}",
+            val_str("/startedAt", running, "<unknown>")
+        )
+        .unwrap();
+    } else if let Some(waiting) = state.get("waiting") {
+        writeln!(buf, "    {}\tWaiting", title).unwrap();
+        let reason = val_str("/reason", waiting, "<none>");
+        if FAILED_WAITING_REASONS.contains(&&*reason) {
+            writeln!(buf, "      Reason:\t{}", Colour::Red.paint(reason)).unwrap();
+        } else {
+            writeln!(buf, "      Reason:\t{}", reason).unwrap();
+        }
+        if let Some(msg) = val_str_opt("/message", waiting) {
+            writeln!(buf, "      Message:\t{}", msg).unwrap();
+        }
+    } else if let Some(terminated) = state.get("terminated") {
+        writeln!(buf, "    {}\tTerminated", title).unwrap();
+        let code = terminated
+            .get("exitCode")
+            .and_then(|c| c.as_i64())
+            .unwrap_or(0);
+        let reason = val_str("/reason", terminated, "<none>");
+        if code == 0 {
+            writeln!(buf, "      Reason:\t{}", reason).unwrap();
+            writeln!(buf, "      Exit Code:\t{}", code).unwrap();
+        } else {
+            writeln!(buf, "      Reason:\t{}", Colour::Red.paint(reason)).unwrap();
+            writeln!(
+                buf,
+                "      Exit Code:\t{}",
+                Colour::Red.paint(code.to_string())
+            )
+            .unwrap();
+        }
+        if let Some(signal) = terminated.get("signal").and_then(|s| s.as_i64()) {
+            writeln!(buf, "      Signal:\t{}", signal).unwrap();
+        }
+        if let Some(msg) = val_str_opt("/message", terminated) {
+            writeln!(buf, "      Message:\t{}", msg).unwrap();
+        }
+        writeln!(
+            buf,
+            "      Started:\t{}",
+            val_str("/startedAt", terminated, "<unknown>")
+        )
+        .unwrap();
+        writeln!(
+            buf,
+            "      Finished:\t{}",
+            val_str("/finishedAt", terminated, "<unknown>")
+        )
+        .unwrap();
+    }
+    buf
+}
+
+/// The containers (or init containers) in the spec of pod, with their images and, if the pod has
+/// a status for them, their current and last states, readiness and restarts
+fn pod_containers_str(pod: &Value, containers: &str, statuses: &str) -> String {
+    let mut buf = String::new();
+    let statuses: Vec<&Value> = pod
+        .pointer(statuses)
+        .and_then(|s| s.as_array())
+        .map(|s| s.iter().collect())
+        .unwrap_or_default();
+    let containers = match pod.pointer(containers).and_then(|c| c.as_array()) {
+        Some(containers) => containers,
+        None => return "  <none>".to_string(),
+    };
+    for container in containers.iter() {
+        let name = val_str("/name", container, "<No Name>");
+        writeln!(buf, "  {}:", name).unwrap();
+        writeln!(
+            buf,
+            "    Image:\t{}",
+            val_str("/image", container, "<No Image>")
+        )
+        .unwrap();
+        let status = statuses
+            .iter()
+            .find(|status| val_str("/name", status, "") == name);
+        if let Some(status) = status {
+            if let Some(state) = status.get("state") {
+                buf.push_str(&container_state_str("State:\t", state));
+            }
+            if let Some(state) = status
+                .get("lastState")
+                .filter(|s| s.as_object().map(|o| !o.is_empty()).unwrap_or(false))
+            {
+                buf.push_str(&container_state_str("Last State:", state));
+            }
+            let ready = status
+                .get("ready")
+                .and_then(|r| r.as_bool())
+                .unwrap_or(false);
+            writeln!(buf, "    Ready:\t{}", if ready { "True" } else { "False" }).unwrap();
+            writeln!(
+                buf,
+                "    Restart Count:\t{}",
+                val_u64("/restartCount", status, 0)
+            )
+            .unwrap();
+        }
+    }
+    // describe_object adds the final newline
+    buf.trim_end_matches('\n').to_string()
+}
+
+fn pod_containers(v: &Value) -> Cow<'_, str> {
+    pod_containers_str(v, "/spec/containers", "/status/containerStatuses").into()
+}
+
+fn pod_init_containers(v: &Value) -> Cow<'_, str> {
+    pod_containers_str(v, "/spec/initContainers", "/status/initContainerStatuses").into()
+}
+
 /// Get volume info out of volume array
 fn get_volume_str(v: &Value) -> Cow<str> {
     let mut buf = String::new();
@@ -293,8 +462,165 @@ fn pod_phase(v: &Value) -> Cow<str> {
     colour.paint(phase_str).to_string().into()
 }
 
-/// Utility function for describe to print out value
-pub fn describe_format_node(v: Value) -> String {
+/// Format the taints of a node as key=value:Effect, one per line
+fn node_taints(v: &Value) -> Cow<str> {
+    let taints: Vec<String> = v
+        .as_array()
+        .map(|taints| {
+            taints
+                .iter()
+                .map(|taint| {
+                    let key = val_str("/key", taint, "");
+                    let effect = val_str("/effect", taint, "");
+                    match val_str_opt("/value", taint) {
+                        Some(value) => format!("{}={}:{}", key, value, effect),
+                        None => format!("{}:{}", key, effect),
+                    }
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    if taints.is_empty() {
+        "<none>".into()
+    } else {
+        taints.join("\n\t\t").into()
+    }
+}
+
+/// Format node conditions, coloring the ones that indicate a problem
+fn node_conditions(v: &Value) -> Cow<str> {
+    let mut buf = String::new();
+    for cond in v.as_array().into_iter().flatten() {
+        let typ = val_str("/type", cond, "<unknown>");
+        let status = val_str("/status", cond, "Unknown");
+        // Ready should be True, the pressure and unavailable conditions False
+        let healthy = (typ == "Ready") == (status == "True");
+        let status = if healthy {
+            Colour::Green.paint(status)
+        } else {
+            Colour::Red.paint(status)
+        };
+        write!(
+            buf,
+            "\n  {}:\t{}\t{}",
+            typ,
+            status,
+            val_str("/message", cond, "")
+        )
+        .unwrap();
+    }
+    if buf.is_empty() {
+        "<none>".into()
+    } else {
+        buf.into()
+    }
+}
+
+/// Format the cpu, memory, and pods quantities of a node's capacity or allocatable
+fn node_resources(v: &Value) -> Cow<str> {
+    format!(
+        "cpu: {}, memory: {}, pods: {}",
+        val_str("/cpu", v, "<unknown>"),
+        val_str("/memory", v, "<unknown>"),
+        val_str("/pods", v, "<unknown>"),
+    )
+    .into()
+}
+
+/// Sum the requests of resource over the containers (or init containers) of a pod spec
+fn sum_requests(spec: &Value, containers: &str, resource: &str) -> Vec<f64> {
+    spec.get(containers)
+        .and_then(|c| c.as_array())
+        .into_iter()
+        .flatten()
+        .map(|cont| {
+            cont.pointer(&format!("/resources/requests/{}", resource))
+                .and_then(|q| q.as_str())
+                .and_then(parse_quantity)
+                .unwrap_or(0.0)
+        })
+        .collect()
+}
+
+/// The effective request of a pod for resource. Like the scheduler, this is the sum over its
+/// containers, or the largest init container request if that's bigger, since those run one at a
+/// time
+fn pod_request(pod: &Value, resource: &str) -> f64 {
+    let spec = match pod.get("spec") {
+        Some(spec) => spec,
+        None => return 0.0,
+    };
+    let containers: f64 = sum_requests(spec, "containers", resource).iter().sum();
+    let init = sum_requests(spec, "initContainers", resource)
+        .into_iter()
+        .fold(0.0, f64::max);
+    containers.max(init)
+}
+
+fn percent_of(used: f64, available: Option<f64>) -> String {
+    match available {
+        Some(available) if available > 0.0 => format!("{}%", (used / available * 100.0).round()),
+        _ => "<unknown>".to_string(),
+    }
+}
+
+/// The pods running on a node with their requests, and the total requested as a part of what's
+/// allocatable
+fn node_pods_summary(node: &Value, pods: &Value) -> String {
+    let mut buf = String::new();
+    let pods = pods
+        .get("items")
+        .and_then(|items| items.as_array())
+        .map(|items| items.as_slice())
+        .unwrap_or_default();
+    let (mut total_cpu, mut total_mem) = (0.0, 0.0);
+    writeln!(buf, "Non-terminated Pods:\t({} in total)", pods.len()).unwrap();
+    if !pods.is_empty() {
+        writeln!(buf, "  Namespace/Name\tCPU Requests\tMemory Requests").unwrap();
+    }
+    for pod in pods.iter() {
+        let cpu = pod_request(pod, "cpu");
+        let mem = pod_request(pod, "memory");
+        total_cpu += cpu;
+        total_mem += mem;
+        writeln!(
+            buf,
+            "  {}/{}\t{}\t{}",
+            val_str("/metadata/namespace", pod, "<none>"),
+            val_str("/metadata/name", pod, "<none>"),
+            format_cpu(cpu),
+            format_memory(mem)
+        )
+        .unwrap();
+    }
+    let allocatable = |resource: &str| {
+        node.pointer(&format!("/status/allocatable/{}", resource))
+            .and_then(|q| q.as_str())
+            .and_then(parse_quantity)
+    };
+    writeln!(buf, "Allocated resources:").unwrap();
+    writeln!(
+        buf,
+        "  CPU Requests:\t{} ({} of {})",
+        format_cpu(total_cpu),
+        percent_of(total_cpu, allocatable("cpu")),
+        val_str("/status/allocatable/cpu", node, "<unknown>")
+    )
+    .unwrap();
+    write!(
+        buf,
+        "  Memory Requests:\t{} ({} of {})",
+        format_memory(total_mem),
+        percent_of(total_mem, allocatable("memory")),
+        val_str("/status/allocatable/memory", node, "<unknown>")
+    )
+    .unwrap();
+    buf
+}
+
+/// Utility function for describe to print out value. pods are the pods running on the node, if
+/// they could be listed
+pub fn describe_format_node(v: Value, pods: Option<Value>) -> String {
     let fields = vec![
         (
             "Name:\t\t",
@@ -333,6 +659,48 @@ pub fn describe_format_node(v: Value) -> String {
                 default: "<N/A>",
             },
         ),
+        (
+            "Taints:\t\t",
+            DescItem::CustomFunc {
+                path: Some("/spec/taints"),
+                func: &node_taints,
+                default: "<none>",
+            },
+        ),
+        (
+            "Unschedulable:\t",
+            DescItem::StaticStr(
+                v.pointer("/spec/unschedulable")
+                    .and_then(|u| u.as_bool())
+                    .unwrap_or(false)
+                    .to_string()
+                    .into(),
+            ),
+        ),
+        (
+            "Conditions:",
+            DescItem::CustomFunc {
+                path: Some("/status/conditions"),
+                func: &node_conditions,
+                default: "<none>",
+            },
+        ),
+        (
+            "Capacity:\t",
+            DescItem::CustomFunc {
+                path: Some("/status/capacity"),
+                func: &node_resources,
+                default: "<unknown>",
+            },
+        ),
+        (
+            "Allocatable:\t",
+            DescItem::CustomFunc {
+                path: Some("/status/allocatable"),
+                func: &node_resources,
+                default: "<unknown>",
+            },
+        ),
         (
             "\nSystem Info:",
             DescItem::KeyValStr {
@@ -341,7 +709,12 @@ pub fn describe_format_node(v: Value) -> String {
             },
         ),
     ];
-    describe_object(&v, fields.into_iter())
+    let mut desc = describe_object(&v, fields.into_iter());
+    match pods {
+        Some(pods) => desc.push_str(&node_pods_summary(&v, &pods)),
+        None => desc.push_str("Non-terminated Pods:\t<unable to list pods>"),
+    }
+    desc
 }
 
 fn node_access_url(v: &Value) -> Cow<str> {
@@ -382,7 +755,11 @@ fn node_access_url(v: &Value) -> Cow<str> {
 
 /// Utility function for describe to print service info
 pub fn describe_format_service(v: Value, endpoint_val: Option<Value>) -> String {
-    let port_str = get_ports_str(v.pointer("/spec/ports"), endpoint_val);
+    let port_str = get_ports_str(v.pointer("/spec/ports"), endpoint_val.as_ref());
+    let pods_str = endpoint_val
+        .as_ref()
+        .map(|ep| endpoint_pods_str(ep).into())
+        .unwrap_or_else(|| "\t<No Endpoints>".into());
     let fields = vec![
         (
             "Name:\t\t",
@@ -391,6 +768,13 @@ pub fn describe_format_service(v: Value, endpoint_val: Option<Value>) -> String
                 default: "<No Name>",
             },
         ),
+        (
+            "Namespace:\t",
+            DescItem::MetadataValStr {
+                path: "/namespace",
+                default: "<No Namespace>",
+            },
+        ),
         (
             "Labels:\t",
             DescItem::KeyValStr {
@@ -417,131 +801,352 @@ pub fn describe_format_service(v: Value, endpoint_val: Option<Value>) -> String
             "Type:\t\t",
             DescItem::ValStr {
                 path: "/spec/type",
-                default: "<No Type>",
+                default: "ClusterIP",
             },
         ),
         (
             "IP:\t\t",
-            DescItem::ValStr {
-                path: "/spec/clusterIP",
-                default: "<No Type>",
+            DescItem::CustomFunc {
+                path: Some("/spec/clusterIP"),
+                func: &|ip| match ip.as_str() {
+                    Some("None") => "None (headless)".into(),
+                    Some(ip) => ip.to_string().into(),
+                    None => "<none>".into(),
+                },
+                default: "<none>",
             },
         ),
         (
-            "LoadBalIngress:\t",
-            DescItem::ValStr {
-                path: "/status/loadBalancer/ingress/0/hostname",
-                default: "<No Ingress>",
+            "External IPs:\t",
+            DescItem::CustomFunc {
+                path: Some("/spec/externalIPs"),
+                func: &|ips| join_strs(ips, "<none>"),
+                default: "<none>",
             },
         ),
+        (
+            "LoadBalIngress:\t",
+            DescItem::StaticStr(load_balancer_ingress_str(&v)),
+        ),
         ("Ports:\n", DescItem::StaticStr(port_str)),
         (
-            "SessionAffnity:\t",
+            "SessionAffinity:\t",
             DescItem::ValStr {
-                path: "/spec/sessionAffnity",
+                path: "/spec/sessionAffinity",
                 default: "<none>",
             },
         ),
+        ("Backing Pods:", DescItem::StaticStr(pods_str)),
     ];
     describe_object(&v, fields.into_iter())
 }
 
-/// Get ports info out of ports array
-fn get_ports_str(v: Option<&Value>, endpoint_val: Option<Value>) -> Cow<str> {
-    if v.is_none() {
+/// Utility function to describe an ingress. health is what we found out about the services it sends
+/// traffic to, see Ingress::backend_tree
+pub fn describe_format_ingress<V: IngressApi>(
+    ingress: &Ingress<V>,
+    health: &HashMap<String, ServiceHealth>,
+) -> String {
+    let v = serde_json::to_value(ingress).unwrap(); // safe, we just deserialized it
+    let addresses = ingress.addresses();
+    let fields = vec![
+        (
+            "Name:\t\t",
+            DescItem::MetadataValStr {
+                path: "/name",
+                default: "<No Name>",
+            },
+        ),
+        (
+            "Namespace:\t",
+            DescItem::MetadataValStr {
+                path: "/namespace",
+                default: "<No Namespace>",
+            },
+        ),
+        (
+            "Labels:\t",
+            DescItem::KeyValStr {
+                parent: "/metadata/labels",
+                secret_vals: false,
+            },
+        ),
+        (
+            "Annotations:",
+            DescItem::KeyValStr {
+                parent: "/metadata/annotations",
+                secret_vals: false,
+            },
+        ),
+        ("Created at:\t", DescItem::ObjectCreated),
+        (
+            "Class:\t\t",
+            DescItem::StaticStr(ingress.class().unwrap_or("<none>").into()),
+        ),
+        (
+            "Address:\t",
+            DescItem::StaticStr(if addresses.is_empty() {
+                "<pending>".into()
+            } else {
+                addresses.join(", ").into()
+            }),
+        ),
+        ("Ports:\t\t", DescItem::StaticStr(ingress.ports().into())),
+        (
+            "Backends:\n",
+            DescItem::StaticStr(ingress.backend_tree(health).into()),
+        ),
+    ];
+    describe_object(&v, fields.into_iter())
+}
+
+/// Join an array of strings with ", ", or return default if there aren't any
+fn join_strs<'a>(v: &Value, default: &'a str) -> Cow<'a, str> {
+    let strs: Vec<&str> = v
+        .as_array()
+        .map(|arr| arr.iter().filter_map(|s| s.as_str()).collect())
+        .unwrap_or_default();
+    if strs.is_empty() {
+        default.into()
+    } else {
+        strs.join(", ").into()
+    }
+}
+
+/// The ips/hostnames a LoadBalancer service has been given. Until the cloud provider has set one
+/// up this is <pending>
+fn load_balancer_ingress_str(v: &Value) -> Cow<'static, str> {
+    if val_str("/spec/type", v, "") != "LoadBalancer" {
         return "<none>".into();
     }
-    let mut buf = String::new();
-    match v.unwrap().as_array() {
-        // safe unwrap, checked above
-        Some(port_array) => {
-            for port in port_array.iter() {
-                let proto = val_str("/protocol", port, "<Unknown>");
-                let name = val_str("/name", port, "<No Name>");
-                let port_num = val_u64("/port", port, 0);
-                let endpoints = match endpoint_val {
-                    Some(ref ep) => {
-                        // to get all the endpoints, we need to check all subsets this port is in
-                        // TODO: This is complex, simplify and/or abstract
-                        let mut epbuf = String::from_str("  Endpoints:\t").unwrap();
-                        let mut found_one = false;
-                        ep.pointer("/subsets").map(|s| {
-                            s.as_array().map(|subsets| {
-                                for subset in subsets.iter() {
-                                    // see if this subset has this port by checking if any port in
-                                    // the ports array has the same port number
-                                    let contains = subset
-                                        .pointer("/ports")
-                                        .map(|p| {
-                                            p.as_array()
-                                                .map(|ports_array| {
-                                                    let mut c = false;
-                                                    for port in ports_array.iter() {
-                                                        if port_num == val_u64("/port", port, 0) {
-                                                            c = true;
-                                                        }
-                                                    }
-                                                    c
-                                                })
-                                                .unwrap_or(false)
-                                        })
-                                        .unwrap_or(false);
-                                    if contains {
-                                        // we do have this port, need to add all addresses as
-                                        // endpoints
-                                        found_one = true;
-                                        let port_num = val_u64("/targetPort", port, 0);
-                                        subset.pointer("/addresses").map(|a| {
-                                            a.as_array().map(|addr_array| {
-                                                let mut first = true;
-                                                for addr in addr_array.iter() {
-                                                    if first {
-                                                        first = false;
-                                                    } else {
-                                                        epbuf.push_str(", ");
-                                                    }
-                                                    epbuf.push_str(
-                                                        format!(
-                                                            "{}:{}",
-                                                            val_str("/ip", addr, "<No IP>"),
-                                                            port_num
-                                                        )
-                                                        .as_str(),
-                                                    );
-                                                }
-                                            })
-                                        });
-                                    }
-                                }
-                            })
-                        });
-                        if !found_one {
-                            epbuf.push_str("<none>");
-                        }
-                        epbuf
+    let ingress: Vec<String> = v
+        .pointer("/status/loadBalancer/ingress")
+        .and_then(|i| i.as_array())
+        .map(|arr| {
+            arr.iter()
+                .filter_map(|lb| {
+                    lb["ip"]
+                        .as_str()
+                        .or_else(|| lb["hostname"].as_str())
+                        .map(|s| s.to_string())
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    if ingress.is_empty() {
+        "<pending>".into()
+    } else {
+        ingress.join(", ").into()
+    }
+}
+
+fn subsets(endpoints: &Value) -> impl Iterator<Item = &Value> {
+    endpoints["subsets"].as_array().into_iter().flatten()
+}
+
+fn addresses<'a>(subset: &'a Value, key: &str) -> impl Iterator<Item = &'a Value> {
+    subset[key].as_array().into_iter().flatten()
+}
+
+/// The endpoints (as ip:port) for the service port called port_name. Endpoint ports are matched
+/// to service ports by name, and have the resolved target port number
+fn port_endpoints(endpoints: &Value, port_name: &str) -> Vec<String> {
+    let mut eps = vec![];
+    for subset in subsets(endpoints) {
+        let port = subset["ports"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .find(|port| port["name"].as_str().unwrap_or("") == port_name);
+        if let Some(port) = port {
+            let port_num = val_u64("/port", port, 0);
+            for addr in addresses(subset, "addresses") {
+                eps.push(format!("{}:{}", val_str("/ip", addr, "<No IP>"), port_num));
+            }
+            for addr in addresses(subset, "notReadyAddresses") {
+                eps.push(format!(
+                    "{}:{} (not ready)",
+                    val_str("/ip", addr, "<No IP>"),
+                    port_num
+                ));
+            }
+        }
+    }
+    eps
+}
+
+/// The pods backing the service, found via the targetRefs of the endpoint addresses
+fn endpoint_pods_str(endpoints: &Value) -> String {
+    let mut pods = vec![];
+    for subset in subsets(endpoints) {
+        for (key, suffix) in [("addresses", ""), ("notReadyAddresses", " (not ready)")].iter() {
+            for addr in addresses(subset, key) {
+                let ip = val_str("/ip", addr, "<No IP>");
+                let pod = match addr.pointer("/targetRef") {
+                    Some(target) if val_str("/kind", target, "") == "Pod" => {
+                        val_str("/name", target, "<No Name>")
                     }
-                    None => "<No Endpoints>".to_owned(),
+                    _ => "<not a pod>".into(),
                 };
-                buf.push_str(format!("  Port:\t\t{} {}/{}\n", name, port_num, proto).as_str());
-                buf.push_str(
-                    format!(
-                        "  NodePort:\t{} {}/{}\n",
-                        val_str("/name", port, "<No Name>"),
-                        val_u64("/nodePort", port, 0),
-                        proto
-                    )
-                    .as_str(),
-                );
-                buf.push_str(endpoints.as_str());
-                buf.push('\n');
-                buf.push('\n');
+                let line = format!("\t{} ({}){}", pod, ip, suffix);
+                if !pods.contains(&line) {
+                    pods.push(line);
+                }
+            }
+        }
+    }
+    if pods.is_empty() {
+        "\t<none>".to_string()
+    } else {
+        pods.join("\n")
+    }
+}
+
+/// Get ports info out of ports array
+fn get_ports_str<'a>(v: Option<&Value>, endpoint_val: Option<&Value>) -> Cow<'a, str> {
+    let port_array = match v.and_then(|v| v.as_array()) {
+        Some(port_array) => port_array,
+        None => return "  <none>".into(),
+    };
+    let mut buf = String::new();
+    for port in port_array.iter() {
+        if !buf.is_empty() {
+            buf.push('\n'); // blank line between ports
+        }
+        let proto = val_str("/protocol", port, "TCP");
+        let name = val_str("/name", port, "");
+        let target_port = match port.get("targetPort") {
+            Some(Value::Number(num)) => num.to_string(),
+            Some(Value::String(name)) => name.clone(),
+            _ => val_u64("/port", port, 0).to_string(), // defaults to the same as port
+        };
+        writeln!(
+            buf,
+            "  Port:\t\t{} {}/{}",
+            if name.is_empty() { "<unset>" } else { &name },
+            val_u64("/port", port, 0),
+            proto
+        )
+        .unwrap();
+        writeln!(buf, "  TargetPort:\t{}/{}", target_port, proto).unwrap();
+        if let Some(node_port) = port.get("nodePort").and_then(|np| np.as_u64()) {
+            writeln!(buf, "  NodePort:\t{}/{}", node_port, proto).unwrap();
+        }
+        let endpoints = match endpoint_val {
+            Some(ep) => {
+                let eps = port_endpoints(ep, &name);
+                if eps.is_empty() {
+                    "<none>".to_string()
+                } else {
+                    eps.join(", ")
+                }
+            }
+            None => "<No Endpoints>".to_string(),
+        };
+        writeln!(buf, "  Endpoints:\t{}", endpoints).unwrap();
+    }
+    buf.pop(); // describe_object adds the final newline
+    buf.into()
+}
+
+/// Longest configmap value describe --show prints. Longer values, and values with more than one
+/// line, are cut off
+const MAX_SHOWN_VALUE_CHARS: usize = 60;
+
+fn shown_value(val: &str) -> String {
+    let first_line = val.lines().next().unwrap_or("");
+    let mut shown: String = first_line.chars().take(MAX_SHOWN_VALUE_CHARS).collect();
+    if shown.len() < val.trim_end_matches('\n').len() {
+        shown.push_str("...");
+    }
+    shown
+}
+
+/// The keys of a configmap's data or binaryData, with the size of each value. If show_values is
+/// set, (the start of) each value in data is shown too
+fn configmap_data_str(data: &Value, binary: bool, show_values: bool) -> Cow<'_, str> {
+    let data = match data.as_object() {
+        Some(data) if !data.is_empty() => data,
+        _ => return "\t<none>".into(),
+    };
+    let mut buf = String::new();
+    for (key, val) in data.iter() {
+        let val = val.as_str().unwrap_or("");
+        if binary {
+            match ::base64::decode(val) {
+                Ok(dec) => writeln!(buf, "\t{}:\t{} bytes, binary", key, dec.len()).unwrap(),
+                Err(_) => writeln!(buf, "\t{}:\tCould not decode data", key).unwrap(),
             }
+        } else if show_values {
+            writeln!(buf, "\t{}:\t{} bytes\t{}", key, val.len(), shown_value(val)).unwrap();
+        } else {
+            writeln!(buf, "\t{}:\t{} bytes", key, val.len()).unwrap();
         }
-        None => buf.push_str("<none>"),
     }
+    buf.pop(); // describe_object adds the final newline
     buf.into()
 }
 
+fn configmap_binary_data_str(data: &Value) -> Cow<'_, str> {
+    configmap_data_str(data, true, false)
+}
+
+/// Utility function to describe a configmap. Values are only shown if show_values is set
+pub fn describe_format_configmap(v: Value, show_values: bool) -> String {
+    let data_func: &dyn Fn(&Value) -> Cow<str> =
+        &|data| configmap_data_str(data, false, show_values);
+    let mut fields = vec![
+        (
+            "Name:\t\t",
+            DescItem::MetadataValStr {
+                path: "/name",
+                default: "<No Name>",
+            },
+        ),
+        (
+            "Namespace:\t",
+            DescItem::MetadataValStr {
+                path: "/namespace",
+                default: "<No Name>",
+            },
+        ),
+        ("Created at:\t", DescItem::ObjectCreated),
+        (
+            "Labels:\t",
+            DescItem::KeyValStr {
+                parent: "/metadata/labels",
+                secret_vals: false,
+            },
+        ),
+        (
+            "Annotations:",
+            DescItem::KeyValStr {
+                parent: "/metadata/annotations",
+                secret_vals: false,
+            },
+        ),
+        (
+            "\nData:\n",
+            DescItem::CustomFunc {
+                path: Some("/data"),
+                func: data_func,
+                default: "\t<none>",
+            },
+        ),
+    ];
+    if v.get("binaryData").is_some() {
+        fields.push((
+            "\nBinary Data:\n",
+            DescItem::CustomFunc {
+                path: Some("/binaryData"),
+                func: &configmap_binary_data_str,
+                default: "\t<none>",
+            },
+        ));
+    }
+    describe_object(&v, fields.into_iter())
+}
+
 /// Utility function to describe a secret
 pub fn describe_format_secret(v: Value) -> String {
     let fields = vec![
@@ -591,6 +1196,203 @@ pub fn describe_format_secret(v: Value) -> String {
     describe_object(&v, fields.into_iter())
 }
 
+/// The condition of a job that says it failed, if it has one
+fn job_failed_condition(v: &Value) -> Option<&Value> {
+    v.pointer("/status/conditions")
+        .and_then(|conds| conds.as_array())
+        .and_then(|conds| {
+            conds.iter().find(|cond| {
+                val_str("/type", cond, "") == "Failed" && val_str("/status", cond, "") == "True"
+            })
+        })
+}
+
+/// How long a job ran for, or has been running if it hasn't finished
+fn job_duration(v: &Value) -> Cow<'_, str> {
+    let time = |path: &str, v: &Value| {
+        val_str_opt(path, v).and_then(|t| DateTime::<Utc>::from_str(&t).ok())
+    };
+    match time("/status/startTime", v) {
+        Some(start) => {
+            let end = time("/status/completionTime", v)
+                .or_else(|| job_failed_condition(v).and_then(|c| time("/lastTransitionTime", c)));
+            match end {
+                Some(end) => format_duration(end.signed_duration_since(start)).into(),
+                None => format!("{} (still running)", time_since(start)).into(),
+            }
+        }
+        None => "<not started>".into(),
+    }
+}
+
+/// The type, reason and message of each of a job's conditions, with failures in red
+fn job_conditions(v: &Value) -> Cow<'_, str> {
+    let mut buf = String::new();
+    if let Some(conds) = v.as_array() {
+        for cond in conds.iter() {
+            let typ = val_str("/type", cond, "<unknown>");
+            let line = format!(
+                "  {} ({}): {}{}",
+                typ,
+                val_str("/status", cond, "Unknown"),
+                val_str("/reason", cond, "<no reason>"),
+                val_str_opt("/message", cond)
+                    .map(|m| format!(", {}", m))
+                    .unwrap_or_default()
+            );
+            let colour = match &*typ {
+                "Complete" => Colour::Green,
+                "Failed" => Colour::Red,
+                _ => Colour::Yellow,
+            };
+            write!(buf, "\n{}", colour.paint(line)).unwrap();
+        }
+    }
+    if buf.is_empty() {
+        "\t<none>".into()
+    } else {
+        buf.into()
+    }
+}
+
+/// The pods a job created, with their phases
+fn job_pods_summary(pods: &Value) -> String {
+    let mut buf = String::new();
+    let pods = pods
+        .get("items")
+        .and_then(|items| items.as_array())
+        .map(|items| items.as_slice())
+        .unwrap_or_default();
+    writeln!(buf, "Pods:\t\t({} in total)", pods.len()).unwrap();
+    if !pods.is_empty() {
+        writeln!(buf, "  Name\tPhase\tStarted").unwrap();
+    }
+    for pod in pods.iter() {
+        let started = val_str_opt("/status/startTime", pod)
+            .and_then(|t| DateTime::<Utc>::from_str(&t).ok())
+            .map(|t| format!("{} ago", time_since(t)))
+            .unwrap_or_else(|| "<not started>".to_string());
+        writeln!(
+            buf,
+            "  {}\t{}\t{}",
+            val_str("/metadata/name", pod, "<none>"),
+            pod_phase(pod),
+            started
+        )
+        .unwrap();
+    }
+    buf.trim_end().to_string()
+}
+
+/// Utility function to describe a job. pods are the pods the job created, if they could be listed
+pub fn describe_format_job(v: Value, pods: Option<Value>) -> String {
+    let mut fields = vec![
+        (
+            "Name:\t\t",
+            DescItem::MetadataValStr {
+                path: "/name",
+                default: "<No Name>",
+            },
+        ),
+        (
+            "Namespace:\t",
+            DescItem::MetadataValStr {
+                path: "/namespace",
+                default: "<No Name>",
+            },
+        ),
+        ("Created at:\t", DescItem::ObjectCreated),
+        (
+            "Labels:\t",
+            DescItem::KeyValStr {
+                parent: "/metadata/labels",
+                secret_vals: false,
+            },
+        ),
+        (
+            "Parallelism:\t",
+            DescItem::Valu64 {
+                path: "/spec/parallelism",
+                default: 1,
+            },
+        ),
+        (
+            "Completions:\t",
+            DescItem::StaticStr(
+                format!(
+                    "{}/{}",
+                    val_u64("/status/succeeded", &v, 0),
+                    v.pointer("/spec/completions")
+                        .and_then(|c| c.as_u64())
+                        .map(|c| c.to_string())
+                        .unwrap_or_else(|| "<unset>".to_string())
+                )
+                .into(),
+            ),
+        ),
+        (
+            "Backoff Limit:\t",
+            DescItem::Valu64 {
+                path: "/spec/backoffLimit",
+                default: 6,
+            },
+        ),
+        (
+            "Duration:\t",
+            DescItem::CustomFunc {
+                path: None,
+                func: &job_duration,
+                default: "<unknown>",
+            },
+        ),
+        (
+            "Pod Statuses:\t",
+            DescItem::StaticStr(
+                format!(
+                    "{} Active / {} Succeeded / {} Failed",
+                    val_u64("/status/active", &v, 0),
+                    val_u64("/status/succeeded", &v, 0),
+                    val_u64("/status/failed", &v, 0)
+                )
+                .into(),
+            ),
+        ),
+    ];
+    if let Some(failed) = job_failed_condition(&v) {
+        let reason = format!(
+            "{}: {}",
+            val_str("/reason", failed, "<no reason>"),
+            val_str("/message", failed, "<no message>")
+        );
+        fields.push((
+            "Failed:\t\t",
+            DescItem::StaticStr(Colour::Red.paint(reason).to_string().into()),
+        ));
+    }
+    fields.push((
+        "\nContainers:\n",
+        DescItem::CustomFunc {
+            path: Some("/spec/template/spec/containers"),
+            func: &get_container_str,
+            default: "<No Containers>",
+        },
+    ));
+    fields.push((
+        "Conditions:",
+        DescItem::CustomFunc {
+            path: Some("/status/conditions"),
+            func: &job_conditions,
+            default: "\t<none>",
+        },
+    ));
+    let mut desc = describe_object(&v, fields.into_iter());
+    match pods {
+        Some(pods) => desc.push_str(&job_pods_summary(&pods)),
+        None => desc.push_str("Pods:\t\t<unable to list pods>"),
+    }
+    desc
+}
+
 /// Get container info out of container array
 fn get_container_str(v: &Value) -> Cow<str> {
     let mut buf = String::new();
@@ -612,6 +1414,7 @@ fn get_container_str(v: &Value) -> Cow<str> {
 }
 
 /// Get status messages out of 'conditions' array
+#[cfg(feature = "argorollouts")]
 fn get_message_str(v: &Value) -> Cow<str> {
     let mut buf = String::new();
     if let Some(condition_array) = v.as_array() {
@@ -627,8 +1430,123 @@ fn get_message_str(v: &Value) -> Cow<str> {
     buf.into()
 }
 
-/// Utility function to describe a deployment
-pub fn describe_format_deployment(v: Value) -> String {
+/// How a deployment replaces its pods: Recreate, or RollingUpdate with its surge and unavailable
+/// limits (which default to 25%)
+fn deployment_strategy(v: &Value) -> Cow<'_, str> {
+    match &*val_str("/type", v, "RollingUpdate") {
+        "RollingUpdate" => {
+            let limit = |path: &str| match v.pointer(path) {
+                Some(Value::Number(n)) => n.to_string(),
+                Some(Value::String(s)) => s.clone(),
+                _ => "25%".to_string(),
+            };
+            format!(
+                "RollingUpdate (max surge {}, max unavailable {})",
+                limit("/rollingUpdate/maxSurge"),
+                limit("/rollingUpdate/maxUnavailable")
+            )
+            .into()
+        }
+        other => other.to_string().into(),
+    }
+}
+
+/// The Available and Progressing conditions of a deployment (and any others it has), green when
+/// true and red when false
+fn deployment_conditions(v: &Value) -> Cow<'_, str> {
+    let mut buf = String::new();
+    if let Some(conds) = v.as_array() {
+        for cond in conds.iter() {
+            let status = val_str("/status", cond, "Unknown");
+            let line = format!(
+                "  {} ({}): {}{}",
+                val_str("/type", cond, "<unknown>"),
+                status,
+                val_str("/reason", cond, "<no reason>"),
+                val_str_opt("/message", cond)
+                    .map(|m| format!(", {}", m))
+                    .unwrap_or_default()
+            );
+            let colour = match &*status {
+                "True" => Colour::Green,
+                "False" => Colour::Red,
+                _ => Colour::Yellow,
+            };
+            write!(buf, "\n{}", colour.paint(line)).unwrap();
+        }
+    }
+    if buf.is_empty() {
+        "\t<none>".into()
+    } else {
+        buf.into()
+    }
+}
+
+/// The rollout revision of a deployment or one of its replicasets
+fn rollout_revision(v: &Value) -> Option<u64> {
+    v.pointer("/metadata/annotations")
+        .and_then(|annotations| annotations.get(REVISION_ANNOTATION))
+        .and_then(|rev| rev.as_str())
+        .and_then(|rev| rev.parse().ok())
+}
+
+fn replicaset_line(rs: &Value) -> String {
+    format!(
+        "{} (revision {}, {}/{} ready)",
+        val_str("/metadata/name", rs, "<none>"),
+        rollout_revision(rs)
+            .map(|rev| rev.to_string())
+            .unwrap_or_else(|| "<unknown>".to_string()),
+        val_u64("/status/readyReplicas", rs, 0),
+        val_u64("/spec/replicas", rs, 0)
+    )
+}
+
+/// The replicasets that belong to deployment (found by their owner references, since other
+/// replicasets can match its selector), split into the one for the current revision and the old
+/// ones, newest first
+fn deployment_replicasets_summary(deployment: &Value, replicasets: &Value) -> String {
+    let uid = val_str("/metadata/uid", deployment, "");
+    let current = rollout_revision(deployment);
+    let mut owned: Vec<&Value> = replicasets
+        .get("items")
+        .and_then(|items| items.as_array())
+        .map(|items| {
+            items
+                .iter()
+                .filter(|rs| {
+                    rs.pointer("/metadata/ownerReferences")
+                        .and_then(|owners| owners.as_array())
+                        .map(|owners| owners.iter().any(|o| val_str("/uid", o, "") == uid))
+                        .unwrap_or(false)
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    owned.sort_by_key(|rs| std::cmp::Reverse(rollout_revision(rs)));
+    let (new, old): (Vec<&Value>, Vec<&Value>) = owned
+        .into_iter()
+        .partition(|rs| current.is_some() && rollout_revision(rs) == current);
+
+    let mut buf = String::new();
+    match new.first() {
+        Some(rs) => writeln!(buf, "New ReplicaSet:\t{}", replicaset_line(rs)).unwrap(),
+        None => writeln!(buf, "New ReplicaSet:\t<none>").unwrap(),
+    }
+    if old.is_empty() {
+        write!(buf, "Old ReplicaSets:\t<none>").unwrap();
+    } else {
+        write!(buf, "Old ReplicaSets:").unwrap();
+        for rs in old.iter() {
+            write!(buf, "\n  {}", replicaset_line(rs)).unwrap();
+        }
+    }
+    buf
+}
+
+/// Utility function to describe a deployment. replicasets are the replicasets in its namespace
+/// that match its selector, if they could be listed
+pub fn describe_format_deployment(v: Value, replicasets: Option<Value>) -> String {
     let fields = vec![
         (
             "Name:\t\t",
@@ -687,6 +1605,21 @@ pub fn describe_format_deployment(v: Value) -> String {
                 default: 0,
             },
         ),
+        (
+            "Unavailable Replicas:\t",
+            DescItem::Valu64 {
+                path: "/status/unavailableReplicas",
+                default: 0,
+            },
+        ),
+        (
+            "Strategy:\t",
+            DescItem::CustomFunc {
+                path: Some("/spec/strategy"),
+                func: &deployment_strategy,
+                default: "RollingUpdate",
+            },
+        ),
         (
             "\nContainers:\n",
             DescItem::CustomFunc {
@@ -696,15 +1629,20 @@ pub fn describe_format_deployment(v: Value) -> String {
             },
         ),
         (
-            "Messages:\n",
+            "Conditions:",
             DescItem::CustomFunc {
                 path: Some("/status/conditions"),
-                func: &get_message_str,
-                default: "<No Messages>",
+                func: &deployment_conditions,
+                default: "\t<none>",
             },
         ),
     ];
-    describe_object(&v, fields.into_iter())
+    let mut desc = describe_object(&v, fields.into_iter());
+    match replicasets {
+        Some(replicasets) => desc.push_str(&deployment_replicasets_summary(&v, &replicasets)),
+        None => desc.push_str("ReplicaSets:\t<unable to list replicasets>"),
+    }
+    desc
 }
 
 /// Utility function to describe a rollout
@@ -787,3 +1725,95 @@ pub fn describe_format_rollout(v: Value) -> String {
     ];
     describe_object(&v, fields.into_iter())
 }
+
+/// Collect the field paths out of a managedFields fieldsV1 value, down to depth levels
+fn managed_field_paths(v: &Value, prefix: &str, depth: usize, paths: &mut Vec<String>) {
+    if let Some(fields) = v.as_object() {
+        for (key, child) in fields.iter() {
+            if key == "." {
+                continue;
+            }
+            // keys look like f:name, k:{"name":"foo"}, v:"value" or i:0
+            let name = match key.split_once(':') {
+                Some(("f", name)) => name.to_string(),
+                Some((_, sel)) => format!("[{}]", sel),
+                None => key.to_string(),
+            };
+            let path = if prefix.is_empty() {
+                name
+            } else if name.starts_with('[') {
+                format!("{}{}", prefix, name)
+            } else {
+                format!("{}.{}", prefix, name)
+            };
+            let has_children = child
+                .as_object()
+                .map(|c| c.keys().any(|k| k != "."))
+                .unwrap_or(false);
+            if depth > 1 && has_children {
+                managed_field_paths(child, &path, depth - 1, paths);
+            } else {
+                paths.push(path);
+            }
+        }
+    }
+}
+
+/// Format the metadata.managedFields of an object compactly, grouped by manager. Field paths are
+/// only shown two levels deep
+pub fn describe_managed_fields(v: &Value) -> String {
+    let mut buf = String::from("Managed Fields:\n");
+    let entries = match v
+        .pointer("/metadata/managedFields")
+        .and_then(|m| m.as_array())
+    {
+        Some(entries) if !entries.is_empty() => entries,
+        _ => {
+            buf.push_str("  <none>\n");
+            return buf;
+        }
+    };
+    let mut managers: Vec<&str> = vec![];
+    for entry in entries.iter() {
+        let manager = entry
+            .get("manager")
+            .and_then(|m| m.as_str())
+            .unwrap_or("<unknown manager>");
+        if !managers.contains(&manager) {
+            managers.push(manager);
+        }
+    }
+    for manager in managers.iter() {
+        writeln!(buf, "  {}:", Colour::Yellow.paint(*manager)).unwrap();
+        for entry in entries.iter().filter(|e| {
+            e.get("manager")
+                .and_then(|m| m.as_str())
+                .unwrap_or("<unknown manager>")
+                == *manager
+        }) {
+            let mut paths = vec![];
+            if let Some(fields) = entry.get("fieldsV1") {
+                managed_field_paths(fields, "", 2, &mut paths);
+            }
+            let subresource = entry
+                .get("subresource")
+                .and_then(|s| s.as_str())
+                .map(|s| format!(" {}", s))
+                .unwrap_or_default();
+            writeln!(
+                buf,
+                "    {}{} ({}): {}",
+                val_str("/operation", entry, "<unknown operation>"),
+                subresource,
+                val_str("/time", entry, "unknown time"),
+                if paths.is_empty() {
+                    "<no fields>".to_string()
+                } else {
+                    paths.join(", ")
+                }
+            )
+            .unwrap();
+        }
+    }
+    buf
+}
@@ -0,0 +1,203 @@
+// Copyright 2021 Databricks, Inc.
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+
+// http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A small line based diff, for comparing the yaml of two objects. It finds the longest common
+//! subsequence of lines, which is quadratic, but objects are at most a few hundred lines
+
+/// A line in a diff
+#[derive(Debug, PartialEq)]
+pub enum DiffLine<'a> {
+    Same(&'a str),
+    Removed(&'a str),
+    Added(&'a str),
+}
+
+/// A hunk of a unified diff: the lines that changed plus some context around them. Line numbers
+/// are 1 based, as they are in diff output
+#[derive(Debug, PartialEq)]
+pub struct Hunk<'a> {
+    pub old_start: usize,
+    pub old_len: usize,
+    pub new_start: usize,
+    pub new_len: usize,
+    pub lines: Vec<DiffLine<'a>>,
+}
+
+impl<'a> Hunk<'a> {
+    /// The @@ line that starts this hunk
+    pub fn header(&self) -> String {
+        format!(
+            "@@ -{},{} +{},{} @@",
+            self.old_start, self.old_len, self.new_start, self.new_len
+        )
+    }
+}
+
+/// Diff old against new line by line
+pub fn diff_lines<'a>(old: &'a str, new: &'a str) -> Vec<DiffLine<'a>> {
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+
+    // lcs[i][j] is the length of the longest common subsequence of old[i..] and new[j..]
+    let mut lcs = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lcs[i][j] = if old[i] == new[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                std::cmp::max(lcs[i + 1][j], lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut lines = vec![];
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() && j < new.len() {
+        if old[i] == new[j] {
+            lines.push(DiffLine::Same(old[i]));
+            i += 1;
+            j += 1;
+        } else if lcs[i + 1][j] >= lcs[i][j + 1] {
+            lines.push(DiffLine::Removed(old[i]));
+            i += 1;
+        } else {
+            lines.push(DiffLine::Added(new[j]));
+            j += 1;
+        }
+    }
+    lines.extend(old[i..].iter().map(|l| DiffLine::Removed(l)));
+    lines.extend(new[j..].iter().map(|l| DiffLine::Added(l)));
+    lines
+}
+
+/// Group a diff into hunks with up to context unchanged lines around each change. Changes closer
+/// together than twice the context end up in the same hunk. No hunks means no differences
+pub fn hunks<'a>(lines: Vec<DiffLine<'a>>, context: usize) -> Vec<Hunk<'a>> {
+    let changed: Vec<usize> = lines
+        .iter()
+        .enumerate()
+        .filter(|(_, l)| !matches!(l, DiffLine::Same(_)))
+        .map(|(i, _)| i)
+        .collect();
+
+    // index ranges (into lines) of each hunk
+    let mut ranges: Vec<(usize, usize)> = vec![];
+    for i in changed {
+        let start = i.saturating_sub(context);
+        let end = std::cmp::min(i + context + 1, lines.len());
+        match ranges.last_mut() {
+            Some(last) if start <= last.1 => last.1 = end,
+            _ => ranges.push((start, end)),
+        }
+    }
+
+    // line numbers where each entry of lines is in old and new
+    let mut old_line = 1;
+    let mut new_line = 1;
+    let mut positions = Vec::with_capacity(lines.len());
+    for line in lines.iter() {
+        positions.push((old_line, new_line));
+        match line {
+            DiffLine::Same(_) => {
+                old_line += 1;
+                new_line += 1;
+            }
+            DiffLine::Removed(_) => old_line += 1,
+            DiffLine::Added(_) => new_line += 1,
+        }
+    }
+
+    let mut hunks = vec![];
+    let mut lines = lines.into_iter().enumerate().peekable();
+    for (start, end) in ranges {
+        let (old_start, new_start) = positions[start];
+        let hunk_lines: Vec<DiffLine> = std::iter::from_fn(|| lines.next_if(|(i, _)| *i < end))
+            .filter(|(i, _)| *i >= start)
+            .map(|(_, l)| l)
+            .collect();
+        let old_len = hunk_lines
+            .iter()
+            .filter(|l| !matches!(l, DiffLine::Added(_)))
+            .count();
+        let new_len = hunk_lines
+            .iter()
+            .filter(|l| !matches!(l, DiffLine::Removed(_)))
+            .count();
+        hunks.push(Hunk {
+            old_start,
+            old_len,
+            new_start,
+            new_len,
+            lines: hunk_lines,
+        });
+    }
+    hunks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn line_diff() {
+        let old = "a\nb\nc\nd\n";
+        let new = "a\nc\nd\ne\n";
+        assert_eq!(
+            diff_lines(old, new),
+            vec![
+                DiffLine::Same("a"),
+                DiffLine::Removed("b"),
+                DiffLine::Same("c"),
+                DiffLine::Same("d"),
+                DiffLine::Added("e"),
+            ]
+        );
+        assert!(hunks(diff_lines(old, old), 3).is_empty());
+    }
+
+    #[test]
+    fn diff_hunks() {
+        let old: String = (1..=20).map(|i| format!("{}\n", i)).collect();
+        let change = |from: &[&str], to: &[&str]| -> String {
+            old.lines()
+                .filter_map(|l| match from.iter().position(|f| *f == l) {
+                    Some(p) if to[p].is_empty() => None,
+                    Some(p) => Some(format!("{}\n", to[p])),
+                    None => Some(format!("{}\n", l)),
+                })
+                .collect()
+        };
+        let new = change(&["3", "18"], &["three", ""]);
+        let found = hunks(diff_lines(&old, &new), 2);
+        assert_eq!(found.len(), 2);
+        assert_eq!(found[0].header(), "@@ -1,5 +1,5 @@");
+        assert_eq!(
+            found[0].lines,
+            vec![
+                DiffLine::Same("1"),
+                DiffLine::Same("2"),
+                DiffLine::Removed("3"),
+                DiffLine::Added("three"),
+                DiffLine::Same("4"),
+                DiffLine::Same("5"),
+            ]
+        );
+        assert_eq!(found[1].header(), "@@ -16,5 +16,4 @@");
+        assert_eq!(found[1].lines[2], DiffLine::Removed("18"));
+
+        // changes close together are in one hunk
+        let new = change(&["3", "6"], &["three", "six"]);
+        assert_eq!(hunks(diff_lines(&old, &new), 2).len(), 1);
+    }
+}
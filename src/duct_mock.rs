@@ -17,52 +17,48 @@
 use chrono::offset::Utc;
 
 use std::ffi::OsString;
-use std::io::Result;
+use std::io::{Error, ErrorKind, Result};
+use std::os::unix::process::ExitStatusExt;
+use std::process::{ExitStatus, Output};
 
 #[derive(Clone)]
 pub struct MockExpression {
     cmd: String,
     args: Vec<String>,
+    env: Vec<(String, String)>,
 }
 
-pub struct MockReader {
-    read_from: String,
-    pos: usize,
-}
-
-impl MockReader {
-    fn new(read_from: String) -> MockReader {
-        MockReader { read_from, pos: 0 }
-    }
-}
-
-impl std::io::Read for MockReader {
-    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
-        let left = self.read_from.len() - self.pos;
-        if left == 0 {
-            Ok(0)
-        } else if left < buf.len() {
-            // copy remaining
-            buf[0..left].copy_from_slice(&self.read_from.as_bytes()[self.pos..]);
-            self.pos += left;
-            Ok(left)
-        } else {
-            // copy what we can
-            let lim = self.pos + buf.len();
-            buf.copy_from_slice(&self.read_from.as_bytes()[self.pos..lim]);
-            self.pos += buf.len();
-            Ok(buf.len())
-        }
+fn output(code: i32, stdout: String, stderr: &str) -> Output {
+    Output {
+        status: ExitStatus::from_raw(code << 8),
+        stdout: stdout.into_bytes(),
+        stderr: stderr.as_bytes().to_vec(),
     }
 }
 
 impl MockExpression {
-    pub fn full_env<T, U, V>(&self, _name_vals: T) -> MockExpression
+    pub fn env<T, U>(&self, name: T, val: U) -> MockExpression
     where
-        T: IntoIterator<Item = (U, V)>,
+        T: Into<OsString>,
         U: Into<OsString>,
-        V: Into<OsString>,
     {
+        let mut expr = self.clone();
+        expr.env.push((
+            name.into().into_string().unwrap(),
+            val.into().into_string().unwrap(),
+        ));
+        expr
+    }
+
+    pub fn stdout_capture(&self) -> MockExpression {
+        self.clone()
+    }
+
+    pub fn stderr_capture(&self) -> MockExpression {
+        self.clone()
+    }
+
+    pub fn unchecked(&self) -> MockExpression {
         self.clone()
     }
 
@@ -70,21 +66,36 @@ impl MockExpression {
         Ok("".to_string())
     }
 
-    pub fn reader(&self) -> Result<MockReader> {
+    pub fn run(&self) -> Result<Output> {
         match self.cmd.as_str() {
-            "aws" => Ok(MockReader::new(format!(
-                r#"{{
+            "aws" => {
+                // answer with the version we were asked for, like a real plugin
+                let info = self
+                    .env
+                    .iter()
+                    .find(|(name, _)| name == "KUBERNETES_EXEC_INFO")
+                    .map(|(_, val)| serde_json::from_str::<serde_json::Value>(val).unwrap())
+                    .unwrap();
+                Ok(output(
+                    0,
+                    format!(
+                        r#"{{
                   "kind": "ExecCredential",
-                  "apiVersion": "client.authentication.k8s.io/v1alpha1",
+                  "apiVersion": "{}",
                   "spec": {{}},
                   "status": {{
                     "expirationTimestamp": "{}",
                     "token": "testtoken"
                   }}
                 }}"#,
-                (Utc::now() + chrono::Duration::hours(1)).format("%Y-%m-%dT%H:%M:%SZ")
-            ))),
-            _ => Ok(MockReader::new("not found".to_string())),
+                        info["apiVersion"].as_str().unwrap(),
+                        (Utc::now() + chrono::Duration::hours(1)).format("%Y-%m-%dT%H:%M:%SZ")
+                    ),
+                    "",
+                ))
+            }
+            "failing-plugin" => Ok(output(1, "".to_string(), "could not get token\n")),
+            _ => Err(Error::new(ErrorKind::NotFound, "No such file or directory")),
         }
     }
 }
@@ -103,5 +114,6 @@ where
     MockExpression {
         cmd: os.into_string().unwrap(),
         args,
+        env: vec![],
     }
 }
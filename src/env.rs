@@ -12,34 +12,123 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use crate::config::{self, Alias, ClickConfig, Config};
+use crate::cache::{CacheKey, DescribeCache, DescribeKey, ListCache};
+use crate::config::{
+    self, parse_colour, parse_prompt_template, Alias, ClickConfig, Config, PromptPart,
+};
 use crate::error::ClickError;
+use crate::k8s::Impersonation;
 use crate::kobj::{KObj, ObjType};
-use crate::output::ClickWriter;
+use crate::output::{clear_screen, ClickWriter};
+use crate::parser::CommandOverrides;
 
 use ansi_term::Colour::{Blue, Green, Red, Yellow};
+use ansi_term::{ANSIString, Colour, Style};
+use atomicwrites::{AllowOverwrite, AtomicFile};
 use rustyline::config as rustyconfig;
 use strfmt::strfmt;
 use tempdir::TempDir;
 
+use std::cell::RefCell;
 use std::collections::{BTreeMap, HashMap};
 use std::fmt;
+use std::fs::{File, OpenOptions};
 use std::io::{self, Write};
-use std::path::PathBuf;
-use std::process::Child;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
 // TODO: Maybe make less of this pub
 
+/// How many contexts we keep around (with their connections and credentials) after switching away
+/// from them
+const CONTEXT_CACHE_SIZE: usize = 5;
+
 /// An ongoing port forward
 pub struct PortForward {
     pub child: Child,
+    pub context: String,
+    pub namespace: String,
     pub pod: String,
     pub ports: Vec<String>,
     pub output: Arc<Mutex<String>>,
 }
 
+/// A port forward as saved to disk, so a later session can find or restore it. Every running click
+/// shares the file, so each forward records the session that owns it
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct SavedPortForward {
+    /// the pid of the click that started the forward, and when that started
+    #[serde(default)]
+    pub session_pid: Option<u32>,
+    #[serde(default)]
+    pub session_started: Option<String>,
+    pub context: String,
+    pub namespace: String,
+    pub pod: String,
+    pub ports: Vec<String>,
+    /// the kubectl process doing the forward. None if click stopped it when exiting
+    pub pid: Option<u32>,
+    /// when pid started, so another process that gets the same pid isn't mistaken for it
+    #[serde(default)]
+    pub started: Option<String>,
+}
+
+impl SavedPortForward {
+    /// Is the kubectl doing this forward still running
+    pub fn forward_running(&self) -> bool {
+        self.pid
+            .map(|pid| process_running(pid, self.started.as_deref()))
+            .unwrap_or(false)
+    }
+
+    /// Is the click that started this forward still running
+    pub fn session_running(&self) -> bool {
+        self.session_pid
+            .map(|pid| process_running(pid, self.session_started.as_deref()))
+            .unwrap_or(false)
+    }
+
+    fn owned_by_us(&self) -> bool {
+        self.session_pid == Some(std::process::id())
+            && self.session_started.as_deref() == SESSION_STARTED.as_deref()
+    }
+}
+
+/// When the process with id pid started, or None if there's no such process. The format is
+/// whatever ps prints, it's only compared with other values from here
+fn process_started(pid: u32) -> Option<String> {
+    let output = Command::new("ps")
+        .args(["-o", "lstart=", "-p", &pid.to_string()])
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    let started = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    if output.status.success() && !started.is_empty() {
+        Some(started)
+    } else {
+        None
+    }
+}
+
+/// Is the process with id pid running, and if started is known, is it the one that started then
+fn process_running(pid: u32, started: Option<&str>) -> bool {
+    match (process_started(pid), started) {
+        (Some(actual), Some(expected)) => actual == expected,
+        (running, _) => running.is_some(),
+    }
+}
+
+/// A session recording. Commands entered while this is active are written to file
+pub struct Recording {
+    pub path: String,
+    file: File,
+    /// if true, don't record commands that only display information
+    pub skip_read_only: bool,
+}
+
 #[derive(Debug)]
 pub struct ExpandedAlias<'a> {
     pub expansion: Option<&'a Alias>,
@@ -53,6 +142,52 @@ pub enum ObjectSelection {
     None,
 }
 
+/// The result of looking for an object by name in the last list
+#[derive(Debug, PartialEq)]
+pub enum NameMatch<'a> {
+    None,
+    Unique(&'a KObj),
+    /// More than one object has the name (in different namespaces)
+    Ambiguous(Vec<&'a KObj>),
+}
+
+/// Is every char of needle in haystack, in order (but not necessarily adjacent)
+fn is_subsequence(needle: &str, haystack: &str) -> bool {
+    let mut hay = haystack.chars();
+    needle.chars().all(|n| hay.any(|h| h == n))
+}
+
+/// Find the names that best match query, ignoring case. An exact match wins, then names starting
+/// with query, then names containing it, and finally names containing its characters in order.
+/// Only the matches from the best of those that has any are returned.
+pub fn fuzzy_match<'a, I>(query: &str, names: I) -> Vec<&'a str>
+where
+    I: IntoIterator<Item = &'a String>,
+{
+    let query = query.to_lowercase();
+    let mut tiers: [Vec<&str>; 4] = Default::default();
+    for name in names {
+        let lower = name.to_lowercase();
+        let tier = if lower == query {
+            0
+        } else if lower.starts_with(&query) {
+            1
+        } else if lower.contains(&query) {
+            2
+        } else if is_subsequence(&query, &lower) {
+            3
+        } else {
+            continue;
+        };
+        tiers[tier].push(name.as_str());
+    }
+    tiers
+        .iter_mut()
+        .find(|tier| !tier.is_empty())
+        .map(std::mem::take)
+        .unwrap_or_default()
+}
+
 /// Keep track of our repl environment
 pub struct Env {
     pub config: Config,
@@ -69,6 +204,15 @@ pub struct Env {
     pub prompt: String,
     range_str: Option<String>,
     pub tempdir: std::io::Result<TempDir>,
+    list_cache: ListCache,
+    recording: Option<Recording>,
+    /// contexts we've switched away from, least recently used first
+    context_cache: Vec<super::k8s::Context>,
+    /// object names last fetched for completion. completers only get an &Env, hence the RefCell
+    completion_names: RefCell<Option<(CacheKey, Instant, Vec<String>)>>,
+    describe_cache: RefCell<DescribeCache>,
+    /// how much of each request to log. the click config's verbose, unless --verbose raised it
+    verbosity: u8,
 }
 
 lazy_static! {
@@ -81,12 +225,20 @@ lazy_static! {
         .expect("Error setting Ctrl-C handler");
         b
     };
+    /// when this click started, which along with its pid identifies the session that owns saved
+    /// port forwards
+    static ref SESSION_STARTED: Option<String> = process_started(std::process::id());
 }
 
 impl Env {
     pub fn new(config: Config, click_config: ClickConfig, click_config_path: PathBuf) -> Env {
         let namespace = click_config.namespace.clone();
-        let context = click_config.context.clone();
+        // the context used last time, or the kubeconfig's current-context on first run
+        let context = click_config
+            .context
+            .clone()
+            .or_else(|| config.current_context.clone());
+        let verbosity = click_config.verbose;
         let mut env = Env {
             config,
             click_config,
@@ -99,15 +251,25 @@ impl Env {
             last_objs: None,
             ctrlcbool: CTC_BOOL.clone(),
             port_forwards: Vec::new(),
-            prompt: format!(
-                "[{}] [{}] [{}] > ",
-                Red.paint("none"),
-                Green.paint("none"),
-                Yellow.paint("none")
-            ),
+            prompt: String::new(),
             range_str: None,
             tempdir: TempDir::new("click"),
+            list_cache: ListCache::new(),
+            recording: None,
+            context_cache: Vec::new(),
+            completion_names: RefCell::new(None),
+            describe_cache: RefCell::new(DescribeCache::new()),
+            verbosity,
         };
+        if let Some(template) = env.click_config.prompt_template.as_deref() {
+            for placeholder in parse_prompt_template(template).1 {
+                eprintln!(
+                    "Warning: unknown placeholder {} in prompt_template, it will be shown as is",
+                    placeholder
+                );
+            }
+        }
+        env.set_prompt();
         env.set_context(context.as_deref());
         env
     }
@@ -116,6 +278,10 @@ impl Env {
         &self.current_selection
     }
 
+    pub fn click_config_path(&self) -> &Path {
+        self.click_config_path.as_path()
+    }
+
     pub fn save_click_config(&mut self) {
         self.click_config.namespace = self.namespace.clone();
         self.click_config.context = self.context.as_ref().map(|c| c.name.clone());
@@ -124,26 +290,133 @@ impl Env {
             .unwrap();
     }
 
+    /// The color set for the current context in the kubeconfig, if there is one and it's valid
+    pub fn context_colour(&self) -> Option<Colour> {
+        let name = &self.context.as_ref()?.name;
+        self.config
+            .contexts
+            .get(name)?
+            .color
+            .as_deref()
+            .and_then(parse_colour)
+    }
+
+    /// The colour to show table headers in: the context's color, if color_table_headers is set
+    pub fn table_header_colour(&self) -> Option<Colour> {
+        if self.click_config.color_table_headers {
+            self.context_colour()
+        } else {
+            None
+        }
+    }
+
+    fn context_prompt_str(&self) -> ANSIString<'_> {
+        match self.context {
+            Some(ref c) => self
+                .context_colour()
+                .unwrap_or(Red)
+                .bold()
+                .paint(c.name.as_str()),
+            None => Red.paint("none"),
+        }
+    }
+
+    fn namespace_prompt_str(&self) -> ANSIString<'_> {
+        match self.namespace {
+            Some(ref n) => Green.bold().paint(n.as_str()),
+            None => Green.paint("none"),
+        }
+    }
+
+    fn object_prompt_str(&self) -> ANSIString<'_> {
+        match self.current_selection {
+            ObjectSelection::Single(ref obj) => obj.prompt_str(),
+            ObjectSelection::Range(_) => Blue.paint(self.range_str.as_ref().unwrap()),
+            ObjectSelection::None => Yellow.paint("none"),
+        }
+    }
+
+    fn objkind_str(&self) -> &str {
+        match self.current_selection {
+            ObjectSelection::Single(ref obj) => obj.type_str(),
+            ObjectSelection::Range(_) => "range",
+            ObjectSelection::None => "none",
+        }
+    }
+
+    /// Build the prompt from a parsed prompt_template. Placeholders get their usual colours,
+    /// unless a colour or bold directive is in effect, in which case they use that
+    fn render_prompt(&self, parts: &[PromptPart]) -> String {
+        let mut prompt = String::new();
+        let mut style: Option<Style> = None;
+        for part in parts.iter() {
+            let piece = match part {
+                PromptPart::Text(text) => Style::new().paint(text.as_str()),
+                PromptPart::Context => self.context_prompt_str(),
+                PromptPart::Namespace => self.namespace_prompt_str(),
+                PromptPart::Object => self.object_prompt_str(),
+                PromptPart::ObjKind => Style::new().paint(self.objkind_str()),
+                PromptPart::Colour(colour) => {
+                    let mut s = style.unwrap_or_default();
+                    s.foreground = Some(*colour);
+                    style = Some(s);
+                    continue;
+                }
+                PromptPart::Bold => {
+                    style = Some(style.unwrap_or_default().bold());
+                    continue;
+                }
+                PromptPart::Reset => {
+                    style = None;
+                    continue;
+                }
+            };
+            match style {
+                Some(s) => prompt.push_str(&s.paint(&*piece).to_string()),
+                None => prompt.push_str(&piece.to_string()),
+            }
+        }
+        prompt
+    }
+
     // sets the prompt string based on current settings
     fn set_prompt(&mut self) {
-        self.prompt = format!(
-            "[{}] [{}] [{}] > ",
-            if let Some(ref c) = self.context {
-                Red.bold().paint(c.name.as_str())
-            } else {
-                Red.paint("none")
-            },
-            if let Some(ref n) = self.namespace {
-                Green.bold().paint(n.as_str())
-            } else {
-                Green.paint("none")
-            },
-            match self.current_selection {
-                ObjectSelection::Single(ref obj) => obj.prompt_str(),
-                ObjectSelection::Range(_) => Blue.paint(self.range_str.as_ref().unwrap()),
-                ObjectSelection::None => Yellow.paint("none"),
+        let prompt = match self.click_config.prompt_template.as_deref() {
+            Some(template) => self.render_prompt(&parse_prompt_template(template).0),
+            None => format!(
+                "[{}] [{}] [{}] > ",
+                self.context_prompt_str(),
+                self.namespace_prompt_str(),
+                self.object_prompt_str()
+            ),
+        };
+        // always shown, even with a custom prompt_template, so insecure mode and impersonation
+        // are never silent
+        let prompt = match self.context.as_ref().and_then(|c| c.impersonation()) {
+            Some(impersonation) => format!(
+                "{} {}",
+                Yellow.bold().paint(format!("[as {}]", impersonation)),
+                prompt
+            ),
+            None => prompt,
+        };
+        self.prompt = match self.context.as_ref() {
+            Some(c) if c.insecure_skip_tls_verify() => {
+                format!("{} {}", Red.bold().paint("[INSECURE]"), prompt)
             }
-        );
+            _ => prompt,
+        };
+    }
+
+    /// Set (or with None, clear) the prompt_template and update the prompt. Returns any unknown
+    /// placeholders in the template
+    pub fn set_prompt_template(&mut self, template: Option<&str>) -> Vec<String> {
+        let unknown = template
+            .map(|t| parse_prompt_template(t).1)
+            .unwrap_or_default();
+        self.click_config.prompt_template = template.map(|t| t.to_string());
+        self.set_prompt();
+        unknown
     }
 
     pub fn get_rustyline_conf(&self) -> rustyconfig::Config {
@@ -154,9 +427,36 @@ impl Env {
         &self.config.contexts
     }
 
+    /// Find the contexts matching query, see `fuzzy_match`. Exact names are always a unique match.
+    pub fn find_context(&self, query: &str) -> Vec<&str> {
+        match self.config.contexts.get_key_value(query) {
+            Some((name, _)) => vec![name.as_str()],
+            None => fuzzy_match(query, self.config.contexts.keys()),
+        }
+    }
+
     pub fn set_context(&mut self, ctx: Option<&str>) {
         if let Some(cname) = ctx {
-            self.context = match self.config.get_context(cname, &self.click_config) {
+            let switching = self.context.as_ref().map(|c| c.name.as_str()) != Some(cname);
+            if switching {
+                // the selection and last list are from the old cluster
+                self.clear_current();
+                self.clear_last_objs();
+                if self.click_config.clear_on_switch && self.context.is_some() {
+                    clear_screen();
+                }
+            }
+            if let Some(mut old) = self.context.take() {
+                // impersonation only lasts until the context changes
+                old.set_impersonation(None);
+                // the cached context keeps its credentials, but its connections are closed. it
+                // reconnects when it's next used
+                if switching {
+                    old.drop_connections();
+                }
+                self.cache_context(old);
+            }
+            self.context = match self.load_context(cname) {
                 Ok(context) => Some(context),
                 Err(e) => {
                     println!(
@@ -167,11 +467,318 @@ impl Env {
                     None
                 }
             };
+            if let Some(context) = self.context.as_ref() {
+                if context.insecure_skip_tls_verify() {
+                    println!(
+                        "{} insecure-skip-tls-verify is set for context {}, the server's \
+                         certificate will not be verified",
+                        Red.bold().paint("[WARN]"),
+                        cname
+                    );
+                }
+            }
+            if self.context.is_some() {
+                // the namespace last used in the context, otherwise its default from the kubeconfig
+                if let Some(ns) = self
+                    .click_config
+                    .context_namespaces
+                    .get(cname)
+                    .or_else(|| self.config.contexts.get(cname)?.namespace.as_ref())
+                {
+                    self.namespace = Some(ns.clone());
+                }
+            }
             self.save_click_config();
             self.set_prompt();
         }
     }
 
+    /// Get the context called name from the cache, or load it from the kubeconfig
+    fn load_context(&mut self, name: &str) -> Result<super::k8s::Context, ClickError> {
+        let mut context = match self.take_cached_context(name) {
+            Some(cached) => cached,
+            None => self.config.get_context(name, &self.click_config)?,
+        };
+        context.set_interrupt(self.ctrlcbool.clone());
+        context.set_verbosity(self.verbosity);
+        Ok(context)
+    }
+
+    /// Keep context around so switching back to it can reuse its credentials, and running commands
+    /// against it with --context can reuse its connections too
+    fn cache_context(&mut self, context: super::k8s::Context) {
+        self.context_cache.retain(|c| c.name != context.name);
+        if self.context_cache.len() >= CONTEXT_CACHE_SIZE {
+            self.context_cache.remove(0);
+        }
+        self.context_cache.push(context);
+    }
+
+    /// Remove and return the cached context called name, if it's there and its credentials haven't
+    /// expired
+    fn take_cached_context(&mut self, name: &str) -> Option<super::k8s::Context> {
+        let pos = self.context_cache.iter().position(|c| c.name == name)?;
+        let context = self.context_cache.remove(pos);
+        if context.credentials_expired() {
+            None
+        } else {
+            Some(context)
+        }
+    }
+
+    /// Run f with the context, namespace, and/or impersonation overridden, for commands run with
+    /// --context, --namespace or --as. Everything f changes about the current context, namespace,
+    /// selection and last list is undone afterwards. An overriding context is put in the context
+    /// cache, so running more commands against it doesn't reconnect each time
+    pub fn with_overrides<F>(
+        &mut self,
+        overrides: &CommandOverrides,
+        f: F,
+    ) -> Result<(), ClickError>
+    where
+        F: FnOnce(&mut Env) -> Result<(), ClickError>,
+    {
+        let context_name = match overrides.context {
+            Some(query) => match self.find_context(query).as_slice() {
+                [name] => Some(name.to_string()),
+                [] => {
+                    return Err(ClickError::CommandError(format!(
+                        "No context matches {}",
+                        query
+                    )))
+                }
+                names => {
+                    return Err(ClickError::CommandError(format!(
+                        "{} matches multiple contexts: {}",
+                        query,
+                        names.join(", ")
+                    )))
+                }
+            },
+            None => None,
+        };
+        let switch_to =
+            context_name.filter(|name| self.context.as_ref().map(|c| &c.name) != Some(name));
+        let saved_context = match switch_to.as_ref() {
+            Some(name) => {
+                let context = self.load_context(name)?;
+                Some(self.context.replace(context))
+            }
+            None => None,
+        };
+        let impersonation = overrides.as_user.map(|user| Impersonation {
+            user: user.to_string(),
+            groups: overrides.as_groups.iter().map(|g| g.to_string()).collect(),
+            uid: overrides.as_uid.map(|uid| uid.to_string()),
+        });
+        let saved_impersonation = match (impersonation, self.context.as_mut()) {
+            (Some(impersonation), Some(context)) => {
+                Some(context.set_impersonation(Some(impersonation)))
+            }
+            _ => None,
+        };
+        // only impersonating doesn't change what the selection refers to, so it's kept
+        let isolate = overrides.context.is_some() || overrides.namespace.is_some();
+        let saved_namespace = self.namespace.clone();
+        let saved_state = if isolate {
+            Some((
+                std::mem::replace(&mut self.current_selection, ObjectSelection::None),
+                self.range_str.take(),
+                self.last_objs.take(),
+            ))
+        } else {
+            None
+        };
+        if let Some(ns) = overrides.namespace {
+            self.namespace = Some(ns.to_string());
+        } else if let Some(name) = switch_to.as_ref() {
+            // like switching context, use the namespace last used there
+            if let Some(ns) = self.click_config.context_namespaces.get(name).or_else(|| {
+                self.config
+                    .contexts
+                    .get(name)
+                    .and_then(|c| c.namespace.as_ref())
+            }) {
+                self.namespace = Some(ns.clone());
+            }
+        }
+
+        let res = f(self);
+
+        if let (Some(saved), Some(context)) = (saved_impersonation, self.context.as_mut()) {
+            context.set_impersonation(saved);
+        }
+        if let Some(original) = saved_context {
+            if let Some(overriding) = std::mem::replace(&mut self.context, original) {
+                self.cache_context(overriding);
+            }
+        }
+        self.namespace = saved_namespace;
+        if let Some((selection, range_str, last_objs)) = saved_state {
+            self.current_selection = selection;
+            self.range_str = range_str;
+            self.last_objs = last_objs;
+        }
+        self.set_prompt();
+        res
+    }
+
+    /// The kubectl flags to impersonate whoever the current context impersonates, for commands we
+    /// run kubectl for
+    pub fn kubectl_impersonation_args(&self) -> Vec<String> {
+        self.context
+            .as_ref()
+            .and_then(|c| c.impersonation())
+            .map(|i| i.kubectl_args())
+            .unwrap_or_default()
+    }
+
+    /// Impersonate someone in the current context (or stop, with None), until the context changes
+    pub fn set_impersonation(&mut self, impersonation: Option<Impersonation>) -> bool {
+        match self.context.as_mut() {
+            Some(context) => {
+                context.set_impersonation(impersonation);
+                self.set_prompt();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Log requests to stderr at verbosity (see crate::trace). If persist is set it's saved in the
+    /// click config, otherwise it only lasts until click exits
+    pub fn set_verbosity(&mut self, verbosity: u8, persist: bool) {
+        let verbosity = verbosity.min(crate::trace::MAX_VERBOSITY);
+        self.verbosity = verbosity;
+        if persist {
+            self.click_config.verbose = verbosity;
+        }
+        if let Some(context) = self.context.as_mut() {
+            context.set_verbosity(verbosity);
+        }
+    }
+
+    /// Turn the proxy on or off for the current context, and reconnect so it takes effect. The
+    /// setting is remembered for the context. Returns false if there's no current context
+    pub fn set_proxy_enabled(&mut self, enabled: bool) -> Result<bool, ClickError> {
+        let (name, impersonation) = match self.context.as_ref() {
+            Some(context) => (context.name.clone(), context.impersonation().cloned()),
+            None => return Ok(false),
+        };
+        if enabled {
+            self.click_config.proxy_disabled_contexts.remove(&name);
+        } else {
+            self.click_config
+                .proxy_disabled_contexts
+                .insert(name.clone());
+        }
+        self.context_cache.retain(|c| c.name != name);
+        let mut context = self.config.get_context(&name, &self.click_config)?;
+        context.set_interrupt(self.ctrlcbool.clone());
+        context.set_verbosity(self.verbosity);
+        context.set_impersonation(impersonation);
+        self.context = Some(context);
+        Ok(true)
+    }
+
+    /// Move click's saved state for context old over to context new, and point old's bookmarks at
+    /// new. If new has saved state of its own this fails, unless replace is set, in which case
+    /// new's state is dropped first. Returns false, changing nothing, if there was no state saved
+    /// for old
+    pub fn rename_context_state(
+        &mut self,
+        old: &str,
+        new: &str,
+        replace: bool,
+    ) -> Result<bool, ClickError> {
+        let has_bookmarks = self
+            .click_config
+            .bookmarks
+            .values()
+            .any(|bookmark| bookmark.context == old);
+        if !self.has_context_state(old) && !has_bookmarks {
+            return Ok(false);
+        }
+        if old != new && self.has_context_state(new) {
+            if !replace {
+                return Err(ClickError::CommandError(format!(
+                    "{} already has saved state, use --force to replace it with the state for {}",
+                    new, old
+                )));
+            }
+            self.forget_context_state(new);
+        }
+        let _ = std::fs::rename(
+            self.context_history_path(old),
+            self.context_history_path(new),
+        );
+        for bookmark in self.click_config.bookmarks.values_mut() {
+            if bookmark.context == old {
+                bookmark.context = new.to_string();
+            }
+        }
+        if self.click_config.proxy_disabled_contexts.remove(old) {
+            self.click_config
+                .proxy_disabled_contexts
+                .insert(new.to_string());
+        }
+        if let Some(ns) = self.click_config.context_namespaces.remove(old) {
+            self.click_config
+                .context_namespaces
+                .insert(new.to_string(), ns);
+        }
+        Ok(true)
+    }
+
+    /// Does click have saved state for the specified context (not counting bookmarks, which
+    /// belong to the user rather than the context)
+    fn has_context_state(&self, context: &str) -> bool {
+        self.context_history_path(context).exists()
+            || self.click_config.context_namespaces.contains_key(context)
+            || self.click_config.proxy_disabled_contexts.contains(context)
+    }
+
+    /// Drop click's saved state for the specified context. Returns false if there was no state
+    /// saved for it
+    pub fn forget_context_state(&mut self, context: &str) -> bool {
+        let removed_history = std::fs::remove_file(self.context_history_path(context)).is_ok();
+        let removed_proxy = self.click_config.proxy_disabled_contexts.remove(context);
+        self.click_config
+            .context_namespaces
+            .remove(context)
+            .is_some()
+            || removed_history
+            || removed_proxy
+    }
+
+    /// The file command history is kept in. This is click.history next to the click config, or a
+    /// file for the current context if history is per context
+    pub fn history_path(&self) -> PathBuf {
+        match (&self.click_config.history, self.context.as_ref()) {
+            (config::HistoryMode::PerContext, Some(context)) => {
+                self.context_history_path(&context.name)
+            }
+            _ => self.click_config_path.with_file_name("click.history"),
+        }
+    }
+
+    /// The history file for the specified context, when history is per context. Characters that
+    /// aren't safe in file names (context names often have / or :) are replaced with _
+    fn context_history_path(&self, context: &str) -> PathBuf {
+        let safe_name: String = context
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() || c == '-' || c == '.' {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .collect();
+        self.click_config_path
+            .with_file_name(format!("click.history.{}", safe_name))
+    }
+
     pub fn set_namespace(&mut self, namespace: Option<&str>) {
         let mut do_clear = false;
         if let (&Some(ref my_ns), Some(new_ns)) = (&self.namespace, namespace) {
@@ -181,8 +788,16 @@ impl Env {
         }
         if do_clear {
             self.clear_current();
+            if self.click_config.clear_on_switch {
+                clear_screen();
+            }
         }
         self.namespace = namespace.map(|n| n.to_owned());
+        if let (Some(ctx), Some(ns)) = (self.context.as_ref(), namespace) {
+            self.click_config
+                .context_namespaces
+                .insert(ctx.name.clone(), ns.to_string());
+        }
         self.set_prompt();
     }
 
@@ -190,6 +805,24 @@ impl Env {
         self.click_config.editor = editor.map(|s| s.to_string());
     }
 
+    pub fn set_pager(&mut self, pager: Option<&str>) {
+        self.click_config.pager = pager.map(|s| s.to_string());
+    }
+
+    /// The command to page output through: the pager setting, otherwise $PAGER, otherwise less.
+    /// less needs -R to pass our colors through
+    pub fn pager_command(&self) -> String {
+        self.click_config
+            .pager
+            .clone()
+            .or_else(|| std::env::var("PAGER").ok().filter(|p| !p.is_empty()))
+            .unwrap_or_else(|| "less -R".to_string())
+    }
+
+    pub fn set_clipboard(&mut self, clipboard: Option<&str>) {
+        self.click_config.clipboard = clipboard.map(|s| s.to_string());
+    }
+
     pub fn set_terminal(&mut self, terminal: Option<&str>) {
         self.click_config.terminal = terminal.map(|s| s.to_string());
     }
@@ -199,6 +832,10 @@ impl Env {
         self.need_new_editor = true;
     }
 
+    pub fn set_history_mode(&mut self, mode: config::HistoryMode) {
+        self.click_config.history = mode;
+    }
+
     pub fn set_edit_mode(&mut self, editmode: config::EditMode) {
         self.click_config.editmode = editmode;
         self.need_new_editor = true;
@@ -235,6 +872,32 @@ impl Env {
         }
     }
 
+    /// Bookmark the current context and namespace as name, replacing any bookmark already called
+    /// that. Returns false if there's no current context to bookmark
+    pub fn add_bookmark(&mut self, name: &str) -> bool {
+        let context = match self.context.as_ref() {
+            Some(context) => context.name.clone(),
+            None => return false,
+        };
+        self.click_config.bookmarks.insert(
+            name.to_string(),
+            config::Bookmark {
+                context,
+                namespace: self.namespace.clone(),
+            },
+        );
+        self.save_click_config();
+        true
+    }
+
+    pub fn remove_bookmark(&mut self, name: &str) -> bool {
+        let removed = self.click_config.bookmarks.remove(name).is_some();
+        if removed {
+            self.save_click_config();
+        }
+        removed
+    }
+
     pub fn set_last_objs<T: Into<Vec<KObj>>>(&mut self, objs: T) {
         self.last_objs = Some(objs.into());
     }
@@ -255,14 +918,92 @@ impl Env {
     }
 
     pub fn set_current(&mut self, num: usize) {
-        self.current_selection = match self.item_at(num) {
-            Some(obj) => ObjectSelection::Single(obj.clone()),
-            None => ObjectSelection::None,
-        };
+        match self.item_at(num).cloned() {
+            Some(obj) => self.set_current_obj(obj),
+            None => {
+                self.current_selection = ObjectSelection::None;
+                self.range_str = None;
+                self.set_prompt();
+            }
+        }
+    }
+
+    /// Make obj the current object. Selecting a namespace also switches to it, so `namespaces`
+    /// followed by a number is a quick way to change namespace
+    pub fn set_current_obj(&mut self, obj: KObj) {
+        if obj.typ == ObjType::Namespace {
+            self.set_namespace(Some(obj.name()));
+        }
+        self.current_selection = ObjectSelection::Single(obj);
         self.range_str = None;
         self.set_prompt();
     }
 
+    /// Find objects in the last list called name. name can be of the form namespace/name to pick
+    /// out an object in a particular namespace
+    pub fn find_by_name(&self, name: &str) -> NameMatch<'_> {
+        let (namespace, name) = match name.split_once('/') {
+            Some((ns, n)) => (Some(ns), n),
+            None => (None, name),
+        };
+        let found: Vec<&KObj> = match self.last_objs.as_ref() {
+            Some(objs) => objs
+                .iter()
+                .filter(|obj| {
+                    obj.name == name
+                        && (namespace.is_none() || obj.namespace.as_deref() == namespace)
+                })
+                .collect(),
+            None => vec![],
+        };
+        match found.len() {
+            0 => NameMatch::None,
+            1 => NameMatch::Unique(found[0]),
+            _ => NameMatch::Ambiguous(found),
+        }
+    }
+
+    /// Select the object in the last list called name. If more than one object has that name, ask
+    /// which namespace is meant. Returns the selected object, or None if nothing was selected
+    pub fn select_by_name(&mut self, name: &str, writer: &mut ClickWriter) -> Option<KObj> {
+        let obj = match self.find_by_name(name) {
+            NameMatch::None => return None,
+            NameMatch::Unique(obj) => obj.clone(),
+            NameMatch::Ambiguous(objs) => {
+                clickwriteln!(writer, "{} exists in more than one namespace:", name);
+                for (i, obj) in objs.iter().enumerate() {
+                    clickwriteln!(
+                        writer,
+                        "  {} = {}",
+                        i,
+                        obj.namespace.as_deref().unwrap_or("[none]")
+                    );
+                }
+                clickwrite!(writer, "Which namespace? [0-{}]? ", objs.len() - 1);
+                writer.flush().unwrap_or(());
+                let mut choice = String::new();
+                if io::stdin().read_line(&mut choice).is_err() {
+                    clickwriteln!(writer, "Could not read response, not selecting anything");
+                    return None;
+                }
+                match choice
+                    .trim()
+                    .parse::<usize>()
+                    .ok()
+                    .and_then(|i| objs.get(i))
+                {
+                    Some(obj) => (*obj).clone(),
+                    None => {
+                        clickwriteln!(writer, "Invalid choice, not selecting anything");
+                        return None;
+                    }
+                }
+            }
+        };
+        self.set_current_obj(obj.clone());
+        Some(obj)
+    }
+
     pub fn set_range(&mut self, range: Vec<KObj>) {
         let range_str = if range.is_empty() {
             "Empty range".to_string()
@@ -279,6 +1020,27 @@ impl Env {
         self.set_prompt();
     }
 
+    /// Drop objs (which have been deleted, say) from the current selection, clearing it if nothing
+    /// is left
+    pub fn remove_from_selection(&mut self, objs: &[KObj]) {
+        match &self.current_selection {
+            ObjectSelection::Single(obj) if objs.contains(obj) => self.clear_current(),
+            ObjectSelection::Range(range) => {
+                let remaining: Vec<KObj> = range
+                    .iter()
+                    .filter(|obj| !objs.contains(obj))
+                    .cloned()
+                    .collect();
+                if remaining.is_empty() {
+                    self.clear_current();
+                } else if remaining.len() != range.len() {
+                    self.set_range(remaining);
+                }
+            }
+            _ => {}
+        }
+    }
+
     pub fn current_pod(&self) -> Option<&KObj> {
         match self.current_selection {
             ObjectSelection::Single(ref obj) => match obj.typ {
@@ -309,7 +1071,7 @@ impl Env {
             clickwriteln!(writer, "  a = all: continue over all future errors");
             clickwriteln!(writer, "  n/N = no: abort range operation (default)");
             clickwrite!(writer, "Continue? [o/a/N]? ");
-            io::stdout().flush().expect("Could not flush stdout");
+            writer.flush().unwrap_or(());
             let mut conf = String::new();
             if io::stdin().read_line(&mut conf).is_ok() {
                 match conf.trim() {
@@ -404,9 +1166,255 @@ impl Env {
         }
     }
 
+    /// Whether namespace exists in the current context. A namespace in a cached namespaces list is
+    /// known to exist, otherwise the server is asked. None if there's no active context or we can't
+    /// tell, say because we aren't allowed to read namespaces
+    pub fn namespace_exists(&self, namespace: &str) -> Option<bool> {
+        let context = self.context.as_ref()?;
+        // not a valid name, so it can't exist, and mustn't be put in a path
+        if namespace.is_empty()
+            || !namespace
+                .chars()
+                .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+        {
+            return Some(false);
+        }
+        if self.click_config.cache_ttl_secs > 0 {
+            let ttl = Duration::from_secs(self.click_config.cache_ttl_secs.into());
+            let kind = <k8s_openapi::api::core::v1::Namespace as k8s_openapi::Resource>::KIND;
+            let cached = self
+                .list_cache
+                .lists_of(&context.name, kind, ttl)
+                .filter_map(|list| list.get("items").and_then(|items| items.as_array()))
+                .flatten()
+                .any(|item| {
+                    item.pointer("/metadata/name").and_then(|n| n.as_str()) == Some(namespace)
+                });
+            if cached {
+                return Some(true);
+            }
+        }
+        let request = k8s_openapi::http::Request::get(format!("/api/v1/namespaces/{}", namespace))
+            .body(vec![])
+            .ok()?;
+        match context.execute(request).ok()?.status() {
+            k8s_openapi::http::StatusCode::OK => Some(true),
+            k8s_openapi::http::StatusCode::NOT_FOUND => Some(false),
+            _ => None,
+        }
+    }
+
+    /// Get the key for caching the list of kind fetched with uri in the current context, or None
+    /// if there's no active context. The namespace is the one in the uri, so lists of cluster
+    /// scoped kinds and lists across all namespaces are under None, and get dropped when any object
+    /// of their kind changes
+    pub fn list_cache_key(&self, kind: &str, uri: &str) -> Option<CacheKey> {
+        self.context.as_ref().map(|c| CacheKey {
+            context: c.name.clone(),
+            namespace: uri_namespace(uri),
+            kind: kind.to_string(),
+        })
+    }
+
+    /// Get a cached list, if caching is enabled and it's not expired
+    pub fn get_cached_list(
+        &self,
+        key: &CacheKey,
+        uri: &str,
+    ) -> Option<(&serde_json::Value, Duration)> {
+        if self.click_config.cache_ttl_secs == 0 {
+            None
+        } else {
+            let ttl = Duration::from_secs(self.click_config.cache_ttl_secs.into());
+            self.list_cache.get(key, uri, ttl)
+        }
+    }
+
+    /// Get the object names cached for completion under key, if they were fetched less than ttl ago
+    pub fn completion_names(&self, key: &CacheKey, ttl: Duration) -> Option<Vec<String>> {
+        match self.completion_names.borrow().as_ref() {
+            Some((cached_key, fetched, names)) if cached_key == key && fetched.elapsed() < ttl => {
+                Some(names.clone())
+            }
+            _ => None,
+        }
+    }
+
+    pub fn cache_completion_names(&self, key: CacheKey, names: Vec<String>) {
+        self.completion_names
+            .replace(Some((key, Instant::now(), names)));
+    }
+
+    pub fn cache_list<T: serde::Serialize>(&mut self, key: CacheKey, uri: String, list: &T) {
+        if self.click_config.cache_ttl_secs > 0 {
+            if let Ok(val) = serde_json::to_value(list) {
+                self.list_cache.insert(key, uri, val);
+            }
+        }
+    }
+
+    /// Call this after a command changes the selected object(s), so later list commands don't
+    /// show stale data
+    pub fn invalidate_cache_for_selection(&mut self) {
+        if let Some(ref c) = self.context {
+            let objs = match self.current_selection {
+                ObjectSelection::Single(ref obj) => std::slice::from_ref(obj),
+                ObjectSelection::Range(ref objs) => objs.as_slice(),
+                ObjectSelection::None => &[],
+            };
+            for obj in objs.iter() {
+                self.list_cache
+                    .invalidate(&c.name, obj.namespace.as_deref(), obj.type_str());
+                self.describe_cache.borrow_mut().invalidate(
+                    &c.name,
+                    obj.namespace.as_deref(),
+                    obj.type_str(),
+                    obj.name(),
+                );
+            }
+        }
+    }
+
+    /// Call this after a command creates objects of kind in namespace, so later list commands
+    /// show them
+    pub fn invalidate_list_cache(&mut self, namespace: &str, kind: &str) {
+        if let Some(ref c) = self.context {
+            self.list_cache.invalidate(&c.name, Some(namespace), kind);
+        }
+    }
+
+    pub fn clear_list_cache(&mut self) {
+        self.list_cache.clear();
+        self.describe_cache.borrow_mut().clear();
+    }
+
+    fn describe_key(&self, obj: &KObj, options: &str) -> Option<DescribeKey> {
+        self.context.as_ref().map(|c| DescribeKey {
+            context: c.name.clone(),
+            namespace: obj.namespace.clone(),
+            kind: obj.type_str().to_string(),
+            name: obj.name().to_string(),
+            options: options.to_string(),
+        })
+    }
+
+    /// Get the output of describing obj with options, if caching is enabled and it was described
+    /// recently enough. Returns the output and how long ago it was cached
+    pub fn get_cached_describe(&self, obj: &KObj, options: &str) -> Option<(Vec<u8>, Duration)> {
+        if self.click_config.cache_ttl_secs == 0 {
+            return None;
+        }
+        let ttl = Duration::from_secs(self.click_config.cache_ttl_secs.into());
+        let key = self.describe_key(obj, options)?;
+        self.describe_cache
+            .borrow()
+            .get(&key, ttl)
+            .map(|(output, age)| (output.to_vec(), age))
+    }
+
+    pub fn cache_describe(&self, obj: &KObj, options: &str, output: Vec<u8>) {
+        if self.click_config.cache_ttl_secs > 0 {
+            if let Some(key) = self.describe_key(obj, options) {
+                self.describe_cache.borrow_mut().insert(key, output);
+            }
+        }
+    }
+
+    /// Start recording commands to the file at path. Commands are appended if the file exists
+    pub fn start_recording(&mut self, path: &str, skip_read_only: bool) -> Result<(), ClickError> {
+        let file = OpenOptions::new().append(true).create(true).open(path)?;
+        self.recording = Some(Recording {
+            path: path.to_string(),
+            file,
+            skip_read_only,
+        });
+        Ok(())
+    }
+
+    /// Stop recording, returning the recording that was active (if any)
+    pub fn stop_recording(&mut self) -> Option<Recording> {
+        self.recording.take()
+    }
+
+    pub fn recording(&self) -> Option<&Recording> {
+        self.recording.as_ref()
+    }
+
+    /// Write line to the current recording, if there is one
+    pub fn record_line(&mut self, line: &str) {
+        if let Some(recording) = self.recording.as_mut() {
+            if let Err(e) = writeln!(recording.file, "{}", line) {
+                println!(
+                    "Failed to write to recording {}, stopping recording: {}",
+                    recording.path, e
+                );
+                self.recording = None;
+            }
+        }
+    }
+
     /// Add a new task for the env to keep track of
+    fn port_forwards_path(&self) -> PathBuf {
+        self.click_config_path.with_file_name("click.portforwards")
+    }
+
+    /// Read the saved port forwards, from this and other sessions
+    pub fn load_saved_port_forwards(&self) -> Result<Vec<SavedPortForward>, ClickError> {
+        match File::open(self.port_forwards_path()) {
+            Ok(f) => serde_yaml::from_reader(f).map_err(ClickError::from),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(vec![]),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Write out our running port forwards, replacing what this session saved before
+    pub fn save_port_forwards(&self) {
+        self.write_port_forwards(true, &[]);
+    }
+
+    /// Remove forwards saved by other sessions, for when they've been restored
+    pub fn forget_saved_port_forwards(&self, forget: &[SavedPortForward]) {
+        self.write_port_forwards(true, forget);
+    }
+
+    /// Save our forwards, keeping those of other sessions, except ones in forget and ones whose
+    /// kubectl has gone. If running is false ours are saved without a pid, marking them as stopped
+    /// at exit and able to be restored
+    fn write_port_forwards(&self, running: bool, forget: &[SavedPortForward]) {
+        let others = match self.load_saved_port_forwards() {
+            Ok(saved) => saved,
+            Err(e) => {
+                println!("[WARN] Could not read saved port forwards: {}", e);
+                vec![]
+            }
+        };
+        let mut saved: Vec<SavedPortForward> = others
+            .into_iter()
+            .filter(|pf| !pf.owned_by_us() && !forget.contains(pf))
+            .filter(|pf| pf.pid.is_none() || pf.forward_running())
+            .collect();
+        saved.extend(self.port_forwards.iter().map(|pf| {
+            let pid = pf.child.id();
+            SavedPortForward {
+                session_pid: Some(std::process::id()),
+                session_started: SESSION_STARTED.clone(),
+                context: pf.context.clone(),
+                namespace: pf.namespace.clone(),
+                pod: pf.pod.clone(),
+                ports: pf.ports.clone(),
+                pid: if running { Some(pid) } else { None },
+                started: if running { process_started(pid) } else { None },
+            }
+        }));
+        let af = AtomicFile::new(self.port_forwards_path(), AllowOverwrite);
+        if let Err(e) = af.write(|f| serde_yaml::to_writer(f, &saved)) {
+            println!("[WARN] Failed to save port forwards: {}", e);
+        }
+    }
+
     pub fn add_port_forward(&mut self, pf: PortForward) {
         self.port_forwards.push(pf);
+        self.save_port_forwards();
     }
 
     pub fn get_port_forwards(&mut self) -> std::slice::IterMut<PortForward> {
@@ -420,6 +1428,7 @@ impl Env {
     pub fn stop_port_forward(&mut self, i: usize) -> Result<(), std::io::Error> {
         if i < self.port_forwards.len() {
             let mut pf = self.port_forwards.remove(i);
+            self.save_port_forwards();
             pf.child.kill()
         } else {
             Ok(())
@@ -428,7 +1437,20 @@ impl Env {
 
     pub fn stop_all_forwards(&mut self) {
         for pf in self.port_forwards.iter_mut() {
-            pf.child.kill().unwrap();
+            // the child may already have died, which is fine
+            pf.child.kill().unwrap_or(());
+        }
+        self.port_forwards = Vec::new();
+        self.save_port_forwards();
+    }
+
+    /// Stop all forwards because click is exiting, saving them so they can be restored by the
+    /// next session
+    pub fn shutdown_forwards(&mut self) {
+        self.write_port_forwards(false, &[]);
+        for pf in self.port_forwards.iter_mut() {
+            // the child may already have died, which is fine
+            pf.child.kill().unwrap_or(());
         }
         self.port_forwards = Vec::new();
     }
@@ -477,8 +1499,14 @@ impl fmt::Display for Env {
   Edit Mode: {}
   Editor: {}
   Terminal: {}
+  Paging: {}
+  Clipboard: {}
+  Proxy: {}
   Range Separator: {}
   Describe Shows Events: {}
+  List Cache TTL (secs): {}
+  Max List Items: {}
+  Command History: {}
 }}",
             if let Some(ref c) = self.context {
                 Green.bold().paint(c.name.as_str())
@@ -507,16 +1535,695 @@ impl fmt::Display for Env {
                     .as_ref()
                     .unwrap_or(&"<unset, will use xterm>".to_owned())
             ),
+            Green.paint(if self.click_config.paging {
+                format!("on ({})", self.pager_command())
+            } else {
+                "off".to_string()
+            }),
+            Green.paint(
+                self.click_config
+                    .clipboard
+                    .as_ref()
+                    .unwrap_or(&"<unset, will use pbcopy, wl-copy, xclip or xsel>".to_owned())
+            ),
+            Green.paint(match self.context.as_ref() {
+                Some(context) => context.proxy().to_string(),
+                None => "<no active context>".to_string(),
+            }),
             Green.paint(&self.click_config.range_separator),
             Green.paint(&self.click_config.describe_include_events.to_string()),
+            Green.paint(self.click_config.cache_ttl_secs.to_string()),
+            Green.paint(if self.click_config.max_items == 0 {
+                "unlimited".to_string()
+            } else {
+                self.click_config.max_items.to_string()
+            }),
+            {
+                let hmstr: String = (&self.click_config.history).into();
+                Green.paint(hmstr)
+            },
         )
     }
 }
 
+/// The namespace a list request uri is for, None if it's for a cluster scoped kind or across all
+/// namespaces
+fn uri_namespace(uri: &str) -> Option<String> {
+    let path = uri.split('?').next().unwrap_or_default();
+    let segments: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+    // .../namespaces/<namespace>/<kind>, rather than a list or get of namespaces themselves
+    segments
+        .windows(3)
+        .find(|w| w[0] == "namespaces")
+        .map(|w| w[1].to_string())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::config::get_test_config;
+    use crate::config::{get_test_config, ContextConf};
+    use std::sync::atomic::AtomicUsize;
+
+    fn make_pod_kobj(name: &str, namespace: &str) -> KObj {
+        KObj {
+            name: name.to_string(),
+            namespace: Some(namespace.to_string()),
+            typ: ObjType::Pod { containers: vec![] },
+        }
+    }
+
+    fn dup_name_env() -> Env {
+        let mut env = Env::new(
+            get_test_config(),
+            ClickConfig::default(),
+            PathBuf::from("/tmp/click.config"),
+        );
+        env.set_last_objs(vec![
+            make_pod_kobj("dup", "ns1"),
+            make_pod_kobj("dup", "ns2"),
+            make_pod_kobj("single", "ns1"),
+        ]);
+        env
+    }
+
+    #[test]
+    fn fuzzy_match_tiers() {
+        let names: Vec<String> = vec![
+            "gke_myproj_us-prod",
+            "gke_myproj_us-staging",
+            "prod-eu",
+            "Prod",
+            "dev",
+        ]
+        .into_iter()
+        .map(|s| s.to_string())
+        .collect();
+        // case insensitive exact match beats prefix matches
+        assert_eq!(fuzzy_match("prod", &names), vec!["Prod"]);
+        assert_eq!(fuzzy_match("PROD-", &names), vec!["prod-eu"]);
+        assert_eq!(fuzzy_match("us-prod", &names), vec!["gke_myproj_us-prod"]);
+        assert_eq!(
+            fuzzy_match("myproj", &names),
+            vec!["gke_myproj_us-prod", "gke_myproj_us-staging"]
+        );
+        // subsequence
+        assert_eq!(fuzzy_match("gkestg", &names), vec!["gke_myproj_us-staging"]);
+        assert_eq!(fuzzy_match("dv", &names), vec!["dev"]);
+        assert!(fuzzy_match("qa", &names).is_empty());
+    }
+
+    #[test]
+    fn remove_deleted_from_selection() {
+        let mut env = dup_name_env();
+        env.set_range(vec![
+            make_pod_kobj("dup", "ns1"),
+            make_pod_kobj("single", "ns1"),
+        ]);
+        env.remove_from_selection(&[make_pod_kobj("dup", "ns1")]);
+        assert_eq!(
+            env.current_selection(),
+            &ObjectSelection::Range(vec![make_pod_kobj("single", "ns1")])
+        );
+        env.remove_from_selection(&[make_pod_kobj("single", "ns1")]);
+        assert_eq!(env.current_selection(), &ObjectSelection::None);
+
+        env.set_current(2);
+        env.remove_from_selection(&[make_pod_kobj("dup", "ns2")]);
+        assert_eq!(
+            env.current_selection(),
+            &ObjectSelection::Single(make_pod_kobj("single", "ns1"))
+        );
+        env.remove_from_selection(&[make_pod_kobj("single", "ns1")]);
+        assert_eq!(env.current_selection(), &ObjectSelection::None);
+    }
+
+    #[test]
+    fn set_current_duplicate_name() {
+        let mut env = dup_name_env();
+        env.set_current(1);
+        assert_eq!(
+            env.current_selection(),
+            &ObjectSelection::Single(make_pod_kobj("dup", "ns2"))
+        );
+        env.set_current(0);
+        assert_eq!(
+            env.current_selection(),
+            &ObjectSelection::Single(make_pod_kobj("dup", "ns1"))
+        );
+    }
+
+    #[test]
+    fn select_namespace_switches() {
+        let mut env = dup_name_env();
+        env.set_namespace(Some("ns1"));
+        let ns2 = KObj {
+            name: "ns2".to_string(),
+            namespace: None,
+            typ: ObjType::Namespace,
+        };
+        env.set_last_objs(vec![ns2.clone()]);
+        env.set_current(0);
+        assert_eq!(env.namespace.as_deref(), Some("ns2"));
+        assert_eq!(env.current_selection(), &ObjectSelection::Single(ns2));
+    }
+
+    #[test]
+    fn find_by_name() {
+        let env = dup_name_env();
+        let dup1 = make_pod_kobj("dup", "ns1");
+        let dup2 = make_pod_kobj("dup", "ns2");
+        let single = make_pod_kobj("single", "ns1");
+        assert_eq!(
+            env.find_by_name("dup"),
+            NameMatch::Ambiguous(vec![&dup1, &dup2])
+        );
+        assert_eq!(env.find_by_name("ns2/dup"), NameMatch::Unique(&dup2));
+        assert_eq!(env.find_by_name("single"), NameMatch::Unique(&single));
+        assert_eq!(env.find_by_name("ns1/single"), NameMatch::Unique(&single));
+        assert_eq!(env.find_by_name("ns2/single"), NameMatch::None);
+        assert_eq!(env.find_by_name("missing"), NameMatch::None);
+    }
+
+    #[test]
+    fn select_by_unique_name() {
+        let mut env = dup_name_env();
+        let mut writer = ClickWriter::with_buffer(vec![], false);
+        assert_eq!(
+            env.select_by_name("ns2/dup", &mut writer),
+            Some(make_pod_kobj("dup", "ns2"))
+        );
+        assert_eq!(
+            env.current_selection(),
+            &ObjectSelection::Single(make_pod_kobj("dup", "ns2"))
+        );
+        assert_eq!(env.select_by_name("missing", &mut writer), None);
+    }
+
+    #[test]
+    fn rename_and_forget_context_state() {
+        let mut cc = ClickConfig::default();
+        cc.context_namespaces
+            .insert("old".to_string(), "ns".to_string());
+        cc.proxy_disabled_contexts.insert("old".to_string());
+        let mut env = Env::new(get_test_config(), cc, PathBuf::from("/tmp/click.config"));
+
+        assert!(env.rename_context_state("old", "new", false).unwrap());
+        assert!(!env.click_config.context_namespaces.contains_key("old"));
+        assert!(env.click_config.proxy_disabled_contexts.contains("new"));
+        assert_eq!(
+            env.click_config.context_namespaces.get("new"),
+            Some(&"ns".to_string())
+        );
+        assert!(!env.rename_context_state("old", "new", false).unwrap());
+
+        // new's state isn't overwritten unless asked
+        env.click_config
+            .context_namespaces
+            .insert("old".to_string(), "other".to_string());
+        assert!(env.rename_context_state("old", "new", false).is_err());
+        assert_eq!(env.click_config.context_namespaces["new"], "ns");
+        assert_eq!(env.click_config.context_namespaces["old"], "other");
+        // and when it is, all of it goes, even what old has nothing to replace with
+        assert!(env.rename_context_state("old", "new", true).unwrap());
+        assert_eq!(env.click_config.context_namespaces["new"], "other");
+        assert!(!env.click_config.context_namespaces.contains_key("old"));
+        assert!(env.click_config.proxy_disabled_contexts.is_empty());
+
+        assert!(env.forget_context_state("new"));
+        assert!(env.click_config.context_namespaces.is_empty());
+        assert!(env.click_config.proxy_disabled_contexts.is_empty());
+        assert!(!env.forget_context_state("new"));
+    }
+
+    fn make_context(name: &str) -> crate::k8s::Context {
+        make_context_at(name, "https://example.com")
+    }
+
+    fn make_context_at(name: &str, server: &str) -> crate::k8s::Context {
+        crate::k8s::Context::new(
+            name,
+            reqwest::Url::parse(server).unwrap(),
+            None,
+            None,
+            None,
+            10,
+            20,
+            0,
+            false,
+            crate::k8s::ProxySetting::Environment,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn persist_port_forwards() {
+        let dir = TempDir::new("click-pf-test").unwrap();
+        let mut env = Env::new(
+            get_test_config(),
+            ClickConfig::default(),
+            dir.path().join("click.config"),
+        );
+        assert!(env.load_saved_port_forwards().unwrap().is_empty());
+
+        let child = std::process::Command::new("sleep")
+            .arg("30")
+            .spawn()
+            .unwrap();
+        let pid = child.id();
+        env.add_port_forward(PortForward {
+            child,
+            context: "ctx".to_string(),
+            namespace: "ns".to_string(),
+            pod: "pod".to_string(),
+            ports: vec!["8080:80".to_string()],
+            output: Arc::new(Mutex::new(String::new())),
+        });
+        let saved = env.load_saved_port_forwards().unwrap();
+        assert_eq!(saved.len(), 1);
+        assert_eq!(saved[0].pod, "pod");
+        assert_eq!(saved[0].pid, Some(pid));
+
+        // exiting keeps the forward, but marks it stopped
+        env.shutdown_forwards();
+        let saved = env.load_saved_port_forwards().unwrap();
+        assert_eq!(saved.len(), 1);
+        assert_eq!(saved[0].pid, None);
+
+        env.stop_all_forwards();
+        assert!(env.load_saved_port_forwards().unwrap().is_empty());
+    }
+
+    #[test]
+    fn port_forwards_from_other_sessions() {
+        let dir = TempDir::new("click-pf-sessions-test").unwrap();
+        let mut env = Env::new(
+            get_test_config(),
+            ClickConfig::default(),
+            dir.path().join("click.config"),
+        );
+        // another click that's still running, with a running forward
+        let mut other = std::process::Command::new("sleep")
+            .arg("30")
+            .spawn()
+            .unwrap();
+        let saved = |pod: &str, session_pid: u32, pid: Option<u32>| SavedPortForward {
+            session_pid: Some(session_pid),
+            session_started: process_started(session_pid),
+            context: "ctx".to_string(),
+            namespace: "ns".to_string(),
+            pod: pod.to_string(),
+            ports: vec!["8080:80".to_string()],
+            pid,
+            started: pid.and_then(process_started),
+        };
+        let live = saved("live", other.id(), Some(other.id()));
+        assert!(live.forward_running() && live.session_running());
+        // a forward stopped when its session exited, and one whose kubectl has gone
+        let stopped = saved("stopped", u32::MAX, None);
+        let stale = SavedPortForward {
+            started: Some("long ago".to_string()),
+            ..saved("stale", u32::MAX, Some(std::process::id()))
+        };
+        assert!(!stale.forward_running());
+        let file = File::create(env.port_forwards_path()).unwrap();
+        serde_yaml::to_writer(file, &vec![live.clone(), stopped.clone(), stale]).unwrap();
+
+        // saving ours keeps the other session's forwards, but drops the stale one
+        let child = std::process::Command::new("sleep")
+            .arg("30")
+            .spawn()
+            .unwrap();
+        env.add_port_forward(PortForward {
+            child,
+            context: "ctx".to_string(),
+            namespace: "ns".to_string(),
+            pod: "ours".to_string(),
+            ports: vec!["9090".to_string()],
+            output: Arc::new(Mutex::new(String::new())),
+        });
+        let pods = |env: &Env| -> Vec<String> {
+            let saved = env.load_saved_port_forwards().unwrap();
+            saved.into_iter().map(|pf| pf.pod).collect()
+        };
+        assert_eq!(pods(&env), vec!["live", "stopped", "ours"]);
+
+        // restoring forgets the stopped one, stopping ours leaves the live one
+        env.forget_saved_port_forwards(&[stopped]);
+        env.stop_all_forwards();
+        assert_eq!(pods(&env), vec!["live"]);
+        other.kill().unwrap();
+        other.wait().unwrap();
+    }
+
+    #[test]
+    fn switching_context_clears_selection() {
+        let mut env = dup_name_env();
+        env.cache_context(make_context("ctx1"));
+        env.cache_context(make_context("ctx2"));
+        env.set_context(Some("ctx1"));
+        env.set_last_objs(vec![make_pod_kobj("pod", "ns1")]);
+        env.set_current(0);
+
+        // setting the same context again keeps everything
+        env.cache_context(make_context("ctx1"));
+        env.set_context(Some("ctx1"));
+        assert_eq!(
+            env.current_selection(),
+            &ObjectSelection::Single(make_pod_kobj("pod", "ns1"))
+        );
+        assert!(env.item_at(0).is_some());
+
+        env.set_context(Some("ctx2"));
+        assert_eq!(env.context.as_ref().map(|c| c.name.as_str()), Some("ctx2"));
+        assert_eq!(env.current_selection(), &ObjectSelection::None);
+        assert!(env.item_at(0).is_none());
+    }
+
+    /// Start a server that answers every request with an empty object, keeping connections open.
+    /// Returns its url, and counts of the connections made to it and of those that were closed
+    fn keep_alive_server() -> (String, Arc<AtomicUsize>, Arc<AtomicUsize>) {
+        use std::io::BufRead;
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let opened = Arc::new(AtomicUsize::new(0));
+        let closed = Arc::new(AtomicUsize::new(0));
+        let (open_count, close_count) = (opened.clone(), closed.clone());
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                open_count.fetch_add(1, Ordering::SeqCst);
+                let mut stream = stream.unwrap();
+                let close_count = close_count.clone();
+                std::thread::spawn(move || {
+                    let mut reader = std::io::BufReader::new(stream.try_clone().unwrap());
+                    loop {
+                        // only gets are sent, so there's no body to read
+                        let mut line = String::new();
+                        while reader.read_line(&mut line).unwrap_or(0) > 0 && line != "\r\n" {
+                            line.clear();
+                        }
+                        if line.is_empty() {
+                            break;
+                        }
+                        let response = "HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\n{}";
+                        if stream.write_all(response.as_bytes()).is_err() {
+                            break;
+                        }
+                    }
+                    close_count.fetch_add(1, Ordering::SeqCst);
+                });
+            }
+        });
+        (url, opened, closed)
+    }
+
+    fn get_version(env: &Env) {
+        let request = k8s_openapi::http::Request::get("/version")
+            .body(vec![])
+            .unwrap();
+        env.run_on_context(|c| c.execute(request)).unwrap();
+    }
+
+    /// Wait up to 5s for count to reach n, connections are closed in the background
+    fn wait_for(count: &AtomicUsize, n: usize) -> usize {
+        let start = std::time::Instant::now();
+        while count.load(Ordering::SeqCst) < n && start.elapsed().as_secs() < 5 {
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+        count.load(Ordering::SeqCst)
+    }
+
+    #[test]
+    fn switching_context_drops_connections() {
+        let (url, opened, closed) = keep_alive_server();
+        let mut env = dup_name_env();
+        env.cache_context(make_context_at("ctx1", &url));
+        env.cache_context(make_context("ctx2"));
+        env.set_context(Some("ctx1"));
+
+        get_version(&env);
+        get_version(&env);
+        // setting the context it's already in isn't a switch
+        env.set_context(Some("ctx1"));
+        get_version(&env);
+        assert_eq!(opened.load(Ordering::SeqCst), 1);
+
+        // the pool goes with the switch, but the context stays cached and reconnects
+        env.set_context(Some("ctx2"));
+        assert_eq!(wait_for(&closed, 1), 1);
+        env.set_context(Some("ctx1"));
+        get_version(&env);
+        assert_eq!(opened.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn overrides_reuse_connections() {
+        let (url, opened, _) = keep_alive_server();
+        let mut env = dup_name_env();
+        env.config.contexts.insert(
+            "ctx1".to_string(),
+            ContextConf {
+                cluster: "c1".to_string(),
+                namespace: None,
+                user: "u1".to_string(),
+                color: None,
+            },
+        );
+        env.cache_context(make_context_at("ctx1", &url));
+        env.cache_context(make_context("ctx2"));
+        env.set_context(Some("ctx2"));
+        let overrides = CommandOverrides {
+            context: Some("ctx1"),
+            ..Default::default()
+        };
+        for _ in 0..3 {
+            env.with_overrides(&overrides, |env| {
+                get_version(env);
+                Ok(())
+            })
+            .unwrap();
+        }
+        assert_eq!(opened.load(Ordering::SeqCst), 1);
+        assert_eq!(env.context.as_ref().unwrap().name, "ctx2");
+    }
+
+    #[test]
+    fn delete_then_list_misses_cache() {
+        let mut env = dup_name_env();
+        env.click_config.cache_ttl_secs = 60;
+        env.cache_context(make_context("ctx1"));
+        env.set_context(Some("ctx1"));
+        env.set_namespace(Some("ns1"));
+        let list = serde_json::json!({"items": []});
+        for uri in &[
+            "/api/v1/nodes",
+            "/api/v1/pods?labelSelector=app",
+            "/api/v1/namespaces/ns1/pods",
+            "/api/v1/namespaces/other/pods",
+        ] {
+            let kind = if uri.ends_with("nodes") {
+                "Node"
+            } else {
+                "Pod"
+            };
+            let key = env.list_cache_key(kind, uri).unwrap();
+            env.cache_list(key, uri.to_string(), &list);
+        }
+        let cached = |env: &Env, kind: &str, uri: &str| {
+            let key = env.list_cache_key(kind, uri).unwrap();
+            env.get_cached_list(&key, uri).is_some()
+        };
+        assert!(cached(&env, "Node", "/api/v1/nodes"));
+
+        // deleting a node drops the nodes list, even though a namespace is set
+        env.set_last_objs(vec![KObj {
+            name: "node1".to_string(),
+            namespace: None,
+            typ: ObjType::Node,
+        }]);
+        env.set_current(0);
+        env.invalidate_cache_for_selection();
+        assert!(!cached(&env, "Node", "/api/v1/nodes"));
+        assert!(cached(&env, "Pod", "/api/v1/pods?labelSelector=app"));
+
+        // deleting a pod drops its namespace's list and the -A list, but not other namespaces
+        env.set_last_objs(vec![make_pod_kobj("pod", "ns1")]);
+        env.set_current(0);
+        env.invalidate_cache_for_selection();
+        assert!(!cached(&env, "Pod", "/api/v1/pods?labelSelector=app"));
+        assert!(!cached(&env, "Pod", "/api/v1/namespaces/ns1/pods"));
+        assert!(cached(&env, "Pod", "/api/v1/namespaces/other/pods"));
+    }
+
+    #[test]
+    fn per_context_history() {
+        let dir = TempDir::new("click-history-test").unwrap();
+        let mut env = Env::new(
+            get_test_config(),
+            ClickConfig::default(),
+            dir.path().join("click.config"),
+        );
+        env.cache_context(make_context("arn:aws:eks:us-west-2:1234:cluster/prod"));
+        env.set_context(Some("arn:aws:eks:us-west-2:1234:cluster/prod"));
+        assert_eq!(env.history_path(), dir.path().join("click.history"));
+
+        env.set_history_mode(config::HistoryMode::PerContext);
+        let prod_path = dir
+            .path()
+            .join("click.history.arn_aws_eks_us-west-2_1234_cluster_prod");
+        assert_eq!(env.history_path(), prod_path);
+
+        // history moves with the rest of the context's state
+        std::fs::write(&prod_path, "pods\n").unwrap();
+        assert!(env
+            .rename_context_state("arn:aws:eks:us-west-2:1234:cluster/prod", "prod", false)
+            .unwrap());
+        assert!(!prod_path.exists());
+        assert!(dir.path().join("click.history.prod").exists());
+
+        // another context's history isn't overwritten without --force
+        std::fs::write(&prod_path, "nodes\n").unwrap();
+        assert!(env
+            .rename_context_state("arn:aws:eks:us-west-2:1234:cluster/prod", "prod", false)
+            .is_err());
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join("click.history.prod")).unwrap(),
+            "pods\n"
+        );
+        assert!(env
+            .rename_context_state("arn:aws:eks:us-west-2:1234:cluster/prod", "prod", true)
+            .unwrap());
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join("click.history.prod")).unwrap(),
+            "nodes\n"
+        );
+        assert!(env.forget_context_state("prod"));
+        assert!(!dir.path().join("click.history.prod").exists());
+    }
+
+    #[test]
+    fn insecure_context_in_prompt() {
+        let mut env = Env::new(
+            get_test_config(),
+            ClickConfig::default(),
+            PathBuf::from("/tmp/click.config"),
+        );
+        env.cache_context(
+            crate::k8s::Context::new(
+                "insecure",
+                reqwest::Url::parse("https://example.com").unwrap(),
+                None,
+                None,
+                None,
+                10,
+                20,
+                0,
+                true,
+                crate::k8s::ProxySetting::Environment,
+            )
+            .unwrap(),
+        );
+        env.cache_context(make_context("secure"));
+        env.set_context(Some("insecure"));
+        assert!(env.prompt.contains("[INSECURE]"));
+        env.set_context(Some("secure"));
+        assert!(!env.prompt.contains("[INSECURE]"));
+    }
+
+    #[test]
+    fn bookmarks() {
+        let mut env = Env::new(
+            get_test_config(),
+            ClickConfig::default(),
+            PathBuf::from("/tmp/click.config"),
+        );
+        assert!(!env.add_bookmark("none"));
+
+        env.cache_context(make_context("old"));
+        env.set_context(Some("old"));
+        env.set_namespace(Some("web"));
+        assert!(env.add_bookmark("w"));
+        assert_eq!(
+            env.click_config.bookmarks.get("w"),
+            Some(&config::Bookmark {
+                context: "old".to_string(),
+                namespace: Some("web".to_string()),
+            })
+        );
+
+        // bookmarks are enough for there to be something to rename
+        env.click_config.context_namespaces.clear();
+        assert!(env.rename_context_state("old", "new", false).unwrap());
+        assert_eq!(env.click_config.bookmarks["w"].context, "new");
+
+        assert!(env.remove_bookmark("w"));
+        assert!(!env.remove_bookmark("w"));
+    }
+
+    #[test]
+    fn context_colour() {
+        let mut config = get_test_config();
+        for (name, color) in [
+            ("prod", Some("purple")),
+            ("plain", None),
+            ("bad", Some("pink")),
+        ] {
+            config.contexts.insert(
+                name.to_string(),
+                ContextConf {
+                    cluster: "c".to_string(),
+                    namespace: None,
+                    user: "u".to_string(),
+                    color: color.map(|c| c.to_string()),
+                },
+            );
+        }
+        let mut env = Env::new(
+            config,
+            ClickConfig::default(),
+            PathBuf::from("/tmp/click.config"),
+        );
+        for name in ["prod", "plain", "bad"] {
+            env.cache_context(make_context(name));
+        }
+
+        env.set_context(Some("prod"));
+        assert_eq!(env.context_colour(), Some(Colour::Purple));
+        assert!(env
+            .prompt
+            .contains(&Colour::Purple.bold().paint("prod").to_string()));
+        assert_eq!(env.table_header_colour(), None);
+        env.click_config.color_table_headers = true;
+        assert_eq!(env.table_header_colour(), Some(Colour::Purple));
+
+        env.set_context(Some("plain"));
+        assert_eq!(env.context_colour(), None);
+        assert!(env.prompt.contains(&Red.bold().paint("plain").to_string()));
+        env.set_context(Some("bad"));
+        assert_eq!(env.context_colour(), None);
+    }
+
+    #[test]
+    fn context_cache_lru() {
+        let mut env = Env::new(
+            get_test_config(),
+            ClickConfig::default(),
+            PathBuf::from("/tmp/click.config"),
+        );
+        for i in 0..CONTEXT_CACHE_SIZE + 1 {
+            env.cache_context(make_context(&format!("ctx{}", i)));
+        }
+        assert_eq!(env.context_cache.len(), CONTEXT_CACHE_SIZE);
+        // ctx0 was least recently used, so was evicted
+        assert!(env.take_cached_context("ctx0").is_none());
+
+        // re-caching moves a context to the most recently used end
+        env.cache_context(make_context("ctx1"));
+        env.cache_context(make_context("ctx6"));
+        assert!(env.take_cached_context("ctx2").is_none());
+        let ctx1 = env.take_cached_context("ctx1");
+        assert_eq!(ctx1.map(|c| c.name), Some("ctx1".to_string()));
+        assert!(env.take_cached_context("ctx1").is_none());
+    }
 
     #[test]
     fn try_expand_alias() {
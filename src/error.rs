@@ -75,7 +75,74 @@ pub enum ClickError {
     JoinPathsError(env::JoinPathsError),
     Pem(pem::PemError),
     Reqwest(reqwest::Error, Option<Value>),
+    Timeout(reqwest::Error),
     UrlParse(url::ParseError),
+    /// The server answered with a status we didn't expect, and the body it sent
+    Status(u16, String),
+}
+
+/// Broad kinds of failure. Used to pick the exit code when running with --exec, so scripts can
+/// tell why a command failed without parsing its output.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ErrorCategory {
+    /// Anything not covered below
+    General,
+    /// A command, its arguments, or some input or response couldn't be parsed
+    Parse,
+    /// The server said we aren't authenticated (401) or not allowed (403)
+    Auth,
+    /// The server said the object doesn't exist (404)
+    NotFound,
+    /// The server rejected a change because of a conflict (409)
+    Conflict,
+    /// The server couldn't be reached, or didn't answer in time
+    Network,
+}
+
+impl ErrorCategory {
+    pub fn from_status(status: u16) -> ErrorCategory {
+        match status {
+            401 | 403 => ErrorCategory::Auth,
+            404 => ErrorCategory::NotFound,
+            409 => ErrorCategory::Conflict,
+            _ => ErrorCategory::General,
+        }
+    }
+
+    /// The process exit code for an error of this kind
+    pub fn exit_code(self) -> i32 {
+        match self {
+            ErrorCategory::General => 1,
+            ErrorCategory::Parse => 2,
+            ErrorCategory::Auth => 3,
+            ErrorCategory::NotFound => 4,
+            ErrorCategory::Conflict => 5,
+            ErrorCategory::Network => 6,
+        }
+    }
+}
+
+impl ClickError {
+    pub fn category(&self) -> ErrorCategory {
+        match self {
+            ClickError::ParseErr(_)
+            | ClickError::Clap(_)
+            | ClickError::DecodeError(_)
+            | ClickError::SerdeJson(_)
+            | ClickError::SerdeYaml(_)
+            | ClickError::ResponseError(_)
+            | ClickError::UrlParse(_) => ErrorCategory::Parse,
+            ClickError::Kube(ClickErrNo::Unauthorized) => ErrorCategory::Auth,
+            ClickError::Status(status, _) => ErrorCategory::from_status(*status),
+            ClickError::Timeout(_) => ErrorCategory::Network,
+            ClickError::Reqwest(err, _) => match err.status() {
+                Some(status) => ErrorCategory::from_status(status.as_u16()),
+                None if err.is_connect() || err.is_request() => ErrorCategory::Network,
+                None => ErrorCategory::General,
+            },
+            _ => ErrorCategory::General,
+        }
+    }
 }
 
 impl fmt::Display for ClickError {
@@ -114,7 +181,16 @@ impl fmt::Display for ClickError {
             ClickError::JoinPathsError(ref err) => write!(f, "Join paths error: {}", err),
             ClickError::Pem(ref err) => write!(f, "Pem error: {}", err),
             ClickError::Reqwest(ref err, _) => write!(f, "Reqwest error: {}", err),
+            ClickError::Timeout(ref err) => write!(
+                f,
+                "Request timed out, the server may be unreachable (see connect_timeout_secs \
+                 and read_timeout_secs in the click config): {}",
+                err
+            ),
             ClickError::UrlParse(ref err) => write!(f, "Error parsing url: {}", err),
+            ClickError::Status(status, ref body) => {
+                write!(f, "Got unexpected status {}: {}", status, body)
+            }
         }
     }
 }
@@ -136,7 +212,9 @@ impl error::Error for ClickError {
             ClickError::JoinPathsError(ref err) => Some(err),
             ClickError::Pem(ref err) => Some(err),
             ClickError::Reqwest(ref err, _) => Some(err),
+            ClickError::Timeout(ref err) => Some(err),
             ClickError::UrlParse(ref err) => Some(err),
+            ClickError::Status(_, _) => None,
         }
     }
 }
@@ -193,7 +271,11 @@ impl From<pem::PemError> for ClickError {
 
 impl From<reqwest::Error> for ClickError {
     fn from(err: reqwest::Error) -> ClickError {
-        ClickError::Reqwest(err, None)
+        if err.is_timeout() {
+            ClickError::Timeout(err)
+        } else {
+            ClickError::Reqwest(err, None)
+        }
     }
 }
 
@@ -0,0 +1,548 @@
+// Copyright 2021 Databricks, Inc.
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+
+// http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Ingresses have moved between api groups over time, and the version of k8s_openapi we use
+//! doesn't know about networking.k8s.io/v1. So we have our own Ingress type, that works with any of
+//! the versions, and use discovery to decide which one to ask the server for
+
+use ansi_term::Colour::Red;
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::ObjectMeta;
+use k8s_openapi::http::{self, Request};
+use k8s_openapi::{ListableResource, Metadata, NamespaceResourceScope, Resource};
+use serde::ser::{Serialize, SerializeStruct, Serializer};
+use serde_json::Value;
+
+use crate::env::Env;
+use crate::error::ClickError;
+
+use std::collections::HashMap;
+use std::marker::PhantomData;
+
+/// An api group/version that can serve ingresses
+pub trait IngressApi {
+    const API_VERSION: &'static str;
+    const GROUP: &'static str;
+    const VERSION: &'static str;
+}
+
+#[derive(Debug)]
+pub struct NetworkingV1;
+impl IngressApi for NetworkingV1 {
+    const API_VERSION: &'static str = "networking.k8s.io/v1";
+    const GROUP: &'static str = "networking.k8s.io";
+    const VERSION: &'static str = "v1";
+}
+
+#[derive(Debug)]
+pub struct NetworkingV1Beta1;
+impl IngressApi for NetworkingV1Beta1 {
+    const API_VERSION: &'static str = "networking.k8s.io/v1beta1";
+    const GROUP: &'static str = "networking.k8s.io";
+    const VERSION: &'static str = "v1beta1";
+}
+
+#[derive(Debug)]
+pub struct ExtensionsV1Beta1;
+impl IngressApi for ExtensionsV1Beta1 {
+    const API_VERSION: &'static str = "extensions/v1beta1";
+    const GROUP: &'static str = "extensions";
+    const VERSION: &'static str = "v1beta1";
+}
+
+/// The api versions we know how to list ingresses from, most preferred first
+pub const INGRESS_API_VERSIONS: &[&str] = &[
+    NetworkingV1::API_VERSION,
+    NetworkingV1Beta1::API_VERSION,
+    ExtensionsV1Beta1::API_VERSION,
+];
+
+/// An ingress from any of the api versions. The spec and status are kept as json since their shape
+/// depends on the version, use the methods below to get at them
+#[derive(Debug, Deserialize)]
+#[serde(bound = "")]
+pub struct Ingress<V> {
+    pub metadata: ObjectMeta,
+    #[serde(default)]
+    pub spec: Option<Value>,
+    #[serde(default)]
+    pub status: Option<Value>,
+    #[serde(skip)]
+    api: PhantomData<V>,
+}
+
+impl<V: IngressApi> Resource for Ingress<V> {
+    const API_VERSION: &'static str = V::API_VERSION;
+    const GROUP: &'static str = V::GROUP;
+    const KIND: &'static str = "Ingress";
+    const VERSION: &'static str = V::VERSION;
+    const URL_PATH_SEGMENT: &'static str = "ingresses";
+    type Scope = NamespaceResourceScope;
+}
+
+impl<V: IngressApi> ListableResource for Ingress<V> {
+    const LIST_KIND: &'static str = "IngressList";
+}
+
+impl<V: IngressApi> Metadata for Ingress<V> {
+    type Ty = ObjectMeta;
+
+    fn metadata(&self) -> &ObjectMeta {
+        &self.metadata
+    }
+
+    fn metadata_mut(&mut self) -> &mut ObjectMeta {
+        &mut self.metadata
+    }
+}
+
+impl<V: IngressApi> Serialize for Ingress<V> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("Ingress", 5)?;
+        state.serialize_field("apiVersion", V::API_VERSION)?;
+        state.serialize_field("kind", "Ingress")?;
+        state.serialize_field("metadata", &self.metadata)?;
+        if let Some(spec) = self.spec.as_ref() {
+            state.serialize_field("spec", spec)?;
+        }
+        if let Some(status) = self.status.as_ref() {
+            state.serialize_field("status", status)?;
+        }
+        state.end()
+    }
+}
+
+/// Format a backend as service:port. v1 has service.name and service.port.{number,name}, older
+/// versions have serviceName and servicePort
+fn backend_str(backend: &Value) -> String {
+    if let Some(service) = backend.get("service") {
+        let name = service["name"].as_str().unwrap_or("<unknown>");
+        let port = &service["port"];
+        match (port["number"].as_i64(), port["name"].as_str()) {
+            (Some(number), _) => format!("{}:{}", name, number),
+            (None, Some(port_name)) => format!("{}:{}", name, port_name),
+            (None, None) => name.to_string(),
+        }
+    } else if let Some(name) = backend["serviceName"].as_str() {
+        match &backend["servicePort"] {
+            Value::Number(port) => format!("{}:{}", name, port),
+            Value::String(port) => format!("{}:{}", name, port),
+            _ => name.to_string(),
+        }
+    } else if let Some(resource) = backend.get("resource") {
+        format!(
+            "{}/{}",
+            resource["kind"].as_str().unwrap_or("<unknown>"),
+            resource["name"].as_str().unwrap_or("<unknown>")
+        )
+    } else {
+        "<unknown>".to_string()
+    }
+}
+
+/// A port of a service, by number or name
+#[derive(Debug, PartialEq)]
+pub enum ServicePort<'a> {
+    Number(i64),
+    Name(&'a str),
+}
+
+/// The service a backend sends traffic to, and the port if it gives one. None for resource
+/// backends
+fn backend_service(backend: &Value) -> Option<(&str, Option<ServicePort<'_>>)> {
+    if let Some(service) = backend.get("service") {
+        let port = &service["port"];
+        let port = match (port["number"].as_i64(), port["name"].as_str()) {
+            (Some(number), _) => Some(ServicePort::Number(number)),
+            (None, Some(port_name)) => Some(ServicePort::Name(port_name)),
+            (None, None) => None,
+        };
+        Some((service["name"].as_str()?, port))
+    } else {
+        let port = match &backend["servicePort"] {
+            Value::Number(port) => port.as_i64().map(ServicePort::Number),
+            Value::String(port) => Some(ServicePort::Name(port)),
+            _ => None,
+        };
+        Some((backend["serviceName"].as_str()?, port))
+    }
+}
+
+/// What we found out about a service that an ingress sends traffic to
+#[derive(Debug)]
+pub enum ServiceHealth {
+    /// The service exists, with these ports (number and name), and its endpoints have this many
+    /// ready and not ready addresses
+    Found {
+        ports: Vec<(i64, Option<String>)>,
+        ready: usize,
+        not_ready: usize,
+    },
+    NotFound,
+    /// We couldn't find out, because of the error in the string
+    Unknown(String),
+}
+
+impl ServiceHealth {
+    /// The health of a service, given it and its endpoints. Services without endpoints have none
+    /// ready
+    pub fn from_values(service: &Value, endpoints: Option<&Value>) -> ServiceHealth {
+        let ports = service
+            .pointer("/spec/ports")
+            .and_then(|ports| ports.as_array())
+            .map(|ports| {
+                ports
+                    .iter()
+                    .map(|port| {
+                        (
+                            port["port"].as_i64().unwrap_or(0),
+                            port["name"].as_str().map(|name| name.to_string()),
+                        )
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        let count = |key: &str| -> usize {
+            endpoints
+                .and_then(|endpoints| endpoints["subsets"].as_array())
+                .map(|subsets| {
+                    subsets
+                        .iter()
+                        .map(|subset| subset[key].as_array().map(|a| a.len()).unwrap_or(0))
+                        .sum()
+                })
+                .unwrap_or(0)
+        };
+        ServiceHealth::Found {
+            ports,
+            ready: count("addresses"),
+            not_ready: count("notReadyAddresses"),
+        }
+    }
+
+    /// How sending traffic to port on this service will go: Ok with the number of ready endpoints,
+    /// or Err with what's wrong
+    fn check(&self, port: Option<&ServicePort>) -> Result<String, String> {
+        match self {
+            ServiceHealth::Found {
+                ports,
+                ready,
+                not_ready,
+            } => {
+                let has_port = match port {
+                    Some(ServicePort::Number(number)) => ports.iter().any(|(p, _)| p == number),
+                    Some(ServicePort::Name(name)) => {
+                        ports.iter().any(|(_, n)| n.as_deref() == Some(*name))
+                    }
+                    None => true,
+                };
+                let not_ready = if *not_ready > 0 {
+                    format!(" ({} not ready)", not_ready)
+                } else {
+                    String::new()
+                };
+                if !has_port {
+                    Err("service has no such port".to_string())
+                } else if *ready == 0 {
+                    Err(format!("no ready endpoints{}", not_ready))
+                } else {
+                    Ok(format!("{} ready{}", ready, not_ready))
+                }
+            }
+            ServiceHealth::NotFound => Err("service not found".to_string()),
+            ServiceHealth::Unknown(err) => Err(format!("couldn't check service: {}", err)),
+        }
+    }
+}
+
+impl<V> Ingress<V> {
+    fn rules(&self) -> &[Value] {
+        self.spec
+            .as_ref()
+            .and_then(|spec| spec["rules"].as_array())
+            .map(|rules| rules.as_slice())
+            .unwrap_or(&[])
+    }
+
+    /// The ingress class, from the spec or the older annotation
+    pub fn class(&self) -> Option<&str> {
+        self.spec
+            .as_ref()
+            .and_then(|spec| spec["ingressClassName"].as_str())
+            .or_else(|| {
+                self.metadata
+                    .annotations
+                    .get("kubernetes.io/ingress.class")
+                    .map(|class| class.as_str())
+            })
+    }
+
+    /// The hosts the rules apply to. A rule without a host applies to all hosts, shown as *
+    pub fn hosts(&self) -> Vec<&str> {
+        let mut hosts: Vec<&str> = self
+            .rules()
+            .iter()
+            .map(|rule| rule["host"].as_str().unwrap_or("*"))
+            .collect();
+        hosts.dedup();
+        hosts
+    }
+
+    /// The addresses the ingress controller has given this ingress
+    pub fn addresses(&self) -> Vec<&str> {
+        self.status
+            .as_ref()
+            .and_then(|status| status.pointer("/loadBalancer/ingress"))
+            .and_then(|ingress| ingress.as_array())
+            .map(|ingress| {
+                ingress
+                    .iter()
+                    .filter_map(|lb| lb["ip"].as_str().or_else(|| lb["hostname"].as_str()))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// 80, plus 443 if the ingress has tls configured
+    pub fn ports(&self) -> &'static str {
+        let has_tls = self
+            .spec
+            .as_ref()
+            .and_then(|spec| spec["tls"].as_array())
+            .map(|tls| !tls.is_empty())
+            .unwrap_or(false);
+        if has_tls {
+            "80, 443"
+        } else {
+            "80"
+        }
+    }
+
+    /// The host, path and backend of each path in the rules
+    fn path_backends(&self) -> Vec<(&str, &str, &Value)> {
+        let mut backends = vec![];
+        for rule in self.rules() {
+            let host = rule["host"].as_str().unwrap_or("*");
+            if let Some(paths) = rule.pointer("/http/paths").and_then(|p| p.as_array()) {
+                for path in paths {
+                    backends.push((host, path["path"].as_str().unwrap_or("/"), &path["backend"]));
+                }
+            }
+        }
+        backends
+    }
+
+    /// The backend for requests that don't match any rule, if there is one
+    fn default_backend(&self) -> Option<&Value> {
+        // v1 calls it defaultBackend, older versions backend
+        self.spec
+            .as_ref()
+            .and_then(|spec| spec.get("defaultBackend").or_else(|| spec.get("backend")))
+    }
+
+    /// Each rule flattened into a "host → path → service:port" line, plus the default backend if
+    /// there is one
+    pub fn rule_lines(&self) -> Vec<String> {
+        let mut lines: Vec<String> = self
+            .path_backends()
+            .into_iter()
+            .map(|(host, path, backend)| format!("{} → {} → {}", host, path, backend_str(backend)))
+            .collect();
+        if let Some(backend) = self.default_backend() {
+            lines.push(format!("(default) → {}", backend_str(backend)));
+        }
+        lines
+    }
+
+    /// The names of the services the ingress sends traffic to
+    pub fn backend_services(&self) -> Vec<&str> {
+        let mut services: Vec<&str> = self
+            .path_backends()
+            .into_iter()
+            .map(|(_, _, backend)| backend)
+            .chain(self.default_backend())
+            .filter_map(|backend| backend_service(backend).map(|(name, _)| name))
+            .collect();
+        services.sort_unstable();
+        services.dedup();
+        services
+    }
+
+    /// The backends as a tree of host → path → service:port → ready endpoints. Backends that won't
+    /// work, because the service or port doesn't exist or nothing is ready, say why in red. health
+    /// has what we found out about each service in backend_services
+    pub fn backend_tree(&self, health: &HashMap<String, ServiceHealth>) -> String {
+        let backend_status = |backend: &Value| -> String {
+            let (name, port) = match backend_service(backend) {
+                Some(service) => service,
+                None => return backend_str(backend),
+            };
+            let status = match health.get(name) {
+                Some(health) => health.check(port.as_ref()),
+                None => Err("not checked".to_string()),
+            };
+            match status {
+                Ok(ready) => format!("{} → {}", backend_str(backend), ready),
+                Err(problem) => format!("{} → {}", backend_str(backend), Red.paint(problem)),
+            }
+        };
+        let mut lines = vec![];
+        let mut last_host = None;
+        for (host, path, backend) in self.path_backends() {
+            if last_host != Some(host) {
+                lines.push(format!("  {}", host));
+                last_host = Some(host);
+            }
+            lines.push(format!("    {} → {}", path, backend_status(backend)));
+        }
+        if let Some(backend) = self.default_backend() {
+            lines.push(format!("  (default) → {}", backend_status(backend)));
+        }
+        if lines.is_empty() {
+            "  <none>".to_string()
+        } else {
+            lines.join("\n")
+        }
+    }
+}
+
+/// Find the most preferred api version the server can give us ingresses from
+pub fn ingress_api_version(env: &Env) -> Result<&'static str, ClickError> {
+    crate::crd::preferred_api_version(env, INGRESS_API_VERSIONS, "ingresses").ok_or_else(|| {
+        ClickError::CommandError(
+            "Cluster doesn't serve ingresses from any api version click knows about".to_string(),
+        )
+    })
+}
+
+/// Build a request to delete the named ingress via the specified api version
+pub fn delete_namespaced_ingress(
+    group_version: &str,
+    name: &str,
+    namespace: &str,
+    optional: k8s_openapi::DeleteOptional<'_>,
+) -> Result<Request<Vec<u8>>, ClickError> {
+    let url = format!(
+        "/apis/{}/namespaces/{}/ingresses/{}",
+        group_version, namespace, name
+    );
+    let body = serde_json::to_vec(&optional)?;
+    Request::delete(url)
+        .header(http::header::CONTENT_TYPE, "application/json")
+        .body(body)
+        .map_err(|e| ClickError::CommandError(format!("Could not build delete request: {}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ingress_from(val: Value) -> Ingress<NetworkingV1> {
+        serde_json::from_value(val).unwrap()
+    }
+
+    #[test]
+    fn v1_rules() {
+        let ingress = ingress_from(serde_json::json!({
+            "metadata": { "name": "web" },
+            "spec": {
+                "tls": [{ "hosts": ["example.com"] }],
+                "defaultBackend": { "service": { "name": "fallback", "port": { "name": "http" } } },
+                "rules": [{
+                    "host": "example.com",
+                    "http": { "paths": [
+                        { "path": "/", "backend": { "service": { "name": "web", "port": { "number": 80 } } } },
+                        { "path": "/api", "backend": { "service": { "name": "api", "port": { "number": 8080 } } } },
+                    ]},
+                }],
+            },
+            "status": { "loadBalancer": { "ingress": [{ "ip": "10.0.0.1" }, { "hostname": "lb.example.com" }] } },
+        }));
+        assert_eq!(ingress.hosts(), vec!["example.com"]);
+        assert_eq!(ingress.addresses(), vec!["10.0.0.1", "lb.example.com"]);
+        assert_eq!(ingress.ports(), "80, 443");
+        assert_eq!(
+            ingress.rule_lines(),
+            vec![
+                "example.com → / → web:80",
+                "example.com → /api → api:8080",
+                "(default) → fallback:http",
+            ]
+        );
+    }
+
+    #[test]
+    fn v1beta1_rules() {
+        let ingress = ingress_from(serde_json::json!({
+            "metadata": {
+                "name": "old",
+                "annotations": { "kubernetes.io/ingress.class": "nginx" },
+            },
+            "spec": {
+                "rules": [{
+                    "http": { "paths": [
+                        { "backend": { "serviceName": "web", "servicePort": "http" } },
+                    ]},
+                }],
+            },
+        }));
+        assert_eq!(ingress.hosts(), vec!["*"]);
+        assert!(ingress.addresses().is_empty());
+        assert_eq!(ingress.ports(), "80");
+        assert_eq!(ingress.class(), Some("nginx"));
+        assert_eq!(ingress.rule_lines(), vec!["* → / → web:http"]);
+        assert_eq!(ingress.backend_services(), vec!["web"]);
+    }
+
+    #[test]
+    fn backend_health() {
+        let ingress = ingress_from(serde_json::json!({
+            "metadata": { "name": "web" },
+            "spec": {
+                "defaultBackend": { "service": { "name": "missing", "port": { "number": 80 } } },
+                "rules": [{
+                    "host": "example.com",
+                    "http": { "paths": [
+                        { "path": "/", "backend": { "service": { "name": "web", "port": { "name": "http" } } } },
+                        { "path": "/api", "backend": { "service": { "name": "api", "port": { "number": 8080 } } } },
+                        { "path": "/admin", "backend": { "service": { "name": "web", "port": { "number": 9000 } } } },
+                    ]},
+                }],
+            },
+        }));
+        assert_eq!(ingress.backend_services(), vec!["api", "missing", "web"]);
+
+        let web = serde_json::json!({ "spec": { "ports": [{ "name": "http", "port": 80 }] } });
+        let web_endpoints = serde_json::json!({ "subsets": [
+            { "addresses": [{ "ip": "10.0.0.1" }, { "ip": "10.0.0.2" }], "notReadyAddresses": [{ "ip": "10.0.0.3" }] },
+        ]});
+        let api = serde_json::json!({ "spec": { "ports": [{ "port": 8080 }] } });
+        let mut health = HashMap::new();
+        health.insert(
+            "web".to_string(),
+            ServiceHealth::from_values(&web, Some(&web_endpoints)),
+        );
+        health.insert("api".to_string(), ServiceHealth::from_values(&api, None));
+        health.insert("missing".to_string(), ServiceHealth::NotFound);
+        assert_eq!(
+            ingress.backend_tree(&health),
+            format!(
+                "  example.com\n    / → web:http → 2 ready (1 not ready)\n    /api → api:8080 → \
+                 {}\n    /admin → web:9000 → {}\n  (default) → missing:80 → {}",
+                Red.paint("no ready endpoints"),
+                Red.paint("service has no such port"),
+                Red.paint("service not found")
+            )
+        );
+    }
+}
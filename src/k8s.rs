@@ -13,21 +13,28 @@
 // limitations under the License.
 
 use bytes::Bytes;
+use chrono::offset::Local;
 use k8s_openapi::{http, List, ListableResource};
-use reqwest::blocking::Client;
-use reqwest::{Certificate, Identity, Url};
+use reqwest::blocking::{Client, RequestBuilder};
+use reqwest::{Certificate, Identity, Proxy, Url};
 use serde::Deserialize;
-use url::Host;
+use url::{Host, Position};
 use yasna::models::ObjectIdentifier;
 
 use std::cell::RefCell;
 use std::fmt::Debug;
 use std::fs::File;
 use std::io::Read;
+use std::net::{SocketAddr, ToSocketAddrs};
 use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::Arc;
+use std::thread;
 use std::time::Duration;
 
 use crate::{
+    certs::{check_client_key, pkcs12_to_pems, ServerNameOverride, PKCS12_PASSWORD_VAR},
     config::{AuthProvider, ExecAuth, ExecProvider},
     error::{ClickErrNo, ClickError},
 };
@@ -35,15 +42,15 @@ use crate::{
 pub enum UserAuth {
     AuthProvider(Box<AuthProvider>),
     ExecProvider(Box<ExecProvider>),
-    Ident(Identity),
+    Ident(ClientCert),
     Token(String),
     UserPass(String, String),
     //KeyCert(PathBuf, PathBuf),
 }
 
 impl UserAuth {
-    pub fn _from_identity(id: Identity) -> Result<UserAuth, ClickError> {
-        Ok(UserAuth::Ident(id))
+    pub fn _from_client_cert(cert: ClientCert) -> Result<UserAuth, ClickError> {
+        Ok(UserAuth::Ident(cert))
     }
 
     pub fn with_auth_provider(auth_provider: AuthProvider) -> Result<UserAuth, ClickError> {
@@ -62,34 +69,56 @@ impl UserAuth {
         Ok(UserAuth::UserPass(user, pass))
     }
 
-    /// construct an identity from a key and cert. need the endpoint to deceide which kind of
-    /// identity to use since rustls wants something different from nativetls, and we use rustls for
-    /// dns name hosts and native for ip hosts
-    pub fn from_key_cert<P>(key: P, cert: P, endpoint: &Url) -> Result<UserAuth, ClickError>
+    /// construct an identity from a key and cert file
+    pub fn from_key_cert<P>(key: P, cert: P) -> Result<UserAuth, ClickError>
     where
         PathBuf: From<P>,
     {
         let key_buf = PathBuf::from(key);
         let cert_buf = PathBuf::from(cert);
-        let pkcs12 = Context::use_pkcs12(endpoint);
-        let id = get_id_from_paths(key_buf, cert_buf, pkcs12)?;
-        Ok(UserAuth::Ident(id))
+        let cert = ClientCert::from_paths(key_buf, cert_buf)?;
+        Ok(UserAuth::Ident(cert))
     }
 
     /// same as above, but use already read data. The data should be base64 encoded pems
-    pub fn from_key_cert_data(
-        key: String,
-        cert: String,
-        endpoint: &Url,
-    ) -> Result<UserAuth, ClickError> {
+    pub fn from_key_cert_data(key: String, cert: String) -> Result<UserAuth, ClickError> {
         let key_decoded = ::base64::decode(&key)?;
         let cert_decoded = ::base64::decode(&cert)?;
-        let pkcs12 = Context::use_pkcs12(endpoint);
-        let id = get_id_from_data(key_decoded, cert_decoded, pkcs12)?;
-        Ok(UserAuth::Ident(id))
+        let cert = ClientCert::new(key_decoded, cert_decoded, "client-key-data")?;
+        Ok(UserAuth::Ident(cert))
+    }
+
+    /// construct an identity from a PKCS#12 bundle holding both the key and cert. The bundle's
+    /// password is read from $CLICK_PKCS12_PASSWORD, and is empty if that isn't set
+    pub fn from_pkcs12<P>(bundle: P) -> Result<UserAuth, ClickError>
+    where
+        PathBuf: From<P>,
+    {
+        let path = PathBuf::from(bundle);
+        let mut der = Vec::new();
+        File::open(&path)?.read_to_end(&mut der)?;
+        let password = std::env::var(PKCS12_PASSWORD_VAR).unwrap_or_default();
+        let source = path.display().to_string();
+        let (key, cert) = pkcs12_to_pems(&der, &password, &source)?;
+        let cert = ClientCert::new(key, cert, &source)?;
+        Ok(UserAuth::Ident(cert))
     }
 }
 
+/// How long to wait before retrying a GET that couldn't connect. Each retry waits twice as long
+const RETRY_BACKOFF_MILLIS: u64 = 250;
+
+/// How often to check if the user hit Ctrl-C while waiting for a response
+const INTERRUPT_POLL_MILLIS: u64 = 100;
+
+/// How long an idle connection stays in a context's pool. reqwest's default of 90s is shorter than
+/// a typical pause at the prompt, which meant a fresh TLS handshake for most commands
+const POOL_IDLE_SECS: u64 = 600;
+
+/// TCP keepalive interval for pooled connections, so idle ones aren't dropped by NATs and load
+/// balancers while they sit in the pool
+const TCP_KEEPALIVE_SECS: u64 = 30;
+
 fn print_token_err() {
     println!(
         "Couldn't get an authentication token. You can try exiting Click and \
@@ -98,6 +127,22 @@ fn print_token_err() {
     );
 }
 
+/// Run send, and if the server said our credentials are bad (401), or that they don't allow the
+/// request (403, which some auth proxies return for expired tokens), ask refresh to get new ones
+/// and run send once more. refresh returns false if the credentials can't be refreshed
+fn with_auth_retry<T, S, St, R>(send: S, status: St, refresh: R) -> Result<T, ClickError>
+where
+    S: Fn() -> Result<T, ClickError>,
+    St: Fn(&T) -> u16,
+    R: Fn() -> bool,
+{
+    let resp = send()?;
+    match status(&resp) {
+        401 | 403 if refresh() => send(),
+        _ => Ok(resp),
+    }
+}
+
 // convert a pkcs1 der to pkcs8 format
 fn pkcs1to8(pkcs1: &[u8]) -> Vec<u8> {
     let oid = ObjectIdentifier::from_slice(&[1, 2, 840, 113_549, 1, 1, 1]);
@@ -137,6 +182,7 @@ fn get_id_from_pkcs12(key: Vec<u8>, cert: Vec<u8>) -> Result<Identity, ClickErro
     let cert_pem = pem::parse(&cert)?;
 
     let pfx = p12::PFX::new(&cert_pem.contents, &key_der, None, "", "")
+        .and_then(|pfx| without_cert_encryption(&pfx))
         .ok_or_else(|| ClickError::ConfigFileError("Could not parse pkcs12 data".to_string()))?;
 
     let pkcs12der = pfx.to_der();
@@ -144,191 +190,661 @@ fn get_id_from_pkcs12(key: Vec<u8>, cert: Vec<u8>) -> Result<Identity, ClickErro
     Identity::from_pkcs12_der(&pkcs12der, "").map_err(|e| e.into())
 }
 
-fn get_id_from_paths(key: PathBuf, cert: PathBuf, pkcs12: bool) -> Result<Identity, ClickError> {
-    let mut key_buf = Vec::new();
-    File::open(key)?.read_to_end(&mut key_buf)?;
-    if pkcs12 {
+/// Rebuild a pkcs12 bundle made by p12, which has an empty password, with its certificate
+/// unencrypted. p12 encrypts it with RC2, which OpenSSL 3 only supports in its legacy provider.
+/// The key is still encrypted, the certificate doesn't need to be
+fn without_cert_encryption(pfx: &p12::PFX) -> Option<p12::PFX> {
+    // the empty password, as the bmp string pkcs12 uses
+    let password = [0, 0];
+    let auth_safe = pfx.auth_safe.data(&password)?;
+    let contents = yasna::parse_der(&auth_safe, |reader| {
+        reader.collect_sequence_of(|reader| reader.read_der())
+    })
+    .ok()?;
+    let safe_contents = contents
+        .iter()
+        .map(|content| p12::ContentInfo::from_der(content).ok()?.data(&password))
+        .collect::<Option<Vec<_>>>()?;
+    let auth_safe = yasna::construct_der(|writer| {
+        writer.write_sequence_of(|writer| {
+            for data in safe_contents.into_iter() {
+                writer
+                    .next()
+                    .write_der(&p12::ContentInfo::Data(data).to_der());
+            }
+        })
+    });
+    Some(p12::PFX {
+        version: 3,
+        mac_data: Some(p12::MacData::new(&auth_safe, &password)),
+        auth_safe: p12::ContentInfo::Data(auth_safe),
+    })
+}
+
+/// A client certificate and its key, as pems. The tls backends want different kinds of identity,
+/// and which backend a client uses depends on its cluster, so the identity is only built when the
+/// client is (see Context::get_client)
+#[derive(Clone)]
+pub struct ClientCert {
+    key: Vec<u8>,
+    cert: Vec<u8>,
+}
+
+impl ClientCert {
+    /// Check that key and cert go together. source says where the key came from, for errors
+    fn new(key: Vec<u8>, cert: Vec<u8>, source: &str) -> Result<ClientCert, ClickError> {
+        check_client_key(&key, &cert, source)?;
+        Ok(ClientCert { key, cert })
+    }
+
+    fn from_paths(key: PathBuf, cert: PathBuf) -> Result<ClientCert, ClickError> {
+        let mut key_buf = Vec::new();
+        File::open(&key)?.read_to_end(&mut key_buf)?;
         let mut cert_buf = Vec::new();
         File::open(cert)?.read_to_end(&mut cert_buf)?;
-        get_id_from_pkcs12(key_buf, cert_buf)
-    } else {
-        // for from_pem key and cert are in same buffer
-        File::open(cert)?.read_to_end(&mut key_buf)?;
-        Identity::from_pem(&key_buf).map_err(|e| e.into())
+        ClientCert::new(key_buf, cert_buf, &key.display().to_string())
+    }
+
+    /// The identity to give reqwest. native-tls needs pkcs12, rustls takes the pems
+    fn identity(&self, pkcs12: bool) -> Result<Identity, ClickError> {
+        if pkcs12 {
+            get_id_from_pkcs12(self.key.clone(), self.cert.clone())
+        } else {
+            let mut pem = self.key.clone();
+            pem.extend_from_slice(&self.cert);
+            Identity::from_pem(&pem).map_err(|e| e.into())
+        }
     }
 }
 
-fn get_id_from_data(
-    mut key: Vec<u8>,
-    mut cert: Vec<u8>,
-    pkcs12: bool,
-) -> Result<Identity, ClickError> {
-    if pkcs12 {
-        get_id_from_pkcs12(key, cert)
-    } else {
-        key.append(&mut cert);
-        Identity::from_pem(&key).map_err(|e| e.into())
+/// Who to impersonate. Requests are sent with Impersonate-* headers, so the api server authorizes
+/// them as this user (if we're allowed to impersonate them)
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Impersonation {
+    pub user: String,
+    pub groups: Vec<String>,
+    pub uid: Option<String>,
+}
+
+impl Impersonation {
+    /// Parse the value of 'set impersonate': a user, optionally followed by a comma separated list
+    /// of groups (commas because group names often contain :)
+    pub fn parse(spec: &str) -> Option<Impersonation> {
+        let mut parts = spec.split(',').map(|part| part.trim());
+        let user = parts.next().filter(|user| !user.is_empty())?;
+        Some(Impersonation {
+            user: user.to_string(),
+            groups: parts
+                .filter(|group| !group.is_empty())
+                .map(|group| group.to_string())
+                .collect(),
+            uid: None,
+        })
+    }
+
+    fn add_headers(&self, req: RequestBuilder) -> RequestBuilder {
+        let mut req = req.header("Impersonate-User", self.user.as_str());
+        for group in self.groups.iter() {
+            req = req.header("Impersonate-Group", group.as_str());
+        }
+        match self.uid.as_ref() {
+            Some(uid) => req.header("Impersonate-Uid", uid.as_str()),
+            None => req,
+        }
+    }
+
+    /// The equivalent kubectl flags, for the commands we run kubectl for
+    pub fn kubectl_args(&self) -> Vec<String> {
+        let mut args = vec!["--as".to_string(), self.user.clone()];
+        for group in self.groups.iter() {
+            args.push("--as-group".to_string());
+            args.push(group.clone());
+        }
+        if let Some(uid) = self.uid.as_ref() {
+            args.push("--as-uid".to_string());
+            args.push(uid.clone());
+        }
+        args
+    }
+}
+
+impl std::fmt::Display for Impersonation {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.user)?;
+        if !self.groups.is_empty() {
+            write!(f, " ({})", self.groups.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+/// How a context's requests reach its server
+#[derive(Clone, Debug, PartialEq)]
+pub enum ProxySetting {
+    /// use the proxy in HTTPS_PROXY, HTTP_PROXY or ALL_PROXY, if any, unless the host is in
+    /// NO_PROXY
+    Environment,
+    /// send everything through this proxy (from the cluster's proxy-url). https requests are
+    /// tunneled through it with CONNECT
+    Url(Url),
+    /// connect directly, even if a proxy is set in the environment
+    Off,
+}
+
+impl std::fmt::Display for ProxySetting {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ProxySetting::Environment => write!(f, "from environment"),
+            ProxySetting::Url(url) => write!(f, "{}", url),
+            ProxySetting::Off => write!(f, "off"),
+        }
     }
 }
 
 pub struct Context {
     pub name: String,
     endpoint: Url,
-    client: RefCell<Client>,
+    /// where requests are sent, the endpoint unless sni_name gives a name to send in its place
+    request_base: Url,
+    /// None after drop_connections, until the next request builds a new one. Also None until
+    /// then when the client has to look up the endpoint, see Context::new
+    client: RefCell<Option<Client>>,
+    /// kept so the client can be rebuilt, see drop_connections
+    client_cert: RefCell<Option<ClientCert>>,
     root_ca: Option<Certificate>,
+    server_name: Option<ServerNameOverride>,
     auth: RefCell<Option<UserAuth>>,
     connect_timeout_secs: u32,
     read_timeout_secs: u32,
+    request_retries: u32,
+    insecure_skip_tls_verify: bool,
+    proxy: ProxySetting,
+    impersonation: Option<Impersonation>,
+    /// set by the Ctrl-C handler, requests give up waiting when it's set
+    interrupt: Option<Arc<AtomicBool>>,
+    /// how much of each request to log, see crate::trace
+    verbosity: u8,
 }
 
 impl Context {
+    #[allow(clippy::too_many_arguments)]
     pub fn new<S: Into<String>>(
         name: S,
         endpoint: Url,
         root_ca: Option<Certificate>,
+        server_name: Option<ServerNameOverride>,
         auth: Option<UserAuth>,
         connect_timeout_secs: u32,
         read_timeout_secs: u32,
-    ) -> Context {
-        let (client, auth) = Context::get_client(
-            &endpoint,
-            root_ca.clone(),
-            auth,
-            None,
-            connect_timeout_secs,
-            read_timeout_secs,
-        );
+        request_retries: u32,
+        insecure_skip_tls_verify: bool,
+        proxy: ProxySetting,
+    ) -> Result<Context, ClickError> {
+        let (client_cert, auth) = match auth {
+            Some(UserAuth::Ident(cert)) => (Some(cert), None),
+            auth => (None, auth),
+        };
+        let mut request_base = endpoint.clone();
+        let sni_name = Context::sni_name(server_name.as_ref(), insecure_skip_tls_verify, &proxy);
+        if let Some(name) = sni_name {
+            request_base.set_host(Some(name))?;
+        }
+        // the client for a tls-server-name sent as SNI has to look up the endpoint's host, so
+        // for a domain that waits for the first request, and fails it if dns does
+        let (client, auth) = if sni_name.is_some() && endpoint.domain().is_some() {
+            (None, auth)
+        } else {
+            let (client, auth) = Context::get_client(
+                &endpoint,
+                root_ca.clone(),
+                server_name.as_ref(),
+                auth,
+                client_cert.clone(),
+                connect_timeout_secs,
+                read_timeout_secs,
+                insecure_skip_tls_verify,
+                &proxy,
+            )?;
+            (Some(client), auth)
+        };
         let client = RefCell::new(client);
         let auth = RefCell::new(auth);
-        Context {
+        Ok(Context {
             name: name.into(),
             endpoint,
+            request_base,
             client,
+            client_cert: RefCell::new(client_cert),
             root_ca,
+            server_name,
             auth,
             connect_timeout_secs,
             read_timeout_secs,
-        }
+            request_retries,
+            insecure_skip_tls_verify,
+            proxy,
+            impersonation: None,
+            interrupt: None,
+            verbosity: 0,
+        })
     }
 
+    /// Build the client for a context. Returns it along with the auth that still has to be added
+    /// to each request, which is everything but a client certificate
+    #[allow(clippy::too_many_arguments)]
     fn get_client(
         endpoint: &Url,
         root_ca: Option<Certificate>,
+        server_name: Option<&ServerNameOverride>,
         auth: Option<UserAuth>,
-        id: Option<Identity>,
+        cert: Option<ClientCert>,
         connect_timeout_secs: u32,
         read_timeout_secs: u32,
-    ) -> (Client, Option<UserAuth>) {
+        insecure_skip_tls_verify: bool,
+        proxy: &ProxySetting,
+    ) -> Result<(Client, Option<UserAuth>), ClickError> {
         let host = endpoint.host().unwrap();
-        let client = match host {
-            Host::Domain(_) => Client::builder().use_rustls_tls(),
+        let (cert, auth) = match auth {
+            Some(UserAuth::Ident(ident)) => (cert.or(Some(ident)), None),
+            auth => (cert, auth),
+        };
+        // a preconfigured rustls config already has the roots and client certificate, reqwest
+        // doesn't add its own to it
+        let preconfigured = server_name.is_some() && !insecure_skip_tls_verify;
+        let client = match (&host, server_name) {
+            // nothing is checked, so there's no point checking the tls-server-name either. the
+            // backend is picked by host as usual, so it matches the identity use_pkcs12 picks
+            (Host::Domain(_), _) if insecure_skip_tls_verify => Client::builder()
+                .use_rustls_tls()
+                .danger_accept_invalid_certs(true),
+            _ if insecure_skip_tls_verify => Client::builder()
+                .use_native_tls()
+                .danger_accept_invalid_certs(true),
+            (_, Some(sno)) => {
+                // neither backend can check the certificate against a name other than the host,
+                // so rustls gets a verifier that does, and uses it for every connection. that
+                // matters through a proxy, where the host is still what we connect to, see
+                // sni_name
+                let config = sno
+                    .client_config(cert.as_ref().map(|c| (c.key.as_slice(), c.cert.as_slice())))?;
+                Client::builder().use_preconfigured_tls(config)
+            }
+            (Host::Domain(_), None) => Client::builder().use_rustls_tls(),
             _ => Client::builder().use_native_tls(),
         };
         let client = match root_ca {
-            Some(ca) => client.add_root_certificate(ca),
-            None => client,
+            Some(ca) if !preconfigured => client.add_root_certificate(ca),
+            _ => client,
         };
-        let (client, auth) = match auth {
-            Some(auth_inner) => match auth_inner {
-                UserAuth::Ident(id) => (client.identity(id), None),
-                _ => (client, Some(auth_inner)),
-            },
-            None => (client, auth),
+        let client = match cert {
+            Some(cert) if !preconfigured => {
+                client.identity(cert.identity(Context::use_pkcs12(endpoint))?)
+            }
+            _ => client,
         };
-        let client = match id {
-            Some(id) => client.identity(id),
+        let client = match proxy {
+            // reqwest reads the environment itself
+            ProxySetting::Environment => client,
+            // the scheme was checked when the config was loaded, so this can't fail
+            ProxySetting::Url(url) => client.proxy(Proxy::all(url.clone()).unwrap()),
+            ProxySetting::Off => client.no_proxy(),
+        };
+        let client = match Context::sni_name(server_name, insecure_skip_tls_verify, proxy) {
+            Some(name) => {
+                let port = endpoint.port_or_known_default().unwrap_or(443);
+                let addrs: Vec<SocketAddr> = match host {
+                    Host::Domain(domain) => (domain, port)
+                        .to_socket_addrs()
+                        .map_err(|e| {
+                            ClickError::CommandError(format!("Could not resolve {}: {}", domain, e))
+                        })?
+                        .collect(),
+                    Host::Ipv4(ip) => vec![SocketAddr::new(ip.into(), port)],
+                    Host::Ipv6(ip) => vec![SocketAddr::new(ip.into(), port)],
+                };
+                client.resolve_to_addrs(name, &addrs)
+            }
             None => client,
         };
-        (
-            client
-                .connect_timeout(Duration::new(connect_timeout_secs.into(), 0))
-                .timeout(Duration::new(read_timeout_secs.into(), 0))
-                .build()
-                .unwrap(),
-            auth,
-        )
+        let client = client
+            .connect_timeout(Duration::new(connect_timeout_secs.into(), 0))
+            .timeout(Duration::new(read_timeout_secs.into(), 0))
+            .pool_idle_timeout(Duration::from_secs(POOL_IDLE_SECS))
+            .tcp_keepalive(Duration::from_secs(TCP_KEEPALIVE_SECS))
+            .build()?;
+        Ok((client, auth))
+    }
+
+    /// The tls-server-name to send as SNI, if there is one to send. reqwest sends the host of the
+    /// url as SNI, so requests go to a url with this name as its host instead, and the client
+    /// resolves it to the endpoint's addresses. Through a proxy that would have the proxy connect
+    /// to this name too, so there it's only used to verify the server's certificate
+    fn sni_name<'a>(
+        server_name: Option<&'a ServerNameOverride>,
+        insecure_skip_tls_verify: bool,
+        proxy: &ProxySetting,
+    ) -> Option<&'a str> {
+        let proxied = match proxy {
+            // the variables reqwest reads for https
+            ProxySetting::Environment => ["HTTPS_PROXY", "https_proxy", "ALL_PROXY", "all_proxy"]
+                .iter()
+                .any(|var| std::env::var_os(var).is_some_and(|val| !val.is_empty())),
+            ProxySetting::Url(_) => true,
+            ProxySetting::Off => false,
+        };
+        match server_name {
+            Some(sno) if !insecure_skip_tls_verify && !proxied => Some(&sno.server_name),
+            _ => None,
+        }
+    }
+
+    /// Close the connections pooled for this context, by dropping its client. Credentials are
+    /// kept, and the next request builds a new client. The pool is per context, so this is done
+    /// when switching away from it
+    pub fn drop_connections(&self) {
+        self.client.borrow_mut().take();
     }
 
+    /// The context's client, building a new one if drop_connections dropped it
+    fn client(&self) -> Result<Client, ClickError> {
+        if let Some(client) = self.client.borrow().as_ref() {
+            return Ok(client.clone());
+        }
+        let (client, _) = Context::get_client(
+            &self.endpoint,
+            self.root_ca.clone(),
+            self.server_name.as_ref(),
+            None,
+            self.client_cert.borrow().clone(),
+            self.connect_timeout_secs,
+            self.read_timeout_secs,
+            self.insecure_skip_tls_verify,
+            &self.proxy,
+        )?;
+        *self.client.borrow_mut() = Some(client.clone());
+        Ok(client)
+    }
+
+    pub fn endpoint(&self) -> &Url {
+        &self.endpoint
+    }
+
+    /// True if this context doesn't verify the server's certificate (insecure-skip-tls-verify is
+    /// set for its cluster)
+    pub fn insecure_skip_tls_verify(&self) -> bool {
+        self.insecure_skip_tls_verify
+    }
+
+    pub fn proxy(&self) -> &ProxySetting {
+        &self.proxy
+    }
+
+    pub fn impersonation(&self) -> Option<&Impersonation> {
+        self.impersonation.as_ref()
+    }
+
+    /// Set who requests in this context impersonate, returning who they impersonated before
+    pub fn set_impersonation(
+        &mut self,
+        impersonation: Option<Impersonation>,
+    ) -> Option<Impersonation> {
+        std::mem::replace(&mut self.impersonation, impersonation)
+    }
+
+    /// Give up waiting for responses when interrupt is set (by the Ctrl-C handler), so a request
+    /// to a server that isn't responding doesn't hang the prompt until it times out
+    pub fn set_interrupt(&mut self, interrupt: Arc<AtomicBool>) {
+        self.interrupt = Some(interrupt);
+    }
+
+    /// Log requests to stderr, see crate::trace for what each level logs
+    pub fn set_verbosity(&mut self, verbosity: u8) {
+        self.verbosity = verbosity;
+    }
+
+    /// Run f on another thread and wait for it, unless the interrupt flag gets set first. If it
+    /// does the thread is left to finish (or time out) on its own
+    fn interruptible<T, F>(&self, f: F) -> Result<T, ClickError>
+    where
+        T: Send + 'static,
+        F: FnOnce() -> Result<T, ClickError> + Send + 'static,
+    {
+        let interrupt = match self.interrupt.as_ref() {
+            Some(interrupt) => interrupt,
+            None => return f(),
+        };
+        // a Ctrl-C from before this request doesn't count
+        interrupt.store(false, Ordering::SeqCst);
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || tx.send(f()));
+        loop {
+            match rx.recv_timeout(Duration::from_millis(INTERRUPT_POLL_MILLIS)) {
+                Ok(res) => return res,
+                Err(RecvTimeoutError::Timeout) => {
+                    if interrupt.load(Ordering::SeqCst) {
+                        return Err(ClickError::CommandError("Request interrupted".to_string()));
+                    }
+                }
+                Err(RecvTimeoutError::Disconnected) => {
+                    return Err(ClickError::CommandError(
+                        "Request failed without a response".to_string(),
+                    ));
+                }
+            }
+        }
+    }
+
+    /// Send req and pass the response to handle (on the request thread, so reading the body can be
+    /// interrupted too). GETs that fail to connect are retried request_retries times, with backoff
+    fn send<T, F>(&self, req: reqwest::blocking::Request, handle: F) -> Result<T, ClickError>
+    where
+        T: Send + 'static,
+        F: Fn(reqwest::blocking::Response) -> Result<T, ClickError> + Send + Copy + 'static,
+    {
+        let retries = if req.method() == reqwest::Method::GET {
+            self.request_retries
+        } else {
+            0
+        };
+        let mut backoff = Duration::from_millis(RETRY_BACKOFF_MILLIS);
+        for _ in 0..retries {
+            let mut retry_req = match req.try_clone() {
+                Some(retry_req) => retry_req,
+                None => break,
+            };
+            // try_clone doesn't copy the timeout
+            *retry_req.timeout_mut() = req.timeout().copied();
+            let client = self.client()?;
+            let verbosity = self.verbosity;
+            match self.interruptible(move || {
+                handle(crate::trace::execute(&client, retry_req, verbosity)?)
+            }) {
+                Err(ClickError::Reqwest(e, _)) | Err(ClickError::Timeout(e)) if e.is_connect() => {
+                    self.interruptible(move || {
+                        thread::sleep(backoff);
+                        Ok(())
+                    })?;
+                    backoff *= 2;
+                }
+                res => return res,
+            }
+        }
+        let client = self.client()?;
+        let verbosity = self.verbosity;
+        self.interruptible(move || handle(crate::trace::execute(&client, req, verbosity)?))
+    }
+
+    /// Do we need a pkcs12 identity for client certs. That's what native-tls wants, and we use
+    /// native-tls for ip hosts, since rustls can only verify certificates for dns names. This
+    /// holds whether or not insecure-skip-tls-verify is set, see get_client
     fn use_pkcs12(endpoint: &Url) -> bool {
         let host = endpoint.host().unwrap();
         !matches!(host, Host::Domain(_))
     }
 
-    fn handle_exec_provider(&self, exec_provider: &ExecProvider) {
-        let (auth, was_expired) = exec_provider.get_auth();
-        match auth {
-            ExecAuth::Token(_) => {} // handled below
-            ExecAuth::ClientCertKey {
-                cert_data,
-                key_data,
-                ..
-            } => {
-                if was_expired {
-                    let pkcs12 = Context::use_pkcs12(&self.endpoint);
-                    let id =
-                        get_id_from_data(key_data.into_bytes(), cert_data.into_bytes(), pkcs12)
-                            .unwrap(); // TODO: Handle error
-                    let auth = self.auth.take();
-                    let (new_client, new_auth) = Context::get_client(
-                        &self.endpoint,
-                        self.root_ca.clone(),
-                        auth,
-                        Some(id),
-                        self.connect_timeout_secs,
-                        self.read_timeout_secs,
-                    );
-                    *self.client.borrow_mut() = new_client;
-                    *self.auth.borrow_mut() = new_auth;
-                }
+    /// Have the credentials this context got from an auth or exec provider expired. Contexts with
+    /// static credentials never expire
+    pub fn credentials_expired(&self) -> bool {
+        let expiry = match &*self.auth.borrow() {
+            Some(UserAuth::AuthProvider(provider)) => *provider.expiry.borrow(),
+            Some(UserAuth::ExecProvider(provider)) => *provider.expiry.borrow(),
+            _ => None,
+        };
+        expiry.map(|e| e < Local::now()).unwrap_or(false)
+    }
+
+    /// Get credentials from the exec provider if this context uses one. If the provider returned a
+    /// new client certificate the client is rebuilt to use it
+    fn handle_exec_provider(&self) -> Result<Option<ExecAuth>, ClickError> {
+        let (auth, was_expired) = match &*self.auth.borrow() {
+            Some(UserAuth::ExecProvider(exec_provider)) => exec_provider.get_auth()?,
+            _ => return Ok(None),
+        };
+        if let ExecAuth::ClientCertKey {
+            cert_data,
+            key_data,
+        } = &auth
+        {
+            if was_expired {
+                let cert = ClientCert::new(
+                    key_data.clone().into_bytes(),
+                    cert_data.clone().into_bytes(),
+                    "the exec plugin's client key",
+                )?;
+                let auth = self.auth.take();
+                *self.client_cert.borrow_mut() = Some(cert.clone());
+                let (new_client, new_auth) = Context::get_client(
+                    &self.endpoint,
+                    self.root_ca.clone(),
+                    self.server_name.as_ref(),
+                    auth,
+                    Some(cert),
+                    self.connect_timeout_secs,
+                    self.read_timeout_secs,
+                    self.insecure_skip_tls_verify,
+                    &self.proxy,
+                )?;
+                *self.client.borrow_mut() = Some(new_client);
+                *self.auth.borrow_mut() = new_auth;
             }
         }
+        Ok(Some(auth))
     }
 
-    pub fn execute(
+    /// Build a reqwest request for the parts of a k8s request, with our credentials added
+    fn build_request(
         &self,
-        k8sreq: http::Request<Vec<u8>>,
-    ) -> Result<http::Response<Bytes>, ClickError> {
-        let (parts, body) = k8sreq.into_parts();
+        method: &http::Method,
+        uri: &http::Uri,
+        headers: &http::HeaderMap,
+        body: Vec<u8>,
+    ) -> Result<RequestBuilder, ClickError> {
+        let url = self.request_base.join(&uri.to_string())?;
 
-        let url = self.endpoint.join(&parts.uri.to_string())?;
-
-        if let Some(UserAuth::ExecProvider(ref exec_provider)) = *self.auth.borrow() {
-            self.handle_exec_provider(exec_provider);
-        }
+        let exec_auth = self.handle_exec_provider()?;
 
-        let req = match parts.method {
-            http::method::Method::GET => self.client.borrow().get(url),
-            http::method::Method::POST => self.client.borrow().post(url),
-            http::method::Method::DELETE => self.client.borrow().delete(url),
+        let client = self.client()?;
+        let req = match *method {
+            http::method::Method::GET => client.get(url),
+            http::method::Method::POST => client.post(url),
+            http::method::Method::DELETE => client.delete(url),
+            http::method::Method::PATCH => client.patch(url),
+            http::method::Method::PUT => client.put(url),
             _ => unimplemented!(),
         };
 
-        let req = req.headers(parts.headers).body(body);
-        let req = match &*self.auth.borrow() {
+        let req = req.headers(headers.clone()).body(body);
+        // only the connection is for the tls-server-name, the request is still for the endpoint
+        let req = if self.request_base != self.endpoint {
+            req.header(
+                reqwest::header::HOST,
+                &self.endpoint[Position::BeforeHost..Position::AfterPort],
+            )
+        } else {
+            req
+        };
+        let req = match self.impersonation.as_ref() {
+            Some(impersonation) => impersonation.add_headers(req),
+            None => req,
+        };
+        Ok(match &*self.auth.borrow() {
             Some(auth) => match auth {
                 UserAuth::AuthProvider(provider) => match provider.ensure_token() {
                     Some(token) => req.bearer_auth(token),
                     None => {
-                        print_token_err();
+                        // oidc prints its own instructions when a refresh fails
+                        if !provider.is_oidc() {
+                            print_token_err();
+                        }
                         req
                     }
                 },
-                UserAuth::ExecProvider(ref exec_provider) => {
-                    let (auth, _) = exec_provider.get_auth();
-                    match auth {
-                        ExecAuth::Token(token) => req.bearer_auth(token),
-                        ExecAuth::ClientCertKey { .. } => req, // handled above
-                    }
-                }
+                UserAuth::ExecProvider(_) => match exec_auth {
+                    Some(ExecAuth::Token(token)) => req.bearer_auth(token),
+                    _ => req, // client certs are handled in handle_exec_provider
+                },
                 UserAuth::Token(token) => req.bearer_auth(token),
                 UserAuth::UserPass(user, pass) => req.basic_auth(user, Some(pass)),
                 _ => req,
             },
             None => req,
-        };
-        let resp = req.send()?;
-        let stat = resp.status();
-        let bytes = resp.bytes()?;
+        })
+    }
+
+    /// Drop the credentials we got from an auth or exec provider, so the next request fetches new
+    /// ones. Returns false if this context's credentials can't be refreshed
+    fn refresh_credentials(&self) -> bool {
+        match &*self.auth.borrow() {
+            Some(UserAuth::AuthProvider(provider)) => provider.invalidate(),
+            Some(UserAuth::ExecProvider(provider)) => {
+                provider.invalidate();
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Tell the user what to do if the server rejected a token we have no way to refresh
+    fn check_static_token(&self, status: http::StatusCode) {
+        if status == http::StatusCode::UNAUTHORIZED {
+            if let Some(UserAuth::Token(_)) = &*self.auth.borrow() {
+                eprintln!(
+                    "The token for context {} was rejected, it has probably expired. Static \
+                     tokens can't be refreshed, re-authenticate and update your kubeconfig",
+                    self.name
+                );
+            }
+        }
+    }
+
+    pub fn execute(
+        &self,
+        k8sreq: http::Request<Vec<u8>>,
+    ) -> Result<http::Response<Bytes>, ClickError> {
+        self.execute_with_timeout(k8sreq, None)
+    }
+
+    /// Like execute, but if timeout is specified it's used instead of the configured read timeout
+    pub fn execute_with_timeout(
+        &self,
+        k8sreq: http::Request<Vec<u8>>,
+        timeout: Option<Duration>,
+    ) -> Result<http::Response<Bytes>, ClickError> {
+        let (parts, body) = k8sreq.into_parts();
+        let (stat, bytes) = with_auth_retry(
+            || {
+                let mut req = self
+                    .build_request(&parts.method, &parts.uri, &parts.headers, body.clone())?
+                    .build()?;
+                if timeout.is_some() {
+                    *req.timeout_mut() = timeout;
+                }
+                self.send(req, |resp| Ok((resp.status(), resp.bytes()?)))
+            },
+            |(stat, _)| stat.as_u16(),
+            || self.refresh_credentials(),
+        )?;
+        self.check_static_token(stat);
 
         Ok(http::response::Builder::new()
             .status(stat)
@@ -344,49 +860,20 @@ impl Context {
         timeout: Option<Duration>,
     ) -> Result<reqwest::blocking::Response, ClickError> {
         let (parts, body) = k8sreq.into_parts();
-
-        let url = self.endpoint.join(&parts.uri.to_string())?;
-
-        if let Some(UserAuth::ExecProvider(ref exec_provider)) = *self.auth.borrow() {
-            self.handle_exec_provider(exec_provider);
-        }
-
-        let req = match parts.method {
-            http::method::Method::GET => self.client.borrow().get(url),
-            http::method::Method::POST => self.client.borrow().post(url),
-            http::method::Method::DELETE => self.client.borrow().delete(url),
-            _ => unimplemented!(),
-        };
-
-        let req = req.body(body);
-        let req = match &*self.auth.borrow() {
-            Some(auth) => match auth {
-                UserAuth::AuthProvider(provider) => match provider.ensure_token() {
-                    Some(token) => req.bearer_auth(token),
-                    None => {
-                        print_token_err();
-                        req
-                    }
-                },
-                UserAuth::ExecProvider(ref exec_provider) => {
-                    let (auth, _) = exec_provider.get_auth();
-                    match auth {
-                        ExecAuth::Token(token) => req.bearer_auth(token),
-                        ExecAuth::ClientCertKey { .. } => req, // handled above
-                    }
-                }
-                UserAuth::Token(token) => req.bearer_auth(token),
-                UserAuth::UserPass(user, pass) => req.basic_auth(user, Some(pass)),
-                _ => req,
+        let resp = with_auth_retry(
+            || {
+                // we build the request here so we can set the timeout to None if needed.
+                // RequestBuilder doesn't support that for some reason
+                let mut req = self
+                    .build_request(&parts.method, &parts.uri, &parts.headers, body.clone())?
+                    .build()?;
+                *req.timeout_mut() = timeout;
+                self.send(req, Ok)
             },
-            None => req,
-        };
-
-        // we build the request here so we can set the timeout to None if needed. RequestBuilder
-        // doesn't support that for some reason
-        let mut req = req.build()?;
-        *req.timeout_mut() = timeout;
-        let resp = self.client.borrow().execute(req)?;
+            |resp| resp.status().as_u16(),
+            || self.refresh_credentials(),
+        )?;
+        self.check_static_token(resp.status());
 
         if resp.status().is_success() {
             Ok(resp)
@@ -417,7 +904,17 @@ impl Context {
         &self,
         k8sreq: http::Request<Vec<u8>>,
     ) -> Result<List<T>, ClickError> {
-        let response = self.execute(k8sreq)?;
+        self.execute_list_with_timeout(k8sreq, None)
+    }
+
+    /// Like execute_list, but if timeout is specified it's used instead of the configured read
+    /// timeout
+    pub fn execute_list_with_timeout<T: ListableResource + for<'de> Deserialize<'de> + Debug>(
+        &self,
+        k8sreq: http::Request<Vec<u8>>,
+        timeout: Option<Duration>,
+    ) -> Result<List<T>, ClickError> {
+        let response = self.execute_with_timeout(k8sreq, timeout)?;
         let status_code: http::StatusCode = response.status();
 
         let res_list: List<T> =
@@ -431,9 +928,9 @@ impl Context {
                     if status_code == http::StatusCode::UNAUTHORIZED {
                         return Err(ClickError::Kube(ClickErrNo::Unauthorized));
                     } else {
-                        return Err(ClickError::ParseErr(
-                            // TODO maybe a special error type for this
-                            format!("Got unexpected status {} {:?}", status_code, other),
+                        return Err(ClickError::Status(
+                            status_code.as_u16(),
+                            format!("{:?}", other),
                         ));
                     }
                 }
@@ -443,3 +940,305 @@ impl Context {
         Ok(res_list)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::cell::Cell;
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::Mutex;
+
+    #[test]
+    fn retry_after_refresh() {
+        // 401 then success, with refreshable credentials
+        let statuses = RefCell::new(vec![200, 401]);
+        let refreshed = Cell::new(false);
+        let status = with_auth_retry(
+            || Ok(statuses.borrow_mut().pop().unwrap()),
+            |s| *s,
+            || {
+                refreshed.set(true);
+                true
+            },
+        )
+        .unwrap();
+        assert_eq!(status, 200);
+        assert!(refreshed.get());
+        assert!(statuses.borrow().is_empty());
+
+        // static credentials, the 401 is returned as is
+        let statuses = RefCell::new(vec![200, 401]);
+        let status = with_auth_retry(
+            || Ok(statuses.borrow_mut().pop().unwrap()),
+            |s| *s,
+            || false,
+        )
+        .unwrap();
+        assert_eq!(status, 401);
+
+        // only retry once
+        let sends = Cell::new(0);
+        let status = with_auth_retry(
+            || {
+                sends.set(sends.get() + 1);
+                Ok(401)
+            },
+            |s| *s,
+            || true,
+        )
+        .unwrap();
+        assert_eq!(status, 401);
+        assert_eq!(sends.get(), 2);
+
+        // no refresh on success
+        let status = with_auth_retry(|| Ok(200), |s| *s, || panic!("refreshed")).unwrap();
+        assert_eq!(status, 200);
+    }
+
+    #[test]
+    fn parse_impersonation() {
+        assert_eq!(Impersonation::parse(""), None);
+        assert_eq!(Impersonation::parse(",admins"), None);
+        let imp = Impersonation::parse("alice, devs,system:masters").unwrap();
+        assert_eq!(imp.user, "alice");
+        assert_eq!(imp.groups, vec!["devs", "system:masters"]);
+        assert_eq!(imp.to_string(), "alice (devs, system:masters)");
+        assert_eq!(
+            imp.kubectl_args(),
+            vec![
+                "--as",
+                "alice",
+                "--as-group",
+                "devs",
+                "--as-group",
+                "system:masters"
+            ]
+        );
+    }
+
+    fn client_for(
+        server: &str,
+        server_name: Option<&str>,
+        insecure_skip_tls_verify: bool,
+    ) -> Result<Client, ClickError> {
+        use crate::certs::tests::{CA_PEM, CLIENT_CERT_PEM, CLIENT_KEY_PEM};
+        let sno = server_name
+            .map(|name| ServerNameOverride::new(name.to_string(), Some(CA_PEM)).unwrap());
+        let cert = ClientCert::new(
+            CLIENT_KEY_PEM.as_bytes().to_vec(),
+            CLIENT_CERT_PEM.as_bytes().to_vec(),
+            "test",
+        )
+        .unwrap();
+        Context::get_client(
+            &Url::parse(server).unwrap(),
+            None,
+            sno.as_ref(),
+            Some(UserAuth::Ident(cert)),
+            None,
+            10,
+            20,
+            insecure_skip_tls_verify,
+            &ProxySetting::Off,
+        )
+        .map(|(client, auth)| {
+            // the certificate is part of the client, not sent with each request
+            assert!(auth.is_none());
+            client
+        })
+    }
+
+    #[test]
+    fn tls_server_name_client() {
+        assert!(client_for("https://localhost", Some("kubernetes.internal"), false).is_ok());
+        assert!(client_for("https://10.0.0.1:6443", Some("kubernetes.internal"), false).is_ok());
+        // the host is looked up by the first request, so the context loads without dns
+        let context = context_for("https://cluster.invalid", Some("kubernetes.internal"));
+        assert!(context.client.borrow().is_none());
+        assert!(context.client().is_err());
+    }
+
+    /// The SNI and host header of a request a test server got
+    type SeenRequests = Arc<Mutex<Vec<(Option<String>, String)>>>;
+
+    /// Serve https on 127.0.0.1 with the kubernetes.internal test certificate, keeping
+    /// connections open unless the client asks to close them. Returns the port it listens on, a
+    /// count of the connections made to it, and the requests it got
+    fn tls_server() -> (u16, Arc<AtomicUsize>, SeenRequests) {
+        use crate::certs::tests::{SERVER_KEY_PEM, SERVER_PEM};
+        use std::io::{Read, Write};
+        use std::net::TcpListener;
+
+        let cert = rustls::Certificate(pem::parse(SERVER_PEM).unwrap().contents);
+        let key = rustls::PrivateKey(pem::parse(SERVER_KEY_PEM).unwrap().contents);
+        let config = Arc::new(
+            rustls::ServerConfig::builder()
+                .with_safe_defaults()
+                .with_no_client_auth()
+                .with_single_cert(vec![cert], key)
+                .unwrap(),
+        );
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let connections = Arc::new(AtomicUsize::new(0));
+        let count = connections.clone();
+        let requests = SeenRequests::default();
+        let seen = requests.clone();
+        thread::spawn(move || {
+            for sock in listener.incoming() {
+                count.fetch_add(1, Ordering::SeqCst);
+                let mut sock = sock.unwrap();
+                let config = config.clone();
+                let seen = seen.clone();
+                thread::spawn(move || {
+                    let mut conn = rustls::ServerConnection::new(config).unwrap();
+                    let mut tls = rustls::Stream::new(&mut conn, &mut sock);
+                    let mut buf = [0; 1024];
+                    loop {
+                        // only gets are sent, so a request ends at the blank line
+                        let mut req = Vec::new();
+                        while !req.windows(4).any(|w| w == b"\r\n\r\n") {
+                            match tls.read(&mut buf) {
+                                Ok(0) | Err(_) => return,
+                                Ok(n) => req.extend_from_slice(&buf[..n]),
+                            }
+                        }
+                        let req = String::from_utf8_lossy(&req).to_lowercase();
+                        let host = req
+                            .lines()
+                            .find_map(|line| line.strip_prefix("host: "))
+                            .unwrap_or_default();
+                        let sni = tls.conn.sni_hostname().map(str::to_string);
+                        seen.lock().unwrap().push((sni, host.to_string()));
+                        let close = req.contains("connection: close");
+                        let response = if close {
+                            "HTTP/1.1 200 OK\r\ncontent-length: 2\r\nconnection: close\r\n\r\nok"
+                        } else {
+                            "HTTP/1.1 200 OK\r\ncontent-length: 2\r\n\r\nok"
+                        };
+                        if tls.write_all(response.as_bytes()).is_err() || close {
+                            let _ = tls.flush();
+                            return;
+                        }
+                    }
+                });
+            }
+        });
+        (port, connections, requests)
+    }
+
+    /// A context for server, with the test certificate authority and no proxy
+    fn context_for(server: &str, server_name: Option<&str>) -> Context {
+        use crate::certs::tests::CA_PEM;
+        let sno = server_name
+            .map(|name| ServerNameOverride::new(name.to_string(), Some(CA_PEM)).unwrap());
+        Context::new(
+            "test",
+            Url::parse(server).unwrap(),
+            None,
+            sno,
+            None,
+            10,
+            20,
+            0,
+            false,
+            ProxySetting::Off,
+        )
+        .unwrap()
+    }
+
+    fn get_root(context: &Context) -> Result<reqwest::blocking::Response, reqwest::Error> {
+        context
+            .build_request(
+                &http::Method::GET,
+                &"/".parse().unwrap(),
+                &http::HeaderMap::new(),
+                vec![],
+            )
+            .unwrap()
+            .send()
+    }
+
+    #[test]
+    fn tls_server_name_ip_server() {
+        // the certificate is only for kubernetes.internal, the server is only reachable by ip
+        let (port, _, requests) = tls_server();
+        let url = format!("https://127.0.0.1:{}", port);
+        let context = context_for(&url, Some("kubernetes.internal"));
+        let res = get_root(&context).unwrap();
+        assert_eq!(res.status(), 200);
+        assert_eq!(res.text().unwrap(), "ok");
+        // the override is sent as SNI, and the request is still for the server in the config
+        assert_eq!(
+            *requests.lock().unwrap(),
+            vec![(
+                Some("kubernetes.internal".to_string()),
+                format!("127.0.0.1:{}", port)
+            )]
+        );
+
+        let (port, _, requests) = tls_server();
+        let url = format!("https://127.0.0.1:{}", port);
+        let context = context_for(&url, Some("other.internal"));
+        assert!(get_root(&context).is_err());
+        assert!(requests.lock().unwrap().is_empty());
+
+        // without an override rustls sends no SNI for an ip (native-tls is used, and the
+        // certificate isn't for 127.0.0.1, so the request fails)
+        let (port, connections, _) = tls_server();
+        let context = context_for(&format!("https://127.0.0.1:{}", port), None);
+        assert!(get_root(&context).is_err());
+        assert_eq!(connections.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn sni_only_without_proxy() {
+        let sno = ServerNameOverride::new("kubernetes.internal".to_string(), None).unwrap();
+        assert_eq!(
+            Context::sni_name(Some(&sno), false, &ProxySetting::Off),
+            Some("kubernetes.internal")
+        );
+        // a proxy would connect to the override, not the server
+        let proxy = ProxySetting::Url(Url::parse("http://proxy.example.com:3128").unwrap());
+        assert_eq!(Context::sni_name(Some(&sno), false, &proxy), None);
+        assert_eq!(
+            Context::sni_name(Some(&sno), true, &ProxySetting::Off),
+            None
+        );
+        assert_eq!(Context::sni_name(None, false, &ProxySetting::Off), None);
+    }
+
+    #[test]
+    fn pooled_requests_reuse_connections() {
+        const REQUESTS: usize = 5;
+        let (port, connections, _) = tls_server();
+        let url = format!("https://127.0.0.1:{}", port);
+        let client = client_for(&url, Some("kubernetes.internal"), false).unwrap();
+        let send = |close: bool| {
+            for _ in 0..REQUESTS {
+                let mut req = client.get(&url);
+                if close {
+                    req = req.header(reqwest::header::CONNECTION, "close");
+                }
+                assert_eq!(req.send().unwrap().text().unwrap(), "ok");
+            }
+        };
+        // back to back requests share one connection, and so one tls handshake
+        send(false);
+        assert_eq!(connections.load(Ordering::SeqCst), 1);
+        // unless the connection is closed after each, which is what pooling saves. the first of
+        // these still goes over the pooled connection
+        send(true);
+        assert_eq!(connections.load(Ordering::SeqCst), REQUESTS);
+    }
+
+    #[test]
+    fn insecure_client_with_cert() {
+        // the identity has to be the kind the tls backend for the host wants
+        assert!(client_for("https://10.0.0.1:6443", None, true).is_ok());
+        assert!(client_for("https://10.0.0.1:6443", Some("kubernetes.internal"), true).is_ok());
+        assert!(client_for("https://cluster.example.com", None, true).is_ok());
+        assert!(client_for("https://10.0.0.1:6443", None, false).is_ok());
+    }
+}
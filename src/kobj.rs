@@ -12,8 +12,10 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use crate::command::OutputFormat;
 use crate::describe;
 use crate::error::ClickError;
+use crate::ingress::{Ingress, NetworkingV1, ServiceHealth};
 use crate::output::ClickWriter;
 use crate::values::val_str_opt;
 use crate::Env;
@@ -24,10 +26,13 @@ use clap::ArgMatches;
 use k8s_openapi::api::{
     apps::v1 as api_apps, batch::v1 as api_batch, core::v1 as api, storage::v1 as api_storage,
 };
+use k8s_openapi::http::{Request, StatusCode};
+use k8s_openapi::{List, ListOptional};
 
 use serde::ser::Serialize;
 use serde_json::Value;
 
+use std::collections::HashMap;
 use std::io::Write;
 
 #[derive(Clone, Debug, PartialEq)]
@@ -44,11 +49,15 @@ pub enum ObjType {
     Service,
     ReplicaSet,
     StatefulSet,
+    DaemonSet,
     ConfigMap,
     Secret,
     Job,
+    CronJob,
+    Ingress,
     Namespace,
     PersistentVolume,
+    PersistentVolumeClaim,
     StorageClass,
     #[cfg(feature = "argorollouts")]
     Rollout,
@@ -80,19 +89,80 @@ fn maybe_full_describe_output<T: ?Sized>(
 where
     T: Serialize,
 {
+    let full_output = matches.is_present("json")
+        || matches.is_present("yaml")
+        || OutputFormat::structured_from_matches(matches).is_some();
+    if !full_output {
+        return false;
+    }
+    let mut val = match serde_json::value::to_value(value) {
+        Ok(val) => val,
+        Err(e) => {
+            clickwriteln!(writer, "Could not convert object to json: {}", e);
+            return true;
+        }
+    };
+    if !matches.is_present("show_managed_fields") {
+        // these are noisy and rarely useful, so hidden by default
+        if let Some(metadata) = val.get_mut("metadata").and_then(|m| m.as_object_mut()) {
+            metadata.remove("managedFields");
+        }
+    }
     if matches.is_present("json") {
-        writer.pretty_color_json(value).unwrap_or(());
-        true
+        writer.pretty_color_json(&val).unwrap_or(());
     } else if matches.is_present("yaml") {
-        writer.print_yaml(value).unwrap_or(());
-        true
-    } else {
-        false
+        writer.print_yaml(&val).unwrap_or(());
+    } else if let Some(format) = OutputFormat::structured_from_matches(matches) {
+        format.write_value(&val, writer);
+    }
+    true
+}
+
+/// If --show-managed-fields was passed, print a summary of the managed fields of value. Used after
+/// printing a regular (non json/yaml) description
+fn maybe_managed_fields_output<T: ?Sized + Serialize>(
+    matches: &ArgMatches,
+    value: &T,
+    writer: &mut ClickWriter,
+) {
+    if matches.is_present("show_managed_fields") {
+        if let Ok(val) = serde_json::value::to_value(value) {
+            clickwrite!(writer, "{}", describe::describe_managed_fields(&val));
+        }
     }
 }
 
 static NOTSUPPORTED: &str = "not supported without -j or -y yet\n";
 
+/// Get the object at path, or None if it doesn't exist
+fn read_optional(env: &Env, path: &str) -> Result<Option<Value>, ClickError> {
+    let request = Request::get(path).body(vec![]).unwrap(); // safe, path built from valid names
+    let response = env.run_on_context(|c| c.execute(request))?;
+    match response.status() {
+        StatusCode::OK => Ok(Some(serde_json::from_slice(response.body())?)),
+        StatusCode::NOT_FOUND => Ok(None),
+        status => Err(ClickError::Status(
+            status.as_u16(),
+            status.canonical_reason().unwrap_or("").to_string(),
+        )),
+    }
+}
+
+/// Find out if the service called name exists, and how many endpoints it has ready
+fn service_health(env: &Env, namespace: &str, name: &str) -> ServiceHealth {
+    let path = format!("/api/v1/namespaces/{}/services/{}", namespace, name);
+    let service = match read_optional(env, &path) {
+        Ok(Some(service)) => service,
+        Ok(None) => return ServiceHealth::NotFound,
+        Err(e) => return ServiceHealth::Unknown(e.to_string()),
+    };
+    let path = format!("/api/v1/namespaces/{}/endpoints/{}", namespace, name);
+    match read_optional(env, &path) {
+        Ok(endpoints) => ServiceHealth::from_values(&service, endpoints.as_ref()),
+        Err(e) => ServiceHealth::Unknown(e.to_string()),
+    }
+}
+
 impl KObj {
     pub fn from_value(value: &Value, typ: ObjType) -> Option<KObj> {
         val_str_opt("/metadata/name", value).map(|name| KObj {
@@ -115,11 +185,15 @@ impl KObj {
             ObjType::Service => "Service",
             ObjType::ReplicaSet => "ReplicaSet",
             ObjType::StatefulSet => "StatefulSet",
+            ObjType::DaemonSet => "DaemonSet",
             ObjType::ConfigMap => "ConfigMap",
             ObjType::Secret => "Secret",
             ObjType::Job => "Job",
+            ObjType::CronJob => "CronJob",
+            ObjType::Ingress => "Ingress",
             ObjType::Namespace => "Namespace",
             ObjType::PersistentVolume => "PersistentVolume",
+            ObjType::PersistentVolumeClaim => "PersistentVolumeClaim",
             ObjType::StorageClass => "StorageClass",
             #[cfg(feature = "argorollouts")]
             ObjType::Rollout => "Rollout",
@@ -135,17 +209,59 @@ impl KObj {
             ObjType::Service => Cyan.bold().paint(self.name.as_str()),
             ObjType::ReplicaSet => Green.bold().paint(self.name.as_str()),
             ObjType::StatefulSet => Green.bold().paint(self.name.as_str()),
+            ObjType::DaemonSet => Green.bold().paint(self.name.as_str()),
             ObjType::ConfigMap => Purple.bold().paint(self.name.as_str()),
             ObjType::Secret => Red.bold().paint(self.name.as_str()),
             ObjType::Job => Purple.bold().paint(self.name.as_str()),
+            ObjType::CronJob => Purple.bold().paint(self.name.as_str()),
+            ObjType::Ingress => Cyan.bold().paint(self.name.as_str()),
             ObjType::Namespace => Green.bold().paint(self.name.as_str()),
             ObjType::PersistentVolume => Blue.bold().paint(self.name.as_str()),
+            ObjType::PersistentVolumeClaim => Blue.bold().paint(self.name.as_str()),
             ObjType::StorageClass => Red.bold().paint(self.name.as_str()),
             #[cfg(feature = "argorollouts")]
             ObjType::Rollout => Purple.bold().paint(self.name.as_str()),
         }
     }
 
+    /// The api path of this object, for generic requests like get and patch
+    pub fn api_path(&self, env: &Env) -> Result<String, ClickError> {
+        let (group_version, resource) = match &self.typ {
+            ObjType::Pod { .. } => ("v1", "pods"),
+            ObjType::Crd {
+                _type,
+                group_version,
+            } => (group_version.as_str(), _type.as_str()),
+            ObjType::Node => ("v1", "nodes"),
+            ObjType::Deployment => ("apps/v1", "deployments"),
+            ObjType::Service => ("v1", "services"),
+            ObjType::ReplicaSet => ("apps/v1", "replicasets"),
+            ObjType::StatefulSet => ("apps/v1", "statefulsets"),
+            ObjType::DaemonSet => ("apps/v1", "daemonsets"),
+            ObjType::ConfigMap => ("v1", "configmaps"),
+            ObjType::Secret => ("v1", "secrets"),
+            ObjType::Job => ("batch/v1", "jobs"),
+            ObjType::CronJob => (crate::cronjob::cronjob_api_version(env)?, "cronjobs"),
+            ObjType::Ingress => (crate::ingress::ingress_api_version(env)?, "ingresses"),
+            ObjType::Namespace => ("v1", "namespaces"),
+            ObjType::PersistentVolume => ("v1", "persistentvolumes"),
+            ObjType::PersistentVolumeClaim => ("v1", "persistentvolumeclaims"),
+            ObjType::StorageClass => ("storage.k8s.io/v1", "storageclasses"),
+            #[cfg(feature = "argorollouts")]
+            ObjType::Rollout => ("argoproj.io/v1alpha1", "rollouts"),
+        };
+        // the core group lives under /api, everything else under /apis
+        let prefix = if group_version == "v1" {
+            "/api/v1".to_string()
+        } else {
+            format!("/apis/{}", group_version)
+        };
+        Ok(match self.namespace.as_ref() {
+            Some(ns) => format!("{}/namespaces/{}/{}/{}", prefix, ns, resource, self.name),
+            None => format!("{}/{}/{}", prefix, resource, self.name),
+        })
+    }
+
     pub fn is(&self, typ: ObjType) -> bool {
         self.typ == typ
     }
@@ -165,10 +281,8 @@ impl KObj {
             api::ReadNamespacedEndpointsResponse::Ok(resp) => {
                 serde_json::value::to_value(&resp).ok()
             }
-            _ => {
-                clickwriteln!(writer, "Error fetching endpoints");
-                None
-            }
+            // services without a selector might not have endpoints, that's fine
+            _ => None,
         };
 
         let (request, _) =
@@ -178,6 +292,7 @@ impl KObj {
                 if !maybe_full_describe_output(matches, &service, writer) {
                     let val = serde_json::value::to_value(&service).unwrap();
                     clickwriteln!(writer, "{}", describe::describe_format_service(val, epval));
+                    maybe_managed_fields_output(matches, &service, writer);
                 }
             }
             _ => {
@@ -186,7 +301,199 @@ impl KObj {
         }
     }
 
+    // node also lists the pods running on it, so handle it here
+    fn node_describe(&self, matches: &ArgMatches, env: &Env, writer: &mut ClickWriter) {
+        let (request, _) = api::Node::read_node(&self.name, Default::default()).unwrap();
+        match env.run_on_context(|c| c.read(request)).unwrap() {
+            api::ReadNodeResponse::Ok(node) => {
+                if !maybe_full_describe_output(matches, &node, writer) {
+                    // like kubectl, skip pods that have finished
+                    let selector = format!(
+                        "spec.nodeName={},status.phase!=Failed,status.phase!=Succeeded",
+                        self.name
+                    );
+                    let (request, _) = api::Pod::list_pod_for_all_namespaces(ListOptional {
+                        field_selector: Some(&selector),
+                        ..Default::default()
+                    })
+                    .unwrap();
+                    let pods = env
+                        .run_on_context::<_, List<api::Pod>>(|c| c.execute_list(request))
+                        .ok()
+                        .and_then(|pods| serde_json::value::to_value(&pods).ok());
+                    let val = serde_json::value::to_value(&node).unwrap();
+                    clickwriteln!(writer, "{}", describe::describe_format_node(val, pods));
+                    maybe_managed_fields_output(matches, &node, writer);
+                }
+            }
+            _ => {
+                clickwriteln!(writer, "Invalid response trying to read node info");
+            }
+        }
+    }
+
+    // job also lists the pods it created, so handle it here
+    fn job_describe(&self, matches: &ArgMatches, env: &Env, writer: &mut ClickWriter) {
+        let ns = match self.namespace.as_ref() {
+            Some(ns) => ns,
+            None => {
+                clickwriteln!(writer, "No namespace for {}, cannot describe", self.name);
+                return;
+            }
+        };
+        let (request, _) =
+            api_batch::Job::read_namespaced_job(&self.name, ns, Default::default()).unwrap();
+        match env.run_on_context(|c| c.read(request)).unwrap() {
+            api_batch::ReadNamespacedJobResponse::Ok(job) => {
+                if !maybe_full_describe_output(matches, &job, writer) {
+                    let selector = format!("job-name={}", self.name);
+                    let (request, _) = api::Pod::list_namespaced_pod(
+                        ns,
+                        ListOptional {
+                            label_selector: Some(&selector),
+                            ..Default::default()
+                        },
+                    )
+                    .unwrap();
+                    let pods = env
+                        .run_on_context::<_, List<api::Pod>>(|c| c.execute_list(request))
+                        .ok()
+                        .and_then(|pods| serde_json::value::to_value(&pods).ok());
+                    let val = serde_json::value::to_value(&job).unwrap();
+                    clickwriteln!(writer, "{}", describe::describe_format_job(val, pods));
+                    maybe_managed_fields_output(matches, &job, writer);
+                }
+            }
+            _ => {
+                clickwriteln!(writer, "Invalid response trying to read job info");
+            }
+        }
+    }
+
+    // deployments show their replicasets, so handle it here
+    fn deployment_describe(&self, matches: &ArgMatches, env: &Env, writer: &mut ClickWriter) {
+        let ns = match self.namespace.as_ref() {
+            Some(ns) => ns,
+            None => {
+                clickwriteln!(writer, "No namespace for {}, cannot describe", self.name);
+                return;
+            }
+        };
+        let (request, _) =
+            api_apps::Deployment::read_namespaced_deployment(&self.name, ns, Default::default())
+                .unwrap();
+        match env.run_on_context(|c| c.read(request)).unwrap() {
+            api_apps::ReadNamespacedDeploymentResponse::Ok(deployment) => {
+                if !maybe_full_describe_output(matches, &deployment, writer) {
+                    let selector = deployment
+                        .spec
+                        .as_ref()
+                        .map(|spec| {
+                            spec.selector
+                                .match_labels
+                                .iter()
+                                .map(|(k, v)| format!("{}={}", k, v))
+                                .collect::<Vec<String>>()
+                                .join(",")
+                        })
+                        .unwrap_or_default();
+                    let (request, _) = api_apps::ReplicaSet::list_namespaced_replica_set(
+                        ns,
+                        ListOptional {
+                            label_selector: Some(&selector),
+                            ..Default::default()
+                        },
+                    )
+                    .unwrap();
+                    let replicasets = env
+                        .run_on_context::<_, List<api_apps::ReplicaSet>>(|c| {
+                            c.execute_list(request)
+                        })
+                        .ok()
+                        .and_then(|rs| serde_json::value::to_value(&rs).ok());
+                    let val = serde_json::value::to_value(&deployment).unwrap();
+                    clickwriteln!(
+                        writer,
+                        "{}",
+                        describe::describe_format_deployment(val, replicasets)
+                    );
+                    maybe_managed_fields_output(matches, &deployment, writer);
+                }
+            }
+            _ => {
+                clickwriteln!(writer, "Invalid response trying to read deployment info");
+            }
+        }
+    }
+
+    // configmap values are only shown if asked for, so handle it here
+    fn configmap_describe(&self, matches: &ArgMatches, env: &Env, writer: &mut ClickWriter) {
+        let ns = match self.namespace.as_ref() {
+            Some(ns) => ns,
+            None => {
+                clickwriteln!(writer, "No namespace for {}, cannot describe", self.name);
+                return;
+            }
+        };
+        let (request, _) =
+            api::ConfigMap::read_namespaced_config_map(&self.name, ns, Default::default()).unwrap();
+        match env.run_on_context(|c| c.read(request)).unwrap() {
+            api::ReadNamespacedConfigMapResponse::Ok(configmap) => {
+                if !maybe_full_describe_output(matches, &configmap, writer) {
+                    let val = serde_json::value::to_value(&configmap).unwrap();
+                    clickwriteln!(
+                        writer,
+                        "{}",
+                        describe::describe_format_configmap(val, matches.is_present("show"))
+                    );
+                    maybe_managed_fields_output(matches, &configmap, writer);
+                }
+            }
+            _ => {
+                clickwriteln!(writer, "Invalid response trying to read configmap info");
+            }
+        }
+    }
+
     // crd is a bit more complex, so handle it here
+    // ingress also checks the services it sends traffic to, so handle it here
+    fn ingress_describe(
+        &self,
+        matches: &ArgMatches,
+        env: &Env,
+        writer: &mut ClickWriter,
+    ) -> Result<(), ClickError> {
+        let ns = self.namespace.as_ref().unwrap();
+        let group_version = crate::ingress::ingress_api_version(env)?;
+        let (request, _) =
+            crate::crd::read_namespaced_resource(&self.name, ns, "ingresses", group_version)?;
+        let val = match env
+            .run_on_context(|c| c.read::<crate::crd::ReadResourceValueResponse>(request))?
+        {
+            crate::crd::ReadResourceValueResponse::Ok(val) => val,
+            crate::crd::ReadResourceValueResponse::Other(e) => {
+                clickwriteln!(writer, "Error getting response: {:?}", e);
+                return Ok(());
+            }
+        };
+        if maybe_full_describe_output(matches, &val, writer) {
+            return Ok(());
+        }
+        let ingress: Ingress<NetworkingV1> = serde_json::from_value(val)?;
+        let health: HashMap<String, ServiceHealth> = ingress
+            .backend_services()
+            .into_iter()
+            .map(|service| (service.to_string(), service_health(env, ns, service)))
+            .collect();
+        clickwriteln!(
+            writer,
+            "{}",
+            describe::describe_format_ingress(&ingress, &health)
+        );
+        maybe_managed_fields_output(matches, &ingress, writer);
+        Ok(())
+    }
+
     fn crd_describe(
         &self,
         _type: &str,
@@ -205,6 +512,7 @@ impl KObj {
             crate::crd::ReadResourceValueResponse::Ok(t) => {
                 if !maybe_full_describe_output(matches, &t, writer) {
                     clickwriteln!(writer, "{} {}", self.type_str(), NOTSUPPORTED);
+                    maybe_managed_fields_output(matches, &t, writer);
                 }
             }
             crate::crd::ReadResourceValueResponse::Other(e) => {
@@ -242,6 +550,7 @@ impl KObj {
                                     clickwriteln!(writer, "{} {}", self.type_str(), NOTSUPPORTED);
                                 }
                             }
+                            maybe_managed_fields_output(matches, &t, writer);
                         }
                     }
                     _ => {} // TODO
@@ -274,6 +583,7 @@ impl KObj {
                                             );
                                         }
                                     }
+                                    maybe_managed_fields_output(matches, &t, writer);
                                 }
                             }
                             _ => {}
@@ -287,28 +597,13 @@ impl KObj {
         }
         match self.typ {
             ObjType::ConfigMap => {
-                do_describe_with_namespace!(
-                    api::ConfigMap::read_namespaced_config_map,
-                    api::ReadNamespacedConfigMapResponse,
-                    api::ReadNamespacedConfigMapResponse::Ok,
-                    None
-                );
+                self.configmap_describe(matches, env, writer);
             }
             ObjType::Deployment => {
-                do_describe_with_namespace!(
-                    api_apps::Deployment::read_namespaced_deployment,
-                    api_apps::ReadNamespacedDeploymentResponse,
-                    api_apps::ReadNamespacedDeploymentResponse::Ok,
-                    Some(describe::describe_format_deployment)
-                );
+                self.deployment_describe(matches, env, writer);
             }
             ObjType::Job => {
-                do_describe_with_namespace!(
-                    api_batch::Job::read_namespaced_job,
-                    api_batch::ReadNamespacedJobResponse,
-                    api_batch::ReadNamespacedJobResponse::Ok,
-                    None
-                );
+                self.job_describe(matches, env, writer);
             }
             ObjType::Namespace => {
                 do_describe!(
@@ -319,12 +614,7 @@ impl KObj {
                 );
             }
             ObjType::Node => {
-                do_describe!(
-                    api::Node::read_node,
-                    api::ReadNodeResponse,
-                    api::ReadNodeResponse::Ok,
-                    Some(describe::describe_format_node)
-                );
+                self.node_describe(matches, env, writer);
             }
             ObjType::PersistentVolume => {
                 do_describe!(
@@ -334,6 +624,14 @@ impl KObj {
                     None
                 );
             }
+            ObjType::PersistentVolumeClaim => {
+                do_describe_with_namespace!(
+                    api::PersistentVolumeClaim::read_namespaced_persistent_volume_claim,
+                    api::ReadNamespacedPersistentVolumeClaimResponse,
+                    api::ReadNamespacedPersistentVolumeClaimResponse::Ok,
+                    None
+                );
+            }
             ObjType::Pod { .. } => {
                 do_describe_with_namespace!(
                     api::Pod::read_namespaced_pod,
@@ -369,6 +667,14 @@ impl KObj {
                     None
                 );
             }
+            ObjType::DaemonSet => {
+                do_describe_with_namespace!(
+                    api_apps::DaemonSet::read_namespaced_daemon_set,
+                    api_apps::ReadNamespacedDaemonSetResponse,
+                    api_apps::ReadNamespacedDaemonSetResponse::Ok,
+                    None
+                );
+            }
             ObjType::StorageClass => {
                 do_describe!(
                     api_storage::StorageClass::read_storage_class,
@@ -383,6 +689,13 @@ impl KObj {
             } => {
                 self.crd_describe(_type, group_version, matches, env, writer)?;
             }
+            ObjType::CronJob => {
+                let group_version = crate::cronjob::cronjob_api_version(env)?;
+                self.crd_describe("cronjobs", group_version, matches, env, writer)?;
+            }
+            ObjType::Ingress => {
+                self.ingress_describe(matches, env, writer)?;
+            }
             #[cfg(feature = "argorollouts")]
             ObjType::Rollout => {
                 use crate::command::rollouts;
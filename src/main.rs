@@ -14,7 +14,6 @@
 
 //! The Command Line Interactive Contoller for Kubernetes
 
-#[macro_use]
 extern crate duct;
 #[macro_use]
 extern crate lazy_static;
@@ -35,6 +34,7 @@ extern crate ctrlc;
 extern crate dirs;
 extern crate duct_sh;
 extern crate humantime;
+extern crate libc;
 extern crate os_pipe;
 extern crate regex;
 extern crate rustls;
@@ -51,8 +51,12 @@ extern crate bytes;
 extern crate k8s_openapi;
 extern crate reqwest;
 extern crate url;
+extern crate webpki;
+extern crate webpki_roots;
 extern crate yasna;
 
+mod cache;
+mod certs;
 #[macro_use]
 mod command;
 #[macro_use]
@@ -60,33 +64,66 @@ mod completer;
 mod command_processor;
 mod config;
 mod crd;
+mod cronjob;
 mod describe;
+mod diff;
 mod env;
 mod error;
+mod ingress;
 mod k8s;
 mod k8s_table;
 mod kobj;
 mod parser;
 mod table;
+mod template;
+mod trace;
 mod values;
 
 #[cfg(test)]
 mod duct_mock;
 
-use clap::{App, Arg};
+use clap::{App, Arg, ArgMatches};
 
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use crate::command_processor::CommandProcessor;
-use crate::config::{ClickConfig, Config};
+use crate::config::{ClickConfig, Config, ConfigSource};
 use crate::env::Env;
 
 use crate::output::ClickWriter;
 
-fn main() {
-    env_logger::init();
-    // Command line arg parsing for click itself
-    let matches = App::new("Click")
+/// The kubernetes config files to load, and where the list came from: --kubeconfig, otherwise
+/// $KUBECONFIG, otherwise the config file in conf_dir
+fn kubeconfig_paths(matches: &ArgMatches, conf_dir: &Path) -> (Vec<String>, ConfigSource) {
+    let (paths, source) = if let Some(paths) = matches.value_of_os("kubeconfig") {
+        (
+            std::env::split_paths(paths).collect::<Vec<PathBuf>>(),
+            ConfigSource::Arg,
+        )
+    } else if let Some(paths) = std::env::var_os("KUBECONFIG") {
+        (
+            std::env::split_paths(&paths).collect::<Vec<PathBuf>>(),
+            ConfigSource::Env,
+        )
+    } else {
+        (vec![conf_dir.join("config")], ConfigSource::Default)
+    };
+    let paths = paths
+        .into_iter()
+        .map(|config_file| {
+            config_file
+                .as_path()
+                .to_str()
+                .unwrap_or("[CONFIG_PATH_EMPTY]")
+                .to_owned()
+        })
+        .collect();
+    (paths, source)
+}
+
+/// The command line args for click itself. Also used to generate completion scripts for them
+pub fn cli_app() -> App<'static, 'static> {
+    App::new("Click")
         .version(crate_version!())
         .author("Nick Lanham <nick@databricks.com>")
         .about("Command Line Interactive Contoller for Kubernetes")
@@ -98,11 +135,26 @@ fn main() {
                 .help("Specify the directory to find kubernetes and click configs")
                 .takes_value(true),
         )
+        .arg(
+            Arg::with_name("kubeconfig")
+                .long("kubeconfig")
+                .value_name("PATHS")
+                .help(
+                    "The kubernetes config file(s) to use, separated by ':' like $KUBECONFIG. \
+                     Takes precedence over $KUBECONFIG, which takes precedence over the config \
+                     file in the config dir",
+                )
+                .takes_value(true),
+        )
         .arg(
             Arg::with_name("exec")
                 .long("exec")
                 .value_name("COMMAND")
-                .help("Execute the specified command then exit")
+                .help(
+                    "Execute the specified command then exit. The exit code says how the \
+                     command went: 0 success, 1 general error, 2 parse or usage error, \
+                     3 not authorized, 4 not found, 5 conflict, 6 server unreachable",
+                )
                 .takes_value(true),
         )
         .arg(
@@ -119,7 +171,32 @@ fn main() {
                 .help("Start in the specified namespace")
                 .takes_value(true),
         )
-        .get_matches();
+        .arg(
+            Arg::with_name("restore-forwards")
+                .long("restore-forwards")
+                .help("Restart the port forwards that were running when click last exited"),
+        )
+        .arg(
+            Arg::with_name("no-startup")
+                .long("no-startup")
+                .help("Don't run the startup_commands from the click config"),
+        )
+        .arg(
+            Arg::with_name("verbose")
+                .short("v")
+                .long("verbose")
+                .multiple(true)
+                .help(
+                    "Log each request's method, url, status and timing to stderr. Give it twice \
+                     to log headers too. Only lasts for this session, use 'set verbose' to keep \
+                     a level",
+                ),
+        )
+}
+
+fn main() {
+    env_logger::init();
+    let matches = cli_app().get_matches();
 
     let conf_dir = if let Some(dir) = matches.value_of("config_dir") {
         PathBuf::from(dir)
@@ -146,28 +223,12 @@ fn main() {
         }
     };
 
-    let config_paths = std::env::var_os("KUBECONFIG")
-        .map(|paths| {
-            let split_paths = std::env::split_paths(&paths);
-            split_paths.collect::<Vec<PathBuf>>()
-        })
-        .unwrap_or_else(|| {
-            let mut config_path = conf_dir.clone();
-            config_path.push("config");
-            vec![config_path]
-        })
-        .into_iter()
-        .map(|config_file| {
-            config_file
-                .as_path()
-                .to_str()
-                .unwrap_or("[CONFIG_PATH_EMPTY]")
-                .to_owned()
-        })
-        .collect::<Vec<_>>();
-
+    let (config_paths, config_source) = kubeconfig_paths(&matches, &conf_dir);
     let config = match Config::from_files(&config_paths) {
-        Ok(c) => c,
+        Ok(c) => Config {
+            source: config_source,
+            ..c
+        },
         Err(e) => {
             println!(
                 "Could not load kubernetes config. Cannot continue.  Error was: {}",
@@ -177,9 +238,6 @@ fn main() {
         }
     };
 
-    let mut hist_path = conf_dir;
-    hist_path.push("click.history");
-
     let mut env = Env::new(config, click_conf, click_path);
     if let Some(context) = matches.value_of("context") {
         env.set_context(Some(context));
@@ -187,12 +245,29 @@ fn main() {
     if let Some(namespace) = matches.value_of("namespace") {
         env.set_namespace(Some(namespace));
     }
+    if matches.is_present("verbose") {
+        env.set_verbosity(matches.occurrences_of("verbose") as u8, false);
+    }
+    if !matches.is_present("exec") {
+        // leave saved forwards for the next interactive session when just running one command
+        command::portforwards::handle_saved_forwards(
+            &mut env,
+            matches.is_present("restore-forwards"),
+        );
+    }
 
+    let hist_path = env.history_path();
     let mut processor = CommandProcessor::new(env, hist_path);
     if let Some(command) = matches.value_of("exec") {
         let writer = ClickWriter::new();
         processor.process_line(command, writer);
+        if let Some(category) = processor.last_error() {
+            std::process::exit(category.exit_code());
+        }
     } else {
+        if !matches.is_present("no-startup") {
+            processor.run_startup_commands();
+        }
         processor.run_repl();
     }
 }
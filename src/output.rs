@@ -76,22 +76,130 @@ impl PipeProc {
     }
 }
 
+/// Output that is held back until we know if it fits in the terminal. If it gets longer than
+/// `height` lines it goes to the pager, otherwise it is printed when the command finishes
+struct PagerBuffer {
+    cmd: String,
+    height: usize,
+    lines: usize,
+    buffer: Vec<u8>,
+}
+
+/// A running pager, we write into its stdin and it writes to the terminal
+struct PagerProc {
+    pipe: PipeWriter,
+    handle: Handle,
+}
+
+impl PagerProc {
+    fn start(cmd: &str, buffered: &[u8]) -> io::Result<PagerProc> {
+        let (pipe_read, mut pipe_write) = pipe()?;
+        // unchecked since pagers can exit with an error on ctrl-c, which isn't a problem for us
+        let handle = sh_dangerous(cmd)
+            .stdin_file(pipe_read)
+            .unchecked()
+            .start()?;
+        pipe_write.write_all(buffered)?;
+        Ok(PagerProc {
+            pipe: pipe_write,
+            handle,
+        })
+    }
+
+    fn finish(self) {
+        drop(self.pipe);
+        self.handle.wait().ok();
+    }
+}
+
+/// The height of the terminal stdout is connected to, or None if stdout isn't a terminal
+#[cfg(unix)]
+fn terminal_height() -> Option<usize> {
+    // safe: isatty and TIOCGWINSZ only read the fd and fill in the winsize we pass
+    unsafe {
+        if libc::isatty(libc::STDOUT_FILENO) != 1 {
+            return None;
+        }
+        let mut size: libc::winsize = std::mem::zeroed();
+        if libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) == 0 && size.ws_row > 0 {
+            Some(size.ws_row as usize)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(not(unix))]
+fn terminal_height() -> Option<usize> {
+    None
+}
+
+/// True if stdin is a terminal, rather than a pipe or a file
+#[cfg(unix)]
+pub fn stdin_is_tty() -> bool {
+    // safe: isatty just checks the fd
+    unsafe { libc::isatty(libc::STDIN_FILENO) == 1 }
+}
+
+#[cfg(not(unix))]
+pub fn stdin_is_tty() -> bool {
+    true
+}
+
+/// True if stderr is a terminal, so progress messages can be shown and overwritten there
+#[cfg(unix)]
+pub fn stderr_is_tty() -> bool {
+    // safe: isatty only looks at the fd
+    unsafe { libc::isatty(libc::STDERR_FILENO) == 1 }
+}
+
+#[cfg(not(unix))]
+pub fn stderr_is_tty() -> bool {
+    false
+}
+
+#[cfg(unix)]
+fn stdout_is_tty() -> bool {
+    // safe: isatty only looks at the fd
+    unsafe { libc::isatty(libc::STDOUT_FILENO) == 1 }
+}
+
+#[cfg(not(unix))]
+fn stdout_is_tty() -> bool {
+    true
+}
+
+/// Clear the terminal and move the cursor to the top left. Nothing is written if stdout isn't a
+/// terminal, so redirected output doesn't get escape codes
+pub fn clear_screen() {
+    if stdout_is_tty() {
+        let mut out = std::io::stdout();
+        write!(out, "\x1b[2J\x1b[H").unwrap_or(());
+        out.flush().unwrap_or(());
+    }
+}
+
 enum WriterOutput {
     Stdout(Stdout),
     #[allow(dead_code)] // used in test
     Buffer(Vec<u8>),
     File(File),
     Pipe(Box<PipeProc>),
+    PagerBuffer(Box<PagerBuffer>),
+    Pager(Box<PagerProc>),
 }
 
 pub struct ClickWriter {
     output: WriterOutput,
+    /// a copy of what's been written since start_capture, if capturing
+    capture: Option<Vec<u8>>,
 }
 
 impl ClickWriter {
     pub fn new() -> ClickWriter {
         ClickWriter {
             output: WriterOutput::Stdout(std::io::stdout()),
+            capture: None,
         }
     }
 
@@ -99,9 +207,20 @@ impl ClickWriter {
     pub fn with_buffer(buffer: Vec<u8>, _do_color: bool) -> ClickWriter {
         ClickWriter {
             output: WriterOutput::Buffer(buffer),
+            capture: None,
         }
     }
 
+    /// Start keeping a copy of everything written, as well as writing it as usual
+    pub fn start_capture(&mut self) {
+        self.capture = Some(vec![]);
+    }
+
+    /// Stop keeping a copy, and return what was written since start_capture
+    pub fn take_capture(&mut self) -> Option<Vec<u8>> {
+        self.capture.take()
+    }
+
     pub fn set_output_file(&mut self, file: File) {
         self.output = WriterOutput::File(file);
     }
@@ -117,8 +236,61 @@ impl ClickWriter {
         Ok(())
     }
 
+    /// Send output through cmd if it turns out to be longer than the terminal. Does nothing if
+    /// output isn't going to a terminal
+    pub fn setup_pager(&mut self, cmd: &str) {
+        if let WriterOutput::Stdout(_) = self.output {
+            if let Some(height) = terminal_height() {
+                self.output = WriterOutput::PagerBuffer(Box::new(PagerBuffer {
+                    cmd: cmd.to_string(),
+                    // leave room for the prompt
+                    height: height.saturating_sub(1),
+                    lines: 0,
+                    buffer: vec![],
+                }));
+            }
+        }
+    }
+
+    /// Write buf to the pager buffer, starting the pager if the output no longer fits
+    fn write_paged(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let start_pager = match self.output {
+            WriterOutput::PagerBuffer(ref mut pager_buffer) => {
+                pager_buffer.buffer.extend_from_slice(buf);
+                pager_buffer.lines += buf.iter().filter(|b| **b == b'\n').count();
+                pager_buffer.lines > pager_buffer.height
+            }
+            _ => false,
+        };
+        if start_pager {
+            let output = std::mem::replace(&mut self.output, WriterOutput::Stdout(io::stdout()));
+            if let WriterOutput::PagerBuffer(pager_buffer) = output {
+                match PagerProc::start(&pager_buffer.cmd, &pager_buffer.buffer) {
+                    Ok(pager) => self.output = WriterOutput::Pager(Box::new(pager)),
+                    Err(e) => {
+                        // fall back to just printing it
+                        let mut stdout = io::stdout();
+                        writeln!(stdout, "Could not start pager {}: {}", pager_buffer.cmd, e)?;
+                        stdout.write_all(&pager_buffer.buffer)?;
+                    }
+                }
+            }
+        }
+        Ok(buf.len())
+    }
+
     pub fn finish_output(self) -> Option<Vec<u8>> {
         match self.output {
+            WriterOutput::PagerBuffer(pager_buffer) => {
+                let mut stdout = io::stdout();
+                stdout.write_all(&pager_buffer.buffer).unwrap_or(());
+                stdout.flush().unwrap_or(());
+                None
+            }
+            WriterOutput::Pager(pager) => {
+                pager.finish();
+                None
+            }
             WriterOutput::Pipe(pipe_proc) => {
                 match pipe_proc.finish() {
                     Ok(out) => {
@@ -135,11 +307,29 @@ impl ClickWriter {
         }
     }
 
+    /// True if output is going to the terminal (possibly via the pager), and so can be colored
+    pub fn is_stdout(&self) -> bool {
+        matches!(
+            self.output,
+            WriterOutput::Stdout(_) | WriterOutput::PagerBuffer(_) | WriterOutput::Pager(_)
+        )
+    }
+
+    /// True if output is going to a terminal that will show colors, so not to a pipe, a file, or
+    /// a redirected stdout
+    pub fn use_color(&self) -> bool {
+        match self.output {
+            WriterOutput::Stdout(_) => stdout_is_tty(),
+            WriterOutput::PagerBuffer(_) | WriterOutput::Pager(_) => true,
+            _ => false,
+        }
+    }
+
     pub fn pretty_color_json<T: ?Sized>(&mut self, value: &T) -> Result<(), JsonError>
     where
         T: Serialize,
     {
-        if let WriterOutput::Stdout(_) = self.output {
+        if self.is_stdout() {
             let mut ser = Serializer::with_formatter(self, PrettyColorFormatter::new());
             value.serialize(&mut ser)
         } else {
@@ -164,12 +354,22 @@ impl Default for ClickWriter {
 
 impl Write for ClickWriter {
     fn write(&mut self, buf: &[u8]) -> Result<usize, io::Error> {
-        match self.output {
+        let res = match self.output {
             WriterOutput::Stdout(ref mut stdout) => stdout.write(buf),
             WriterOutput::Buffer(ref mut buffer) => buffer.write(buf),
             WriterOutput::File(ref mut file) => file.write(buf),
             WriterOutput::Pipe(ref mut pipe_proc) => pipe_proc.write(buf),
+            WriterOutput::PagerBuffer(_) => self.write_paged(buf),
+            WriterOutput::Pager(ref mut pager) => match pager.pipe.write(buf) {
+                // the user quit the pager, that's not an error for the command writing the output
+                Err(ref e) if e.kind() == io::ErrorKind::BrokenPipe => Ok(buf.len()),
+                res => res,
+            },
+        };
+        if let (Ok(written), Some(capture)) = (res.as_ref(), self.capture.as_mut()) {
+            capture.extend_from_slice(&buf[..*written]);
         }
+        res
     }
 
     fn flush(&mut self) -> Result<(), io::Error> {
@@ -178,6 +378,21 @@ impl Write for ClickWriter {
             WriterOutput::Buffer(ref mut buffer) => buffer.flush(),
             WriterOutput::File(ref mut file) => file.flush(),
             WriterOutput::Pipe(ref mut pipe_proc) => pipe_proc.flush(),
+            WriterOutput::PagerBuffer(_) => {
+                // flushing means the command wants the output seen now (e.g. it's about to ask a
+                // question), so give up on paging and print what we have
+                let output =
+                    std::mem::replace(&mut self.output, WriterOutput::Stdout(io::stdout()));
+                let mut stdout = io::stdout();
+                if let WriterOutput::PagerBuffer(pager_buffer) = output {
+                    stdout.write_all(&pager_buffer.buffer)?;
+                }
+                stdout.flush()
+            }
+            WriterOutput::Pager(ref mut pager) => match pager.pipe.flush() {
+                Err(ref e) if e.kind() == io::ErrorKind::BrokenPipe => Ok(()),
+                res => res,
+            },
         }
     }
 }
@@ -17,6 +17,7 @@
 // This is take and modified from:
 // https://github.com/klemens/cmdline-parser/blob/master/src/unix.rs
 
+use std::fmt;
 use std::iter::Peekable;
 use std::ops::Range;
 use std::str::CharIndices;
@@ -183,10 +184,479 @@ pub fn try_parse_csl(line: &str) -> Option<Box<dyn Iterator<Item = usize>>> {
     Some(Box::new(ret.into_iter()))
 }
 
+/// A single step in a jsonpath expression, see parse_jsonpath
+#[derive(Debug, PartialEq)]
+pub enum JsonPathSeg {
+    /// select the named field of an object
+    Field(String),
+    /// select the item at this index of an array
+    Index(usize),
+    /// select every item in an array (or every value in an object)
+    Wildcard,
+    /// select the items of an array whose value at path (relative to the item) is equal to value
+    Filter { path: Vec<String>, value: String },
+}
+
+/// Parse a kubectl style jsonpath expression like `{.spec.containers[*].name}`. We support the
+/// common subset of jsonpath: dotted fields, array indexes, `[*]` wildcards, and filters of the
+/// form `[?(@.key=="val")]`. The surrounding {} and a leading $ are optional.
+pub fn parse_jsonpath(expr: &str) -> Result<Vec<JsonPathSeg>, String> {
+    let mut expr = expr.trim();
+    if expr.starts_with('{') && expr.ends_with('}') {
+        expr = &expr[1..expr.len() - 1];
+    }
+    let expr = expr.strip_prefix('$').unwrap_or(expr);
+
+    let mut segs = vec![];
+    let mut rest = expr;
+    while !rest.is_empty() {
+        if let Some(bracketed) = rest.strip_prefix('[') {
+            let end = find_close_bracket(bracketed)
+                .ok_or_else(|| format!("Unclosed [ in jsonpath: {}", expr))?;
+            segs.push(parse_bracketed(&bracketed[..end])?);
+            rest = &bracketed[end + 1..];
+        } else {
+            // a field, which might not have a leading . if it's the first thing
+            let field_start = rest.strip_prefix('.').unwrap_or(rest);
+            let end = field_start.find(['.', '[']).unwrap_or(field_start.len());
+            let field = &field_start[..end];
+            match field {
+                "" => return Err(format!("Empty field name in jsonpath: {}", expr)),
+                "*" => segs.push(JsonPathSeg::Wildcard),
+                _ => segs.push(JsonPathSeg::Field(field.to_string())),
+            }
+            rest = &field_start[end..];
+        }
+    }
+    Ok(segs)
+}
+
+/// Split a comma separated list of sort keys, like `status,{.spec.nodeName},name`. Commas inside
+/// a jsonpath's brackets, braces or quotes don't split it
+pub fn split_sort_keys(keys: &str) -> Vec<&str> {
+    let mut ret = vec![];
+    let mut depth = 0;
+    let mut quote = None;
+    let mut start = 0;
+    for (i, c) in keys.char_indices() {
+        match (quote, c) {
+            (None, '"') | (None, '\'') => quote = Some(c),
+            (Some(q), c) if q == c => quote = None,
+            (None, '[') | (None, '{') | (None, '(') => depth += 1,
+            (None, ']') | (None, '}') | (None, ')') => depth -= 1,
+            (None, ',') if depth == 0 => {
+                ret.push(keys[start..i].trim());
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    ret.push(keys[start..].trim());
+    ret.retain(|key| !key.is_empty());
+    ret
+}
+
+/// find the index of the ] that closes a [, ignoring any inside quotes
+fn find_close_bracket(s: &str) -> Option<usize> {
+    let mut quote = None;
+    for (i, c) in s.char_indices() {
+        match (quote, c) {
+            (None, '"') | (None, '\'') => quote = Some(c),
+            (Some(q), c) if q == c => quote = None,
+            (None, ']') => return Some(i),
+            _ => {}
+        }
+    }
+    None
+}
+
+fn unquote(s: &str) -> &str {
+    let s = s.trim();
+    if s.len() >= 2
+        && ((s.starts_with('"') && s.ends_with('"')) || (s.starts_with('\'') && s.ends_with('\'')))
+    {
+        &s[1..s.len() - 1]
+    } else {
+        s
+    }
+}
+
+/// parse what was inside [...] in a jsonpath
+fn parse_bracketed(inner: &str) -> Result<JsonPathSeg, String> {
+    let inner = inner.trim();
+    if inner == "*" {
+        Ok(JsonPathSeg::Wildcard)
+    } else if let Ok(index) = inner.parse::<usize>() {
+        Ok(JsonPathSeg::Index(index))
+    } else if let Some(filter) = inner.strip_prefix("?(").and_then(|f| f.strip_suffix(')')) {
+        let (lhs, rhs) = match filter.find("==") {
+            Some(idx) => (&filter[..idx], &filter[idx + 2..]),
+            None => return Err(format!("Only == filters are supported, got: {}", filter)),
+        };
+        let lhs = lhs.trim();
+        let path_str = lhs
+            .strip_prefix('@')
+            .ok_or_else(|| format!("Filter must start with @, got: {}", lhs))?;
+        let path: Vec<String> = path_str
+            .split('.')
+            .filter(|p| !p.is_empty())
+            .map(|p| p.to_string())
+            .collect();
+        Ok(JsonPathSeg::Filter {
+            path,
+            value: unquote(rhs).to_string(),
+        })
+    } else if inner.starts_with('"') || inner.starts_with('\'') {
+        Ok(JsonPathSeg::Field(unquote(inner).to_string()))
+    } else {
+        Err(format!("Unsupported jsonpath subscript: [{}]", inner))
+    }
+}
+
+/// A single requirement in a label selector, see parse_label_selector
+#[derive(Debug, PartialEq)]
+pub enum SelectorReq {
+    Equals { key: String, value: String },
+    NotEquals { key: String, value: String },
+    In { key: String, values: Vec<String> },
+    NotIn { key: String, values: Vec<String> },
+    Exists(String),
+    NotExists(String),
+}
+
+impl fmt::Display for SelectorReq {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SelectorReq::Equals { key, value } => write!(f, "{}={}", key, value),
+            SelectorReq::NotEquals { key, value } => write!(f, "{}!={}", key, value),
+            SelectorReq::In { key, values } => write!(f, "{} in ({})", key, values.join(",")),
+            SelectorReq::NotIn { key, values } => {
+                write!(f, "{} notin ({})", key, values.join(","))
+            }
+            SelectorReq::Exists(key) => write!(f, "{}", key),
+            SelectorReq::NotExists(key) => write!(f, "!{}", key),
+        }
+    }
+}
+
+fn valid_label_key(key: &str) -> Result<String, String> {
+    if !key.is_empty()
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.' || c == '/')
+    {
+        Ok(key.to_string())
+    } else {
+        Err(format!("Invalid label key: '{}'", key))
+    }
+}
+
+fn valid_label_value(value: &str) -> Result<String, String> {
+    let value = value.trim();
+    if value
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.')
+    {
+        Ok(value.to_string())
+    } else {
+        Err(format!("Invalid label value: '{}'", value))
+    }
+}
+
+/// parse the (a,b,c) part of a set based requirement
+fn parse_label_set(set: &str) -> Result<Vec<String>, String> {
+    let inner = set
+        .trim()
+        .strip_prefix('(')
+        .and_then(|s| s.strip_suffix(')'))
+        .ok_or_else(|| format!("Expected a set like (a,b), got: {}", set.trim()))?;
+    inner.split(',').map(valid_label_value).collect()
+}
+
+fn parse_selector_req(req: &str) -> Result<SelectorReq, String> {
+    let req = req.trim();
+    if let Some(key) = req.strip_prefix('!') {
+        return Ok(SelectorReq::NotExists(valid_label_key(key.trim())?));
+    }
+    if let Some(idx) = req.find("!=") {
+        return Ok(SelectorReq::NotEquals {
+            key: valid_label_key(req[..idx].trim())?,
+            value: valid_label_value(&req[idx + 2..])?,
+        });
+    }
+    if let Some(idx) = req.find('=') {
+        let value = &req[idx + 1..];
+        return Ok(SelectorReq::Equals {
+            key: valid_label_key(req[..idx].trim())?,
+            value: valid_label_value(value.strip_prefix('=').unwrap_or(value))?,
+        });
+    }
+    let mut words = req.splitn(2, char::is_whitespace);
+    let key = words.next().unwrap_or("");
+    let rest = words.next().unwrap_or("").trim_start();
+    if let Some(set) = rest.strip_prefix("notin") {
+        Ok(SelectorReq::NotIn {
+            key: valid_label_key(key)?,
+            values: parse_label_set(set)?,
+        })
+    } else if let Some(set) = rest.strip_prefix("in") {
+        Ok(SelectorReq::In {
+            key: valid_label_key(key)?,
+            values: parse_label_set(set)?,
+        })
+    } else if rest.is_empty() {
+        Ok(SelectorReq::Exists(valid_label_key(key)?))
+    } else {
+        Err(format!("Invalid selector requirement: '{}'", req))
+    }
+}
+
+/// Parse a kubernetes label selector, like `app=nginx,tier!=db,env in (prod,qa),!canary`.
+/// Requirements are comma separated, except for commas inside the parens of a set
+pub fn parse_label_selector(selector: &str) -> Result<Vec<SelectorReq>, String> {
+    let mut reqs = vec![];
+    let mut depth = 0;
+    let mut start = 0;
+    for (i, c) in selector.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            ',' if depth == 0 => {
+                reqs.push(parse_selector_req(&selector[start..i])?);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    if depth != 0 {
+        return Err(format!("Unbalanced parentheses in selector: {}", selector));
+    }
+    reqs.push(parse_selector_req(&selector[start..])?);
+    Ok(reqs)
+}
+
+/// The context and namespace a single command should run against, and who it should impersonate,
+/// see extract_overrides
+#[derive(Debug, Default, PartialEq)]
+pub struct CommandOverrides<'a> {
+    pub context: Option<&'a str>,
+    pub namespace: Option<&'a str>,
+    pub as_user: Option<&'a str>,
+    pub as_groups: Vec<&'a str>,
+    pub as_uid: Option<&'a str>,
+}
+
+impl CommandOverrides<'_> {
+    pub fn is_empty(&self) -> bool {
+        self.context.is_none()
+            && self.namespace.is_none()
+            && self.as_user.is_none()
+            && self.as_groups.is_empty()
+            && self.as_uid.is_none()
+    }
+}
+
+/// The flags any command can be given to override the context, namespace or user it runs as (see
+/// extract_overrides)
+pub const OVERRIDE_FLAGS: &[&str] = &["--context", "--namespace", "--as", "--as-group", "--as-uid"];
+
+/// Pull `--context <name>`, `--namespace <ns>`, and the impersonation flags `--as <user>`,
+/// `--as-group <group>` (which can be repeated) and `--as-uid <uid>` out of the arguments to a
+/// command (`--context=<name>` etc. work too), returning the remaining arguments and the
+/// overrides. Anything after a `--` is left alone, so it can be passed on to things like exec
+pub fn extract_overrides<'a>(
+    args: &[&'a str],
+) -> Result<(Vec<&'a str>, CommandOverrides<'a>), String> {
+    let mut rest = vec![];
+    let mut overrides = CommandOverrides::default();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if *arg == "--" {
+            rest.push(*arg);
+            rest.extend(args);
+            break;
+        }
+        let (flag, inline_val) = match arg.split_once('=') {
+            Some((flag, val)) => (flag, Some(val)),
+            None => (*arg, None),
+        };
+        if !OVERRIDE_FLAGS.contains(&flag) {
+            rest.push(*arg);
+            continue;
+        }
+        let val = match inline_val {
+            Some(val) => val,
+            None => args
+                .next()
+                .copied()
+                .ok_or_else(|| format!("{} needs a value", flag))?,
+        };
+        if val.is_empty() {
+            return Err(format!("{} needs a value", flag));
+        }
+        let slot = match flag {
+            "--as-group" => {
+                overrides.as_groups.push(val);
+                continue;
+            }
+            "--context" => &mut overrides.context,
+            "--namespace" => &mut overrides.namespace,
+            "--as" => &mut overrides.as_user,
+            _ => &mut overrides.as_uid,
+        };
+        if slot.replace(val).is_some() {
+            return Err(format!("{} can only be specified once", flag));
+        }
+    }
+    if overrides.as_user.is_none()
+        && (!overrides.as_groups.is_empty() || overrides.as_uid.is_some())
+    {
+        return Err("--as-group and --as-uid can only be used with --as".to_string());
+    }
+    Ok((rest, overrides))
+}
+
+/// The number of single character insertions, deletions, substitutions or swaps of adjacent
+/// characters it takes to turn a into b
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    // dist[i][j] is the distance between a[..i] and b[..j]
+    let mut dist = vec![vec![0usize; b.len() + 1]; a.len() + 1];
+    for (i, row) in dist.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, cell) in dist[0].iter_mut().enumerate() {
+        *cell = j;
+    }
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = if a[i - 1] == b[j - 1] { 0 } else { 1 };
+            let mut best = (dist[i - 1][j] + 1)
+                .min(dist[i][j - 1] + 1)
+                .min(dist[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                best = best.min(dist[i - 2][j - 2] + 1);
+            }
+            dist[i][j] = best;
+        }
+    }
+    dist[a.len()][b.len()]
+}
+
+/// The candidate closest to word, if one is close enough that word is probably a typo of it
+pub fn closest_match<'a, I>(word: &str, candidates: I) -> Option<&'a str>
+where
+    I: IntoIterator<Item = &'a str>,
+{
+    let allowed = std::cmp::max(1, word.chars().count() / 4);
+    candidates
+        .into_iter()
+        .map(|candidate| (edit_distance(word, candidate), candidate))
+        .filter(|(distance, _)| *distance <= allowed)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate)
+}
+
+/// A single requirement in a field selector, see parse_field_selector
+#[derive(Debug, PartialEq)]
+pub struct FieldSelectorReq {
+    pub field: String,
+    pub value: String,
+    pub negated: bool,
+}
+
+impl fmt::Display for FieldSelectorReq {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let op = if self.negated { "!=" } else { "=" };
+        write!(f, "{}{}{}", self.field, op, self.value)
+    }
+}
+
+fn parse_field_selector_req(req: &str) -> Result<FieldSelectorReq, String> {
+    let req = req.trim();
+    let (field, value, negated) = if let Some(idx) = req.find("!=") {
+        (&req[..idx], &req[idx + 2..], true)
+    } else if let Some(idx) = req.find('=') {
+        let value = &req[idx + 1..];
+        (&req[..idx], value.strip_prefix('=').unwrap_or(value), false)
+    } else {
+        return Err(format!(
+            "Invalid field selector requirement: '{}', expected field=value or field!=value",
+            req
+        ));
+    };
+    let field = field.trim();
+    if field.is_empty()
+        || !field
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '_')
+    {
+        return Err(format!("Invalid field in selector: '{}'", field));
+    }
+    Ok(FieldSelectorReq {
+        field: field.to_string(),
+        value: value.trim().to_string(),
+        negated,
+    })
+}
+
+/// Parse a kubernetes field selector, like `status.phase=Running,spec.nodeName!=node1`. Only
+/// =, == and != are supported by the api server, and requirements are comma separated
+pub fn parse_field_selector(selector: &str) -> Result<Vec<FieldSelectorReq>, String> {
+    selector.split(',').map(parse_field_selector_req).collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    fn words(line: &str) -> Vec<String> {
+        Parser::new(line).map(|(_, _, word)| word).collect()
+    }
+
+    #[test]
+    fn parser_quoting_test() {
+        assert_eq!(words("pods  -r   web"), vec!["pods", "-r", "web"]);
+        // spaces in quotes are kept, and quotes of the other kind are just characters
+        assert_eq!(
+            words(r#"exec "ls -l" 'echo "hi there"' "it's""#),
+            vec!["exec", "ls -l", r#"echo "hi there""#, "it's"]
+        );
+        // quoted parts join with what's next to them, and empty quotes are an empty word
+        assert_eq!(words(r#"a"b c"'d' '' x"#), vec!["ab cd", "", "x"]);
+        // escapes outside quotes take the next character literally
+        assert_eq!(words(r"a\ b c\'d \\"), vec!["a b", "c'd", "\\"]);
+        // in double quotes only \" and \\ are escapes, other backslashes are kept
+        assert_eq!(
+            words(r#""say \"hi\"" "a\\b" "\n""#),
+            vec![r#"say "hi""#, r"a\b", r"\n"]
+        );
+        // nothing is special in single quotes
+        assert_eq!(words(r"'a\ | b'"), vec![r"a\ | b"]);
+        // an unfinished quote runs to the end of the line
+        assert_eq!(
+            words("logs 'unfinished quote"),
+            vec!["logs", "unfinished quote"]
+        );
+    }
+
+    #[test]
+    fn parser_separator_test() {
+        let parsed: Vec<(Range<usize>, char, String)> =
+            Parser::new("pods|grep 'a|b' > out").collect();
+        assert_eq!(
+            parsed,
+            vec![
+                (0..4, '|', "pods".to_string()),
+                (5..9, ' ', "grep".to_string()),
+                (10..15, ' ', "a|b".to_string()),
+                (16..16, '>', "".to_string()),
+                (18..21, ' ', "out".to_string()),
+            ]
+        );
+    }
+
     #[test]
     fn try_parse_csl_test() {
         let v: Vec<usize> = try_parse_csl("1,2,3").unwrap().collect();
@@ -248,4 +718,230 @@ mod tests {
         assert!(try_parse_range("").is_none());
         assert!(try_parse_range("   1..=2").is_none());
     }
+
+    #[test]
+    fn parse_label_selector_test() {
+        let key = |k: &str| k.to_string();
+        assert_eq!(
+            parse_label_selector("app=nginx").unwrap(),
+            vec![SelectorReq::Equals {
+                key: key("app"),
+                value: "nginx".to_string()
+            }]
+        );
+        assert_eq!(
+            parse_label_selector("app==nginx, tier!=db").unwrap(),
+            vec![
+                SelectorReq::Equals {
+                    key: key("app"),
+                    value: "nginx".to_string()
+                },
+                SelectorReq::NotEquals {
+                    key: key("tier"),
+                    value: "db".to_string()
+                },
+            ]
+        );
+        assert_eq!(
+            parse_label_selector("env in (prod, qa),!canary,k8s.io/role").unwrap(),
+            vec![
+                SelectorReq::In {
+                    key: key("env"),
+                    values: vec!["prod".to_string(), "qa".to_string()]
+                },
+                SelectorReq::NotExists(key("canary")),
+                SelectorReq::Exists(key("k8s.io/role")),
+            ]
+        );
+        assert_eq!(
+            parse_label_selector("env notin (dev)").unwrap(),
+            vec![SelectorReq::NotIn {
+                key: key("env"),
+                values: vec!["dev".to_string()]
+            }]
+        );
+        let canonical: Vec<String> = parse_label_selector("a = b,c in (d,e)")
+            .unwrap()
+            .iter()
+            .map(|r| r.to_string())
+            .collect();
+        assert_eq!(canonical.join(","), "a=b,c in (d,e)");
+
+        assert!(parse_label_selector("").is_err());
+        assert!(parse_label_selector("app=ng inx").is_err());
+        assert!(parse_label_selector("env in (a,b").is_err());
+        assert!(parse_label_selector("env in a").is_err());
+        assert!(parse_label_selector("a=b,,c=d").is_err());
+    }
+
+    #[test]
+    fn parse_jsonpath_test() {
+        assert_eq!(
+            parse_jsonpath("{.spec.nodeName}").unwrap(),
+            vec![
+                JsonPathSeg::Field("spec".to_string()),
+                JsonPathSeg::Field("nodeName".to_string())
+            ]
+        );
+        assert_eq!(
+            parse_jsonpath("$.spec.containers[*].ports[0]").unwrap(),
+            vec![
+                JsonPathSeg::Field("spec".to_string()),
+                JsonPathSeg::Field("containers".to_string()),
+                JsonPathSeg::Wildcard,
+                JsonPathSeg::Field("ports".to_string()),
+                JsonPathSeg::Index(0),
+            ]
+        );
+        assert_eq!(
+            parse_jsonpath(r#".status.conditions[?(@.type=="Ready")].status"#).unwrap(),
+            vec![
+                JsonPathSeg::Field("status".to_string()),
+                JsonPathSeg::Field("conditions".to_string()),
+                JsonPathSeg::Filter {
+                    path: vec!["type".to_string()],
+                    value: "Ready".to_string()
+                },
+                JsonPathSeg::Field("status".to_string()),
+            ]
+        );
+        assert_eq!(
+            parse_jsonpath("metadata.labels['app.kubernetes.io/name']").unwrap(),
+            vec![
+                JsonPathSeg::Field("metadata".to_string()),
+                JsonPathSeg::Field("labels".to_string()),
+                JsonPathSeg::Field("app.kubernetes.io/name".to_string()),
+            ]
+        );
+        assert!(parse_jsonpath(".spec..name").is_err());
+        assert!(parse_jsonpath(".spec.containers[0").is_err());
+        assert!(parse_jsonpath(".items[?(@.a!=1)]").is_err());
+    }
+
+    #[test]
+    fn split_sort_keys_test() {
+        assert_eq!(split_sort_keys("status,name"), vec!["status", "name"]);
+        assert_eq!(
+            split_sort_keys(r#"{.status.conditions[?(@.type=="a,b")].status}, age,"#),
+            vec![r#"{.status.conditions[?(@.type=="a,b")].status}"#, "age"]
+        );
+        assert_eq!(
+            split_sort_keys(".metadata.labels['x,y'],name"),
+            vec![".metadata.labels['x,y']", "name"]
+        );
+        assert!(split_sort_keys("").is_empty());
+    }
+
+    #[test]
+    fn jsonpath_values_test() {
+        use crate::values::jsonpath_str;
+        let pod = serde_json::json!({
+            "spec": {
+                "nodeName": "node-1",
+                "containers": [
+                    {"name": "app", "ports": [{"containerPort": 8080}]},
+                    {"name": "sidecar", "ports": [{"containerPort": 9090}]}
+                ]
+            },
+            "status": {
+                "conditions": [
+                    {"type": "Initialized", "status": "True"},
+                    {"type": "Ready", "status": "False"}
+                ]
+            }
+        });
+        let path = parse_jsonpath("{.spec.nodeName}").unwrap();
+        assert_eq!(jsonpath_str(&path, &pod), "node-1");
+        let path = parse_jsonpath("{.spec.containers[*].name}").unwrap();
+        assert_eq!(jsonpath_str(&path, &pod), "app sidecar");
+        let path = parse_jsonpath("{.spec.containers[1].ports[0].containerPort}").unwrap();
+        assert_eq!(jsonpath_str(&path, &pod), "9090");
+        let path = parse_jsonpath(r#"{.status.conditions[?(@.type=="Ready")].status}"#).unwrap();
+        assert_eq!(jsonpath_str(&path, &pod), "False");
+        let path = parse_jsonpath("{.spec.missing}").unwrap();
+        assert_eq!(jsonpath_str(&path, &pod), "");
+    }
+
+    #[test]
+    fn parse_field_selector_test() {
+        assert_eq!(
+            parse_field_selector("status.phase=Running").unwrap(),
+            vec![FieldSelectorReq {
+                field: "status.phase".to_string(),
+                value: "Running".to_string(),
+                negated: false,
+            }]
+        );
+        let canonical: Vec<String> =
+            parse_field_selector("status.phase==Running, spec.nodeName != node1")
+                .unwrap()
+                .iter()
+                .map(|r| r.to_string())
+                .collect();
+        assert_eq!(
+            canonical,
+            vec!["status.phase=Running", "spec.nodeName!=node1"]
+        );
+        // an empty value is allowed, e.g. for pods not yet scheduled
+        assert_eq!(parse_field_selector("spec.nodeName=").unwrap()[0].value, "");
+
+        assert!(parse_field_selector("").is_err());
+        assert!(parse_field_selector("status.phase").is_err());
+        assert!(parse_field_selector("=Running").is_err());
+        assert!(parse_field_selector("status phase=Running").is_err());
+        assert!(parse_field_selector("a=b,,c=d").is_err());
+    }
+
+    #[test]
+    fn edit_distance_test() {
+        assert_eq!(edit_distance("follow", "follow"), 0);
+        assert_eq!(edit_distance("folow", "follow"), 1);
+        assert_eq!(edit_distance("tial", "tail"), 1);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(edit_distance("", "abc"), 3);
+
+        let flags = ["--namespace", "--context", "--as", "--as-group"];
+        assert_eq!(
+            closest_match("--namepace", flags.iter().copied()),
+            Some("--namespace")
+        );
+        assert_eq!(
+            closest_match("--as-grop", flags.iter().copied()),
+            Some("--as-group")
+        );
+        assert_eq!(closest_match("--labels", flags.iter().copied()), None);
+    }
+
+    #[test]
+    fn extract_overrides_test() {
+        let (rest, overrides) =
+            extract_overrides(&["-l", "app=x", "--context", "prod", "--namespace=web"]).unwrap();
+        assert_eq!(rest, vec!["-l", "app=x"]);
+        assert_eq!(overrides.context, Some("prod"));
+        assert_eq!(overrides.namespace, Some("web"));
+
+        let (rest, overrides) = extract_overrides(&["ls", "--", "--context", "x"]).unwrap();
+        assert_eq!(rest, vec!["ls", "--", "--context", "x"]);
+        assert!(overrides.is_empty());
+
+        assert!(extract_overrides(&["--context"]).is_err());
+        assert!(extract_overrides(&["--namespace="]).is_err());
+        assert!(extract_overrides(&["--context", "a", "--context", "b"]).is_err());
+
+        let (rest, overrides) = extract_overrides(&[
+            "--as",
+            "alice",
+            "--as-group=devs",
+            "--as-group",
+            "system:masters",
+            "--as-uid",
+            "42",
+        ])
+        .unwrap();
+        assert!(rest.is_empty());
+        assert_eq!(overrides.as_user, Some("alice"));
+        assert_eq!(overrides.as_groups, vec!["devs", "system:masters"]);
+        assert_eq!(overrides.as_uid, Some("42"));
+        assert!(extract_overrides(&["--as-group", "devs"]).is_err());
+    }
 }
@@ -16,15 +16,16 @@
 /// applying filters and sorting
 use crate::output::ClickWriter;
 
+use ansi_term::Colour;
+use chrono::{DateTime, Duration, Utc};
 use clap::ArgMatches;
-use prettytable::Cell;
 use prettytable::Row;
+use prettytable::{color, Attr, Cell};
 use prettytable::{format, Table};
 use regex::Regex;
 
 use std::borrow::Cow;
 use std::cmp::{Ordering, PartialEq, PartialOrd};
-use std::io::Write;
 
 lazy_static! {
     pub static ref TBLFMT: format::TableFormat = format::FormatBuilder::new()
@@ -34,12 +35,27 @@ lazy_static! {
         )
         .padding(1, 1)
         .build();
+    /// Format for tables printed a page at a time. There's no bottom separator as we don't know
+    /// which page is the last one when we print it
+    pub static ref STREAMFMT: format::TableFormat = format::FormatBuilder::new()
+        .separators(
+            &[format::LinePosition::Title],
+            format::LineSeparator::new('-', '+', '+', '+')
+        )
+        .padding(1, 1)
+        .build();
+    /// Format for tables printed without titles (--no-headers): just the columns, for scripts
+    pub static ref PLAINFMT: format::TableFormat = format::FormatBuilder::new()
+        .padding(0, 2)
+        .build();
 }
 
 #[derive(Debug)]
 enum CellSpecTxt<'a> {
     Index,
     Int(i64),
+    /// An int followed by some extra text, sorted by the int
+    IntSuffix(i64, String),
     Str(Cow<'a, str>),
 }
 
@@ -67,6 +83,14 @@ impl<'a> CellSpec<'a> {
         }
     }
 
+    pub fn new_int_suffix(num: i64, suffix: String) -> CellSpec<'a> {
+        CellSpec {
+            txt: CellSpecTxt::IntSuffix(num, suffix),
+            style: None,
+            align: None,
+        }
+    }
+
     pub fn with_style(txt: Cow<'a, str>, style: &'a str) -> CellSpec<'a> {
         CellSpec {
             txt: CellSpecTxt::Str(txt),
@@ -84,18 +108,32 @@ impl<'a> CellSpec<'a> {
     }
 
     pub fn to_cell(&self, index: usize) -> Cell {
+        self.to_cell_min_width(index, 0)
+    }
+
+    /// Build a cell that is at least min_width wide, padding the text as needed. This is used
+    /// when printing tables a page at a time, so that later pages line up with earlier ones
+    pub fn to_cell_min_width(&self, index: usize, min_width: usize) -> Cell {
         let mut cell = match &self.txt {
             CellSpecTxt::Index => {
-                let mut c = Cell::new(format!("{}", index).as_str());
+                let mut c = Cell::new(&format!("{:>1$}", index, min_width));
                 c.align(format::Alignment::RIGHT);
                 c
             }
             CellSpecTxt::Int(num) => {
-                let mut c = Cell::new(format!("{}", num).as_str());
+                let mut c = Cell::new(&format!("{:>1$}", num, min_width));
+                c.align(format::Alignment::RIGHT);
+                c
+            }
+            CellSpecTxt::IntSuffix(num, suffix) => {
+                let mut c = Cell::new(&format!("{:>1$}", format!("{} {}", num, suffix), min_width));
                 c.align(format::Alignment::RIGHT);
                 c
             }
-            CellSpecTxt::Str(s) => Cell::new(s),
+            CellSpecTxt::Str(s) => match self.align {
+                Some(format::Alignment::RIGHT) => Cell::new(&format!("{:>1$}", s, min_width)),
+                _ => Cell::new(&format!("{:1$}", s, min_width)),
+            },
         };
 
         if let Some(a) = self.align {
@@ -109,6 +147,18 @@ impl<'a> CellSpec<'a> {
         }
     }
 
+    /// The width (in chars) the text of this cell will take up, for the given index
+    pub fn width(&self, index: usize) -> usize {
+        match &self.txt {
+            CellSpecTxt::Index => format!("{}", index).len(),
+            CellSpecTxt::Int(num) => format!("{}", num).len(),
+            CellSpecTxt::IntSuffix(num, suffix) => {
+                format!("{}", num).len() + 1 + suffix.chars().count()
+            }
+            CellSpecTxt::Str(s) => s.lines().map(|l| l.chars().count()).max().unwrap_or(0),
+        }
+    }
+
     pub fn matches(&self, regex: &Regex) -> bool {
         match &self.txt {
             CellSpecTxt::Index => false,
@@ -116,6 +166,7 @@ impl<'a> CellSpec<'a> {
                 let s = format!("{}", num); // TODO: is there a fast way to do this
                 regex.is_match(&s)
             }
+            CellSpecTxt::IntSuffix(num, suffix) => regex.is_match(&format!("{} {}", num, suffix)),
             CellSpecTxt::Str(s) => regex.is_match(s),
         }
     }
@@ -186,6 +237,9 @@ impl<'a> PartialEq for CellSpec<'a> {
             (CellSpecTxt::Index, CellSpecTxt::Index) => true,
             (CellSpecTxt::Str(st), CellSpecTxt::Str(ot)) => st == ot,
             (CellSpecTxt::Int(num1), CellSpecTxt::Int(num2)) => num1 == num2,
+            (CellSpecTxt::IntSuffix(num1, s1), CellSpecTxt::IntSuffix(num2, s2)) => {
+                num1 == num2 && s1 == s2
+            }
             _ => false,
         }
     }
@@ -196,8 +250,16 @@ impl<'a> PartialOrd for CellSpec<'a> {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
         match (&self.txt, &other.txt) {
             (CellSpecTxt::Index, CellSpecTxt::Index) => Some(Ordering::Equal),
-            (CellSpecTxt::Str(st), CellSpecTxt::Str(ot)) => st.partial_cmp(ot),
-            (CellSpecTxt::Int(num1), CellSpecTxt::Int(num2)) => num1.partial_cmp(num2),
+            (CellSpecTxt::Str(st), CellSpecTxt::Str(ot)) => Some(str_cmp(st, ot)),
+            (CellSpecTxt::Int(num1), CellSpecTxt::Int(num2))
+            | (CellSpecTxt::IntSuffix(num1, _), CellSpecTxt::IntSuffix(num2, _))
+            | (CellSpecTxt::Int(num1), CellSpecTxt::IntSuffix(num2, _))
+            | (CellSpecTxt::IntSuffix(num1, _), CellSpecTxt::Int(num2)) => num1.partial_cmp(num2),
+            // numbers go before text like "Unknown" in the same column
+            (CellSpecTxt::Int(_), CellSpecTxt::Str(_))
+            | (CellSpecTxt::IntSuffix(_, _), CellSpecTxt::Str(_)) => Some(Ordering::Less),
+            (CellSpecTxt::Str(_), CellSpecTxt::Int(_))
+            | (CellSpecTxt::Str(_), CellSpecTxt::IntSuffix(_, _)) => Some(Ordering::Greater),
             _ => None,
         }
     }
@@ -212,6 +274,37 @@ impl<'a> Ord for CellSpec<'a> {
     }
 }
 
+/// Compare the text of two cells. If both are numbers (like values picked out by a jsonpath column)
+/// they compare numerically, otherwise lexically
+fn str_cmp(a: &str, b: &str) -> Ordering {
+    match (a.parse::<f64>(), b.parse::<f64>()) {
+        (Ok(anum), Ok(bnum)) => anum.partial_cmp(&bnum).unwrap_or(Ordering::Equal),
+        _ => a.cmp(b),
+    }
+}
+
+/// A way of comparing two rows, for sort_by_keys
+pub type SortKeyCmp<'a, R> = Box<dyn Fn(&R, &R) -> Ordering + 'a>;
+
+/// Sort rows by each of keys in turn: later keys only break ties in earlier ones. With descending
+/// each key's order is flipped. The sort is stable, so rows that tie on every key keep their
+/// original (api server) order either way, which is how this differs from reversing the list
+pub fn sort_by_keys<R>(rows: &mut [R], keys: &[SortKeyCmp<R>], descending: bool) {
+    rows.sort_by(|a, b| {
+        keys.iter()
+            .map(|key| {
+                let ord = key(a, b);
+                if descending {
+                    ord.reverse()
+                } else {
+                    ord
+                }
+            })
+            .find(|ord| *ord != Ordering::Equal)
+            .unwrap_or(Ordering::Equal)
+    });
+}
+
 pub fn get_regex(matches: &ArgMatches) -> Result<Option<Regex>, String> {
     match matches.value_of("regex") {
         Some(pattern) => {
@@ -225,7 +318,11 @@ pub fn get_regex(matches: &ArgMatches) -> Result<Option<Regex>, String> {
     }
 }
 
-fn term_print_table<T: Write>(table: &Table, writer: &mut T) -> bool {
+/// Print table with colors, if writer is going to a terminal. Returns false if it wasn't printed
+fn term_print_table(table: &Table, writer: &mut ClickWriter) -> bool {
+    if !writer.use_color() {
+        return false;
+    }
     match term::TerminfoTerminal::new(writer) {
         Some(ref mut term) => {
             table.print_term(term).unwrap_or(0);
@@ -242,16 +339,251 @@ pub fn print_filled_table(table: &mut Table, writer: &mut ClickWriter) {
     }
 }
 
-#[allow(clippy::ptr_arg)]
-pub fn print_table(titles: Row, specs: Vec<Vec<CellSpec<'_>>>, writer: &mut ClickWriter) {
+fn build_table(specs: Vec<Vec<CellSpec<'_>>>) -> Table {
     let mut table = Table::new();
-    table.set_titles(titles);
     for (index, t_spec) in specs.iter().enumerate() {
         let row_vec: Vec<Cell> = t_spec.iter().map(|spec| spec.to_cell(index)).collect();
         table.add_row(Row::new(row_vec));
     }
+    table
+}
+
+#[allow(clippy::ptr_arg)]
+pub fn print_table(titles: Row, specs: Vec<Vec<CellSpec<'_>>>, writer: &mut ClickWriter) {
+    let mut table = build_table(specs);
+    table.set_titles(titles);
     table.set_format(*TBLFMT);
     if !term_print_table(&table, writer) {
         table.print(writer).unwrap_or(0);
     }
 }
+
+/// Print just the rows, with no titles or separators, so scripts don't have to skip them
+pub fn print_table_no_titles(specs: Vec<Vec<CellSpec<'_>>>, writer: &mut ClickWriter) {
+    let mut table = build_table(specs);
+    table.set_format(*PLAINFMT);
+    if !term_print_table(&table, writer) {
+        table.print(writer).unwrap_or(0);
+    }
+}
+
+/// A cell for a table title, in colour if one is given. Only the named colours can be shown
+pub fn title_cell(title: &str, colour: Option<Colour>) -> Cell {
+    let term_colour = match colour {
+        Some(Colour::Black) => color::BLACK,
+        Some(Colour::Red) => color::RED,
+        Some(Colour::Green) => color::GREEN,
+        Some(Colour::Yellow) => color::YELLOW,
+        Some(Colour::Blue) => color::BLUE,
+        Some(Colour::Purple) => color::MAGENTA,
+        Some(Colour::Cyan) => color::CYAN,
+        Some(Colour::White) => color::WHITE,
+        _ => return Cell::new(title),
+    };
+    Cell::new(title).with_style(Attr::ForegroundColor(term_colour))
+}
+
+/// Prints a table one page of rows at a time, as the rows become available. Column widths are
+/// fixed by the first page (and the titles), and only grow if a later page has a wider value, so
+/// alignment across pages is best effort
+pub struct StreamingTable {
+    titles: Option<Vec<String>>,
+    title_colour: Option<Colour>,
+    widths: Vec<usize>,
+    next_index: usize,
+    plain: bool,
+}
+
+impl StreamingTable {
+    pub fn new(titles: Vec<String>, title_colour: Option<Colour>) -> StreamingTable {
+        let widths = titles.iter().map(|t| t.chars().count()).collect();
+        StreamingTable {
+            titles: Some(titles),
+            title_colour,
+            widths,
+            next_index: 0,
+            plain: false,
+        }
+    }
+
+    /// A table that prints no titles or separators (for --no-headers)
+    pub fn without_titles() -> StreamingTable {
+        StreamingTable {
+            titles: None,
+            title_colour: None,
+            widths: vec![],
+            next_index: 0,
+            plain: true,
+        }
+    }
+
+    /// Print the rows in specs. Index cells are numbered continuing on from any earlier pages
+    pub fn print_page(&mut self, specs: Vec<Vec<CellSpec<'_>>>, writer: &mut ClickWriter) {
+        for (offset, t_spec) in specs.iter().enumerate() {
+            for (col, spec) in t_spec.iter().enumerate() {
+                let width = spec.width(self.next_index + offset);
+                match self.widths.get_mut(col) {
+                    Some(w) => *w = std::cmp::max(*w, width),
+                    None => self.widths.push(width),
+                }
+            }
+        }
+
+        let mut table = Table::new();
+        if let Some(titles) = self.titles.take() {
+            let title_cells: Vec<Cell> = titles
+                .iter()
+                .zip(self.widths.iter())
+                .map(|(title, width)| {
+                    title_cell(&format!("{:1$}", title, width), self.title_colour)
+                })
+                .collect();
+            table.set_titles(Row::new(title_cells));
+        }
+        for t_spec in specs.iter() {
+            let row_vec: Vec<Cell> = t_spec
+                .iter()
+                .zip(self.widths.iter())
+                .map(|(spec, width)| spec.to_cell_min_width(self.next_index, *width))
+                .collect();
+            table.add_row(Row::new(row_vec));
+            self.next_index += 1;
+        }
+        table.set_format(if self.plain { *PLAINFMT } else { *STREAMFMT });
+        if !term_print_table(&table, writer) {
+            table.print(writer).unwrap_or(0);
+        }
+    }
+}
+
+/// Format a duration like kubectl formats ages: the longer the duration, the less precise
+/// (e.g. 45s, 5m30s, 12m, 3h20m, 20h, 5d, 2y30d)
+pub fn format_duration(duration: Duration) -> String {
+    let seconds = duration.num_seconds();
+    if seconds < -1 {
+        // a bit of clock skew is fine, more means something is wrong
+        return "<invalid>".to_string();
+    } else if seconds < 0 {
+        return "0s".to_string();
+    } else if seconds < 60 * 2 {
+        return format!("{}s", seconds);
+    }
+    let minutes = duration.num_minutes();
+    if minutes < 10 {
+        let s = seconds % 60;
+        return if s == 0 {
+            format!("{}m", minutes)
+        } else {
+            format!("{}m{}s", minutes, s)
+        };
+    } else if minutes < 60 * 3 {
+        return format!("{}m", minutes);
+    }
+    let hours = duration.num_hours();
+    if hours < 8 {
+        let m = minutes % 60;
+        if m == 0 {
+            format!("{}h", hours)
+        } else {
+            format!("{}h{}m", hours, m)
+        }
+    } else if hours < 48 {
+        format!("{}h", hours)
+    } else if hours < 24 * 8 {
+        let h = hours % 24;
+        if h == 0 {
+            format!("{}d", hours / 24)
+        } else {
+            format!("{}d{}h", hours / 24, h)
+        }
+    } else if hours < 24 * 365 * 2 {
+        format!("{}d", hours / 24)
+    } else if hours < 24 * 365 * 8 {
+        let d = (hours / 24) % 365;
+        if d == 0 {
+            format!("{}y", hours / 24 / 365)
+        } else {
+            format!("{}y{}d", hours / 24 / 365, d)
+        }
+    } else {
+        format!("{}y", hours / 24 / 365)
+    }
+}
+
+/// How long ago date was, formatted by format_duration
+pub fn time_since(date: DateTime<Utc>) -> String {
+    format_duration(Utc::now().signed_duration_since(date))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_duration() {
+        let cases = [
+            (Duration::seconds(-5), "<invalid>"),
+            (Duration::milliseconds(-500), "0s"),
+            (Duration::seconds(45), "45s"),
+            (Duration::seconds(119), "119s"),
+            (Duration::seconds(330), "5m30s"),
+            (Duration::minutes(5), "5m"),
+            (Duration::minutes(12), "12m"),
+            (Duration::minutes(200), "3h20m"),
+            (Duration::hours(3), "3h"),
+            (Duration::hours(20), "20h"),
+            (Duration::hours(50), "2d2h"),
+            (Duration::days(5), "5d"),
+            (Duration::days(40), "40d"),
+            (Duration::days(760), "2y30d"),
+            (Duration::days(365 * 3), "3y"),
+            (Duration::days(365 * 10), "10y"),
+        ];
+        for (duration, expected) in cases.iter() {
+            assert_eq!(format_duration(*duration), *expected);
+        }
+    }
+
+    #[test]
+    fn int_suffix_sorts_by_int() {
+        let mut specs = [
+            CellSpec::new_int_suffix(10, "(5m ago)".to_string()),
+            CellSpec::new_int(2),
+            CellSpec::new_int_suffix(3, "(1h ago)".to_string()),
+        ];
+        specs.sort();
+        let widths: Vec<usize> = specs.iter().map(|s| s.width(0)).collect();
+        assert_eq!(widths, [1, 10, 11]);
+        assert!(specs[2].matches(&Regex::new("^10 \\(5m").unwrap()));
+    }
+
+    #[test]
+    fn numeric_text_sorts_numerically() {
+        let mut specs: Vec<CellSpec> = vec!["10".into(), "Unknown".into(), "9".into(), "b".into()];
+        specs.push(CellSpec::new_int(3));
+        specs.sort();
+        let widths: Vec<usize> = specs.iter().map(|s| s.width(0)).collect();
+        assert_eq!(widths, [1, 1, 2, 7, 1]);
+        assert!(specs[0].matches(&Regex::new("^3$").unwrap()));
+        assert!(specs[4].matches(&Regex::new("^b$").unwrap()));
+    }
+
+    #[test]
+    fn sort_by_keys_chains_and_keeps_ties() {
+        // (status, name, api order)
+        let mut rows = vec![
+            ("Running", "b", 0),
+            ("Pending", "a", 1),
+            ("Running", "a", 2),
+            ("Running", "a", 3),
+        ];
+        let keys: Vec<SortKeyCmp<(&str, &str, i32)>> =
+            vec![Box::new(|a, b| a.0.cmp(b.0)), Box::new(|a, b| a.1.cmp(b.1))];
+        sort_by_keys(&mut rows, &keys, false);
+        let order: Vec<i32> = rows.iter().map(|r| r.2).collect();
+        assert_eq!(order, [1, 2, 3, 0]);
+        sort_by_keys(&mut rows, &keys, true);
+        let order: Vec<i32> = rows.iter().map(|r| r.2).collect();
+        assert_eq!(order, [0, 2, 3, 1]);
+    }
+}
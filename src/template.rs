@@ -0,0 +1,781 @@
+// Copyright 2021 Databricks, Inc.
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+
+// http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A small renderer for Go templates, for `-o go-template=...`. It supports the subset people
+//! commonly use with kubectl:
+//!
+//! * text, and actions in `{{ }}`, with `{{-` and `-}}` to trim surrounding whitespace
+//! * `.` and field chains like `.metadata.name`, `$` (the object being rendered) and `$.spec`
+//! * string (`"..."` or `` `...` ``), number, `true`, `false` and `nil` literals
+//! * `{{if PIPELINE}} ... {{else}} ... {{end}}`, `{{else if ...}}` included
+//! * `{{range PIPELINE}} ... {{else}} ... {{end}}` over arrays and maps (in key order)
+//! * pipelines with `|`, and the functions `printf`, `len`, `index`, `eq`, `ne`, `not`, `and` and
+//!   `or`
+//! * comments, `{{/* ... */}}`
+//!
+//! Variables other than `$`, `with`, `define`/`template`, and parenthesized sub-expressions are
+//! not supported.
+
+use serde_json::Value;
+
+use std::fmt::Write;
+
+/// A parsed template
+#[derive(Debug)]
+pub struct Template {
+    nodes: Vec<Node>,
+}
+
+#[derive(Debug)]
+enum Node {
+    Text(String),
+    Action(Pipeline),
+    If {
+        cond: Pipeline,
+        then: Vec<Node>,
+        otherwise: Vec<Node>,
+    },
+    Range {
+        over: Pipeline,
+        body: Vec<Node>,
+        otherwise: Vec<Node>,
+    },
+}
+
+/// commands separated by |. The result of each command is passed as the last argument of the next
+#[derive(Debug)]
+struct Pipeline {
+    commands: Vec<Command>,
+    /// the action this came from, for errors
+    source: String,
+}
+
+#[derive(Debug)]
+enum Command {
+    Term(Term),
+    Call { func: String, args: Vec<Term> },
+}
+
+#[derive(Debug)]
+enum Term {
+    /// fields of ., empty for . itself
+    Dot(Vec<String>),
+    /// fields of $
+    Root(Vec<String>),
+    Literal(Value),
+}
+
+/// The text of a template split into text and actions. Actions have their delimiters removed
+#[derive(Debug)]
+enum Token<'a> {
+    Text(&'a str),
+    Action { body: &'a str, source: &'a str },
+}
+
+fn err_at(template: &str, pos: usize, msg: &str) -> String {
+    format!("{}: '{}' at position {}", msg, template, pos)
+}
+
+fn tokenize(template: &str) -> Result<Vec<Token<'_>>, String> {
+    let mut tokens = vec![];
+    let mut rest = template;
+    let mut offset = 0;
+    let mut trim_next = false;
+    while let Some(start) = rest.find("{{") {
+        let mut text = &rest[..start];
+        if trim_next {
+            text = text.trim_start();
+        }
+        let after_open = &rest[start + 2..];
+        let end = after_open.find("}}").ok_or_else(|| {
+            err_at(
+                &rest[start..],
+                offset + start,
+                "Unclosed action in go-template",
+            )
+        })?;
+        let mut body = &after_open[..end];
+        if let Some(trimmed) = body.strip_prefix('-') {
+            text = text.trim_end();
+            body = trimmed;
+        }
+        trim_next = false;
+        if let Some(trimmed) = body.strip_suffix('-') {
+            trim_next = true;
+            body = trimmed;
+        }
+        if !text.is_empty() {
+            tokens.push(Token::Text(text));
+        }
+        tokens.push(Token::Action {
+            body: body.trim(),
+            source: &rest[start..start + end + 4],
+        });
+        offset += start + end + 4;
+        rest = &rest[start + end + 4..];
+    }
+    let text = if trim_next { rest.trim_start() } else { rest };
+    if !text.is_empty() {
+        tokens.push(Token::Text(text));
+    }
+    Ok(tokens)
+}
+
+/// Split the body of an action into words, keeping quoted strings together
+fn split_words(body: &str) -> Result<Vec<&str>, String> {
+    let mut words = vec![];
+    let mut chars = body.char_indices().peekable();
+    while let Some((start, c)) = chars.next() {
+        if c.is_whitespace() {
+            continue;
+        }
+        let end = match c {
+            '"' | '`' => {
+                let mut escaped = false;
+                let mut end = None;
+                for (i, qc) in chars.by_ref() {
+                    if escaped {
+                        escaped = false;
+                    } else if qc == '\\' && c == '"' {
+                        escaped = true;
+                    } else if qc == c {
+                        end = Some(i + 1);
+                        break;
+                    }
+                }
+                end.ok_or_else(|| format!("Unterminated string {}", &body[start..]))?
+            }
+            '|' => start + 1,
+            _ => {
+                let mut end = body.len();
+                while let Some(&(i, wc)) = chars.peek() {
+                    if wc.is_whitespace() || wc == '|' {
+                        end = i;
+                        break;
+                    }
+                    chars.next();
+                }
+                end
+            }
+        };
+        words.push(&body[start..end]);
+    }
+    Ok(words)
+}
+
+fn unquote(s: &str) -> Result<String, String> {
+    if let Some(raw) = s.strip_prefix('`') {
+        return Ok(raw.trim_end_matches('`').to_string());
+    }
+    let inner = &s[1..s.len() - 1];
+    let mut out = String::new();
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            match chars.next() {
+                Some('n') => out.push('\n'),
+                Some('t') => out.push('\t'),
+                Some('r') => out.push('\r'),
+                Some('\\') => out.push('\\'),
+                Some('"') => out.push('"'),
+                other => return Err(format!("Unsupported escape \\{}", other.unwrap_or(' '))),
+            }
+        } else {
+            out.push(c);
+        }
+    }
+    Ok(out)
+}
+
+fn fields(path: &str) -> Vec<String> {
+    path.split('.')
+        .filter(|f| !f.is_empty())
+        .map(|f| f.to_string())
+        .collect()
+}
+
+fn parse_term(word: &str) -> Result<Term, String> {
+    if word.starts_with('"') || word.starts_with('`') {
+        unquote(word).map(|s| Term::Literal(Value::String(s)))
+    } else if let Some(path) = word.strip_prefix('$') {
+        if path.is_empty() || path.starts_with('.') {
+            Ok(Term::Root(fields(path)))
+        } else {
+            Err(format!(
+                "Unsupported variable '{}', only $ is supported",
+                word
+            ))
+        }
+    } else if word.starts_with('.') {
+        Ok(Term::Dot(fields(word)))
+    } else {
+        match word {
+            "true" => Ok(Term::Literal(Value::Bool(true))),
+            "false" => Ok(Term::Literal(Value::Bool(false))),
+            "nil" => Ok(Term::Literal(Value::Null)),
+            _ => match serde_json::from_str::<serde_json::Number>(word) {
+                Ok(num) => Ok(Term::Literal(Value::Number(num))),
+                Err(_) => Err(format!("Unexpected '{}'", word)),
+            },
+        }
+    }
+}
+
+const FUNCTIONS: &[&str] = &["printf", "len", "index", "eq", "ne", "not", "and", "or"];
+
+fn parse_pipeline(body: &str, source: &str) -> Result<Pipeline, String> {
+    let words = split_words(body)?;
+    let mut commands = vec![];
+    for cmd_words in words.split(|w| *w == "|") {
+        let (first, rest) = cmd_words
+            .split_first()
+            .ok_or_else(|| "Empty command in pipeline".to_string())?;
+        let command = if FUNCTIONS.contains(first) {
+            Command::Call {
+                func: first.to_string(),
+                args: rest
+                    .iter()
+                    .map(|w| parse_term(w))
+                    .collect::<Result<_, _>>()?,
+            }
+        } else if rest.is_empty() {
+            Command::Term(parse_term(first)?)
+        } else if first.chars().all(|c| c.is_ascii_alphanumeric()) {
+            return Err(format!("Unknown function '{}'", first));
+        } else {
+            return Err(format!("Unexpected '{}' after '{}'", rest[0], first));
+        };
+        commands.push(command);
+    }
+    Ok(Pipeline {
+        commands,
+        source: source.to_string(),
+    })
+}
+
+/// What ended a list of nodes
+enum Terminator {
+    End,
+    Else,
+    ElseIf(Pipeline),
+    Eof,
+}
+
+struct TemplateParser<'a> {
+    tokens: std::vec::IntoIter<Token<'a>>,
+    template: &'a str,
+}
+
+impl<'a> TemplateParser<'a> {
+    fn error(&self, source: &str, msg: &str) -> String {
+        let pos = self.template.find(source).unwrap_or(0);
+        err_at(source, pos, msg)
+    }
+
+    fn parse_nodes(&mut self) -> Result<(Vec<Node>, Terminator), String> {
+        let mut nodes = vec![];
+        while let Some(token) = self.tokens.next() {
+            let (body, source) = match token {
+                Token::Text(text) => {
+                    nodes.push(Node::Text(text.to_string()));
+                    continue;
+                }
+                Token::Action { body, source } => (body, source),
+            };
+            let mut words = body.splitn(2, char::is_whitespace);
+            let keyword = words.next().unwrap_or("");
+            let rest = words.next().unwrap_or("").trim();
+            let pipeline = |s: &str| parse_pipeline(s, source).map_err(|e| self.error(source, &e));
+            match keyword {
+                _ if body.starts_with("/*") => {
+                    if !body.ends_with("*/") {
+                        return Err(self.error(source, "Unclosed comment"));
+                    }
+                }
+                "" => return Err(self.error(source, "Empty action")),
+                "end" => return Ok((nodes, Terminator::End)),
+                "else" => {
+                    return match rest.strip_prefix("if") {
+                        Some(cond) if cond.starts_with(char::is_whitespace) => {
+                            Ok((nodes, Terminator::ElseIf(pipeline(cond)?)))
+                        }
+                        _ if rest.is_empty() => Ok((nodes, Terminator::Else)),
+                        _ => Err(self.error(source, "Unexpected text after else")),
+                    }
+                }
+                "if" => {
+                    let node = self.parse_if(pipeline(rest)?, source)?;
+                    nodes.push(node);
+                }
+                "range" => {
+                    let over = pipeline(rest)?;
+                    let (body, term) = self.parse_nodes()?;
+                    let otherwise = match term {
+                        Terminator::End => vec![],
+                        Terminator::Else => self.parse_until_end(source)?,
+                        Terminator::ElseIf(_) => {
+                            return Err(self.error(source, "else if not allowed in range"))
+                        }
+                        Terminator::Eof => return Err(self.error(source, "Missing {{end}}")),
+                    };
+                    nodes.push(Node::Range {
+                        over,
+                        body,
+                        otherwise,
+                    });
+                }
+                "with" | "define" | "template" | "block" => {
+                    return Err(self.error(source, &format!("Unsupported action '{}'", keyword)))
+                }
+                _ => nodes.push(Node::Action(pipeline(body)?)),
+            }
+        }
+        Ok((nodes, Terminator::Eof))
+    }
+
+    fn parse_until_end(&mut self, source: &str) -> Result<Vec<Node>, String> {
+        match self.parse_nodes()? {
+            (nodes, Terminator::End) => Ok(nodes),
+            (_, Terminator::Eof) => Err(self.error(source, "Missing {{end}}")),
+            _ => Err(self.error(source, "Multiple {{else}} for")),
+        }
+    }
+
+    fn parse_if(&mut self, cond: Pipeline, source: &str) -> Result<Node, String> {
+        let (then, term) = self.parse_nodes()?;
+        let otherwise = match term {
+            Terminator::End => vec![],
+            Terminator::Else => self.parse_until_end(source)?,
+            // else if is an if nested in the else, sharing the outer {{end}}
+            Terminator::ElseIf(cond) => vec![self.parse_if(cond, source)?],
+            Terminator::Eof => return Err(self.error(source, "Missing {{end}}")),
+        };
+        Ok(Node::If {
+            cond,
+            then,
+            otherwise,
+        })
+    }
+}
+
+impl Template {
+    pub fn parse(template: &str) -> Result<Template, String> {
+        let mut parser = TemplateParser {
+            tokens: tokenize(template)?.into_iter(),
+            template,
+        };
+        match parser.parse_nodes()? {
+            (nodes, Terminator::Eof) => Ok(Template { nodes }),
+            (_, _) => Err("Unexpected {{end}} or {{else}} in go-template".to_string()),
+        }
+    }
+
+    /// Render the template with value as both . and $
+    pub fn render(&self, value: &Value) -> Result<String, String> {
+        let mut out = String::new();
+        render_nodes(&self.nodes, value, value, &mut out)?;
+        Ok(out)
+    }
+}
+
+fn lookup(mut value: &Value, fields: &[String]) -> Value {
+    for field in fields.iter() {
+        value = match value.get(field.as_str()) {
+            Some(v) => v,
+            None => return Value::Null,
+        };
+    }
+    value.clone()
+}
+
+fn eval_term(term: &Term, dot: &Value, root: &Value) -> Value {
+    match term {
+        Term::Dot(fields) => lookup(dot, fields),
+        Term::Root(fields) => lookup(root, fields),
+        Term::Literal(val) => val.clone(),
+    }
+}
+
+/// Go's idea of truth: false, 0, nil, and empty strings, arrays and maps are false
+fn truthy(value: &Value) -> bool {
+    match value {
+        Value::Null => false,
+        Value::Bool(b) => *b,
+        Value::Number(n) => n.as_f64().map(|f| f != 0.0).unwrap_or(true),
+        Value::String(s) => !s.is_empty(),
+        Value::Array(a) => !a.is_empty(),
+        Value::Object(o) => !o.is_empty(),
+    }
+}
+
+/// Format a value the way Go's fmt does with %v
+fn display(value: &Value) -> String {
+    match value {
+        Value::Null => "<no value>".to_string(),
+        Value::String(s) => s.clone(),
+        Value::Bool(b) => b.to_string(),
+        Value::Number(n) => n.to_string(),
+        Value::Array(a) => {
+            let items: Vec<String> = a.iter().map(display).collect();
+            format!("[{}]", items.join(" "))
+        }
+        Value::Object(o) => {
+            let items: Vec<String> = o
+                .iter()
+                .map(|(k, v)| format!("{}:{}", k, display(v)))
+                .collect();
+            format!("map[{}]", items.join(" "))
+        }
+    }
+}
+
+fn pad(s: String, width: Option<usize>, left: bool, zero: bool) -> String {
+    match width {
+        Some(w) if s.chars().count() < w => {
+            let fill = w - s.chars().count();
+            if left {
+                format!("{}{}", s, " ".repeat(fill))
+            } else if zero {
+                format!("{}{}", "0".repeat(fill), s)
+            } else {
+                format!("{}{}", " ".repeat(fill), s)
+            }
+        }
+        _ => s,
+    }
+}
+
+/// A subset of Go's printf: %s %v %d %f %q %t and %%, with -, 0, width and .precision
+fn printf(format: &str, args: &[Value]) -> Result<String, String> {
+    let mut out = String::new();
+    let mut args = args.iter();
+    let mut chars = format.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            out.push(c);
+            continue;
+        }
+        let (mut left, mut zero) = (false, false);
+        while let Some(&flag) = chars.peek() {
+            match flag {
+                '-' => left = true,
+                '0' => zero = true,
+                _ => break,
+            }
+            chars.next();
+        }
+        let mut width = String::new();
+        while let Some(&d) = chars.peek().filter(|d| d.is_ascii_digit()) {
+            width.push(d);
+            chars.next();
+        }
+        let mut precision = None;
+        if chars.peek() == Some(&'.') {
+            chars.next();
+            let mut p = String::new();
+            while let Some(&d) = chars.peek().filter(|d| d.is_ascii_digit()) {
+                p.push(d);
+                chars.next();
+            }
+            precision = Some(p.parse::<usize>().unwrap_or(0));
+        }
+        let width = width.parse::<usize>().ok();
+        let verb = chars
+            .next()
+            .ok_or_else(|| format!("printf format ends with %: {}", format))?;
+        if verb == '%' {
+            out.push('%');
+            continue;
+        }
+        let arg = match args.next() {
+            Some(arg) => arg,
+            None => {
+                write!(out, "%!{}(MISSING)", verb).unwrap();
+                continue;
+            }
+        };
+        let formatted = match verb {
+            's' | 'v' => display(arg),
+            'q' => format!("{:?}", display(arg)),
+            't' => match arg {
+                Value::Bool(b) => b.to_string(),
+                _ => format!("%!t({})", display(arg)),
+            },
+            'd' => match arg
+                .as_i64()
+                .or_else(|| arg.as_f64().filter(|f| f.fract() == 0.0).map(|f| f as i64))
+            {
+                Some(i) => i.to_string(),
+                None => format!("%!d({})", display(arg)),
+            },
+            'f' => match arg.as_f64() {
+                Some(f) => format!("{:.*}", precision.unwrap_or(6), f),
+                None => format!("%!f({})", display(arg)),
+            },
+            _ => return Err(format!("Unsupported printf verb %{}", verb)),
+        };
+        out.push_str(&pad(formatted, width, left, zero));
+    }
+    Ok(out)
+}
+
+fn call(func: &str, args: &[Value]) -> Result<Value, String> {
+    let want = |n: usize| {
+        if args.len() == n {
+            Ok(())
+        } else {
+            Err(format!(
+                "{} takes {} argument(s), got {}",
+                func,
+                n,
+                args.len()
+            ))
+        }
+    };
+    match func {
+        "printf" => match args.split_first() {
+            Some((Value::String(format), rest)) => printf(format, rest).map(Value::String),
+            _ => Err("printf needs a format string as its first argument".to_string()),
+        },
+        "len" => {
+            want(1)?;
+            let len = match &args[0] {
+                Value::String(s) => s.chars().count(),
+                Value::Array(a) => a.len(),
+                Value::Object(o) => o.len(),
+                Value::Null => 0,
+                other => return Err(format!("len of {} is not defined", display(other))),
+            };
+            Ok(Value::from(len))
+        }
+        "index" => {
+            let (target, keys) = args
+                .split_first()
+                .ok_or_else(|| "index needs at least one argument".to_string())?;
+            let mut cur = target.clone();
+            for key in keys.iter() {
+                cur = match (key, &cur) {
+                    (Value::Number(n), Value::Array(a)) => n
+                        .as_u64()
+                        .and_then(|i| a.get(i as usize).cloned())
+                        .ok_or_else(|| format!("index {} out of range", n))?,
+                    (Value::String(s), Value::Object(o)) => {
+                        o.get(s).cloned().unwrap_or(Value::Null)
+                    }
+                    (Value::String(_), Value::Null) => Value::Null,
+                    _ => {
+                        return Err(format!(
+                            "can't index {} with {}",
+                            display(&cur),
+                            display(key)
+                        ))
+                    }
+                };
+            }
+            Ok(cur)
+        }
+        "eq" => {
+            want(2)?;
+            Ok(Value::Bool(args[0] == args[1]))
+        }
+        "ne" => {
+            want(2)?;
+            Ok(Value::Bool(args[0] != args[1]))
+        }
+        "not" => {
+            want(1)?;
+            Ok(Value::Bool(!truthy(&args[0])))
+        }
+        // like Go, and/or return the first falsy/truthy argument (or the last one)
+        "and" => Ok(args
+            .iter()
+            .find(|a| !truthy(a))
+            .or_else(|| args.last())
+            .cloned()
+            .unwrap_or(Value::Null)),
+        "or" => Ok(args
+            .iter()
+            .find(|a| truthy(a))
+            .or_else(|| args.last())
+            .cloned()
+            .unwrap_or(Value::Null)),
+        _ => Err(format!("Unknown function '{}'", func)),
+    }
+}
+
+fn eval_pipeline(pipeline: &Pipeline, dot: &Value, root: &Value) -> Result<Value, String> {
+    let mut result: Option<Value> = None;
+    for command in pipeline.commands.iter() {
+        let value = match command {
+            Command::Term(term) => {
+                if result.is_some() {
+                    return Err(format!(
+                        "Can't pipe into a non-function in go-template action {}",
+                        pipeline.source
+                    ));
+                }
+                eval_term(term, dot, root)
+            }
+            Command::Call { func, args } => {
+                let mut args: Vec<Value> = args.iter().map(|t| eval_term(t, dot, root)).collect();
+                args.extend(result.take());
+                call(func, &args)
+                    .map_err(|e| format!("{} in go-template action {}", e, pipeline.source))?
+            }
+        };
+        result = Some(value);
+    }
+    Ok(result.unwrap_or(Value::Null))
+}
+
+fn render_nodes(nodes: &[Node], dot: &Value, root: &Value, out: &mut String) -> Result<(), String> {
+    for node in nodes.iter() {
+        match node {
+            Node::Text(text) => out.push_str(text),
+            Node::Action(pipeline) => out.push_str(&display(&eval_pipeline(pipeline, dot, root)?)),
+            Node::If {
+                cond,
+                then,
+                otherwise,
+            } => {
+                if truthy(&eval_pipeline(cond, dot, root)?) {
+                    render_nodes(then, dot, root, out)?;
+                } else {
+                    render_nodes(otherwise, dot, root, out)?;
+                }
+            }
+            Node::Range {
+                over,
+                body,
+                otherwise,
+            } => {
+                let items: Vec<Value> = match eval_pipeline(over, dot, root)? {
+                    Value::Array(a) => a,
+                    // serde_json maps iterate in key order, which is also what Go does
+                    Value::Object(o) => o.into_iter().map(|(_, v)| v).collect(),
+                    Value::Null => vec![],
+                    other => {
+                        return Err(format!(
+                            "Can't range over {} in go-template action {}",
+                            display(&other),
+                            over.source
+                        ))
+                    }
+                };
+                if items.is_empty() {
+                    render_nodes(otherwise, dot, root, out)?;
+                }
+                for item in items.iter() {
+                    render_nodes(body, item, root, out)?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn render(template: &str, value: &Value) -> String {
+        Template::parse(template).unwrap().render(value).unwrap()
+    }
+
+    #[test]
+    fn fields_and_literals() {
+        let pod = json!({"metadata": {"name": "web-1", "labels": {"app": "web"}}, "spec": {}});
+        assert_eq!(render("{{.metadata.name}}", &pod), "web-1");
+        assert_eq!(render("name: {{ $.metadata.name }}!", &pod), "name: web-1!");
+        assert_eq!(render("{{.metadata.labels}}", &pod), "map[app:web]");
+        assert_eq!(render("{{.spec.nodeName}}", &pod), "<no value>");
+        assert_eq!(render(r#"{{"a\tb"}}{{`raw\n`}}{{3}}"#, &pod), "a\tbraw\\n3");
+        assert_eq!(render("{{/* a comment */}}x", &pod), "x");
+        assert_eq!(render("a  {{- .metadata.name -}}  b", &pod), "aweb-1b");
+    }
+
+    #[test]
+    fn range_and_if() {
+        let list = json!({"items": [
+            {"metadata": {"name": "a"}, "status": {"phase": "Running"}},
+            {"metadata": {"name": "b"}, "status": {"phase": "Pending"}},
+        ]});
+        assert_eq!(
+            render(
+                r#"{{range .items}}{{.metadata.name}}{{if eq .status.phase "Running"}} up{{else}} down{{end}}{{"\n"}}{{end}}"#,
+                &list
+            ),
+            "a up\nb down\n"
+        );
+        assert_eq!(
+            render("{{range .none}}x{{else}}empty{{end}}", &list),
+            "empty"
+        );
+        assert_eq!(
+            render(
+                r#"{{range .items}}{{if eq .metadata.name "x"}}x{{else if eq .metadata.name "b"}}b{{else}}-{{end}}{{end}}"#,
+                &list
+            ),
+            "-b"
+        );
+        assert_eq!(
+            render("{{range .items}}{{$.items | len}}{{end}}", &list),
+            "22"
+        );
+        assert_eq!(render("{{if not .none}}no{{end}}", &list), "no");
+    }
+
+    #[test]
+    fn functions() {
+        let val = json!({"name": "web", "count": 3, "ratio": 0.5, "list": [1, 2], "m": {"k": "v"}});
+        assert_eq!(
+            render(
+                r#"{{printf "%-5s|%3d|%.2f|%v%%" .name .count .ratio .list}}"#,
+                &val
+            ),
+            "web  |  3|0.50|[1 2]%"
+        );
+        assert_eq!(render(r#"{{.name | printf "%q"}}"#, &val), "\"web\"");
+        assert_eq!(render(r#"{{index .list 1}} {{index .m "k"}}"#, &val), "2 v");
+        assert_eq!(render("{{len .name}} {{len .list}}", &val), "3 2");
+        assert_eq!(
+            render("{{or .none .name}} {{and .name .count}}", &val),
+            "web 3"
+        );
+    }
+
+    #[test]
+    fn errors() {
+        let err = Template::parse("{{range .items}}{{.x}}").unwrap_err();
+        assert!(err.contains("Missing {{end}}"), "{}", err);
+        assert!(err.contains("{{range .items}}"), "{}", err);
+
+        let err = Template::parse("ok {{rnge .items}}").unwrap_err();
+        assert!(err.contains("Unknown function 'rnge'"), "{}", err);
+        assert!(err.contains("position 3"), "{}", err);
+
+        assert!(Template::parse("{{.a").is_err());
+        assert!(Template::parse("{{end}}").is_err());
+        assert!(Template::parse("{{$x}}").is_err());
+        assert!(Template::parse(r#"{{"unterminated}}"#).is_err());
+        assert!(Template::parse("{{with .a}}{{end}}").is_err());
+
+        let err = Template::parse("{{range .a}}{{end}}")
+            .unwrap()
+            .render(&json!({"a": 1}))
+            .unwrap_err();
+        assert!(err.contains("Can't range over 1"), "{}", err);
+    }
+}
@@ -0,0 +1,101 @@
+// Copyright 2021 Databricks, Inc.
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+
+// http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Logging of the requests click sends to stderr, for debugging connection and auth problems. The
+//! verbosity is set with `set verbose <level>` or `--verbose`. At 1 each request's method, url,
+//! status and how long it took are logged, at 2 the request and response headers are too.
+
+use reqwest::blocking::{Client, Request, Response};
+use reqwest::header::{HeaderMap, HeaderValue, AUTHORIZATION, PROXY_AUTHORIZATION};
+
+use std::time::Instant;
+
+/// The most verbose level, anything higher is the same as this
+pub const MAX_VERBOSITY: u8 = 2;
+
+/// The value to log for a header. Credentials are replaced, keeping the scheme so it's still clear
+/// what kind of auth was used
+fn header_value(name: &str, value: &HeaderValue) -> String {
+    let value = String::from_utf8_lossy(value.as_bytes());
+    if name == AUTHORIZATION.as_str() || name == PROXY_AUTHORIZATION.as_str() {
+        match value.split_once(' ') {
+            Some((scheme, _)) => format!("{} <redacted>", scheme),
+            None => "<redacted>".to_string(),
+        }
+    } else {
+        value.into_owned()
+    }
+}
+
+fn log_headers(title: &str, headers: &HeaderMap) {
+    eprintln!("{}:", title);
+    for (name, value) in headers.iter() {
+        eprintln!("    {}: {}", name, header_value(name.as_str(), value));
+    }
+}
+
+/// Send req with client, logging it and its response at the given verbosity
+pub fn execute(client: &Client, req: Request, verbosity: u8) -> reqwest::Result<Response> {
+    if verbosity == 0 {
+        return client.execute(req);
+    }
+    let method = req.method().clone();
+    let url = req.url().clone();
+    if verbosity >= 2 {
+        eprintln!("{} {}", method, url);
+        log_headers("Request Headers", req.headers());
+    }
+    let start = Instant::now();
+    let res = client.execute(req);
+    let millis = start.elapsed().as_millis();
+    match res.as_ref() {
+        Ok(resp) => {
+            eprintln!(
+                "{} {} {} in {} milliseconds",
+                method,
+                url,
+                resp.status(),
+                millis
+            );
+            if verbosity >= 2 {
+                log_headers("Response Headers", resp.headers());
+            }
+        }
+        Err(e) => eprintln!(
+            "{} {} failed in {} milliseconds: {}",
+            method, url, millis, e
+        ),
+    }
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn redacts_credentials() {
+        let bearer = HeaderValue::from_static("Bearer secret-token");
+        assert_eq!(header_value("authorization", &bearer), "Bearer <redacted>");
+        let basic = HeaderValue::from_static("Basic dXNlcjpwYXNz");
+        assert_eq!(
+            header_value("proxy-authorization", &basic),
+            "Basic <redacted>"
+        );
+        let bare = HeaderValue::from_static("secret");
+        assert_eq!(header_value("authorization", &bare), "<redacted>");
+        let accept = HeaderValue::from_static("application/json");
+        assert_eq!(header_value("accept", &accept), "application/json");
+    }
+}
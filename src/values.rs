@@ -12,12 +12,16 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use serde::Deserialize;
 /// Helper functions to deal with Values
 use serde_json::value::Value;
+use serde_json::Map;
 
 use crate::error::ClickError;
+use crate::parser::JsonPathSeg;
 
 use std::borrow::Cow;
+use std::cmp::Ordering;
 
 pub fn val_str<'a>(pointer: &str, value: &'a Value, default: &'a str) -> Cow<'a, str> {
     match value.pointer(pointer) {
@@ -85,3 +89,245 @@ where
         None => Err(ClickError::ParseErr("Can't deserialize".to_owned())),
     }
 }
+
+/// Get all the values selected by the parsed jsonpath out of value
+pub fn jsonpath_values<'a>(path: &[JsonPathSeg], value: &'a Value) -> Vec<&'a Value> {
+    let mut current = vec![value];
+    for seg in path.iter() {
+        let mut next = vec![];
+        for val in current.into_iter() {
+            match seg {
+                JsonPathSeg::Field(name) => next.extend(val.get(name.as_str())),
+                JsonPathSeg::Index(index) => next.extend(val.get(*index)),
+                JsonPathSeg::Wildcard => match val {
+                    Value::Array(items) => next.extend(items.iter()),
+                    Value::Object(map) => next.extend(map.values()),
+                    _ => {}
+                },
+                JsonPathSeg::Filter {
+                    path: filter_path,
+                    value: want,
+                } => {
+                    if let Some(items) = val.as_array() {
+                        for item in items.iter() {
+                            let found = filter_path
+                                .iter()
+                                .try_fold(item, |v, field| v.get(field.as_str()));
+                            if found.is_some_and(|f| jsonpath_value_str(f) == *want) {
+                                next.push(item);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        current = next;
+    }
+    current
+}
+
+/// Format a single value for jsonpath output. Strings are printed without quotes, like kubectl
+fn jsonpath_value_str(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+/// Evaluate the jsonpath against value, and format the result. Multiple results are separated by
+/// spaces
+pub fn jsonpath_str(path: &[JsonPathSeg], value: &Value) -> String {
+    jsonpath_values(path, value)
+        .into_iter()
+        .map(jsonpath_value_str)
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+/// Order values selected by a jsonpath, for sorting. Numbers compare numerically and everything
+/// else by its printed form. Missing (or null) values sort after everything else
+pub fn jsonpath_cmp(a: Option<&Value>, b: Option<&Value>) -> Ordering {
+    let a = a.filter(|v| !v.is_null());
+    let b = b.filter(|v| !v.is_null());
+    match (a, b) {
+        (None, None) => Ordering::Equal,
+        (None, Some(_)) => Ordering::Greater,
+        (Some(_), None) => Ordering::Less,
+        (Some(a), Some(b)) => match (a.as_f64(), b.as_f64()) {
+            (Some(anum), Some(bnum)) => anum.partial_cmp(&bnum).unwrap_or(Ordering::Equal),
+            _ => jsonpath_value_str(a).cmp(&jsonpath_value_str(b)),
+        },
+    }
+}
+
+/// Parse a kubernetes quantity (like "250m", "1.5", "64Mi" or "1e3") into its value in base
+/// units (cores for cpu, bytes for memory)
+pub fn parse_quantity(quantity: &str) -> Option<f64> {
+    let split = quantity
+        .find(|c: char| !(c.is_ascii_digit() || c == '.' || c == '+' || c == '-'))
+        .unwrap_or(quantity.len());
+    let (num, suffix) = quantity.split_at(split);
+    let num: f64 = num.parse().ok()?;
+    let multiplier = match suffix {
+        "" => 1.0,
+        "n" => 1e-9,
+        "u" => 1e-6,
+        "m" => 1e-3,
+        "k" => 1e3,
+        "M" => 1e6,
+        "G" => 1e9,
+        "T" => 1e12,
+        "P" => 1e15,
+        "E" => 1e18,
+        "Ki" => 1024.0,
+        "Mi" => 1024f64.powi(2),
+        "Gi" => 1024f64.powi(3),
+        "Ti" => 1024f64.powi(4),
+        "Pi" => 1024f64.powi(5),
+        "Ei" => 1024f64.powi(6),
+        _ => {
+            // decimal exponent, like 1e3
+            let exp = suffix
+                .strip_prefix('e')
+                .or_else(|| suffix.strip_prefix('E'))?;
+            10f64.powi(exp.parse().ok()?)
+        }
+    };
+    Some(num * multiplier)
+}
+
+/// Format cpu cores in millicores, the way kubectl shows them
+pub fn format_cpu(cores: f64) -> String {
+    format!("{}m", (cores * 1000.0).round())
+}
+
+/// Format bytes of memory in Mi
+pub fn format_memory(bytes: f64) -> String {
+    format!("{}Mi", (bytes / 1024f64.powi(2)).round())
+}
+
+/// Build a json merge patch (RFC 7386) that turns original into modified. Fields that were removed
+/// are set to null, and arrays are replaced as a whole. Returns None if there are no changes
+pub fn merge_patch(original: &Value, modified: &Value) -> Option<Value> {
+    match (original, modified) {
+        (Value::Object(orig), Value::Object(modi)) => {
+            let mut patch = Map::new();
+            for key in orig.keys() {
+                if !modi.contains_key(key) {
+                    patch.insert(key.clone(), Value::Null);
+                }
+            }
+            for (key, val) in modi.iter() {
+                match orig.get(key) {
+                    Some(orig_val) => {
+                        if let Some(sub_patch) = merge_patch(orig_val, val) {
+                            patch.insert(key.clone(), sub_patch);
+                        }
+                    }
+                    None => {
+                        patch.insert(key.clone(), val.clone());
+                    }
+                }
+            }
+            if patch.is_empty() {
+                None
+            } else {
+                Some(Value::Object(patch))
+            }
+        }
+        _ => {
+            if original == modified {
+                None
+            } else {
+                Some(modified.clone())
+            }
+        }
+    }
+}
+
+/// Parse the objects in content, which can have many yaml documents. Empty documents are skipped,
+/// and lists (like `kubectl get -o yaml` outputs) are replaced by their items
+pub fn yaml_objects(content: &str) -> Result<Vec<Value>, ClickError> {
+    let mut objects = vec![];
+    for document in serde_yaml::Deserializer::from_str(content) {
+        match Value::deserialize(document)? {
+            Value::Null => {}
+            Value::Object(mut obj) if obj.get("kind").and_then(|k| k.as_str()) == Some("List") => {
+                if let Some(Value::Array(items)) = obj.remove("items") {
+                    objects.extend(items);
+                }
+            }
+            value => objects.push(value),
+        }
+    }
+    Ok(objects)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_yaml_objects() {
+        let content = "---
+apiVersion: v1
+kind: ConfigMap
+metadata:
+  name: one
+---
+# just a comment
+---
+apiVersion: v1
+kind: List
+items:
+- apiVersion: v1
+  kind: Secret
+  metadata:
+    name: two
+";
+        let objects = yaml_objects(content).unwrap();
+        assert_eq!(objects.len(), 2);
+        assert_eq!(objects[0]["metadata"]["name"], json!("one"));
+        assert_eq!(objects[1]["kind"], json!("Secret"));
+        assert!(yaml_objects("a: [").is_err());
+    }
+
+    #[test]
+    fn test_merge_patch() {
+        let original = json!({
+            "metadata": { "name": "web", "labels": { "app": "web", "old": "yes" } },
+            "spec": { "replicas": 1, "ports": [80, 443] },
+        });
+        assert_eq!(merge_patch(&original, &original), None);
+
+        let modified = json!({
+            "metadata": { "name": "web", "labels": { "app": "web", "new": "yes" } },
+            "spec": { "replicas": 3, "ports": [80] },
+        });
+        assert_eq!(
+            merge_patch(&original, &modified),
+            Some(json!({
+                "metadata": { "labels": { "old": null, "new": "yes" } },
+                "spec": { "replicas": 3, "ports": [80] },
+            }))
+        );
+    }
+
+    #[test]
+    fn jsonpath_ordering() {
+        let nums = (json!(9), json!(10));
+        assert_eq!(jsonpath_cmp(Some(&nums.0), Some(&nums.1)), Ordering::Less);
+        let strs = (json!("9"), json!("10"));
+        assert_eq!(
+            jsonpath_cmp(Some(&strs.0), Some(&strs.1)),
+            Ordering::Greater
+        );
+        assert_eq!(jsonpath_cmp(None, Some(&strs.0)), Ordering::Greater);
+        assert_eq!(
+            jsonpath_cmp(Some(&Value::Null), Some(&nums.0)),
+            Ordering::Greater
+        );
+        assert_eq!(jsonpath_cmp(None, Some(&Value::Null)), Ordering::Equal);
+    }
+}